@@ -0,0 +1,146 @@
+//! A module defining the placement and the priority of the threads on the hot path,
+//! i.e. the WebSocket connections of the notification streams (see
+//! `NotificationStream::set_thread_config`) and the book maintenance thread of
+//! `LiveOrderBook` (see `LiveOrderBook::with_thread_config`).
+//!
+//! Pinning these threads to dedicated cores and giving them a real-time priority keeps
+//! them from being preempted or migrated by the OS scheduler, which reduces the jitter
+//! of latency sensitive deployments. Pinning is only supported on Linux, and raising
+//! the priority usually requires additional privileges, e.g. `CAP_SYS_NICE`.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use failure::bail;
+use serde_derive::{Serialize, Deserialize};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Placement and priority of a thread. The default configuration leaves the thread
+/// untouched.
+pub struct ThreadConfig {
+    /// Cores the thread is allowed to run on, or any core if empty.
+    pub cores: Vec<usize>,
+
+    /// Real-time priority of the thread under the `SCHED_FIFO` policy, from 1 (lowest)
+    /// to 99 (highest) on Linux, or `None` for keeping the current scheduling policy.
+    pub priority: Option<i32>,
+}
+
+impl ThreadConfig {
+    /// Apply this configuration to the calling thread.
+    pub fn apply(&self) -> Result<(), failure::Error> {
+        if !self.cores.is_empty() {
+            set_affinity(&self.cores)?;
+        }
+
+        if let Some(priority) = self.priority {
+            set_priority(priority)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cores: &[usize]) -> Result<(), failure::Error> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for core in cores {
+            if *core >= 8 * std::mem::size_of::<libc::cpu_set_t>() {
+                bail!("invalid core: {}", core);
+            }
+            libc::CPU_SET(*core, &mut set);
+        }
+
+        // A pid of 0 designates the calling thread.
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!("cannot pin thread: {}", std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_: &[usize]) -> Result<(), failure::Error> {
+    bail!("pinning threads is not supported on this platform")
+}
+
+#[cfg(unix)]
+fn set_priority(priority: i32) -> Result<(), failure::Error> {
+    unsafe {
+        let param = libc::sched_param {
+            sched_priority: priority,
+        };
+
+        // Unlike most functions, the error code is returned.
+        let code = libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param);
+        if code != 0 {
+            bail!(
+                "cannot set thread priority: {}",
+                std::io::Error::from_raw_os_error(code)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_priority(_: i32) -> Result<(), failure::Error> {
+    bail!("setting thread priorities is not supported on this platform")
+}
+
+/// A `ThreadConfig` shared by the connections of a stream, along with a version
+/// incremented on each change so that each connection can tell whether it already
+/// applied the latest configuration.
+#[derive(Debug, Default)]
+crate struct SharedThreadConfig {
+    config: Mutex<ThreadConfig>,
+    version: AtomicUsize,
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "alpaca",
+        feature = "binance",
+        feature = "bitfinex",
+        feature = "bitmex",
+        feature = "bybit",
+        feature = "coinbase",
+        feature = "deribit",
+        feature = "dydx",
+        feature = "gateio",
+        feature = "gdax",
+        feature = "gemini",
+        feature = "hitbtc",
+        feature = "huobi",
+        feature = "kraken",
+        feature = "kraken_futures",
+        feature = "kucoin",
+        feature = "mexc",
+        feature = "okx",
+        feature = "testkit",
+        feature = "upbit",
+    )),
+    allow(dead_code)
+)]
+impl SharedThreadConfig {
+    crate fn new() -> Self {
+        SharedThreadConfig::default()
+    }
+
+    crate fn set(&self, config: ThreadConfig) {
+        *self.config.lock().unwrap() = config;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Apply the configuration to the calling thread if its version differs from
+    /// `*applied`, which is then updated.
+    crate fn apply_if_changed(&self, applied: &mut usize) -> Result<(), failure::Error> {
+        let version = self.version.load(Ordering::Acquire);
+        if version == *applied {
+            return Ok(());
+        }
+        *applied = version;
+
+        let config = self.config.lock().unwrap().clone();
+        config.apply()
+    }
+}
@@ -0,0 +1,181 @@
+//! A module defining error types specific to Alpaca.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+/// Error code of an order rejected for insufficient buying power.
+const INSUFFICIENT_BUYING_POWER: u64 = 40_310_000;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Alpaca REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Alpaca error code, e.g. `40310000`: see API documentation.
+    pub code: Option<u64>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl RestError {
+    fn msg_contains(&self, pattern: &str) -> bool {
+        self.message.as_ref()
+            .map(|msg| msg.to_lowercase().contains(pattern))
+            .unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        // Alpaca answers invalid key pairs with either 401 or 403.
+        if self.kind == RestErrorKind::Unauthorized
+            || (self.kind == RestErrorKind::Forbidden
+                && self.code != Some(INSUFFICIENT_BUYING_POWER))
+        {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        // Orders which are already filled or canceled are reported as not cancelable.
+        if self.kind == RestErrorKind::NotFound || self.kind == RestErrorKind::Unprocessable {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.code == Some(INSUFFICIENT_BUYING_POWER) || self.msg_contains("insufficient") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.msg_contains("client_order_id must be unique") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let Some(code) = self.code {
+            write!(f, " (code = {})", code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Error returned along with a non-success HTTP status code.
+    pub(super) fn from_alpaca_error(
+        status: StatusCode,
+        code: Option<u64>,
+        message: Option<String>
+    ) -> Self
+    {
+        RestError {
+            kind: RestErrorKind::from_status_code(status),
+            code,
+            message,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code to an Alpaca error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key pair.
+    Unauthorized,
+
+    #[fail(display = "forbidden")]
+    /// The account cannot perform the request, e.g. for lack of buying power, or the
+    /// key pair is invalid.
+    Forbidden,
+
+    #[fail(display = "not found")]
+    /// Not found, issue on the consumer side.
+    NotFound,
+
+    #[fail(display = "unprocessable")]
+    /// Well-formed but invalid request, e.g. an order on an asset which is not tradable,
+    /// or a cancel of an order which is already closed.
+    Unprocessable,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Alpaca. See Alpaca API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Alpaca side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED => Unauthorized,
+            StatusCode::FORBIDDEN => Forbidden,
+            StatusCode::NOT_FOUND => NotFound,
+            StatusCode::UNPROCESSABLE_ENTITY => Unprocessable,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+}
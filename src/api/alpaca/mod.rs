@@ -0,0 +1,179 @@
+//! Implementation of `ApiClient` for the Alpaca trading API, a broker for US equities.
+//! Orders go through the REST API of the trading account, while trades and quotes come
+//! from the market data stream and order updates from the `trade_updates` stream.
+//!
+//! Prices are expressed in cents: sub-penny prices, which are only accepted for stocks
+//! trading below $1, are truncated. Sizes are expressed in whole shares, fractional
+//! shares being restricted to market and day orders.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An Alpaca key pair: key id + secret key.
+pub struct KeyPair {
+    key_id: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(key_id: String, secret_key: String) -> Self {
+        KeyPair {
+            key_id,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    key_id: String,
+    secret_key: String,
+}
+
+/// An Alpaca API client.
+pub struct Client {
+    params: Params,
+    keys: Keys,
+
+    /// client order id => server order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Params {
+    /// Return the parameters of the Alpaca paper trading API, along with the free IEX
+    /// market data stream. Paper trading accounts have their own keys.
+    pub fn alpaca_paper() -> Self {
+        Params {
+            streaming_endpoint: "wss://stream.data.alpaca.markets/v2/iex".to_owned(),
+            rest_endpoint: "https://paper-api.alpaca.markets".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new Alpaca API client with given `params`, i.e. the trading API, e.g.
+    /// `https://paper-api.alpaca.markets` or `https://api.alpaca.markets`, and the
+    /// market data stream, e.g. `wss://stream.data.alpaca.markets/v2/iex`. Order
+    /// updates are streamed from the `/stream` endpoint of the trading API.
+    ///
+    /// Every Alpaca API, market data included, requires a key pair. Note that Alpaca
+    /// limits the number of market data connections per account, one on the free plan,
+    /// while each call to `stream` opens its own connection.
+    ///
+    /// # Note
+    /// This method will block, fetching the tradable assets from Alpaca.
+    pub fn new(params: Params, key_pair: KeyPair) -> Result<Self, failure::Error> {
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys: Keys {
+                key_id: key_pair.key_id,
+                secret_key: key_pair.secret_key,
+            },
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Alpaca
+    }
+
+    /// Assets are found by their ticker, e.g. `AAPL`, case insensitively.
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_uppercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        self.cancel_impl(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Alpaca accepts client order ids of at most 128 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 128 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().to_string()
+        }
+    }
+}
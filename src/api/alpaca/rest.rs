@@ -0,0 +1,396 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::{Tick, ConversionError};
+use crate::api::{
+    self,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{convert_str_timestamp, Timestamped, IntoTimestamped};
+use crate::api::query_string::QueryString;
+use crate::api::alpaca::Client;
+use crate::api::alpaca::errors::RestError;
+
+/// Prices are expressed in cents.
+const PRICE_TICK: u64 = 100;
+
+/// Sizes are expressed in whole shares.
+const SIZE_TICK: u64 = 1;
+
+/// Ticks used for computing locked balances, fractional positions being expressed
+/// with up to 9 decimals.
+const BALANCE_TICK: u64 = 1_000_000_000;
+
+/// Currency of the cash balance.
+const CASH_CURRENCY: &str = "USD";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaError {
+    code: Option<u64>,
+    message: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct AlpacaOrder<'a> {
+    symbol: &'a str,
+    qty: &'a str,
+    side: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    time_in_force: &'a str,
+    limit_price: &'a str,
+    client_order_id: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaOrderAck<'a> {
+    id: &'a str,
+    created_at: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaAccount<'a> {
+    cash: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaPosition<'a> {
+    symbol: &'a str,
+    qty: &'a str,
+    qty_available: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaClock<'a> {
+    timestamp: &'a str,
+    is_open: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaAsset<'a> {
+    symbol: &'a str,
+    tradable: bool,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+/// Return the part of a position held for open orders. Short positions are reported
+/// with negative quantities.
+fn locked_qty(qty: &str, qty_available: &str) -> Result<String, ConversionError> {
+    let tick = Tick::new(BALANCE_TICK);
+    let qty = tick.ticked(qty.trim_start_matches('-'))?;
+    let available = tick.ticked(qty_available.trim_start_matches('-'))?;
+    tick.unticked(qty.saturating_sub(available))
+}
+
+impl Client {
+    /// Requests are authenticated by the key pair, sent as is in the headers.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let address = if query.is_empty() {
+            format!("{}/{}", self.params.rest_endpoint, endpoint)
+        } else {
+            format!("{}/{}?{}", self.params.rest_endpoint, endpoint, query)
+        };
+
+        let mut request = Request::builder();
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..])
+            .header("APCA-API-KEY-ID", self.keys.key_id.as_bytes())
+            .header("APCA-API-SECRET-KEY", self.keys.secret_key.as_bytes());
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if !status.is_success() {
+                let (code, message) = match serde_json::from_slice(&body) {
+                    Ok(AlpacaError { code, message }) => (code, message),
+                    Err(_) => (None, None),
+                };
+                let error = RestError::from_alpaca_error(status, code, message);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Alpaca");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Alpaca");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_ == OrderType::LimitMaker {
+            warn!("called `order` with `LimitMaker`, which is not supported by Alpaca");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let time_in_force = match order.time_in_force {
+            TimeInForce::GoodTilCanceled => "gtc",
+            TimeInForce::ImmediateOrCancel => "ioc",
+            TimeInForce::FillOrKilll => "fok",
+            TimeInForce::GoodTilDate(_) => {
+                warn!("called `order` with `GoodTilDate`, which is not supported by Alpaca");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // The client order id is needed for matching the order updates.
+        let client_order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => <Self as api::GenerateOrderId>::new_order_id(""),
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let body = AlpacaOrder {
+            symbol: symbol.name(),
+            qty: size.borrow(),
+            side: order.side.as_str(),
+            type_: "limit",
+            time_in_force,
+            limit_price: price.borrow(),
+            client_order_id: &client_order_id,
+        };
+        let body = serde_json::to_string(&body).expect("invalid json");
+
+        let order_ids = self.order_ids.clone();
+
+        let fut = self.request("v2/orders", Method::POST, QueryString::new(), body)
+            .and_then(move |body| {
+                let ack: AlpacaOrderAck<'_> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                let timestamp = convert_str_timestamp(ack.created_at)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                order_ids.insert(client_order_id.clone(), ack.id.to_owned());
+                debug!("insert order id {} (from REST)", client_order_id);
+
+                Ok(OrderAck {
+                    order_id: client_order_id,
+                }.with_timestamp(timestamp))
+            });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        // Orders can only be canceled by their server order id.
+        let server_order_id = match self.order_ids.get(&cancel.order_id) {
+            Some(order_id) => order_id.clone(),
+            None => {
+                warn!("called `cancel` with a not yet inserted order id");
+                return Box::new(futures::future::err(api::errors::ApiError::RestError(
+                    api::errors::RestErrorKind::Specific(
+                        api::errors::CancelErrorKind::UnknownOrder
+                    ).into()
+                )));
+            }
+        };
+
+        // The cancel is only acknowledged here, its completion being notified by the
+        // `trade_updates` stream.
+        let endpoint = format!("v2/orders/{}", server_order_id);
+        let fut = self.request(&endpoint, Method::DELETE, QueryString::new(), String::new())
+            .and_then(|_| Ok(CancelAck.timestamped()));
+        Box::new(fut)
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v2/clock", Method::GET, QueryString::new(), String::new()).and_then(|body| {
+            let clock: AlpacaClock<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let timestamp = convert_str_timestamp(clock.timestamp)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(timestamp))
+        })
+    }
+
+    /// Balances are the cash of the account, in USD, along with the positions: shares
+    /// held for open sell orders are locked.
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let account = self.request("v2/account", Method::GET, QueryString::new(), String::new());
+        let positions = self.request("v2/positions", Method::GET, QueryString::new(), String::new());
+
+        account.join(positions).and_then(|(account, positions)| {
+            let account: AlpacaAccount<'_> = serde_json::from_slice(&account)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let positions: Vec<AlpacaPosition<'_>> = serde_json::from_slice(&positions)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut balances = Balances::new();
+            balances.insert(CASH_CURRENCY.to_owned(), Balance {
+                free: account.cash.to_owned(),
+                locked: "0".to_owned(),
+            });
+
+            for position in positions {
+                let locked = locked_qty(position.qty, position.qty_available)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                balances.insert(position.symbol.to_owned(), Balance {
+                    free: position.qty_available.to_owned(),
+                    locked,
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    /// Outside of regular trading hours, orders are queued until the next session: the
+    /// status is then reported as `Maintenance`.
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v2/clock", Method::GET, QueryString::new(), String::new()).and_then(|body| {
+            let clock: AlpacaClock<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let timestamp = convert_str_timestamp(clock.timestamp)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = if clock.is_open {
+                ExchangeStatus::Normal
+            } else {
+                ExchangeStatus::Maintenance
+            };
+            Ok(status.with_timestamp(timestamp))
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("status", "active");
+        query.push_str("asset_class", "us_equity");
+
+        self.request("v2/assets", Method::GET, query, String::new()).and_then(|body| {
+            let assets: Vec<AlpacaAsset<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let price_tick = Tick::new(PRICE_TICK);
+            let size_tick = Tick::new(SIZE_TICK);
+
+            let mut symbols = HashMap::new();
+            for asset in assets {
+                if !asset.tradable {
+                    continue;
+                }
+
+                match Symbol::new(asset.symbol, price_tick, size_tick) {
+                    Some(symbol) => {
+                        symbols.insert(asset.symbol.to_uppercase(), symbol);
+                    }
+                    None => error!("symbol name too long: `{}`", asset.symbol),
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::alpaca::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+const TIMESTAMP: u64 = 1_614_009_105_335;
+
+fn symbol() -> Symbol {
+    Symbol::new("AAPL", Tick::new(100), Tick::new(1)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[String]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+fn quote(symbol: &str, bid: f64, bid_size: f64, ask: f64, ask_size: f64) -> String {
+    format!(
+        r#"{{"T":"q","S":"{}","bx":"V","bp":{},"bs":{},"ax":"V","ap":{},"as":{},
+            "c":["R"],"z":"C","t":"2021-02-22T15:51:45.335689322Z"}}"#,
+        symbol,
+        bid,
+        bid_size,
+        ask,
+        ask_size
+    )
+}
+
+#[test]
+fn book_from_quotes() {
+    let frames = [
+        r#"[{"T":"success","msg":"connected"},{"T":"success","msg":"authenticated"}]"#.to_owned(),
+        format!("[{}]", quote("AAPL", 150.1, 2., 150.2, 3.)),
+
+        // Quotes of other symbols of the same batch are skipped.
+        format!("[{},{}]", quote("MSFT", 240., 1., 240.5, 1.), quote("AAPL", 150.1, 5., 150.25, 1.)),
+
+        // A side without any quote removes the previous level.
+        format!("[{}]", quote("AAPL", 0., 0., 150.25, 1.)),
+    ];
+
+    let notifs = parse(&frames);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(15_020, 3, Side::Ask).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(15_010, 2, Side::Bid).with_timestamp(TIMESTAMP),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(15_020, 0, Side::Ask).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(15_025, 1, Side::Ask).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(15_010, 5, Side::Bid).with_timestamp(TIMESTAMP),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(15_010, 0, Side::Bid).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(15_025, 1, Side::Ask).with_timestamp(TIMESTAMP),
+        ]),
+    ]);
+}
@@ -0,0 +1,629 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, str, thread};
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{convert_str_timestamp, timestamp_ms, IntoTimestamped};
+use crate::api::alpaca::{Client, Keys};
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let public_endpoint = self.params.streaming_endpoint.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        // Order updates are streamed by the trading API rather than by the market data
+        // API, see `PrivateHandlerImpl`.
+        let private_endpoint = format!(
+            "{}/stream",
+            self.params.rest_endpoint.replacen("https://", "wss://", 1)
+        );
+        let keys = self.keys.clone();
+        let private_snd = snd.clone();
+        let order_ids = self.order_ids.clone();
+        let public_control = control.clone();
+
+        // The flags of the private connection are those of the public one,
+        // see `PrivateHandlerImpl::control`.
+        let private_control = control.linked();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", private_endpoint);
+
+            if let Err(err) = ws::connect(private_endpoint, |out| {
+                wss::Handler::new(
+                    out.clone(),
+                    private_snd.clone(),
+                    private_control.clone(),
+                    wss::KeepAlive::True,
+                    PrivateHandlerImpl {
+                        symbol,
+                        keys: keys.clone(),
+                        out,
+                        control: public_control.clone(),
+                        order_ids: order_ids.clone(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        let keys = self.keys.clone();
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", public_endpoint);
+
+            if let Err(err) = ws::connect(public_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        out: None,
+                        authenticated: false,
+                        order_book: OrderBook::new(),
+                        quote: None,
+                        last_trade: None,
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Best bid and ask prices of the last quote.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+struct Quote {
+    bid: TickUnit,
+    ask: TickUnit,
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Keys,
+
+    /// Set once the connection is open, needed for subscribing from within `on_message`.
+    out: Option<ws::Sender>,
+
+    /// Subscriptions are only accepted once the connection is authenticated.
+    authenticated: bool,
+
+    /// Local copy of the top of the book, needed for removing the previous levels when
+    /// the best prices change.
+    order_book: OrderBook,
+
+    /// Last quote, for inferring the side of the trades.
+    quote: Option<Quote>,
+
+    /// Price and maker side of the last trade.
+    last_trade: Option<(TickUnit, Side)>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct AlpacaSubscription<'a> {
+    action: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    trades: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    quotes: Vec<&'a str>,
+}
+
+/// Messages of the market data stream, sent by batches as JSON arrays. Trades and
+/// quotes carry their prices and sizes as JSON numbers.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct AlpacaMessage<'a> {
+    T: &'a str,
+    msg: Option<String>,
+    code: Option<u64>,
+    S: Option<&'a str>,
+    t: Option<&'a str>,
+
+    /// Trades only.
+    p: Option<f64>,
+    s: Option<f64>,
+
+    /// Quotes only.
+    bp: Option<f64>,
+    bs: Option<f64>,
+    ap: Option<f64>,
+    #[serde(rename = "as")]
+    as_: Option<f64>,
+}
+
+fn convert_alpaca_number(tick: Tick, value: f64) -> Result<TickUnit, failure::Error> {
+    Ok(tick.ticked(&format!("{}", value))?)
+}
+
+fn send_subscription(
+    action: &str,
+    trades: Vec<&str>,
+    quotes: Vec<&str>,
+    out: &ws::Sender
+) -> ws::Result<()>
+{
+    if trades.is_empty() && quotes.is_empty() {
+        return Ok(());
+    }
+
+    match serde_json::to_string(&AlpacaSubscription { action, trades, quotes }) {
+        Ok(value) => out.send(value),
+        Err(err) => {
+            panic!("failed to serialize `AlpacaSubscription`: `{}`", err);
+        }
+    }
+}
+
+/// Quotes are needed for inferring the side of the trades as well.
+fn needs_quotes(flags: NotificationFlags) -> bool {
+    flags.intersects(NotificationFlags::ORDER_BOOK | NotificationFlags::TRADES)
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, with empty keys and which is not
+    /// connected, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: Keys {
+                key_id: String::new(),
+                secret_key: String::new(),
+            },
+            out: None,
+            authenticated: false,
+            order_book: OrderBook::new(),
+            quote: None,
+            last_trade: None,
+        }
+    }
+
+    fn subscribe(&self, flags: NotificationFlags) -> ws::Result<()> {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        let name = self.symbol.name();
+        let trades = if flags.contains(NotificationFlags::TRADES) { vec![name] } else { vec![] };
+        let quotes = if needs_quotes(flags) { vec![name] } else { vec![] };
+        send_subscription("subscribe", trades, quotes, out)
+    }
+
+    /// Infer the side of the maker with the Lee-Ready algorithm: trades above the mid
+    /// price of the last quote are buys, trades below are sells, while trades at the
+    /// mid price are classified by comparing their price with the last trade.
+    fn maker_side(&self, price: TickUnit) -> Side {
+        let mid = self.quote
+            .filter(|quote| quote.bid > 0 && quote.ask > 0)
+            .map(|quote| quote.bid + quote.ask);
+
+        match mid {
+            Some(mid) if 2 * price > mid => return Side::Ask,
+            Some(mid) if 2 * price < mid => return Side::Bid,
+            _ => (),
+        }
+
+        match self.last_trade {
+            Some((last_price, _)) if price > last_price => Side::Ask,
+            Some((last_price, _)) if price < last_price => Side::Bid,
+            Some((_, side)) => side,
+            None => Side::Ask,
+        }
+    }
+
+    fn parse_trade(&mut self, trade: &AlpacaMessage<'_>, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let (price, size, time) = match (trade.p, trade.s, trade.t) {
+            (Some(price), Some(size), Some(time)) => (price, size, time),
+            _ => bail!("malformed trade: `{:?}`", trade),
+        };
+
+        let price = convert_alpaca_number(self.symbol.price_tick(), price)?;
+        let maker_side = self.maker_side(price);
+        self.last_trade = Some((price, maker_side));
+
+        if self.flags.contains(NotificationFlags::TRADES) {
+            let trade = Notification::Trade(Trade {
+                price,
+                size: convert_alpaca_number(self.symbol.size_tick(), size)?,
+                maker_side,
+            }.with_timestamp(convert_str_timestamp(time)?));
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Quotes carry the best bid and ask, which are forwarded as the only levels of
+    /// the book.
+    fn parse_quote(&mut self, quote: &AlpacaMessage<'_>, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let (bid, bid_size, ask, ask_size, time) = match (
+            quote.bp,
+            quote.bs,
+            quote.ap,
+            quote.as_,
+            quote.t
+        ) {
+            (Some(bid), Some(bid_size), Some(ask), Some(ask_size), Some(time)) => {
+                (bid, bid_size, ask, ask_size, time)
+            }
+            _ => bail!("malformed quote: `{:?}`", quote),
+        };
+
+        let price_tick = self.symbol.price_tick();
+        let size_tick = self.symbol.size_tick();
+        let bid = LimitUpdate::new(
+            convert_alpaca_number(price_tick, bid)?,
+            convert_alpaca_number(size_tick, bid_size)?,
+            Side::Bid
+        );
+        let ask = LimitUpdate::new(
+            convert_alpaca_number(price_tick, ask)?,
+            convert_alpaca_number(size_tick, ask_size)?,
+            Side::Ask
+        );
+
+        self.quote = Some(Quote {
+            bid: bid.price,
+            ask: ask.price,
+        });
+
+        if !self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            return Ok(());
+        }
+
+        let stale_bids = self.order_book.bid()
+            .filter(|(price, _)| **price != bid.price)
+            .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+        let stale_asks = self.order_book.ask()
+            .filter(|(price, _)| **price != ask.price)
+            .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+        let mut updates: Vec<_> = stale_asks.chain(stale_bids).collect();
+
+        // A side without any quote is sent with a zero price and size.
+        updates.extend([ask, bid].iter().filter(|update| update.price > 0).cloned());
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+
+        let timestamp = convert_str_timestamp(time)?;
+        let updates = updates.into_iter()
+            .map(|update| update.with_timestamp(timestamp))
+            .collect();
+        out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let messages: Vec<AlpacaMessage<'_>> = serde_json::from_str(json)?;
+
+        for message in messages {
+            if message.S.is_some() && message.S != Some(self.symbol.name()) {
+                continue;
+            }
+
+            match message.T {
+                "success" if message.msg.as_ref().map(|msg| msg == "authenticated")
+                    .unwrap_or(false) =>
+                {
+                    self.authenticated = true;
+                    self.subscribe(self.flags)?;
+                }
+                "error" => bail!("error {:?}: {:?}", message.code, message.msg),
+                "t" => self.parse_trade(&message, out)?,
+                "q" => self.parse_quote(&message, out)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        // The connection is authenticated by the headers of the handshake, see
+        // `handshake_headers`, and subscriptions wait for the confirmation.
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        if !self.authenticated {
+            return Ok(());
+        }
+
+        // Orders are streamed through the private connection, where `ORDERS` only
+        // acts as a filter.
+        if !previous.contains(NotificationFlags::ORDER_BOOK)
+            && flags.contains(NotificationFlags::ORDER_BOOK)
+        {
+            self.order_book = OrderBook::new();
+        }
+
+        let name = self.symbol.name();
+        let mut subscribe = (Vec::new(), Vec::new());
+        let mut unsubscribe = (Vec::new(), Vec::new());
+
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => subscribe.0.push(name),
+            (true, false) => unsubscribe.0.push(name),
+            _ => (),
+        }
+
+        match (needs_quotes(previous), needs_quotes(flags)) {
+            (false, true) => subscribe.1.push(name),
+            (true, false) => {
+                self.quote = None;
+                unsubscribe.1.push(name);
+            }
+            _ => (),
+        }
+
+        send_subscription("subscribe", subscribe.0, subscribe.1, out)?;
+        send_subscription("unsubscribe", unsubscribe.0, unsubscribe.1, out)
+    }
+
+    fn handshake_headers(&mut self) -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("APCA-API-KEY-ID".to_owned(), self.keys.key_id.clone().into_bytes()),
+            ("APCA-API-SECRET-KEY".to_owned(), self.keys.secret_key.clone().into_bytes()),
+        ]
+    }
+}
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    keys: Keys,
+    out: ws::Sender,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// client order id => server order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct AlpacaAuth<'a> {
+    action: &'a str,
+    key: &'a str,
+    secret: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct AlpacaStreams<'a> {
+    streams: &'a [&'a str],
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct AlpacaListen<'a> {
+    action: &'a str,
+    data: AlpacaStreams<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaStream<'a, T> {
+    stream: &'a str,
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaAuthorization<'a> {
+    status: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaOrder<'a> {
+    id: &'a str,
+    client_order_id: &'a str,
+    symbol: &'a str,
+    side: &'a str,
+
+    /// `None` for orders placed with a notional value.
+    qty: Option<&'a str>,
+    filled_qty: &'a str,
+
+    /// `None` for market orders.
+    limit_price: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct AlpacaTradeUpdate<'a> {
+    event: &'a str,
+    timestamp: Option<&'a str>,
+
+    /// Price and size of the fill, for `fill` and `partial_fill` events only.
+    price: Option<&'a str>,
+    qty: Option<&'a str>,
+
+    #[serde(borrow)]
+    order: AlpacaOrder<'a>,
+}
+
+fn convert_alpaca_side(side: &str) -> Result<Side, failure::Error> {
+    let side = match side {
+        "buy" => Side::Bid,
+        "sell" => Side::Ask,
+        other => bail!("wrong side: `{}`", other),
+    };
+    Ok(side)
+}
+
+impl PrivateHandlerImpl {
+    fn parse_trade_update(&self, json: &str, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let update: AlpacaStream<'_, AlpacaTradeUpdate<'_>> = serde_json::from_str(json)?;
+        let update = update.data;
+        let order = &update.order;
+
+        // The stream carries the orders of every symbol.
+        if order.symbol != self.symbol.name() {
+            return Ok(());
+        }
+
+        // Don't forget to update the concurrent map `client order id => server order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(order.client_order_id) {
+            self.order_ids.insert(order.client_order_id.to_owned(), order.id.to_owned());
+            debug!("insert order id {} (from WSS)", order.client_order_id);
+        }
+
+        let size_tick = self.symbol.size_tick();
+        let order_id = order.client_order_id.to_owned();
+        let size = size_tick.ticked(order.qty.unwrap_or("0"))?;
+        let filled_size = size_tick.ticked(order.filled_qty)?;
+        let timestamp = match update.timestamp {
+            Some(timestamp) => convert_str_timestamp(timestamp)?,
+            None => timestamp_ms(),
+        };
+
+        match update.event {
+            "new" => {
+                let price = match order.limit_price {
+                    Some(price) => self.symbol.price_tick().ticked(price)?,
+                    None => return Ok(()),
+                };
+
+                let order = OrderConfirmation {
+                    size,
+                    price,
+                    side: convert_alpaca_side(order.side)?,
+                    order_id,
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+            }
+
+            "fill" | "partial_fill" => {
+                let (price, qty) = match (update.price, update.qty) {
+                    (Some(price), Some(qty)) => (price, qty),
+                    _ => bail!("malformed fill: `{:?}`", update),
+                };
+
+                let update = OrderUpdate {
+                    order_id,
+                    consumed_size: size_tick.ticked(qty)?,
+                    remaining_size: size.saturating_sub(filled_size),
+                    consumed_price: self.symbol.price_tick().ticked(price)?,
+                    commission: 0,
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+            }
+
+            "canceled" | "expired" | "rejected" | "done_for_day" => {
+                let expiration = OrderExpiration {
+                    order_id,
+                    filled_size: Some(filled_size),
+                    remaining_size: Some(size.saturating_sub(filled_size)),
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn parse_message(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: AlpacaStream<'_, serde_json::Value> = serde_json::from_str(json)?;
+
+        match message.stream {
+            "authorization" => {
+                let auth: AlpacaStream<'_, AlpacaAuthorization<'_>> = serde_json::from_str(json)?;
+                if auth.data.status != "authorized" {
+                    bail!("authorization failed: `{}`", auth.data.status);
+                }
+
+                let listen = AlpacaListen {
+                    action: "listen",
+                    data: AlpacaStreams {
+                        streams: &["trade_updates"],
+                    },
+                };
+                self.out.send(serde_json::to_string(&listen)?)?;
+            }
+
+            "trade_updates" if self.control.flags().contains(NotificationFlags::ORDERS) => {
+                self.parse_trade_update(json, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        let auth = AlpacaAuth {
+            action: "auth",
+            key: &self.keys.key_id,
+            secret: &self.keys.secret_key,
+        };
+        match serde_json::to_string(&auth) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `AlpacaAuth`: `{}`", err);
+            }
+        }
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    /// Paper trading accounts send their messages as binary frames.
+    fn on_binary(&mut self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(str::from_utf8(data)?, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+}
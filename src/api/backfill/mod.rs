@@ -0,0 +1,136 @@
+//! A module defining a backfill of recent history in front of a live notification
+//! stream, so that indicators consuming the stream are warmed up right away.
+//!
+//! Upon subscription, the live stream is started first and its notifications are
+//! buffered while the most recent bars and trades are fetched over REST. Once the
+//! history is received, the bars are output, then the trades, then the buffered live
+//! notifications, after which live notifications are forwarded as they come.
+//!
+//! Backfilled trades which may overlap the live stream, i.e. those not older than the
+//! first live trade, are dropped.
+
+mod test;
+#[cfg(feature = "network")]
+mod stream;
+
+#[cfg(feature = "network")]
+pub use self::stream::BackfillStream;
+
+use crate::api::{Notification, Trade};
+use crate::api::timestamp::{Timestamp, Timestamped};
+use crate::data::resample::Bar;
+
+#[derive(Clone, PartialEq, Debug)]
+/// An event output by a `Backfill`, either backfilled over REST or received live.
+pub enum BackfillEvent {
+    /// A bar fetched over REST, indexed by the start of its interval. The last bar
+    /// is usually still in progress when it is fetched.
+    Bar(Timestamped<Bar>),
+
+    /// A trade fetched over REST.
+    Trade(Timestamped<Trade>),
+
+    /// A notification from the live stream.
+    Live(Notification),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// Splice recent history fetched over REST in front of a live notification stream.
+pub struct Backfill {
+    trades: usize,
+    bars: Option<(Timestamp, usize)>,
+    pending: bool,
+    first_live_trade: Option<Timestamp>,
+    buffered: Vec<Notification>,
+}
+
+impl Backfill {
+    /// Return a new `Backfill`, fetching nothing until configured otherwise.
+    pub fn new() -> Self {
+        Backfill {
+            trades: 0,
+            bars: None,
+            pending: true,
+            first_live_trade: None,
+            buffered: Vec::new(),
+        }
+    }
+
+    /// Fetch the `count` most recent trades.
+    pub fn with_trades(mut self, count: usize) -> Self {
+        self.trades = count;
+        self
+    }
+
+    /// Fetch the `count` most recent bars lasting `interval` milliseconds, see
+    /// `ApiClient::recent_bars` for the intervals supported by each exchange.
+    ///
+    /// # Panics
+    /// Panic if `interval` is `0`.
+    pub fn with_bars(mut self, interval: Timestamp, count: usize) -> Self {
+        if interval == 0 {
+            panic!("`interval` must be positive");
+        }
+        self.bars = Some((interval, count));
+        self
+    }
+
+    /// Return the number of trades to fetch.
+    pub fn trades(&self) -> usize {
+        self.trades
+    }
+
+    /// Return the interval and number of bars to fetch, if any.
+    pub fn bars(&self) -> Option<(Timestamp, usize)> {
+        self.bars
+    }
+
+    /// Return `true` if the history has not been received yet, in which case live
+    /// notifications are buffered.
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Process a live notification, which is buffered while the history is pending.
+    pub fn on_notification(&mut self, notif: Notification) -> Vec<BackfillEvent> {
+        if let Notification::Trade(trade) = &notif {
+            if self.first_live_trade.is_none() {
+                self.first_live_trade = Some(trade.timestamp());
+            }
+        }
+
+        if self.pending {
+            self.buffered.push(notif);
+            return Vec::new();
+        }
+        vec![BackfillEvent::Live(notif)]
+    }
+
+    /// Process the history, bars and trades both being ordered oldest first. Return
+    /// the backfilled events followed by the buffered live notifications.
+    ///
+    /// When the history cannot be fetched, call this with empty vectors in order to
+    /// switch to live notifications only.
+    pub fn on_history(&mut self, bars: Vec<Timestamped<Bar>>, trades: Vec<Timestamped<Trade>>)
+        -> Vec<BackfillEvent>
+    {
+        self.pending = false;
+
+        let first_live_trade = self.first_live_trade;
+        let trades = trades.into_iter().filter(|trade| {
+            first_live_trade.map(|first| trade.timestamp() < first).unwrap_or(true)
+        });
+
+        bars.into_iter()
+            .map(BackfillEvent::Bar)
+            .chain(trades.map(BackfillEvent::Trade))
+            .chain(self.buffered.drain(..).map(BackfillEvent::Live))
+            .collect()
+    }
+}
+
+impl Default for Backfill {
+    fn default() -> Self {
+        Backfill::new()
+    }
+}
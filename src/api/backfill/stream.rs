@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use futures::prelude::*;
+use log::warn;
+use crate::api::{errors, ApiClient, Notification, NotificationFlags, Trade};
+use crate::api::backfill::{Backfill, BackfillEvent};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::Timestamped;
+use crate::data::resample::Bar;
+
+type History = Box<
+    dyn Future<
+        Item = (Vec<Timestamped<Bar>>, Vec<Timestamped<Trade>>),
+        Error = errors::Error
+    > + Send + 'static
+>;
+
+impl Backfill {
+    /// Start streaming notifications for `symbol` from `client`, preceded by the
+    /// configured history. The returned stream ends when the live stream ends.
+    pub fn stream<C: ApiClient>(self, client: &C, symbol: Symbol) -> BackfillStream<C::Stream> {
+        self.stream_with_flags(client, symbol, NotificationFlags::ALL)
+    }
+
+    /// Same as `stream`, only forward the live notifications indicated by `flags`.
+    pub fn stream_with_flags<C: ApiClient>(
+        self,
+        client: &C,
+        symbol: Symbol,
+        flags: NotificationFlags
+    ) -> BackfillStream<C::Stream>
+    {
+        // Subscribe first, so that no live notification is missed.
+        let live = client.stream_with_flags(symbol, flags);
+
+        let bars: Box<dyn Future<Item = _, Error = _> + Send> = match self.bars {
+            Some((interval, count)) if count > 0 => client.recent_bars(symbol, interval, count),
+            _ => Box::new(futures::future::ok(Vec::new())),
+        };
+        let trades: Box<dyn Future<Item = _, Error = _> + Send> = match self.trades {
+            0 => Box::new(futures::future::ok(Vec::new())),
+            count => client.recent_trades(symbol, count),
+        };
+
+        BackfillStream {
+            backfill: self,
+            live: Some(live),
+            history: Some(Box::new(bars.join(trades))),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// A stream of `BackfillEvent`, see `Backfill::stream`.
+pub struct BackfillStream<S> {
+    backfill: Backfill,
+    live: Option<S>,
+    history: Option<History>,
+    pending: VecDeque<BackfillEvent>,
+}
+
+impl<S> BackfillStream<S> {
+    /// Return the underlying `Backfill`.
+    pub fn backfill(&self) -> &Backfill {
+        &self.backfill
+    }
+}
+
+impl<S> Stream for BackfillStream<S> where S: Stream<Item = Notification, Error = ()> {
+    type Item = BackfillEvent;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<BackfillEvent>, ()> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Async::Ready(Some(event)));
+            }
+
+            let history = match self.history.as_mut().map(|history| history.poll()) {
+                Some(Ok(Async::Ready((bars, trades)))) => Some((bars, trades)),
+                Some(Err(err)) => {
+                    warn!("cannot fetch history, streaming live notifications only: {}", err);
+                    Some((Vec::new(), Vec::new()))
+                }
+                Some(Ok(Async::NotReady)) | None => None,
+            };
+            if let Some((bars, trades)) = history {
+                self.history = None;
+                self.pending.extend(self.backfill.on_history(bars, trades));
+                continue;
+            }
+
+            let live = match self.live.as_mut() {
+                Some(live) => live,
+                None if self.history.is_some() => return Ok(Async::NotReady),
+                None => return Ok(Async::Ready(None)),
+            };
+
+            match live.poll()? {
+                Async::Ready(Some(notif)) => {
+                    self.pending.extend(self.backfill.on_notification(notif));
+                }
+                Async::Ready(None) => self.live = None,
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
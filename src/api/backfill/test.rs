@@ -0,0 +1,77 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade};
+use crate::api::backfill::{Backfill, BackfillEvent};
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::Bar;
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn trade(timestamp: Timestamp, price: TickUnit) -> Timestamped<Trade> {
+    Trade {
+        price,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp)
+}
+
+fn bar(timestamp: Timestamp, close: TickUnit) -> Timestamped<Bar> {
+    Bar {
+        open: close,
+        high: close,
+        low: close,
+        close,
+        volume: 0,
+        count: 0,
+    }.with_timestamp(timestamp)
+}
+
+fn update(timestamp: Timestamp) -> Notification {
+    Notification::LimitUpdates(vec![LimitUpdate::new(100, 1, Side::Bid).with_timestamp(timestamp)])
+}
+
+#[test]
+fn splice() {
+    let mut backfill = Backfill::new().with_trades(3).with_bars(60_000, 2);
+    assert_eq!(backfill.trades(), 3);
+    assert_eq!(backfill.bars(), Some((60_000, 2)));
+    assert!(backfill.is_pending());
+
+    assert!(backfill.on_notification(update(110)).is_empty());
+    assert!(backfill.on_notification(Notification::Trade(trade(120, 101))).is_empty());
+
+    let events = backfill.on_history(
+        vec![bar(0, 99), bar(60_000, 100)],
+        vec![trade(100, 99), trade(110, 100), trade(120, 101)]
+    );
+    assert!(!backfill.is_pending());
+
+    // The last backfilled trade overlaps the live stream.
+    assert_eq!(events, vec![
+        BackfillEvent::Bar(bar(0, 99)),
+        BackfillEvent::Bar(bar(60_000, 100)),
+        BackfillEvent::Trade(trade(100, 99)),
+        BackfillEvent::Trade(trade(110, 100)),
+        BackfillEvent::Live(update(110)),
+        BackfillEvent::Live(Notification::Trade(trade(120, 101))),
+    ]);
+
+    assert_eq!(backfill.on_notification(update(130)), vec![BackfillEvent::Live(update(130))]);
+}
+
+#[test]
+fn failed_history() {
+    let mut backfill = Backfill::new().with_trades(10);
+    assert!(backfill.on_notification(update(10)).is_empty());
+    assert_eq!(
+        backfill.on_history(Vec::new(), Vec::new()),
+        vec![BackfillEvent::Live(update(10))]
+    );
+}
+
+#[test]
+#[should_panic]
+fn zero_interval() {
+    Backfill::new().with_bars(0, 10);
+}
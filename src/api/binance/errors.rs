@@ -46,6 +46,15 @@ impl api::errors::ErrorKinded<!> for RestError {
             return api::errors::RestErrorKind::OutsideTimeWindow;
         }
 
+        // Unauthorized, invalid signature, invalid API key format, rejected API key.
+        if self.error_code == Some(-1002) ||
+            self.error_code == Some(-1022) ||
+            self.error_code == Some(-2014) ||
+            self.error_code == Some(-2015)
+        {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
         if self.kind == RestErrorKind::InternalError ||
             self.error_code == Some(-1001)
         {
@@ -111,10 +120,37 @@ impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
             );
         }
 
+        // Futures report these with dedicated codes.
+        if self.error_code == Some(-2019) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.error_code == Some(-5022) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+
         <Self as api::errors::ErrorKinded<!>>::kind(self).into()
     }
 }
 
+impl api::errors::ErrorKinded<api::errors::ReplaceErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::ReplaceErrorKind> {
+        // The cancel failed, hence the new order was not sent. The reason is only
+        // detailed in the response body, the previous order being most likely filled.
+        if self.error_code == Some(-2022) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::ReplaceErrorKind::Cancel(api::errors::CancelErrorKind::UnknownOrder)
+            );
+        }
+
+        api::errors::replace_kind(self)
+    }
+}
+
 impl RestError {
     pub(super) fn from_binance_error(status: StatusCode, binance_error: Option<BinanceRestError>)
         -> Self
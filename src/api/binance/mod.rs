@@ -6,8 +6,10 @@ mod rest;
 
 use openssl::pkey::{PKey, Private};
 use std::collections::HashMap;
+use std::sync::{Arc, Weak, Mutex, RwLock};
 use futures::prelude::*;
-use log::debug;
+use futures::sync::oneshot;
+use log::{debug, warn};
 use serde_derive::{Serialize, Deserialize};
 use crate::api::{
     self,
@@ -16,14 +18,25 @@ use crate::api::{
     Params,
     Order,
     OrderAck,
+    OneCancelsOther,
+    OcoAck,
     Cancel,
     CancelAck,
-    Notification,
+    OrderStatus,
+    Fill,
+    Replace,
     NotificationFlags,
     Balances,
+    ExchangeStatus,
+    Trade,
 };
+use crate::api::wallet::{WalletApi, DustConversion};
+use crate::api::derivatives::{DerivativesApi, Position, FundingRate, OpenInterest};
+use crate::api::stream::NotificationStream;
 use crate::api::symbol::{Symbol, WithSymbol};
-use crate::api::timestamp::Timestamped;
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamp, Timestamped};
+use crate::data::resample::Bar;
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// A binance key pair: api key + secret key.
@@ -42,10 +55,38 @@ impl KeyPair {
     }
 }
 
-struct Keys {
+crate struct Keys {
     api_key: String,
     secret_key: PKey<Private>,
-    listen_key: String,
+
+    /// Resolved once every request signed with these keys has completed, i.e. once
+    /// the last reference to them is dropped, see `Client::rotate_keys`.
+    drained: Mutex<Option<oneshot::Receiver<()>>>,
+    _in_flight: oneshot::Sender<()>,
+}
+
+impl Keys {
+    fn new(pair: KeyPair) -> Result<Self, openssl::error::ErrorStack> {
+        let (in_flight, drained) = oneshot::channel();
+        Ok(Keys {
+            api_key: pair.api_key,
+            secret_key: PKey::hmac(pair.secret_key.as_bytes())?,
+            drained: Mutex::new(Some(drained)),
+            _in_flight: in_flight,
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Market traded by a client. Spot pairs and USD-M futures share the same symbol
+/// names, e.g. `BTCUSDT`, hence a client only deals with one market.
+pub enum Market {
+    /// Spot pairs, reached at `https://api.binance.com` and `wss://stream.binance.com:9443`.
+    Spot,
+
+    /// USD-M futures, margined and settled in USDT or BUSD, reached at
+    /// `https://fapi.binance.com` and `wss://fstream.binance.com`.
+    UsdMargined,
 }
 
 /// A binance API client.
@@ -59,13 +100,45 @@ struct Keys {
 /// recommends sending a ping every 30 minutes. If the listen key becomes invalid, this client
 /// will stop forwarding the user data stream. The only way to fix it will be to drop the client
 /// and create a new one.
+///
+/// The key pair can be replaced on a live client through `Client::rotate_keys`.
 pub struct Client {
     params: Params,
-    keys: Option<Keys>,
+    market: Market,
+    keys: Arc<RwLock<Option<Arc<Keys>>>>,
+
+    /// Shared with the handlers of the notification streams.
+    listen_key: Arc<RwLock<Option<String>>>,
+
+    /// Controls of the notification streams, woken up when the listen key changes.
+    streams: Arc<Mutex<Vec<Weak<api::wss::Control>>>>,
     symbols: HashMap<String, Symbol>,
     http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
 }
 
+impl Params {
+    /// Return the parameters of the binance spot testnet, where keys are generated at
+    /// `https://testnet.binance.vision`. To be used with `Market::Spot`.
+    ///
+    /// # Note
+    /// The testnet does not provide `system_status`.
+    pub fn binance_testnet() -> Self {
+        Params {
+            streaming_endpoint: "wss://testnet.binance.vision".to_owned(),
+            rest_endpoint: "https://testnet.binance.vision".to_owned(),
+        }
+    }
+
+    /// Return the parameters of the binance futures testnet, where keys are generated at
+    /// `https://testnet.binancefuture.com`. To be used with `Market::UsdMargined`.
+    pub fn binance_futures_testnet() -> Self {
+        Params {
+            streaming_endpoint: "wss://stream.binancefuture.com".to_owned(),
+            rest_endpoint: "https://testnet.binancefuture.com".to_owned(),
+        }
+    }
+}
+
 impl Client {
     /// Create a new binance API client with given `params`. If `key_pair` is not
     /// `None`, this will enable performing requests to the REST API and will request
@@ -75,53 +148,131 @@ impl Client {
     /// The request may block the thread if requesting a listen key for the user data stream.
     /// The method will also block when fetching the available symbols from binance.
     pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        Self::with_market(params, Market::Spot, key_pair)
+    }
+
+    /// Create a new binance API client for the given `market`, with given `params`
+    /// which must point to the endpoints of this market. See `Client::new`.
+    pub fn with_market(params: Params, market: Market, key_pair: Option<KeyPair>)
+        -> Result<Self, failure::Error>
+    {
         use tokio::runtime::current_thread;
 
         let http_client = hyper::Client::builder().build::<_, hyper::Body>(
             hyper_tls::HttpsConnector::new(2)?
         );
 
-        let mut client = match key_pair {
-            Some(pair) => {
-                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
-
-                let mut client = Client {
-                    params,
-                    keys: Some(Keys {
-                        api_key: pair.api_key,
-                        secret_key,
-                        listen_key: String::new(),
-                    }),
-                    symbols: HashMap::new(),
-                    http_client,
-                };
-
-                debug!("requesting listen key");
-                let listen_key = current_thread::Runtime::new()?
-                    .block_on(client.get_listen_key())?;
-                debug!("received listen key");
-
-                client.keys.as_mut().unwrap().listen_key = listen_key;
-                client
-            }
-            None => Client {
-                params,
-                keys: None,
-                symbols: HashMap::new(),
-                http_client,
-            }
+        let keys = match key_pair {
+            Some(pair) => Some(Arc::new(Keys::new(pair)?)),
+            None => None,
+        };
+
+        let mut client = Client {
+            params,
+            market,
+            keys: Arc::new(RwLock::new(keys)),
+            listen_key: Arc::new(RwLock::new(None)),
+            streams: Arc::new(Mutex::new(Vec::new())),
+            symbols: HashMap::new(),
+            http_client,
         };
 
+        if let Some(keys) = client.keys() {
+            debug!("requesting listen key");
+            let listen_key = current_thread::Runtime::new()?
+                .block_on(client.get_listen_key(keys))?;
+            debug!("received listen key");
+
+            *client.listen_key.write().unwrap() = Some(listen_key);
+        }
+
         debug!("requesting symbols");
         client.symbols = current_thread::Runtime::new()?
             .block_on(client.get_symbols())?;
         debug!("received symbols");
         Ok(client)
     }
+
+    /// Return the market traded by this client.
+    pub fn market(&self) -> Market {
+        self.market
+    }
+
+    /// Replace the key pair of this client with `key_pair`, without interrupting it.
+    ///
+    /// A listen key is first requested with the new key pair. Requests are then
+    /// atomically signed with the new key pair, and the notification streams switch
+    /// their user data stream to the new listen key in the background. The previous
+    /// listen key is then closed, a failure to do so being only logged since the
+    /// listen key expires by itself after an hour. The returned future resolves once
+    /// every request signed with the previous key pair has completed, after which the
+    /// previous key pair can safely be revoked.
+    ///
+    /// If the client was created without a key pair, this enables the private requests.
+    ///
+    /// # Note
+    /// Only the binance client supports rotating its key pair, the clients of the other
+    /// exchanges must be dropped and created again with the new key pair.
+    pub fn rotate_keys(&self, key_pair: KeyPair)
+        -> Box<dyn Future<Item = (), Error = api::errors::Error> + Send + 'static>
+    {
+        let new_keys = match Keys::new(key_pair) {
+            Ok(keys) => Arc::new(keys),
+            Err(err) => return Box::new(futures::future::err(
+                api::errors::ApiError::RequestError(api::errors::RequestError::new(err))
+            )),
+        };
+
+        // The request is only sent once the streams have been switched to the new
+        // listen key.
+        let previous_listen_key = self.listen_key.read().unwrap().clone();
+        let close = match (self.keys(), previous_listen_key) {
+            (Some(keys), Some(listen_key)) => futures::future::Either::A(
+                self.close_listen_key(keys, &listen_key).then(|result| {
+                    if let Err(err) = result {
+                        warn!("failed to close the previous listen key: `{}`", err);
+                    }
+                    Ok(())
+                })
+            ),
+            _ => futures::future::Either::B(futures::future::ok(())),
+        };
+
+        let keys = self.keys.clone();
+        let listen_key = self.listen_key.clone();
+        let streams = self.streams.clone();
+
+        debug!("requesting listen key for the new key pair");
+        let fut = self.get_listen_key(new_keys.clone()).and_then(move |new_listen_key| {
+            let previous = keys.write().unwrap().replace(new_keys);
+            *listen_key.write().unwrap() = Some(new_listen_key);
+            debug!("switched to the new key pair");
+
+            for control in streams.lock().unwrap().iter().filter_map(Weak::upgrade) {
+                control.refresh();
+            }
+
+            let drained = previous.and_then(|keys| keys.drained.lock().unwrap().take());
+            close.and_then(|()| match drained {
+                // The sender is dropped along with the previous keys.
+                Some(drained) => futures::future::Either::A(drained.then(|_| Ok(()))),
+                None => futures::future::Either::B(futures::future::ok(())),
+            })
+        });
+        Box::new(fut)
+    }
+
+    fn keys(&self) -> Option<Arc<Keys>> {
+        self.keys.read().unwrap().clone()
+    }
 }
 
 impl ApiClient for Client {
-    type Stream = futures::sync::mpsc::UnboundedReceiver<Notification>;
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Binance
+    }
 
     fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
         self.symbols.get(&symbol.to_lowercase()).cloned()
@@ -134,12 +285,93 @@ impl ApiClient for Client {
     fn order(&self, order: WithSymbol<&Order>)
         -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
     {
-        Box::new(self.order_impl(order))
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    /// Only spot supports OCO orders.
+    fn supports_oco(&self) -> bool {
+        self.market == Market::Spot
+    }
+
+    fn oco(&self, oco: WithSymbol<&OneCancelsOther>)
+        -> Box<dyn Future<Item = Timestamped<OcoAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.market != Market::Spot {
+            warn!("called `oco` on futures, which is only supported on spot");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.oco_impl(oco)
+    }
+
+    fn order_status(&self, symbol: Symbol, order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.order_status_impl(symbol, order_id))
+    }
+
+    fn my_trades(&self, symbol: Symbol, since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.my_trades_impl(symbol, since))
+    }
+
+    fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.recent_trades_impl(symbol, limit))
+    }
+
+    fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = api::errors::Error> + Send + 'static>
+    {
+        self.recent_bars_impl(symbol, interval, limit)
+    }
+
+    /// Only spot replaces orders atomically.
+    fn supports_atomic_replace(&self) -> bool {
+        self.market == Market::Spot
+    }
+
+    fn replace(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::ReplaceError> + Send + 'static>
+    {
+        if self.market != Market::Spot {
+            return api::cancel_then_order(self, replace);
+        }
+
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.replace_impl(replace)
     }
 
     fn cancel(&self, cancel: WithSymbol<&Cancel>)
         -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
     {
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
         Box::new(self.cancel_impl(cancel))
     }
 
@@ -152,7 +384,100 @@ impl ApiClient for Client {
     fn balances(&self)
         -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
     {
-        Box::new(self.balances_impl())
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.balances_impl()
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        self.system_status_impl()
+    }
+}
+
+impl WalletApi for Client {
+    fn convert_dust(&self, assets: &[&str])
+        -> Box<dyn Future<Item = Timestamped<DustConversion>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.market != Market::Spot {
+            warn!("called `convert_dust` on futures, which is only supported on spot");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.convert_dust_impl(assets))
+    }
+}
+
+/// Only available on USD-M futures, spot clients reject the requests with
+/// `RestErrorKind::InvalidRequest`.
+impl DerivativesApi for Client {
+    fn set_leverage(&self, symbol: &Symbol, leverage: u32)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.market != Market::UsdMargined {
+            warn!("called `set_leverage` on spot, which is only supported on futures");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.set_leverage_impl(symbol, leverage))
+    }
+
+    fn positions(&self)
+        -> Box<dyn Future<Item = Vec<Position>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.market != Market::UsdMargined {
+            warn!("called `positions` on spot, which is only supported on futures");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if self.keys().is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.positions_impl())
+    }
+
+    fn funding_rates(&self, symbol: &Symbol, start: Timestamp, end: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<FundingRate>>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.market != Market::UsdMargined {
+            warn!("called `funding_rates` on spot, which is only supported on futures");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        Box::new(self.funding_rates_impl(symbol, start, end))
+    }
+
+    fn open_interest(&self, symbol: &Symbol, period: Timestamp, start: Timestamp, end: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<OpenInterest>>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.market != Market::UsdMargined {
+            warn!("called `open_interest` on spot, which is only supported on futures");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        self.open_interest_impl(symbol, period, start, end)
     }
 }
 
@@ -1,32 +1,90 @@
 use hyper::Method;
 use futures::prelude::*;
 use std::collections::HashMap;
-use failure::Fail;
+use std::sync::{Arc, RwLock};
+use failure::{Fail, bail};
 use serde_derive::Deserialize;
-use log::error;
+use log::{warn, error};
 use crate::Side;
-use crate::tick::Tick;
+use crate::tick::{Tick, TickUnit, ConversionError};
 use crate::api::{
     self,
     OrderType,
     TimeInForce,
     Order,
     OrderAck,
+    OneCancelsOther,
+    OcoAck,
     Cancel,
     CancelAck,
+    Replace,
+    OrderState,
+    OrderStatus,
+    Fill,
+    ExchangeStatus,
+    Trade,
 };
+use crate::api::fees::Liquidity;
+use crate::api::wallet::{DustConversion, DustTransfer};
+use crate::api::derivatives::{Position, FundingRate, OpenInterest};
 use crate::api::query_string::QueryString;
 use crate::api::errors::ErrorKinded;
-use crate::api::symbol::{Symbol, WithSymbol};
-use crate::api::binance::Client;
+use crate::api::symbol::{Symbol, WithSymbol, IntoWithSymbol};
+use crate::api::binance::{Client, Keys, Market};
 use crate::api::binance::errors::RestError;
-use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::Bar;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct BinanceOrderAck<'a> {
     clientOrderId: &'a str,
-    transactTime: u64,
+
+    /// Spot only.
+    transactTime: Option<u64>,
+
+    /// Futures only.
+    updateTime: Option<u64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceOrder<'a> {
+    clientOrderId: &'a str,
+    status: &'a str,
+    executedQty: &'a str,
+
+    /// Spot only.
+    cummulativeQuoteQty: Option<&'a str>,
+
+    /// Futures only.
+    avgPrice: Option<&'a str>,
+
+    updateTime: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceCancelReplaceAck<'a> {
+    #[serde(borrow)]
+    newOrderResponse: BinanceOrderAck<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceOcoReport<'a> {
+    clientOrderId: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceOcoAck<'a> {
+    listClientOrderId: &'a str,
+    transactionTime: u64,
+    #[serde(borrow)]
+    orderReports: Vec<BinanceOcoReport<'a>>,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
@@ -42,12 +100,121 @@ struct BinanceAccountInformation<'a> {
     balances: Vec<BinanceBalance<'a>>,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceFuturesBalance<'a> {
+    asset: &'a str,
+    balance: &'a str,
+    availableBalance: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceFundingRate<'a> {
+    symbol: &'a str,
+    fundingRate: &'a str,
+    fundingTime: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceOpenInterest<'a> {
+    symbol: &'a str,
+    sumOpenInterest: &'a str,
+    sumOpenInterestValue: &'a str,
+    timestamp: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinancePosition<'a> {
+    symbol: &'a str,
+    positionAmt: &'a str,
+    entryPrice: &'a str,
+    unRealizedProfit: &'a str,
+    leverage: &'a str,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct BinanceListenKey<'a> {
     listenKey: &'a str,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceDustTransfer<'a> {
+    fromAsset: &'a str,
+    amount: &'a str,
+    transferedAmount: &'a str,
+    serviceChargeAmount: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceDustConversion<'a> {
+    totalTransfered: &'a str,
+    totalServiceCharge: &'a str,
+    #[serde(borrow)]
+    transferResult: Vec<BinanceDustTransfer<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceTrade<'a> {
+    price: &'a str,
+    qty: &'a str,
+    time: u64,
+    isBuyerMaker: bool,
+}
+
+/// Open time, open, high, low, close, volume, close time, quote volume, number of
+/// trades, taker buy volume, taker buy quote volume, unused field.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceAccountTrade<'a> {
+    id: u64,
+    orderId: u64,
+    price: &'a str,
+    qty: &'a str,
+    commission: &'a str,
+    commissionAsset: &'a str,
+    time: u64,
+
+    /// Spot only.
+    isBuyer: Option<bool>,
+
+    /// Spot only.
+    isMaker: Option<bool>,
+
+    /// Futures only.
+    buyer: Option<bool>,
+
+    /// Futures only.
+    maker: Option<bool>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BinanceKline<'a>(
+    u64,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    u64,
+    &'a str,
+    u64,
+    &'a str,
+    &'a str,
+    &'a str,
+);
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BinanceSystemStatus {
+    status: u32,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 #[allow(non_snake_case)]
 #[allow(non_camel_case_types)]
@@ -56,8 +223,10 @@ enum BinanceFilter<'a> {
     PRICE_FILTER { tickSize: &'a str },
     LOT_SIZE { stepSize: &'a str },
     MIN_NOTIONAL,
-    ICEBERG_PARTS,
+    ICEBERG_PARTS { limit: u64 },
     MAX_NUM_ALGO_ORDERS,
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
@@ -73,6 +242,104 @@ struct BinanceExchangeInfo<'a> {
     symbols: Vec<BinanceSymbol<'a>>,
 }
 
+/// Ticks per unit used for futures balances, which are sent with 8 decimals.
+const AMOUNT_TICK: TickUnit = 100_000_000;
+
+/// Maximum number of entries returned by `api/v3/trades` and `api/v3/klines`.
+const HISTORY_LIMIT: usize = 1000;
+
+/// Maximum number of entries returned by `fapi/v1/fundingRate`.
+const FUNDING_RATE_LIMIT: u64 = 1000;
+
+/// Maximum number of entries returned by `futures/data/openInterestHist`.
+const OPEN_INTEREST_LIMIT: u64 = 500;
+
+/// Name of the open interest period lasting `period` milliseconds, if supported.
+fn open_interest_period(period: Timestamp) -> Option<&'static str> {
+    const MINUTE: Timestamp = 60 * 1000;
+    const HOUR: Timestamp = 60 * MINUTE;
+
+    let name = match period {
+        p if p == 5 * MINUTE => "5m",
+        p if p == 15 * MINUTE => "15m",
+        p if p == 30 * MINUTE => "30m",
+        p if p == HOUR => "1h",
+        p if p == 2 * HOUR => "2h",
+        p if p == 4 * HOUR => "4h",
+        p if p == 6 * HOUR => "6h",
+        p if p == 12 * HOUR => "12h",
+        p if p == 24 * HOUR => "1d",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Name of the kline interval lasting `interval` milliseconds, if supported.
+fn kline_interval(interval: Timestamp) -> Option<&'static str> {
+    const MINUTE: Timestamp = 60 * 1000;
+    const HOUR: Timestamp = 60 * MINUTE;
+    const DAY: Timestamp = 24 * HOUR;
+
+    let name = match interval {
+        i if i == MINUTE => "1m",
+        i if i == 3 * MINUTE => "3m",
+        i if i == 5 * MINUTE => "5m",
+        i if i == 15 * MINUTE => "15m",
+        i if i == 30 * MINUTE => "30m",
+        i if i == HOUR => "1h",
+        i if i == 2 * HOUR => "2h",
+        i if i == 4 * HOUR => "4h",
+        i if i == 6 * HOUR => "6h",
+        i if i == 8 * HOUR => "8h",
+        i if i == 12 * HOUR => "12h",
+        i if i == DAY => "1d",
+        i if i == 3 * DAY => "3d",
+        i if i == 7 * DAY => "1w",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn convert_order_status(symbol: Symbol, order: BinanceOrder<'_>)
+    -> Result<Timestamped<OrderStatus>, failure::Error>
+{
+    let state = match order.status {
+        "NEW" | "PENDING_NEW" => OrderState::New,
+        "PARTIALLY_FILLED" => OrderState::PartiallyFilled,
+        "FILLED" => OrderState::Filled,
+        "CANCELED" | "PENDING_CANCEL" => OrderState::Canceled,
+        "REJECTED" => OrderState::Rejected,
+        "EXPIRED" | "EXPIRED_IN_MATCH" => OrderState::Expired,
+        other => bail!("wrong order status: `{}`", other),
+    };
+
+    let filled_size = symbol.size_tick().ticked(order.executedQty)?;
+    let average_price = if filled_size == 0 {
+        None
+    } else {
+        let average_price = match (order.cummulativeQuoteQty, order.avgPrice) {
+            (Some(quote), _) => quote.parse::<f64>()? / order.executedQty.parse::<f64>()?,
+            (None, Some(average_price)) => average_price.parse()?,
+            (None, None) => bail!("missing average price"),
+        };
+        Some(symbol.price_tick().rounded(average_price))
+    };
+
+    Ok(OrderStatus {
+        order_id: order.clientOrderId.to_owned(),
+        state,
+        filled_size,
+        average_price,
+        // Only reported with the fills of the order.
+        commission: None,
+    }.with_timestamp(order.updateTime))
+}
+
+/// Return `true` if `amount`, unticked and possibly negative, is zero.
+fn is_zero(amount: &str) -> bool {
+    amount.trim_start_matches('-').chars().all(|c| c == '0' || c == '.')
+}
+
 trait AsStr {
     fn as_str(self) -> &'static str;
 }
@@ -91,6 +358,9 @@ impl AsStr for OrderType {
         match self {
             OrderType::Limit => "LIMIT",
             OrderType::LimitMaker => "LIMIT_MAKER",
+            OrderType::Market => "MARKET",
+            OrderType::StopLimit => "STOP_LOSS_LIMIT",
+            OrderType::StopMarket => "STOP_LOSS",
         }
     }
 }
@@ -101,6 +371,174 @@ impl AsStr for TimeInForce {
             TimeInForce::GoodTilCanceled => "GTC",
             TimeInForce::FillOrKilll => "FOK",
             TimeInForce::ImmediateOrCancel => "IOC",
+            TimeInForce::GoodTilDate(_) => "GTD",
+        }
+    }
+}
+
+impl Market {
+    fn order_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/order",
+            Market::UsdMargined => "fapi/v1/order",
+        }
+    }
+
+    fn listen_key_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/userDataStream",
+            Market::UsdMargined => "fapi/v1/listenKey",
+        }
+    }
+
+    /// Path of the symbols description, also polled for the trading status of the streamed
+    /// symbol, see `wss::HandlerImpl::on_poll`.
+    crate fn exchange_info_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/exchangeInfo",
+            Market::UsdMargined => "fapi/v1/exchangeInfo",
+        }
+    }
+
+    fn trades_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/trades",
+            Market::UsdMargined => "fapi/v1/trades",
+        }
+    }
+
+    fn my_trades_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/myTrades",
+            Market::UsdMargined => "fapi/v1/userTrades",
+        }
+    }
+
+    fn klines_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/klines",
+            Market::UsdMargined => "fapi/v1/klines",
+        }
+    }
+
+    /// Path of the order book snapshot, see `wss::HandlerImpl::request_book_snapshot`.
+    crate fn depth_path(self) -> &'static str {
+        match self {
+            Market::Spot => "api/v3/depth",
+            Market::UsdMargined => "fapi/v1/depth",
+        }
+    }
+}
+
+type HttpClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+/// Send a request to `rest_endpoint`, signed with `keys`, which are kept alive until the
+/// request completes.
+fn send_request<K: api::errors::ErrorKind>(
+    http_client: &HttpClient,
+    rest_endpoint: &str,
+    keys: Option<Arc<Keys>>,
+    path: &str,
+    method: Method,
+    query: QueryString
+) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+        where RestError: ErrorKinded<K>
+{
+    use hyper::Request;
+
+    let mut request = Request::builder();
+
+    let query = match keys.as_ref() {
+        None => query.into_string(),
+        Some(keys) => {
+            request.header("X-MBX-APIKEY", keys.api_key.as_bytes());
+            query.into_string_with_signature(&keys.secret_key)
+        }
+    };
+
+    let address = format!(
+        "{}/{}",
+        rest_endpoint,
+        path,
+    );
+
+    request.method(method)
+        .header("User-Agent", &b"hyper"[..])
+        .header("Content-Type", &b"application/x-www-form-urlencoded"[..])
+        .uri(&address);
+
+    // Unwrap because it is a bug if this fails (header failed to parse or something)
+    let request = request.body(query.into()).unwrap();
+    http_client.request(request).and_then(|res| {
+        let status = res.status();
+        res.into_body().concat2().and_then(move |body| {
+            Ok((status, body))
+        })
+    })
+    .map_err(api::errors::RequestError::new)
+    .map_err(api::errors::ApiError::RequestError)
+    .and_then(|(status, body)| {
+        if status != hyper::StatusCode::OK {
+            let binance_error = serde_json::from_slice(&body);
+            let error = RestError::from_binance_error(status, binance_error.ok());
+            let kind = error.kind();
+            Err(
+                api::errors::ApiError::RestError(error.context(kind).into())
+            )?;
+        }
+        Ok(body)
+    })
+    .then(move |result| {
+        drop(keys);
+        result
+    })
+}
+
+/// The cancel of a good til date order on spot, sent once its date is reached.
+struct Expiry {
+    http_client: HttpClient,
+    rest_endpoint: String,
+
+    /// Read when the cancel is sent, so that rotated keys are used.
+    keys: Arc<RwLock<Option<Arc<Keys>>>>,
+    symbol: String,
+    expiration: Timestamp,
+}
+
+impl Expiry {
+    /// Schedule the cancel of `order_id` on the current tokio executor. The user stream
+    /// then notifies the cancel as an `OrderExpiration`.
+    fn schedule(self, order_id: String) {
+        use std::time::{Duration, Instant};
+        use tokio::executor::Executor;
+
+        let Expiry { http_client, rest_endpoint, keys, symbol, expiration } = self;
+        let delay = Duration::from_millis(expiration.saturating_sub(timestamp_ms()));
+
+        let fut = tokio::timer::Delay::new(Instant::now() + delay)
+            .map_err(|err| error!("expiration timer encountered error: `{}`", err))
+            .and_then(move |()| {
+                let mut query = QueryString::new();
+                query.push_str("symbol", &symbol);
+                query.push_str("origClientOrderId", &order_id);
+                query.push("timestamp", timestamp_ms());
+
+                let keys = keys.read().unwrap().clone();
+                send_request::<api::errors::CancelErrorKind>(
+                    &http_client,
+                    &rest_endpoint,
+                    keys,
+                    Market::Spot.order_path(),
+                    Method::DELETE,
+                    query
+                )
+                .map(|_| ())
+                // The order may have been filled or canceled in the meantime.
+                .map_err(move |err| warn!("cannot expire order `{}`: `{}`", order_id, err))
+            });
+
+        if let Err(err) = tokio::executor::DefaultExecutor::current().spawn(Box::new(fut)) {
+            error!("cannot schedule the expiration of an order: `{}`", err);
         }
     }
 }
@@ -114,89 +552,321 @@ impl Client {
     ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
             where RestError: ErrorKinded<K>
     {
-        use hyper::Request;
-
-        let mut request = Request::builder();
+        self.request_with_keys(self.keys(), path, method, query)
+    }
 
-        let query = match self.keys.as_ref() {
-            None => query.into_string(),
-            Some(keys) => {
-                request.header("X-MBX-APIKEY", keys.api_key.as_bytes());
-                query.into_string_with_signature(&keys.secret_key)
-            }
-        };
+    /// Sign the request with `keys`, which are kept alive until the request completes.
+    fn request_with_keys<K: api::errors::ErrorKind>(
+        &self,
+        keys: Option<Arc<Keys>>,
+        path: &str,
+        method: Method,
+        query: QueryString
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        send_request(&self.http_client, &self.params.rest_endpoint, keys, path, method, query)
+    }
 
-        let address = format!(
-            "{}/{}",
-            self.params.rest_endpoint,
-            path,
-        );
+    /// Return the expiry of `order` if it is to be canceled by the client, i.e. if it is
+    /// a good til date order on spot, which does not support them.
+    fn expiry(&self, order: WithSymbol<&Order>) -> Option<Expiry> {
+        let expiration = order.time_in_force.expiration()?;
+        if self.market != Market::Spot || order.type_ == OrderType::Market {
+            return None;
+        }
 
-        request.method(method)
-            .header("User-Agent", &b"hyper"[..])
-            .header("Content-Type", &b"application/x-www-form-urlencoded"[..])
-            .uri(&address);
-
-        // Unwrap because it is a bug if this fails (header failed to parse or something)
-        let request = request.body(query.into()).unwrap();
-        self.http_client.request(request).and_then(|res| {
-            let status = res.status();
-            res.into_body().concat2().and_then(move |body| {
-                Ok((status, body))
-            })
-        })
-        .map_err(api::errors::RequestError::new)
-        .map_err(api::errors::ApiError::RequestError)
-        .and_then(|(status, body)| {
-            if status != hyper::StatusCode::OK {
-                let binance_error = serde_json::from_slice(&body);
-                let error = RestError::from_binance_error(status, binance_error.ok());
-                let kind = error.kind();
-                Err(
-                    api::errors::ApiError::RestError(error.context(kind).into())
-                )?;
-            }
-            Ok(body)
+        Some(Expiry {
+            http_client: self.http_client.clone(),
+            rest_endpoint: self.params.rest_endpoint.clone(),
+            keys: self.keys.clone(),
+            symbol: order.symbol().name().to_owned(),
+            expiration,
         })
     }
 
-    crate fn order_impl(&self, order: WithSymbol<&Order>)
-        -> impl Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static
-    {
+    /// Return the parameters of `order`, or `None` if it is not supported.
+    fn order_query(&self, order: WithSymbol<&Order>) -> Option<QueryString> {
         use std::borrow::Borrow;
 
+        if order.quote_size && order.type_ != OrderType::Market {
+            warn!("called `order` with a quote size, which is only supported by market orders");
+            return None;
+        }
+
+        if order.type_.is_stop() && order.trigger_price.is_none() && order.trailing_delta.is_none() {
+            warn!("called `order` with a stop order but neither a trigger price nor a trailing delta");
+            return None;
+        }
+
         let mut query = QueryString::new();
         let symbol = order.symbol();
         query.push_str("symbol", symbol.name());
         query.push_str("side", order.side.as_str());
-        query.push_str("type", order.type_.as_str());
-        if order.type_ == OrderType::Limit {
-            query.push("timeInForce", order.time_in_force.as_str());
+        match self.market {
+            Market::Spot => {
+                if order.reduce_only {
+                    warn!("called `order` with reduce only, which is not supported on spot");
+                    return None;
+                }
+
+                // Trailing stops are stop orders with a trailing delta.
+                let type_ = match (order.type_, order.trailing_delta) {
+                    (OrderType::Market, Some(_)) | (OrderType::StopMarket, _) => {
+                        OrderType::StopMarket
+                    }
+                    (_, Some(_)) => OrderType::StopLimit,
+                    (type_, None) => type_,
+                };
+                query.push_str("type", type_.as_str());
+                if type_ == OrderType::Limit || type_ == OrderType::StopLimit {
+                    // Good til date orders are canceled by the client, see `Expiry`.
+                    let time_in_force = match order.time_in_force {
+                        TimeInForce::GoodTilDate(_) => TimeInForce::GoodTilCanceled,
+                        time_in_force => time_in_force,
+                    };
+                    query.push("timeInForce", time_in_force.as_str());
+                }
+                if let Some(trailing_delta) = order.trailing_delta {
+                    query.push("trailingDelta", trailing_delta);
+                }
+
+                if let Some(display_size) = &order.display_size {
+                    if !order.valid_display_size(symbol) {
+                        warn!("called `order` with a display size not complying with the iceberg rules of `{}`", symbol.name());
+                        return None;
+                    }
+                    query.push_str(
+                        "icebergQty",
+                        display_size.unticked(symbol.size_tick()).borrow() as &str
+                    );
+                }
+            }
+
+            Market::UsdMargined => {
+                if order.quote_size {
+                    warn!("called `order` with a quote size, which is not supported on futures");
+                    return None;
+                }
+
+                // Trailing stops are market orders, with a callback rate in percent.
+                if let Some(trailing_delta) = order.trailing_delta {
+                    let valid = order.type_ == OrderType::Market &&
+                        trailing_delta % 10 == 0 && trailing_delta >= 10;
+                    if !valid || trailing_delta > 500 {
+                        warn!("called `order` with a trailing delta of {} bps on a {:?} order, futures only support trailing market orders with a delta multiple of 10 bps between 10 and 500 bps", trailing_delta, order.type_);
+                        return None;
+                    }
+                }
+
+                if order.display_size.is_some() {
+                    warn!("called `order` with a display size, which is not supported on futures");
+                    return None;
+                }
+
+                // Post only orders are limit orders with a `GTX` time in force.
+                let (type_, time_in_force) = match (order.type_, order.time_in_force) {
+                    (OrderType::Market, _) if order.trailing_delta.is_some() => {
+                        ("TRAILING_STOP_MARKET", None)
+                    }
+                    (OrderType::Market, _) => ("MARKET", None),
+                    (OrderType::StopMarket, _) => ("STOP_MARKET", None),
+                    (OrderType::Limit, time_in_force) => ("LIMIT", Some(time_in_force.as_str())),
+                    (OrderType::StopLimit, time_in_force) => ("STOP", Some(time_in_force.as_str())),
+                    (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => ("LIMIT", Some("GTX")),
+                    (OrderType::LimitMaker, time_in_force) => {
+                        warn!(
+                            "called `order` with `LimitMaker` and `{:?}`, which is not supported on futures",
+                            time_in_force
+                        );
+                        return None;
+                    }
+                };
+                query.push_str("type", type_);
+                if let Some(time_in_force) = time_in_force {
+                    query.push_str("timeInForce", time_in_force);
+                }
+                match (type_, order.time_in_force) {
+                    ("LIMIT", TimeInForce::GoodTilDate(expiration))
+                        | ("STOP", TimeInForce::GoodTilDate(expiration)) => {
+                        query.push("goodTillDate", expiration);
+                    }
+                    _ => (),
+                }
+                if order.reduce_only {
+                    query.push_str("reduceOnly", "true");
+                }
+                if let Some(trailing_delta) = order.trailing_delta {
+                    let callback_rate = format!("{}.{}", trailing_delta / 100, trailing_delta % 100 / 10);
+                    query.push_str("callbackRate", &callback_rate);
+                    if let Some(trigger_price) = &order.trigger_price {
+                        query.push_str(
+                            "activationPrice",
+                            trigger_price.unticked(symbol.price_tick()).borrow() as &str
+                        );
+                    }
+                }
+            }
+        }
+        if order.quote_size {
+            query.push_str(
+                "quoteOrderQty",
+                order.size.unticked(symbol.price_tick()).borrow() as &str
+            );
+        } else {
+            query.push_str(
+                "quantity",
+                order.size.unticked(symbol.size_tick()).borrow() as &str
+            );
+        }
+        if order.type_ != OrderType::Market && order.type_ != OrderType::StopMarket {
+            query.push_str(
+                "price",
+                order.price.unticked(symbol.price_tick()).borrow() as &str
+            );
+        }
+        match &order.trigger_price {
+            Some(trigger_price) if order.type_.is_stop() => {
+                query.push_str(
+                    "stopPrice",
+                    trigger_price.unticked(symbol.price_tick()).borrow() as &str
+                );
+            }
+            _ => (),
         }
-        query.push_str(
-            "quantity",
-            order.size.unticked(symbol.size_tick()).borrow() as &str
-        );
-        query.push_str(
-            "price",
-            order.price.unticked(symbol.price_tick()).borrow() as &str
-        );
         if let Some(order_id) = &order.order_id {
             query.push_str("newClientOrderId", order_id);
         }
         query.push("recvWindow", order.time_window);
         query.push("timestamp", timestamp_ms());
+        Some(query)
+    }
 
-        self.request("api/v3/order", Method::POST, query)
-            .and_then(|body|
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        let expiry = self.expiry(order.clone());
+        let query = match self.order_query(order) {
+            Some(query) => query,
+            None => return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            )),
+        };
+
+        let fut = self.request(self.market.order_path(), Method::POST, query)
+            .and_then(move |body|
         {
             let ack: BinanceOrderAck<'_> = serde_json::from_slice(&body)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
+            if let Some(expiry) = expiry {
+                expiry.schedule(ack.clientOrderId.to_owned());
+            }
+            let timestamp = ack.transactTime
+                .or(ack.updateTime)
+                .unwrap_or_else(timestamp_ms);
             Ok(OrderAck {
                 order_id: ack.clientOrderId.to_owned(),
-            }.with_timestamp(ack.transactTime))
-        })
+            }.with_timestamp(timestamp))
+        });
+        Box::new(fut)
+    }
+
+    crate fn oco_impl(&self, oco: WithSymbol<&OneCancelsOther>)
+        -> Box<dyn Future<Item = Timestamped<OcoAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        let (limit, stop) = (oco.limit(), oco.stop());
+        let valid = limit.side == stop.side && limit.size == stop.size &&
+            (limit.type_ == OrderType::Limit || limit.type_ == OrderType::LimitMaker) &&
+            stop.type_.is_stop() && stop.trigger_price.is_some();
+        if !valid {
+            warn!("called `oco` with orders which cannot form an OCO, see `OneCancelsOther`");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let unsupported = [limit, stop].iter().any(|order| {
+            order.quote_size || order.reduce_only || order.trailing_delta.is_some() ||
+                order.display_size.is_some() || order.time_in_force.expiration().is_some()
+        });
+        if unsupported {
+            warn!("called `oco` with a quote size, reduce only, a trailing delta, a display size or a good til date, which are not supported by OCO orders");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let mut query = QueryString::new();
+        let symbol = oco.symbol();
+        query.push_str("symbol", symbol.name());
+        query.push_str("side", limit.side.as_str());
+        query.push_str(
+            "quantity",
+            limit.size.unticked(symbol.size_tick()).borrow() as &str
+        );
+        query.push_str(
+            "price",
+            limit.price.unticked(symbol.price_tick()).borrow() as &str
+        );
+        if let Some(trigger_price) = &stop.trigger_price {
+            query.push_str(
+                "stopPrice",
+                trigger_price.unticked(symbol.price_tick()).borrow() as &str
+            );
+        }
+
+        // Without a limit price, the stop order is a `STOP_LOSS` one.
+        if stop.type_ == OrderType::StopLimit {
+            query.push_str(
+                "stopLimitPrice",
+                stop.price.unticked(symbol.price_tick()).borrow() as &str
+            );
+            query.push_str("stopLimitTimeInForce", stop.time_in_force.as_str());
+        }
+        if let Some(list_id) = oco.list_id() {
+            query.push_str("listClientOrderId", list_id);
+        }
+        if let Some(order_id) = &limit.order_id {
+            query.push_str("limitClientOrderId", order_id);
+        }
+        if let Some(order_id) = &stop.order_id {
+            query.push_str("stopClientOrderId", order_id);
+        }
+        query.push("recvWindow", limit.time_window);
+        query.push("timestamp", timestamp_ms());
+
+        let fut = self.request("api/v3/order/oco", Method::POST, query).and_then(|body| {
+            let ack: BinanceOcoAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut limit = None;
+            let mut stop = None;
+            for report in &ack.orderReports {
+                let order_ack = OrderAck {
+                    order_id: report.clientOrderId.to_owned(),
+                };
+                match report.type_ {
+                    "LIMIT_MAKER" => limit = Some(order_ack),
+                    _ => stop = Some(order_ack),
+                }
+            }
+
+            match (limit, stop) {
+                (Some(limit), Some(stop)) => Ok(OcoAck {
+                    list_id: ack.listClientOrderId.to_owned(),
+                    limit,
+                    stop,
+                }.with_timestamp(ack.transactionTime)),
+                _ => Err(api::errors::ApiError::RequestError(
+                    api::errors::RequestError::new(failure::err_msg("missing OCO order report").compat())
+                )),
+            }
+        });
+        Box::new(fut)
     }
 
     crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
@@ -209,17 +879,70 @@ impl Client {
         query.push("recvWindow", cancel.time_window);
         query.push("timestamp", timestamp_ms());
 
-        self.request("api/v3/order", Method::DELETE, query).and_then(|_| {
+        self.request(self.market.order_path(), Method::DELETE, query).and_then(|_| {
             Ok(CancelAck.timestamped())
         })
     }
 
-    crate fn get_listen_key(&self)
+    /// Only available on spot, the new order being sent only if the cancel succeeds.
+    crate fn replace_impl(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::ReplaceError> + Send + 'static>
+    {
+        let symbol = replace.symbol();
+        let mut query = match self.order_query(replace.order().with_symbol(symbol)) {
+            Some(query) => query,
+            None => return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            )),
+        };
+        query.push_str("cancelReplaceMode", "STOP_ON_FAILURE");
+        query.push_str("cancelOrigClientOrderId", replace.cancel().order_id());
+
+        let expiry = self.expiry(replace.order().with_symbol(symbol));
+        let fut = self.request("api/v3/order/cancelReplace", Method::POST, query)
+            .and_then(move |body|
+        {
+            let ack: BinanceCancelReplaceAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ReplaceError::RequestError)?;
+            let ack = ack.newOrderResponse;
+            if let Some(expiry) = expiry {
+                expiry.schedule(ack.clientOrderId.to_owned());
+            }
+            Ok(OrderAck {
+                order_id: ack.clientOrderId.to_owned(),
+            }.with_timestamp(ack.transactTime.unwrap_or_else(timestamp_ms)))
+        });
+        Box::new(fut)
+    }
+
+    crate fn order_status_impl(&self, symbol: Symbol, order_id: &str)
+        -> impl Future<Item = Timestamped<OrderStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push_str("origClientOrderId", order_id);
+        query.push("timestamp", timestamp_ms());
+
+        self.request(self.market.order_path(), Method::GET, query).and_then(move |body| {
+            let order: BinanceOrder<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = convert_order_status(symbol, order)
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(status)
+        })
+    }
+
+    crate fn get_listen_key(&self, keys: Arc<Keys>)
         -> impl Future<Item = String, Error = api::errors::Error> + Send + 'static
     {
         let query = QueryString::new();
+        let path = self.market.listen_key_path();
 
-        self.request("api/v1/userDataStream", Method::POST, query).and_then(|body| {
+        self.request_with_keys(Some(keys), path, Method::POST, query).and_then(|body| {
             let key: BinanceListenKey<'_> = serde_json::from_slice(&body)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
@@ -227,14 +950,26 @@ impl Client {
         })
     }
 
+    /// Close `listen_key`, which was requested with `keys`.
+    crate fn close_listen_key(&self, keys: Arc<Keys>, listen_key: &str)
+        -> impl Future<Item = (), Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("listenKey", listen_key);
+        let path = self.market.listen_key_path();
+
+        self.request_with_keys(Some(keys), path, Method::DELETE, query).map(|_| ())
+    }
+
     crate fn ping_impl(&self)
         -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
     {
-        if let Some(listen_key) = self.keys.as_ref().map(|keys| &keys.listen_key) {
+        let listen_key = self.listen_key.read().unwrap().clone();
+        if let Some(listen_key) = listen_key {
             let mut query = QueryString::new();
-            query.push_str("listenKey", listen_key);
+            query.push_str("listenKey", &listen_key);
 
-            let fut = self.request("api/v1/userDataStream", Method::PUT, query)
+            let fut = self.request(self.market.listen_key_path(), Method::PUT, query)
                 .and_then(|_| Ok(().timestamped()));
             Box::new(fut)
         } else {
@@ -243,13 +978,17 @@ impl Client {
     }
 
     crate fn balances_impl(&self)
-        -> impl Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static
+        -> Box<dyn Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static>
     {
         let mut query = QueryString::new();
         query.push("recvWindow", 5000);
         query.push("timestamp", timestamp_ms());
 
-        self.request("api/v3/account", Method::GET, query).and_then(|body| {
+        if self.market == Market::UsdMargined {
+            return Box::new(self.futures_balances_impl(query));
+        }
+
+        let fut = self.request("api/v3/account", Method::GET, query).and_then(|body| {
             let info: BinanceAccountInformation<'_> = serde_json::from_slice(&body)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
@@ -261,15 +1000,339 @@ impl Client {
                 })
             }).collect();
             Ok(balances)
+        });
+        Box::new(fut)
+    }
+
+    /// The wallet balance of each margin asset includes the margin of the open orders
+    /// and positions, which is reported as locked.
+    fn futures_balances_impl(&self, query: QueryString)
+        -> impl Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("fapi/v2/balance", Method::GET, query).and_then(|body| {
+            let binance_balances: Vec<BinanceFuturesBalance<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let tick = Tick::new(AMOUNT_TICK);
+            let mut balances = api::Balances::new();
+            for balance in binance_balances {
+                let locked = tick.ticked(balance.balance)
+                    .and_then(|total| {
+                        let available = tick.ticked(balance.availableBalance)?;
+                        tick.unticked(total.saturating_sub(available))
+                    })
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                balances.insert(balance.asset.to_owned(), api::Balance {
+                    free: balance.availableBalance.to_owned(),
+                    locked,
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn set_leverage_impl(&self, symbol: &Symbol, leverage: u32)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push("leverage", leverage);
+        query.push("recvWindow", 5000);
+        query.push("timestamp", timestamp_ms());
+
+        self.request("fapi/v1/leverage", Method::POST, query).and_then(|_| {
+            Ok(().timestamped())
+        })
+    }
+
+    crate fn positions_impl(&self)
+        -> impl Future<Item = Vec<Position>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push("recvWindow", 5000);
+        query.push("timestamp", timestamp_ms());
+
+        self.request("fapi/v2/positionRisk", Method::GET, query).and_then(|body| {
+            let binance_positions: Vec<BinancePosition<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut positions = Vec::new();
+            for position in binance_positions {
+                // Every symbol is listed, including those without any position.
+                if is_zero(position.positionAmt) {
+                    continue;
+                }
+
+                // The position amount is negative for short positions.
+                let (side, size) = if position.positionAmt.starts_with('-') {
+                    (Side::Ask, &position.positionAmt[1..])
+                } else {
+                    (Side::Bid, position.positionAmt)
+                };
+
+                positions.push(Position {
+                    symbol: position.symbol.to_owned(),
+                    side,
+                    size: size.to_owned(),
+                    entry_price: position.entryPrice.to_owned(),
+                    unrealized_pnl: position.unRealizedProfit.to_owned(),
+                    leverage: position.leverage.parse()
+                        .map_err(api::errors::RequestError::new)
+                        .map_err(api::errors::ApiError::RequestError)?,
+                });
+            }
+            Ok(positions)
+        })
+    }
+
+    crate fn convert_dust_impl(&self, assets: &[&str])
+        -> impl Future<Item = Timestamped<DustConversion>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        for asset in assets {
+            query.push_str("asset", asset);
+        }
+        query.push("recvWindow", 5000);
+        query.push("timestamp", timestamp_ms());
+
+        self.request("sapi/v1/asset/dust", Method::POST, query).and_then(|body| {
+            let conversion: BinanceDustConversion<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let transfers = conversion.transferResult.into_iter().map(|transfer| {
+                DustTransfer {
+                    from_asset: transfer.fromAsset.to_owned(),
+                    amount: transfer.amount.to_owned(),
+                    transferred_amount: transfer.transferedAmount.to_owned(),
+                    service_charge: transfer.serviceChargeAmount.to_owned(),
+                }
+            }).collect();
+
+            Ok(DustConversion {
+                total_transferred: conversion.totalTransfered.to_owned(),
+                total_service_charge: conversion.totalServiceCharge.to_owned(),
+                transfers,
+            }.timestamped())
+        })
+    }
+
+    crate fn funding_rates_impl(&self, symbol: &Symbol, start: Timestamp, end: Timestamp)
+        -> impl Future<Item = Vec<Timestamped<FundingRate>>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push("startTime", start);
+        query.push("endTime", end);
+        query.push("limit", FUNDING_RATE_LIMIT);
+
+        self.request("fapi/v1/fundingRate", Method::GET, query).and_then(|body| {
+            let binance_rates: Vec<BinanceFundingRate<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let rates = binance_rates.into_iter().map(|rate| {
+                FundingRate {
+                    symbol: rate.symbol.to_owned(),
+                    rate: rate.fundingRate.to_owned(),
+                }.with_timestamp(rate.fundingTime)
+            }).collect();
+            Ok(rates)
         })
     }
 
+    crate fn open_interest_impl(
+        &self,
+        symbol: &Symbol,
+        period: Timestamp,
+        start: Timestamp,
+        end: Timestamp
+    ) -> Box<dyn Future<Item = Vec<Timestamped<OpenInterest>>, Error = api::errors::Error> + Send + 'static>
+    {
+        let period_name = match open_interest_period(period) {
+            Some(name) => name,
+            None => {
+                warn!("called `open_interest` with an unsupported period of {} ms", period);
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::InvalidRequest.into()
+                    )
+                ));
+            }
+        };
+
+        // Binance returns the latest entries of the range when it does not fit in a
+        // single response, hence we shrink the range so that entries come from `start`.
+        let end = std::cmp::min(
+            end,
+            start.saturating_add(period * OPEN_INTEREST_LIMIT).saturating_sub(1)
+        );
+
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push_str("period", period_name);
+        query.push("startTime", start);
+        query.push("endTime", end);
+        query.push("limit", OPEN_INTEREST_LIMIT);
+
+        let fut = self.request("futures/data/openInterestHist", Method::GET, query).and_then(|body| {
+            let binance_interests: Vec<BinanceOpenInterest<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut interests: Vec<_> = binance_interests.into_iter().map(|interest| {
+                OpenInterest {
+                    symbol: interest.symbol.to_owned(),
+                    open_interest: interest.sumOpenInterest.to_owned(),
+                    value: Some(interest.sumOpenInterestValue.to_owned()),
+                }.with_timestamp(interest.timestamp)
+            }).collect();
+            interests.sort_by_key(|interest| interest.timestamp());
+            Ok(interests)
+        });
+        Box::new(fut)
+    }
+
+    crate fn recent_trades_impl(&self, symbol: Symbol, limit: usize)
+        -> impl Future<Item = Vec<Timestamped<Trade>>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push("limit", std::cmp::min(limit, HISTORY_LIMIT));
+
+        self.request(self.market.trades_path(), Method::GET, query).and_then(move |body| {
+            let binance_trades: Vec<BinanceTrade<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let trades = binance_trades.into_iter().map(|trade| {
+                Ok(Trade {
+                    price: symbol.price_tick().ticked(trade.price)?,
+                    size: symbol.size_tick().ticked(trade.qty)?,
+                    maker_side: if trade.isBuyerMaker { Side::Bid } else { Side::Ask },
+                }.with_timestamp(trade.time))
+            }).collect::<Result<_, ConversionError>>()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(trades)
+        })
+    }
+
+    crate fn my_trades_impl(&self, symbol: Symbol, since: Timestamp)
+        -> impl Future<Item = Vec<Timestamped<Fill>>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push("startTime", since);
+        query.push("limit", HISTORY_LIMIT);
+        query.push("timestamp", timestamp_ms());
+
+        self.request(self.market.my_trades_path(), Method::GET, query).and_then(move |body| {
+            let binance_trades: Vec<BinanceAccountTrade<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let fills = binance_trades.into_iter().map(|trade| {
+                let is_buyer = trade.isBuyer.or(trade.buyer).unwrap_or(false);
+                let is_maker = trade.isMaker.or(trade.maker).unwrap_or(false);
+                Ok(Fill {
+                    trade_id: trade.id.to_string(),
+                    order_id: trade.orderId.to_string(),
+                    side: if is_buyer { Side::Bid } else { Side::Ask },
+                    price: symbol.price_tick().ticked(trade.price)?,
+                    size: symbol.size_tick().ticked(trade.qty)?,
+                    fee: trade.commission.to_owned(),
+                    fee_asset: Some(trade.commissionAsset.to_owned()),
+                    liquidity: Some(if is_maker { Liquidity::Maker } else { Liquidity::Taker }),
+                }.with_timestamp(trade.time))
+            }).collect::<Result<_, ConversionError>>()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(fills)
+        })
+    }
+
+    crate fn recent_bars_impl(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = api::errors::Error> + Send + 'static>
+    {
+        let interval_name = match kline_interval(interval) {
+            Some(name) => name,
+            None => {
+                warn!("called `recent_bars` with an unsupported interval of {} ms", interval);
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::InvalidRequest.into()
+                    )
+                ));
+            }
+        };
+
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push_str("interval", interval_name);
+        query.push("limit", std::cmp::min(limit, HISTORY_LIMIT));
+
+        let fut = self.request(self.market.klines_path(), Method::GET, query).and_then(move |body| {
+            let klines: Vec<BinanceKline<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+            let bars = klines.into_iter().map(|kline| {
+                Ok(Bar {
+                    open: price_tick.ticked(kline.1)?,
+                    high: price_tick.ticked(kline.2)?,
+                    low: price_tick.ticked(kline.3)?,
+                    close: price_tick.ticked(kline.4)?,
+                    volume: size_tick.ticked(kline.5)?,
+                    count: kline.8,
+                }.with_timestamp(kline.0))
+            }).collect::<Result<_, ConversionError>>()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(bars)
+        });
+        Box::new(fut)
+    }
+
+    crate fn system_status_impl(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        let query = QueryString::new();
+
+        // The futures API has no status endpoint, being able to reach it is the best
+        // we can do.
+        if self.market == Market::UsdMargined {
+            let fut = self.request("fapi/v1/ping", Method::GET, query).and_then(|_| {
+                Ok(ExchangeStatus::Normal.timestamped())
+            });
+            return Box::new(fut);
+        }
+
+        let fut = self.request("sapi/v1/system/status", Method::GET, query).and_then(|body| {
+            let status: BinanceSystemStatus = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match status.status {
+                0 => ExchangeStatus::Normal,
+                _ => ExchangeStatus::Maintenance,
+            };
+            Ok(status.timestamped())
+        });
+        Box::new(fut)
+    }
+
     crate fn get_symbols(&self)
         -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
     {
         let query = QueryString::new();
 
-        self.request("api/v1/exchangeInfo", Method::GET, query).and_then(|body| {
+        self.request(self.market.exchange_info_path(), Method::GET, query).and_then(|body| {
             let info: BinanceExchangeInfo<'_> = serde_json::from_slice(&body)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
@@ -278,6 +1341,7 @@ impl Client {
             for symbol in info.symbols.into_iter() {
                 let mut price_tick = None;
                 let mut size_tick = None;
+                let mut iceberg_parts = None;
 
                 for filter in symbol.filters {
                     #[allow(non_snake_case)]
@@ -288,6 +1352,9 @@ impl Client {
                         BinanceFilter::LOT_SIZE { stepSize } => {
                             size_tick = Tick::tick_size(stepSize);
                         }
+                        BinanceFilter::ICEBERG_PARTS { limit } => {
+                            iceberg_parts = Some(limit);
+                        }
                         _ => (),
                     }
                 }
@@ -308,6 +1375,10 @@ impl Client {
                     size_tick.unwrap()
                 )
                 {
+                    let symbol = match iceberg_parts {
+                        Some(limit) => symbol.with_iceberg_parts(limit),
+                        None => symbol,
+                    };
                     symbols.insert(symbol.name().to_lowercase(), symbol);
                 } else {
                     error!("symbol name too long: `{}`", symbol.symbol);
@@ -1,12 +1,14 @@
 use std::{mem, thread};
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, RwLock};
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use futures::prelude::*;
-use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use futures::sync::mpsc::unbounded;
 use log::{error, debug};
 use failure::bail;
-use serde_derive::Deserialize;
+use serde_derive::{Serialize, Deserialize};
 use crate::{tick, Side};
+use crate::tick::TickUnit;
 use crate::order_book::LimitUpdate;
 use crate::api::{
     Notification,
@@ -16,48 +18,99 @@ use crate::api::{
     OrderConfirmation,
     OrderUpdate,
     OrderExpiration,
+    TradingStatus,
 };
+use crate::api::index::IndexPrice;
 use crate::api::symbol::Symbol;
+use crate::api::stream::NotificationStream;
 use crate::api::wss;
 use crate::api::timestamp::{Timestamped, IntoTimestamped};
-use crate::api::binance::Client;
+use crate::api::binance::{Client, Market};
 use crate::api::binance::errors::RestError;
 
 
 impl Client {
     crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
-        -> UnboundedReceiver<Notification>
+        -> NotificationStream
     {
         let params = self.params.clone();
-        let listen_key = self.keys.as_ref().map(|keys| keys.listen_key.clone());
+        let market = self.market;
+        let shared_listen_key = self.listen_key.clone();
+        let listen_key = shared_listen_key.read().unwrap().clone();
         let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        {
+            let mut streams = self.streams.lock().unwrap();
+            streams.retain(|control| control.upgrade().is_some());
+            streams.push(Arc::downgrade(&control));
+        }
+
         thread::spawn(move || {
             let mut address = format!(
-               "{0}/ws/{1}@trade/{1}@depth",
+               "{0}/ws/{1}@{2}/{1}@depth",
                 params.streaming_endpoint,
                 symbol.name().to_lowercase(),
+                market.trade_stream(),
             );
-            if let Some(listen_key) = listen_key {
+            if let Some(stream) = market.index_stream() {
+                address += &format!("/{}@{}", symbol.name().to_lowercase(), stream);
+            }
+            if let Some(listen_key) = listen_key.as_ref() {
                 address += &format!("/{}", listen_key);
             }
 
             debug!("initiating WebSocket connection at {}", address);
 
             if let Err(err) = ws::connect(address, |out| {
-                wss::Handler::new(out, snd.clone(), wss::KeepAlive::True, HandlerImpl{
-                    flags,
-                    symbol,
-                    params: params.clone(),
-                    book_snapshot_state: BookSnapshotState::None,
-                    previous_u: None,
-                })
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        flags,
+                        symbol,
+                        market,
+                        params: params.clone(),
+                        listen_key: listen_key.clone(),
+                        shared_listen_key: shared_listen_key.clone(),
+                        book_snapshot_state: BookSnapshotState::None,
+                        previous_u: None,
+                        request_id: 0,
+                        status_request: None,
+                        halted: None,
+                        oco_legs: HashMap::new(),
+                        expired_legs: HashSet::new(),
+                        linked_expirations: Vec::new(),
+                    }
+                )
             })
             {
                 error!("WebSocket connection terminated with error: `{}`", err);
             }
         });
 
-        rcv
+        NotificationStream::new(rcv, control)
+    }
+}
+
+impl Market {
+    /// Futures only provide aggregated trades, with the same fields as spot trades.
+    fn trade_stream(self) -> &'static str {
+        match self {
+            Market::Spot => "trade",
+            Market::UsdMargined => "aggTrade",
+        }
+    }
+
+    /// Index prices are only provided on futures, along with the mark price.
+    fn index_stream(self) -> Option<&'static str> {
+        match self {
+            Market::Spot => None,
+            Market::UsdMargined => Some("markPrice@1s"),
+        }
     }
 }
 
@@ -68,7 +121,9 @@ struct LimitUpdates {
     updates: Vec<Timestamped<LimitUpdate>>,
 }
 
-type BookReceiver = mpsc::Receiver<Result<BinanceBookSnapshot<'static>, failure::Error>>;
+type Receiver<T> = mpsc::Receiver<Result<T, failure::Error>>;
+
+type BookReceiver = Receiver<BinanceBookSnapshot<'static>>;
 
 #[derive(Debug)]
 struct BookWaitingState {
@@ -92,12 +147,39 @@ enum BookSnapshotState {
 struct HandlerImpl {
     symbol: Symbol,
     flags: NotificationFlags,
+    market: Market,
     params: Params,
+
+    /// Listen key of the user data stream this connection is subscribed to.
+    listen_key: Option<String>,
+
+    /// Current listen key of the client, which changes when its keys are rotated.
+    shared_listen_key: Arc<RwLock<Option<String>>>,
+
     book_snapshot_state: BookSnapshotState,
 
     /// Keep track of the `u` indicator sent by binance, this is used for checking
     /// the of the ordering of the limit updates.
     previous_u: Option<u64>,
+
+    /// Id of the last (un)subscription request sent over the connection.
+    request_id: u64,
+
+    /// Pending request for the trading status of `symbol`, see `on_poll`.
+    status_request: Option<Receiver<String>>,
+
+    /// Whether trading on `symbol` is halted, as of the latest status request.
+    halted: Option<bool>,
+
+    /// order list id => (client order id, size) of the live legs of each OCO
+    oco_legs: HashMap<i64, Vec<(String, TickUnit)>>,
+
+    /// OCO legs whose expiration was notified as soon as their sibling was filled: the
+    /// expiration later reported by binance must be dropped.
+    expired_legs: HashSet<String>,
+
+    /// Expirations of OCO legs to be notified right after the current notification.
+    linked_expirations: Vec<Notification>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
@@ -134,6 +216,10 @@ struct BinanceDepthUpdate<'a> {
     E: u64,
     U: u64,
     u: u64,
+
+    /// Final update id of the previous event, futures only.
+    pu: Option<u64>,
+
     #[serde(borrow)]
     b: Vec<BinanceLimitUpdate<'a>>,
     #[serde(borrow)]
@@ -163,8 +249,13 @@ impl<'a> BinanceBookSnapshot<'a> {
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct BinanceExecutionReport<'a> {
+    s: &'a str,
     c: &'a str,
+
+    /// Original client order id of a canceled order, spot only.
+    #[serde(default)]
     C: &'a str,
+
     S: &'a str,
     q: &'a str,
     p: &'a str,
@@ -172,13 +263,55 @@ struct BinanceExecutionReport<'a> {
     l: &'a str,
     z: &'a str,
     L: &'a str,
-    n: &'a str,
+
+    /// Absent from futures updates which did not incur any commission.
+    n: Option<&'a str>,
+
     T: u64,
+
+    /// Id of the order list (OCO) of the order, `-1` if none. Spot only.
+    #[serde(default)]
+    g: Option<i64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+/// Futures counterpart of the `executionReport` event.
+struct BinanceOrderTradeUpdate<'a> {
+    #[serde(borrow)]
+    o: BinanceExecutionReport<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BinanceMarkPrice<'a> {
+    E: u64,
+    s: &'a str,
+    i: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BinanceSymbolStatus<'a> {
+    symbol: &'a str,
+    status: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BinanceExchangeInfo<'a> {
+    #[serde(borrow)]
+    symbols: Vec<BinanceSymbolStatus<'a>>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 struct EventType<'a> {
-    e: &'a str,
+    #[serde(borrow)]
+    e: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct BinanceSubscription<'a> {
+    method: &'a str,
+    params: &'a [String],
+    id: u64,
 }
 
 impl HandlerImpl {
@@ -197,8 +330,14 @@ impl HandlerImpl {
     fn parse_message(&mut self, json: &str) -> Result<Option<Notification>, failure::Error> {
         let event_type: EventType<'_> = serde_json::from_str(json)?;
 
-        let notif = match event_type.e {
-            "trade" if self.flags.contains(NotificationFlags::TRADES) => {
+        // Messages without an event type are responses to (un)subscription requests.
+        let event_type = match event_type.e {
+            Some(event_type) => event_type,
+            None => return Ok(None),
+        };
+
+        let notif = match event_type {
+            "trade" | "aggTrade" if self.flags.contains(NotificationFlags::TRADES) => {
                 let trade: BinanceTrade<'_> = serde_json::from_str(json)?;
                 Some(
                     Notification::Trade(Trade {
@@ -212,10 +351,16 @@ impl HandlerImpl {
             "depthUpdate" if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
                 let depth_update: BinanceDepthUpdate<'_> = serde_json::from_str(json)?;
 
-                // The order book is consistent if the previous `u + 1` is equal to current `U`.
+                // The order book is consistent if the previous `u + 1` is equal to current `U`,
+                // or on futures if the previous `u` is equal to current `pu`.
                 if let Some(previous_u) = self.previous_u {
-                    if previous_u + 1 != depth_update.U {
-                        panic!("previous `u + 1` and current `U` do not match");
+                    match (self.market, depth_update.pu) {
+                        (Market::UsdMargined, Some(pu)) => if previous_u != pu {
+                            panic!("previous `u` and current `pu` do not match");
+                        },
+                        _ => if previous_u + 1 != depth_update.U {
+                            panic!("previous `u + 1` and current `U` do not match");
+                        },
                     }
                 }
                 self.previous_u = Some(depth_update.u);
@@ -237,50 +382,29 @@ impl HandlerImpl {
                 }
             },
 
+            "markPriceUpdate" if self.flags.contains(NotificationFlags::INDEX_PRICES) => {
+                let mark_price: BinanceMarkPrice<'_> = serde_json::from_str(json)?;
+                Some(
+                    Notification::IndexPrice(IndexPrice {
+                        index: mark_price.s.to_owned(),
+                        price: mark_price.i.to_owned(),
+                    }.with_timestamp(mark_price.E))
+                )
+            }
+
             "executionReport" if self.flags.contains(NotificationFlags::ORDERS) => {
                 let report: BinanceExecutionReport<'_> = serde_json::from_str(json)?;
+                self.parse_execution_report(&report)?
+            }
+
+            "ORDER_TRADE_UPDATE" if self.flags.contains(NotificationFlags::ORDERS) => {
+                let update: BinanceOrderTradeUpdate<'_> = serde_json::from_str(json)?;
 
-                match report.x {
-                    "NEW" => Some(
-                        Notification::OrderConfirmation(OrderConfirmation {
-                            order_id: report.c.to_owned(),
-                            size: self.symbol.size_tick().ticked(report.q)?,
-                            price: self.symbol.price_tick().ticked(report.p)?,
-                            side: match report.S {
-                                "BUY" => Side::Bid,
-                                "SELL" => Side::Ask,
-                                other => bail!("wrong side `{}`", other),
-                            },
-                        }.with_timestamp(report.T))
-                    ),
-                    
-                    "TRADE" => Some(
-                        Notification::OrderUpdate(OrderUpdate {
-                            order_id: report.c.to_owned(),
-                            consumed_size: self.symbol.size_tick().ticked(report.l)?,
-
-                            remaining_size: self.symbol.size_tick().ticked(report.q)?
-                                - self.symbol.size_tick().ticked(report.z)?,
-
-                            consumed_price: self.symbol.price_tick().ticked(report.L)?,
-                            commission: self.symbol.commission_tick().ticked(report.n)?,
-                        }.with_timestamp(report.T))
-                    ),
-
-                    "EXPIRED" => Some(
-                        Notification::OrderExpiration(OrderExpiration {
-                            order_id: report.c.to_owned(), // subtle: lower case `c`
-                        }.with_timestamp(report.T))
-                    ),
-
-                    "CANCELED" => Some(
-                        Notification::OrderExpiration(OrderExpiration {
-                            order_id: report.C.to_owned(), // subtle: upper case `C`
-                        }.with_timestamp(report.T))
-                    ),
-
-                    // "REJECTED" should already be handled by the REST API.
-                    _ => None,
+                // The futures user data stream carries the updates of every symbol.
+                if update.o.s == self.symbol.name() {
+                    self.parse_execution_report(&update.o)?
+                } else {
+                    None
                 }
             }
 
@@ -289,6 +413,104 @@ impl HandlerImpl {
         Ok(notif)
     }
 
+    fn parse_execution_report(&mut self, report: &BinanceExecutionReport<'_>)
+        -> Result<Option<Notification>, failure::Error>
+    {
+        let list_id = report.g.filter(|list_id| *list_id >= 0);
+        let notif = match report.x {
+            "NEW" => {
+                let size = self.symbol.size_tick().ticked(report.q)?;
+                if let Some(list_id) = list_id {
+                    self.oco_legs
+                        .entry(list_id)
+                        .or_default()
+                        .push((report.c.to_owned(), size));
+                }
+
+                Some(
+                    Notification::OrderConfirmation(OrderConfirmation {
+                        order_id: report.c.to_owned(),
+                        size,
+                        price: self.symbol.price_tick().ticked(report.p)?,
+                        side: match report.S {
+                            "BUY" => Side::Bid,
+                            "SELL" => Side::Ask,
+                            other => bail!("wrong side `{}`", other),
+                        },
+                    }.with_timestamp(report.T))
+                )
+            }
+
+            "TRADE" => {
+                // Any fill of an OCO leg expires its siblings, which we notify right away.
+                if let Some(legs) = list_id.and_then(|list_id| self.oco_legs.remove(&list_id)) {
+                    for (order_id, size) in legs.into_iter().filter(|(id, _)| id != report.c) {
+                        self.linked_expirations.push(
+                            Notification::OrderExpiration(OrderExpiration {
+                                order_id: order_id.clone(),
+                                filled_size: Some(0),
+                                remaining_size: Some(size),
+                            }.with_timestamp(report.T))
+                        );
+                        self.expired_legs.insert(order_id);
+                    }
+                }
+
+                Some(
+                    Notification::OrderUpdate(OrderUpdate {
+                        order_id: report.c.to_owned(),
+                        consumed_size: self.symbol.size_tick().ticked(report.l)?,
+
+                        remaining_size: self.symbol.size_tick().ticked(report.q)?
+                            - self.symbol.size_tick().ticked(report.z)?,
+
+                        consumed_price: self.symbol.price_tick().ticked(report.L)?,
+                        commission: self.symbol.commission_tick().ticked(report.n.unwrap_or("0"))?,
+                    }.with_timestamp(report.T))
+                )
+            }
+
+            "EXPIRED" | "CANCELED" => {
+                let filled_size = self.symbol.size_tick().ticked(report.z)?;
+                let remaining_size = self.symbol.size_tick().ticked(report.q)?
+                    - filled_size;
+
+                let order_id = match (self.market, report.x) {
+                    (Market::Spot, "CANCELED") => report.C, // subtle: upper case `C`
+                    _ => report.c, // subtle: lower case `c`
+                };
+
+                if self.expired_legs.remove(order_id) {
+                    return Ok(None);
+                }
+                if let Some(list_id) = list_id {
+                    let now_empty = match self.oco_legs.get_mut(&list_id) {
+                        Some(legs) => {
+                            legs.retain(|(id, _)| id != order_id);
+                            legs.is_empty()
+                        }
+                        None => false,
+                    };
+                    if now_empty {
+                        self.oco_legs.remove(&list_id);
+                    }
+                }
+
+                Some(
+                    Notification::OrderExpiration(OrderExpiration {
+                        order_id: order_id.to_owned(),
+                        filled_size: Some(filled_size),
+                        remaining_size: Some(remaining_size),
+                    }.with_timestamp(report.T))
+                )
+            }
+
+            // "REJECTED" should already be handled by the REST API.
+            _ => None,
+        };
+        Ok(notif)
+    }
+
     fn process_book_snapshot(
         &self,
         snapshot: Result<BinanceBookSnapshot, failure::Error>,
@@ -307,9 +529,14 @@ impl HandlerImpl {
             .map(|l| self.convert_binance_update(l, Side::Ask))
             .map(|l| Ok(l?.timestamped()));
 
+        // On futures, the first event to process is the one which includes `lastUpdateId`.
+        let market = self.market;
         let buffered = buffered_events
             .into_iter()
-            .filter(|update| update.u > snapshot.lastUpdateId)
+            .filter(|update| match market {
+                Market::Spot => update.u > snapshot.lastUpdateId,
+                Market::UsdMargined => update.u >= snapshot.lastUpdateId,
+            })
             .flat_map(|update| update.updates)
             .map(Ok);
 
@@ -371,49 +598,94 @@ impl HandlerImpl {
         );
 
         let address = format!(
-            "{}/api/v1/depth?symbol={}&limit=1000",
+            "{}/{}?symbol={}&limit=1000",
             self.params.rest_endpoint,
+            self.market.depth_path(),
             self.symbol.name()
         ).parse().expect("invalid address");
 
         debug!("initiating LOB request at `{}`", address);
 
-        thread::spawn(move || {
-            let https = match hyper_tls::HttpsConnector::new(2) {
-                Ok(https) => https,
-                Err(err) => {
-                    let _ = snd.send(Err(err).map_err(From::from));
-                    return;
+        spawn_get(address, |body| {
+            let snapshot: BinanceBookSnapshot<'_> = serde_json::from_slice(body)?;
+            Ok(snapshot.owned())
+        }, snd);
+    }
+
+    /// Check the pending status request if any, and start a new one otherwise.
+    fn poll_status(&mut self) -> Result<Option<Notification>, failure::Error> {
+        if let Some(rcv) = self.status_request.take() {
+            let status = match rcv.try_recv() {
+                Ok(status) => status?,
+                Err(mpsc::TryRecvError::Empty) => {
+                    self.status_request = Some(rcv);
+                    return Ok(None);
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    bail!("status sender has disconnected");
                 }
             };
 
-            let client = hyper::Client::builder().build::<_, hyper::Body>(https);
-            let fut = client.get(address).and_then(|res| {
-                let status = res.status();
-                res.into_body().concat2().and_then(move |body| {
-                    Ok((status, body))
-                })
-            }).map_err(From::from).and_then(move |(status, body)| {
-                if status != hyper::StatusCode::OK {
-                    let binance_error = serde_json::from_slice(&body);
-                    Err(
-                        RestError::from_binance_error(
-                            status,
-                            binance_error.ok()
-                        )
-                    )?;
-                }
+            // The first status only reports a halt, not the fact that trading is open.
+            let halted = status != "TRADING";
+            let previous = self.halted.replace(halted);
+            if previous.unwrap_or(false) == halted {
+                return Ok(None);
+            }
 
-                let snapshot: BinanceBookSnapshot<'_> = serde_json::from_slice(&body)?;
-                Ok(snapshot.owned())
-            }).then(move |res| {
-                let _ = snd.send(res);
-                Ok::<(), !>(())
-            });
+            let status = TradingStatus {
+                status,
+                message: None,
+            }.timestamped();
+            return Ok(Some(if halted {
+                Notification::TradingHalted(status)
+            } else {
+                Notification::TradingResumed(status)
+            }));
+        }
 
-            use tokio::runtime::current_thread;
-            current_thread::block_on_all(fut).unwrap();
-        });
+        let (snd, rcv) = mpsc::sync_channel(1);
+        self.status_request = Some(rcv);
+
+        let address = format!(
+            "{}/{}?symbol={}",
+            self.params.rest_endpoint,
+            self.market.exchange_info_path(),
+            self.symbol.name()
+        ).parse().expect("invalid address");
+
+        // Futures ignore the `symbol` parameter and describe all the symbols.
+        let symbol = self.symbol.name().to_owned();
+        spawn_get(address, move |body| {
+            let info: BinanceExchangeInfo<'_> = serde_json::from_slice(body)?;
+            match info.symbols.into_iter().find(|s| s.symbol == symbol) {
+                Some(s) => Ok(s.status.to_owned()),
+                None => bail!("symbol `{}` not found", symbol),
+            }
+        }, snd);
+        Ok(None)
+    }
+
+    fn send_subscription(&mut self, method: &str, streams: &[String], out: &ws::Sender)
+        -> ws::Result<()>
+    {
+        if streams.is_empty() {
+            return Ok(());
+        }
+
+        self.request_id += 1;
+        let subscription = BinanceSubscription {
+            method,
+            params: streams,
+            id: self.request_id,
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `BinanceSubscription`: `{}`", err);
+            }
+        }
     }
 }
 
@@ -455,6 +727,101 @@ impl wss::HandlerImpl for HandlerImpl {
 
             None => (),
         }
+
+        for notif in self.linked_expirations.drain(..) {
+            out.unbounded_send(notif).unwrap();
+        }
         Ok(())
     }
+
+    /// Binance does not stream trading halts, so we poll the status of the symbol.
+    fn on_poll(&mut self, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        if let Some(notif) = self.poll_status()? {
+            out.unbounded_send(notif).unwrap();
+        }
+        Ok(())
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+        let name = self.symbol.name().to_lowercase();
+
+        // The user data stream and the index price stream are part of the connection
+        // address and are kept, `ORDERS` and `INDEX_PRICES` only act as filters.
+        let mut subscribe = Vec::new();
+        let mut unsubscribe = Vec::new();
+        let streams = [
+            (NotificationFlags::TRADES, format!("{}@{}", name, self.market.trade_stream())),
+            (NotificationFlags::ORDER_BOOK, format!("{}@depth", name)),
+        ];
+        for (flag, stream) in streams.iter() {
+            match (previous.contains(*flag), flags.contains(*flag)) {
+                (false, true) => subscribe.push(stream.clone()),
+                (true, false) => unsubscribe.push(stream.clone()),
+                _ => (),
+            }
+        }
+
+        // Depth updates were dropped in the meantime, so we need a new snapshot.
+        if !previous.contains(NotificationFlags::ORDER_BOOK)
+            && flags.contains(NotificationFlags::ORDER_BOOK)
+        {
+            self.book_snapshot_state = BookSnapshotState::None;
+            self.previous_u = None;
+        }
+
+        // The handler is also woken up when the keys of the client are rotated.
+        let listen_key = self.shared_listen_key.read().unwrap().clone();
+        if listen_key != self.listen_key {
+            debug!("switching the user data stream to a new listen key");
+            unsubscribe.extend(self.listen_key.take());
+            subscribe.extend(listen_key.clone());
+            self.listen_key = listen_key;
+        }
+
+        self.send_subscription("UNSUBSCRIBE", &unsubscribe, out)?;
+        self.send_subscription("SUBSCRIBE", &subscribe, out)
+    }
+}
+
+/// Perform a GET request at `address` in a separate thread, and send the body parsed by
+/// `parse` through `snd`.
+fn spawn_get<T, F>(address: hyper::Uri, parse: F, snd: mpsc::SyncSender<Result<T, failure::Error>>)
+    where T: Send + 'static, F: FnOnce(&[u8]) -> Result<T, failure::Error> + Send + 'static
+{
+    thread::spawn(move || {
+        let https = match hyper_tls::HttpsConnector::new(2) {
+            Ok(https) => https,
+            Err(err) => {
+                let _ = snd.send(Err(From::from(err)));
+                return;
+            }
+        };
+
+        let client = hyper::Client::builder().build::<_, hyper::Body>(https);
+        let fut = client.get(address).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        }).map_err(From::from).and_then(move |(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let binance_error = serde_json::from_slice(&body);
+                Err(
+                    RestError::from_binance_error(
+                        status,
+                        binance_error.ok()
+                    )
+                )?;
+            }
+
+            parse(&body)
+        }).then(move |res| {
+            let _ = snd.send(res);
+            Ok::<(), !>(())
+        });
+
+        use tokio::runtime::current_thread;
+        current_thread::block_on_all(fut).unwrap();
+    });
 }
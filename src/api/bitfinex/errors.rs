@@ -0,0 +1,185 @@
+//! A module defining error types specific to Bitfinex.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use std::borrow::Cow;
+use crate::api;
+
+/// Errors are sent as `["error", code, message]`.
+pub(super) type BitfinexRestError<'a> = (&'a str, i32, Cow<'a, str>);
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Bitfinex REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Internal Bitfinex error code: see API documentation.
+    pub error_code: Option<i32>,
+
+    /// Description of the error.
+    pub error_msg: Option<String>,
+}
+
+impl RestError {
+    fn msg_contains(&self, pattern: &str) -> bool {
+        self.error_msg.as_ref().map(|msg| msg.contains(pattern)).unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.msg_contains("Order not found") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.msg_contains("not enough") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest && self.msg_contains("duplicate") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error_msg) = &self.error_msg {
+            write!(f, ": `{}`", error_msg)?;
+        }
+        if let Some(error_code) = self.error_code {
+            write!(f, " (error_code = {})", error_code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Bitfinex reports most errors with an HTTP 500 status code, the actual category
+    /// being given by the error code.
+    pub(super) fn from_bitfinex_error(
+        status: StatusCode,
+        bitfinex_error: Option<BitfinexRestError>
+    ) -> Self
+    {
+        let kind = bitfinex_error.as_ref()
+            .and_then(|error| RestErrorKind::from_error_code(error.1))
+            .unwrap_or_else(|| RestErrorKind::from_status_code(status));
+
+        RestError {
+            kind,
+            error_code: bitfinex_error.as_ref().map(|error| error.1),
+            error_msg: bitfinex_error.map(|error| error.2.into_owned()),
+        }
+    }
+
+    /// An order or cancel request was processed but rejected, with the given message.
+    pub(super) fn rejected(error_msg: &str) -> Self {
+        RestError {
+            kind: RestErrorKind::BadRequest,
+            error_code: None,
+            error_msg: Some(error_msg.to_owned()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Bitfinex error code to a Bitfinex error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key, signature or nonce, or missing permission for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Bitfinex. See Bitfinex API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Bitfinex side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is not ready or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::OK => panic!("`RestErrorKind::from_status_code` with `StatusCode::Ok`"),
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_error_code(code: i32) -> Option<Self> {
+        use self::RestErrorKind::*;
+        let kind = match code {
+            10001 | 10020 => BadRequest,
+            10100 ..= 10114 => Unauthorized,
+            11010 => TooManyRequests,
+            11000 | 20060 => ServiceUnavailable,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
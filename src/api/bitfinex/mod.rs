@@ -0,0 +1,261 @@
+//! Implementation of `ApiClient` for the Bitfinex (v2) API.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::pkey::{PKey, Private};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::tick::{self, Tick, TickUnit};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+
+/// Ticks per unit used for prices, sizes and balances: Bitfinex amounts have at most
+/// 8 decimals.
+const AMOUNT_TICK: TickUnit = 100_000_000;
+
+/// Bitfinex sends amounts as JSON numbers, possibly negative depending on the side.
+fn convert_bitfinex_amount(tick: Tick, amount: f64) -> Result<TickUnit, tick::ConversionError> {
+    tick.ticked(&format!("{}", amount.abs()))
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Bitfinex key pair: api key + secret key.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Precision of the order book channel.
+///
+/// Bitfinex quotes prices with 5 significant digits and up to 8 decimals, so every
+/// symbol uses a price tick (and a size tick) of `10^-8`: aggregated levels are then
+/// exact multiples of the price tick, whatever the precision level.
+pub enum BookPrecision {
+    /// Raw book: every order is forwarded, and aggregated into levels by the client.
+    Raw,
+
+    /// Levels aggregated at 5 significant digits.
+    P0,
+
+    /// Levels aggregated at 4 significant digits.
+    P1,
+
+    /// Levels aggregated at 3 significant digits.
+    P2,
+
+    /// Levels aggregated at 2 significant digits.
+    P3,
+
+    /// Levels aggregated at 1 significant digit.
+    P4,
+}
+
+impl BookPrecision {
+    /// Number of significant digits of the aggregated levels, or `None` for the raw
+    /// book.
+    pub fn significant_digits(self) -> Option<u32> {
+        match self {
+            BookPrecision::Raw => None,
+            BookPrecision::P0 => Some(5),
+            BookPrecision::P1 => Some(4),
+            BookPrecision::P2 => Some(3),
+            BookPrecision::P3 => Some(2),
+            BookPrecision::P4 => Some(1),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BookPrecision::Raw => "R0",
+            BookPrecision::P0 => "P0",
+            BookPrecision::P1 => "P1",
+            BookPrecision::P2 => "P2",
+            BookPrecision::P3 => "P3",
+            BookPrecision::P4 => "P4",
+        }
+    }
+}
+
+/// A Bitfinex API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+    book_precision: BookPrecision,
+
+    /// Last nonce sent along an authenticated request, nonces must be strictly increasing.
+    nonce: Arc<Mutex<u64>>,
+
+    /// client order id => server order id
+    order_ids: Arc<CHashMap<String, u64>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new Bitfinex API client with given `params`. If `key_pair` is not
+    /// `None`, this will enable performing requests to the REST API and will forward
+    /// the user data stream. The order book is streamed with the `P0` precision by
+    /// default, see `with_book_precision`.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Bitfinex.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            book_precision: BookPrecision::P0,
+            nonce: Arc::new(Mutex::new(0)),
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+
+    /// Stream the order book with the given precision.
+    pub fn with_book_precision(mut self, book_precision: BookPrecision) -> Self {
+        self.book_precision = book_precision;
+        self
+    }
+
+    /// Return the precision with which the order book is streamed.
+    pub fn book_precision(&self) -> BookPrecision {
+        self.book_precision
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Bitfinex
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.cancel_impl(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(Ok(().timestamped()).into_future())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Bitfinex only accepts integer client order ids, which must be unique over a
+    /// day: the hint is ignored and the id is derived from the current time.
+    fn new_order_id(_: &str) -> String {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use crate::api::timestamp::timestamp_ms;
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        const MS_PER_DAY: u64 = 86_400_000;
+        let count = (COUNTER.fetch_add(1, Ordering::Relaxed) % 1000) as u64;
+        format!("{}", (timestamp_ms() % MS_PER_DAY) * 1000 + count)
+    }
+}
@@ -0,0 +1,373 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_json::Value;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+use crate::api::bitfinex::{Client, convert_bitfinex_amount, AMOUNT_TICK};
+use crate::api::bitfinex::errors::RestError;
+
+/// Flag marking an order as post only.
+const POST_ONLY: u32 = 4096;
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
+struct BitfinexOrder<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    symbol: &'a str,
+    amount: &'a str,
+    price: &'a str,
+    flags: u32,
+    cid: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct BitfinexCancel {
+    id: u64,
+}
+
+/// Response to write requests: `[MTS, TYPE, MESSAGE_ID, null, DATA, CODE, STATUS, TEXT]`.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct BitfinexNotification(
+    u64,
+    String,
+    Option<Value>,
+    Option<Value>,
+    Value,
+    Option<i64>,
+    String,
+    Option<String>,
+);
+
+impl Client {
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let address = format!(
+            "{}/{}",
+            self.params.rest_endpoint,
+            endpoint,
+        );
+
+        let mut request = Request::builder();
+
+        // Authenticated endpoints are reached through POST requests, signed with a nonce.
+        if method == Method::POST {
+            if let Some(keys) = self.keys.as_ref() {
+                let nonce = {
+                    let mut last = self.nonce.lock().unwrap();
+                    *last = std::cmp::max(*last + 1, timestamp_ms() * 1000);
+                    *last
+                };
+
+                let mut signer = Signer::new(MessageDigest::sha384(), &keys.secret_key).unwrap();
+                let what = format!("/api/{}{}{}", endpoint, nonce, body);
+                signer.update(what.as_bytes()).unwrap();
+                let signature = hex::encode(&signer.sign_to_vec().unwrap());
+
+                request.header("bfx-nonce", format!("{}", nonce).as_bytes())
+                    .header("bfx-apikey", keys.api_key.as_bytes())
+                    .header("bfx-signature", signature.as_bytes());
+            }
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let bitfinex_error = serde_json::from_slice(&body);
+                let error = RestError::from_bitfinex_error(status, bitfinex_error.ok());
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    /// Parse the response to a write request, which may have been rejected.
+    fn parse_notification<K: api::errors::ErrorKind>(body: &[u8])
+        -> Result<BitfinexNotification, api::errors::ApiError<K>>
+            where RestError: ErrorKinded<K>
+    {
+        let notification: BitfinexNotification = serde_json::from_slice(body)
+            .map_err(api::errors::RequestError::new)
+            .map_err(api::errors::ApiError::RequestError)?;
+
+        if notification.6 != "SUCCESS" {
+            let error = RestError::rejected(
+                notification.7.as_ref().map(|text| text.as_str()).unwrap_or("<empty>")
+            );
+            let kind = error.kind();
+            Err(
+                api::errors::ApiError::RestError(error.context(kind).into())
+            )?;
+        }
+        Ok(notification)
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Bitfinex");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Bitfinex");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        // Always send a client order id, so that the WebSocket notifications can be
+        // matched with the order even if they arrive before the HTTP response.
+        let order_id = order.order_id.clone().unwrap_or_else(|| Self::new_order_id(""));
+        let cid: u64 = match order_id.parse() {
+            Ok(cid) => cid,
+            Err(..) => {
+                warn!("called `order` with a non integer order id");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::InvalidRequest.into()
+                    )
+                ));
+            }
+        };
+
+        let symbol = order.symbol();
+
+        let type_ = match order.time_in_force {
+            TimeInForce::GoodTilCanceled => "EXCHANGE LIMIT",
+            TimeInForce::ImmediateOrCancel => "EXCHANGE IOC",
+            TimeInForce::FillOrKilll => "EXCHANGE FOK",
+            TimeInForce::GoodTilDate(_) => {
+                warn!("called `order` with `GoodTilDate`, which is not supported by Bitfinex");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // The amount is negative for sell orders.
+        let size = order.size.unticked(symbol.size_tick());
+        let amount = match order.side {
+            Side::Bid => size.into_owned(),
+            Side::Ask => format!("-{}", size),
+        };
+        let price = order.price.unticked(symbol.price_tick());
+        let bitfinex_symbol = format!("t{}", symbol.name());
+
+        let order_body = BitfinexOrder {
+            type_,
+            symbol: &bitfinex_symbol,
+            amount: &amount,
+            price: price.borrow(),
+            flags: if order.type_ == OrderType::LimitMaker { POST_ONLY } else { 0 },
+            cid,
+        };
+
+        let body = serde_json::to_string(&order_body).expect("invalid json");
+
+        let order_ids = self.order_ids.clone();
+
+        let fut = self.request("v2/auth/w/order/submit", Method::POST, body).and_then(move |body| {
+            let notification = Self::parse_notification(&body)?;
+
+            let orders: Vec<Vec<Value>> = serde_json::from_value(notification.4)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let id = match orders.first().and_then(|o| o.first()).and_then(Value::as_u64) {
+                Some(id) => id,
+                None => {
+                    error!("order submission response is missing the order id");
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::UnknownStatus.into()
+                    ));
+                }
+            };
+
+            order_ids.insert(order_id.clone(), id);
+            debug!("insert order id {} (from REST)", order_id);
+
+            Ok(OrderAck {
+                order_id,
+            }.with_timestamp(notification.0))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        let id = match self.order_ids.get(&cancel.order_id) {
+            Some(id) => *id,
+            None => {
+                warn!("called `cancel` with a not yet inserted order id");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::Specific(
+                            api::errors::CancelErrorKind::UnknownOrder
+                        ).into()
+                    )
+                ));
+            }
+        };
+
+        let body = serde_json::to_string(&BitfinexCancel { id }).expect("invalid json");
+
+        let fut = self.request("v2/auth/w/order/cancel", Method::POST, body).and_then(|body| {
+            let notification = Self::parse_notification(&body)?;
+            Ok(CancelAck.with_timestamp(notification.0))
+        });
+        Box::new(fut)
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v2/auth/r/wallets", Method::POST, "{}".to_owned()).and_then(|body| {
+            // `[[WALLET_TYPE, CURRENCY, BALANCE, UNSETTLED_INTEREST, AVAILABLE_BALANCE, ...]]`
+            let wallets: Vec<Vec<Value>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let tick = Tick::new(AMOUNT_TICK);
+            let mut balances = Balances::new();
+            for wallet in wallets {
+                if wallet.len() < 5 || wallet[0].as_str() != Some("exchange") {
+                    continue;
+                }
+
+                let currency = match wallet[1].as_str() {
+                    Some(currency) => currency,
+                    None => continue,
+                };
+
+                let total = wallet[2].as_f64().unwrap_or(0.);
+
+                // The available balance is not always computed by Bitfinex.
+                let available = wallet[4].as_f64().unwrap_or(total);
+
+                let (free, locked) = convert_bitfinex_amount(tick, total)
+                    .and_then(|total| {
+                        let free = convert_bitfinex_amount(tick, available)?;
+                        Ok((tick.unticked(free)?, tick.unticked(total.saturating_sub(free))?))
+                    })
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                balances.insert(currency.to_owned(), Balance {
+                    free,
+                    locked,
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v2/platform/status", Method::GET, String::new()).and_then(|body| {
+            let status: Vec<u32> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match status.first() {
+                Some(1) => ExchangeStatus::Normal,
+                _ => ExchangeStatus::Maintenance,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        let endpoint = "v2/conf/pub:list:pair:exchange";
+
+        self.request(endpoint, Method::GET, String::new()).and_then(|body| {
+            let pairs: Vec<Vec<&str>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let tick = Tick::new(AMOUNT_TICK);
+            let mut symbols = HashMap::new();
+            for pair in pairs.into_iter().flatten() {
+                if let Some(symbol) = Symbol::new(pair, tick, tick) {
+                    symbols.insert(symbol.name().to_lowercase(), symbol);
+                } else {
+                    error!("symbol name too long: `{}`", pair);
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
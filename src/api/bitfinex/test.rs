@@ -0,0 +1,64 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::bitfinex::BookPrecision;
+use crate::api::bitfinex::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+const SUBSCRIBED: &str = r#"{"event":"subscribed","channel":"book","chanId":17,
+    "symbol":"tBTCUSD","prec":"P0","freq":"F0","len":"100","pair":"BTCUSD"}"#;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTCUSD", Tick::new(10), Tick::new(100_000_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book with `precision`, and return
+/// the limit updates without their local timestamps.
+fn parse_book(precision: BookPrecision, frames: &[&str]) -> Vec<Vec<LimitUpdate>> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags, precision);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| match notif.unwrap() {
+        Notification::LimitUpdates(updates) => updates.iter().map(|u| **u).collect(),
+        other => panic!("expected limit updates, got {:?}", other),
+    }).collect()
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let snapshot = "[17,[[7254.7,3,3.3],[7254.8,1,-1.0]]]";
+    let update = "[17,[7254.7,0,1]]";
+
+    assert_eq!(parse_book(BookPrecision::P0, &[SUBSCRIBED, snapshot, "[17,\"hb\"]", update]), vec![
+        vec![
+            LimitUpdate::new(72547, 330_000_000, Side::Bid),
+            LimitUpdate::new(72548, 100_000_000, Side::Ask),
+        ],
+        vec![LimitUpdate::new(72547, 0, Side::Bid)],
+    ]);
+}
+
+#[test]
+fn raw_book_aggregation() {
+    let snapshot = "[17,[[101,7254.7,1.5],[102,7254.7,0.5],[103,7254.8,-1.0]]]";
+    let update = "[17,[101,0,1.5]]";
+
+    assert_eq!(parse_book(BookPrecision::Raw, &[SUBSCRIBED, snapshot, update]), vec![
+        vec![
+            LimitUpdate::new(72547, 150_000_000, Side::Bid),
+            LimitUpdate::new(72547, 200_000_000, Side::Bid),
+            LimitUpdate::new(72548, 100_000_000, Side::Ask),
+        ],
+        vec![LimitUpdate::new(72547, 50_000_000, Side::Bid)],
+    ]);
+}
@@ -0,0 +1,635 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chashmap::CHashMap;
+use serde_json::Value;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, IntoTimestamped};
+use crate::api::bitfinex::{Keys, Client, BookPrecision, convert_bitfinex_amount};
+
+/// Number of levels (or orders for the raw book) per side of the subscribed order book.
+const BOOK_LENGTH: &str = "100";
+
+/// Channel id of the authenticated channel.
+const AUTH_CHANNEL: u64 = 0;
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let streaming_endpoint = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+        let nonce = self.nonce.clone();
+        let order_ids = self.order_ids.clone();
+        let book_precision = self.book_precision;
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", streaming_endpoint);
+
+            if let Err(err) = ws::connect(streaming_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        bitfinex_symbol: format!("t{}", symbol.name()),
+                        flags,
+                        keys: keys.clone(),
+                        nonce: nonce.clone(),
+                        book_precision,
+                        channels: HashMap::new(),
+                        order_book: OrderBook::new(),
+                        raw_orders: HashMap::new(),
+                        orders: HashMap::new(),
+                        order_ids: order_ids.clone(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum Channel {
+    Book,
+    Trades,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct OrderState {
+    order_id: String,
+    remaining_size: TickUnit,
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+
+    /// Symbol name used by the API, e.g. `tBTCUSD`.
+    bitfinex_symbol: String,
+
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Nonce shared with `Client`.
+    nonce: Arc<Mutex<u64>>,
+
+    book_precision: BookPrecision,
+
+    /// channel id => subscribed channel
+    channels: HashMap<u64, Channel>,
+
+    /// Local copy of the order book, needed for aggregating the raw book into levels.
+    order_book: OrderBook,
+
+    /// order id => (price, size, side) for the raw book
+    raw_orders: HashMap<u64, (TickUnit, TickUnit, Side)>,
+
+    /// server order id => client order
+    orders: HashMap<u64, OrderState>,
+
+    /// client order id => server order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, u64>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct BitfinexSubscription<'a> {
+    event: &'a str,
+    channel: &'a str,
+    symbol: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prec: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    len: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct BitfinexUnsubscription<'a> {
+    event: &'a str,
+    chanId: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct BitfinexAuth<'a> {
+    event: &'a str,
+    apiKey: &'a str,
+    authSig: String,
+    authPayload: String,
+    authNonce: u64,
+    filter: &'a [&'a str],
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BitfinexEvent<'a> {
+    event: &'a str,
+    channel: Option<&'a str>,
+    chanId: Option<u64>,
+    status: Option<&'a str>,
+    msg: Option<&'a str>,
+}
+
+/// An aggregated level: price, number of orders and amount (negative for asks).
+type BitfinexLevel = (f64, u64, f64);
+
+/// An order of the raw book: order id, price (zero once removed) and amount (negative
+/// for asks).
+type BitfinexRawOrder = (u64, f64, f64);
+
+/// A public trade: id, timestamp, amount (negative if the taker sold) and price.
+type BitfinexTrade = (u64, u64, f64, f64);
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags, book_precision: BookPrecision)
+        -> Self
+    {
+        HandlerImpl {
+            symbol,
+            bitfinex_symbol: format!("t{}", symbol.name()),
+            flags,
+            keys: None,
+            nonce: Arc::new(Mutex::new(0)),
+            book_precision,
+            channels: HashMap::new(),
+            order_book: OrderBook::new(),
+            raw_orders: HashMap::new(),
+            orders: HashMap::new(),
+            order_ids: Arc::new(CHashMap::new()),
+        }
+    }
+
+    fn convert_bitfinex_side(amount: f64) -> Side {
+        if amount > 0. {
+            Side::Bid
+        } else {
+            Side::Ask
+        }
+    }
+
+    fn convert_level(&self, level: BitfinexLevel) -> Result<LimitUpdate, failure::Error> {
+        let (price, count, amount) = level;
+        let size = if count == 0 {
+            0
+        } else {
+            convert_bitfinex_amount(self.symbol.size_tick(), amount)?
+        };
+
+        Ok(LimitUpdate {
+            side: Self::convert_bitfinex_side(amount),
+            price: convert_bitfinex_amount(self.symbol.price_tick(), price)?,
+            size,
+        })
+    }
+
+    /// Aggregate an order of the raw book into its level, return the updated levels.
+    fn apply_raw_order(&mut self, raw_order: BitfinexRawOrder)
+        -> Result<Vec<LimitUpdate>, failure::Error>
+    {
+        let (id, price, amount) = raw_order;
+        let mut updates = Vec::new();
+
+        if let Some((price, size, side)) = self.raw_orders.remove(&id) {
+            let level_size = self.order_book.size_at_limit(side, price);
+            updates.push(LimitUpdate::new(price, level_size.saturating_sub(size), side));
+        }
+
+        if price != 0. {
+            let side = Self::convert_bitfinex_side(amount);
+            let price = convert_bitfinex_amount(self.symbol.price_tick(), price)?;
+            let size = convert_bitfinex_amount(self.symbol.size_tick(), amount)?;
+
+            let level_size = match updates.first() {
+                Some(update) if update.side == side && update.price == price => update.size,
+                _ => self.order_book.size_at_limit(side, price),
+            };
+            updates.push(LimitUpdate::new(price, level_size + size, side));
+            self.raw_orders.insert(id, (price, size, side));
+        }
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+        Ok(updates)
+    }
+
+    fn parse_book(&mut self, payload: Value, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        // A snapshot is a list of entries, whereas an update is a single entry.
+        let snapshot = payload.as_array()
+            .and_then(|entries| entries.first())
+            .map(|entry| entry.is_array())
+            .unwrap_or(false);
+
+        let entries = if snapshot {
+            self.order_book = OrderBook::new();
+            self.raw_orders.clear();
+            serde_json::from_value(payload)?
+        } else {
+            vec![payload]
+        };
+
+        let mut updates = Vec::new();
+        for entry in entries {
+            if self.book_precision == BookPrecision::Raw {
+                updates.extend(self.apply_raw_order(serde_json::from_value(entry)?)?);
+            } else {
+                updates.push(self.convert_level(serde_json::from_value(entry)?)?);
+            }
+        }
+
+        let updates: Vec<_> = updates.into_iter().map(|l| l.timestamped()).collect();
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trade(&self, trade: Value, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let (_, timestamp, amount, price): BitfinexTrade = serde_json::from_value(trade)?;
+
+        let trade = Notification::Trade(Trade {
+            price: convert_bitfinex_amount(self.symbol.price_tick(), price)?,
+            size: convert_bitfinex_amount(self.symbol.size_tick(), amount)?,
+
+            // The amount is positive if the taker bought.
+            maker_side: Self::convert_bitfinex_side(-amount),
+        }.with_timestamp(timestamp));
+
+        out.unbounded_send(trade).unwrap();
+        Ok(())
+    }
+
+    /// Orders are sent as `[ID, GID, CID, SYMBOL, MTS_CREATE, MTS_UPDATE, AMOUNT,
+    /// AMOUNT_ORIG, TYPE, TYPE_PREV, MTS_TIF, _, FLAGS, STATUS, _, _, PRICE, ...]`.
+    fn parse_order(&mut self, kind: &str, order: &[Value], out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        if order.len() < 17 {
+            bail!("malformed order: `{:?}`", order);
+        }
+
+        if order[3].as_str() != Some(&self.bitfinex_symbol) {
+            return Ok(());
+        }
+
+        let id = match order[0].as_u64() {
+            Some(id) => id,
+            None => bail!("missing order id: `{:?}`", order),
+        };
+
+        // Orders sent by `Client` always carry a client order id, fall back to the server
+        // order id for orders inserted by other means.
+        let order_id = match order[2].as_u64() {
+            Some(cid) => cid.to_string(),
+            None => id.to_string(),
+        };
+
+        let timestamp = order[5].as_u64().unwrap_or_else(timestamp_ms);
+        let amount = order[6].as_f64().unwrap_or(0.);
+        let amount_orig = order[7].as_f64().unwrap_or(0.);
+        let status = order[13].as_str().unwrap_or("");
+
+        match kind {
+            "os" | "on" => {
+                // Don't forget to update the concurrent map `client order id => server
+                // order id` in case the WebSocket notif arrives before the HTTP response
+                if !self.order_ids.contains_key(&order_id) {
+                    self.order_ids.insert(order_id.clone(), id);
+                    debug!("insert order id {} (from WSS)", order_id);
+                }
+
+                self.orders.insert(id, OrderState {
+                    order_id: order_id.clone(),
+                    remaining_size: convert_bitfinex_amount(self.symbol.size_tick(), amount)?,
+                });
+
+                if kind == "on" && self.flags.contains(NotificationFlags::ORDERS) {
+                    let order = OrderConfirmation {
+                        size: convert_bitfinex_amount(self.symbol.size_tick(), amount_orig)?,
+                        price: convert_bitfinex_amount(
+                            self.symbol.price_tick(),
+                            order[16].as_f64().unwrap_or(0.)
+                        )?,
+                        side: Self::convert_bitfinex_side(amount_orig),
+                        order_id,
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+                }
+            }
+
+            "oc" => {
+                self.orders.remove(&id);
+
+                // Fully executed orders are closed as well, e.g. `EXECUTED @ 107.6(-0.2)`.
+                if status.contains("CANCELED") && self.flags.contains(NotificationFlags::ORDERS) {
+                    let remaining_size = convert_bitfinex_amount(self.symbol.size_tick(), amount)?;
+                    let size = convert_bitfinex_amount(self.symbol.size_tick(), amount_orig)?;
+                    let expiration = OrderExpiration {
+                        order_id,
+                        filled_size: Some(size - remaining_size),
+                        remaining_size: Some(remaining_size),
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+                }
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Own trades are sent as `[ID, SYMBOL, MTS_CREATE, ORDER_ID, EXEC_AMOUNT, EXEC_PRICE,
+    /// ...]`, and precede the closing of the order.
+    fn parse_own_trade(&mut self, trade: &[Value], out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        if trade.len() < 6 {
+            bail!("malformed trade: `{:?}`", trade);
+        }
+
+        if trade[1].as_str() != Some(&self.bitfinex_symbol) {
+            return Ok(());
+        }
+
+        let consumed_size = convert_bitfinex_amount(
+            self.symbol.size_tick(),
+            trade[4].as_f64().unwrap_or(0.)
+        )?;
+        let consumed_price = convert_bitfinex_amount(
+            self.symbol.price_tick(),
+            trade[5].as_f64().unwrap_or(0.)
+        )?;
+        let forward = self.flags.contains(NotificationFlags::ORDERS);
+
+        let state = match trade[3].as_u64().and_then(|id| self.orders.get_mut(&id)) {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        state.remaining_size = state.remaining_size.saturating_sub(consumed_size);
+
+        if forward {
+            let update = OrderUpdate {
+                order_id: state.order_id.clone(),
+                consumed_size,
+                remaining_size: state.remaining_size,
+                consumed_price,
+                commission: 0,
+            }.with_timestamp(trade[2].as_u64().unwrap_or_else(timestamp_ms));
+            out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_auth_message(&mut self, mut message: Vec<Value>, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        // `[0, TYPE, PAYLOAD]`
+        if message.len() < 3 {
+            return Ok(());
+        }
+
+        let kind = message[1].as_str().unwrap_or("").to_owned();
+        let payload = message.swap_remove(2);
+
+        match kind.as_str() {
+            "os" => {
+                let orders: Vec<Vec<Value>> = serde_json::from_value(payload)?;
+                for order in orders {
+                    self.parse_order("os", &order, out)?;
+                }
+            }
+
+            "on" | "oc" => {
+                let order: Vec<Value> = serde_json::from_value(payload)?;
+                self.parse_order(&kind, &order, out)?;
+            }
+
+            "te" => {
+                let trade: Vec<Value> = serde_json::from_value(payload)?;
+                self.parse_own_trade(&trade, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let value: Value = serde_json::from_str(json)?;
+
+        // Channel messages are arrays, whereas events are objects.
+        let mut message = match value {
+            Value::Array(message) => message,
+            _ => {
+                let event: BitfinexEvent<'_> = serde_json::from_str(json)?;
+                match event.event {
+                    "subscribed" => {
+                        let channel = match event.channel {
+                            Some("book") => Channel::Book,
+                            Some("trades") => Channel::Trades,
+                            _ => return Ok(()),
+                        };
+                        if let Some(chan_id) = event.chanId {
+                            self.channels.insert(chan_id, channel);
+                        }
+                    }
+                    "unsubscribed" => {
+                        if let Some(chan_id) = event.chanId {
+                            self.channels.remove(&chan_id);
+                        }
+                    }
+                    "auth" if event.status != Some("OK") => {
+                        bail!("authentication failed: {:?}", event.msg);
+                    }
+                    "error" => bail!("{:?}", event.msg),
+                    _ => (),
+                }
+                return Ok(());
+            }
+        };
+
+        if message.len() < 2 || message[1].as_str() == Some("hb") {
+            return Ok(());
+        }
+
+        let chan_id = match message[0].as_u64() {
+            Some(chan_id) => chan_id,
+            None => bail!("missing channel id: `{}`", json),
+        };
+
+        if chan_id == AUTH_CHANNEL {
+            return self.parse_auth_message(message, out);
+        }
+
+        match self.channels.get(&chan_id) {
+            Some(Channel::Book) if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book(message.swap_remove(1), out)?;
+            }
+
+            // Only forward the `te` messages: the trades snapshot is skipped, and `tu`
+            // messages duplicate the `te` ones.
+            Some(Channel::Trades) if self.flags.contains(NotificationFlags::TRADES) &&
+                message.len() >= 3 && message[1].as_str() == Some("te") =>
+            {
+                self.parse_trade(message.swap_remove(2), out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn send_subscription(&self, channel: Channel, out: &ws::Sender) -> ws::Result<()> {
+        let subscription = match channel {
+            Channel::Book => BitfinexSubscription {
+                event: "subscribe",
+                channel: "book",
+                symbol: &self.bitfinex_symbol,
+                prec: Some(self.book_precision.as_str()),
+                len: Some(BOOK_LENGTH),
+            },
+            Channel::Trades => BitfinexSubscription {
+                event: "subscribe",
+                channel: "trades",
+                symbol: &self.bitfinex_symbol,
+                prec: None,
+                len: None,
+            },
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `BitfinexSubscription`: `{}`", err);
+            }
+        }
+    }
+
+    fn send_unsubscription(&self, channel: Channel, out: &ws::Sender) -> ws::Result<()> {
+        let chan_id = self.channels.iter()
+            .find(|(_, c)| **c == channel)
+            .map(|(chan_id, _)| *chan_id);
+
+        // Not subscribed yet: the messages will be filtered out anyway.
+        let chan_id = match chan_id {
+            Some(chan_id) => chan_id,
+            None => return Ok(()),
+        };
+
+        let unsubscription = BitfinexUnsubscription {
+            event: "unsubscribe",
+            chanId: chan_id,
+        };
+
+        match serde_json::to_string(&unsubscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `BitfinexUnsubscription`: `{}`", err);
+            }
+        }
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.send_subscription(Channel::Book, out)?;
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            self.send_subscription(Channel::Trades, out)?;
+        }
+
+        if let Some(keys) = self.keys.as_ref() {
+            use openssl::{sign::Signer, hash::MessageDigest};
+
+            let nonce = {
+                let mut last = self.nonce.lock().unwrap();
+                *last = std::cmp::max(*last + 1, timestamp_ms() * 1000);
+                *last
+            };
+
+            let payload = format!("AUTH{}", nonce);
+            let mut signer = Signer::new(MessageDigest::sha384(), &keys.secret_key).unwrap();
+            signer.update(payload.as_bytes()).unwrap();
+
+            let auth = BitfinexAuth {
+                event: "auth",
+                apiKey: &keys.api_key,
+                authSig: hex::encode(&signer.sign_to_vec().unwrap()),
+                authPayload: payload,
+                authNonce: nonce,
+                filter: &["trading"],
+            };
+
+            match serde_json::to_string(&auth) {
+                Ok(value) => out.send(value)?,
+                Err(err) => {
+                    panic!("failed to serialize `BitfinexAuth`: `{}`", err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The authenticated channel cannot be unsubscribed from, `ORDERS` only acts
+        // as a filter.
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            // A new snapshot will be sent.
+            (false, true) => self.send_subscription(Channel::Book, out)?,
+            (true, false) => self.send_unsubscription(Channel::Book, out)?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => self.send_subscription(Channel::Trades, out)?,
+            (true, false) => self.send_unsubscription(Channel::Trades, out)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! A module defining error types specific to BitMEX.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by BitMEX REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Name of the error, e.g. `HTTPError` or `ValidationError`.
+    pub name: Option<String>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl RestError {
+    /// BitMEX does not report error codes, errors are told apart by their message.
+    fn has_message(&self, messages: &[&str]) -> bool {
+        match &self.message {
+            Some(message) => messages.iter().any(|m| message.contains(m)),
+            None => false,
+        }
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest
+            && self.has_message(&["Not Found", "Unable to cancel order"])
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest
+            && self.has_message(&["insufficient Available Balance"])
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " ({})", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// BitMEX reports errors as an `error` object, with a `name` and a `message`.
+    pub(super) fn from_bitmex_error(status: StatusCode, bitmex_error: Option<(String, String)>)
+        -> Self
+    {
+        let (name, message) = match bitmex_error {
+            Some((name, message)) => (Some(name), Some(message)),
+            None => (None, None),
+        };
+
+        RestError {
+            kind: RestErrorKind::from_status_code(status),
+            name,
+            message,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code to a BitMEX error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid api key or signature, expired request or missing permission.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by BitMEX. See BitMEX API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on BitMEX side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// The system is overloaded or under maintenance. The request was not processed.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+}
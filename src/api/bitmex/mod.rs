@@ -0,0 +1,244 @@
+//! Implementation of `ApiClient` for the BitMEX API, trading perpetual and dated
+//! futures.
+//!
+//! Orders and cancels are sent over REST, while the order book, trades, executions
+//! and positions are streamed over WebSocket.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::tick::{self, Tick, TickUnit};
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{timestamp_ms, Timestamped};
+
+/// Validity of signed requests, in seconds.
+const EXPIRATION: u64 = 10;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A BitMEX key pair: api key + secret key.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+impl Keys {
+    /// Signed HTTP requests and the authentication of the WebSocket connection both
+    /// carry the hex HMAC-SHA256 of the verb, the path along with the query string,
+    /// the expiration time and the body.
+    fn signature(&self, verb: &str, path: &str, expires: u64, body: &str) -> String {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.secret_key).unwrap();
+        let what = format!("{}{}{}{}", verb, path, expires, body);
+        signer.update(what.as_bytes()).unwrap();
+        hex::encode(&signer.sign_to_vec().unwrap())
+    }
+}
+
+/// Return the expiration time of a request signed now, in seconds.
+fn expires() -> u64 {
+    timestamp_ms() / 1000 + EXPIRATION
+}
+
+/// Amounts are sent as integers in the smallest unit of their currency, e.g. satoshis
+/// for `XBt`: return the usual name of the currency along with the tick of its amounts.
+fn convert_bitmex_currency(currency: &str) -> (String, Tick) {
+    match currency {
+        "XBt" => ("XBT".to_owned(), Tick::new(100_000_000)),
+        "USDt" => ("USDT".to_owned(), Tick::new(1_000_000)),
+        "Gwei" => ("ETH".to_owned(), Tick::new(1_000_000_000)),
+        other => (other.to_uppercase(), Tick::new(1)),
+    }
+}
+
+/// Convert a signed amount expressed in the smallest unit of `currency`.
+fn convert_bitmex_amount(currency: &str, amount: i64) -> Result<String, tick::ConversionError> {
+    let (_, tick) = convert_bitmex_currency(currency);
+    if amount < 0 {
+        Ok(format!("-{}", tick.unticked(-amount as TickUnit)?))
+    } else {
+        tick.unticked(amount as TickUnit)
+    }
+}
+
+/// Return the order id specified by the user, i.e. the `clOrdID`, which defaults to the
+/// server order id in case it was left empty.
+fn order_id(server_order_id: &str, client_order_id: Option<&str>) -> String {
+    match client_order_id {
+        Some(client_order_id) if !client_order_id.is_empty() => client_order_id.to_owned(),
+        _ => server_order_id.to_owned(),
+    }
+}
+
+/// A BitMEX API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Params {
+    /// Return the parameters of the BitMEX testnet, where keys are generated at
+    /// `https://testnet.bitmex.com`.
+    pub fn bitmex_testnet() -> Self {
+        Params {
+            streaming_endpoint: "wss://ws.testnet.bitmex.com/realtime".to_owned(),
+            rest_endpoint: "https://testnet.bitmex.com/api/v1".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new BitMEX API client with given `params`, e.g. with the usual
+    /// `https://www.bitmex.com/api/v1` and `wss://ws.bitmex.com/realtime` endpoints.
+    /// If `key_pair` is not `None`, this will enable performing requests to the REST
+    /// API and will forward the executions and position updates.
+    ///
+    /// # Note
+    /// This method will block, fetching the active instruments from BitMEX.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+        debug!("requesting instruments");
+        let symbols = runtime.block_on(client.get_instruments())?;
+        debug!("received instruments");
+        client.symbols = symbols;
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Bitmex
+    }
+
+    /// Instruments are found by their BitMEX name, e.g. `XBTUSD` or `XBTZ24`, case
+    /// insensitively.
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_uppercase()).cloned()
+    }
+
+    /// Position updates are only forwarded if the client was created with a key pair,
+    /// along with the `POSITIONS` flag.
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// `clOrdID` is limited to 36 characters by BitMEX.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 36 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use std::collections::HashMap;
+use log::{warn, error};
+use serde_derive::Deserialize;
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{convert_str_timestamp, Timestamp, Timestamped, IntoTimestamped};
+use crate::api::query_string::QueryString;
+use crate::api::bitmex::{Client, expires, convert_bitmex_currency, convert_bitmex_amount, order_id};
+use crate::api::bitmex::errors::RestError;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BitmexErrorData {
+    name: String,
+    message: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BitmexError {
+    error: BitmexErrorData,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BitmexOrderAck<'a> {
+    orderID: &'a str,
+    clOrdID: Option<&'a str>,
+    ordStatus: &'a str,
+    text: Option<String>,
+    transactTime: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BitmexCanceledOrder {
+    /// Set if the order could not be canceled, e.g. because it was already filled.
+    error: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BitmexTime {
+    timestamp: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BitmexMargin<'a> {
+    currency: &'a str,
+    walletBalance: Option<i64>,
+    availableMargin: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BitmexInstrument<'a> {
+    symbol: &'a str,
+    state: &'a str,
+    tickSize: f64,
+    lotSize: f64,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for TimeInForce {
+    fn as_str(self) -> &'static str {
+        match self {
+            TimeInForce::GoodTilCanceled => "GoodTillCancel",
+            TimeInForce::ImmediateOrCancel => "ImmediateOrCancel",
+            TimeInForce::FillOrKilll => "FillOrKill",
+            TimeInForce::GoodTilDate(_) => unreachable!("rejected by `order_impl`"),
+        }
+    }
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "Sell",
+            Side::Bid => "Buy",
+        }
+    }
+}
+
+/// Percent-encode `value` for use in a query string.
+fn url_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Increments are sent as JSON numbers, e.g. `0.5` or `100`. Increments of one or
+/// more are represented by a tick of one.
+fn convert_bitmex_increment(increment: f64) -> Option<Tick> {
+    if increment >= 1. {
+        return Some(Tick::new(1));
+    }
+    Tick::tick_size(&format!("{:.12}", increment))
+}
+
+impl Client {
+    /// Parameters are carried by the query string for GET requests and by a form
+    /// encoded body otherwise. If a key pair was provided, requests are signed: the
+    /// signature covers the method, the path along with the query string, the
+    /// expiration time and the body.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let (address, body) = if method == Method::GET && !query.is_empty() {
+            (format!("{}/{}?{}", self.params.rest_endpoint, endpoint, query), String::new())
+        } else {
+            (format!("{}/{}", self.params.rest_endpoint, endpoint), query)
+        };
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let path = address.parse::<hyper::Uri>()
+                .ok()
+                .and_then(|uri| uri.path_and_query().map(|path| path.as_str().to_owned()))
+                .unwrap_or_default();
+            let expires = expires();
+            let signature = keys.signature(method.as_str(), &path, expires, &body);
+
+            request.header("api-expires", format!("{}", expires).as_bytes())
+                .header("api-key", keys.api_key.as_bytes())
+                .header("api-signature", signature.as_bytes());
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/x-www-form-urlencoded"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let bitmex_error = serde_json::from_slice(&body)
+                    .ok()
+                    .map(|error: BitmexError| (error.error.name, error.error.message));
+                let error = RestError::from_bitmex_error(status, bitmex_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.time_in_force.expiration().is_some() {
+            warn!("called `order` with `GoodTilDate`, which is not supported by BitMEX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by BitMEX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by BitMEX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let mut exec_inst = Vec::new();
+        match (order.type_, order.time_in_force) {
+            (OrderType::Limit, _) => (),
+
+            // Post only orders which would take liquidity are canceled by BitMEX.
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => {
+                exec_inst.push("ParticipateDoNotInitiate");
+            }
+
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by BitMEX",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        }
+
+        if order.reduce_only {
+            exec_inst.push("ReduceOnly");
+        }
+
+        let mut query = QueryString::new();
+        let symbol = order.symbol();
+        query.push_str("symbol", symbol.name());
+        query.push_str("side", order.side.as_str());
+        query.push_str("orderQty", order.size.unticked(symbol.size_tick()).borrow());
+        query.push_str("price", order.price.unticked(symbol.price_tick()).borrow());
+        query.push_str("ordType", "Limit");
+        query.push_str("timeInForce", order.time_in_force.as_str());
+        if !exec_inst.is_empty() {
+            query.push_str("execInst", &url_encode(&exec_inst.join(",")));
+        }
+
+        // Orders are tracked by their `clOrdID`, which is always sent.
+        let client_order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+        query.push_str("clOrdID", &url_encode(&client_order_id));
+
+        let post_only = order.type_ == OrderType::LimitMaker;
+        let fut = self.request("order", Method::POST, query).and_then(move |body| {
+            let ack: BitmexOrderAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Rejected orders are still acknowledged, along with an explanation.
+            match ack.ordStatus {
+                "Canceled" if post_only => {
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::Specific(
+                            api::errors::OrderErrorKind::WouldTakeLiquidity
+                        ).into()
+                    ));
+                }
+                "Rejected" => {
+                    warn!("order rejected by BitMEX: `{}`", ack.text.unwrap_or_default());
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::InvalidRequest.into()
+                    ));
+                }
+                _ => (),
+            }
+
+            let timestamp = convert_str_timestamp(ack.transactTime)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(OrderAck {
+                order_id: order_id(ack.orderID, ack.clOrdID),
+            }.with_timestamp(timestamp))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("clOrdID", &url_encode(&cancel.order_id));
+
+        self.request("order", Method::DELETE, query).and_then(|body| {
+            let canceled: Vec<BitmexCanceledOrder> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Orders which cannot be canceled are returned along with an error.
+            match canceled.first() {
+                Some(order) if order.error.is_none() => Ok(CancelAck.timestamped()),
+                _ => Err(api::errors::ApiError::RestError(
+                    api::errors::RestErrorKind::Specific(
+                        api::errors::CancelErrorKind::UnknownOrder
+                    ).into()
+                )),
+            }
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("", Method::GET, QueryString::new()).and_then(|body| {
+            let time: BitmexTime = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(time.timestamp))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("currency", "all");
+
+        self.request("user/margin", Method::GET, query).and_then(|body| {
+            let margins: Vec<BitmexMargin<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // The part of the wallet balance which is not available is reserved by the
+            // open orders and positions.
+            let mut balances = Balances::new();
+            for margin in margins {
+                let total = margin.walletBalance.unwrap_or(0);
+                let available = margin.availableMargin.unwrap_or(0);
+                let (free, locked) = convert_bitmex_amount(margin.currency, available)
+                    .and_then(|free| {
+                        let locked = (total - available).max(0);
+                        Ok((free, convert_bitmex_amount(margin.currency, locked)?))
+                    })
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                let (currency, _) = convert_bitmex_currency(margin.currency);
+                balances.insert(currency, Balance {
+                    free,
+                    locked,
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        // BitMEX has no status endpoint, being able to reach it is the best we can do.
+        self.request("", Method::GET, QueryString::new()).and_then(|_| {
+            Ok(ExchangeStatus::Normal.timestamped())
+        })
+    }
+
+    crate fn get_instruments(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("instrument/active", Method::GET, QueryString::new()).and_then(|body| {
+            let instruments: Vec<BitmexInstrument<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            for instrument in instruments {
+                if instrument.state != "Open" {
+                    continue;
+                }
+
+                let (price_tick, size_tick) = match (
+                    convert_bitmex_increment(instrument.tickSize),
+                    convert_bitmex_increment(instrument.lotSize)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", instrument.symbol);
+                        continue;
+                    }
+                };
+
+                match Symbol::new(instrument.symbol, price_tick, size_tick) {
+                    Some(symbol) => {
+                        symbols.insert(instrument.symbol.to_uppercase(), symbol);
+                    }
+                    None => error!("symbol name too long: `{}`", instrument.symbol),
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
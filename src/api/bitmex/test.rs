@@ -0,0 +1,80 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::bitmex::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+const TIMESTAMP: u64 = 1_546_300_800_000;
+
+fn symbol() -> Symbol {
+    Symbol::new("XBTUSD", Tick::new(2), Tick::new(1)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[String]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+fn book(action: &str, levels: &str) -> String {
+    format!(r#"{{"table":"orderBookL2","action":"{}","data":[{}]}}"#, action, levels)
+}
+
+fn level(id: u64, side: &str, size: Option<u64>, price: Option<f64>, second: u64) -> String {
+    let mut level = format!(r#"{{"symbol":"XBTUSD","id":{},"side":"{}""#, id, side);
+    if let Some(size) = size {
+        level += &format!(r#","size":{}"#, size);
+    }
+    if let Some(price) = price {
+        level += &format!(r#","price":{}"#, price);
+    }
+    level + &format!(r#","timestamp":"2019-01-01T00:00:0{}.000Z"}}"#, second)
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let frames = [
+        // Updates are ignored until the snapshot.
+        book("update", &level(8_799_500_000, "Sell", Some(300), None, 0)),
+        book("partial", &format!(
+            "{},{}",
+            level(8_799_500_000, "Sell", Some(200), Some(5000.), 0),
+            level(8_799_500_050, "Buy", Some(100), Some(4999.5), 0)
+        )),
+
+        // Levels are identified by their id, prices are omitted from the updates.
+        book("update", &level(8_799_500_000, "Sell", Some(150), None, 1)),
+        book("delete", &level(8_799_500_050, "Buy", None, None, 2)),
+        book("insert", &level(8_799_500_100, "Buy", Some(50), Some(4999.), 3)),
+    ];
+
+    let notifs = parse(&frames);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(10_000, 200, Side::Ask).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(9_999, 100, Side::Bid).with_timestamp(TIMESTAMP),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(10_000, 150, Side::Ask).with_timestamp(TIMESTAMP + 1000),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(9_999, 0, Side::Bid).with_timestamp(TIMESTAMP + 2000),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(9_998, 50, Side::Bid).with_timestamp(TIMESTAMP + 3000),
+        ]),
+    ]);
+}
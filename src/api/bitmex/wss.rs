@@ -0,0 +1,581 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::collections::HashMap;
+use std::{mem, thread};
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::derivatives::Position;
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{convert_str_timestamp, timestamp_ms, IntoTimestamped};
+use crate::api::bitmex::{Client, Keys, expires, convert_bitmex_amount, order_id};
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let endpoint = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", endpoint);
+
+            if let Err(err) = ws::connect(endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        out: None,
+                        authenticated: false,
+                        levels: HashMap::new(),
+                        snapshot_received: false,
+                        position: PositionState::default(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Public and private topics are subscribed to over the same connection, the private
+/// ones once the connection is authenticated.
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Set once the connection is open, needed for resubscribing to the book from
+    /// within `on_message`.
+    out: Option<ws::Sender>,
+
+    authenticated: bool,
+
+    /// `orderBookL2` identifies each level by an id, and may omit the price of the
+    /// updated levels: keep track of the side and price of each id, so that levels can
+    /// be converted to `LimitUpdate`.
+    levels: HashMap<u64, (Side, TickUnit)>,
+
+    /// Updates are ignored until a snapshot is received.
+    snapshot_received: bool,
+
+    /// The `position` topic only sends the fields which have changed.
+    position: PositionState,
+}
+
+#[derive(Clone, PartialEq, Default, Debug)]
+struct PositionState {
+    currency: String,
+    current_qty: f64,
+    avg_entry_price: Option<f64>,
+    unrealised_pnl: i64,
+    leverage: f64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct BitmexRequest<'a, T> {
+    op: &'a str,
+    args: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BitmexRequestEcho<'a> {
+    op: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BitmexEvent<'a> {
+    success: Option<bool>,
+    error: Option<String>,
+    #[serde(borrow)]
+    request: Option<BitmexRequestEcho<'a>>,
+    table: Option<&'a str>,
+    action: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct BitmexTable<T> {
+    data: Vec<T>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct BitmexLevel<'a> {
+    id: u64,
+    side: &'a str,
+    size: Option<f64>,
+    price: Option<f64>,
+    timestamp: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct BitmexTrade<'a> {
+    timestamp: &'a str,
+    side: &'a str,
+    size: f64,
+    price: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BitmexExecution<'a> {
+    execType: &'a str,
+    orderID: &'a str,
+    clOrdID: Option<&'a str>,
+    side: Option<&'a str>,
+    orderQty: Option<f64>,
+    price: Option<f64>,
+    lastQty: Option<f64>,
+    lastPx: Option<f64>,
+    leavesQty: Option<f64>,
+    cumQty: Option<f64>,
+    execComm: Option<i64>,
+    settlCurrency: Option<&'a str>,
+    transactTime: &'a str,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BitmexPosition<'a> {
+    symbol: &'a str,
+    currency: Option<&'a str>,
+    currentQty: Option<f64>,
+    avgEntryPrice: Option<f64>,
+    unrealisedPnl: Option<i64>,
+    leverage: Option<f64>,
+    timestamp: Option<&'a str>,
+}
+
+fn book_topic(symbol: &Symbol) -> String {
+    format!("orderBookL2:{}", symbol.name())
+}
+
+fn trade_topic(symbol: &Symbol) -> String {
+    format!("trade:{}", symbol.name())
+}
+
+fn execution_topic(symbol: &Symbol) -> String {
+    format!("execution:{}", symbol.name())
+}
+
+fn position_topic(symbol: &Symbol) -> String {
+    format!("position:{}", symbol.name())
+}
+
+/// Prices and sizes are sent as JSON numbers.
+fn convert_bitmex_number(tick: Tick, value: f64) -> Result<TickUnit, failure::Error> {
+    Ok(tick.ticked(&format!("{}", value))?)
+}
+
+fn convert_bitmex_side(side: &str) -> Result<Side, failure::Error> {
+    match side {
+        "Buy" => Ok(Side::Bid),
+        "Sell" => Ok(Side::Ask),
+        other => bail!("wrong side: `{}`", other),
+    }
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys and which is not connected,
+    /// for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: None,
+            out: None,
+            authenticated: false,
+            levels: HashMap::new(),
+            snapshot_received: false,
+            position: PositionState::default(),
+        }
+    }
+
+    fn send_request<T: serde::Serialize>(&mut self, op: &str, args: T) -> ws::Result<()> {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        let request = BitmexRequest {
+            op,
+            args,
+        };
+
+        match serde_json::to_string(&request) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `BitmexRequest`: `{}`", err);
+            }
+        }
+    }
+
+    fn subscribe(&mut self, op: &str, topics: &[String]) -> ws::Result<()> {
+        if topics.is_empty() {
+            return Ok(());
+        }
+        self.send_request(op, topics)
+    }
+
+    /// Return the topics enabled by `flags`, the private ones only if the connection is
+    /// authenticated.
+    fn topics(&self, flags: NotificationFlags) -> Vec<String> {
+        let mut topics = Vec::new();
+        if flags.contains(NotificationFlags::ORDER_BOOK) {
+            topics.push(book_topic(&self.symbol));
+        }
+        if flags.contains(NotificationFlags::TRADES) {
+            topics.push(trade_topic(&self.symbol));
+        }
+        if self.authenticated && flags.contains(NotificationFlags::ORDERS) {
+            topics.push(execution_topic(&self.symbol));
+        }
+        if self.authenticated && flags.contains(NotificationFlags::POSITIONS) {
+            topics.push(position_topic(&self.symbol));
+        }
+        topics
+    }
+
+    /// The signature covers the verb and path of the WebSocket endpoint along with an
+    /// expiration time, so that the secret key is never sent.
+    fn authenticate(&mut self) -> ws::Result<()> {
+        let keys = match self.keys.clone() {
+            Some(keys) => keys,
+            None => return Ok(()),
+        };
+
+        let expires = expires();
+        let signature = keys.signature("GET", "/realtime", expires, "");
+        self.send_request("authKeyExpires", (&keys.api_key, expires, signature))
+    }
+
+    /// Convert a level of `orderBookL2`, keeping `self.levels` up to date.
+    fn convert_bitmex_level(&mut self, level: &BitmexLevel<'_>, action: &str)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        let levels = &mut self.levels;
+        let (side, price) = match level.price {
+            Some(price) => (
+                convert_bitmex_side(level.side)?,
+                convert_bitmex_number(self.symbol.price_tick(), price)?
+            ),
+            None => match levels.get(&level.id) {
+                Some(level) => *level,
+                None => bail!("unknown level id: `{}`", level.id),
+            },
+        };
+
+        let size = match (action, level.size) {
+            ("delete", _) => {
+                levels.remove(&level.id);
+                0
+            }
+            (_, Some(size)) => {
+                levels.insert(level.id, (side, price));
+                convert_bitmex_number(self.symbol.size_tick(), size)?
+            }
+            (_, None) => bail!("missing size for level id: `{}`", level.id),
+        };
+
+        Ok(LimitUpdate {
+            side,
+            price,
+            size,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, action: &str, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let book: BitmexTable<BitmexLevel<'_>> = serde_json::from_str(json)?;
+
+        let mut updates = Vec::new();
+        match action {
+            // A snapshot replaces the whole book: levels which are not part of it anymore
+            // are removed.
+            "partial" => {
+                self.snapshot_received = true;
+                let levels = HashMap::with_capacity(book.data.len());
+                let previous = mem::replace(&mut self.levels, levels);
+                let timestamp = timestamp_ms();
+                for (id, (side, price)) in previous {
+                    if book.data.iter().all(|level| level.id != id) {
+                        updates.push(LimitUpdate {
+                            side,
+                            price,
+                            size: 0,
+                        }.with_timestamp(timestamp));
+                    }
+                }
+            }
+
+            // Still waiting for the snapshot.
+            _ if !self.snapshot_received => return Ok(()),
+
+            _ => (),
+        }
+
+        for level in &book.data {
+            let update = match self.convert_bitmex_level(level, action) {
+                Ok(update) => update,
+
+                // The book is not consistent anymore, start again from a new snapshot.
+                Err(err) => {
+                    self.snapshot_received = false;
+                    let topics = [book_topic(&self.symbol)];
+                    self.subscribe("unsubscribe", &topics)?;
+                    self.subscribe("subscribe", &topics)?;
+                    return Err(err);
+                }
+            };
+
+            let timestamp = match level.timestamp {
+                Some(timestamp) => convert_str_timestamp(timestamp)?,
+                None => timestamp_ms(),
+            };
+            updates.push(update.with_timestamp(timestamp));
+        }
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trades: BitmexTable<BitmexTrade<'_>> = serde_json::from_str(json)?;
+
+        for trade in trades.data {
+            // The side is the one of the taker.
+            let maker_side = match convert_bitmex_side(trade.side)? {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid,
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: convert_bitmex_number(self.symbol.price_tick(), trade.price)?,
+                size: convert_bitmex_number(self.symbol.size_tick(), trade.size)?,
+                maker_side,
+            }.with_timestamp(convert_str_timestamp(trade.timestamp)?));
+
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_executions(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let executions: BitmexTable<BitmexExecution<'_>> = serde_json::from_str(json)?;
+        let price_tick = self.symbol.price_tick();
+        let size_tick = self.symbol.size_tick();
+
+        for execution in executions.data {
+            let timestamp = convert_str_timestamp(execution.transactTime)?;
+            let order_id = order_id(execution.orderID, execution.clOrdID);
+            let size = convert_bitmex_number(size_tick, execution.orderQty.unwrap_or(0.))?;
+            let filled_size = convert_bitmex_number(size_tick, execution.cumQty.unwrap_or(0.))?;
+
+            let notif = match execution.execType {
+                "New" => Notification::OrderConfirmation(OrderConfirmation {
+                    order_id,
+                    price: convert_bitmex_number(price_tick, execution.price.unwrap_or(0.))?,
+                    size,
+                    side: convert_bitmex_side(execution.side.unwrap_or_default())?,
+                }.with_timestamp(timestamp)),
+
+                // The commission is paid in the settlement currency, and is negative
+                // for rebates.
+                "Trade" => {
+                    let commission = convert_bitmex_amount(
+                        execution.settlCurrency.unwrap_or_default(),
+                        execution.execComm.unwrap_or(0).abs()
+                    )?;
+
+                    Notification::OrderUpdate(OrderUpdate {
+                        order_id,
+                        consumed_size: convert_bitmex_number(
+                            size_tick,
+                            execution.lastQty.unwrap_or(0.)
+                        )?,
+                        remaining_size: convert_bitmex_number(
+                            size_tick,
+                            execution.leavesQty.unwrap_or(0.)
+                        )?,
+                        consumed_price: convert_bitmex_number(
+                            price_tick,
+                            execution.lastPx.unwrap_or(0.)
+                        )?,
+                        commission: self.symbol.commission_tick().ticked(&commission)?,
+                    }.with_timestamp(timestamp))
+                }
+
+                // `leavesQty` is zeroed once the order is canceled.
+                "Canceled" => Notification::OrderExpiration(OrderExpiration {
+                    order_id,
+                    filled_size: Some(filled_size),
+                    remaining_size: Some(size.saturating_sub(filled_size)),
+                }.with_timestamp(timestamp)),
+
+                // Funding, settlement, amends...
+                _ => continue,
+            };
+            out.unbounded_send(notif).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_positions(&mut self, json: &str, action: &str, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let positions: BitmexTable<BitmexPosition<'_>> = serde_json::from_str(json)?;
+
+        if action == "partial" {
+            self.position = PositionState::default();
+        }
+
+        for position in positions.data {
+            if position.symbol != self.symbol.name() {
+                continue;
+            }
+
+            let state = &mut self.position;
+            if let Some(currency) = position.currency {
+                state.currency = currency.to_owned();
+            }
+            if let Some(current_qty) = position.currentQty {
+                state.current_qty = current_qty;
+            }
+            if position.avgEntryPrice.is_some() {
+                state.avg_entry_price = position.avgEntryPrice;
+            }
+            if let Some(unrealised_pnl) = position.unrealisedPnl {
+                state.unrealised_pnl = unrealised_pnl;
+            }
+            if let Some(leverage) = position.leverage {
+                state.leverage = leverage;
+            }
+
+            let timestamp = match position.timestamp {
+                Some(timestamp) => convert_str_timestamp(timestamp)?,
+                None => timestamp_ms(),
+            };
+
+            let position = Position {
+                symbol: self.symbol.name().to_owned(),
+                side: if state.current_qty < 0. { Side::Ask } else { Side::Bid },
+                size: format!("{}", state.current_qty.abs()),
+                entry_price: format!("{}", state.avg_entry_price.unwrap_or(0.)),
+                unrealized_pnl: convert_bitmex_amount(&state.currency, state.unrealised_pnl)?,
+                leverage: state.leverage.round() as u32,
+            }.with_timestamp(timestamp);
+            out.unbounded_send(Notification::PositionUpdate(position)).unwrap();
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: BitmexEvent<'_> = serde_json::from_str(json)?;
+
+        if let Some(error) = event.error {
+            bail!("{}", error);
+        }
+
+        // Responses to our requests: private topics are only accepted once
+        // authenticated.
+        if let (Some(true), Some(request)) = (event.success, event.request) {
+            if request.op == "authKeyExpires" {
+                self.authenticated = true;
+                let mut topics = Vec::new();
+                if self.flags.contains(NotificationFlags::ORDERS) {
+                    topics.push(execution_topic(&self.symbol));
+                }
+                if self.flags.contains(NotificationFlags::POSITIONS) {
+                    topics.push(position_topic(&self.symbol));
+                }
+                self.subscribe("subscribe", &topics)?;
+            }
+            return Ok(());
+        }
+
+        let (table, action) = match (event.table, event.action) {
+            (Some(table), Some(action)) => (table, action),
+            _ => return Ok(()),
+        };
+
+        match table {
+            "orderBookL2" if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book(json, action, out)?;
+            }
+            "trade" if self.flags.contains(NotificationFlags::TRADES) && action == "insert" => {
+                self.parse_trades(json, out)?;
+            }
+            "execution" if self.flags.contains(NotificationFlags::ORDERS) && action == "insert" => {
+                self.parse_executions(json, out)?;
+            }
+            "position" if self.flags.contains(NotificationFlags::POSITIONS) => {
+                self.parse_positions(json, action, out)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        let topics = self.topics(self.flags);
+        self.subscribe("subscribe", &topics)?;
+        self.authenticate()
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+        let (previous, current) = (self.topics(previous), self.topics(flags));
+
+        let subscribe = current.iter()
+            .filter(|topic| !previous.contains(topic))
+            .cloned()
+            .collect::<Vec<_>>();
+        let unsubscribe = previous.iter()
+            .filter(|topic| !current.contains(topic))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // A new subscription starts with a new snapshot.
+        if subscribe.contains(&book_topic(&self.symbol)) {
+            self.snapshot_received = false;
+        }
+
+        self.subscribe("unsubscribe", &unsubscribe)?;
+        self.subscribe("subscribe", &subscribe)
+    }
+}
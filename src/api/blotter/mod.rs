@@ -0,0 +1,156 @@
+//! A module defining a blotter, i.e. the state of one symbol as seen by a trader: the
+//! live order book, the most recent trades, and the open orders and positions of the
+//! account, all maintained from a notification stream.
+//!
+//! The blotter only reflects the notifications it is fed: open orders and positions
+//! prior to the subscription are unknown, unless the exchange sends a snapshot of them
+//! on the user stream. The `trade-tui` viewer, behind the `tui` feature, renders it.
+
+mod test;
+
+use std::collections::VecDeque;
+use crate::Side;
+use crate::api::{Notification, Trade};
+use crate::api::derivatives::Position;
+use crate::api::timestamp::{Timestamp, Timestamped};
+use crate::order_book::OrderBook;
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// An open order of the account, as tracked by a `Blotter`.
+pub struct OpenOrder {
+    /// Unique order id.
+    pub order_id: String,
+
+    /// Side of the order.
+    pub side: Side,
+
+    /// Price at which the order was inserted, zero for market orders.
+    pub price: TickUnit,
+
+    /// Size at which the order was inserted.
+    pub size: TickUnit,
+
+    /// Size which remains to be filled.
+    pub remaining_size: TickUnit,
+
+    /// Timestamp of the confirmation of the order.
+    pub timestamp: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// Maintain the order book, recent trades, open orders and positions of a symbol from
+/// a sequence of notifications.
+pub struct Blotter {
+    order_book: OrderBook,
+    trades: VecDeque<Timestamped<Trade>>,
+    max_trades: usize,
+    orders: Vec<OpenOrder>,
+    positions: Vec<Position>,
+}
+
+impl Blotter {
+    /// Return a new, empty `Blotter` keeping the `max_trades` most recent trades.
+    pub fn new(max_trades: usize) -> Self {
+        Blotter {
+            order_book: OrderBook::new(),
+            trades: VecDeque::new(),
+            max_trades,
+            orders: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    /// Return the maintained order book.
+    pub fn order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    /// Return an iterator over the recent trades, most recent first.
+    pub fn trades(&self) -> impl Iterator<Item = &Timestamped<Trade>> {
+        self.trades.iter()
+    }
+
+    /// Return the open orders, in the order they were confirmed.
+    pub fn open_orders(&self) -> &[OpenOrder] {
+        &self.orders
+    }
+
+    /// Return the open positions, in the order they were first reported.
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    /// Process a notification. Notifications which do not affect the blotter are
+    /// ignored.
+    pub fn on_notification(&mut self, notif: &Notification) {
+        match notif {
+            Notification::LimitUpdates(updates) => {
+                for update in updates {
+                    self.order_book.update(**update);
+                }
+            }
+
+            Notification::Trade(trade) => {
+                self.trades.push_front(*trade);
+                self.trades.truncate(self.max_trades);
+            }
+
+            Notification::OrderConfirmation(confirmation) => {
+                // Exchanges may confirm an order again in a snapshot of the open orders.
+                if self.orders.iter().any(|order| order.order_id == confirmation.order_id) {
+                    return;
+                }
+                self.orders.push(OpenOrder {
+                    order_id: confirmation.order_id.clone(),
+                    side: confirmation.side,
+                    price: confirmation.price,
+                    size: confirmation.size,
+                    remaining_size: confirmation.size,
+                    timestamp: confirmation.timestamp(),
+                });
+            }
+
+            Notification::OrderUpdate(update) => {
+                let index = self.orders.iter().position(|order| order.order_id == update.order_id);
+                if let Some(index) = index {
+                    if update.remaining_size == 0 {
+                        self.orders.remove(index);
+                    } else {
+                        self.orders[index].remaining_size = update.remaining_size;
+                    }
+                }
+            }
+
+            Notification::OrderExpiration(expiration) => {
+                self.orders.retain(|order| order.order_id != expiration.order_id);
+            }
+
+            Notification::PositionUpdate(position) => self.on_position((**position).clone()),
+
+            _ => (),
+        }
+    }
+
+    /// Record the open positions retrieved over REST, e.g. through
+    /// `DerivativesApi::positions`, on top of those notified so far.
+    pub fn on_positions(&mut self, positions: Vec<Position>) {
+        for position in positions {
+            self.on_position(position);
+        }
+    }
+
+    fn on_position(&mut self, position: Position) {
+        let index = self.positions.iter().position(|p| p.symbol == position.symbol);
+        let closed = position.size.parse::<f64>().map(|size| size == 0.).unwrap_or(false);
+
+        match (index, closed) {
+            (Some(index), true) => {
+                self.positions.remove(index);
+            }
+            (Some(index), false) => self.positions[index] = position,
+            (None, true) => (),
+            (None, false) => self.positions.push(position),
+        }
+    }
+}
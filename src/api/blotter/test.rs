@@ -0,0 +1,110 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{
+    Notification,
+    Trade,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+};
+use crate::api::blotter::{Blotter, OpenOrder};
+use crate::api::derivatives::Position;
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn trade(timestamp: Timestamp, price: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+fn confirmation(order_id: &str, timestamp: Timestamp) -> Notification {
+    Notification::OrderConfirmation(OrderConfirmation {
+        order_id: order_id.to_owned(),
+        price: 100,
+        size: 5,
+        side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+fn fill(order_id: &str, remaining: TickUnit) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: 1,
+        remaining_size: remaining,
+        consumed_price: 100,
+        commission: 0,
+    }.with_timestamp(0))
+}
+
+fn position(symbol: &str, size: &str) -> Position {
+    Position {
+        symbol: symbol.to_owned(),
+        side: Side::Bid,
+        size: size.to_owned(),
+        entry_price: "100".to_owned(),
+        unrealized_pnl: "0".to_owned(),
+        leverage: 1,
+    }
+}
+
+#[test]
+fn book_and_trades() {
+    let mut blotter = Blotter::new(2);
+    blotter.on_notification(&Notification::LimitUpdates(vec![
+        LimitUpdate::new(99, 3, Side::Bid).with_timestamp(0),
+        LimitUpdate::new(101, 4, Side::Ask).with_timestamp(0),
+    ]));
+    assert_eq!(blotter.order_book().best_bid(), 99);
+    assert_eq!(blotter.order_book().best_ask(), 101);
+
+    for (timestamp, price) in &[(10, 99), (20, 100), (30, 101)] {
+        blotter.on_notification(&trade(*timestamp, *price));
+    }
+    let prices: Vec<_> = blotter.trades().map(|trade| trade.price).collect();
+    assert_eq!(prices, vec![101, 100]);
+}
+
+#[test]
+fn open_orders() {
+    let mut blotter = Blotter::new(10);
+    blotter.on_notification(&confirmation("a", 10));
+    blotter.on_notification(&confirmation("b", 20));
+
+    // Confirmed again, e.g. in a snapshot.
+    blotter.on_notification(&confirmation("a", 30));
+
+    blotter.on_notification(&fill("a", 4));
+    blotter.on_notification(&fill("b", 0));
+    assert_eq!(blotter.open_orders(), &[OpenOrder {
+        order_id: "a".to_owned(),
+        side: Side::Bid,
+        price: 100,
+        size: 5,
+        remaining_size: 4,
+        timestamp: 10,
+    }]);
+
+    blotter.on_notification(&Notification::OrderExpiration(OrderExpiration {
+        order_id: "a".to_owned(),
+        filled_size: Some(1),
+        remaining_size: Some(4),
+    }.with_timestamp(40)));
+    assert!(blotter.open_orders().is_empty());
+}
+
+#[test]
+fn positions() {
+    let mut blotter = Blotter::new(10);
+    blotter.on_positions(vec![position("BTCUSDT", "1"), position("ETHUSDT", "2")]);
+
+    blotter.on_notification(&Notification::PositionUpdate(position("BTCUSDT", "0.5").with_timestamp(0)));
+    blotter.on_notification(&Notification::PositionUpdate(position("ETHUSDT", "0").with_timestamp(0)));
+    blotter.on_notification(&Notification::PositionUpdate(position("XRPUSDT", "0.0").with_timestamp(0)));
+
+    assert_eq!(blotter.positions(), &[position("BTCUSDT", "0.5")]);
+}
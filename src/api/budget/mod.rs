@@ -0,0 +1,310 @@
+//! A module defining a global memory budget for the notifications queued by the
+//! streams of this crate, see `set_memory_budget`.
+//!
+//! Streams are backed by unbounded channels: a consumer not keeping up with the
+//! exchange lets notifications pile up until the process runs out of memory. Under a
+//! budget, the footprint of the notifications queued and not delivered yet is
+//! accounted across all the streams, which degrade in two stages:
+//! * from half of the budget, limit updates are conflated: instead of being queued,
+//!   they are merged per price level into a pending batch, delivered once the consumer
+//!   has caught up with the rest of its queue
+//! * from the budget on, trades and index prices are dropped
+//!
+//! Orders, positions and exchange statuses are never shed, since they carry the state
+//! of the account. Conflated limit updates may be delivered after trades which
+//! followed them, but they keep their order with respect to the other limit updates.
+//!
+//! Footprints are estimates, which do not account for the overhead of the allocator.
+
+mod test;
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::task::{self, Task};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::Notification;
+use crate::api::timestamp::Timestamped;
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Statistics about the memory budget, see `stats`.
+pub struct BudgetStats {
+    /// Current budget in bytes, or `None` if unlimited.
+    pub limit: Option<usize>,
+
+    /// Estimated footprint in bytes of the notifications queued and not delivered yet.
+    pub queued: usize,
+
+    /// Highest value reached by `queued`.
+    pub peak: usize,
+
+    /// Number of limit updates merged into a later update of the same level.
+    pub conflated: usize,
+
+    /// Number of notifications dropped.
+    pub shed: usize,
+}
+
+/// Set the memory budget shared by all the streams, in bytes, or remove it with
+/// `None`. Takes effect with the next notification of each stream.
+pub fn set_memory_budget(bytes: Option<usize>) {
+    global().set_limit(bytes);
+}
+
+/// Return the current memory budget, in bytes, or `None` if unlimited.
+pub fn memory_budget() -> Option<usize> {
+    global().limit()
+}
+
+/// Return statistics about the memory budget. The footprint of the queued
+/// notifications is accounted even without a budget.
+pub fn stats() -> BudgetStats {
+    global().stats()
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+/// Degradation stage of the streams, see the module documentation.
+crate enum Pressure {
+    Normal,
+    Conflate,
+    Shed,
+}
+
+/// Accounting of the footprint of the queued notifications against a limit.
+#[derive(Debug, Default)]
+crate struct Budget {
+    /// Limit in bytes, or `0` if unlimited.
+    limit: AtomicUsize,
+    queued: AtomicUsize,
+    peak: AtomicUsize,
+    conflated: AtomicUsize,
+    shed: AtomicUsize,
+}
+
+static GLOBAL: Budget = Budget::new();
+
+/// Budget shared by all the streams.
+crate fn global() -> &'static Budget {
+    &GLOBAL
+}
+
+impl Budget {
+    crate const fn new() -> Self {
+        Budget {
+            limit: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            conflated: AtomicUsize::new(0),
+            shed: AtomicUsize::new(0),
+        }
+    }
+
+    crate fn set_limit(&self, bytes: Option<usize>) {
+        self.limit.store(bytes.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    crate fn limit(&self) -> Option<usize> {
+        match self.limit.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    crate fn pressure(&self) -> Pressure {
+        let limit = match self.limit() {
+            Some(limit) => limit,
+            None => return Pressure::Normal,
+        };
+
+        let queued = self.queued.load(Ordering::Relaxed);
+        if queued >= limit {
+            Pressure::Shed
+        } else if queued >= limit / 2 {
+            Pressure::Conflate
+        } else {
+            Pressure::Normal
+        }
+    }
+
+    fn acquire(&self, bytes: usize) {
+        let queued = self.queued.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let mut peak = self.peak.load(Ordering::Relaxed);
+        while queued > peak {
+            match self.peak.compare_exchange_weak(peak, queued, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => peak = current,
+            }
+        }
+    }
+
+    fn release(&self, bytes: usize) {
+        self.queued.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    crate fn stats(&self) -> BudgetStats {
+        BudgetStats {
+            limit: self.limit(),
+            queued: self.queued.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            conflated: self.conflated.load(Ordering::Relaxed),
+            shed: self.shed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Estimated footprint of `notif` in bytes, heap allocations included.
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+crate fn footprint(notif: &Notification) -> usize {
+    let heap = match notif {
+        Notification::LimitUpdates(updates) => {
+            updates.capacity() * mem::size_of::<Timestamped<LimitUpdate>>()
+        }
+        Notification::OrderConfirmation(confirmation) => confirmation.order_id.capacity(),
+        Notification::OrderUpdate(update) => update.order_id.capacity(),
+        Notification::OrderExpiration(expiration) => expiration.order_id.capacity(),
+        _ => 0,
+    };
+    mem::size_of::<Notification>() + heap
+}
+
+#[derive(Debug, Default)]
+/// Degradation state of a stream, shared by its connections and the stream itself.
+crate struct Shedder {
+    /// Footprint of the notifications of this stream queued and not delivered yet.
+    queued: usize,
+
+    /// Conflated limit updates, in the order their level was first updated.
+    pending: Vec<Timestamped<LimitUpdate>>,
+
+    /// (side, price) => index in `pending`
+    levels: HashMap<(Side, TickUnit), usize>,
+
+    conflated: usize,
+    shed: usize,
+
+    /// Set once the stream is dropped, after which nothing is accounted anymore.
+    closed: bool,
+
+    /// Consumer of the stream, waiting for notifications.
+    waiting: Option<Task>,
+}
+
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+impl Shedder {
+    crate fn new() -> Self {
+        Shedder::default()
+    }
+
+    /// Number of limit updates merged into a later update of the same level.
+    crate fn conflated(&self) -> usize {
+        self.conflated
+    }
+
+    /// Number of notifications dropped.
+    crate fn shed(&self) -> usize {
+        self.shed
+    }
+
+    /// Return the notifications to queue in place of `notif`, given the pressure on
+    /// `budget`.
+    crate fn admit(&mut self, notif: Notification, budget: &Budget) -> Vec<Notification> {
+        let pressure = budget.pressure();
+
+        match notif {
+            Notification::LimitUpdates(updates) => {
+                if pressure == Pressure::Normal && self.pending.is_empty() {
+                    return vec![Notification::LimitUpdates(updates)];
+                }
+
+                let was_empty = self.pending.is_empty();
+                for update in updates {
+                    let key = (update.side, update.price);
+                    match self.levels.get(&key) {
+                        Some(index) => {
+                            self.pending[*index] = update;
+                            self.conflated += 1;
+                            budget.conflated.fetch_add(1, Ordering::Relaxed);
+                        }
+                        None => {
+                            self.levels.insert(key, self.pending.len());
+                            self.pending.push(update);
+                        }
+                    }
+                }
+
+                // Pending updates are queued along with the next ones once the pressure
+                // is relieved, so that they keep their order.
+                if pressure == Pressure::Normal {
+                    return self.take_pending().into_iter().collect();
+                }
+
+                if was_empty && !self.pending.is_empty() {
+                    if let Some(task) = self.waiting.take() {
+                        task.notify();
+                    }
+                }
+                Vec::new()
+            }
+
+            Notification::Trade(..) | Notification::IndexPrice(..)
+                if pressure == Pressure::Shed =>
+            {
+                self.shed += 1;
+                budget.shed.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+
+            other => vec![other],
+        }
+    }
+
+    /// Return the conflated limit updates, if any.
+    crate fn take_pending(&mut self) -> Option<Notification> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.levels.clear();
+        Some(Notification::LimitUpdates(mem::replace(&mut self.pending, Vec::new())))
+    }
+
+    /// Drop the conflated limit updates, the book being sent again from a fresh
+    /// snapshot.
+    crate fn reset_book(&mut self) {
+        self.pending.clear();
+        self.levels.clear();
+    }
+
+    /// Register the current task for being notified when limit updates get conflated.
+    /// Must be called from within a task.
+    crate fn park(&mut self) {
+        self.waiting = Some(task::current());
+    }
+
+    /// Account for a notification of `bytes` being queued.
+    crate fn on_queued(&mut self, bytes: usize, budget: &Budget) {
+        if !self.closed {
+            self.queued += bytes;
+            budget.acquire(bytes);
+        }
+    }
+
+    /// Account for a notification of `bytes` being delivered, or failing to be queued.
+    crate fn on_delivered(&mut self, bytes: usize, budget: &Budget) {
+        if !self.closed {
+            self.queued -= bytes;
+            budget.release(bytes);
+        }
+    }
+
+    /// Release the footprint of the notifications which will never be delivered.
+    crate fn close(&mut self, budget: &Budget) {
+        if !self.closed {
+            budget.release(self.queued);
+            self.queued = 0;
+            self.closed = true;
+        }
+    }
+}
@@ -0,0 +1,144 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade, OrderUpdate};
+use crate::api::budget::{Budget, Pressure, Shedder, footprint};
+use crate::api::timestamp::IntoTimestamped;
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn trade() -> Notification {
+    Notification::Trade(Trade {
+        price: 100,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(0))
+}
+
+fn update() -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: "order".to_owned(),
+        consumed_size: 1,
+        remaining_size: 0,
+        consumed_price: 100,
+        commission: 0,
+    }.with_timestamp(0))
+}
+
+fn limits(updates: &[(TickUnit, TickUnit, Side)], timestamp: u64) -> Notification {
+    Notification::LimitUpdates(
+        updates.iter()
+            .map(|(price, size, side)| LimitUpdate::new(*price, *size, *side).with_timestamp(timestamp))
+            .collect()
+    )
+}
+
+/// Queue the notifications admitted by `shedder`, i.e. account for them.
+fn queue(shedder: &mut Shedder, notif: Notification, budget: &Budget) -> Vec<Notification> {
+    let notifs = shedder.admit(notif, budget);
+    for notif in &notifs {
+        shedder.on_queued(footprint(notif), budget);
+    }
+    notifs
+}
+
+#[test]
+fn pressure() {
+    let budget = Budget::new();
+    let mut shedder = Shedder::new();
+    assert_eq!(budget.pressure(), Pressure::Normal);
+
+    let size = footprint(&trade());
+    budget.set_limit(Some(4 * size));
+    assert_eq!(budget.limit(), Some(4 * size));
+
+    queue(&mut shedder, trade(), &budget);
+    assert_eq!(budget.pressure(), Pressure::Normal);
+    queue(&mut shedder, trade(), &budget);
+    assert_eq!(budget.pressure(), Pressure::Conflate);
+    queue(&mut shedder, trade(), &budget);
+    queue(&mut shedder, trade(), &budget);
+    assert_eq!(budget.pressure(), Pressure::Shed);
+
+    shedder.on_delivered(size, &budget);
+    assert_eq!(budget.pressure(), Pressure::Conflate);
+
+    let stats = budget.stats();
+    assert_eq!(stats.queued, 3 * size);
+    assert_eq!(stats.peak, 4 * size);
+
+    // The notifications of a dropped stream will never be delivered.
+    shedder.close(&budget);
+    assert_eq!(budget.stats().queued, 0);
+    queue(&mut shedder, trade(), &budget);
+    assert_eq!(budget.stats().queued, 0);
+
+    budget.set_limit(None);
+    assert_eq!(budget.limit(), None);
+}
+
+#[test]
+fn conflation() {
+    let budget = Budget::new();
+    let mut other = Shedder::new();
+    let mut shedder = Shedder::new();
+
+    let size = footprint(&trade());
+    budget.set_limit(Some(4 * size));
+    queue(&mut other, trade(), &budget);
+    queue(&mut other, trade(), &budget);
+
+    let notifs = queue(&mut shedder, limits(&[(10, 1, Side::Bid), (12, 1, Side::Ask)], 1), &budget);
+    assert!(notifs.is_empty());
+    let notifs = queue(&mut shedder, limits(&[(12, 3, Side::Ask), (9, 2, Side::Bid)], 2), &budget);
+    assert!(notifs.is_empty());
+    assert_eq!(shedder.conflated(), 1);
+
+    // Not affected by the conflation.
+    assert_eq!(queue(&mut shedder, update(), &budget), vec![update()]);
+
+    // Pending updates are flushed along with the next ones once the pressure is relieved.
+    other.on_delivered(size, &budget);
+    let notifs = queue(&mut shedder, limits(&[(10, 0, Side::Bid)], 3), &budget);
+    assert_eq!(budget.pressure(), Pressure::Conflate);
+    assert!(notifs.is_empty());
+
+    other.on_delivered(size, &budget);
+    shedder.on_delivered(footprint(&update()), &budget);
+    let notifs = queue(&mut shedder, limits(&[(13, 1, Side::Ask)], 4), &budget);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(10, 0, Side::Bid).with_timestamp(3),
+            LimitUpdate::new(12, 3, Side::Ask).with_timestamp(2),
+            LimitUpdate::new(9, 2, Side::Bid).with_timestamp(2),
+            LimitUpdate::new(13, 1, Side::Ask).with_timestamp(4),
+        ])
+    ]);
+    assert_eq!(shedder.take_pending(), None);
+    assert_eq!(shedder.conflated(), 2);
+    assert_eq!(budget.stats().conflated, 2);
+
+    // Delivered by the stream once its queue is empty.
+    budget.set_limit(Some(1));
+    queue(&mut shedder, limits(&[(10, 1, Side::Bid)], 5), &budget);
+    assert_eq!(shedder.take_pending(), Some(limits(&[(10, 1, Side::Bid)], 5)));
+
+    queue(&mut shedder, limits(&[(10, 2, Side::Bid)], 6), &budget);
+    shedder.reset_book();
+    assert_eq!(shedder.take_pending(), None);
+}
+
+#[test]
+fn shedding() {
+    let budget = Budget::new();
+    let mut shedder = Shedder::new();
+    budget.set_limit(Some(1));
+    queue(&mut shedder, update(), &budget);
+    assert_eq!(budget.pressure(), Pressure::Shed);
+
+    assert!(queue(&mut shedder, trade(), &budget).is_empty());
+    assert_eq!(queue(&mut shedder, update(), &budget), vec![update()]);
+
+    assert_eq!(shedder.shed(), 1);
+    assert_eq!(budget.stats().shed, 1);
+}
@@ -0,0 +1,172 @@
+//! A module defining error types specific to Bybit.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Bybit REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Internal Bybit error code (`retCode`): see API documentation.
+    pub error_code: Option<i64>,
+
+    /// Description of the error (`retMsg`).
+    pub error_msg: Option<String>,
+}
+
+impl RestError {
+    fn has_code(&self, codes: &[i64]) -> bool {
+        self.error_code.map(|code| codes.contains(&code)).unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        // Linear and spot codes respectively.
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[110001, 170213]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest &&
+            self.has_code(&[110004, 110007, 170131])
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[110072, 170141]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error_msg) = &self.error_msg {
+            write!(f, ": `{}`", error_msg)?;
+        }
+        if let Some(error_code) = self.error_code {
+            write!(f, " (error_code = {})", error_code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Bybit mostly reports errors with an HTTP 200 status code and a non zero `retCode`
+    /// in the response body.
+    pub(super) fn from_bybit_error(status: StatusCode, bybit_error: Option<(i64, String)>)
+        -> Self
+    {
+        let kind = match &bybit_error {
+            Some((code, _)) if status == StatusCode::OK => RestErrorKind::from_error_code(*code),
+            _ => RestErrorKind::from_status_code(status),
+        };
+
+        RestError {
+            kind,
+            error_code: bybit_error.as_ref().map(|error| error.0),
+            error_msg: bybit_error.map(|error| error.1),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Bybit error code to a Bybit error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key, signature or timestamp, or missing permission for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Bybit. See Bybit API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Bybit side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is down or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::OK => panic!("`RestErrorKind::from_status_code` with `StatusCode::Ok`"),
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_error_code(code: i64) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            10002 | 10003 | 10004 | 10005 | 10007 | 10009 | 10010 => Unauthorized,
+            10006 | 10018 => TooManyRequests,
+            10016 => InternalError,
+            _ => BadRequest,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Implementation of `ApiClient` for the Bybit (v5) API.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::pkey::{PKey, Private};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Bybit key pair: api key + secret key.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Market traded by a client. Spot pairs and linear perpetuals share the same symbol
+/// names, e.g. `BTCUSDT`, hence a client only deals with one category.
+pub enum Category {
+    /// Spot pairs.
+    Spot,
+
+    /// Perpetual contracts margined and settled in USDT or USDC.
+    Linear,
+}
+
+impl Category {
+    fn as_str(self) -> &'static str {
+        match self {
+            Category::Spot => "spot",
+            Category::Linear => "linear",
+        }
+    }
+}
+
+/// A Bybit API client.
+pub struct Client {
+    params: Params,
+    category: Category,
+    keys: Option<Keys>,
+
+    /// server order id => client order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Params {
+    /// Return the parameters of the Bybit testnet, where keys are generated at
+    /// `https://testnet.bybit.com`.
+    pub fn bybit_testnet() -> Self {
+        Params {
+            streaming_endpoint: "wss://stream-testnet.bybit.com".to_owned(),
+            rest_endpoint: "https://api-testnet.bybit.com".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new Bybit API client for the given `category`, with given `params`.
+    /// The streaming endpoint is the base address of the WebSocket API, e.g.
+    /// `wss://stream.bybit.com`. If `key_pair` is not `None`, this will enable performing
+    /// requests to the REST API and will forward the user data stream.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Bybit.
+    pub fn new(params: Params, category: Category, key_pair: Option<KeyPair>)
+        -> Result<Self, failure::Error>
+    {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            category,
+            keys,
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+
+    /// Return the market traded by this client.
+    pub fn category(&self) -> Category {
+        self.category
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Bybit
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Bybit accepts client order ids of at most 36 characters.
+    fn new_order_id(hint: &str) -> String {
+        if hint.len() <= 36 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().to_string()
+        }
+    }
+}
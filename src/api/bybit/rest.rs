@@ -0,0 +1,456 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+use crate::api::bybit::{Client, Category};
+use crate::api::bybit::errors::RestError;
+
+/// Validity window of signed requests, in ms.
+const RECV_WINDOW: u64 = 5000;
+
+/// Ticks used for computing free balances, extra decimals are truncated.
+const BALANCE_TICK: u64 = 100_000_000;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitStatus {
+    retCode: i64,
+    retMsg: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitResponse<T> {
+    result: T,
+    time: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct BybitOrder<'a> {
+    category: &'a str,
+    symbol: &'a str,
+    side: &'a str,
+    orderType: &'a str,
+    qty: &'a str,
+    price: &'a str,
+    timeInForce: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orderLinkId: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reduceOnly: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct BybitCancel<'a> {
+    category: &'a str,
+    symbol: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orderId: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orderLinkId: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitOrderAck<'a> {
+    orderId: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitList<T> {
+    list: Vec<T>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitWallet<'a> {
+    #[serde(borrow)]
+    coin: Vec<BybitCoin<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitCoin<'a> {
+    coin: &'a str,
+    walletBalance: &'a str,
+    locked: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitMaintenance {
+    /// 1: scheduled, 2: ongoing, 3: finished, 4: canceled.
+    state: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitPriceFilter<'a> {
+    tickSize: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitLotSizeFilter<'a> {
+    /// Spot only.
+    basePrecision: Option<&'a str>,
+
+    /// Derivatives only.
+    qtyStep: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitInstrument<'a> {
+    symbol: &'a str,
+    status: &'a str,
+    contractType: Option<&'a str>,
+    #[serde(borrow)]
+    priceFilter: BybitPriceFilter<'a>,
+    #[serde(borrow)]
+    lotSizeFilter: BybitLotSizeFilter<'a>,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "Sell",
+            Side::Bid => "Buy",
+        }
+    }
+}
+
+impl Client {
+    /// Signed requests carry the hex HMAC-SHA256 of the timestamp, the api key, the
+    /// receive window and the payload, which is the query string for GET requests and
+    /// the JSON body for POST requests.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        payload: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let address = if method == Method::GET && !payload.is_empty() {
+            format!("{}/{}?{}", self.params.rest_endpoint, endpoint, payload)
+        } else {
+            format!("{}/{}", self.params.rest_endpoint, endpoint)
+        };
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let timestamp = timestamp_ms();
+            let mut signer = Signer::new(MessageDigest::sha256(), &keys.secret_key).unwrap();
+            let what = format!("{}{}{}{}", timestamp, keys.api_key, RECV_WINDOW, payload);
+            signer.update(what.as_bytes()).unwrap();
+            let signature = hex::encode(&signer.sign_to_vec().unwrap());
+
+            request.header("X-BAPI-API-KEY", keys.api_key.as_bytes())
+                .header("X-BAPI-TIMESTAMP", format!("{}", timestamp).as_bytes())
+                .header("X-BAPI-RECV-WINDOW", format!("{}", RECV_WINDOW).as_bytes())
+                .header("X-BAPI-SIGN", signature.as_bytes());
+        }
+
+        let body = if method == Method::POST {
+            payload
+        } else {
+            String::new()
+        };
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let bybit_error = serde_json::from_slice(&body)
+                .ok()
+                .map(|status: BybitStatus| (status.retCode, status.retMsg));
+
+            let failed = match &bybit_error {
+                Some((code, _)) => *code != 0,
+                None => true,
+            };
+
+            if status != hyper::StatusCode::OK || failed {
+                let error = RestError::from_bybit_error(status, bybit_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Bybit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Bybit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only && self.category == Category::Spot {
+            warn!("called `order` with reduce only, which is not supported on spot");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let time_in_force = match (order.type_, order.time_in_force) {
+            (OrderType::LimitMaker, _) => "PostOnly",
+            (_, TimeInForce::GoodTilCanceled) => "GTC",
+            (_, TimeInForce::ImmediateOrCancel) => "IOC",
+            (_, TimeInForce::FillOrKilll) => "FOK",
+            (_, TimeInForce::GoodTilDate(_)) => {
+                warn!("called `order` with `GoodTilDate`, which is not supported by Bybit");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let order_body = BybitOrder {
+            category: self.category.as_str(),
+            symbol: symbol.name(),
+            side: order.side.as_str(),
+            orderType: "Limit",
+            qty: size.borrow(),
+            price: price.borrow(),
+            timeInForce: time_in_force,
+            orderLinkId: order.order_id.as_ref().map(|order_id| order_id.as_str()),
+            reduceOnly: if order.reduce_only { Some(true) } else { None },
+        };
+
+        let body = serde_json::to_string(&order_body).expect("invalid json");
+
+        let client_order_id = order.order_id.clone();
+        let order_ids = self.order_ids.clone();
+
+        let fut = self.request("v5/order/create", Method::POST, body).and_then(move |body| {
+            let ack: BybitResponse<BybitOrderAck<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // The order id specified by the user, which defaults to the server order id
+            // in case it was left unspecified.
+            let order_id = client_order_id.unwrap_or_else(|| ack.result.orderId.to_owned());
+            order_ids.insert(ack.result.orderId.to_owned(), order_id.clone());
+            debug!("insert order id {} (from REST)", order_id);
+
+            Ok(OrderAck {
+                order_id,
+            }.with_timestamp(ack.time))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        // Orders inserted without a client order id are known by their server order id.
+        let server_order_id = self.order_ids.contains_key(&cancel.order_id);
+
+        let symbol = cancel.symbol();
+        let cancel_body = BybitCancel {
+            category: self.category.as_str(),
+            symbol: symbol.name(),
+            orderId: if server_order_id { Some(&cancel.order_id) } else { None },
+            orderLinkId: if server_order_id { None } else { Some(&cancel.order_id) },
+        };
+
+        let body = serde_json::to_string(&cancel_body).expect("invalid json");
+
+        self.request("v5/order/cancel", Method::POST, body).and_then(|body| {
+            let ack: BybitResponse<BybitOrderAck<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(CancelAck.with_timestamp(ack.time))
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v5/market/time", Method::GET, String::new()).and_then(|body| {
+            let time: BybitResponse<serde_json::Value> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(time.time))
+        })
+    }
+
+    /// Balances of the unified trading account, which holds both spot and derivatives
+    /// positions.
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("accountType", "UNIFIED");
+
+        let endpoint = "v5/account/wallet-balance";
+        self.request(endpoint, Method::GET, query.into_string()).and_then(|body| {
+            let wallets: BybitResponse<BybitList<BybitWallet<'_>>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let tick = Tick::new(BALANCE_TICK);
+            let mut balances = Balances::new();
+            for coin in wallets.result.list.into_iter().flat_map(|wallet| wallet.coin) {
+                // Fields not applicable to the account are sent as empty strings.
+                let locked = if coin.locked.is_empty() { "0" } else { coin.locked };
+                let free = tick.ticked(coin.walletBalance)
+                    .and_then(|total| tick.unticked(total.saturating_sub(tick.ticked(locked)?)))
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                balances.insert(coin.coin.to_owned(), Balance {
+                    free,
+                    locked: locked.to_owned(),
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v5/system/status", Method::GET, String::new()).and_then(|body| {
+            let maintenances: BybitResponse<BybitList<BybitMaintenance>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let ongoing = maintenances.result.list.iter().any(|m| m.state == 2);
+            let status = if ongoing {
+                ExchangeStatus::Maintenance
+            } else {
+                ExchangeStatus::Normal
+            };
+            Ok(status.with_timestamp(maintenances.time))
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        let category = self.category;
+
+        // All the linear instruments fit in a single page of 1000 entries.
+        let mut query = QueryString::new();
+        query.push_str("category", category.as_str());
+        query.push_str("limit", "1000");
+
+        let endpoint = "v5/market/instruments-info";
+        self.request(endpoint, Method::GET, query.into_string()).and_then(move |body| {
+            let instruments: BybitResponse<BybitList<BybitInstrument<'_>>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            for instrument in instruments.result.list {
+                if instrument.status != "Trading" {
+                    continue;
+                }
+
+                // Dated futures are listed along with the perpetuals.
+                if category == Category::Linear &&
+                    instrument.contractType != Some("LinearPerpetual")
+                {
+                    continue;
+                }
+
+                let price_tick = Tick::tick_size(instrument.priceFilter.tickSize);
+                let size_tick = instrument.lotSizeFilter.basePrecision
+                    .or(instrument.lotSizeFilter.qtyStep)
+                    .and_then(Tick::tick_size);
+
+                let (price_tick, size_tick) = match (price_tick, size_tick) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", instrument.symbol);
+                        continue;
+                    }
+                };
+
+                if let Some(symbol) = Symbol::new(instrument.symbol, price_tick, size_tick) {
+                    symbols.insert(symbol.name().to_lowercase(), symbol);
+                } else {
+                    error!("symbol name too long: `{}`", instrument.symbol);
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
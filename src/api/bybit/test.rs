@@ -0,0 +1,64 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::bybit::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTCUSDT", Tick::new(100), Tick::new(1000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let subscribed = r#"{"success":true,"ret_msg":"","conn_id":"abc","op":"subscribe"}"#;
+    let snapshot = r#"{"topic":"orderbook.50.BTCUSDT","type":"snapshot","ts":1672304484978,
+        "data":{"s":"BTCUSDT","b":[["16493.50","0.006"],["16493.00","0.100"]],
+        "a":[["16611.00","0.029"]],"u":18521288,"seq":7961638724}}"#;
+    let delta = r#"{"topic":"orderbook.50.BTCUSDT","type":"delta","ts":1672304485000,
+        "data":{"s":"BTCUSDT","b":[["16493.50","0"]],"a":[["16611.50","0.500"]],
+        "u":18521289,"seq":7961638725}}"#;
+
+    // A new snapshot after a restart of the service removes the stale levels.
+    let restart = r#"{"topic":"orderbook.50.BTCUSDT","type":"snapshot","ts":1672304486000,
+        "data":{"s":"BTCUSDT","b":[["16493.00","0.200"]],"a":[["16611.00","0.029"]],
+        "u":1,"seq":7961638726}}"#;
+
+    let notifs = parse(&[subscribed, snapshot, delta, restart]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(1_661_100, 29, Side::Ask).with_timestamp(1_672_304_484_978),
+            LimitUpdate::new(1_649_350, 6, Side::Bid).with_timestamp(1_672_304_484_978),
+            LimitUpdate::new(1_649_300, 100, Side::Bid).with_timestamp(1_672_304_484_978),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(1_661_150, 500, Side::Ask).with_timestamp(1_672_304_485_000),
+            LimitUpdate::new(1_649_350, 0, Side::Bid).with_timestamp(1_672_304_485_000),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(1_661_100, 0, Side::Ask).with_timestamp(1_672_304_486_000),
+            LimitUpdate::new(1_661_150, 0, Side::Ask).with_timestamp(1_672_304_486_000),
+            LimitUpdate::new(1_649_300, 0, Side::Bid).with_timestamp(1_672_304_486_000),
+            LimitUpdate::new(1_661_100, 29, Side::Ask).with_timestamp(1_672_304_486_000),
+            LimitUpdate::new(1_649_300, 200, Side::Bid).with_timestamp(1_672_304_486_000),
+        ]),
+    ]);
+}
@@ -0,0 +1,514 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::bybit::{Client, Category, Keys};
+
+/// Depth of the subscribed order book.
+const BOOK_DEPTH: usize = 50;
+
+/// Validity of the authentication request of the private connection, in ms.
+const AUTH_EXPIRATION: u64 = 10_000;
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let public_endpoint = format!(
+            "{}/v5/public/{}",
+            self.params.streaming_endpoint,
+            self.category.as_str()
+        );
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        if let Some(keys) = self.keys.clone() {
+            let private_endpoint = format!("{}/v5/private", self.params.streaming_endpoint);
+            let category = self.category;
+            let snd = snd.clone();
+            let order_ids = self.order_ids.clone();
+            let public_control = control.clone();
+
+            // The flags of the private connection are those of the public one,
+            // see `PrivateHandlerImpl::control`.
+            let private_control = control.linked();
+
+            thread::spawn(move || {
+                debug!("initiating WebSocket connection at {}", private_endpoint);
+
+                if let Err(err) = ws::connect(private_endpoint, |out| {
+                    wss::Handler::new(
+                        out,
+                        snd.clone(),
+                        private_control.clone(),
+                        wss::KeepAlive::True,
+                        PrivateHandlerImpl {
+                            symbol,
+                            category,
+                            keys: keys.clone(),
+                            control: public_control.clone(),
+                            order_ids: order_ids.clone(),
+                        }
+                    )
+                })
+                {
+                    error!("WebSocket connection terminated with error: `{}`", err);
+                }
+            });
+        }
+
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", public_endpoint);
+
+            if let Err(err) = ws::connect(public_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        order_book: OrderBook::new(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+
+    /// Local copy of the order book, needed for removing the stale levels when Bybit
+    /// sends a new snapshot.
+    order_book: OrderBook,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct BybitRequest<'a> {
+    op: &'a str,
+    args: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitEvent<'a> {
+    op: Option<&'a str>,
+    success: Option<bool>,
+    ret_msg: Option<String>,
+    topic: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitMessage<'a, T> {
+    #[serde(rename = "type")]
+    type_: Option<&'a str>,
+    ts: Option<Timestamp>,
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct BybitBook<'a> {
+    #[serde(borrow)]
+    b: Vec<(&'a str, &'a str)>,
+    #[serde(borrow)]
+    a: Vec<(&'a str, &'a str)>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitTrade<'a> {
+    T: Timestamp,
+    S: &'a str,
+    v: &'a str,
+    p: &'a str,
+}
+
+fn book_topic(symbol: &Symbol) -> String {
+    format!("orderbook.{}.{}", BOOK_DEPTH, symbol.name())
+}
+
+fn trades_topic(symbol: &Symbol) -> String {
+    format!("publicTrade.{}", symbol.name())
+}
+
+fn send_request(op: &str, args: Vec<String>, out: &ws::Sender) -> ws::Result<()> {
+    match serde_json::to_string(&BybitRequest { op, args }) {
+        Ok(value) => out.send(value),
+        Err(err) => {
+            panic!("failed to serialize `BybitRequest`: `{}`", err);
+        }
+    }
+}
+
+/// Check the response to a request sent by the client. Return `Ok(None)` for such
+/// responses, `Ok(Some(topic))` for data messages.
+fn check_event<'a>(event: &BybitEvent<'a>) -> Result<Option<&'a str>, failure::Error> {
+    if let Some(op) = event.op {
+        if event.success == Some(false) {
+            bail!("{}: {:?}", op, event.ret_msg);
+        }
+        return Ok(None);
+    }
+    Ok(event.topic)
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            order_book: OrderBook::new(),
+        }
+    }
+
+    fn convert_bybit_level(&self, level: (&str, &str), side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        Ok(LimitUpdate {
+            side,
+            price: self.symbol.price_tick().ticked(level.0)?,
+            size: self.symbol.size_tick().ticked(level.1)?,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let book: BybitMessage<'_, BybitBook<'_>> = serde_json::from_str(json)?;
+        let timestamp = book.ts.unwrap_or_else(timestamp_ms);
+
+        let mut updates = Vec::new();
+
+        // A snapshot is sent after subscribing, or when Bybit restarts its service: in
+        // the latter case, the levels which are not part of the snapshot are stale.
+        if book.type_ == Some("snapshot") {
+            let asks = self.order_book.ask()
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+            let bids = self.order_book.bid()
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+            updates.extend(asks.chain(bids));
+        }
+
+        let asks = book.data.a.iter().map(|l| self.convert_bybit_level(*l, Side::Ask));
+        let bids = book.data.b.iter().map(|l| self.convert_bybit_level(*l, Side::Bid));
+        updates.extend(asks.chain(bids).collect::<Result<Vec<_>, _>>()?);
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.with_timestamp(timestamp))
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trades: BybitMessage<'_, Vec<BybitTrade<'_>>> = serde_json::from_str(json)?;
+
+        for trade in trades.data {
+            // The side is the one of the taker.
+            let maker_side = match trade.S {
+                "Buy" => Side::Ask,
+                "Sell" => Side::Bid,
+                other => bail!("wrong side: `{}`", other),
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: self.symbol.price_tick().ticked(trade.p)?,
+                size: self.symbol.size_tick().ticked(trade.v)?,
+                maker_side,
+            }.with_timestamp(trade.T));
+
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: BybitEvent<'_> = serde_json::from_str(json)?;
+
+        match check_event(&event)? {
+            Some(topic) if topic.starts_with("orderbook.") &&
+                self.flags.contains(NotificationFlags::ORDER_BOOK) =>
+            {
+                self.parse_book(json, out)?;
+            }
+
+            Some(topic) if topic.starts_with("publicTrade.") &&
+                self.flags.contains(NotificationFlags::TRADES) =>
+            {
+                self.parse_trades(json, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        let mut topics = Vec::new();
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            topics.push(book_topic(&self.symbol));
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            topics.push(trades_topic(&self.symbol));
+        }
+
+        if topics.is_empty() {
+            return Ok(());
+        }
+        send_request("subscribe", topics, out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // Orders are streamed through the private connection, where `ORDERS` only
+        // acts as a filter.
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            // A new snapshot will be sent.
+            (false, true) => {
+                self.order_book = OrderBook::new();
+                send_request("subscribe", vec![book_topic(&self.symbol)], out)?;
+            }
+            (true, false) => send_request("unsubscribe", vec![book_topic(&self.symbol)], out)?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => send_request("subscribe", vec![trades_topic(&self.symbol)], out)?,
+            (true, false) => send_request("unsubscribe", vec![trades_topic(&self.symbol)], out)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    category: Category,
+    keys: Keys,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// server order id => client order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitOrder<'a> {
+    category: &'a str,
+    symbol: &'a str,
+    orderId: &'a str,
+    orderLinkId: &'a str,
+    side: &'a str,
+    orderStatus: &'a str,
+    price: &'a str,
+    qty: &'a str,
+    cumExecQty: &'a str,
+    updatedTime: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct BybitExecution<'a> {
+    category: &'a str,
+    symbol: &'a str,
+    orderId: &'a str,
+    orderLinkId: &'a str,
+    execType: &'a str,
+    execPrice: &'a str,
+    execQty: &'a str,
+    leavesQty: &'a str,
+    execTime: &'a str,
+}
+
+impl PrivateHandlerImpl {
+    fn convert_bybit_side(&self, side: &str) -> Result<Side, failure::Error> {
+        let side = match side {
+            "Buy" => Side::Bid,
+            "Sell" => Side::Ask,
+            other => bail!("wrong side: `{}`", other),
+        };
+        Ok(side)
+    }
+
+    /// The private streams carry the orders of every category and symbol.
+    fn is_tracked(&self, category: &str, symbol: &str) -> bool {
+        category == self.category.as_str() && symbol == self.symbol.name()
+    }
+
+    /// Return the order id specified by the user, which defaults to the server order id
+    /// in case it was left unspecified.
+    fn order_id(&self, server_order_id: &str, order_link_id: &str) -> String {
+        let order_id = if order_link_id.is_empty() {
+            self.order_ids.get(server_order_id)
+                .map(|order_id| order_id.clone())
+                .unwrap_or_else(|| server_order_id.to_owned())
+        } else {
+            order_link_id.to_owned()
+        };
+
+        // Don't forget to update the concurrent map `server order id => client order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(server_order_id) {
+            self.order_ids.insert(server_order_id.to_owned(), order_id.clone());
+            debug!("insert order id {} (from WSS)", order_id);
+        }
+        order_id
+    }
+
+    fn parse_orders(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let orders: BybitMessage<'_, Vec<BybitOrder<'_>>> = serde_json::from_str(json)?;
+
+        for order in orders.data {
+            if !self.is_tracked(order.category, order.symbol) {
+                continue;
+            }
+
+            let order_id = self.order_id(order.orderId, order.orderLinkId);
+            let timestamp = order.updatedTime.parse::<Timestamp>()?;
+            let size = self.symbol.size_tick().ticked(order.qty)?;
+
+            match order.orderStatus {
+                "New" => {
+                    let order = OrderConfirmation {
+                        size,
+                        price: self.symbol.price_tick().ticked(order.price)?,
+                        side: self.convert_bybit_side(order.side)?,
+                        order_id,
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+                }
+
+                // Post only orders which would take liquidity are canceled as well.
+                "Cancelled" | "PartiallyFilledCanceled" | "Deactivated" => {
+                    let filled_size = self.symbol.size_tick().ticked(order.cumExecQty)?;
+                    let expiration = OrderExpiration {
+                        order_id,
+                        filled_size: Some(filled_size),
+                        remaining_size: Some(size.saturating_sub(filled_size)),
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+                }
+
+                // Fills are forwarded from the execution stream.
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_executions(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let executions: BybitMessage<'_, Vec<BybitExecution<'_>>> = serde_json::from_str(json)?;
+
+        for execution in executions.data {
+            if !self.is_tracked(execution.category, execution.symbol) {
+                continue;
+            }
+
+            // Funding and liquidations are reported as well.
+            if execution.execType != "Trade" {
+                continue;
+            }
+
+            let update = OrderUpdate {
+                order_id: self.order_id(execution.orderId, execution.orderLinkId),
+                consumed_size: self.symbol.size_tick().ticked(execution.execQty)?,
+                remaining_size: self.symbol.size_tick().ticked(execution.leavesQty)?,
+                consumed_price: self.symbol.price_tick().ticked(execution.execPrice)?,
+                commission: 0,
+            }.with_timestamp(execution.execTime.parse::<Timestamp>()?);
+            out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_message(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: BybitEvent<'_> = serde_json::from_str(json)?;
+        let topic = check_event(&event)?;
+
+        if !self.control.flags().contains(NotificationFlags::ORDERS) {
+            return Ok(());
+        }
+
+        match topic {
+            Some("order") => self.parse_orders(json, out)?,
+            Some("execution") => self.parse_executions(json, out)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// The signature covers `GET/realtime` followed by the expiration time in ms.
+    fn auth_args(&self) -> Vec<String> {
+        let expires = timestamp_ms() + AUTH_EXPIRATION;
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.keys.secret_key).unwrap();
+        signer.update(format!("GET/realtime{}", expires).as_bytes()).unwrap();
+        let signature = hex::encode(&signer.sign_to_vec().unwrap());
+
+        vec![self.keys.api_key.clone(), expires.to_string(), signature]
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        // Requests are processed in order, the subscription is hence authenticated.
+        send_request("auth", self.auth_args(), out)?;
+        send_request("subscribe", vec!["order".to_owned(), "execution".to_owned()], out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! A module defining the expiry schedules of dated futures, along with helpers for
+//! the time left before an expiry and for rolling a position to the next contract.
+//!
+//! All times are UTC timestamps in milliseconds. Dated futures on the supported venues
+//! expire on Fridays, at a settlement hour specific to each venue: weekly contracts
+//! every Friday, monthly contracts on the last Friday of the month and quarterly
+//! contracts on the last Friday of March, June, September and December.
+
+mod test;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamp;
+
+/// Milliseconds in an hour.
+pub const HOUR: Timestamp = 3_600_000;
+
+/// Milliseconds in a day.
+pub const DAY: Timestamp = 24 * HOUR;
+
+/// Milliseconds in a year of 365 days, the convention used for `years_to_expiry`.
+pub const YEAR: Timestamp = 365 * DAY;
+
+/// Day of the week of Fridays, counted from Monday.
+const FRIDAY: u64 = 4;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+/// Listing cycle of a dated future, ordered by increasing length.
+pub enum Cycle {
+    /// Expires every Friday.
+    Weekly,
+
+    /// Expires on the last Friday of each month.
+    Monthly,
+
+    /// Expires on the last Friday of March, June, September and December.
+    Quarterly,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An expiry of a dated future.
+pub struct Expiry {
+    /// Expiry time.
+    pub timestamp: Timestamp,
+
+    /// Longest cycle expiring at that time, e.g. a quarterly expiry is also the
+    /// expiry of a weekly and of a monthly contract.
+    pub cycle: Cycle,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A suggestion for rolling a position on a dated future to the next contract of the
+/// same cycle.
+pub struct RollSuggestion {
+    /// Expiry of the contract currently held, i.e. the nearest one.
+    pub from: Timestamp,
+
+    /// Expiry of the contract to roll into.
+    pub to: Timestamp,
+
+    /// Time from which the position should be rolled.
+    pub roll_at: Timestamp,
+}
+
+impl RollSuggestion {
+    /// Return `true` if the position should be rolled at time `now`.
+    pub fn is_due(&self, now: Timestamp) -> bool {
+        now >= self.roll_at
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// The expiry schedule of the dated futures listed by a venue.
+pub struct ExpirySchedule {
+    settlement_hour: u64,
+    cycles: Vec<Cycle>,
+}
+
+impl ExpirySchedule {
+    /// Return a schedule of contracts expiring at `settlement_hour` UTC, listed with
+    /// the given cycles.
+    ///
+    /// # Panics
+    /// Panic if `settlement_hour` is not below 24 or if `cycles` is empty.
+    pub fn new(settlement_hour: u64, mut cycles: Vec<Cycle>) -> Self {
+        assert!(settlement_hour < 24, "invalid settlement hour: {}", settlement_hour);
+        assert!(!cycles.is_empty(), "an expiry schedule needs at least one cycle");
+
+        cycles.sort();
+        cycles.dedup();
+        ExpirySchedule {
+            settlement_hour,
+            cycles,
+        }
+    }
+
+    /// Schedule of the dated futures of the given exchange, or `None` if it does not
+    /// list any or if they are not supported.
+    pub fn for_exchange(exchange: ExchangeId) -> Option<Self> {
+        use self::Cycle::*;
+        let schedule = match exchange {
+            ExchangeId::Binance => ExpirySchedule::new(8, vec![Quarterly]),
+            ExchangeId::Bitmex => ExpirySchedule::new(12, vec![Quarterly]),
+            ExchangeId::Bybit => ExpirySchedule::new(8, vec![Quarterly]),
+            ExchangeId::Deribit => ExpirySchedule::new(8, vec![Weekly, Monthly, Quarterly]),
+            ExchangeId::Okx => ExpirySchedule::new(8, vec![Weekly, Quarterly]),
+            _ => return None,
+        };
+        Some(schedule)
+    }
+
+    /// Settlement hour, UTC.
+    pub fn settlement_hour(&self) -> u64 {
+        self.settlement_hour
+    }
+
+    /// Cycles listed, sorted by increasing length.
+    pub fn cycles(&self) -> &[Cycle] {
+        &self.cycles
+    }
+
+    /// First expiry of `cycle` strictly after `after`, whether or not the cycle is
+    /// listed.
+    pub fn next_expiry(&self, cycle: Cycle, after: Timestamp) -> Timestamp {
+        let settlement = self.settlement_hour * HOUR;
+        let mut day = after / DAY;
+
+        if cycle == Cycle::Weekly {
+            day += (FRIDAY + 7 - weekday(day)) % 7;
+            if day * DAY + settlement <= after {
+                day += 7;
+            }
+            return day * DAY + settlement;
+        }
+
+        let (mut year, mut month, _) = civil_from_days(day);
+        loop {
+            if cycle == Cycle::Monthly || month % 3 == 0 {
+                let expiry = last_friday(year, month) * DAY + settlement;
+                if expiry > after {
+                    return expiry;
+                }
+            }
+
+            if month == 12 {
+                year += 1;
+                month = 1;
+            } else {
+                month += 1;
+            }
+        }
+    }
+
+    /// Expiries of the listed cycles strictly after `after` and up to `until`
+    /// included, in chronological order.
+    pub fn expiries(&self, after: Timestamp, until: Timestamp) -> Vec<Expiry> {
+        let mut expiries: Vec<Expiry> = Vec::new();
+        for cycle in &self.cycles {
+            let mut timestamp = self.next_expiry(*cycle, after);
+            while timestamp <= until {
+                expiries.push(Expiry {
+                    timestamp,
+                    cycle: *cycle,
+                });
+                timestamp = self.next_expiry(*cycle, timestamp);
+            }
+        }
+
+        // Keep the longest cycle for expiries shared by several cycles.
+        expiries.sort_by_key(|expiry| (expiry.timestamp, std::cmp::Reverse(expiry.cycle)));
+        expiries.dedup_by_key(|expiry| expiry.timestamp);
+        expiries
+    }
+
+    /// Suggest rolling a position on the nearest contract of `cycle` into the next
+    /// one, `window` milliseconds before the nearest contract expires. Liquidity
+    /// usually moves to the next contract during the last days before an expiry.
+    pub fn roll_suggestion(&self, cycle: Cycle, now: Timestamp, window: Timestamp)
+        -> RollSuggestion
+    {
+        let from = self.next_expiry(cycle, now);
+        RollSuggestion {
+            from,
+            to: self.next_expiry(cycle, from),
+            roll_at: from.saturating_sub(window),
+        }
+    }
+}
+
+/// Time left at `now` before `expiry`, zero if it has passed.
+pub fn time_to_expiry(now: Timestamp, expiry: Timestamp) -> Timestamp {
+    expiry.saturating_sub(now)
+}
+
+/// Time left at `now` before `expiry` in years of 365 days, as used for pricing
+/// options and for annualizing the basis of a future.
+pub fn years_to_expiry(now: Timestamp, expiry: Timestamp) -> f64 {
+    time_to_expiry(now, expiry) as f64 / YEAR as f64
+}
+
+/// Day of the week of `days` since the epoch, counted from Monday. The epoch was a
+/// Thursday.
+fn weekday(days: u64) -> u64 {
+    (days + 3) % 7
+}
+
+/// Number of days since the epoch of the given date.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // Years start in March, so that the leap day is the last one.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of `days` since the epoch, as a year, a month and a day.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (
+        day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096
+    ) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+/// Number of days since the epoch of the last Friday of the given month.
+fn last_friday(year: u64, month: u64) -> u64 {
+    let first_of_next = if month == 12 {
+        days_from_civil(year + 1, 1, 1)
+    } else {
+        days_from_civil(year, month + 1, 1)
+    };
+    let last_day = first_of_next - 1;
+    last_day - (weekday(last_day) + 7 - FRIDAY) % 7
+}
@@ -0,0 +1,70 @@
+#![cfg(test)]
+
+use crate::api::calendar::*;
+use crate::api::id::ExchangeId;
+
+// 2024-03-20 00:00 UTC, a Wednesday.
+const MARCH_20: u64 = 1710892800000;
+
+// Fridays of 2024 at 08:00 UTC.
+const MARCH_22: u64 = 1711094400000;
+const MARCH_29: u64 = 1711699200000;
+const APRIL_05: u64 = 1712304000000;
+const APRIL_26: u64 = 1714118400000;
+const JUNE_28: u64 = 1719561600000;
+
+#[test]
+fn next_expiry() {
+    let schedule = ExpirySchedule::for_exchange(ExchangeId::Deribit).unwrap();
+
+    assert_eq!(schedule.next_expiry(Cycle::Weekly, MARCH_20), MARCH_22);
+    assert_eq!(schedule.next_expiry(Cycle::Monthly, MARCH_20), MARCH_29);
+    assert_eq!(schedule.next_expiry(Cycle::Quarterly, MARCH_20), MARCH_29);
+
+    // Expiries are strictly after the given time.
+    assert_eq!(schedule.next_expiry(Cycle::Weekly, MARCH_22 - 1), MARCH_22);
+    assert_eq!(schedule.next_expiry(Cycle::Weekly, MARCH_22), MARCH_29);
+    assert_eq!(schedule.next_expiry(Cycle::Monthly, MARCH_29), APRIL_26);
+    assert_eq!(schedule.next_expiry(Cycle::Quarterly, MARCH_29), JUNE_28);
+
+    // Across a year boundary: 2024-12-27 then 2025-03-28.
+    assert_eq!(schedule.next_expiry(Cycle::Quarterly, 1735286400000), 1743148800000);
+
+    // Settlement hours are specific to each venue.
+    let bitmex = ExpirySchedule::for_exchange(ExchangeId::Bitmex).unwrap();
+    assert_eq!(bitmex.next_expiry(Cycle::Quarterly, JUNE_28), JUNE_28 + 4 * HOUR);
+    assert_eq!(ExpirySchedule::for_exchange(ExchangeId::Gdax), None);
+}
+
+#[test]
+fn expiries() {
+    let schedule = ExpirySchedule::for_exchange(ExchangeId::Deribit).unwrap();
+    assert_eq!(schedule.expiries(MARCH_20, APRIL_05), vec![
+        Expiry { timestamp: MARCH_22, cycle: Cycle::Weekly },
+        Expiry { timestamp: MARCH_29, cycle: Cycle::Quarterly },
+        Expiry { timestamp: APRIL_05, cycle: Cycle::Weekly },
+    ]);
+
+    let schedule = ExpirySchedule::new(8, vec![Cycle::Quarterly, Cycle::Monthly]);
+    assert_eq!(schedule.cycles(), &[Cycle::Monthly, Cycle::Quarterly]);
+    assert_eq!(
+        schedule.expiries(MARCH_29, JUNE_28).iter().map(|e| e.cycle).collect::<Vec<_>>(),
+        vec![Cycle::Monthly, Cycle::Monthly, Cycle::Quarterly]
+    );
+}
+
+#[test]
+fn roll_suggestion() {
+    let schedule = ExpirySchedule::for_exchange(ExchangeId::Binance).unwrap();
+    let roll = schedule.roll_suggestion(Cycle::Quarterly, MARCH_20, 7 * DAY);
+
+    assert_eq!(roll.from, MARCH_29);
+    assert_eq!(roll.to, JUNE_28);
+    assert_eq!(roll.roll_at, MARCH_22);
+    assert!(!roll.is_due(MARCH_20));
+    assert!(roll.is_due(MARCH_22));
+
+    assert_eq!(time_to_expiry(MARCH_20, MARCH_22), 2 * DAY + 8 * HOUR);
+    assert_eq!(time_to_expiry(MARCH_29, MARCH_22), 0);
+    assert_eq!(years_to_expiry(0, YEAR), 1.0);
+}
@@ -0,0 +1,177 @@
+//! A module defining error types specific to Coinbase.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Coinbase REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Coinbase error code, e.g. `INSUFFICIENT_FUND`: see API documentation. Rejected
+    /// orders and cancels carry their failure reason here.
+    pub error: Option<String>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl RestError {
+    fn has_error(&self, errors: &[&str]) -> bool {
+        match &self.error {
+            Some(error) => errors.contains(&error.as_str()),
+            None => false,
+        }
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::NotFound || self.has_error(&["UNKNOWN_CANCEL_ORDER"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.has_error(&["INSUFFICIENT_FUND"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.has_error(&["INVALID_LIMIT_PRICE_POST_ONLY"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " (error = {})", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Error returned along with a non-success HTTP status code.
+    pub(super) fn from_coinbase_error(
+        status: StatusCode,
+        error: Option<String>,
+        message: Option<String>
+    ) -> Self
+    {
+        RestError {
+            kind: RestErrorKind::from_status_code(status),
+            error,
+            message,
+        }
+    }
+
+    /// Order or cancel rejected with a failure reason, while the request itself
+    /// succeeded.
+    pub(super) fn rejected(error: String, message: Option<String>) -> Self {
+        RestError {
+            kind: RestErrorKind::Rejected,
+            error: Some(error),
+            message,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code to a Coinbase error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "rejected")]
+    /// The order or the cancel was rejected, see the `error` field of `RestError`.
+    Rejected,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid or expired token, or missing permission.
+    Unauthorized,
+
+    #[fail(display = "not found")]
+    /// Not found, issue on the consumer side.
+    NotFound,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Coinbase. See Coinbase API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Coinbase side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::NOT_FOUND => NotFound,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+}
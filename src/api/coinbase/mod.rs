@@ -0,0 +1,245 @@
+//! Implementation of `ApiClient` for the Coinbase Advanced Trade API, which replaces the
+//! Coinbase Pro API. See `api::gdax` for the latter, which is still usable against its
+//! sandbox.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{ec::EcKey, ecdsa::EcdsaSig, pkey::Private};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{timestamp_ms, Timestamped};
+
+/// Validity of the JSON Web Tokens, in seconds.
+const JWT_VALIDITY: u64 = 120;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Coinbase Developer Platform key pair: key name, e.g.
+/// `organizations/{org_id}/apiKeys/{key_id}`, + EC private key in PEM format.
+pub struct KeyPair {
+    key_name: String,
+    private_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(key_name: String, private_key: String) -> Self {
+        KeyPair {
+            key_name,
+            private_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    key_name: String,
+    private_key: Arc<EcKey<Private>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct JwtHeader<'a> {
+    alg: &'a str,
+    typ: &'a str,
+    kid: &'a str,
+    nonce: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct JwtClaims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    nbf: u64,
+    exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<&'a str>,
+}
+
+impl Keys {
+    /// Return a JSON Web Token signed with ES256. REST requests carry a token bound
+    /// to their `uri`, e.g. `GET api.coinbase.com/api/v3/brokerage/accounts`, while
+    /// WebSocket subscriptions carry a token without `uri`.
+    fn jwt(&self, uri: Option<&str>) -> String {
+        use uuid::Uuid;
+
+        let now = timestamp_ms() / 1000;
+        let header = JwtHeader {
+            alg: "ES256",
+            typ: "JWT",
+            kid: &self.key_name,
+            nonce: Uuid::new_v4().simple().to_string(),
+        };
+        let claims = JwtClaims {
+            iss: "cdp",
+            sub: &self.key_name,
+            nbf: now,
+            exp: now + JWT_VALIDITY,
+            uri,
+        };
+
+        let encode = |value: &[u8]| base64::encode_config(value, base64::URL_SAFE_NO_PAD);
+        let message = format!(
+            "{}.{}",
+            encode(serde_json::to_string(&header).unwrap().as_bytes()),
+            encode(serde_json::to_string(&claims).unwrap().as_bytes())
+        );
+
+        // JWS signatures are made of `r` and `s` as 32 bytes big endian integers.
+        let digest = openssl::sha::sha256(message.as_bytes());
+        let signature = EcdsaSig::sign(&digest, &self.private_key).unwrap();
+        let mut raw = Vec::with_capacity(64);
+        for value in &[signature.r().to_vec(), signature.s().to_vec()] {
+            let padded_len = raw.len() + 32usize.saturating_sub(value.len());
+            raw.resize(padded_len, 0);
+            raw.extend(value);
+        }
+
+        format!("{}.{}", message, encode(&raw))
+    }
+}
+
+/// A Coinbase Advanced Trade API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// client order id => server order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new Coinbase API client with given `params`, e.g. with the usual
+    /// `https://api.coinbase.com/api/v3/brokerage` and
+    /// `wss://advanced-trade-ws.coinbase.com` endpoints. If `key_pair` is not `None`,
+    /// this will enable performing requests to the REST API and will forward the
+    /// `user` channel.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Coinbase.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let private_key = EcKey::private_key_from_pem(pair.private_key.as_bytes())?;
+
+                Some(Keys {
+                    key_name: pair.key_name,
+                    private_key: Arc::new(private_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Coinbase
+    }
+
+    /// Products are found by their Coinbase name, e.g. `BTC-USD`, case insensitively.
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_uppercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.cancel_impl(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Client order ids are UUIDs, so that they never collide in the map of the server
+    /// order ids, which are needed for canceling.
+    fn new_order_id(_: &str) -> String {
+        use uuid::Uuid;
+        Uuid::new_v4().to_string()
+    }
+}
@@ -0,0 +1,514 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::api::query_string::QueryString;
+use crate::api::coinbase::Client;
+use crate::api::coinbase::errors::RestError;
+
+/// Coinbase publishes the status of its services on a Statuspage instance, which is
+/// not part of the REST API.
+const STATUS_ENDPOINT: &str = "https://status.coinbase.com/api/v2/status.json";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseError {
+    error: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+enum CoinbaseOrderConfiguration<'a> {
+    #[serde(rename = "limit_limit_gtc")]
+    GoodTilCanceled {
+        base_size: &'a str,
+        limit_price: &'a str,
+        post_only: bool,
+    },
+
+    #[serde(rename = "sor_limit_ioc")]
+    ImmediateOrCancel {
+        base_size: &'a str,
+        limit_price: &'a str,
+    },
+
+    #[serde(rename = "limit_limit_fok")]
+    FillOrKill {
+        base_size: &'a str,
+        limit_price: &'a str,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct CoinbaseOrder<'a> {
+    client_order_id: &'a str,
+    product_id: &'a str,
+    side: &'a str,
+    order_configuration: CoinbaseOrderConfiguration<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseSuccessResponse {
+    order_id: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseErrorResponse {
+    error: Option<String>,
+    message: Option<String>,
+    new_order_failure_reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseOrderAck {
+    success: bool,
+    success_response: Option<CoinbaseSuccessResponse>,
+    error_response: Option<CoinbaseErrorResponse>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct CoinbaseCancel<'a> {
+    order_ids: &'a [&'a str],
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseCancelResult {
+    success: bool,
+    failure_reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseCancelAck {
+    results: Vec<CoinbaseCancelResult>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct CoinbaseTime<'a> {
+    epochMillis: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseAmount<'a> {
+    value: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseAccount<'a> {
+    currency: &'a str,
+    #[serde(borrow)]
+    available_balance: CoinbaseAmount<'a>,
+    #[serde(borrow)]
+    hold: CoinbaseAmount<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseAccounts<'a> {
+    #[serde(borrow)]
+    accounts: Vec<CoinbaseAccount<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseProduct<'a> {
+    product_id: &'a str,
+    quote_increment: &'a str,
+    base_increment: &'a str,
+    trading_disabled: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseProducts<'a> {
+    #[serde(borrow)]
+    products: Vec<CoinbaseProduct<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseStatusIndicator<'a> {
+    indicator: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseStatusPage<'a> {
+    #[serde(borrow)]
+    status: CoinbaseStatusIndicator<'a>,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "SELL",
+            Side::Bid => "BUY",
+        }
+    }
+}
+
+impl Client {
+    /// Signed requests carry a JSON Web Token bound to the method and to the path of
+    /// the request.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let address = if query.is_empty() {
+            format!("{}/{}", self.params.rest_endpoint, endpoint)
+        } else {
+            format!("{}/{}?{}", self.params.rest_endpoint, endpoint, query)
+        };
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let uri = address.parse::<hyper::Uri>()
+                .map(|uri| format!(
+                    "{} {}{}",
+                    method.as_str(),
+                    uri.host().unwrap_or_default(),
+                    uri.path()
+                ))
+                .unwrap_or_default();
+
+            request.header(
+                "Authorization",
+                format!("Bearer {}", keys.jwt(Some(&uri))).as_bytes()
+            );
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if !status.is_success() {
+                let (error, message) = match serde_json::from_slice(&body) {
+                    Ok(CoinbaseError { error, message }) => (error, message),
+                    Err(_) => (None, None),
+                };
+                let error = RestError::from_coinbase_error(status, error, message);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Coinbase");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Coinbase");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+        let base_size = size.borrow();
+        let limit_price = price.borrow();
+
+        // Post only orders are only available as good til canceled orders.
+        let order_configuration = match (order.type_, order.time_in_force) {
+            (order_type, TimeInForce::GoodTilCanceled) => {
+                CoinbaseOrderConfiguration::GoodTilCanceled {
+                    base_size,
+                    limit_price,
+                    post_only: order_type == OrderType::LimitMaker,
+                }
+            }
+            (OrderType::Limit, TimeInForce::ImmediateOrCancel) => {
+                CoinbaseOrderConfiguration::ImmediateOrCancel {
+                    base_size,
+                    limit_price,
+                }
+            }
+            (OrderType::Limit, TimeInForce::FillOrKilll) => {
+                CoinbaseOrderConfiguration::FillOrKill {
+                    base_size,
+                    limit_price,
+                }
+            }
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by Coinbase",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // The client order id is mandatory.
+        let client_order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => <Self as api::GenerateOrderId>::new_order_id(""),
+        };
+
+        let body = CoinbaseOrder {
+            client_order_id: &client_order_id,
+            product_id: symbol.name(),
+            side: order.side.as_str(),
+            order_configuration,
+        };
+        let body = serde_json::to_string(&body).expect("invalid json");
+
+        let order_ids = self.order_ids.clone();
+
+        let fut = self.request("orders", Method::POST, QueryString::new(), body)
+            .and_then(move |body| {
+                let ack: CoinbaseOrderAck = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                let server_order_id = match ack.success_response {
+                    Some(response) if ack.success => response.order_id,
+                    _ => {
+                        let (error, message) = match ack.error_response {
+                            Some(response) => (
+                                response.new_order_failure_reason
+                                    .or(response.error)
+                                    .unwrap_or_default(),
+                                response.message,
+                            ),
+                            None => (String::new(), None),
+                        };
+                        let error = RestError::rejected(error, message);
+                        let kind = error.kind();
+                        return Err(api::errors::ApiError::RestError(error.context(kind).into()));
+                    }
+                };
+
+                order_ids.insert(client_order_id.clone(), server_order_id);
+                debug!("insert order id {} (from REST)", client_order_id);
+
+                Ok(OrderAck {
+                    order_id: client_order_id,
+                }.timestamped())
+            });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        // Orders can only be canceled by their server order id.
+        let server_order_id = match self.order_ids.get(&cancel.order_id) {
+            Some(order_id) => order_id.clone(),
+            None => {
+                warn!("called `cancel` with a not yet inserted order id");
+                return Box::new(futures::future::err(api::errors::ApiError::RestError(
+                    api::errors::RestErrorKind::Specific(
+                        api::errors::CancelErrorKind::UnknownOrder
+                    ).into()
+                )));
+            }
+        };
+
+        let body = CoinbaseCancel {
+            order_ids: &[&server_order_id],
+        };
+        let body = serde_json::to_string(&body).expect("invalid json");
+
+        let fut = self.request("orders/batch_cancel", Method::POST, QueryString::new(), body)
+            .and_then(|body| {
+                let ack: CoinbaseCancelAck = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                match ack.results.into_iter().next() {
+                    Some(ref result) if result.success => Ok(CancelAck.timestamped()),
+                    result => {
+                        let error = result.and_then(|result| result.failure_reason)
+                            .unwrap_or_default();
+                        let error = RestError::rejected(error, None);
+                        let kind = error.kind();
+                        Err(api::errors::ApiError::RestError(error.context(kind).into()))
+                    }
+                }
+            });
+        Box::new(fut)
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("time", Method::GET, QueryString::new(), String::new()).and_then(|body| {
+            let time: CoinbaseTime<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let timestamp: Timestamp = time.epochMillis.parse()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(timestamp))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push("limit", 250);
+
+        self.request("accounts", Method::GET, query, String::new()).and_then(|body| {
+            let accounts: CoinbaseAccounts<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let balances = accounts.accounts.into_iter().map(|account| {
+                (account.currency.to_owned(), Balance {
+                    free: account.available_balance.value.to_owned(),
+                    locked: account.hold.value.to_owned(),
+                })
+            }).collect();
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = STATUS_ENDPOINT.parse().expect("invalid address");
+
+        self.http_client.get(address).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError).and_then(|(status, body)| {
+            if !status.is_success() {
+                let error = RestError::from_coinbase_error(status, None, None);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+
+            let page: CoinbaseStatusPage<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match page.status.indicator {
+                "none" => ExchangeStatus::Normal,
+                "minor" => ExchangeStatus::Degraded,
+                "maintenance" => ExchangeStatus::Maintenance,
+                _ => ExchangeStatus::Outage,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        // The public endpoint does not require a key pair.
+        self.request("market/products", Method::GET, QueryString::new(), String::new())
+            .and_then(|body| {
+                let products: CoinbaseProducts<'_> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                let mut symbols = HashMap::new();
+                for p in products.products {
+                    if p.trading_disabled {
+                        continue;
+                    }
+
+                    let (price_tick, size_tick) = match (
+                        Tick::tick_size(p.quote_increment),
+                        Tick::tick_size(p.base_increment)
+                    ) {
+                        (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                        _ => {
+                            error!("cannot read ticks for symbol `{}`", p.product_id);
+                            continue;
+                        }
+                    };
+
+                    match Symbol::new(p.product_id, price_tick, size_tick) {
+                        Some(symbol) => {
+                            symbols.insert(p.product_id.to_uppercase(), symbol);
+                        }
+                        None => error!("symbol name too long: `{}`", p.product_id),
+                    }
+                }
+                Ok(symbols)
+            })
+    }
+}
@@ -0,0 +1,77 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::coinbase::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+const TIME: &str = "2023-02-09T20:32:50.714964855Z";
+const TIMESTAMP: u64 = 1_675_974_770_714;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC-USD", Tick::new(100), Tick::new(100_000_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[String]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+fn book(sequence_num: u64, type_: &str, levels: &[(&str, &str, &str)]) -> String {
+    let updates = levels.iter().map(|(side, price, size)| format!(
+        r#"{{"side":"{}","event_time":"{}","price_level":"{}","new_quantity":"{}"}}"#,
+        side,
+        TIME,
+        price,
+        size
+    )).collect::<Vec<_>>();
+
+    format!(
+        r#"{{"channel":"l2_data","client_id":"","timestamp":"{}","sequence_num":{},
+            "events":[{{"type":"{}","product_id":"BTC-USD","updates":[{}]}}]}}"#,
+        TIME,
+        sequence_num,
+        type_,
+        updates.join(",")
+    )
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let frames = [
+        book(0, "snapshot", &[("bid", "21921.73", "0.06317902"), ("offer", "21921.74", "1.5")]),
+        book(1, "update", &[("offer", "21921.74", "0"), ("bid", "21921.50", "0.1")]),
+
+        // After a gap, the snapshot sent again is turned into updates.
+        book(5, "snapshot", &[("bid", "21921.73", "0.06317902"), ("offer", "21922.00", "2")]),
+    ];
+
+    let notifs = parse(&frames);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(2_192_173, 6_317_902, Side::Bid).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(2_192_174, 150_000_000, Side::Ask).with_timestamp(TIMESTAMP),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(2_192_174, 0, Side::Ask).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(2_192_150, 10_000_000, Side::Bid).with_timestamp(TIMESTAMP),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(2_192_150, 0, Side::Bid).with_timestamp(TIMESTAMP),
+            LimitUpdate::new(2_192_200, 200_000_000, Side::Ask).with_timestamp(TIMESTAMP),
+        ]),
+    ]);
+}
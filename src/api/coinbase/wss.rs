@@ -0,0 +1,518 @@
+use futures::sync::mpsc::unbounded;
+use std::{mem, thread};
+use std::collections::HashMap;
+use chashmap::CHashMap;
+use std::sync::Arc;
+use log::{debug, error, warn};
+use failure::bail;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    OrderConfirmation,
+    OrderUpdate,
+    Trade,
+    OrderExpiration,
+};
+use crate::api::symbol::Symbol;
+use crate::api::stream::NotificationStream;
+use crate::api::wss;
+use crate::api::timestamp::{convert_str_timestamp, IntoTimestamped};
+use crate::api::coinbase::{Keys, Client};
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let streaming_endpoint = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+        let order_ids = self.order_ids.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", streaming_endpoint);
+
+            if let Err(err) = ws::connect(streaming_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::False,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        out: None,
+                        sequence_num: None,
+                        order_book: OrderBook::new(),
+                        orders: HashMap::new(),
+                        order_ids: order_ids.clone(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Cumulated state of an order as of its last update: the `user` channel only carries
+/// cumulated quantities, individual fills are recovered from the changes.
+#[derive(Clone, PartialEq, Debug)]
+struct OrderState {
+    order_id: String,
+    filled: TickUnit,
+    filled_value: f64,
+    fees: f64,
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Set once the connection is open, needed for subscribing to the book again from
+    /// within `on_message`.
+    out: Option<ws::Sender>,
+
+    /// Sequence number of the last message, shared by all the channels.
+    sequence_num: Option<u64>,
+
+    /// Local copy of the order book: snapshots sent again after a resubscription are
+    /// turned into updates against it.
+    order_book: OrderBook,
+
+    /// server order id => order state
+    orders: HashMap<String, OrderState>,
+
+    /// client order id => server order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct CoinbaseSubscription<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    product_ids: &'a [&'a str],
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jwt: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseHeader<'a> {
+    #[serde(rename = "type")]
+    type_: Option<&'a str>,
+    message: Option<&'a str>,
+    channel: Option<&'a str>,
+    sequence_num: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseMessage<'a, T> {
+    timestamp: &'a str,
+    events: Vec<T>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseLevel<'a> {
+    side: &'a str,
+    price_level: &'a str,
+    new_quantity: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseBookEvent<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    product_id: &'a str,
+    #[serde(borrow)]
+    updates: Vec<CoinbaseLevel<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseTrade<'a> {
+    product_id: &'a str,
+    price: &'a str,
+    size: &'a str,
+    side: &'a str,
+    time: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseTradesEvent<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    #[serde(borrow)]
+    trades: Vec<CoinbaseTrade<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseOrder<'a> {
+    order_id: &'a str,
+    client_order_id: &'a str,
+    product_id: &'a str,
+    order_side: &'a str,
+    status: &'a str,
+    limit_price: Option<&'a str>,
+    cumulative_quantity: &'a str,
+    leaves_quantity: &'a str,
+    avg_price: &'a str,
+    total_fees: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct CoinbaseUserEvent<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    #[serde(borrow)]
+    orders: Vec<CoinbaseOrder<'a>>,
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys and which is not connected,
+    /// for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: None,
+            out: None,
+            sequence_num: None,
+            order_book: OrderBook::new(),
+            orders: HashMap::new(),
+            order_ids: Arc::new(CHashMap::new()),
+        }
+    }
+
+    fn send_request(&mut self, type_: &str, channel: &str) -> ws::Result<()> {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        // Public channels also accept a token, which raises the rate limits.
+        let subscription = CoinbaseSubscription {
+            type_,
+            product_ids: &[self.symbol.name()],
+            channel,
+            jwt: self.keys.as_ref().map(|keys| keys.jwt(None)),
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `CoinbaseSubscription`: `{}`", err);
+            }
+        }
+    }
+
+    fn convert_coinbase_side(&self, side: &str) -> Result<Side, failure::Error> {
+        let side = match side {
+            "bid" | "BUY" => Side::Bid,
+            "offer" | "SELL" => Side::Ask,
+            other => bail!("wrong side: `{}`", other),
+        };
+        Ok(side)
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: CoinbaseMessage<'_, CoinbaseBookEvent<'_>> = serde_json::from_str(json)?;
+        let timestamp = convert_str_timestamp(message.timestamp)?;
+
+        let mut updates = Vec::new();
+        for event in message.events {
+            if event.product_id != self.symbol.name() {
+                continue;
+            }
+
+            let levels = event.updates.iter().map(|level| {
+                Ok(LimitUpdate {
+                    side: self.convert_coinbase_side(level.side)?,
+                    price: self.symbol.price_tick().ticked(level.price_level)?,
+                    size: self.symbol.size_tick().ticked(level.new_quantity)?,
+                })
+            }).collect::<Result<Vec<_>, failure::Error>>()?;
+
+            // Levels which are not part of a new snapshot were removed in the meantime.
+            if event.type_ == "snapshot" {
+                let contains = |side: Side, price: TickUnit| {
+                    levels.iter().any(|level| level.side == side && level.price == price)
+                };
+                let asks = self.order_book.ask()
+                    .filter(|(price, _)| !contains(Side::Ask, **price))
+                    .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+                let bids = self.order_book.bid()
+                    .filter(|(price, _)| !contains(Side::Bid, **price))
+                    .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+                let removed = asks.chain(bids).collect::<Vec<_>>();
+                for update in &removed {
+                    self.order_book.update(*update);
+                }
+                updates.extend(removed);
+            }
+
+            for level in levels {
+                if self.order_book.size_at_limit(level.side, level.price) != level.size {
+                    self.order_book.update(level);
+                    updates.push(level);
+                }
+            }
+        }
+
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.with_timestamp(timestamp))
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: CoinbaseMessage<'_, CoinbaseTradesEvent<'_>> = serde_json::from_str(json)?;
+
+        for event in message.events {
+            // The snapshot only holds past trades.
+            if event.type_ != "update" {
+                continue;
+            }
+
+            for trade in event.trades {
+                if trade.product_id != self.symbol.name() {
+                    continue;
+                }
+
+                // As on the legacy API, the side is the one of the maker order.
+                let trade = Notification::Trade(Trade {
+                    price: self.symbol.price_tick().ticked(trade.price)?,
+                    size: self.symbol.size_tick().ticked(trade.size)?,
+                    maker_side: self.convert_coinbase_side(trade.side)?,
+                }.with_timestamp(convert_str_timestamp(trade.time)?));
+                out.unbounded_send(trade).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_user(&mut self, json: &str, out: Option<&wss::NotifSender>) -> Result<(), failure::Error> {
+        let message: CoinbaseMessage<'_, CoinbaseUserEvent<'_>> = serde_json::from_str(json)?;
+        let timestamp = convert_str_timestamp(message.timestamp)?;
+        let size_tick = self.symbol.size_tick();
+        let send = |notification| {
+            if let Some(out) = out {
+                out.unbounded_send(notification).unwrap();
+            }
+        };
+
+        for event in message.events {
+            // The snapshot lists the open orders, which may already be known.
+            let snapshot = event.type_ == "snapshot";
+
+            for order in event.orders {
+                if order.product_id != self.symbol.name() {
+                    continue;
+                }
+
+                let filled = size_tick.ticked(order.cumulative_quantity)?;
+                let remaining_size = size_tick.ticked(order.leaves_quantity)?;
+                let filled_value = filled as f64 / size_tick.ticks_per_unit() as f64
+                    * order.avg_price.parse::<f64>()?;
+                let fees = order.total_fees.parse::<f64>()?;
+
+                let previous = match self.orders.get(order.order_id) {
+                    Some(previous) => previous.clone(),
+                    None => {
+                        // The order id specified by the user, which defaults to the server
+                        // order id in case it was left unspecified.
+                        let order_id = if order.client_order_id.is_empty() {
+                            order.order_id.to_owned()
+                        } else {
+                            order.client_order_id.to_owned()
+                        };
+
+                        // Don't forget to update the concurrent map in case the
+                        // WebSocket notif arrives before the HTTP response.
+                        self.order_ids.insert(order_id.clone(), order.order_id.to_owned());
+                        debug!("insert order id {} (from WSS)", order_id);
+
+                        let state = OrderState {
+                            order_id,
+                            filled: 0,
+                            filled_value: 0.,
+                            fees: 0.,
+                        };
+
+                        if snapshot {
+                            OrderState {
+                                filled,
+                                filled_value,
+                                fees,
+                                ..state
+                            }
+                        } else {
+                            let price = match order.limit_price {
+                                Some(price) => self.symbol.price_tick().ticked(price)?,
+                                None => {
+                                    warn!("received an order without limit price");
+                                    0
+                                }
+                            };
+
+                            let confirmation = OrderConfirmation {
+                                order_id: state.order_id.clone(),
+                                price,
+                                size: filled + remaining_size,
+                                side: self.convert_coinbase_side(order.order_side)?,
+                            }.with_timestamp(timestamp);
+                            send(Notification::OrderConfirmation(confirmation));
+                            state
+                        }
+                    }
+                };
+
+                if filled > previous.filled {
+                    let consumed_size = filled - previous.filled;
+                    let consumed_units = consumed_size as f64 / size_tick.ticks_per_unit() as f64;
+
+                    let update = OrderUpdate {
+                        order_id: previous.order_id.clone(),
+                        consumed_size,
+                        remaining_size,
+                        consumed_price: self.symbol.price_tick().rounded(
+                            (filled_value - previous.filled_value) / consumed_units
+                        ),
+                        commission: self.symbol.commission_tick().rounded(
+                            fees - previous.fees
+                        ),
+                    }.with_timestamp(timestamp);
+                    send(Notification::OrderUpdate(update));
+                }
+
+                match order.status {
+                    "FILLED" => {
+                        self.orders.remove(order.order_id);
+                    }
+                    "CANCELLED" | "EXPIRED" | "FAILED" => {
+                        self.orders.remove(order.order_id);
+
+                        let expiration = OrderExpiration {
+                            order_id: previous.order_id,
+                            filled_size: Some(filled),
+                            remaining_size: Some(remaining_size),
+                        }.with_timestamp(timestamp);
+                        send(Notification::OrderExpiration(expiration));
+                    }
+                    _ => {
+                        self.orders.insert(order.order_id.to_owned(), OrderState {
+                            filled,
+                            filled_value,
+                            fees,
+                            ..previous
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let header: CoinbaseHeader<'_> = serde_json::from_str(json)?;
+
+        if header.type_ == Some("error") {
+            bail!("{}", header.message.unwrap_or_default());
+        }
+
+        // Sequence numbers are shared by all the channels of the connection: after a
+        // gap, the book is subscribed to again in order to receive a fresh snapshot.
+        if let Some(sequence_num) = header.sequence_num {
+            let previous = self.sequence_num.replace(sequence_num);
+            match previous {
+                Some(previous) if sequence_num != previous + 1 => {
+                    warn!("missed messages before sequence number {}", sequence_num);
+                    if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+                        self.send_request("unsubscribe", "level2")?;
+                        self.send_request("subscribe", "level2")?;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        match header.channel {
+            Some("l2_data") if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book(json, out)?;
+            }
+            Some("market_trades") if self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_trades(json, out)?;
+            }
+
+            // Order states must be tracked even if they are not forwarded.
+            Some("user") => {
+                let forward = self.flags.contains(NotificationFlags::ORDERS);
+                self.parse_user(json, if forward { Some(out) } else { None })?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        // Heartbeats keep the connection alive when the other channels are quiet.
+        self.send_request("subscribe", "heartbeats")?;
+
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.send_request("subscribe", "level2")?;
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            self.send_request("subscribe", "market_trades")?;
+        }
+        if self.keys.is_some() {
+            self.send_request("subscribe", "user")?;
+        }
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The `user` channel is kept, `ORDERS` only acts as a filter.
+        match (previous.contains(NotificationFlags::TRADES), flags.contains(NotificationFlags::TRADES)) {
+            (false, true) => self.send_request("subscribe", "market_trades")?,
+            (true, false) => self.send_request("unsubscribe", "market_trades")?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => self.send_request("subscribe", "level2")?,
+
+            // The book will be forwarded again from scratch.
+            (true, false) => {
+                self.order_book = OrderBook::new();
+                self.send_request("unsubscribe", "level2")?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
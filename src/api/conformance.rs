@@ -0,0 +1,426 @@
+//! A module defining a conformance suite exercising an `ApiClient` implementation
+//! against a live venue, usually its sandbox.
+//!
+//! The suite is meant to be run from the integration tests of a backend, so that
+//! every backend is validated the same way: symbol discovery, order lifecycle,
+//! cancellation and notification flags.
+//!
+//! # Example
+//! ```no_run
+//! # extern crate trade;
+//! # use std::sync::Arc;
+//! # use futures::prelude::*;
+//! # use trade::prelude::*;
+//! # use trade::api::Order;
+//! # use trade::api::conformance::Suite;
+//! fn check<C>(client: Arc<C>) where C: ApiClient + Send + Sync + 'static {
+//!     // A bid far below the market, which should rest on the book.
+//!     let order = Order::new("0.0001", "1", Side::Bid);
+//!     let suite = Suite::new(client, "BTCUSDT", order);
+//!     let report = tokio::runtime::Runtime::new()
+//!         .unwrap()
+//!         .block_on(suite.run())
+//!         .unwrap();
+//!     assert!(report.is_success(), "{:?}", report);
+//! }
+//! # fn main() { }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::{future, stream};
+use futures::prelude::*;
+use failure::format_err;
+use tokio::timer::{Delay, Timeout};
+use crate::api::{ApiClient, Notification, NotificationFlags, Order, Cancel};
+use crate::api::errors::{ApiError, CancelErrorKind, RestErrorKind};
+use crate::api::symbol::{Symbol, IntoWithSymbol};
+use crate::api::timestamp::timestamp_ms;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// A check performed by the conformance suite.
+pub enum Check {
+    /// `find_symbol` finds the tested symbol and does not find an unknown one.
+    SymbolDiscovery,
+
+    /// An order is acknowledged, confirmed on the stream, then canceled and expired
+    /// on the stream. Only order notifications are received on an `ORDERS` stream.
+    OrderLifecycle,
+
+    /// Canceling an unknown order fails with `CancelErrorKind::UnknownOrder`.
+    CancelUnknownOrder,
+
+    /// An `ORDER_BOOK` stream receives the order book, and only limit updates.
+    StreamFlags,
+}
+
+impl Check {
+    /// Return all the checks, in the order they are run by `Suite::run`.
+    pub fn all() -> Vec<Check> {
+        vec![
+            Check::SymbolDiscovery,
+            Check::OrderLifecycle,
+            Check::CancelUnknownOrder,
+            Check::StreamFlags,
+        ]
+    }
+}
+
+#[derive(Debug)]
+/// Result of a run of the conformance suite.
+pub struct Report {
+    /// Result of each check, in the order they were run.
+    pub results: Vec<(Check, Result<(), failure::Error>)>,
+}
+
+impl Report {
+    /// Return `true` if every check succeeded.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// Return the checks which failed, along with their error.
+    pub fn failures(&self) -> impl Iterator<Item = (Check, &failure::Error)> {
+        self.results.iter().filter_map(|(check, result)| {
+            result.as_ref().err().map(|err| (*check, err))
+        })
+    }
+}
+
+/// A conformance suite for a given client and symbol.
+pub struct Suite<C> {
+    client: Arc<C>,
+    symbol: String,
+    order: Order,
+    timeout: Duration,
+    settle: Duration,
+    seed: Option<String>,
+}
+
+// `#[derive(Clone)]` would require `C: Clone`.
+impl<C> Clone for Suite<C> {
+    fn clone(&self) -> Self {
+        Suite {
+            client: self.client.clone(),
+            symbol: self.symbol.clone(),
+            order: self.order.clone(),
+            timeout: self.timeout,
+            settle: self.settle,
+            seed: self.seed.clone(),
+        }
+    }
+}
+
+impl<C> Suite<C> where C: ApiClient + Send + Sync + 'static {
+    /// Return a new suite testing `client` on `symbol`. `order` is sent during the order
+    /// lifecycle check: it must be valid for the tested account and must rest on the book,
+    /// i.e. its price should be far from the market. Its order id, if any, is replaced.
+    pub fn new(client: Arc<C>, symbol: &str, order: Order) -> Self {
+        Suite {
+            client,
+            symbol: symbol.to_owned(),
+            order,
+            timeout: Duration::from_secs(10),
+            settle: Duration::from_secs(2),
+            seed: None,
+        }
+    }
+
+    /// Maximum delay for receiving a response or an expected notification. Default
+    /// is 10s.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Delay left to a new stream for connecting before sending requests whose
+    /// notifications are expected on this stream. Also used as the duration during
+    /// which notifications are sampled by the stream flags check. Default is 2s.
+    pub fn with_settle_delay(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// Derive the hints of the order ids sent by the suite from `seed` instead of the
+    /// current time, so that the requests are the same from one run to the other, e.g.
+    /// for replaying fixtures (see `api::fixtures`). Only affects the backends whose
+    /// order ids are derived from the hint.
+    pub fn with_order_id_seed(mut self, seed: &str) -> Self {
+        self.seed = Some(seed.to_owned());
+        self
+    }
+
+    /// Run all the checks one after the other.
+    pub fn run(&self) -> impl Future<Item = Report, Error = ()> + Send + 'static {
+        let suite = self.clone();
+        stream::iter_ok(Check::all())
+            .and_then(move |check| suite.check(check).then(move |result| Ok((check, result))))
+            .collect()
+            .map(|results| Report { results })
+    }
+
+    /// Run a single check.
+    pub fn check(&self, check: Check)
+        -> Box<dyn Future<Item = (), Error = failure::Error> + Send + 'static>
+    {
+        match check {
+            Check::SymbolDiscovery => Box::new(future::result(self.symbol_discovery())),
+            Check::OrderLifecycle => self.order_lifecycle(),
+            Check::CancelUnknownOrder => self.cancel_unknown_order(),
+            Check::StreamFlags => self.stream_flags(),
+        }
+    }
+
+    fn seed(&self) -> String {
+        self.seed.clone().unwrap_or_else(|| timestamp_ms().to_string())
+    }
+
+    fn find_symbol(&self) -> Result<Symbol, failure::Error> {
+        self.client.find_symbol(&self.symbol)
+            .ok_or_else(|| format_err!("symbol `{}` not found", self.symbol))
+    }
+
+    fn symbol_discovery(&self) -> Result<(), failure::Error> {
+        let symbol = self.find_symbol()?;
+        if !symbol.name().eq_ignore_ascii_case(&self.symbol) {
+            return Err(format_err!(
+                "looked for symbol `{}`, found `{}`", self.symbol, symbol.name()
+            ));
+        }
+
+        let unknown = "CONFORMANCE-UNKNOWN-SYMBOL";
+        if let Some(symbol) = self.client.find_symbol(unknown) {
+            return Err(format_err!("looked for symbol `{}`, found `{}`", unknown, symbol.name()));
+        }
+        Ok(())
+    }
+
+    fn order_lifecycle(&self)
+        -> Box<dyn Future<Item = (), Error = failure::Error> + Send + 'static>
+    {
+        let symbol = match self.find_symbol() {
+            Ok(symbol) => symbol,
+            Err(err) => return Box::new(future::err(err)),
+        };
+
+        let hint = format!("conformance{}", self.seed());
+        let order = self.order.clone().with_order_id::<C>(&hint);
+        let order_id = order.order_id().unwrap().to_owned();
+        let flags = NotificationFlags::ORDERS;
+        let notifications = self.client.stream_with_flags(symbol, flags);
+
+        let client = self.client.clone();
+        let timeout = self.timeout;
+
+        let sent = Delay::new(Instant::now() + self.settle)
+            .map_err(failure::Error::from)
+            .and_then({
+                let client = client.clone();
+                move |()| {
+                    with_timeout(
+                        client.order(order.with_symbol(symbol)).map_err(failure::Error::from),
+                        timeout,
+                        "order acknowledgment"
+                    )
+                }
+            });
+
+        let confirmed = sent.and_then({
+            let order_id = order_id.clone();
+            move |ack| {
+                if ack.order_id != order_id {
+                    return future::Either::A(future::err(format_err!(
+                        "sent order `{}`, acknowledged order `{}`", order_id, ack.order_id
+                    )));
+                }
+
+                future::Either::B(wait_for(
+                    notifications,
+                    flags,
+                    timeout,
+                    "order confirmation",
+                    move |notif| match notif {
+                        Notification::OrderConfirmation(confirmation) => {
+                            confirmation.order_id == order_id
+                        }
+                        _ => false,
+                    }
+                ))
+            }
+        });
+
+        let canceled = confirmed.and_then({
+            let order_id = order_id.clone();
+            move |notifications| {
+                let cancel = Cancel::new(order_id);
+                with_timeout(
+                    client.cancel(cancel.with_symbol(symbol)).map_err(failure::Error::from),
+                    timeout,
+                    "cancel acknowledgment"
+                ).map(move |_| notifications)
+            }
+        });
+
+        let expired = canceled.and_then(move |notifications| {
+            wait_for(
+                notifications,
+                flags,
+                timeout,
+                "order expiration",
+                move |notif| match notif {
+                    Notification::OrderExpiration(expiration) => {
+                        expiration.order_id == order_id
+                    }
+                    _ => false,
+                }
+            )
+        });
+
+        Box::new(expired.map(|_| ()))
+    }
+
+    fn cancel_unknown_order(&self)
+        -> Box<dyn Future<Item = (), Error = failure::Error> + Send + 'static>
+    {
+        let symbol = match self.find_symbol() {
+            Ok(symbol) => symbol,
+            Err(err) => return Box::new(future::err(err)),
+        };
+
+        let hint = format!("conformanceunknown{}", self.seed());
+        let cancel = Cancel::new(C::new_order_id(&hint));
+        let request = self.client.cancel(cancel.with_symbol(symbol)).then(|result| {
+            match result {
+                Err(ApiError::RestError(ref err))
+                    if err.kind() == RestErrorKind::Specific(CancelErrorKind::UnknownOrder) =>
+                {
+                    Ok(())
+                }
+                Err(err) => Err(format_err!("expected an unknown order error, got `{}`", err)),
+                Ok(_) => Err(format_err!("canceling an unknown order succeeded")),
+            }
+        });
+        Box::new(with_timeout(request, self.timeout, "cancel response"))
+    }
+
+    fn stream_flags(&self)
+        -> Box<dyn Future<Item = (), Error = failure::Error> + Send + 'static>
+    {
+        let symbol = match self.find_symbol() {
+            Ok(symbol) => symbol,
+            Err(err) => return Box::new(future::err(err)),
+        };
+
+        let flags = NotificationFlags::ORDER_BOOK;
+        let notifications = self.client.stream_with_flags(symbol, flags);
+        let settle = self.settle;
+
+        let received = wait_for(
+            notifications,
+            flags,
+            self.timeout + self.settle,
+            "order book",
+            |notif| match notif {
+                Notification::LimitUpdates(..) => true,
+                _ => false,
+            }
+        );
+
+        // Keep sampling the stream: any notification not allowed by `flags` is an error,
+        // reaching the end of the sampling period is a success.
+        let sampled = received.and_then(move |notifications| {
+            let sampling = WaitFor {
+                notifications: Some(notifications),
+                flags,
+                what: "the end of the sampling period",
+                pred: |_: &Notification| false,
+            };
+            Timeout::new(sampling, settle).then(|result| match result {
+                Err(ref err) if err.is_elapsed() => Ok(()),
+                Err(err) => Err(
+                    err.into_inner().unwrap_or_else(|| format_err!("timer error"))
+                ),
+                Ok(_) => unreachable!(),
+            })
+        });
+
+        Box::new(sampled)
+    }
+}
+
+/// Return the notification flag enabling `notif`, if any.
+fn required_flag(notif: &Notification) -> NotificationFlags {
+    match notif {
+        Notification::Trade(..) => NotificationFlags::TRADES,
+        Notification::LimitUpdates(..) => NotificationFlags::ORDER_BOOK,
+        Notification::OrderConfirmation(..) |
+        Notification::OrderUpdate(..) |
+        Notification::OrderExpiration(..) => NotificationFlags::ORDERS,
+        Notification::PositionUpdate(..) => NotificationFlags::POSITIONS,
+        Notification::IndexPrice(..) => NotificationFlags::INDEX_PRICES,
+        Notification::ExchangeStatus(..) |
+        Notification::TradingHalted(..) |
+        Notification::TradingResumed(..) |
+        Notification::AuctionUpdate(..) => NotificationFlags::empty(),
+    }
+}
+
+fn with_timeout<F>(future: F, timeout: Duration, what: &'static str)
+    -> impl Future<Item = F::Item, Error = failure::Error>
+        where F: Future<Error = failure::Error>
+{
+    Timeout::new(future, timeout).map_err(move |err| {
+        err.into_inner().unwrap_or_else(|| format_err!("timed out waiting for {}", what))
+    })
+}
+
+/// Wait for a notification matching `pred` and return the stream. Fail if a notification
+/// not allowed by `flags` is received first.
+fn wait_for<S, F>(notifications: S, flags: NotificationFlags, timeout: Duration, what: &'static str, pred: F)
+    -> impl Future<Item = S, Error = failure::Error>
+        where S: Stream<Item = Notification, Error = ()>, F: FnMut(&Notification) -> bool
+{
+    let wait = WaitFor {
+        notifications: Some(notifications),
+        flags,
+        what,
+        pred,
+    };
+    with_timeout(wait, timeout, what)
+}
+
+struct WaitFor<S, F> {
+    notifications: Option<S>,
+    flags: NotificationFlags,
+    what: &'static str,
+    pred: F,
+}
+
+impl<S, F> Future for WaitFor<S, F>
+    where S: Stream<Item = Notification, Error = ()>, F: FnMut(&Notification) -> bool
+{
+    type Item = S;
+    type Error = failure::Error;
+
+    fn poll(&mut self) -> Poll<S, failure::Error> {
+        loop {
+            let notif = match self.notifications.as_mut().expect("polled after completion").poll() {
+                Ok(Async::Ready(Some(notif))) => notif,
+                Ok(Async::Ready(None)) | Err(()) => {
+                    return Err(format_err!("stream ended while waiting for {}", self.what));
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+            };
+
+            if !self.flags.contains(required_flag(&notif)) {
+                return Err(format_err!(
+                    "received {:?} on a stream with flags {:?}", notif, self.flags
+                ));
+            }
+
+            if (self.pred)(&notif) {
+                return Ok(Async::Ready(self.notifications.take().unwrap()));
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! A module defining a best-effort suppression of the notifications delivered twice,
+//! e.g. when fills or trades are replayed after a reconnect.
+//!
+//! Notifications do not carry exchange sequence numbers, so each one is identified by
+//! its content: an order update by its order id and remaining size, which strictly
+//! decreases with each fill, a confirmation or expiration by its order id, and a trade
+//! by its timestamp, price, size and maker side. Keys are remembered for a window
+//! expressed in ms of exchange time, relative to the latest timestamp seen so far: a
+//! replayed notification older than the window is forwarded again.
+//!
+//! Limit updates carry absolute sizes and are idempotent, and the exchange status is
+//! timestamped locally, hence these notifications are always forwarded.
+
+mod test;
+mod stream;
+
+pub use self::stream::DedupStream;
+
+use std::collections::{HashSet, VecDeque};
+use crate::api::{Notification, Trade};
+use crate::api::timestamp::Timestamp;
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Key {
+    Trade(Timestamp, Trade),
+    OrderConfirmation(String),
+    OrderUpdate(String, TickUnit),
+    OrderExpiration(String),
+}
+
+/// Return the key identifying `notif` along with its timestamp, or `None` if `notif`
+/// is always forwarded.
+fn key(notif: &Notification, trades: bool) -> Option<(Timestamp, Key)> {
+    let key = match notif {
+        Notification::Trade(trade) if trades => {
+            (trade.timestamp(), Key::Trade(trade.timestamp(), **trade))
+        }
+        Notification::OrderConfirmation(confirmation) => {
+            (confirmation.timestamp(), Key::OrderConfirmation(confirmation.order_id.clone()))
+        }
+        Notification::OrderUpdate(update) => (
+            update.timestamp(),
+            Key::OrderUpdate(update.order_id.clone(), update.remaining_size)
+        ),
+        Notification::OrderExpiration(expiration) => {
+            (expiration.timestamp(), Key::OrderExpiration(expiration.order_id.clone()))
+        }
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// Filter out the notifications already seen within a time window.
+pub struct Deduplicator {
+    window: Timestamp,
+    trades: bool,
+    seen: HashSet<Key>,
+
+    /// Seen keys in arrival order, for evicting them once out of the window.
+    history: VecDeque<(Timestamp, Key)>,
+
+    latest: Timestamp,
+    duplicates: usize,
+}
+
+impl Deduplicator {
+    /// Return a new `Deduplicator` remembering each notification during `window` ms.
+    pub fn new(window: Timestamp) -> Self {
+        Deduplicator {
+            window,
+            trades: true,
+            seen: HashSet::new(),
+            history: VecDeque::new(),
+            latest: 0,
+            duplicates: 0,
+        }
+    }
+
+    /// Whether to filter out trades, `true` by default. Distinct trades with the same
+    /// timestamp, price, size and maker side cannot be told apart, hence only the first
+    /// one is forwarded: disable this if the exchange timestamps lack precision.
+    pub fn with_trades(mut self, trades: bool) -> Self {
+        self.trades = trades;
+        self
+    }
+
+    /// Return the window, in ms.
+    pub fn window(&self) -> Timestamp {
+        self.window
+    }
+
+    /// Return the number of notifications filtered out so far.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    /// Return `true` if `notif` was already seen within the window, in which case it
+    /// should be dropped. Otherwise, remember `notif` and return `false`.
+    pub fn is_duplicate(&mut self, notif: &Notification) -> bool {
+        let (timestamp, key) = match key(notif, self.trades) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        if self.seen.contains(&key) {
+            self.duplicates += 1;
+            return true;
+        }
+
+        self.latest = std::cmp::max(self.latest, timestamp);
+        while let Some((oldest, _)) = self.history.front() {
+            if oldest.saturating_add(self.window) >= self.latest {
+                break;
+            }
+            if let Some((_, key)) = self.history.pop_front() {
+                self.seen.remove(&key);
+            }
+        }
+
+        if timestamp.saturating_add(self.window) >= self.latest {
+            self.seen.insert(key.clone());
+            self.history.push_back((timestamp, key));
+        }
+        false
+    }
+}
@@ -0,0 +1,48 @@
+use futures::prelude::*;
+use crate::api::Notification;
+use crate::api::dedup::Deduplicator;
+
+impl Deduplicator {
+    /// Drive `self` from a notification stream, e.g. a `FailoverStream` or a stream
+    /// which is reconnected on failure. The returned stream ends when `notifications`
+    /// ends.
+    pub fn stream<S>(self, notifications: S) -> DedupStream<S>
+        where S: Stream<Item = Notification, Error = ()>
+    {
+        DedupStream {
+            deduplicator: self,
+            notifications,
+        }
+    }
+}
+
+/// A stream of notifications without duplicates, see `Deduplicator::stream`.
+pub struct DedupStream<S> {
+    deduplicator: Deduplicator,
+    notifications: S,
+}
+
+impl<S> DedupStream<S> {
+    /// Return the underlying `Deduplicator`.
+    pub fn deduplicator(&self) -> &Deduplicator {
+        &self.deduplicator
+    }
+}
+
+impl<S> Stream for DedupStream<S> where S: Stream<Item = Notification, Error = ()> {
+    type Item = Notification;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Notification>, ()> {
+        loop {
+            match self.notifications.poll()? {
+                Async::Ready(Some(notif)) => {
+                    if !self.deduplicator.is_duplicate(&notif) {
+                        return Ok(Async::Ready(Some(notif)));
+                    }
+                }
+                other => return Ok(other),
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::api::{Notification, Trade, OrderUpdate, OrderExpiration};
+use crate::api::dedup::Deduplicator;
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn trade(timestamp: Timestamp, price: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size: 1,
+        maker_side: Side::Ask,
+    }.with_timestamp(timestamp))
+}
+
+fn update(timestamp: Timestamp, order_id: &str, remaining_size: TickUnit) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: 1,
+        remaining_size,
+        consumed_price: 100,
+        commission: 0,
+    }.with_timestamp(timestamp))
+}
+
+#[test]
+fn replayed_fills() {
+    let mut dedup = Deduplicator::new(1000);
+    assert!(!dedup.is_duplicate(&update(10, "a", 3)));
+    assert!(!dedup.is_duplicate(&update(20, "a", 2)));
+    assert!(!dedup.is_duplicate(&update(20, "b", 2)));
+
+    // Reconnect and replay.
+    assert!(dedup.is_duplicate(&update(10, "a", 3)));
+    assert!(dedup.is_duplicate(&update(20, "a", 2)));
+    assert!(!dedup.is_duplicate(&update(30, "a", 1)));
+
+    let expiration = Notification::OrderExpiration(OrderExpiration {
+        order_id: "b".to_owned(),
+        filled_size: Some(1),
+        remaining_size: Some(2),
+    }.with_timestamp(40));
+    assert!(!dedup.is_duplicate(&expiration));
+    assert!(dedup.is_duplicate(&expiration));
+    assert_eq!(dedup.duplicates(), 3);
+}
+
+#[test]
+fn trades() {
+    let mut dedup = Deduplicator::new(1000);
+    assert!(!dedup.is_duplicate(&trade(10, 100)));
+    assert!(!dedup.is_duplicate(&trade(10, 101)));
+    assert!(dedup.is_duplicate(&trade(10, 100)));
+
+    let mut dedup = Deduplicator::new(1000).with_trades(false);
+    assert!(!dedup.is_duplicate(&trade(10, 100)));
+    assert!(!dedup.is_duplicate(&trade(10, 100)));
+
+    // Limit updates are always forwarded.
+    let updates = Notification::LimitUpdates(vec![
+        LimitUpdate::new(100, 5, Side::Bid).with_timestamp(10)
+    ]);
+    assert!(!dedup.is_duplicate(&updates));
+    assert!(!dedup.is_duplicate(&updates));
+    assert_eq!(dedup.duplicates(), 0);
+}
+
+#[test]
+fn window() {
+    let mut dedup = Deduplicator::new(100);
+    assert!(!dedup.is_duplicate(&trade(1000, 1)));
+    assert!(!dedup.is_duplicate(&trade(1100, 2)));
+    assert!(dedup.is_duplicate(&trade(1000, 1)));
+
+    // Evicts the first trade.
+    assert!(!dedup.is_duplicate(&trade(1101, 3)));
+    assert!(!dedup.is_duplicate(&trade(1000, 1)));
+    assert!(dedup.is_duplicate(&trade(1100, 2)));
+}
+
+#[test]
+fn stream() {
+    let notifications = vec![trade(10, 1), trade(20, 2), trade(10, 1), trade(30, 3)];
+    let stream = Deduplicator::new(1000).stream(futures::stream::iter_ok(notifications));
+    let forwarded = stream.collect().wait().unwrap();
+    assert_eq!(forwarded, vec![trade(10, 1), trade(20, 2), trade(30, 3)]);
+}
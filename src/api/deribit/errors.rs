@@ -0,0 +1,186 @@
+//! A module defining error types specific to Deribit.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Deribit REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Deribit error code, e.g. `10009` for `not_enough_funds`: see API documentation.
+    pub code: Option<i64>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl RestError {
+    fn has_code(&self, codes: &[i64]) -> bool {
+        self.code.map(|code| codes.contains(&code)).unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        // `order_not_found`, `already_closed` and `not_open_order`.
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[10004, 10010, 11044]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        // `not_enough_funds`.
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[10009]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        // `post_only_reject`.
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[11054]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let Some(code) = self.code {
+            write!(f, " (code = {})", code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Deribit reports errors as JSON-RPC errors, with a `code` and a `message`.
+    pub(super) fn from_deribit_error(status: StatusCode, deribit_error: Option<(i64, String)>)
+        -> Self
+    {
+        let kind = match &deribit_error {
+            Some((code, _)) => RestErrorKind::from_code(*code)
+                .unwrap_or_else(|| RestErrorKind::from_status_code(status)),
+            None => RestErrorKind::from_status_code(status),
+        };
+
+        RestError {
+            kind,
+            code: deribit_error.as_ref().map(|error| error.0),
+            message: deribit_error.map(|error| error.1),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Deribit error code to a Deribit error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid client id or signature, or missing scope.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Deribit. See Deribit API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Deribit side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy, under maintenance or settling.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            // Rejected requests, along with an error `code`.
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_code(code: i64) -> Option<Self> {
+        use self::RestErrorKind::*;
+        let kind = match code {
+            // `authorization_required`, `invalid_credentials`, `unauthorized`,
+            // `forbidden` and `scope_exceeded`.
+            10000 | 13004 | 13009 | 13021 | 13403 => Unauthorized,
+
+            // `too_many_requests`.
+            10028 => TooManyRequests,
+
+            // `internal_server_error` and `matching_engine_queue_full`.
+            11094 | 10047 => InternalError,
+
+            // `settlement_in_progress`, `system_maintenance` and `temporarily_unavailable`.
+            10041 | 11051 | 13028 => ServiceUnavailable,
+
+            // `timed_out`.
+            13888 => Timeout,
+
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
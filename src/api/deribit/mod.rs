@@ -0,0 +1,256 @@
+//! Implementation of `ApiClient` for the Deribit API, trading futures and options.
+//!
+//! Deribit exposes a single JSON-RPC API, reachable both over HTTP and over WebSocket.
+//! Orders, cancels and account requests are sent as JSON-RPC calls over HTTP, while
+//! the notification stream subscribes to channels over WebSocket.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::{debug, warn};
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::options::{OptionsApi, OptionTicker, OptionPricing};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamp, Timestamped};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Deribit key pair: client id + client secret.
+pub struct KeyPair {
+    client_id: String,
+    client_secret: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        KeyPair {
+            client_id,
+            client_secret,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    client_id: String,
+    client_secret: Arc<PKey<Private>>,
+}
+
+impl Keys {
+    /// Signed HTTP requests and the authentication of the WebSocket connection both
+    /// carry the hex HMAC-SHA256 of a timestamp, a nonce and some request data,
+    /// separated by new lines.
+    fn signature(&self, timestamp: Timestamp, nonce: &str, data: &str) -> String {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.client_secret).unwrap();
+        let what = format!("{}\n{}\n{}", timestamp, nonce, data);
+        signer.update(what.as_bytes()).unwrap();
+        hex::encode(&signer.sign_to_vec().unwrap())
+    }
+}
+
+/// Return a new random nonce for signing a request.
+fn new_nonce() -> String {
+    use uuid::Uuid;
+    Uuid::new_v4().simple().to_string()
+}
+
+/// A Deribit API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+    symbols: HashMap<String, Symbol>,
+
+    /// Active options, sorted by underlying, expiration, strike and type.
+    options: Vec<Symbol>,
+
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Params {
+    /// Return the parameters of the Deribit testnet, where keys are generated at
+    /// `https://test.deribit.com`.
+    pub fn deribit_testnet() -> Self {
+        Params {
+            streaming_endpoint: "wss://test.deribit.com/ws/api/v2".to_owned(),
+            rest_endpoint: "https://test.deribit.com/api/v2".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new Deribit API client with given `params`, e.g. with the usual
+    /// `https://www.deribit.com/api/v2` and `wss://www.deribit.com/ws/api/v2`
+    /// endpoints. If `key_pair` is not `None`, this will enable performing requests to
+    /// the REST API and will forward the order updates.
+    ///
+    /// # Note
+    /// This method will block, fetching the active futures and options from Deribit.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let client_secret = PKey::hmac(pair.client_secret.as_bytes())?;
+
+                Some(Keys {
+                    client_id: pair.client_id,
+                    client_secret: Arc::new(client_secret),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            symbols: HashMap::new(),
+            options: Vec::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+        debug!("requesting instruments");
+        let (symbols, options) = runtime.block_on(client.get_instruments())?;
+        debug!("received instruments");
+        client.symbols = symbols;
+        client.options = options;
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Deribit
+    }
+
+    /// Futures and options are found by their instrument name, e.g. `BTC-PERPETUAL`,
+    /// `BTC-27DEC24` or `BTC-27DEC24-100000-C`, case insensitively.
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_uppercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order, OptionPricing::Premium)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl OptionsApi for Client {
+    fn option_chain(&self, underlying: &str) -> Vec<Symbol> {
+        self.options.iter().filter(|symbol| {
+            symbol.option_spec()
+                .map(|spec| spec.underlying().eq_ignore_ascii_case(underlying))
+                .unwrap_or(false)
+        }).cloned().collect()
+    }
+
+    fn option_ticker(&self, symbol: &Symbol)
+        -> Box<dyn Future<Item = Timestamped<OptionTicker>, Error = api::errors::Error> + Send + 'static>
+    {
+        if symbol.option_spec().is_none() {
+            warn!("called `option_ticker` on `{}`, which is not an option", symbol.name());
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        Box::new(self.option_ticker_impl(symbol))
+    }
+
+    fn option_order(&self, order: WithSymbol<&Order>, pricing: OptionPricing)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if order.symbol().option_spec().is_none() {
+            warn!("called `option_order` on `{}`, which is not an option", order.symbol().name());
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order, pricing)
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Orders are tracked by their label, which Deribit limits to 64 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 64 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
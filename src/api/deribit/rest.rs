@@ -0,0 +1,536 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use std::collections::HashMap;
+use log::{warn, error};
+use serde_derive::Deserialize;
+use crate::Side;
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::api::query_string::QueryString;
+use crate::api::options::{OptionSpec, OptionType, OptionTicker, OptionPricing, Greeks};
+use crate::api::deribit::{Client, new_nonce};
+use crate::api::deribit::errors::RestError;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitErrorData {
+    code: i64,
+    message: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitError {
+    error: Option<DeribitErrorData>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitResponse<T> {
+    result: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitOrder<'a> {
+    label: &'a str,
+    creation_timestamp: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitOrderResult<'a> {
+    #[serde(borrow)]
+    order: DeribitOrder<'a>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitAccountSummary<'a> {
+    currency: &'a str,
+    available_funds: f64,
+    initial_margin: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitAccountSummaries<'a> {
+    #[serde(borrow)]
+    summaries: Vec<DeribitAccountSummary<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitStatus<'a> {
+    locked: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct DeribitGreeks {
+    delta: f64,
+    gamma: f64,
+    vega: f64,
+    theta: f64,
+    rho: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitTicker<'a> {
+    instrument_name: &'a str,
+    timestamp: Timestamp,
+    underlying_price: f64,
+    mark_price: f64,
+    mark_iv: f64,
+    best_bid_price: f64,
+    best_bid_amount: f64,
+    bid_iv: f64,
+    best_ask_price: f64,
+    best_ask_amount: f64,
+    ask_iv: f64,
+    open_interest: f64,
+    greeks: DeribitGreeks,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitInstrument<'a> {
+    instrument_name: &'a str,
+    kind: &'a str,
+    is_active: bool,
+    tick_size: f64,
+    min_trade_amount: f64,
+    base_currency: &'a str,
+    expiration_timestamp: Timestamp,
+    strike: Option<f64>,
+    option_type: Option<&'a str>,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for TimeInForce {
+    fn as_str(self) -> &'static str {
+        match self {
+            TimeInForce::GoodTilCanceled => "good_til_cancelled",
+            TimeInForce::ImmediateOrCancel => "immediate_or_cancel",
+            TimeInForce::FillOrKilll => "fill_or_kill",
+            TimeInForce::GoodTilDate(_) => unreachable!("rejected by `order_impl`"),
+        }
+    }
+}
+
+/// Percent-encode `value` for use in a query string.
+fn url_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Increments are sent as JSON numbers, e.g. `0.5` or `10`. Increments of one or
+/// more are represented by a tick of one.
+fn convert_deribit_increment(increment: f64) -> Option<Tick> {
+    if increment >= 1. {
+        return Some(Tick::new(1));
+    }
+    Tick::tick_size(&format!("{:.12}", increment))
+}
+
+/// Strikes are sent as JSON numbers, e.g. `100000` or `0.625`: the tick of the strike
+/// is derived from its number of decimals.
+fn convert_deribit_strike(strike: f64) -> Option<(TickUnit, Tick)> {
+    let strike = format!("{}", strike);
+    let decimals = strike.find('.').map(|index| strike.len() - index - 1).unwrap_or(0);
+    let tick = Tick::new(10u64.checked_pow(decimals as u32)?);
+    Some((tick.ticked(&strike).ok()?, tick))
+}
+
+impl Client {
+    /// Each JSON-RPC method is reached with a GET request at the path of the same name,
+    /// the parameters being carried by the query string. If a key pair was provided,
+    /// requests are signed: the signature covers the method, the path along with the
+    /// query string and the (empty) body.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        method: &str,
+        query: QueryString,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let address = if query.is_empty() {
+            format!("{}/{}", self.params.rest_endpoint, method)
+        } else {
+            format!("{}/{}?{}", self.params.rest_endpoint, method, query)
+        };
+
+        let mut request = Request::builder();
+        request.method(Method::GET)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        if let Some(keys) = self.keys.as_ref() {
+            let uri = address.parse::<hyper::Uri>()
+                .ok()
+                .and_then(|uri| uri.path_and_query().map(|path| path.as_str().to_owned()))
+                .unwrap_or_default();
+            let timestamp = timestamp_ms();
+            let nonce = new_nonce();
+            let signature = keys.signature(timestamp, &nonce, &format!("GET\n{}\n\n", uri));
+            let authorization = format!(
+                "deri-hmac-sha256 id={},ts={},sig={},nonce={}",
+                keys.client_id,
+                timestamp,
+                signature,
+                nonce
+            );
+            request.header("Authorization", authorization.as_bytes());
+        }
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(hyper::Body::empty()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let deribit_error = serde_json::from_slice::<DeribitError>(&body)
+                .ok()
+                .and_then(|error| error.error);
+
+            if status != hyper::StatusCode::OK || deribit_error.is_some() {
+                let deribit_error = deribit_error.map(|error| (error.code, error.message));
+                let error = RestError::from_deribit_error(status, deribit_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>, pricing: OptionPricing)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.time_in_force.expiration().is_some() {
+            warn!("called `order` with `GoodTilDate`, which is not supported by Deribit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Deribit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Deribit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let mut query = QueryString::new();
+        let symbol = order.symbol();
+        query.push_str("instrument_name", symbol.name());
+        query.push_str("type", "limit");
+        query.push_str("amount", order.size.unticked(symbol.size_tick()).borrow());
+        query.push_str("price", order.price.unticked(symbol.price_tick()).borrow());
+        query.push_str("time_in_force", order.time_in_force.as_str());
+
+        match (order.type_, order.time_in_force) {
+            (OrderType::Limit, _) => (),
+
+            // Reject rather than reprice post only orders which would take liquidity.
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => {
+                query.push_str("post_only", "true");
+                query.push_str("reject_post_only", "true");
+            }
+
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by Deribit",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        }
+
+        if order.reduce_only {
+            query.push_str("reduce_only", "true");
+        }
+
+        // Options may be priced in USD or in implied volatility.
+        match pricing {
+            OptionPricing::Premium => (),
+            OptionPricing::Usd => query.push_str("advanced", "usd"),
+            OptionPricing::ImpliedVolatility => query.push_str("advanced", "implv"),
+        }
+
+        // Orders are tracked by their label, which is always sent.
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+        query.push_str("label", &url_encode(&order_id));
+
+        let method = match order.side {
+            Side::Bid => "private/buy",
+            Side::Ask => "private/sell",
+        };
+
+        let fut = self.request(method, query).and_then(|body| {
+            let result: DeribitResponse<DeribitOrderResult<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(OrderAck {
+                order_id: result.result.order.label.to_owned(),
+            }.with_timestamp(result.result.order.creation_timestamp))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("label", &url_encode(&cancel.order_id));
+
+        self.request("private/cancel_by_label", query).and_then(|body| {
+            let canceled: DeribitResponse<u64> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // The number of canceled orders is returned, rather than an error.
+            if canceled.result == 0 {
+                return Err(api::errors::ApiError::RestError(
+                    api::errors::RestErrorKind::Specific(
+                        api::errors::CancelErrorKind::UnknownOrder
+                    ).into()
+                ));
+            }
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn option_ticker_impl(&self, symbol: &Symbol)
+        -> impl Future<Item = Timestamped<OptionTicker>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("instrument_name", symbol.name());
+
+        self.request("public/ticker", query).and_then(|body| {
+            let ticker: DeribitResponse<DeribitTicker<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            let ticker = ticker.result;
+
+            // An empty side of the book is reported with a zero amount.
+            let best_bid = if ticker.best_bid_amount > 0. {
+                Some((ticker.best_bid_price, ticker.bid_iv))
+            } else {
+                None
+            };
+            let best_ask = if ticker.best_ask_amount > 0. {
+                Some((ticker.best_ask_price, ticker.ask_iv))
+            } else {
+                None
+            };
+
+            Ok(OptionTicker {
+                symbol: ticker.instrument_name.to_owned(),
+                underlying_price: ticker.underlying_price,
+                mark_price: ticker.mark_price,
+                mark_iv: ticker.mark_iv,
+                best_bid,
+                best_ask,
+                open_interest: ticker.open_interest,
+                greeks: Greeks {
+                    delta: ticker.greeks.delta,
+                    gamma: ticker.greeks.gamma,
+                    vega: ticker.greeks.vega,
+                    theta: ticker.greeks.theta,
+                    rho: ticker.greeks.rho,
+                },
+            }.with_timestamp(ticker.timestamp))
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("public/get_time", QueryString::new()).and_then(|body| {
+            let time: DeribitResponse<Timestamp> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(time.result))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("private/get_account_summaries", QueryString::new()).and_then(|body| {
+            let summaries: DeribitResponse<DeribitAccountSummaries<'_>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            // The initial margin is the part of the equity reserved by the open orders
+            // and positions.
+            let balances = summaries.result.summaries.into_iter().map(|summary| {
+                (summary.currency.to_owned(), Balance {
+                    free: summary.available_funds.to_string(),
+                    locked: summary.initial_margin.to_string(),
+                })
+            }).collect();
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("public/status", QueryString::new()).and_then(|body| {
+            let status: DeribitResponse<DeribitStatus<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // The platform may be locked for some currencies only.
+            let status = match status.result.locked {
+                "false" => ExchangeStatus::Normal,
+                "partial" => ExchangeStatus::Degraded,
+                _ => ExchangeStatus::Maintenance,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    /// Return the active futures and options, along with the options sorted by
+    /// underlying, expiration, strike and type.
+    crate fn get_instruments(&self)
+        -> impl Future<Item = (HashMap<String, Symbol>, Vec<Symbol>), Error = api::errors::Error>
+            + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("currency", "any");
+        query.push_str("expired", "false");
+
+        self.request("public/get_instruments", query).and_then(|body| {
+            let instruments: DeribitResponse<Vec<DeribitInstrument<'_>>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            let mut options = Vec::new();
+            for instrument in instruments.result {
+                // Spot pairs and combos are not supported.
+                if !instrument.is_active ||
+                    (instrument.kind != "future" && instrument.kind != "option")
+                {
+                    continue;
+                }
+
+                let (price_tick, size_tick) = match (
+                    convert_deribit_increment(instrument.tick_size),
+                    convert_deribit_increment(instrument.min_trade_amount)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", instrument.instrument_name);
+                        continue;
+                    }
+                };
+
+                let mut symbol = match Symbol::new(instrument.instrument_name, price_tick, size_tick) {
+                    Some(symbol) => symbol,
+                    None => {
+                        error!("symbol name too long: `{}`", instrument.instrument_name);
+                        continue;
+                    }
+                };
+
+                if instrument.kind == "option" {
+                    let option_type = match instrument.option_type {
+                        Some("call") => OptionType::Call,
+                        Some("put") => OptionType::Put,
+                        other => {
+                            error!(
+                                "wrong option type `{:?}` for symbol `{}`",
+                                other,
+                                instrument.instrument_name
+                            );
+                            continue;
+                        }
+                    };
+
+                    let spec = instrument.strike
+                        .and_then(convert_deribit_strike)
+                        .and_then(|(strike, strike_tick)| OptionSpec::new(
+                            instrument.base_currency,
+                            instrument.expiration_timestamp,
+                            strike,
+                            strike_tick,
+                            option_type
+                        ));
+                    symbol = match spec {
+                        Some(spec) => symbol.with_option_spec(spec),
+                        None => {
+                            error!("cannot read strike for symbol `{}`", instrument.instrument_name);
+                            continue;
+                        }
+                    };
+                    options.push((instrument.strike.unwrap_or_default(), symbol));
+                }
+                symbols.insert(symbol.name().to_uppercase(), symbol);
+            }
+
+            options.sort_by(|(a_strike, a), (b_strike, b)| {
+                let (a, b) = (a.option_spec().unwrap(), b.option_spec().unwrap());
+                a.underlying().cmp(b.underlying())
+                    .then(a.expiration().cmp(&b.expiration()))
+                    .then(a_strike.partial_cmp(b_strike).unwrap_or(std::cmp::Ordering::Equal))
+                    .then((a.option_type() as u8).cmp(&(b.option_type() as u8)))
+            });
+            let options = options.into_iter().map(|(_, option)| option).collect();
+
+            Ok((symbols, options))
+        })
+    }
+}
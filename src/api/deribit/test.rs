@@ -0,0 +1,86 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::deribit::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC-PERPETUAL", Tick::new(100), Tick::new(1)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the results of the
+/// parsing along with the notifications.
+fn parse(frames: &[String]) -> (Vec<bool>, Vec<Notification>) {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    let parsed = frames.iter().map(|frame| handler.parse_message(frame, &out).is_ok()).collect();
+    drop(out);
+    (parsed, receiver.wait().map(|notif| notif.unwrap()).collect())
+}
+
+fn book(data: &str) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","method":"subscription","params":{{
+            "channel":"book.BTC-PERPETUAL.100ms","data":{}}}}}"#,
+        data
+    )
+}
+
+fn snapshot() -> String {
+    book(r#"{"type":"snapshot","timestamp":1554373962454,"instrument_name":"BTC-PERPETUAL",
+        "change_id":297217,"bids":[["new",5042.34,30],["new",5041.94,20]],
+        "asks":[["new",5042.64,40],["new",5043.3,40]]}"#)
+}
+
+fn change(prev_change_id: u64, change_id: u64) -> String {
+    book(&format!(
+        r#"{{"type":"change","timestamp":1554373962854,"instrument_name":"BTC-PERPETUAL",
+            "prev_change_id":{},"change_id":{},"bids":[["delete",5041.94,0]],
+            "asks":[["change",5042.64,39]]}}"#,
+        prev_change_id,
+        change_id
+    ))
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let (parsed, notifs) = parse(&[snapshot(), change(297217, 297218)]);
+    assert_eq!(parsed, vec![true, true]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(504_234, 30, Side::Bid).with_timestamp(1_554_373_962_454),
+            LimitUpdate::new(504_194, 20, Side::Bid).with_timestamp(1_554_373_962_454),
+            LimitUpdate::new(504_264, 40, Side::Ask).with_timestamp(1_554_373_962_454),
+            LimitUpdate::new(504_330, 40, Side::Ask).with_timestamp(1_554_373_962_454),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(504_194, 0, Side::Bid).with_timestamp(1_554_373_962_854),
+            LimitUpdate::new(504_264, 39, Side::Ask).with_timestamp(1_554_373_962_854),
+        ]),
+    ]);
+}
+
+#[test]
+fn book_change_id_gap() {
+    // Changes are dropped until the next snapshot, both before the first one and after
+    // a gap.
+    let frames = [
+        change(297216, 297217),
+        snapshot(),
+        change(297218, 297219),
+        change(297219, 297220),
+        snapshot(),
+    ];
+    let (parsed, notifs) = parse(&frames);
+    assert_eq!(parsed, vec![true, true, false, true, true]);
+    assert_eq!(notifs.len(), 2);
+    assert_eq!(notifs[0], notifs[1]);
+}
@@ -0,0 +1,569 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::collections::HashMap;
+use std::{mem, thread};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::index::IndexPrice;
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::deribit::{Client, Keys, new_nonce};
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let endpoint = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", endpoint);
+
+            if let Err(err) = ws::connect(endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        out: None,
+                        last_id: 0,
+                        auth_id: None,
+                        previous_change_id: None,
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Public and private channels are subscribed to over the same connection, the private
+/// ones once the connection is authenticated.
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Set once the connection is open, needed for resubscribing to the book from
+    /// within `on_message`.
+    out: Option<ws::Sender>,
+
+    /// Id of the last JSON-RPC request sent over the connection.
+    last_id: u64,
+
+    /// Id of the authentication request, if any.
+    auth_id: Option<u64>,
+
+    /// Keep track of the `change_id` sent by Deribit, which is checked against the
+    /// `prev_change_id` of the next book change.
+    previous_change_id: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct DeribitRequest<'a, T> {
+    jsonrpc: &'a str,
+    id: u64,
+    method: &'a str,
+    params: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct DeribitChannels<'a> {
+    channels: &'a [String],
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct DeribitAuth<'a> {
+    grant_type: &'a str,
+    client_id: &'a str,
+    timestamp: Timestamp,
+    signature: String,
+    nonce: String,
+    data: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitErrorData {
+    code: i64,
+    message: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitEvent<'a> {
+    id: Option<u64>,
+    error: Option<DeribitErrorData>,
+    method: Option<&'a str>,
+    #[serde(borrow)]
+    params: Option<DeribitChannel<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DeribitChannel<'a> {
+    channel: &'a str,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitMessage<T> {
+    params: DeribitData<T>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitData<T> {
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitBook<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    timestamp: Timestamp,
+    change_id: u64,
+    prev_change_id: Option<u64>,
+    #[serde(borrow)]
+    bids: Vec<(&'a str, f64, f64)>,
+    #[serde(borrow)]
+    asks: Vec<(&'a str, f64, f64)>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct DeribitTrade<'a> {
+    timestamp: Timestamp,
+    price: f64,
+    amount: f64,
+    direction: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct DeribitIndexPrice<'a> {
+    index_name: &'a str,
+    price: f64,
+    timestamp: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitChanges<'a> {
+    #[serde(borrow)]
+    trades: Vec<DeribitUserTrade<'a>>,
+    #[serde(borrow)]
+    orders: Vec<DeribitOrder<'a>>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitUserTrade<'a> {
+    order_id: &'a str,
+    label: Option<&'a str>,
+    amount: f64,
+    price: f64,
+    fee: f64,
+    timestamp: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct DeribitOrder<'a> {
+    order_id: &'a str,
+    label: Option<&'a str>,
+    order_state: &'a str,
+    direction: &'a str,
+
+    /// Either a number or `"market_price"`.
+    price: Value,
+
+    amount: f64,
+    filled_amount: f64,
+    creation_timestamp: Timestamp,
+    last_update_timestamp: Timestamp,
+}
+
+fn book_channel(symbol: &Symbol) -> String {
+    format!("book.{}.100ms", symbol.name())
+}
+
+fn trades_channel(symbol: &Symbol) -> String {
+    format!("trades.{}.100ms", symbol.name())
+}
+
+/// Index names are derived from the instrument names, e.g. `btc_usd` for `BTC-PERPETUAL`
+/// and `sol_usdc` for `SOL_USDC-PERPETUAL`.
+fn index_channel(symbol: &Symbol) -> String {
+    let currency = symbol.name().split('-').next().unwrap_or_default().to_lowercase();
+    if currency.contains('_') {
+        format!("deribit_price_index.{}", currency)
+    } else {
+        format!("deribit_price_index.{}_usd", currency)
+    }
+}
+
+fn changes_channel(symbol: &Symbol) -> String {
+    format!("user.changes.{}.raw", symbol.name())
+}
+
+/// Prices and sizes are sent as JSON numbers.
+fn convert_deribit_number(tick: Tick, value: f64) -> Result<TickUnit, failure::Error> {
+    Ok(tick.ticked(&format!("{}", value))?)
+}
+
+/// Return the order id specified by the user, i.e. the label, which defaults to the
+/// server order id in case it was left empty.
+fn order_id(server_order_id: &str, label: Option<&str>) -> String {
+    match label {
+        Some(label) if !label.is_empty() => label.to_owned(),
+        _ => server_order_id.to_owned(),
+    }
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys and which is not connected,
+    /// for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: None,
+            out: None,
+            last_id: 0,
+            auth_id: None,
+            previous_change_id: None,
+        }
+    }
+
+    fn send_request<T: serde::Serialize>(&mut self, method: &str, params: T)
+        -> ws::Result<u64>
+    {
+        self.last_id += 1;
+        let request = DeribitRequest {
+            jsonrpc: "2.0",
+            id: self.last_id,
+            method,
+            params,
+        };
+
+        let value = match serde_json::to_string(&request) {
+            Ok(value) => value,
+            Err(err) => {
+                panic!("failed to serialize `DeribitRequest`: `{}`", err);
+            }
+        };
+        if let Some(out) = &self.out {
+            out.send(value)?;
+        }
+        Ok(self.last_id)
+    }
+
+    fn subscribe(&mut self, method: &str, channels: &[String]) -> ws::Result<()> {
+        if channels.is_empty() {
+            return Ok(());
+        }
+        self.send_request(method, DeribitChannels { channels })?;
+        Ok(())
+    }
+
+    /// The signature covers a timestamp, a nonce and some (empty) data, so that the
+    /// client secret is never sent.
+    fn authenticate(&mut self) -> ws::Result<()> {
+        let keys = match self.keys.clone() {
+            Some(keys) => keys,
+            None => return Ok(()),
+        };
+
+        let timestamp = timestamp_ms();
+        let nonce = new_nonce();
+        let auth = DeribitAuth {
+            grant_type: "client_signature",
+            client_id: &keys.client_id,
+            timestamp,
+            signature: keys.signature(timestamp, &nonce, ""),
+            nonce,
+            data: "",
+        };
+        self.auth_id = Some(self.send_request("public/auth", auth)?);
+        Ok(())
+    }
+
+    fn convert_deribit_level(&self, level: &(&str, f64, f64), side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        let size = match level.0 {
+            "delete" => 0,
+            _ => convert_deribit_number(self.symbol.size_tick(), level.2)?,
+        };
+
+        Ok(LimitUpdate {
+            side,
+            price: convert_deribit_number(self.symbol.price_tick(), level.1)?,
+            size,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let book: DeribitMessage<DeribitBook<'_>> = serde_json::from_str(json)?;
+        let book = book.params.data;
+
+        // The book is consistent if the previous `change_id` is equal to the current
+        // `prev_change_id`. Otherwise we start again from a new snapshot.
+        if book.type_ == "change" {
+            match self.previous_change_id {
+                // Still waiting for the snapshot.
+                None => return Ok(()),
+
+                Some(change_id) if Some(change_id) != book.prev_change_id => {
+                    self.previous_change_id = None;
+                    let channels = [book_channel(&self.symbol)];
+                    self.subscribe("public/unsubscribe", &channels)?;
+                    self.subscribe("public/subscribe", &channels)?;
+                    bail!("previous `change_id` and current `prev_change_id` do not match");
+                }
+
+                Some(_) => (),
+            }
+        }
+        self.previous_change_id = Some(book.change_id);
+
+        let bids = book.bids.iter().map(|l| self.convert_deribit_level(l, Side::Bid));
+        let asks = book.asks.iter().map(|l| self.convert_deribit_level(l, Side::Ask));
+        let updates = bids.chain(asks)
+            .map(|l| Ok(l?.with_timestamp(book.timestamp)))
+            .collect::<Result<Vec<_>, failure::Error>>()?;
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trades: DeribitMessage<Vec<DeribitTrade<'_>>> = serde_json::from_str(json)?;
+
+        for trade in trades.params.data {
+            // The direction is the one of the taker.
+            let maker_side = match trade.direction {
+                "buy" => Side::Ask,
+                "sell" => Side::Bid,
+                other => bail!("wrong side: `{}`", other),
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: convert_deribit_number(self.symbol.price_tick(), trade.price)?,
+                size: convert_deribit_number(self.symbol.size_tick(), trade.amount)?,
+                maker_side,
+            }.with_timestamp(trade.timestamp));
+
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_index_price(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let index: DeribitMessage<DeribitIndexPrice<'_>> = serde_json::from_str(json)?;
+        let index = index.params.data;
+
+        let index = Notification::IndexPrice(IndexPrice {
+            index: index.index_name.to_owned(),
+            price: format!("{}", index.price),
+        }.with_timestamp(index.timestamp));
+        out.unbounded_send(index).unwrap();
+        Ok(())
+    }
+
+    /// Each change carries the trades along with the resulting state of the orders,
+    /// so the remaining size after each trade is recovered from the filled size of
+    /// its order.
+    fn parse_changes(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let changes: DeribitMessage<DeribitChanges<'_>> = serde_json::from_str(json)?;
+        let changes = changes.params.data;
+        let size_tick = self.symbol.size_tick();
+
+        // server order id => (size, size filled before the trades of this change)
+        let mut sizes = HashMap::new();
+        for order in &changes.orders {
+            let size = convert_deribit_number(size_tick, order.amount)?;
+            let filled = convert_deribit_number(size_tick, order.filled_amount)?;
+            sizes.insert(order.order_id, (size, filled));
+        }
+        for trade in &changes.trades {
+            if let Some((_, filled)) = sizes.get_mut(trade.order_id) {
+                *filled = filled.saturating_sub(convert_deribit_number(size_tick, trade.amount)?);
+            }
+        }
+
+        // New orders are the ones which were not updated since their creation.
+        for order in &changes.orders {
+            if order.creation_timestamp != order.last_update_timestamp ||
+                order.order_state == "rejected"
+            {
+                continue;
+            }
+
+            let side = match order.direction {
+                "buy" => Side::Bid,
+                "sell" => Side::Ask,
+                other => bail!("wrong side: `{}`", other),
+            };
+
+            let confirmation = OrderConfirmation {
+                order_id: order_id(order.order_id, order.label),
+                size: convert_deribit_number(size_tick, order.amount)?,
+                price: match order.price.as_f64() {
+                    Some(price) => convert_deribit_number(self.symbol.price_tick(), price)?,
+                    None => 0,
+                },
+                side,
+            }.with_timestamp(order.creation_timestamp);
+            out.unbounded_send(Notification::OrderConfirmation(confirmation)).unwrap();
+        }
+
+        for trade in &changes.trades {
+            let consumed_size = convert_deribit_number(size_tick, trade.amount)?;
+            let remaining_size = match sizes.get_mut(trade.order_id) {
+                Some((size, filled)) => {
+                    *filled += consumed_size;
+                    size.saturating_sub(*filled)
+                }
+                None => 0,
+            };
+
+            let update = OrderUpdate {
+                order_id: order_id(trade.order_id, trade.label),
+                consumed_size,
+                remaining_size,
+                consumed_price: convert_deribit_number(self.symbol.price_tick(), trade.price)?,
+                commission: convert_deribit_number(
+                    self.symbol.commission_tick(),
+                    trade.fee.abs()
+                )?,
+            }.with_timestamp(trade.timestamp);
+            out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+        }
+
+        for order in &changes.orders {
+            if order.order_state != "cancelled" {
+                continue;
+            }
+
+            let size = convert_deribit_number(size_tick, order.amount)?;
+            let filled_size = convert_deribit_number(size_tick, order.filled_amount)?;
+            let expiration = OrderExpiration {
+                order_id: order_id(order.order_id, order.label),
+                filled_size: Some(filled_size),
+                remaining_size: Some(size.saturating_sub(filled_size)),
+            }.with_timestamp(order.last_update_timestamp);
+            out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: DeribitEvent<'_> = serde_json::from_str(json)?;
+
+        if let Some(error) = event.error {
+            bail!("{} (code = {})", error.message, error.code);
+        }
+
+        // Responses to our requests: private channels are only accepted once
+        // authenticated.
+        if let Some(id) = event.id {
+            if Some(id) == self.auth_id {
+                self.subscribe("private/subscribe", &[changes_channel(&self.symbol)])?;
+            }
+            return Ok(());
+        }
+
+        let channel = match (event.method, event.params) {
+            (Some("subscription"), Some(params)) => params.channel,
+            _ => return Ok(()),
+        };
+
+        if channel.starts_with("book.") && self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.parse_book(json, out)?;
+        } else if channel.starts_with("trades.") && self.flags.contains(NotificationFlags::TRADES) {
+            self.parse_trades(json, out)?;
+        } else if channel.starts_with("deribit_price_index.") &&
+            self.flags.contains(NotificationFlags::INDEX_PRICES)
+        {
+            self.parse_index_price(json, out)?;
+        } else if channel.starts_with("user.changes.") &&
+            self.flags.contains(NotificationFlags::ORDERS)
+        {
+            self.parse_changes(json, out)?;
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        let mut channels = Vec::new();
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            channels.push(book_channel(&self.symbol));
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            channels.push(trades_channel(&self.symbol));
+        }
+        if self.flags.contains(NotificationFlags::INDEX_PRICES) {
+            channels.push(index_channel(&self.symbol));
+        }
+        self.subscribe("public/subscribe", &channels)?;
+        self.authenticate()
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The private channel is kept, `ORDERS` only acts as a filter.
+        let mut subscribe = Vec::new();
+        let mut unsubscribe = Vec::new();
+        let channels = [
+            (NotificationFlags::TRADES, trades_channel(&self.symbol)),
+            (NotificationFlags::ORDER_BOOK, book_channel(&self.symbol)),
+            (NotificationFlags::INDEX_PRICES, index_channel(&self.symbol)),
+        ];
+        for (flag, channel) in channels.iter() {
+            match (previous.contains(*flag), flags.contains(*flag)) {
+                (false, true) => subscribe.push(channel.clone()),
+                (true, false) => unsubscribe.push(channel.clone()),
+                _ => (),
+            }
+        }
+
+        // A new subscription starts with a new snapshot.
+        if !previous.contains(NotificationFlags::ORDER_BOOK)
+            && flags.contains(NotificationFlags::ORDER_BOOK)
+        {
+            self.previous_change_id = None;
+        }
+
+        self.subscribe("public/unsubscribe", &unsubscribe)?;
+        self.subscribe("public/subscribe", &subscribe)
+    }
+}
@@ -0,0 +1,89 @@
+//! A module defining operations on derivatives, which are only available on some
+//! exchanges.
+
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::errors;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{Timestamp, Timestamped};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An open position on a contract.
+pub struct Position {
+    /// Name of the contract.
+    pub symbol: String,
+
+    /// `Side::Bid` for a long position, `Side::Ask` for a short position.
+    pub side: Side,
+
+    /// Size of the position, unticked and always positive.
+    pub size: String,
+
+    /// Average entry price, unticked.
+    pub entry_price: String,
+
+    /// Unrealized profit (or loss if negative), unticked, in the margin asset.
+    pub unrealized_pnl: String,
+
+    /// Leverage currently applied to the contract.
+    pub leverage: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A funding rate applied on a perpetual contract, timestamped with the funding time.
+pub struct FundingRate {
+    /// Name of the contract.
+    pub symbol: String,
+
+    /// Rate paid by long positions to short positions (or the converse if negative),
+    /// unticked, e.g. `0.0001` for 0.01%.
+    pub rate: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Open interest of a contract at the end of a period.
+pub struct OpenInterest {
+    /// Name of the contract.
+    pub symbol: String,
+
+    /// Total size of the open positions, unticked, in contracts.
+    pub open_interest: String,
+
+    /// Value of the open positions, unticked, in the margin asset, if provided by
+    /// the exchange.
+    pub value: Option<String>,
+}
+
+/// A trait implemented by clients of exchanges which provide trading on derivatives,
+/// on top of `ApiClient`.
+pub trait DerivativesApi {
+    /// Set the leverage used for the new orders on `symbol`.
+    fn set_leverage(&self, symbol: &Symbol, leverage: u32)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = errors::Error> + Send + 'static>;
+
+    /// Retrieve the open positions of the account, positions of size zero are omitted.
+    fn positions(&self)
+        -> Box<dyn Future<Item = Vec<Position>, Error = errors::Error> + Send + 'static>;
+
+    /// Retrieve the funding rates applied on `symbol` between `start` and `end`,
+    /// both included, in chronological order. The request does not require a key pair.
+    ///
+    /// # Note
+    /// Exchanges limit the number of entries returned at once: a longer history is
+    /// downloaded by calling this method again with `start` set right after the last
+    /// timestamp received, until an empty vector is returned.
+    fn funding_rates(&self, symbol: &Symbol, start: Timestamp, end: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<FundingRate>>, Error = errors::Error> + Send + 'static>;
+
+    /// Retrieve the open interest of `symbol` sampled every `period` milliseconds,
+    /// between `start` and `end`, both included, in chronological order. Periods not
+    /// supported by the exchange are rejected with `RestErrorKind::InvalidRequest`.
+    /// The request does not require a key pair.
+    ///
+    /// # Note
+    /// The number of entries returned at once is limited in the same way as
+    /// `DerivativesApi::funding_rates`.
+    fn open_interest(&self, symbol: &Symbol, period: Timestamp, start: Timestamp, end: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<OpenInterest>>, Error = errors::Error> + Send + 'static>;
+}
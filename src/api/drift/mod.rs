@@ -0,0 +1,121 @@
+//! A module defining an estimator of the clock drift of an exchange, used for
+//! correcting the timestamps of its notifications.
+//!
+//! Timestamps set by different exchanges are not directly comparable: each exchange
+//! has its own clock, and notifications take some time to reach us. The offset between
+//! the exchange timestamps and the local receive times is estimated with an
+//! exponentially weighted moving average, and used for translating exchange timestamps
+//! into local times. One estimator should be used per exchange.
+
+mod test;
+
+use crate::api::Notification;
+use crate::api::timestamp::{Timestamp, Timestamped};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// Estimate the offset between the timestamps of an exchange and the local clock.
+pub struct DriftEstimator {
+    alpha: f64,
+    offset: Option<f64>,
+}
+
+impl DriftEstimator {
+    /// Return a new estimator, `alpha` being the weight given to each new sample
+    /// in the moving average.
+    ///
+    /// # Panics
+    /// Panic if `alpha` is not in `(0, 1]`.
+    pub fn new(alpha: f64) -> Self {
+        if !(alpha > 0. && alpha <= 1.) {
+            panic!("`alpha` must be in (0, 1]");
+        }
+
+        DriftEstimator {
+            alpha,
+            offset: None,
+        }
+    }
+
+    /// Return the estimated offset in ms to add to an exchange timestamp in order to
+    /// get a local time, or `None` if no sample was observed yet. This includes the
+    /// average transmission delay.
+    pub fn offset(&self) -> Option<i64> {
+        self.offset.map(|offset| offset.round() as i64)
+    }
+
+    /// Observe an event timestamped `exchange_timestamp` by the exchange and received
+    /// at `local_timestamp`.
+    pub fn observe(&mut self, exchange_timestamp: Timestamp, local_timestamp: Timestamp) {
+        let sample = local_timestamp as f64 - exchange_timestamp as f64;
+        self.offset = Some(match self.offset {
+            Some(offset) => offset + self.alpha * (sample - offset),
+            None => sample,
+        });
+    }
+
+    /// Translate `exchange_timestamp` into a local time. Identity if no sample was
+    /// observed yet.
+    pub fn correct(&self, exchange_timestamp: Timestamp) -> Timestamp {
+        match self.offset() {
+            Some(offset) if offset >= 0 => exchange_timestamp.saturating_add(offset as u64),
+            Some(offset) => exchange_timestamp.saturating_sub(offset.wrapping_neg() as u64),
+            None => exchange_timestamp,
+        }
+    }
+
+    /// Observe `notif`, received at `local_timestamp`, and attach corrected timestamps
+    /// to it. `Notification::ExchangeStatus`, trading halts and auction updates are left
+    /// untouched, since they are timestamped locally.
+    pub fn on_notification(&mut self, notif: Notification, local_timestamp: Timestamp)
+        -> Notification
+    {
+        let latest = match &notif {
+            Notification::Trade(trade) => trade.timestamp(),
+            Notification::LimitUpdates(updates) => {
+                match updates.iter().map(|update| update.timestamp()).max() {
+                    Some(timestamp) => timestamp,
+                    None => return notif,
+                }
+            }
+            Notification::OrderConfirmation(confirmation) => confirmation.timestamp(),
+            Notification::OrderUpdate(update) => update.timestamp(),
+            Notification::OrderExpiration(expiration) => expiration.timestamp(),
+            Notification::PositionUpdate(position) => position.timestamp(),
+            Notification::IndexPrice(price) => price.timestamp(),
+            Notification::ExchangeStatus(..)
+                | Notification::TradingHalted(..)
+                | Notification::TradingResumed(..)
+                | Notification::AuctionUpdate(..) => return notif,
+        };
+        self.observe(latest, local_timestamp);
+
+        match notif {
+            Notification::Trade(trade) => Notification::Trade(self.annotate(trade)),
+            Notification::LimitUpdates(updates) => Notification::LimitUpdates(
+                updates.into_iter().map(|update| self.annotate(update)).collect()
+            ),
+            Notification::OrderConfirmation(confirmation) => {
+                Notification::OrderConfirmation(self.annotate(confirmation))
+            }
+            Notification::OrderUpdate(update) => {
+                Notification::OrderUpdate(self.annotate(update))
+            }
+            Notification::OrderExpiration(expiration) => {
+                Notification::OrderExpiration(self.annotate(expiration))
+            }
+            Notification::PositionUpdate(position) => {
+                Notification::PositionUpdate(self.annotate(position))
+            }
+            Notification::IndexPrice(price) => Notification::IndexPrice(self.annotate(price)),
+            Notification::ExchangeStatus(status) => Notification::ExchangeStatus(status),
+            Notification::TradingHalted(status) => Notification::TradingHalted(status),
+            Notification::TradingResumed(status) => Notification::TradingResumed(status),
+            Notification::AuctionUpdate(auction) => Notification::AuctionUpdate(auction),
+        }
+    }
+
+    fn annotate<T>(&self, value: Timestamped<T>) -> Timestamped<T> {
+        let corrected = self.correct(value.timestamp());
+        value.with_corrected_timestamp(corrected)
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade, ExchangeStatus};
+use crate::api::drift::DriftEstimator;
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+
+fn trade(timestamp: Timestamp) -> Notification {
+    Notification::Trade(Trade {
+        price: 100,
+        size: 1,
+        maker_side: Side::Ask,
+    }.with_timestamp(timestamp))
+}
+
+#[test]
+fn moving_average() {
+    let mut drift = DriftEstimator::new(0.5);
+    assert_eq!(drift.offset(), None);
+    assert_eq!(drift.correct(1000), 1000);
+
+    drift.observe(1000, 1100);
+    assert_eq!(drift.offset(), Some(100));
+
+    drift.observe(2000, 2060);
+    assert_eq!(drift.offset(), Some(80));
+    assert_eq!(drift.correct(3000), 3080);
+
+    // The exchange clock is ahead of ours.
+    let mut drift = DriftEstimator::new(1.);
+    drift.observe(1000, 900);
+    assert_eq!(drift.offset(), Some(-100));
+    assert_eq!(drift.correct(1000), 900);
+    assert_eq!(drift.correct(50), 0);
+}
+
+#[test]
+fn notifications() {
+    let mut drift = DriftEstimator::new(0.5);
+
+    match drift.on_notification(trade(1000), 1100) {
+        Notification::Trade(trade) => {
+            assert_eq!(trade.timestamp(), 1000);
+            assert_eq!(trade.corrected_timestamp(), 1100);
+        }
+        _ => panic!("expected a trade"),
+    }
+
+    let updates = Notification::LimitUpdates(vec![
+        LimitUpdate::new(100, 1, Side::Bid).with_timestamp(1990),
+        LimitUpdate::new(101, 1, Side::Ask).with_timestamp(2000),
+    ]);
+    match drift.on_notification(updates, 2060) {
+        Notification::LimitUpdates(updates) => {
+            let corrected: Vec<_> = updates.iter()
+                .map(|update| update.corrected_timestamp())
+                .collect();
+            assert_eq!(corrected, vec![2070, 2080]);
+        }
+        _ => panic!("expected limit updates"),
+    }
+
+    let status = Notification::ExchangeStatus(ExchangeStatus::Normal.with_timestamp(5000));
+    assert_eq!(drift.on_notification(status.clone(), 9000), status);
+    assert_eq!(drift.offset(), Some(80));
+}
+
+#[test]
+fn uncorrected_timestamp() {
+    let trade = 1.with_timestamp(1000);
+    assert_eq!(trade.corrected_timestamp(), 1000);
+    assert_eq!(trade.with_corrected_timestamp(1100).timestamp(), 1000);
+}
+
+#[test]
+#[should_panic]
+fn invalid_alpha() {
+    let _ = DriftEstimator::new(0.);
+}
@@ -0,0 +1,211 @@
+//! A module defining error types specific to dYdX.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+/// Codespace of the errors raised by the Cosmos SDK itself, e.g. for an invalid
+/// signature, as opposed to the errors raised by the dYdX modules.
+crate const SDK_CODESPACE: &str = "sdk";
+
+/// `sdk` error code of an invalid signature.
+const SDK_UNAUTHORIZED: u32 = 4;
+
+/// `sdk` error code of insufficient funds.
+const SDK_INSUFFICIENT_FUNDS: u32 = 5;
+
+/// `sdk` error code of a transaction signed with a stale account sequence.
+crate const SDK_WRONG_SEQUENCE: u32 = 32;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by the dYdX indexer or by the validator node.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Module which rejected the transaction, e.g. `clob` or `sdk`.
+    pub codespace: Option<String>,
+
+    /// Error code, only unique within its codespace: see the dYdX and Cosmos SDK
+    /// sources.
+    pub code: Option<u32>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl RestError {
+    fn has_code(&self, codespace: &str, code: u32) -> bool {
+        self.codespace.as_ref().map(|c| c == codespace).unwrap_or(false)
+            && self.code == Some(code)
+    }
+
+    fn msg_contains(&self, pattern: &str) -> bool {
+        self.message.as_ref()
+            .map(|msg| msg.to_lowercase().contains(pattern))
+            .unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        // The `clob` module reports unknown orders with messages such as `Order does not
+        // exist in memclob` or `Stateful order does not exist`.
+        if self.kind == RestErrorKind::Rejected && self.msg_contains("does not exist") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::Rejected
+            && (self.has_code(SDK_CODESPACE, SDK_INSUFFICIENT_FUNDS)
+                || self.msg_contains("undercollateralized"))
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.kind == RestErrorKind::Rejected && self.msg_contains("post-only") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+
+        if self.kind == RestErrorKind::Rejected && self.msg_contains("already exists") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let (Some(codespace), Some(code)) = (&self.codespace, self.code) {
+            write!(f, " (codespace = {}, code = {})", codespace, code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Error returned along with a non-success HTTP status code.
+    pub(super) fn from_dydx_error(status: StatusCode, message: Option<String>) -> Self {
+        RestError {
+            kind: RestErrorKind::from_status_code(status),
+            codespace: None,
+            code: None,
+            message,
+        }
+    }
+
+    /// Transaction rejected by the validator node, while the HTTP request itself
+    /// succeeded.
+    pub(super) fn rejected(codespace: String, code: u32, message: String) -> Self {
+        let kind = if codespace == SDK_CODESPACE && code == SDK_UNAUTHORIZED {
+            RestErrorKind::Unauthorized
+        } else {
+            RestErrorKind::Rejected
+        };
+
+        RestError {
+            kind,
+            codespace: Some(codespace),
+            code: Some(code),
+            message: Some(message),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a rejected transaction to a dYdX error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "rejected")]
+    /// The transaction carrying the order or the cancel was rejected, see the
+    /// `codespace` and `code` fields of `RestError`.
+    Rejected,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid signature, or unknown account.
+    Unauthorized,
+
+    #[fail(display = "not found")]
+    /// Not found, issue on the consumer side.
+    NotFound,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by the indexer or by the node.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on the indexer or node side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::NOT_FOUND => NotFound,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Implementation of `ApiClient` for dYdX v4, a decentralized exchange trading
+//! perpetual contracts. Market data and account updates come from the indexer, while
+//! orders and cancels are transactions signed locally and broadcast to a validator
+//! node.
+//!
+//! The chain never assigns ids to orders: an order is identified by its subaccount,
+//! its client id, which is a 32 bits integer, its flags and its market. Order ids
+//! used with this client are thus client ids written in decimal, see `new_order_id`.
+
+pub mod errors;
+mod rest;
+mod tx;
+mod wss;
+mod test;
+
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+use self::tx::Signer;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A dYdX account: address, e.g. `dydx1...`, + hex encoded secp256k1 private key,
+/// along with the number of the subaccount to trade with.
+pub struct KeyPair {
+    address: String,
+    private_key: String,
+    subaccount_number: u32,
+}
+
+impl KeyPair {
+    /// Return a new key pair trading with the given subaccount, usually `0`.
+    pub fn new(address: String, private_key: String, subaccount_number: u32) -> Self {
+        KeyPair {
+            address,
+            private_key,
+            subaccount_number,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Params of the validator node which transactions are broadcast to.
+pub struct NodeParams {
+    /// REST API endpoint of the node, e.g. `https://dydx-ops-rest.kingnodes.com`.
+    pub rest_endpoint: String,
+
+    /// Chain id, e.g. `dydx-mainnet-1` or `dydx-testnet-4`.
+    pub chain_id: String,
+}
+
+#[derive(Clone)]
+struct Keys {
+    address: String,
+    subaccount_number: u32,
+    signer: Arc<Signer>,
+    account_number: u64,
+
+    /// Sequence of the next transaction. Only long term orders and cancels consume a
+    /// sequence, short term ones skip the check.
+    sequence: Arc<Mutex<u64>>,
+}
+
+impl Keys {
+    /// Id of the subaccount, as used by the indexer.
+    fn subaccount_id(&self) -> String {
+        format!("{}/{}", self.address, self.subaccount_number)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// A perpetual market, along with the conversions from ticks to the integer units
+/// used on the chain.
+crate struct Market {
+    symbol: Symbol,
+    clob_pair_id: u32,
+
+    /// Number of base quantums in one size tick.
+    quantums_per_tick: u64,
+
+    /// Number of subticks in one price tick.
+    subticks_per_tick: u64,
+}
+
+/// A dYdX v4 API client.
+pub struct Client {
+    params: Params,
+    node: NodeParams,
+    keys: Option<Keys>,
+
+    /// client order id => order flags, needed for canceling
+    order_flags: Arc<CHashMap<String, u32>>,
+
+    markets: HashMap<String, Market>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new dYdX API client with given `params`, which are the indexer
+    /// endpoints, e.g. `https://indexer.dydx.trade/v4` and
+    /// `wss://indexer.dydx.trade/v4/ws`. Transactions are broadcast to the node
+    /// described by `node`. If `key_pair` is not `None`, this will enable sending
+    /// orders and will forward the updates of the subaccount.
+    ///
+    /// # Note
+    /// This method will block, fetching the available markets from the indexer, and
+    /// the account number from the node if `key_pair` is not `None`.
+    pub fn new(params: Params, node: NodeParams, key_pair: Option<KeyPair>)
+        -> Result<Self, failure::Error>
+    {
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            node,
+            keys: None,
+            order_flags: Arc::new(CHashMap::new()),
+            markets: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+
+        debug!("requesting markets");
+        client.markets = runtime.block_on(client.get_markets())?;
+        debug!("received markets");
+
+        if let Some(pair) = key_pair {
+            let signer = Signer::new(&pair.private_key)?;
+
+            debug!("requesting account");
+            let (account_number, sequence) = runtime.block_on(client.get_account(&pair.address))?;
+            debug!("received account");
+
+            client.keys = Some(Keys {
+                address: pair.address,
+                subaccount_number: pair.subaccount_number,
+                signer: Arc::new(signer),
+                account_number,
+                sequence: Arc::new(Mutex::new(sequence)),
+            });
+        }
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Dydx
+    }
+
+    /// Markets are found by their ticker, e.g. `BTC-USD`, case insensitively.
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.markets.get(&symbol.to_uppercase()).map(|market| market.symbol)
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.cancel_impl(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Client ids are 32 bits integers: `hint` is kept if it is such an integer written
+    /// in decimal, otherwise a random id is drawn.
+    fn new_order_id(hint: &str) -> String {
+        use uuid::Uuid;
+
+        if hint.parse::<u32>().is_ok() {
+            return hint.to_owned();
+        }
+
+        let bytes = Uuid::new_v4();
+        let bytes = bytes.as_bytes();
+        let client_id = bytes[..4].iter().fold(0u32, |id, byte| id << 8 | u32::from(*byte));
+        client_id.to_string()
+    }
+}
@@ -0,0 +1,645 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::{Serialize, Deserialize};
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+    GenerateOrderId,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{
+    convert_str_timestamp,
+    timestamp_ms,
+    Timestamp,
+    Timestamped,
+    IntoTimestamped,
+};
+use crate::api::dydx::{Client, Keys, Market};
+use crate::api::dydx::errors::{RestError, SDK_CODESPACE, SDK_WRONG_SEQUENCE};
+use crate::api::dydx::tx::{self, GoodTil, OrderId, OrderTimeInForce};
+
+type HttpClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+/// Validity of long term orders and cancels, in seconds. The chain accepts up to 95 days.
+const LONG_TERM_VALIDITY: u64 = 28 * 24 * 3600;
+
+/// Delay after which the chain is deemed halted if no block was produced, in ms.
+const HALTED_AFTER: u64 = 60_000;
+
+/// Quote amounts are expressed in quantums of USDC, which has 6 decimals.
+const QUOTE_ATOMIC_RESOLUTION: i32 = -6;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxErrorMessage {
+    msg: String,
+}
+
+/// The indexer reports errors as a list of `errors`, the node with a single `message`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxError {
+    errors: Option<Vec<DydxErrorMessage>>,
+    message: Option<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxHeight<'a> {
+    height: &'a str,
+    time: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct DydxTime {
+    epoch: f64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct DydxMarket<'a> {
+    clobPairId: &'a str,
+    status: &'a str,
+    tickSize: &'a str,
+    stepSize: &'a str,
+    atomicResolution: i32,
+    quantumConversionExponent: i32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+struct DydxMarkets<'a> {
+    #[serde(borrow)]
+    markets: HashMap<&'a str, DydxMarket<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxAssetPosition<'a> {
+    side: &'a str,
+    size: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct DydxSubaccount<'a> {
+    #[serde(borrow)]
+    assetPositions: HashMap<&'a str, DydxAssetPosition<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+struct DydxSubaccountResponse<'a> {
+    #[serde(borrow)]
+    subaccount: DydxSubaccount<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxAccount<'a> {
+    account_number: &'a str,
+    sequence: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxAccountResponse<'a> {
+    #[serde(borrow)]
+    account: DydxAccount<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct DydxBroadcast<'a> {
+    tx_bytes: &'a str,
+    mode: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxTxResponse {
+    code: u32,
+    #[serde(default)]
+    codespace: String,
+    #[serde(default)]
+    raw_log: String,
+    txhash: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxBroadcastResponse {
+    tx_response: DydxTxResponse,
+}
+
+/// Return `10^exp / tick.ticks_per_unit()`, i.e. the number of units worth `10^exp`
+/// in one tick, if it is an integer.
+fn units_per_tick(exp: i32, tick: Tick) -> Option<u64> {
+    if exp < 0 {
+        return None;
+    }
+
+    let pow = 10u64.checked_pow(exp as u32)?;
+    if pow % tick.ticks_per_unit() != 0 {
+        return None;
+    }
+    Some(pow / tick.ticks_per_unit())
+}
+
+/// Parse the expected sequence out of the log of a transaction rejected because of a
+/// stale sequence, e.g. `account sequence mismatch, expected 12, got 11`.
+fn expected_sequence(raw_log: &str) -> Option<u64> {
+    let start = raw_log.find("expected ")? + "expected ".len();
+    let digits = raw_log[start..].chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+fn request<K: api::errors::ErrorKind>(
+    http_client: &HttpClient,
+    address: String,
+    method: Method,
+    body: String,
+) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+        where RestError: ErrorKinded<K>
+{
+    let mut request = Request::builder();
+    request.method(method)
+        .uri(&address)
+        .header("User-Agent", &b"hyper"[..])
+        .header("Content-Type", &b"application/json"[..]);
+
+    // Unwrap because it is a bug if this fails (header failed to parse or something)
+    let request = request.body(body.into()).unwrap();
+    http_client.request(request).and_then(|res| {
+        let status = res.status();
+        res.into_body().concat2().and_then(move |body| {
+            Ok((status, body))
+        })
+    })
+    .map_err(api::errors::RequestError::new)
+    .map_err(api::errors::ApiError::RequestError)
+    .and_then(|(status, body)| {
+        if !status.is_success() {
+            let message = serde_json::from_slice(&body).ok().and_then(|error| {
+                let DydxError { errors, message } = error;
+                message.or_else(|| {
+                    errors.and_then(|errors| errors.into_iter().next()).map(|error| error.msg)
+                })
+            });
+            let error = RestError::from_dydx_error(status, message);
+            let kind = error.kind();
+            Err(
+                api::errors::ApiError::RestError(error.context(kind).into())
+            )?;
+        }
+        Ok(body)
+    })
+}
+
+/// Sign a transaction carrying `message` and broadcast it to the node at `address`.
+/// Long term orders and cancels consume the sequence of the account, which is given
+/// back if the transaction is rejected.
+fn broadcast<K: api::errors::ErrorKind>(
+    http_client: &HttpClient,
+    address: String,
+    chain_id: &str,
+    keys: Keys,
+    message: tx::Message,
+    order_flags: u32,
+) -> Box<dyn Future<Item = (), Error = api::errors::ApiError<K>> + Send + 'static>
+        where RestError: ErrorKinded<K>
+{
+    let sequence = {
+        let mut sequence = keys.sequence.lock().unwrap();
+        let current = *sequence;
+        if order_flags == tx::LONG_TERM {
+            *sequence += 1;
+        }
+        current
+    };
+
+    let tx_bytes = match keys.signer.sign_tx(
+        &[message],
+        chain_id,
+        keys.account_number,
+        sequence
+    ) {
+        Ok(tx_bytes) => base64::encode(&tx_bytes),
+        Err(err) => {
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RequestError(api::errors::RequestError::new(err))
+            ));
+        }
+    };
+
+    // Transactions are checked synchronously, so that rejected orders are reported
+    // as errors, but are not waited for until they are included in a block.
+    let body = DydxBroadcast {
+        tx_bytes: &tx_bytes,
+        mode: "BROADCAST_MODE_SYNC",
+    };
+    let body = serde_json::to_string(&body).expect("invalid json");
+
+    let fut = request(http_client, address, Method::POST, body).and_then(move |body| {
+        let response: DydxBroadcastResponse = serde_json::from_slice(&body)
+            .map_err(api::errors::RequestError::new)
+            .map_err(api::errors::ApiError::RequestError)?;
+        let response = response.tx_response;
+
+        if response.code != 0 {
+            if order_flags == tx::LONG_TERM {
+                let mut current = keys.sequence.lock().unwrap();
+                let stale = response.codespace == SDK_CODESPACE
+                    && response.code == SDK_WRONG_SEQUENCE;
+
+                match expected_sequence(&response.raw_log) {
+                    Some(expected) if stale => {
+                        warn!("account sequence out of sync, expected {}", expected);
+                        *current = expected;
+                    }
+                    _ if *current == sequence + 1 => *current = sequence,
+                    _ => (),
+                }
+            }
+
+            let error = RestError::rejected(response.codespace, response.code, response.raw_log);
+            let kind = error.kind();
+            return Err(api::errors::ApiError::RestError(error.context(kind).into()));
+        }
+
+        debug!("broadcast transaction {}", response.txhash);
+        Ok(())
+    });
+    Box::new(fut)
+}
+
+impl Client {
+    fn indexer_address(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.params.rest_endpoint, endpoint)
+    }
+
+    fn node_address(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.node.rest_endpoint, endpoint)
+    }
+
+    fn height<K: api::errors::ErrorKind>(&self)
+        -> impl Future<Item = u32, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let address = self.indexer_address("height");
+        request(&self.http_client, address, Method::GET, String::new()).and_then(|body| {
+            let height: DydxHeight<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let height = height.height.parse()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(height)
+        })
+    }
+
+    /// Short term orders and cancels expire after a few blocks, long term ones after
+    /// `LONG_TERM_VALIDITY`.
+    fn good_til<K: api::errors::ErrorKind>(&self, order_flags: u32)
+        -> Box<dyn Future<Item = GoodTil, Error = api::errors::ApiError<K>> + Send + 'static>
+            where RestError: ErrorKinded<K>
+    {
+        if order_flags == tx::LONG_TERM {
+            let time = timestamp_ms() / 1000 + LONG_TERM_VALIDITY;
+            return Box::new(futures::future::ok(GoodTil::BlockTime(time as u32)));
+        }
+
+        Box::new(self.height().map(|height| GoodTil::Block(height + tx::SHORT_BLOCK_WINDOW)))
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by dYdX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by dYdX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let symbol = order.symbol();
+        let market = match self.markets.get(symbol.name()) {
+            Some(market) => *market,
+            None => {
+                warn!("called `order` with an unknown symbol `{}`", symbol.name());
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let client_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => <Self as GenerateOrderId>::new_order_id(""),
+        };
+        let client_id: u32 = match client_id.parse() {
+            Ok(client_id) => client_id,
+            Err(_) => {
+                warn!("called `order` with an order id which is not a 32 bits integer");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // Only long term orders can rest in the book until canceled.
+        let (order_flags, time_in_force) = match (order.type_, order.time_in_force) {
+            (OrderType::Limit, TimeInForce::GoodTilCanceled) => {
+                (tx::LONG_TERM, OrderTimeInForce::Unspecified)
+            }
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => {
+                (tx::LONG_TERM, OrderTimeInForce::PostOnly)
+            }
+            (OrderType::Limit, TimeInForce::ImmediateOrCancel) => {
+                (tx::SHORT_TERM, OrderTimeInForce::ImmediateOrCancel)
+            }
+            (OrderType::Limit, TimeInForce::FillOrKilll) => {
+                (tx::SHORT_TERM, OrderTimeInForce::FillOrKill)
+            }
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by dYdX",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let quantums = order.size.ticked(symbol.size_tick()) * market.quantums_per_tick;
+        let subticks = order.price.ticked(symbol.price_tick()) * market.subticks_per_tick;
+        let side = order.side;
+        let reduce_only = order.reduce_only;
+
+        let keys = self.keys.clone().expect("no keys");
+        let http_client = self.http_client.clone();
+        let address = self.node_address("cosmos/tx/v1beta1/txs");
+        let chain_id = self.node.chain_id.clone();
+        let order_flags_by_id = self.order_flags.clone();
+
+        let fut = self.good_til(order_flags).and_then(move |good_til| {
+            let order_id = OrderId {
+                owner: &keys.address,
+                subaccount_number: keys.subaccount_number,
+                client_id,
+                order_flags,
+                clob_pair_id: market.clob_pair_id,
+            };
+            let message = tx::place_order(
+                &order_id,
+                side,
+                quantums,
+                subticks,
+                good_til,
+                time_in_force,
+                reduce_only
+            );
+            broadcast(&http_client, address, &chain_id, keys, message, order_flags)
+        }).map(move |()| {
+            let order_id = client_id.to_string();
+            order_flags_by_id.insert(order_id.clone(), order_flags);
+            debug!("insert order id {} (from REST)", order_id);
+
+            OrderAck {
+                order_id,
+            }.timestamped()
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        let unknown_order = || Box::new(futures::future::err(api::errors::ApiError::RestError(
+            api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            ).into()
+        )));
+
+        // Orders can only be canceled along with their flags, which are part of their id.
+        let (client_id, order_flags) = match (
+            cancel.order_id.parse::<u32>(),
+            self.order_flags.get(&cancel.order_id)
+        ) {
+            (Ok(client_id), Some(order_flags)) => (client_id, *order_flags),
+            _ => {
+                warn!("called `cancel` with a not yet inserted order id");
+                return unknown_order();
+            }
+        };
+
+        let symbol = cancel.symbol();
+        let clob_pair_id = match self.markets.get(symbol.name()) {
+            Some(market) => market.clob_pair_id,
+            None => {
+                warn!("called `cancel` with an unknown symbol `{}`", symbol.name());
+                return unknown_order();
+            }
+        };
+
+        let keys = self.keys.clone().expect("no keys");
+        let http_client = self.http_client.clone();
+        let address = self.node_address("cosmos/tx/v1beta1/txs");
+        let chain_id = self.node.chain_id.clone();
+
+        let fut = self.good_til(order_flags).and_then(move |good_til| {
+            let order_id = OrderId {
+                owner: &keys.address,
+                subaccount_number: keys.subaccount_number,
+                client_id,
+                order_flags,
+                clob_pair_id,
+            };
+            let message = tx::cancel_order(&order_id, good_til);
+            broadcast(&http_client, address, &chain_id, keys, message, order_flags)
+        }).map(|()| CancelAck.timestamped());
+        Box::new(fut)
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = self.indexer_address("time");
+        request(&self.http_client, address, Method::GET, String::new()).and_then(|body| {
+            let time: DydxTime = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let timestamp = (time.epoch * 1000.).round() as Timestamp;
+            Ok(().with_timestamp(timestamp))
+        })
+    }
+
+    /// Balances are the asset positions of the subaccount, e.g. `USDC`. The collateral
+    /// backing open positions is not reported as locked.
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let keys = self.keys.as_ref().expect("no keys");
+        let address = self.indexer_address(&format!(
+            "addresses/{}/subaccountNumber/{}",
+            keys.address,
+            keys.subaccount_number
+        ));
+
+        request(&self.http_client, address, Method::GET, String::new()).and_then(|body| {
+            let response: DydxSubaccountResponse<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let balances = response.subaccount.assetPositions.into_iter()
+                .map(|(asset, position)| {
+                    let free = if position.side == "SHORT" {
+                        format!("-{}", position.size)
+                    } else {
+                        position.size.to_owned()
+                    };
+
+                    (asset.to_owned(), Balance {
+                        free,
+                        locked: "0".to_owned(),
+                    })
+                })
+                .collect();
+            Ok(balances)
+        })
+    }
+
+    /// dYdX has no status page: the chain is deemed halted if the last block seen by
+    /// the indexer is too old.
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = self.indexer_address("height");
+        request(&self.http_client, address, Method::GET, String::new()).and_then(|body| {
+            let height: DydxHeight<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let time = convert_str_timestamp(height.time)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = if timestamp_ms().saturating_sub(time) > HALTED_AFTER {
+                ExchangeStatus::Outage
+            } else {
+                ExchangeStatus::Normal
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn get_markets(&self)
+        -> impl Future<Item = HashMap<String, Market>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = self.indexer_address("perpetualMarkets");
+        request(&self.http_client, address, Method::GET, String::new()).and_then(|body| {
+            let markets: DydxMarkets<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut result = HashMap::new();
+            for (name, m) in markets.markets {
+                if m.status != "ACTIVE" {
+                    continue;
+                }
+
+                let (price_tick, size_tick) = match (
+                    Tick::tick_size(m.tickSize),
+                    Tick::tick_size(m.stepSize)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", name);
+                        continue;
+                    }
+                };
+
+                // Sizes are expressed in base quantums worth `10^atomicResolution` units,
+                // and prices in subticks: one subtick per base quantum is worth
+                // `10^quantumConversionExponent` quote quantums.
+                let quantums_per_tick = units_per_tick(-m.atomicResolution, size_tick);
+                let subticks_per_tick = units_per_tick(
+                    m.atomicResolution - m.quantumConversionExponent - QUOTE_ATOMIC_RESOLUTION,
+                    price_tick
+                );
+                let (quantums_per_tick, subticks_per_tick, clob_pair_id) = match (
+                    quantums_per_tick,
+                    subticks_per_tick,
+                    m.clobPairId.parse()
+                ) {
+                    (Some(quantums), Some(subticks), Ok(clob_pair_id)) => {
+                        (quantums, subticks, clob_pair_id)
+                    }
+                    _ => {
+                        error!("cannot convert ticks for symbol `{}`", name);
+                        continue;
+                    }
+                };
+
+                match Symbol::new(name, price_tick, size_tick) {
+                    Some(symbol) => {
+                        result.insert(name.to_uppercase(), Market {
+                            symbol,
+                            clob_pair_id,
+                            quantums_per_tick,
+                            subticks_per_tick,
+                        });
+                    }
+                    None => error!("symbol name too long: `{}`", name),
+                }
+            }
+            Ok(result)
+        })
+    }
+
+    /// Return the account number and the current sequence of the account at `address`.
+    crate fn get_account(&self, address: &str)
+        -> impl Future<Item = (u64, u64), Error = api::errors::Error> + Send + 'static
+    {
+        let address = self.node_address(&format!("cosmos/auth/v1beta1/accounts/{}", address));
+        request(&self.http_client, address, Method::GET, String::new()).and_then(|body| {
+            let response: DydxAccountResponse<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let parse = |value: &str| value.parse::<u64>()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError);
+            Ok((parse(response.account.account_number)?, parse(response.account.sequence)?))
+        })
+    }
+}
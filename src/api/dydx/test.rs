@@ -0,0 +1,55 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::dydx::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC-USD", Tick::new(1), Tick::new(10_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the limit updates
+/// without their local timestamps.
+fn parse_book(frames: &[&str]) -> Vec<Vec<LimitUpdate>> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| match notif.unwrap() {
+        Notification::LimitUpdates(updates) => updates.iter().map(|u| **u).collect(),
+        other => panic!("expected limit updates, got {:?}", other),
+    }).collect()
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    // Snapshots carry levels as objects, updates as arrays.
+    let snapshot = r#"{"type":"subscribed","connection_id":"a1b2","message_id":1,
+        "channel":"v4_orderbook","id":"BTC-USD","contents":{
+        "bids":[{"price":"65000","size":"1.5"},{"price":"64999","size":"0.2"}],
+        "asks":[{"price":"65001","size":"0.7"}]}}"#;
+    let update = r#"{"type":"channel_data","connection_id":"a1b2","message_id":2,
+        "channel":"v4_orderbook","id":"BTC-USD","version":"1.0.0","contents":{
+        "bids":[["64999","0"]],"asks":[["65002","1"]]}}"#;
+
+    assert_eq!(parse_book(&[snapshot, update]), vec![
+        vec![
+            LimitUpdate::new(65_000, 15_000, Side::Bid),
+            LimitUpdate::new(64_999, 2_000, Side::Bid),
+            LimitUpdate::new(65_001, 7_000, Side::Ask),
+        ],
+        vec![
+            LimitUpdate::new(64_999, 0, Side::Bid),
+            LimitUpdate::new(65_002, 10_000, Side::Ask),
+        ],
+    ]);
+}
@@ -0,0 +1,311 @@
+//! Cosmos transactions carrying orders and cancels. dYdX has no order entry API:
+//! orders are messages of the `clob` module, wrapped into transactions signed with the
+//! secp256k1 key of the account and broadcast to a validator node. The few protobuf
+//! messages needed are encoded by hand.
+
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use std::cmp::Ordering;
+use failure::bail;
+use crate::Side;
+
+/// Flags of short term orders, which live in the memory of the validators for at
+/// most `SHORT_BLOCK_WINDOW` blocks.
+crate const SHORT_TERM: u32 = 0;
+
+/// Flags of long term orders, which are stored in the state of the chain until their
+/// expiration time.
+crate const LONG_TERM: u32 = 64;
+
+/// Maximum number of blocks short term orders and cancels can be valid for.
+crate const SHORT_BLOCK_WINDOW: u32 = 20;
+
+/// Gas limit of the transactions. Orders and cancels do not pay fees, the limit only
+/// has to be high enough for the transaction to be executed.
+const GAS_LIMIT: u64 = 1_000_000;
+
+/// Protobuf wire types.
+const VARINT: u32 = 0;
+const LENGTH_DELIMITED: u32 = 2;
+const FIXED32: u32 = 5;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A protobuf message being encoded. Fields equal to their default value are
+/// omitted, as in proto3.
+crate struct Message(Vec<u8>);
+
+impl Message {
+    fn new() -> Self {
+        Message(Vec::new())
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) {
+        self.raw_varint(u64::from(field << 3 | wire_type));
+    }
+
+    fn uint(mut self, field: u32, value: u64) -> Self {
+        if value != 0 {
+            self.key(field, VARINT);
+            self.raw_varint(value);
+        }
+        self
+    }
+
+    fn fixed32(mut self, field: u32, value: u32) -> Self {
+        if value != 0 {
+            self.key(field, FIXED32);
+            for i in 0..4 {
+                self.0.push((value >> (8 * i)) as u8);
+            }
+        }
+        self
+    }
+
+    fn bytes(mut self, field: u32, value: &[u8]) -> Self {
+        if !value.is_empty() {
+            self.key(field, LENGTH_DELIMITED);
+            self.raw_varint(value.len() as u64);
+            self.0.extend_from_slice(value);
+        }
+        self
+    }
+
+    fn string(self, field: u32, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    /// Embedded messages are always written, even if empty.
+    fn message(mut self, field: u32, value: &Message) -> Self {
+        self.key(field, LENGTH_DELIMITED);
+        self.raw_varint(value.0.len() as u64);
+        self.0.extend_from_slice(&value.0);
+        self
+    }
+
+    /// Wrap `self` into a `google.protobuf.Any`.
+    fn into_any(self, type_url: &str) -> Message {
+        Message::new().string(1, type_url).bytes(2, &self.0)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// Identifier of an order on the chain.
+crate struct OrderId<'a> {
+    crate owner: &'a str,
+    crate subaccount_number: u32,
+    crate client_id: u32,
+    crate order_flags: u32,
+    crate clob_pair_id: u32,
+}
+
+impl<'a> OrderId<'a> {
+    fn encode(&self) -> Message {
+        let subaccount_id = Message::new()
+            .string(1, self.owner)
+            .uint(2, u64::from(self.subaccount_number));
+
+        Message::new()
+            .message(1, &subaccount_id)
+            .fixed32(2, self.client_id)
+            .uint(3, u64::from(self.order_flags))
+            .uint(4, u64::from(self.clob_pair_id))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Expiration of an order or of a cancel.
+crate enum GoodTil {
+    /// Last block height, for short term orders.
+    Block(u32),
+
+    /// Unix timestamp in seconds, for long term orders.
+    BlockTime(u32),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Time in force as understood by the `clob` module. Good til canceled orders are
+/// long term orders with an unspecified time in force.
+crate enum OrderTimeInForce {
+    Unspecified = 0,
+    ImmediateOrCancel = 1,
+    PostOnly = 2,
+    FillOrKill = 3,
+}
+
+/// Return a `MsgPlaceOrder`, with `quantums` and `subticks` being the size and the price
+/// in the units of the market.
+crate fn place_order(
+    order_id: &OrderId,
+    side: Side,
+    quantums: u64,
+    subticks: u64,
+    good_til: GoodTil,
+    time_in_force: OrderTimeInForce,
+    reduce_only: bool
+) -> Message
+{
+    let side = match side {
+        Side::Bid => 1,
+        Side::Ask => 2,
+    };
+
+    let order = Message::new()
+        .message(1, &order_id.encode())
+        .uint(2, side)
+        .uint(3, quantums)
+        .uint(4, subticks);
+
+    let order = match good_til {
+        GoodTil::Block(height) => order.uint(5, u64::from(height)),
+        GoodTil::BlockTime(time) => order.fixed32(6, time),
+    };
+
+    let order = order
+        .uint(7, time_in_force as u64)
+        .uint(8, reduce_only as u64);
+
+    Message::new()
+        .message(1, &order)
+        .into_any("/dydxprotocol.clob.MsgPlaceOrder")
+}
+
+/// Return a `MsgCancelOrder`.
+crate fn cancel_order(order_id: &OrderId, good_til: GoodTil) -> Message {
+    let cancel = Message::new().message(1, &order_id.encode());
+
+    let cancel = match good_til {
+        GoodTil::Block(height) => cancel.uint(2, u64::from(height)),
+        GoodTil::BlockTime(time) => cancel.fixed32(3, time),
+    };
+
+    cancel.into_any("/dydxprotocol.clob.MsgCancelOrder")
+}
+
+/// Signs transactions with the secp256k1 key of an account.
+crate struct Signer {
+    key: EcKey<Private>,
+
+    /// Compressed public key.
+    public_key: Vec<u8>,
+
+    /// Order of the curve, for normalizing signatures.
+    order: BigNum,
+}
+
+impl Signer {
+    /// Return a new signer from a hex encoded private key.
+    crate fn new(private_key: &str) -> Result<Self, failure::Error> {
+        let private_key = hex::decode(private_key.trim_start_matches("0x"))?;
+        if private_key.len() > 32 {
+            bail!("invalid private key length: {}", private_key.len());
+        }
+
+        // Wrap the key into a SEC1 `ECPrivateKey` structure on secp256k1, the public key
+        // being computed by OpenSSL when decoding it.
+        let mut der = vec![0x30, 0x2e, 0x02, 0x01, 0x01, 0x04, 0x20];
+        der.resize(der.len() + 32 - private_key.len(), 0);
+        der.extend(&private_key);
+        der.extend(&[0xa0, 0x07, 0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x0a]);
+
+        let key = EcKey::private_key_from_der(&der)?;
+        key.check_key()?;
+
+        let group = EcGroup::from_curve_name(Nid::SECP256K1)?;
+        let mut ctx = BigNumContext::new()?;
+        let mut order = BigNum::new()?;
+        group.order(&mut order, &mut ctx)?;
+
+        let public_key = key.public_key()
+            .to_bytes(&group, PointConversionForm::COMPRESSED, &mut ctx)?;
+
+        Ok(Signer {
+            key,
+            public_key,
+            order,
+        })
+    }
+
+    /// Return the signature of `message` as `r` and `s` as 32 bytes big endian
+    /// integers, `s` being normalized to the lower half of the curve order as
+    /// required by the Cosmos SDK.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        let digest = openssl::sha::sha256(message);
+        let signature = EcdsaSig::sign(&digest, &self.key)?;
+
+        let mut half_order = BigNum::new()?;
+        half_order.rshift1(&self.order)?;
+
+        let mut s = signature.s().to_owned()?;
+        if s.ucmp(&half_order) == Ordering::Greater {
+            let mut low_s = BigNum::new()?;
+            low_s.checked_sub(&self.order, &s)?;
+            s = low_s;
+        }
+
+        let mut raw = Vec::with_capacity(64);
+        for value in &[signature.r().to_vec(), s.to_vec()] {
+            let padded_len = raw.len() + 32usize.saturating_sub(value.len());
+            raw.resize(padded_len, 0);
+            raw.extend(value);
+        }
+        Ok(raw)
+    }
+
+    /// Return the bytes of a `TxRaw` carrying `messages`, signed in direct mode.
+    crate fn sign_tx(
+        &self,
+        messages: &[Message],
+        chain_id: &str,
+        account_number: u64,
+        sequence: u64
+    ) -> Result<Vec<u8>, ErrorStack>
+    {
+        let body = messages.iter().fold(Message::new(), |body, message| {
+            body.message(1, message)
+        });
+
+        let public_key = Message::new()
+            .bytes(1, &self.public_key)
+            .into_any("/cosmos.crypto.secp256k1.PubKey");
+
+        // `SIGN_MODE_DIRECT`
+        let mode_info = Message::new().message(1, &Message::new().uint(1, 1));
+
+        let signer_info = Message::new()
+            .message(1, &public_key)
+            .message(2, &mode_info)
+            .uint(3, sequence);
+
+        let fee = Message::new().uint(2, GAS_LIMIT);
+
+        let auth_info = Message::new()
+            .message(1, &signer_info)
+            .message(2, &fee);
+
+        let sign_doc = Message::new()
+            .bytes(1, &body.0)
+            .bytes(2, &auth_info.0)
+            .string(3, chain_id)
+            .uint(4, account_number);
+
+        let signature = self.sign(&sign_doc.0)?;
+
+        let tx = Message::new()
+            .bytes(1, &body.0)
+            .bytes(2, &auth_info.0)
+            .bytes(3, &signature);
+        Ok(tx.0)
+    }
+}
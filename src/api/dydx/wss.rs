@@ -0,0 +1,501 @@
+use futures::sync::mpsc::unbounded;
+use std::{mem, thread};
+use std::collections::HashMap;
+use chashmap::CHashMap;
+use std::sync::Arc;
+use log::{debug, error, warn};
+use failure::bail;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    OrderConfirmation,
+    OrderUpdate,
+    Trade,
+    OrderExpiration,
+};
+use crate::api::symbol::Symbol;
+use crate::api::stream::NotificationStream;
+use crate::api::wss;
+use crate::api::timestamp::{convert_str_timestamp, IntoTimestamped};
+use crate::api::dydx::Client;
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let streaming_endpoint = self.params.streaming_endpoint.clone();
+        let subaccount_id = self.keys.as_ref().map(|keys| keys.subaccount_id());
+        let order_flags = self.order_flags.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", streaming_endpoint);
+
+            if let Err(err) = ws::connect(streaming_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        subaccount_id: subaccount_id.clone(),
+                        out: None,
+                        order_book: OrderBook::new(),
+                        orders: HashMap::new(),
+                        order_flags: order_flags.clone(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// State of an order as of its last fill.
+#[derive(Clone, PartialEq, Debug)]
+struct OrderState {
+    order_id: String,
+    size: TickUnit,
+    filled: TickUnit,
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+
+    /// `{address}/{subaccount number}`, if a key pair was provided.
+    subaccount_id: Option<String>,
+
+    /// Set once the connection is open.
+    out: Option<ws::Sender>,
+
+    /// Local copy of the order book: snapshots sent again after a resubscription are
+    /// turned into updates against it.
+    order_book: OrderBook,
+
+    /// server order id => order state
+    orders: HashMap<String, OrderState>,
+
+    /// client order id => order flags (shared with `Client`)
+    order_flags: Arc<CHashMap<String, u32>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct DydxSubscription<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    channel: &'a str,
+    id: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxHeader<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    message: Option<&'a str>,
+    channel: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxMessage<T> {
+    contents: T,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxLevel<'a> {
+    price: &'a str,
+    size: &'a str,
+}
+
+/// Snapshots carry levels as objects, updates as `[price, size]` arrays.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(untagged)]
+enum DydxBookLevel<'a> {
+    #[serde(borrow)]
+    Object(DydxLevel<'a>),
+    #[serde(borrow)]
+    Array(Vec<&'a str>),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxBook<'a> {
+    #[serde(borrow, default)]
+    bids: Vec<DydxBookLevel<'a>>,
+    #[serde(borrow, default)]
+    asks: Vec<DydxBookLevel<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct DydxTrade<'a> {
+    side: &'a str,
+    size: &'a str,
+    price: &'a str,
+    createdAt: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxTrades<'a> {
+    #[serde(borrow)]
+    trades: Vec<DydxTrade<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct DydxOrder<'a> {
+    id: &'a str,
+    clientId: &'a str,
+    ticker: &'a str,
+    side: &'a str,
+    size: &'a str,
+    price: &'a str,
+    status: &'a str,
+    orderFlags: &'a str,
+    totalFilled: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct DydxFill<'a> {
+    orderId: Option<&'a str>,
+    ticker: &'a str,
+    size: &'a str,
+    price: &'a str,
+    fee: &'a str,
+    createdAt: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct DydxSubaccountContents<'a> {
+    #[serde(borrow, default)]
+    orders: Vec<DydxOrder<'a>>,
+    #[serde(borrow, default)]
+    fills: Vec<DydxFill<'a>>,
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys and which is not connected,
+    /// for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            subaccount_id: None,
+            out: None,
+            order_book: OrderBook::new(),
+            orders: HashMap::new(),
+            order_flags: Arc::new(CHashMap::new()),
+        }
+    }
+
+    fn send_request(&mut self, type_: &str, channel: &str) -> ws::Result<()> {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        // The subaccount channel is identified by the subaccount, the others by the market.
+        let id = if channel == "v4_subaccounts" {
+            match &self.subaccount_id {
+                Some(id) => id.clone(),
+                None => return Ok(()),
+            }
+        } else {
+            self.symbol.name().to_owned()
+        };
+
+        let subscription = DydxSubscription {
+            type_,
+            channel,
+            id: &id,
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `DydxSubscription`: `{}`", err);
+            }
+        }
+    }
+
+    fn convert_dydx_side(&self, side: &str) -> Result<Side, failure::Error> {
+        let side = match side {
+            "BUY" => Side::Bid,
+            "SELL" => Side::Ask,
+            other => bail!("wrong side: `{}`", other),
+        };
+        Ok(side)
+    }
+
+    fn convert_dydx_level(&self, level: &DydxBookLevel<'_>, side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        let (price, size) = match level {
+            DydxBookLevel::Object(level) => (level.price, level.size),
+            DydxBookLevel::Array(level) if level.len() >= 2 => (level[0], level[1]),
+            DydxBookLevel::Array(level) => bail!("wrong level: `{:?}`", level),
+        };
+
+        Ok(LimitUpdate {
+            side,
+            price: self.symbol.price_tick().ticked(price)?,
+            size: self.symbol.size_tick().ticked(size)?,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, snapshot: bool, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let message: DydxMessage<DydxBook<'_>> = serde_json::from_str(json)?;
+        let book = message.contents;
+
+        let bids = book.bids.iter().map(|level| self.convert_dydx_level(level, Side::Bid));
+        let asks = book.asks.iter().map(|level| self.convert_dydx_level(level, Side::Ask));
+        let levels = bids.chain(asks).collect::<Result<Vec<_>, failure::Error>>()?;
+
+        let mut updates = Vec::new();
+
+        // Levels which are not part of a new snapshot were removed in the meantime.
+        if snapshot {
+            let contains = |side: Side, price: TickUnit| {
+                levels.iter().any(|level| level.side == side && level.price == price)
+            };
+            let asks = self.order_book.ask()
+                .filter(|(price, _)| !contains(Side::Ask, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+            let bids = self.order_book.bid()
+                .filter(|(price, _)| !contains(Side::Bid, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+            let removed = asks.chain(bids).collect::<Vec<_>>();
+            for update in &removed {
+                self.order_book.update(*update);
+            }
+            updates.extend(removed);
+        }
+
+        for level in levels {
+            if self.order_book.size_at_limit(level.side, level.price) != level.size {
+                self.order_book.update(level);
+                updates.push(level);
+            }
+        }
+
+        // The indexer does not timestamp book updates.
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.timestamped())
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: DydxMessage<DydxTrades<'_>> = serde_json::from_str(json)?;
+
+        for trade in message.contents.trades {
+            // The side is the one of the taker order.
+            let maker_side = match self.convert_dydx_side(trade.side)? {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid,
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: self.symbol.price_tick().ticked(trade.price)?,
+                size: self.symbol.size_tick().ticked(trade.size)?,
+                maker_side,
+            }.with_timestamp(convert_str_timestamp(trade.createdAt)?));
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Orders carry their state, while fills carry the traded price and the fee: fills
+    /// are matched with the orders seen before or in the same message.
+    fn parse_subaccount(&mut self, json: &str, snapshot: bool, out: Option<&wss::NotifSender>)
+        -> Result<(), failure::Error>
+    {
+        let message: DydxMessage<DydxSubaccountContents<'_>> = serde_json::from_str(json)?;
+        let contents = message.contents;
+        let size_tick = self.symbol.size_tick();
+        let send = |notification| {
+            if let Some(out) = out {
+                out.unbounded_send(notification).unwrap();
+            }
+        };
+
+        let mut finished = Vec::new();
+        for order in &contents.orders {
+            if order.ticker != self.symbol.name() {
+                continue;
+            }
+
+            let size = size_tick.ticked(order.size)?;
+            if !self.orders.contains_key(order.id) {
+                // Don't forget to update the concurrent map in case the WebSocket notif
+                // arrives before the HTTP response.
+                self.order_flags.insert(order.clientId.to_owned(), order.orderFlags.parse()?);
+                debug!("insert order id {} (from WSS)", order.clientId);
+
+                // The snapshot lists the open orders, which may already be partially filled.
+                let filled = match order.totalFilled {
+                    Some(filled) if snapshot => size_tick.ticked(filled)?,
+                    _ => 0,
+                };
+
+                self.orders.insert(order.id.to_owned(), OrderState {
+                    order_id: order.clientId.to_owned(),
+                    size,
+                    filled,
+                });
+
+                if !snapshot {
+                    let confirmation = OrderConfirmation {
+                        order_id: order.clientId.to_owned(),
+                        price: self.symbol.price_tick().ticked(order.price)?,
+                        size,
+                        side: self.convert_dydx_side(order.side)?,
+                    }.timestamped();
+                    send(Notification::OrderConfirmation(confirmation));
+                }
+            }
+
+            match order.status {
+                "FILLED" | "CANCELED" | "BEST_EFFORT_CANCELED" => {
+                    finished.push((order.id, order.status));
+                }
+                _ => (),
+            }
+        }
+
+        for fill in &contents.fills {
+            if fill.ticker != self.symbol.name() {
+                continue;
+            }
+
+            let state = match fill.orderId.and_then(|order_id| self.orders.get_mut(order_id)) {
+                Some(state) => state,
+                None => {
+                    warn!("received a fill for an unknown order");
+                    continue;
+                }
+            };
+
+            let consumed_size = size_tick.ticked(fill.size)?;
+            state.filled += consumed_size;
+
+            let update = OrderUpdate {
+                order_id: state.order_id.clone(),
+                consumed_size,
+                remaining_size: state.size.saturating_sub(state.filled),
+                consumed_price: self.symbol.price_tick().ticked(fill.price)?,
+                commission: self.symbol.commission_tick().rounded(fill.fee.parse()?),
+            }.with_timestamp(convert_str_timestamp(fill.createdAt)?);
+            send(Notification::OrderUpdate(update));
+        }
+
+        for (order_id, status) in finished {
+            let state = match self.orders.remove(order_id) {
+                Some(state) => state,
+                None => continue,
+            };
+            self.order_flags.remove(&state.order_id);
+
+            if status != "FILLED" {
+                let expiration = OrderExpiration {
+                    order_id: state.order_id,
+                    filled_size: Some(state.filled),
+                    remaining_size: Some(state.size.saturating_sub(state.filled)),
+                }.timestamped();
+                send(Notification::OrderExpiration(expiration));
+            }
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let header: DydxHeader<'_> = serde_json::from_str(json)?;
+
+        let snapshot = match header.type_ {
+            "subscribed" => true,
+            "channel_data" => false,
+            "error" => bail!("{}", header.message.unwrap_or_default()),
+            _ => return Ok(()),
+        };
+
+        match header.channel {
+            Some("v4_orderbook") if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book(json, snapshot, out)?;
+            }
+
+            // The snapshot only holds past trades.
+            Some("v4_trades") if !snapshot && self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_trades(json, out)?;
+            }
+
+            // Order states must be tracked even if they are not forwarded.
+            Some("v4_subaccounts") => {
+                let forward = self.flags.contains(NotificationFlags::ORDERS);
+                self.parse_subaccount(json, snapshot, if forward { Some(out) } else { None })?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.send_request("subscribe", "v4_orderbook")?;
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            self.send_request("subscribe", "v4_trades")?;
+        }
+        self.send_request("subscribe", "v4_subaccounts")
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The subaccount channel is kept, `ORDERS` only acts as a filter.
+        match (previous.contains(NotificationFlags::TRADES), flags.contains(NotificationFlags::TRADES)) {
+            (false, true) => self.send_request("subscribe", "v4_trades")?,
+            (true, false) => self.send_request("unsubscribe", "v4_trades")?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => self.send_request("subscribe", "v4_orderbook")?,
+
+            // The book will be forwarded again from scratch.
+            (true, false) => {
+                self.order_book = OrderBook::new();
+                self.send_request("unsubscribe", "v4_orderbook")?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
@@ -70,6 +70,11 @@ pub enum OrderErrorKind {
     #[fail(display = "order would take liquidity")]
     /// The order would take liquidity but was marked as liquidity maker only.
     WouldTakeLiquidity,
+
+    #[fail(display = "trading is halted")]
+    /// The order was not sent because a `risk::CircuitBreaker` blocked it, see
+    /// `risk::guard`.
+    TradingHalted,
 }
 
 impl private::Sealed for OrderErrorKind { }
@@ -86,6 +91,22 @@ pub enum CancelErrorKind {
 impl private::Sealed for CancelErrorKind { }
 impl ErrorKind for CancelErrorKind { }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error kind specific to the `replace` API request, telling which of the cancel or
+/// the new order failed.
+pub enum ReplaceErrorKind {
+    #[fail(display = "cancel failed: {}", _0)]
+    /// The previous order could not be canceled, and the new order was not sent.
+    Cancel(CancelErrorKind),
+
+    #[fail(display = "new order failed: {}", _0)]
+    /// The previous order was canceled, but the new order was rejected.
+    Order(OrderErrorKind),
+}
+
+impl private::Sealed for ReplaceErrorKind { }
+impl ErrorKind for ReplaceErrorKind { }
+
 impl private::Sealed for ! { }
 impl ErrorKind for ! { }
 
@@ -112,6 +133,11 @@ pub enum RestErrorKind<K: ErrorKind> {
     /// The request timestamp was outside of the specified time window.
     OutsideTimeWindow,
 
+    #[fail(display = "unauthorized")]
+    /// The request needs credentials: either the client was created without a key
+    /// pair, or the exchange rejected the key pair.
+    Unauthorized,
+
     #[fail(display = "{}", _0)]
     /// More specific error kind, depending on the request being made.
     Specific(K),
@@ -124,6 +150,28 @@ pub struct RequestError {
 }
 
 impl RequestError {
+    #[cfg(any(
+        feature = "alpaca",
+        feature = "binance",
+        feature = "bitfinex",
+        feature = "bitmex",
+        feature = "bybit",
+        feature = "coinbase",
+        feature = "deribit",
+        feature = "dydx",
+        feature = "gateio",
+        feature = "gdax",
+        feature = "gemini",
+        feature = "hitbtc",
+        feature = "huobi",
+        feature = "kraken",
+        feature = "kraken_futures",
+        feature = "kucoin",
+        feature = "mexc",
+        feature = "okx",
+        feature = "testkit",
+        feature = "upbit",
+    ))]
     crate fn new<E: failure::Fail>(err: E) -> Self {
         RequestError {
             inner: Box::new(err),
@@ -159,15 +207,87 @@ pub enum ApiError<K: ErrorKind> {
     RequestError(#[cause] RequestError),
 }
 
+impl<K: ErrorKind> ApiError<K> {
+    /// Error returned by private requests, without reaching the exchange, when the
+    /// client was created without a key pair.
+    #[cfg(any(
+        feature = "alpaca",
+        feature = "binance",
+        feature = "bitfinex",
+        feature = "bitmex",
+        feature = "bybit",
+        feature = "coinbase",
+        feature = "deribit",
+        feature = "dydx",
+        feature = "gateio",
+        feature = "gdax",
+        feature = "gemini",
+        feature = "hitbtc",
+        feature = "huobi",
+        feature = "kraken",
+        feature = "kraken_futures",
+        feature = "kucoin",
+        feature = "mexc",
+        feature = "okx",
+        feature = "upbit",
+    ))]
+    crate fn unauthorized() -> Self {
+        ApiError::RestError(RestErrorKind::Unauthorized.into())
+    }
+}
+
 /// Error type adding error kinds specific to `ApiClient::order`.
 pub type OrderError = ApiError<OrderErrorKind>;
 
 /// Error type adding error kinds specific to `ApiClient::cancel`.
 pub type CancelError = ApiError<CancelErrorKind>;
 
+/// Error type adding error kinds specific to `ApiClient::replace`.
+pub type ReplaceError = ApiError<ReplaceErrorKind>;
+
 /// Basic error type not adding any specific error kinds.
 pub type Error = ApiError<!>;
 
+impl<K: ErrorKind> RestErrorKind<K> {
+    fn map_specific<L: ErrorKind, F: FnOnce(K) -> L>(self, f: F) -> RestErrorKind<L> {
+        match self {
+            RestErrorKind::TooManyRequests => RestErrorKind::TooManyRequests,
+            RestErrorKind::InvalidRequest => RestErrorKind::InvalidRequest,
+            RestErrorKind::UnknownStatus => RestErrorKind::UnknownStatus,
+            RestErrorKind::OtherSide => RestErrorKind::OtherSide,
+            RestErrorKind::OutsideTimeWindow => RestErrorKind::OutsideTimeWindow,
+            RestErrorKind::Unauthorized => RestErrorKind::Unauthorized,
+            RestErrorKind::Specific(x) => RestErrorKind::Specific(f(x)),
+        }
+    }
+}
+
+impl<K: ErrorKind> ApiError<K> {
+    fn map_specific<L: ErrorKind, F: FnOnce(K) -> L>(self, f: F) -> ApiError<L> {
+        use failure::Fail;
+
+        match self {
+            ApiError::RestError(err) => {
+                let kind = err.kind().map_specific(f);
+                ApiError::RestError(err.context(kind).into())
+            }
+            ApiError::RequestError(err) => ApiError::RequestError(err),
+        }
+    }
+}
+
+impl From<CancelError> for ReplaceError {
+    fn from(err: CancelError) -> ReplaceError {
+        err.map_specific(ReplaceErrorKind::Cancel)
+    }
+}
+
+impl From<OrderError> for ReplaceError {
+    fn from(err: OrderError) -> ReplaceError {
+        err.map_specific(ReplaceErrorKind::Order)
+    }
+}
+
 impl From<RestErrorKind<!>> for RestErrorKind<CancelErrorKind> {
     fn from(err: RestErrorKind<!>) -> RestErrorKind<CancelErrorKind> {
         match err {
@@ -176,6 +296,7 @@ impl From<RestErrorKind<!>> for RestErrorKind<CancelErrorKind> {
             RestErrorKind::UnknownStatus => RestErrorKind::UnknownStatus,
             RestErrorKind::OtherSide => RestErrorKind::OtherSide,
             RestErrorKind::OutsideTimeWindow => RestErrorKind::OutsideTimeWindow,
+            RestErrorKind::Unauthorized => RestErrorKind::Unauthorized,
             RestErrorKind::Specific(x) => x,
         }
     }
@@ -189,11 +310,46 @@ impl From<RestErrorKind<!>> for RestErrorKind<OrderErrorKind> {
             RestErrorKind::UnknownStatus => RestErrorKind::UnknownStatus,
             RestErrorKind::OtherSide => RestErrorKind::OtherSide,
             RestErrorKind::OutsideTimeWindow => RestErrorKind::OutsideTimeWindow,
+            RestErrorKind::Unauthorized => RestErrorKind::Unauthorized,
             RestErrorKind::Specific(x) => x,
         }
     }
 }
 
+#[cfg(any(
+    feature = "alpaca",
+    feature = "binance",
+    feature = "bitfinex",
+    feature = "bitmex",
+    feature = "bybit",
+    feature = "coinbase",
+    feature = "deribit",
+    feature = "dydx",
+    feature = "gateio",
+    feature = "gdax",
+    feature = "gemini",
+    feature = "hitbtc",
+    feature = "huobi",
+    feature = "kraken",
+    feature = "kraken_futures",
+    feature = "kucoin",
+    feature = "mexc",
+    feature = "okx",
+    feature = "testkit",
+    feature = "upbit",
+))]
 crate trait ErrorKinded<K: ErrorKind> {
     fn kind(&self) -> RestErrorKind<K>;
 }
+
+/// Kind of an error returned by an atomic replace request, for exchanges reporting the
+/// errors of the cancel and of the new order in the same way as for single requests.
+#[cfg(any(feature = "binance", feature = "hitbtc"))]
+crate fn replace_kind<E>(error: &E) -> RestErrorKind<ReplaceErrorKind>
+    where E: ErrorKinded<CancelErrorKind> + ErrorKinded<OrderErrorKind>
+{
+    match ErrorKinded::<CancelErrorKind>::kind(error) {
+        RestErrorKind::Specific(kind) => RestErrorKind::Specific(ReplaceErrorKind::Cancel(kind)),
+        _ => ErrorKinded::<OrderErrorKind>::kind(error).map_specific(ReplaceErrorKind::Order),
+    }
+}
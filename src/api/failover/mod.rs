@@ -0,0 +1,239 @@
+//! A module defining a failover between two market data feeds for the same pair,
+//! e.g. gdax `BTC-USD` as primary and binance `BTCUSDT` as backup.
+//!
+//! Notifications are forwarded from the primary feed as long as it is alive. When it
+//! stops sending anything for a given amount of time, the backup feed takes over until
+//! the primary one comes back. On each switchover, the order book is transitioned
+//! through limit updates, so that a consumer maintaining its own book only ever sees
+//! one consistent order book.
+//!
+//! All the prices and sizes are expressed in the ticks of the primary symbol. Values
+//! coming from the backup feed are converted, truncating extra decimals.
+
+mod test;
+#[cfg(feature = "network")]
+mod stream;
+
+#[cfg(feature = "network")]
+pub use self::stream::FailoverStream;
+
+use std::time::{Duration, Instant};
+use crate::api::{Notification, Trade};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+use crate::order_book::{OrderBook, LimitUpdate};
+use crate::tick::{Tick, TickUnit};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// One of the two feeds of a `Failover`.
+pub enum FeedSource {
+    /// The preferred feed.
+    Primary,
+
+    /// The feed used when the primary one is stale.
+    Backup,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// An event output by a `Failover`.
+pub enum FailoverEvent {
+    /// A notification from the active feed, converted to the primary symbol ticks.
+    Notification(Notification),
+
+    /// The active feed has changed. This event is followed by the limit updates
+    /// transitioning the order book to the one of the new active feed.
+    Switched(FeedSource),
+}
+
+fn convert(value: TickUnit, from: Tick, to: Tick) -> TickUnit {
+    (u128::from(value) * u128::from(to.ticks_per_unit()) / u128::from(from.ticks_per_unit()))
+        as TickUnit
+}
+
+struct Feed {
+    symbol: Symbol,
+
+    /// Order book in the ticks of `symbol`.
+    raw: OrderBook,
+
+    /// Order book in the ticks of the primary symbol.
+    book: OrderBook,
+
+    last_seen: Option<Instant>,
+}
+
+impl Feed {
+    fn new(symbol: Symbol) -> Self {
+        Feed {
+            symbol,
+            raw: OrderBook::new(),
+            book: OrderBook::new(),
+            last_seen: None,
+        }
+    }
+
+    /// Apply an update expressed in the ticks of `self.symbol`, return the resulting
+    /// update expressed in the ticks of `target`.
+    fn update(&mut self, update: LimitUpdate, target: Symbol) -> LimitUpdate {
+        let price_tick = (self.symbol.price_tick(), target.price_tick());
+        let size_tick = (self.symbol.size_tick(), target.size_tick());
+
+        let previous = self.raw.size_at_limit(update.side, update.price);
+        self.raw.update(update);
+
+        // Several raw levels may map to the same converted level.
+        let price = convert(update.price, price_tick.0, price_tick.1);
+        let size = self.book.size_at_limit(update.side, price)
+            - convert(previous, size_tick.0, size_tick.1)
+            + convert(update.size, size_tick.0, size_tick.1);
+
+        let update = LimitUpdate::new(price, size, update.side);
+        self.book.update(update);
+        update
+    }
+
+    fn trade(&self, trade: &Trade, target: Symbol) -> Trade {
+        Trade {
+            price: convert(trade.price, self.symbol.price_tick(), target.price_tick()),
+            size: convert(trade.size, self.symbol.size_tick(), target.size_tick()),
+            maker_side: trade.maker_side,
+        }
+    }
+}
+
+/// State machine switching between a primary and a backup market data feed.
+///
+/// It is fed with the notifications of both feeds and the current time, and does not
+/// perform any IO by itself: see `Failover::stream` for driving it from two streams.
+/// Only market data is relevant: notifications other than limit updates and trades
+/// are forwarded from the primary feed only.
+pub struct Failover {
+    primary: Feed,
+    backup: Feed,
+    stale_after: Duration,
+    active: FeedSource,
+    created: Instant,
+}
+
+impl Failover {
+    /// Return a new `Failover` between feeds of `primary` and `backup` symbols. The primary
+    /// feed is considered stale once nothing was received for `stale_after`.
+    pub fn new(primary: Symbol, backup: Symbol, stale_after: Duration) -> Self {
+        Failover {
+            primary: Feed::new(primary),
+            backup: Feed::new(backup),
+            stale_after,
+            active: FeedSource::Primary,
+            created: Instant::now(),
+        }
+    }
+
+    /// Return the feed notifications are currently forwarded from.
+    pub fn active(&self) -> FeedSource {
+        self.active
+    }
+
+    /// Return the order book of the active feed, in the ticks of the primary symbol.
+    pub fn order_book(&self) -> &OrderBook {
+        &self.feed(self.active).book
+    }
+
+    fn feed(&self, source: FeedSource) -> &Feed {
+        match source {
+            FeedSource::Primary => &self.primary,
+            FeedSource::Backup => &self.backup,
+        }
+    }
+
+    fn is_stale(&self, source: FeedSource, now: Instant) -> bool {
+        let last_seen = match (source, self.feed(source).last_seen) {
+            (_, Some(last_seen)) => last_seen,
+
+            // A backup feed which never sent anything cannot be switched to.
+            (FeedSource::Backup, None) => return true,
+            (FeedSource::Primary, None) => self.created,
+        };
+        now > last_seen + self.stale_after
+    }
+
+    fn switch(&mut self, source: FeedSource, events: &mut Vec<FailoverEvent>) {
+        let updates: Vec<_> = self.feed(self.active).book
+            .diff(&self.feed(source).book)
+            .map(|update| update.timestamped())
+            .collect();
+
+        self.active = source;
+        events.push(FailoverEvent::Switched(source));
+        if !updates.is_empty() {
+            events.push(FailoverEvent::Notification(Notification::LimitUpdates(updates)));
+        }
+    }
+
+    /// Process a notification received at time `now` from the given feed, return the
+    /// resulting events.
+    pub fn on_notification(&mut self, source: FeedSource, notification: Notification, now: Instant)
+        -> Vec<FailoverEvent>
+    {
+        let target = self.primary.symbol;
+        let feed = match source {
+            FeedSource::Primary => &mut self.primary,
+            FeedSource::Backup => &mut self.backup,
+        };
+        feed.last_seen = Some(now);
+
+        let notification = match notification {
+            Notification::LimitUpdates(updates) => {
+                let updates = updates.into_iter()
+                    .map(|update| {
+                        let timestamp = update.timestamp();
+                        feed.update(update.into_inner(), target).with_timestamp(timestamp)
+                    })
+                    .collect::<Vec<Timestamped<_>>>();
+                Some(Notification::LimitUpdates(updates))
+            }
+            Notification::Trade(trade) => {
+                let timestamp = trade.timestamp();
+                Some(Notification::Trade(feed.trade(&trade, target).with_timestamp(timestamp)))
+            }
+            other if source == FeedSource::Primary => Some(other),
+            _ => None,
+        };
+
+        let mut events = Vec::new();
+        let switched = match (source, self.active) {
+            // The primary feed is alive again.
+            (FeedSource::Primary, FeedSource::Backup) => true,
+            (FeedSource::Backup, FeedSource::Primary) => {
+                self.is_stale(FeedSource::Primary, now)
+            }
+            _ => false,
+        };
+        if switched {
+            self.switch(source, &mut events);
+        }
+
+        if source == self.active {
+            match notification {
+                // Already included in the transition to the new book.
+                Some(Notification::LimitUpdates(_)) if switched => (),
+                Some(notification) => events.push(FailoverEvent::Notification(notification)),
+                None => (),
+            }
+        }
+        events
+    }
+
+    /// Check whether the primary feed has become stale at time `now`, return the
+    /// resulting events. This should be called periodically, since a stale feed does
+    /// not send any notification.
+    pub fn check(&mut self, now: Instant) -> Vec<FailoverEvent> {
+        let mut events = Vec::new();
+        if self.active == FeedSource::Primary
+            && self.is_stale(FeedSource::Primary, now)
+            && !self.is_stale(FeedSource::Backup, now)
+        {
+            self.switch(FeedSource::Backup, &mut events);
+        }
+        events
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+use futures::prelude::*;
+use log::error;
+use crate::api::Notification;
+use crate::api::failover::{Failover, FailoverEvent, FeedSource};
+
+impl Failover {
+    /// Drive `self` from the notification streams of the primary and backup symbols. The
+    /// staleness of the primary feed is checked every `stale_after / 2`. The returned
+    /// stream must be run inside a tokio runtime, and ends when both feeds have ended.
+    pub fn stream<P, B>(self, primary: P, backup: B) -> FailoverStream<P, B>
+        where P: Stream<Item = Notification, Error = ()>,
+              B: Stream<Item = Notification, Error = ()>
+    {
+        use tokio::timer::Interval;
+
+        let interval = Interval::new_interval(self.stale_after / 2);
+        FailoverStream {
+            failover: self,
+            primary: Some(primary),
+            backup: Some(backup),
+            interval,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// A stream of `FailoverEvent`, see `Failover::stream`.
+pub struct FailoverStream<P, B> {
+    failover: Failover,
+    primary: Option<P>,
+    backup: Option<B>,
+    interval: tokio::timer::Interval,
+    pending: VecDeque<FailoverEvent>,
+}
+
+impl<P, B> FailoverStream<P, B> {
+    /// Return the underlying `Failover`.
+    pub fn failover(&self) -> &Failover {
+        &self.failover
+    }
+}
+
+impl<P, B> Stream for FailoverStream<P, B>
+    where P: Stream<Item = Notification, Error = ()>,
+          B: Stream<Item = Notification, Error = ()>
+{
+    type Item = FailoverEvent;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<FailoverEvent>, ()> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Async::Ready(Some(event)));
+            }
+
+            let mut progress = false;
+
+            if let Some(primary) = self.primary.as_mut() {
+                match primary.poll()? {
+                    Async::Ready(Some(notif)) => {
+                        let events = self.failover.on_notification(
+                            FeedSource::Primary,
+                            notif,
+                            Instant::now()
+                        );
+                        self.pending.extend(events);
+                        progress = true;
+                    }
+                    Async::Ready(None) => self.primary = None,
+                    Async::NotReady => (),
+                }
+            }
+
+            if let Some(backup) = self.backup.as_mut() {
+                match backup.poll()? {
+                    Async::Ready(Some(notif)) => {
+                        let events = self.failover.on_notification(
+                            FeedSource::Backup,
+                            notif,
+                            Instant::now()
+                        );
+                        self.pending.extend(events);
+                        progress = true;
+                    }
+                    Async::Ready(None) => self.backup = None,
+                    Async::NotReady => (),
+                }
+            }
+
+            match self.interval.poll() {
+                Ok(Async::Ready(_)) => {
+                    self.pending.extend(self.failover.check(Instant::now()));
+                    progress = true;
+                }
+                Ok(Async::NotReady) => (),
+                Err(err) => error!("failover timer encountered error: `{}`", err),
+            }
+
+            if self.pending.is_empty() {
+                if self.primary.is_none() && self.backup.is_none() {
+                    return Ok(Async::Ready(None));
+                }
+                if !progress {
+                    return Ok(Async::NotReady);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+#![cfg(test)]
+
+use std::time::{Duration, Instant};
+use crate::Side;
+use crate::api::{Notification, Trade};
+use crate::api::failover::{Failover, FailoverEvent, FeedSource};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::order_book::LimitUpdate;
+use crate::tick::{Tick, TickUnit};
+
+fn updates(updates: &[(TickUnit, TickUnit, Side)]) -> Notification {
+    Notification::LimitUpdates(
+        updates.iter()
+            .map(|&(price, size, side)| LimitUpdate::new(price, size, side).with_timestamp(0))
+            .collect()
+    )
+}
+
+fn trade(price: TickUnit, size: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size,
+        maker_side: Side::Bid,
+    }.with_timestamp(0))
+}
+
+/// Transitions between books are timestamped with the current time, reset it.
+fn reset_timestamps(events: Vec<FailoverEvent>) -> Vec<FailoverEvent> {
+    events.into_iter()
+        .map(|event| match event {
+            FailoverEvent::Notification(Notification::LimitUpdates(updates)) => {
+                FailoverEvent::Notification(Notification::LimitUpdates(
+                    updates.into_iter()
+                        .map(|update| update.into_inner().with_timestamp(0))
+                        .collect()
+                ))
+            }
+            other => other,
+        })
+        .collect()
+}
+
+fn failover() -> Failover {
+    // Backup prices have one more decimal, backup sizes have the same decimals.
+    let primary = Symbol::new("BTC-USD", Tick::new(100), Tick::new(100)).unwrap();
+    let backup = Symbol::new("BTCUSDT", Tick::new(1000), Tick::new(100)).unwrap();
+    Failover::new(primary, backup, Duration::from_secs(1))
+}
+
+#[test]
+fn failover_and_back() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut failover = failover();
+
+    let events = failover.on_notification(
+        FeedSource::Primary,
+        updates(&[(100, 5, Side::Bid), (101, 5, Side::Ask)]),
+        at(10)
+    );
+    assert_eq!(
+        events,
+        vec![FailoverEvent::Notification(updates(&[(100, 5, Side::Bid), (101, 5, Side::Ask)]))]
+    );
+
+    // Backup levels `1001` and `1005` both map to `100`.
+    let events = failover.on_notification(
+        FeedSource::Backup,
+        updates(&[(1001, 2, Side::Bid), (1005, 3, Side::Bid), (1010, 4, Side::Ask)]),
+        at(20)
+    );
+    assert!(events.is_empty());
+    assert!(failover.check(at(500)).is_empty());
+
+    assert!(failover.on_notification(FeedSource::Backup, trade(1003, 1), at(1000)).is_empty());
+    assert_eq!(
+        reset_timestamps(failover.on_notification(FeedSource::Backup, trade(1003, 1), at(1100))),
+        vec![
+            FailoverEvent::Switched(FeedSource::Backup),
+            FailoverEvent::Notification(updates(&[(101, 4, Side::Ask)])),
+            FailoverEvent::Notification(trade(100, 1)),
+        ]
+    );
+    assert_eq!(failover.active(), FeedSource::Backup);
+
+    let events = failover.on_notification(
+        FeedSource::Backup,
+        updates(&[(1005, 0, Side::Bid)]),
+        at(1300)
+    );
+    assert_eq!(events, vec![FailoverEvent::Notification(updates(&[(100, 2, Side::Bid)]))]);
+
+    let events = failover.on_notification(FeedSource::Primary, trade(100, 1), at(1400));
+    assert_eq!(
+        reset_timestamps(events),
+        vec![
+            FailoverEvent::Switched(FeedSource::Primary),
+            FailoverEvent::Notification(updates(&[(100, 5, Side::Bid), (101, 5, Side::Ask)])),
+            FailoverEvent::Notification(trade(100, 1)),
+        ]
+    );
+}
+
+#[test]
+fn check() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut failover = failover();
+
+    // The backup feed never sent anything.
+    assert!(failover.check(at(5000)).is_empty());
+
+    failover.on_notification(FeedSource::Primary, trade(100, 1), at(5000));
+    failover.on_notification(FeedSource::Backup, trade(1000, 1), at(5900));
+    assert!(failover.check(at(6000)).is_empty());
+    assert_eq!(failover.check(at(6100)), vec![FailoverEvent::Switched(FeedSource::Backup)]);
+
+    // Other notifications are only forwarded from the primary feed.
+    let events = failover.on_notification(
+        FeedSource::Backup,
+        Notification::OrderExpiration(crate::api::OrderExpiration {
+            order_id: "a".to_owned(),
+            filled_size: None,
+            remaining_size: None,
+        }.timestamped()),
+        at(5600)
+    );
+    assert!(events.is_empty());
+}
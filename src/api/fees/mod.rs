@@ -0,0 +1,156 @@
+//! A module defining the fee schedules of the exchanges.
+//!
+//! Fees are usually tiered by the trading volume of the account over a trailing
+//! period, with a distinct rate for the liquidity maker and the liquidity taker. Some
+//! venues pay a rebate to makers instead of charging them, which is represented by a
+//! negative rate.
+//!
+//! Rates are expressed in millionths of the notional of a trade, e.g. a rate of `1000`
+//! is a fee of 0.1%, and a rate of `-25` a rebate of 0.0025%. Volumes and fees are
+//! expressed in units of the notional, i.e. the product of a price and a size in
+//! tick units.
+
+mod test;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::tick::TickUnit;
+
+/// Denominator of the fee rates.
+pub const RATE_DENOMINATOR: i64 = 1000000;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Which side of a trade an order was on.
+pub enum Liquidity {
+    /// The order was resting in the book.
+    Maker,
+
+    /// The order traded against a resting order.
+    Taker,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Maker and taker fee rates, in millionths of the notional. A negative rate is a
+/// rebate.
+pub struct FeeRates {
+    /// Rate applied to the liquidity maker.
+    pub maker: i64,
+
+    /// Rate applied to the liquidity taker.
+    pub taker: i64,
+}
+
+impl FeeRates {
+    /// Return the given maker and taker rates.
+    pub fn new(maker: i64, taker: i64) -> Self {
+        FeeRates {
+            maker,
+            taker,
+        }
+    }
+
+    /// Rate applied to the given side of a trade.
+    pub fn rate(&self, liquidity: Liquidity) -> i64 {
+        match liquidity {
+            Liquidity::Maker => self.maker,
+            Liquidity::Taker => self.taker,
+        }
+    }
+
+    /// Fee for a trade of the given notional, positive if charged, negative if paid
+    /// as a rebate. Fees are rounded up and rebates are rounded down, as exchanges
+    /// do not round in favor of the account.
+    pub fn fee(&self, liquidity: Liquidity, notional: TickUnit) -> i64 {
+        let amount = i128::from(notional) * i128::from(self.rate(liquidity));
+        let denominator = i128::from(RATE_DENOMINATOR);
+
+        // The division rounds toward zero, i.e. rebates down.
+        let fee = if amount > 0 && amount % denominator != 0 {
+            amount / denominator + 1
+        } else {
+            amount / denominator
+        };
+        let fee = std::cmp::min(fee, i128::from(i64::max_value()));
+        std::cmp::max(fee, i128::from(i64::min_value())) as i64
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A tier of a fee schedule.
+pub struct FeeTier {
+    /// Trading volume from which the tier applies, in units of the notional.
+    pub min_volume: TickUnit,
+
+    /// Rates of the tier.
+    pub rates: FeeRates,
+}
+
+impl FeeTier {
+    /// Return a tier applying from `min_volume` with the given rates.
+    pub fn new(min_volume: TickUnit, rates: FeeRates) -> Self {
+        FeeTier {
+            min_volume,
+            rates,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// The fee schedule of an exchange, made of tiers by trading volume.
+pub struct FeeSchedule {
+    /// Sorted by `min_volume`, the first one starting at a zero volume.
+    tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    /// Return a schedule with the given tiers, in any order. If no tier starts at a
+    /// zero volume, the lowest one is extended down to zero.
+    ///
+    /// # Panics
+    /// Panic if `tiers` is empty.
+    pub fn new(mut tiers: Vec<FeeTier>) -> Self {
+        assert!(!tiers.is_empty(), "a fee schedule needs at least one tier");
+
+        tiers.sort_by_key(|tier| tier.min_volume);
+        tiers.dedup_by_key(|tier| tier.min_volume);
+        tiers[0].min_volume = 0;
+        FeeSchedule {
+            tiers,
+        }
+    }
+
+    /// Return a schedule applying the same rates regardless of the volume.
+    pub fn flat(rates: FeeRates) -> Self {
+        FeeSchedule::new(vec![FeeTier::new(0, rates)])
+    }
+
+    /// Return a schedule charging no fees.
+    pub fn zero() -> Self {
+        FeeSchedule::flat(FeeRates::default())
+    }
+
+    /// Tiers of the schedule, sorted by increasing volume.
+    pub fn tiers(&self) -> &[FeeTier] {
+        &self.tiers
+    }
+
+    /// Rates applying to an account having traded the given volume.
+    pub fn rates(&self, volume: TickUnit) -> FeeRates {
+        self.tiers.iter()
+            .take_while(|tier| tier.min_volume <= volume)
+            .last()
+            .map(|tier| tier.rates)
+            .unwrap_or_default()
+    }
+
+    /// Fee for a trade of the given notional, for an account having traded the given
+    /// volume beforehand. See `FeeRates::fee`.
+    pub fn fee(&self, volume: TickUnit, liquidity: Liquidity, notional: TickUnit) -> i64 {
+        self.rates(volume).fee(liquidity, notional)
+    }
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        FeeSchedule::zero()
+    }
+}
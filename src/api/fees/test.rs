@@ -0,0 +1,47 @@
+#![cfg(test)]
+
+use crate::api::fees::*;
+
+fn schedule() -> FeeSchedule {
+    FeeSchedule::new(vec![
+        FeeTier::new(1000000, FeeRates::new(-25, 400)),
+        FeeTier::new(0, FeeRates::new(1000, 1000)),
+        FeeTier::new(100000, FeeRates::new(500, 800)),
+    ])
+}
+
+#[test]
+fn tiers_by_volume() {
+    let schedule = schedule();
+    assert_eq!(
+        schedule.tiers().iter().map(|tier| tier.min_volume).collect::<Vec<_>>(),
+        vec![0, 100000, 1000000]
+    );
+
+    assert_eq!(schedule.rates(0), FeeRates::new(1000, 1000));
+    assert_eq!(schedule.rates(99999), FeeRates::new(1000, 1000));
+    assert_eq!(schedule.rates(100000), FeeRates::new(500, 800));
+    assert_eq!(schedule.rates(5000000), FeeRates::new(-25, 400));
+}
+
+#[test]
+fn lowest_tier_starts_at_zero() {
+    let schedule = FeeSchedule::new(vec![FeeTier::new(500, FeeRates::new(10, 20))]);
+    assert_eq!(schedule.tiers()[0].min_volume, 0);
+    assert_eq!(schedule.rates(0), FeeRates::new(10, 20));
+}
+
+#[test]
+fn rounding() {
+    let rates = FeeRates::new(-25, 1000);
+
+    // 0.1% of 1500 is 1.5, rounded up.
+    assert_eq!(rates.fee(Liquidity::Taker, 1500), 2);
+    assert_eq!(rates.fee(Liquidity::Taker, 1000000), 1000);
+
+    // 0.0025% of 100000 is 2.5, rounded down.
+    assert_eq!(rates.fee(Liquidity::Maker, 100000), -2);
+    assert_eq!(rates.fee(Liquidity::Maker, 1000), 0);
+
+    assert_eq!(FeeSchedule::zero().fee(1000000, Liquidity::Taker, 1000000), 0);
+}
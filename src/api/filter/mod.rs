@@ -0,0 +1,191 @@
+//! A module defining a declarative filter of the notifications of a stream, see
+//! `NotificationStream::set_filter`.
+//!
+//! The filter is evaluated by the connection before a notification is queued, so that
+//! the consumer of a stream is not woken up by notifications it would discard anyway,
+//! e.g. an alerting service only watching large trades. Streams are opened per
+//! symbol, hence the symbol is selected by the choice of the stream itself.
+//!
+//! Limiting the depth of the book requires knowing the levels which are not
+//! forwarded: the full book is thus maintained along with the levels forwarded so
+//! far, and each batch of limit updates is turned into the updates bringing the
+//! consumer's copy of the book to the best levels of the full book.
+
+mod test;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::Notification;
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Conditions which notifications must meet for being forwarded. The default filter
+/// forwards every notification.
+pub struct NotificationFilter {
+    /// Only forward the book levels and the trades of this side, i.e. the side of the
+    /// maker for trades. Orders are forwarded whatever their side.
+    pub side: Option<Side>,
+
+    /// Trades of a smaller size, in ticks, are dropped.
+    pub min_trade_size: TickUnit,
+
+    /// Only forward the `book_depth` best levels of each side of the book.
+    pub book_depth: Option<usize>,
+
+    /// Only forward the orders whose id starts with this prefix, e.g. the orders placed
+    /// by one process on an account shared with others.
+    pub order_id_prefix: Option<String>,
+}
+
+impl NotificationFilter {
+    fn forwards_side(&self, side: Side) -> bool {
+        self.side.map(|s| s == side).unwrap_or(true)
+    }
+
+    fn forwards_order(&self, order_id: &str) -> bool {
+        self.order_id_prefix.as_ref().map(|prefix| order_id.starts_with(prefix.as_str()))
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+/// A `NotificationFilter` along with the state needed for applying it.
+crate struct FilterState {
+    filter: NotificationFilter,
+
+    /// Full order book, as sent by the exchange.
+    book: OrderBook,
+
+    /// Levels forwarded so far, i.e. the consumer's copy of the book.
+    forwarded: OrderBook,
+
+    /// Set when the filter changes, so that the forwarded levels are rebuilt with the
+    /// next limit updates.
+    resync: bool,
+
+    /// Number of notifications dropped.
+    filtered: usize,
+}
+
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+impl FilterState {
+    crate fn new() -> Self {
+        FilterState::default()
+    }
+
+    crate fn filter(&self) -> &NotificationFilter {
+        &self.filter
+    }
+
+    /// Replace the filter. The forwarded levels of the book are brought in line with
+    /// the new filter by the next limit updates.
+    crate fn set_filter(&mut self, filter: NotificationFilter) {
+        self.resync = self.resync || filter.side != self.filter.side
+            || filter.book_depth != self.filter.book_depth;
+        self.filter = filter;
+    }
+
+    /// Forget the book, which is about to be sent again from a fresh snapshot.
+    crate fn reset_book(&mut self) {
+        self.book = OrderBook::new();
+        self.forwarded = OrderBook::new();
+        self.resync = false;
+    }
+
+    /// Number of notifications dropped so far.
+    crate fn filtered(&self) -> usize {
+        self.filtered
+    }
+
+    /// Return the best levels of the full book which are to be forwarded.
+    fn target(&self) -> OrderBook {
+        let depth = self.filter.book_depth.unwrap_or(usize::max_value());
+        let mut target = OrderBook::new();
+
+        if self.filter.forwards_side(Side::Bid) {
+            for (price, size) in self.book.bid().take(depth) {
+                target.update(LimitUpdate::new(*price, *size, Side::Bid));
+            }
+        }
+        if self.filter.forwards_side(Side::Ask) {
+            for (price, size) in self.book.ask().take(depth) {
+                target.update(LimitUpdate::new(*price, *size, Side::Ask));
+            }
+        }
+        target
+    }
+
+    fn apply_limit_updates(&mut self, updates: Vec<Timestamped<LimitUpdate>>)
+        -> Vec<Timestamped<LimitUpdate>>
+    {
+        for update in &updates {
+            self.book.update(**update);
+        }
+
+        // Without a depth, the updates of the selected sides are forwarded as is.
+        if self.filter.book_depth.is_none() && !self.resync {
+            let filter = &self.filter;
+            let updates: Vec<_> = updates.into_iter()
+                .filter(|update| filter.forwards_side(update.side))
+                .collect();
+            for update in &updates {
+                self.forwarded.update(**update);
+            }
+            return updates;
+        }
+        self.resync = false;
+
+        // The rebuilt levels are stamped with the time of the last update.
+        let timestamp = match updates.last() {
+            Some(update) => update.timestamp(),
+            None => return Vec::new(),
+        };
+
+        let forwarded: Vec<_> = self.forwarded.diff(&self.target()).collect();
+        for update in &forwarded {
+            self.forwarded.update(*update);
+        }
+        forwarded.into_iter().map(|update| update.with_timestamp(timestamp)).collect()
+    }
+
+    /// Return `notif` if it is to be forwarded. Limit updates may be rewritten, so as
+    /// to only forward the levels selected by the filter.
+    crate fn apply(&mut self, notif: Notification) -> Option<Notification> {
+        let notif = match notif {
+            Notification::LimitUpdates(updates) => {
+                let updates = self.apply_limit_updates(updates);
+                if updates.is_empty() {
+                    None
+                } else {
+                    Some(Notification::LimitUpdates(updates))
+                }
+            }
+
+            Notification::Trade(trade) => {
+                if trade.size >= self.filter.min_trade_size
+                    && self.filter.forwards_side(trade.maker_side)
+                {
+                    Some(Notification::Trade(trade))
+                } else {
+                    None
+                }
+            }
+
+            Notification::OrderConfirmation(ref order)
+                if !self.filter.forwards_order(&order.order_id) => None,
+            Notification::OrderUpdate(ref order)
+                if !self.filter.forwards_order(&order.order_id) => None,
+            Notification::OrderExpiration(ref order)
+                if !self.filter.forwards_order(&order.order_id) => None,
+
+            other => Some(other),
+        };
+
+        if notif.is_none() {
+            self.filtered += 1;
+        }
+        notif
+    }
+}
@@ -0,0 +1,163 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade, OrderUpdate};
+use crate::api::filter::{FilterState, NotificationFilter};
+use crate::api::timestamp::IntoTimestamped;
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn trade(size: TickUnit, maker_side: Side) -> Notification {
+    Notification::Trade(Trade {
+        price: 100,
+        size,
+        maker_side,
+    }.with_timestamp(0))
+}
+
+fn update(order_id: &str) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: 1,
+        remaining_size: 0,
+        consumed_price: 100,
+        commission: 0,
+    }.with_timestamp(0))
+}
+
+fn limits(updates: &[(TickUnit, TickUnit, Side)]) -> Notification {
+    Notification::LimitUpdates(
+        updates.iter()
+            .map(|(price, size, side)| LimitUpdate::new(*price, *size, *side).with_timestamp(0))
+            .collect()
+    )
+}
+
+/// Return the forwarded limit updates, sorted.
+fn forwarded(notif: Option<Notification>) -> Vec<(TickUnit, TickUnit, Side)> {
+    let mut updates: Vec<_> = match notif {
+        Some(Notification::LimitUpdates(updates)) => {
+            updates.iter().map(|update| (update.price, update.size, update.side)).collect()
+        }
+        None => Vec::new(),
+        other => panic!("unexpected notification: {:?}", other),
+    };
+    updates.sort_by_key(|(price, _, _)| *price);
+    updates
+}
+
+#[test]
+fn default_forwards_everything() {
+    let mut state = FilterState::new();
+    let notifs = vec![
+        trade(1, Side::Ask),
+        update("a"),
+        limits(&[(99, 1, Side::Bid), (101, 1, Side::Ask)]),
+    ];
+
+    for notif in notifs {
+        assert_eq!(state.apply(notif.clone()), Some(notif));
+    }
+    assert_eq!(state.filtered(), 0);
+}
+
+#[test]
+fn trades() {
+    let mut state = FilterState::new();
+    state.set_filter(NotificationFilter {
+        side: Some(Side::Bid),
+        min_trade_size: 10,
+        ..NotificationFilter::default()
+    });
+
+    assert!(state.apply(trade(10, Side::Bid)).is_some());
+    assert!(state.apply(trade(9, Side::Bid)).is_none());
+    assert!(state.apply(trade(10, Side::Ask)).is_none());
+    assert_eq!(state.filtered(), 2);
+}
+
+#[test]
+fn orders() {
+    let mut state = FilterState::new();
+    state.set_filter(NotificationFilter {
+        order_id_prefix: Some("mm-".to_owned()),
+        ..NotificationFilter::default()
+    });
+
+    assert!(state.apply(update("mm-1")).is_some());
+    assert!(state.apply(update("arb-1")).is_none());
+}
+
+#[test]
+fn book_side() {
+    let mut state = FilterState::new();
+    state.set_filter(NotificationFilter {
+        side: Some(Side::Ask),
+        ..NotificationFilter::default()
+    });
+
+    assert_eq!(
+        forwarded(state.apply(limits(&[(99, 1, Side::Bid), (101, 1, Side::Ask)]))),
+        vec![(101, 1, Side::Ask)]
+    );
+    assert_eq!(forwarded(state.apply(limits(&[(98, 1, Side::Bid)]))), vec![]);
+    assert_eq!(state.filtered(), 1);
+}
+
+#[test]
+fn book_depth() {
+    let mut state = FilterState::new();
+    state.set_filter(NotificationFilter {
+        book_depth: Some(2),
+        ..NotificationFilter::default()
+    });
+
+    let snapshot = limits(&[
+        (97, 3, Side::Bid),
+        (98, 2, Side::Bid),
+        (99, 1, Side::Bid),
+        (101, 1, Side::Ask),
+    ]);
+    assert_eq!(
+        forwarded(state.apply(snapshot)),
+        vec![(98, 2, Side::Bid), (99, 1, Side::Bid), (101, 1, Side::Ask)]
+    );
+
+    // Updates below the forwarded levels are dropped.
+    assert_eq!(forwarded(state.apply(limits(&[(97, 5, Side::Bid)]))), vec![]);
+
+    // The third level moves up when the best one is removed.
+    assert_eq!(
+        forwarded(state.apply(limits(&[(99, 0, Side::Bid)]))),
+        vec![(97, 5, Side::Bid), (99, 0, Side::Bid)]
+    );
+
+    // A better level pushes the second one out.
+    assert_eq!(
+        forwarded(state.apply(limits(&[(100, 1, Side::Bid)]))),
+        vec![(97, 0, Side::Bid), (100, 1, Side::Bid)]
+    );
+}
+
+#[test]
+fn filter_change_resyncs_book() {
+    let mut state = FilterState::new();
+    state.set_filter(NotificationFilter {
+        book_depth: Some(1),
+        ..NotificationFilter::default()
+    });
+
+    state.apply(limits(&[(98, 2, Side::Bid), (99, 1, Side::Bid)]));
+
+    state.set_filter(NotificationFilter::default());
+    assert_eq!(
+        forwarded(state.apply(limits(&[(101, 1, Side::Ask)]))),
+        vec![(98, 2, Side::Bid), (101, 1, Side::Ask)]
+    );
+
+    // Back to forwarding updates as is.
+    assert_eq!(
+        forwarded(state.apply(limits(&[(97, 1, Side::Bid)]))),
+        vec![(97, 1, Side::Bid)]
+    );
+}
@@ -0,0 +1,214 @@
+//! A module defining fixtures of a backend: the REST responses and WebSocket frames
+//! received from a venue, usually its sandbox, while the conformance suite runs a
+//! scripted order lifecycle against it. Fixtures are replayed by a local server, so that
+//! the suite can be run again offline, e.g. in the tests of the backend, without any
+//! account on the venue.
+//!
+//! A fixture is recorded through a `RecordingProxy`, which forwards the requests and
+//! connections of the client to the venue, and replayed by a `ReplayServer`. The
+//! `record` and `replay` functions run the conformance suite through them.
+//!
+//! On replay, REST requests are matched by method, path and query, ignoring the
+//! parameters listed in `VOLATILE_PARAMS`: identical requests receive the recorded
+//! responses in order, the last one being repeated once they are exhausted. Each
+//! WebSocket connection receives the frames of the recorded connection to the same
+//! resource with the same rank, each frame being released once as many REST responses
+//! have been served as when it was recorded. The frames sent by the client are
+//! forwarded to the venue during the recording, but are neither recorded nor checked.
+//!
+//! Replay requires the requests to be the same from one run to the other, hence the
+//! suite is run with `Suite::with_order_id_seed`: backends generating random order ids
+//! whatever the hint cannot be replayed. Fixtures contain the responses regarding the
+//! account, but neither the headers of the requests nor the keys.
+//!
+//! # Example
+//! ```no_run
+//! # extern crate trade;
+//! # use std::sync::Arc;
+//! # use trade::prelude::*;
+//! # use trade::api::{Order, Params};
+//! # use trade::api::conformance::Suite;
+//! # use trade::api::fixtures::{self, Fixture};
+//! # use trade::api::testkit::Client;
+//! # fn main() -> Result<(), failure::Error> {
+//! let suite = |params| -> Result<_, failure::Error> {
+//!     let client = Arc::new(Client::new(params)?);
+//!     Ok(Suite::new(client, "BTCUSD", Order::new("0.01", "1", Side::Bid)))
+//! };
+//!
+//! let sandbox = Params {
+//!     streaming_endpoint: "wss://sandbox.example.com".to_owned(),
+//!     rest_endpoint: "https://sandbox.example.com".to_owned(),
+//! };
+//! let (fixture, report) = fixtures::record(sandbox, suite)?;
+//! assert!(report.is_success(), "{:?}", report);
+//! fixture.save("tests/fixtures/testkit.json")?;
+//!
+//! let fixture = Fixture::load("tests/fixtures/testkit.json")?;
+//! let report = fixtures::replay(fixture, suite)?;
+//! assert!(report.is_success(), "{:?}", report);
+//! # Ok(())
+//! # }
+//! ```
+
+mod record;
+mod replay;
+mod test;
+
+pub use self::record::RecordingProxy;
+pub use self::replay::ReplayServer;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{ApiClient, Params};
+use crate::api::conformance::{Report, Suite};
+
+/// Query parameters ignored when matching a request against the recorded ones, since
+/// they change from one run to the other.
+pub const VOLATILE_PARAMS: &[&str] = &[
+    "timestamp",
+    "signature",
+    "recvWindow",
+    "nonce",
+    "expires",
+    "sign",
+];
+
+/// Seed of the order ids of the conformance suite, see `Suite::with_order_id_seed`.
+pub const ORDER_ID_SEED: &str = "fixture";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// The exchanges of a client with a venue, see the module documentation.
+pub struct Fixture {
+    /// REST requests, in the order their responses were received.
+    pub requests: Vec<RecordedRequest>,
+
+    /// WebSocket connections, in the order they were opened.
+    pub connections: Vec<RecordedConnection>,
+
+    /// WebSocket frames received from the venue, in the order they were received.
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A REST request, along with its response.
+pub struct RecordedRequest {
+    /// HTTP method, e.g. `GET`.
+    pub method: String,
+
+    /// Path and query of the request, relative to the REST endpoint.
+    pub path: String,
+
+    /// HTTP status code of the response.
+    pub status: u16,
+
+    /// Body of the response.
+    pub body: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A WebSocket connection.
+pub struct RecordedConnection {
+    /// Requested resource, i.e. path and query relative to the streaming endpoint.
+    pub resource: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A WebSocket frame received from the venue.
+pub struct RecordedFrame {
+    /// Index of the connection in `Fixture::connections`.
+    pub connection: usize,
+
+    /// Number of REST responses received before the frame.
+    pub after: usize,
+
+    /// Content of the frame, base64 encoded for binary frames.
+    pub data: String,
+
+    /// Whether the frame is binary.
+    pub binary: bool,
+}
+
+impl RecordedFrame {
+    fn message(&self) -> Result<ws::Message, failure::Error> {
+        if self.binary {
+            Ok(ws::Message::Binary(base64::decode(&self.data)?))
+        } else {
+            Ok(ws::Message::Text(self.data.clone()))
+        }
+    }
+}
+
+impl Fixture {
+    /// Load a fixture saved with `Fixture::save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Save the fixture to `path` as pretty-printed JSON, so that changes are readable
+    /// in a diff.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), failure::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Return the key matching a request against the recorded ones: the method, path and
+/// query, without the `VOLATILE_PARAMS`.
+crate fn request_key(method: &str, path: &str) -> String {
+    let mut parts = path.splitn(2, '?');
+    let path = parts.next().unwrap_or("");
+    let query: Vec<_> = parts.next()
+        .unwrap_or("")
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or("");
+            !param.is_empty() && !VOLATILE_PARAMS.contains(&name)
+        })
+        .collect();
+
+    if query.is_empty() {
+        format!("{} {}", method, path)
+    } else {
+        format!("{} {}?{}", method, path, query.join("&"))
+    }
+}
+
+/// Run the conformance suite returned by `suite` through a `RecordingProxy` in front of
+/// the venue at `upstream`, and return the recorded fixture along with the report of
+/// the suite. `suite` is called with the endpoints of the proxy, to which the client
+/// must connect.
+pub fn record<C, F>(upstream: Params, suite: F) -> Result<(Fixture, Report), failure::Error>
+    where C: ApiClient + Send + Sync + 'static,
+          F: FnOnce(Params) -> Result<Suite<C>, failure::Error>
+{
+    let proxy = RecordingProxy::start(upstream)?;
+    let report = run(suite(proxy.params())?)?;
+    Ok((proxy.fixture(), report))
+}
+
+/// Run the conformance suite returned by `suite` against a `ReplayServer` serving
+/// `fixture`, and return its report. `suite` is called with the endpoints of the
+/// server, to which the client must connect.
+pub fn replay<C, F>(fixture: Fixture, suite: F) -> Result<Report, failure::Error>
+    where C: ApiClient + Send + Sync + 'static,
+          F: FnOnce(Params) -> Result<Suite<C>, failure::Error>
+{
+    let server = ReplayServer::start(fixture)?;
+    run(suite(server.params())?)
+}
+
+fn run<C>(suite: Suite<C>) -> Result<Report, failure::Error>
+    where C: ApiClient + Send + Sync + 'static
+{
+    let suite = suite.with_order_id_seed(ORDER_ID_SEED);
+    let report = tokio::runtime::Runtime::new()?
+        .block_on(suite.run())
+        .expect("the conformance suite never fails");
+    Ok(report)
+}
@@ -0,0 +1,283 @@
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use futures::prelude::*;
+use futures::future::{self, Either};
+use futures::sync::oneshot;
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::{ACCEPT_ENCODING, CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use hyper::service::service_fn;
+use log::{debug, warn, error};
+use crate::api::Params;
+use crate::api::fixtures::{Fixture, RecordedRequest, RecordedConnection, RecordedFrame};
+
+type HttpClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+/// A proxy listening on the loopback interface, forwarding the REST requests and the
+/// WebSocket connections of a client to a venue, and recording what it receives from
+/// the venue, see the module documentation.
+pub struct RecordingProxy {
+    params: Params,
+    fixture: Arc<Mutex<Fixture>>,
+    broadcaster: ws::Sender,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl RecordingProxy {
+    /// Start the HTTP and WebSocket servers of the proxy on ephemeral ports of the
+    /// loopback interface, each in its own thread, forwarding to the endpoints of
+    /// `upstream`. They are shut down when the returned proxy is dropped.
+    pub fn start(upstream: Params) -> Result<Self, failure::Error> {
+        let fixture = Arc::new(Mutex::new(Fixture::default()));
+
+        let ws_fixture = fixture.clone();
+        let streaming_endpoint = upstream.streaming_endpoint;
+        let socket = ws::WebSocket::new(move |out| {
+            Downstream {
+                out,
+                upstream_endpoint: streaming_endpoint.clone(),
+                fixture: ws_fixture.clone(),
+                upstream: Arc::new(Mutex::new(Upstream::Connecting(Vec::new()))),
+            }
+        })?.bind("127.0.0.1:0")?;
+        let ws_address = socket.local_addr()?;
+        let broadcaster = socket.broadcaster();
+
+        thread::spawn(move || {
+            if let Err(err) = socket.run() {
+                error!("recording proxy WebSocket server terminated with error: `{}`", err);
+            }
+        });
+
+        let http_client = hyper::Client::builder().build::<_, Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+        let http_fixture = fixture.clone();
+        let rest_endpoint = upstream.rest_endpoint;
+        let (shutdown, signal) = oneshot::channel();
+        let http_server = hyper::Server::try_bind(&([127, 0, 0, 1], 0).into())?
+            .serve(move || {
+                let http_client = http_client.clone();
+                let rest_endpoint = rest_endpoint.clone();
+                let fixture = http_fixture.clone();
+                service_fn(move |request| forward(&http_client, &rest_endpoint, &fixture, request))
+            });
+        let http_address = http_server.local_addr();
+        let http_server = http_server
+            .with_graceful_shutdown(signal.map_err(|_| ()))
+            .map_err(|err| error!("recording proxy HTTP server terminated with error: `{}`", err));
+
+        thread::spawn(move || hyper::rt::run(http_server));
+
+        debug!("recording proxy listening at {} and {}", http_address, ws_address);
+        Ok(RecordingProxy {
+            params: Params {
+                streaming_endpoint: format!("ws://{}", ws_address),
+                rest_endpoint: format!("http://{}", http_address),
+            },
+            fixture,
+            broadcaster,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Endpoints of the proxy.
+    pub fn params(&self) -> Params {
+        self.params.clone()
+    }
+
+    /// Return what has been recorded so far.
+    pub fn fixture(&self) -> Fixture {
+        self.fixture.lock().unwrap().clone()
+    }
+}
+
+impl Drop for RecordingProxy {
+    fn drop(&mut self) {
+        if let Err(err) = self.broadcaster.shutdown() {
+            warn!("failed to shut down the recording proxy WebSocket server: `{}`", err);
+        }
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn bad_gateway(message: String) -> Response<Body> {
+    // Unwrap because it is a bug if this fails (header failed to parse or something)
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(Body::from(message))
+        .unwrap()
+}
+
+/// Forward `request` to `rest_endpoint` and record the response.
+fn forward(
+    http_client: &HttpClient,
+    rest_endpoint: &str,
+    fixture: &Arc<Mutex<Fixture>>,
+    request: Request<Body>
+) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send
+{
+    let (parts, body) = request.into_parts();
+    let method = parts.method.to_string();
+    let path = parts.uri.path_and_query()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "/".to_owned());
+
+    let mut builder = Request::builder();
+    builder.method(parts.method.clone()).uri(format!("{}{}", rest_endpoint, path));
+    for (name, value) in &parts.headers {
+        // Ask for an uncompressed body, which can be recorded as text.
+        if name != HOST && name != ACCEPT_ENCODING {
+            builder.header(name, value.clone());
+        }
+    }
+
+    let request = match builder.body(body) {
+        Ok(request) => request,
+        Err(err) => return Either::A(future::ok(bad_gateway(err.to_string()))),
+    };
+
+    let fixture = fixture.clone();
+    let response = http_client.request(request).and_then(move |response| {
+        let (mut parts, body) = response.into_parts();
+        body.concat2().map(move |body| {
+            fixture.lock().unwrap().requests.push(RecordedRequest {
+                method,
+                path,
+                status: parts.status.as_u16(),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+
+            // The whole body is sent at once.
+            parts.headers.remove(CONTENT_LENGTH);
+            parts.headers.remove(TRANSFER_ENCODING);
+            Response::from_parts(parts, Body::from(body.to_vec()))
+        })
+    }).or_else(|err| Ok(bad_gateway(err.to_string())));
+
+    Either::B(response)
+}
+
+/// Connection to the venue of a proxied WebSocket connection.
+enum Upstream {
+    /// Messages sent by the client while the connection is being opened.
+    Connecting(Vec<ws::Message>),
+    Open(ws::Sender),
+    Closed,
+}
+
+/// Connection of a client to the WebSocket server of the proxy.
+struct Downstream {
+    out: ws::Sender,
+    upstream_endpoint: String,
+    fixture: Arc<Mutex<Fixture>>,
+    upstream: Arc<Mutex<Upstream>>,
+}
+
+impl ws::Handler for Downstream {
+    fn on_open(&mut self, shake: ws::Handshake) -> ws::Result<()> {
+        let resource = shake.request.resource().to_owned();
+        let address = if resource == "/" {
+            self.upstream_endpoint.clone()
+        } else {
+            format!("{}{}", self.upstream_endpoint, resource)
+        };
+
+        let connection = {
+            let mut fixture = self.fixture.lock().unwrap();
+            fixture.connections.push(RecordedConnection { resource });
+            fixture.connections.len() - 1
+        };
+
+        let downstream = self.out.clone();
+        let fixture = self.fixture.clone();
+        let upstream = self.upstream.clone();
+        thread::spawn(move || {
+            debug!("initiating upstream WebSocket connection at {}", address);
+
+            if let Err(err) = ws::connect(address, |out| {
+                UpstreamHandler {
+                    out,
+                    downstream: downstream.clone(),
+                    fixture: fixture.clone(),
+                    upstream: upstream.clone(),
+                    connection,
+                }
+            })
+            {
+                error!("upstream WebSocket connection terminated with error: `{}`", err);
+                let _ = downstream.close(ws::CloseCode::Away);
+            }
+        });
+        Ok(())
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        match &mut *self.upstream.lock().unwrap() {
+            Upstream::Connecting(pending) => pending.push(msg),
+            Upstream::Open(out) => out.send(msg)?,
+            Upstream::Closed => (),
+        }
+        Ok(())
+    }
+
+    fn on_close(&mut self, _: ws::CloseCode, _: &str) {
+        let upstream = mem::replace(&mut *self.upstream.lock().unwrap(), Upstream::Closed);
+        if let Upstream::Open(out) = upstream {
+            let _ = out.close(ws::CloseCode::Normal);
+        }
+    }
+}
+
+/// Connection of the proxy to the venue, on behalf of a `Downstream` connection.
+struct UpstreamHandler {
+    out: ws::Sender,
+    downstream: ws::Sender,
+    fixture: Arc<Mutex<Fixture>>,
+    upstream: Arc<Mutex<Upstream>>,
+    connection: usize,
+}
+
+impl ws::Handler for UpstreamHandler {
+    fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+        let mut upstream = self.upstream.lock().unwrap();
+        match mem::replace(&mut *upstream, Upstream::Open(self.out.clone())) {
+            Upstream::Connecting(pending) => {
+                for msg in pending {
+                    self.out.send(msg)?;
+                }
+            }
+            Upstream::Open(..) => (),
+            Upstream::Closed => {
+                *upstream = Upstream::Closed;
+                self.out.close(ws::CloseCode::Normal)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        {
+            let mut fixture = self.fixture.lock().unwrap();
+            let (data, binary) = match &msg {
+                ws::Message::Text(text) => (text.clone(), false),
+                ws::Message::Binary(data) => (base64::encode(data), true),
+            };
+            let after = fixture.requests.len();
+            fixture.frames.push(RecordedFrame {
+                connection: self.connection,
+                after,
+                data,
+                binary,
+            });
+        }
+        self.downstream.send(msg)
+    }
+
+    fn on_close(&mut self, _: ws::CloseCode, _: &str) {
+        *self.upstream.lock().unwrap() = Upstream::Closed;
+        let _ = self.downstream.close(ws::CloseCode::Normal);
+    }
+}
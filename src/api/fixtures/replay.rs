@@ -0,0 +1,263 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use futures::prelude::*;
+use futures::sync::oneshot;
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::service::service_fn;
+use log::{debug, warn, error};
+use crate::api::Params;
+use crate::api::fixtures::{request_key, Fixture, RecordedRequest, RecordedFrame};
+
+/// A server listening on the loopback interface, replaying a fixture to a client, see
+/// the module documentation.
+pub struct ReplayServer {
+    params: Params,
+    state: Arc<Mutex<State>>,
+    broadcaster: ws::Sender,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl ReplayServer {
+    /// Start the HTTP and WebSocket servers on ephemeral ports of the loopback
+    /// interface, each in its own thread. They are shut down when the returned server
+    /// is dropped.
+    pub fn start(fixture: Fixture) -> Result<Self, failure::Error> {
+        let mut responses = HashMap::new();
+        for request in fixture.requests {
+            responses.entry(request_key(&request.method, &request.path))
+                .or_insert_with(VecDeque::new)
+                .push_back(request);
+        }
+
+        let mut frames = vec![Vec::new(); fixture.connections.len()];
+        for frame in fixture.frames {
+            if let Some(connection) = frames.get_mut(frame.connection) {
+                connection.push(frame);
+            }
+        }
+
+        let state = Arc::new(Mutex::new(State {
+            responses,
+            last_responses: HashMap::new(),
+            served: 0,
+            unmatched: Vec::new(),
+            resources: fixture.connections.into_iter()
+                .map(|connection| connection.resource)
+                .collect(),
+            frames,
+            replayed: Vec::new(),
+            connections: HashMap::new(),
+        }));
+
+        let ws_state = state.clone();
+        let socket = ws::WebSocket::new(move |out| {
+            Connection {
+                out,
+                state: ws_state.clone(),
+            }
+        })?.bind("127.0.0.1:0")?;
+        let ws_address = socket.local_addr()?;
+        let broadcaster = socket.broadcaster();
+
+        thread::spawn(move || {
+            if let Err(err) = socket.run() {
+                error!("replay WebSocket server terminated with error: `{}`", err);
+            }
+        });
+
+        let http_state = state.clone();
+        let (shutdown, signal) = oneshot::channel();
+        let http_server = hyper::Server::try_bind(&([127, 0, 0, 1], 0).into())?
+            .serve(move || {
+                let state = http_state.clone();
+                service_fn(move |request| serve(&state, request))
+            });
+        let http_address = http_server.local_addr();
+        let http_server = http_server
+            .with_graceful_shutdown(signal.map_err(|_| ()))
+            .map_err(|err| error!("replay HTTP server terminated with error: `{}`", err));
+
+        thread::spawn(move || hyper::rt::run(http_server));
+
+        debug!("replay server listening at {} and {}", http_address, ws_address);
+        Ok(ReplayServer {
+            params: Params {
+                streaming_endpoint: format!("ws://{}", ws_address),
+                rest_endpoint: format!("http://{}", http_address),
+            },
+            state,
+            broadcaster,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Endpoints of the server.
+    pub fn params(&self) -> Params {
+        self.params.clone()
+    }
+
+    /// Requests received so far which did not match any recorded request, as
+    /// `METHOD path`. They were answered with an HTTP `404` status code.
+    pub fn unmatched(&self) -> Vec<String> {
+        self.state.lock().unwrap().unmatched.clone()
+    }
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        if let Err(err) = self.broadcaster.shutdown() {
+            warn!("failed to shut down the replay WebSocket server: `{}`", err);
+        }
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// A client connection replaying a recorded connection.
+struct Replayed {
+    out: ws::Sender,
+
+    /// Index of the recorded connection.
+    connection: usize,
+
+    /// Number of frames already sent.
+    sent: usize,
+}
+
+struct State {
+    /// request key => recorded requests not served yet
+    responses: HashMap<String, VecDeque<RecordedRequest>>,
+
+    /// request key => last recorded request served, repeated once the recorded
+    /// requests are exhausted
+    last_responses: HashMap<String, RecordedRequest>,
+
+    /// Number of responses served.
+    served: usize,
+
+    unmatched: Vec<String>,
+
+    /// Resource of each recorded connection.
+    resources: Vec<String>,
+
+    /// Frames of each recorded connection.
+    frames: Vec<Vec<RecordedFrame>>,
+
+    /// Recorded connections already replayed.
+    replayed: Vec<usize>,
+
+    /// connection id => replayed connection
+    connections: HashMap<u32, Replayed>,
+}
+
+impl State {
+    fn respond(&mut self, method: &str, path: &str) -> Option<RecordedRequest> {
+        let key = request_key(method, path);
+        let request = match self.responses.get_mut(&key).and_then(|queue| queue.pop_front()) {
+            Some(request) => {
+                self.last_responses.insert(key, request.clone());
+                request
+            }
+            None => match self.last_responses.get(&key) {
+                Some(request) => request.clone(),
+                None => {
+                    warn!("no recorded response for `{}`", key);
+                    self.unmatched.push(key);
+                    return None;
+                }
+            },
+        };
+
+        self.served += 1;
+        self.release();
+        Some(request)
+    }
+
+    /// Open a connection to `resource`, replaying the first recorded connection to the
+    /// same resource not replayed yet.
+    fn open(&mut self, out: ws::Sender, resource: &str) {
+        let replayed = &self.replayed;
+        let connection = self.resources.iter()
+            .enumerate()
+            .position(|(index, recorded)| recorded == resource && !replayed.contains(&index));
+
+        let connection = match connection {
+            Some(connection) => connection,
+            None => {
+                warn!("no recorded connection left for `{}`", resource);
+                return;
+            }
+        };
+
+        self.replayed.push(connection);
+        self.connections.insert(out.connection_id(), Replayed {
+            out,
+            connection,
+            sent: 0,
+        });
+        self.release();
+    }
+
+    /// Send the frames whose recorded responses have all been served.
+    fn release(&mut self) {
+        let served = self.served;
+        for replayed in self.connections.values_mut() {
+            let frames = &self.frames[replayed.connection][replayed.sent..];
+            for frame in frames.iter().take_while(|frame| frame.after <= served) {
+                let result = frame.message().and_then(|msg| Ok(replayed.out.send(msg)?));
+                if let Err(err) = result {
+                    error!("failed to replay frame: `{}`", err);
+                }
+                replayed.sent += 1;
+            }
+        }
+    }
+}
+
+fn serve(state: &Arc<Mutex<State>>, request: Request<Body>)
+    -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send
+{
+    let path = request.uri().path_and_query()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "/".to_owned());
+    let response = state.lock().unwrap().respond(request.method().as_str(), &path);
+
+    // Unwrap because it is a bug if this fails (header failed to parse or something)
+    let response = match response {
+        Some(request) => Response::builder()
+            .status(StatusCode::from_u16(request.status).unwrap_or(StatusCode::OK))
+            .header("Content-Type", "application/json")
+            .body(Body::from(request.body))
+            .unwrap(),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from(format!("no recorded response for `{}`", path)))
+            .unwrap(),
+    };
+
+    // Consume the body of the request, which is not matched.
+    request.into_body().concat2().map(move |_| response)
+}
+
+/// Connection of a client to the WebSocket server.
+struct Connection {
+    out: ws::Sender,
+    state: Arc<Mutex<State>>,
+}
+
+impl ws::Handler for Connection {
+    fn on_open(&mut self, shake: ws::Handshake) -> ws::Result<()> {
+        self.state.lock().unwrap().open(self.out.clone(), shake.request.resource());
+        Ok(())
+    }
+
+    fn on_message(&mut self, _: ws::Message) -> ws::Result<()> {
+        Ok(())
+    }
+
+    fn on_close(&mut self, _: ws::CloseCode, _: &str) {
+        self.state.lock().unwrap().connections.remove(&self.out.connection_id());
+    }
+}
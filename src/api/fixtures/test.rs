@@ -0,0 +1,58 @@
+#![cfg(test)]
+
+use std::sync::Arc;
+use std::time::Duration;
+use crate::Side;
+use crate::api::{Order, Params};
+use crate::api::conformance::Suite;
+use crate::api::fixtures::{self, request_key, Fixture};
+use crate::api::testkit::{Client, MockExchange};
+use crate::sim::LimitOrder;
+use crate::tick::Tick;
+
+const SYMBOL: &str = "BTCUSD";
+
+fn suite(params: Params) -> Result<Suite<Client>, failure::Error> {
+    let client = Arc::new(Client::new(params)?);
+    let order = Order::new(90_u64, 1_u64, Side::Bid);
+    Ok(Suite::new(client, SYMBOL, order)
+        .with_timeout(Duration::from_secs(5))
+        .with_settle_delay(Duration::from_millis(300)))
+}
+
+#[test]
+fn keys() {
+    assert_eq!(request_key("GET", "/api/v3/time"), "GET /api/v3/time");
+    assert_eq!(
+        request_key("POST", "/api/v3/order?symbol=BTCUSDT&timestamp=1&signature=abc"),
+        "POST /api/v3/order?symbol=BTCUSDT"
+    );
+    assert_eq!(
+        request_key("DELETE", "/order?timestamp=1&orderId=a&recvWindow=5000"),
+        "DELETE /order?orderId=a"
+    );
+}
+
+#[test]
+fn record_and_replay() {
+    let server = MockExchange::new()
+        .with_symbol(SYMBOL, Tick::new(100), Tick::new(1000))
+        .start()
+        .unwrap();
+    server.submit(SYMBOL, LimitOrder::new("ask".to_owned(), 1, Side::Ask, 110, 5));
+
+    let (fixture, report) = fixtures::record(server.params(), suite).unwrap();
+    assert!(report.is_success(), "{:?}", report);
+    assert!(!fixture.requests.is_empty());
+    assert!(!fixture.frames.is_empty());
+    drop(server);
+
+    let path = std::env::temp_dir().join(format!("trade-fixture-{}.json", std::process::id()));
+    fixture.save(&path).unwrap();
+    let loaded = Fixture::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, fixture);
+
+    let report = fixtures::replay(loaded, suite).unwrap();
+    assert!(report.is_success(), "{:?}", report);
+}
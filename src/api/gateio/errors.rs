@@ -0,0 +1,175 @@
+//! A module defining error types specific to Gate.io.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Gate.io REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Gate.io error label, e.g. `BALANCE_NOT_ENOUGH`: see API documentation.
+    pub label: Option<String>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl RestError {
+    fn has_label(&self, labels: &[&str]) -> bool {
+        match &self.label {
+            Some(label) => labels.contains(&label.as_str()),
+            None => false,
+        }
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest
+            && self.has_label(&["ORDER_NOT_FOUND", "ORDER_CLOSED", "ORDER_CANCELLED"])
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_label(&["BALANCE_NOT_ENOUGH"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, " (label = {})", label)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Gate.io reports errors with a `label` and a `message`.
+    pub(super) fn from_gateio_error(status: StatusCode, gateio_error: Option<(String, String)>)
+        -> Self
+    {
+        let kind = match &gateio_error {
+            Some((label, _)) => RestErrorKind::from_label(label)
+                .unwrap_or_else(|| RestErrorKind::from_status_code(status)),
+            None => RestErrorKind::from_status_code(status),
+        };
+
+        let (label, message) = match gateio_error {
+            Some((label, message)) => (Some(label), Some(message)),
+            None => (None, None),
+        };
+
+        RestError {
+            kind,
+            label,
+            message,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Gate.io error label to a Gate.io error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid api key or signature, expired request or missing permission.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Gate.io. See Gate.io API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Gate.io side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            // Rejected requests, along with an error `label`.
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        use self::RestErrorKind::*;
+        let kind = match label {
+            "INVALID_KEY" | "INVALID_SIGNATURE" | "REQUEST_EXPIRED" | "FORBIDDEN"
+                | "READ_ONLY" | "IP_FORBIDDEN" => Unauthorized,
+            "TOO_MANY_REQUESTS" => TooManyRequests,
+            "SERVER_ERROR" => InternalError,
+            "TOO_BUSY" => ServiceUnavailable,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
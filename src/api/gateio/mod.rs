@@ -0,0 +1,202 @@
+//! Implementation of `ApiClient` for the Gate.io (v4) API, trading spot pairs.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+/// Maximum length of a client order id, once stripped of the mandatory `t-` prefix.
+const MAX_ORDER_ID_LEN: usize = 28;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Gate.io key pair: api key + secret key.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+impl Keys {
+    /// Signed HTTP requests and the private channels of the WebSocket API both carry
+    /// the hex HMAC-SHA512 of some request data.
+    fn signature(&self, what: &str) -> String {
+        let mut signer = Signer::new(MessageDigest::sha512(), &self.secret_key).unwrap();
+        signer.update(what.as_bytes()).unwrap();
+        hex::encode(&signer.sign_to_vec().unwrap())
+    }
+}
+
+/// Client order ids are carried by the `text` field of the orders, which must start
+/// with `t-`: return the order id specified by the user, which defaults to the server
+/// order id in case it was left empty.
+fn order_id(server_order_id: &str, text: Option<&str>) -> String {
+    match text {
+        Some(text) if text.starts_with("t-") && text.len() > 2 => text[2..].to_owned(),
+        _ => server_order_id.to_owned(),
+    }
+}
+
+/// A Gate.io API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new Gate.io API client with given `params`, e.g. with the usual
+    /// `https://api.gateio.ws/api/v4` and `wss://api.gateio.ws/ws/v4/` endpoints. If
+    /// `key_pair` is not `None`, this will enable performing requests to the REST API
+    /// and will forward the order updates.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Gate.io.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+        debug!("requesting symbols");
+        let symbols = runtime.block_on(client.get_symbols())?;
+        debug!("received symbols");
+        client.symbols = symbols;
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Gateio
+    }
+
+    /// Pairs are found by their Gate.io name, e.g. `BTC_USDT`, case insensitively.
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_uppercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Client order ids are limited to 28 characters among alphanumerics, `_`, `-` and
+    /// `.` by Gate.io.
+    fn new_order_id(hint: &str) -> String {
+        let valid = hint.chars().all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+        if !hint.is_empty() && hint.len() <= MAX_ORDER_ID_LEN && valid {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            let mut order_id = Uuid::new_v4().simple().to_string();
+            order_id.truncate(MAX_ORDER_ID_LEN);
+            order_id
+        }
+    }
+}
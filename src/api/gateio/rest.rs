@@ -0,0 +1,366 @@
+use openssl::hash::{hash, MessageDigest};
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use std::collections::HashMap;
+use log::{warn, error};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::api::query_string::QueryString;
+use crate::api::gateio::{Client, order_id};
+use crate::api::gateio::errors::RestError;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioError {
+    label: String,
+    message: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GateioOrder<'a> {
+    text: &'a str,
+    currency_pair: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    account: &'a str,
+    side: &'a str,
+    amount: &'a str,
+    price: &'a str,
+    time_in_force: &'a str,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct GateioOrderAck<'a> {
+    id: &'a str,
+    text: Option<&'a str>,
+    status: &'a str,
+    finish_as: Option<&'a str>,
+    create_time_ms: f64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioTime {
+    server_time: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioAccount<'a> {
+    currency: &'a str,
+    available: &'a str,
+    locked: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioPair<'a> {
+    id: &'a str,
+    precision: u32,
+    amount_precision: u32,
+    trade_status: &'a str,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+/// Percent-encode `value` for use in a path or a query string.
+fn url_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+impl Client {
+    /// Signed requests carry the hex HMAC-SHA512 of the method, the path, the query
+    /// string, the hex SHA512 of the body and the timestamp in seconds, separated by
+    /// new lines.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let address = if query.is_empty() {
+            format!("{}/{}", self.params.rest_endpoint, endpoint)
+        } else {
+            format!("{}/{}?{}", self.params.rest_endpoint, endpoint, query)
+        };
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let path = address.parse::<hyper::Uri>()
+                .map(|uri| uri.path().to_owned())
+                .unwrap_or_default();
+            let timestamp = timestamp_ms() / 1000;
+            let body_hash = hex::encode(&hash(MessageDigest::sha512(), body.as_bytes()).unwrap());
+            let signature = keys.signature(&format!(
+                "{}\n{}\n{}\n{}\n{}",
+                method.as_str(),
+                path,
+                query,
+                body_hash,
+                timestamp
+            ));
+
+            request.header("KEY", keys.api_key.as_bytes())
+                .header("Timestamp", format!("{}", timestamp).as_bytes())
+                .header("SIGN", signature.as_bytes());
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Accept", &b"application/json"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if !status.is_success() {
+                let gateio_error = serde_json::from_slice(&body)
+                    .ok()
+                    .map(|error: GateioError| (error.label, error.message));
+                let error = RestError::from_gateio_error(status, gateio_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Gate.io");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Gate.io");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        // Post only orders are a time in force on Gate.io.
+        let time_in_force = match (order.type_, order.time_in_force) {
+            (OrderType::Limit, TimeInForce::GoodTilCanceled) => "gtc",
+            (OrderType::Limit, TimeInForce::ImmediateOrCancel) => "ioc",
+            (OrderType::Limit, TimeInForce::FillOrKilll) => "fok",
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => "poc",
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by Gate.io",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // Orders are tracked by their `text`, which is always sent.
+        let text = match &order.order_id {
+            Some(order_id) => format!("t-{}", order_id),
+            None => format!("t-{}", Self::new_order_id("")),
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let body = GateioOrder {
+            text: &text,
+            currency_pair: symbol.name(),
+            type_: "limit",
+            account: "spot",
+            side: order.side.as_str(),
+            amount: size.borrow(),
+            price: price.borrow(),
+            time_in_force,
+        };
+        let body = match serde_json::to_string(&body) {
+            Ok(body) => body,
+            Err(err) => panic!("failed to serialize `GateioOrder`: `{}`", err),
+        };
+
+        let fut = self.request("spot/orders", Method::POST, QueryString::new(), body)
+            .and_then(|body| {
+                let ack: GateioOrderAck<'_> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                // Post only orders which would take liquidity are canceled right away.
+                if ack.status == "cancelled" && ack.finish_as == Some("poc") {
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::Specific(
+                            api::errors::OrderErrorKind::WouldTakeLiquidity
+                        ).into()
+                    ));
+                }
+
+                Ok(OrderAck {
+                    order_id: order_id(ack.id, ack.text),
+                }.with_timestamp(ack.create_time_ms as Timestamp))
+            });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("currency_pair", cancel.symbol().name());
+
+        // Orders can be canceled by their `text`.
+        let endpoint = format!("spot/orders/{}", url_encode(&format!("t-{}", cancel.order_id)));
+        self.request(&endpoint, Method::DELETE, query, String::new()).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("spot/time", Method::GET, QueryString::new(), String::new()).and_then(|body| {
+            let time: GateioTime = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(time.server_time))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("spot/accounts", Method::GET, QueryString::new(), String::new())
+            .and_then(|body| {
+                let accounts: Vec<GateioAccount<'_>> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                let balances = accounts.into_iter().map(|account| {
+                    (account.currency.to_owned(), Balance {
+                        free: account.available.to_owned(),
+                        locked: account.locked.to_owned(),
+                    })
+                }).collect();
+                Ok(balances)
+            })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        // Gate.io has no status endpoint, being able to reach it is the best we can do.
+        self.request("spot/time", Method::GET, QueryString::new(), String::new()).and_then(|_| {
+            Ok(ExchangeStatus::Normal.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("spot/currency_pairs", Method::GET, QueryString::new(), String::new())
+            .and_then(|body| {
+                let pairs: Vec<GateioPair<'_>> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                let mut symbols = HashMap::new();
+                for pair in pairs {
+                    if pair.trade_status != "tradable" {
+                        continue;
+                    }
+
+                    // Precisions are numbers of decimals.
+                    let (price_tick, size_tick) = match (
+                        10u64.checked_pow(pair.precision),
+                        10u64.checked_pow(pair.amount_precision)
+                    ) {
+                        (Some(price_tick), Some(size_tick)) => {
+                            (Tick::new(price_tick), Tick::new(size_tick))
+                        }
+                        _ => {
+                            error!("cannot read ticks for symbol `{}`", pair.id);
+                            continue;
+                        }
+                    };
+
+                    match Symbol::new(pair.id, price_tick, size_tick) {
+                        Some(symbol) => {
+                            symbols.insert(pair.id.to_uppercase(), symbol);
+                        }
+                        None => error!("symbol name too long: `{}`", pair.id),
+                    }
+                }
+                Ok(symbols)
+            })
+    }
+}
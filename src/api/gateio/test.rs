@@ -0,0 +1,59 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::gateio::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC_USDT", Tick::new(100), Tick::new(10_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let subscribed = r#"{"time":1606292218,"time_ms":1606292218100,"channel":"spot.order_book",
+        "event":"subscribe","error":null,"result":{"status":"success"}}"#;
+    let snapshot = r#"{"time":1606292218,"time_ms":1606292218231,"channel":"spot.order_book",
+        "event":"update","result":{"t":1606292218213,"lastUpdateId":48791820,"s":"BTC_USDT",
+        "bids":[["19079.55","0.0195"],["19079.07","0.7341"]],
+        "asks":[["19080.24","0.1638"],["19080.91","0.1366"]]}}"#;
+
+    // Only the changes of the top of the book are forwarded.
+    let update = r#"{"time":1606292218,"time_ms":1606292218331,"channel":"spot.order_book",
+        "event":"update","result":{"t":1606292218313,"lastUpdateId":48791821,"s":"BTC_USDT",
+        "bids":[["19079.55","0.0195"],["19079.07","0.5"]],
+        "asks":[["19080.91","0.1366"],["19081.00","0.01"]]}}"#;
+
+    let notifs = parse(&[subscribed, snapshot, update]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(1_908_024, 1638, Side::Ask).with_timestamp(1_606_292_218_213),
+            LimitUpdate::new(1_908_091, 1366, Side::Ask).with_timestamp(1_606_292_218_213),
+            LimitUpdate::new(1_907_955, 195, Side::Bid).with_timestamp(1_606_292_218_213),
+            LimitUpdate::new(1_907_907, 7341, Side::Bid).with_timestamp(1_606_292_218_213),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(1_908_024, 0, Side::Ask).with_timestamp(1_606_292_218_313),
+            LimitUpdate::new(1_908_100, 100, Side::Ask).with_timestamp(1_606_292_218_313),
+            LimitUpdate::new(1_907_907, 5000, Side::Bid).with_timestamp(1_606_292_218_313),
+        ]),
+    ]);
+}
@@ -0,0 +1,456 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::collections::HashMap;
+use std::{mem, thread};
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::gateio::{Client, Keys, order_id};
+
+/// Depth of the subscribed order book.
+const BOOK_DEPTH: &str = "20";
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let endpoint = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", endpoint);
+
+            if let Err(err) = ws::connect(endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        out: None,
+                        order_book: OrderBook::new(),
+                        orders: HashMap::new(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Public and private channels are subscribed to over the same connection, each
+/// private subscription carrying its own signature.
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Set once the connection is open.
+    out: Option<ws::Sender>,
+
+    /// Local copy of the order book: Gate.io sends the top levels of the book each
+    /// time, only the changes are forwarded.
+    order_book: OrderBook,
+
+    /// server order id => state of the order as of its last update: `spot.orders` only
+    /// carries the cumulated fills, individual fills are recovered from the changes.
+    orders: HashMap<String, OrderState>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct OrderState {
+    left: TickUnit,
+    filled_total: f64,
+    fee: f64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GateioAuth<'a> {
+    method: &'a str,
+    #[serde(rename = "KEY")]
+    key: &'a str,
+    #[serde(rename = "SIGN")]
+    sign: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GateioRequest<'a> {
+    time: u64,
+    channel: &'a str,
+    event: &'a str,
+    payload: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth: Option<GateioAuth<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioErrorData {
+    code: i64,
+    message: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioEvent<'a> {
+    channel: &'a str,
+    event: &'a str,
+    error: Option<GateioErrorData>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct GateioMessage<T> {
+    result: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioBook<'a> {
+    t: Timestamp,
+    #[serde(borrow)]
+    bids: Vec<(&'a str, &'a str)>,
+    #[serde(borrow)]
+    asks: Vec<(&'a str, &'a str)>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioTrade<'a> {
+    create_time_ms: &'a str,
+    side: &'a str,
+    amount: &'a str,
+    price: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GateioOrder<'a> {
+    id: &'a str,
+    text: Option<&'a str>,
+    update_time_ms: &'a str,
+    event: &'a str,
+    currency_pair: &'a str,
+    side: &'a str,
+    amount: &'a str,
+    price: &'a str,
+    left: &'a str,
+    filled_total: &'a str,
+    fee: &'a str,
+    finish_as: Option<&'a str>,
+}
+
+/// Timestamps are sent in ms as strings, possibly with decimals.
+fn convert_gateio_timestamp(timestamp: &str) -> Result<Timestamp, failure::Error> {
+    Ok(timestamp.parse::<f64>()? as Timestamp)
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys and which is not connected,
+    /// for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: None,
+            out: None,
+            order_book: OrderBook::new(),
+            orders: HashMap::new(),
+        }
+    }
+
+    fn send_request(&mut self, channel: &str, event: &str, payload: &[&str], private: bool)
+        -> ws::Result<()>
+    {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        let time = timestamp_ms() / 1000;
+
+        // The signature covers the channel, the event and the time.
+        let auth = match (&self.keys, private) {
+            (Some(keys), true) => Some(GateioAuth {
+                method: "api_key",
+                key: &keys.api_key,
+                sign: keys.signature(
+                    &format!("channel={}&event={}&time={}", channel, event, time)
+                ),
+            }),
+            _ => None,
+        };
+
+        let request = GateioRequest {
+            time,
+            channel,
+            event,
+            payload,
+            auth,
+        };
+
+        match serde_json::to_string(&request) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `GateioRequest`: `{}`", err);
+            }
+        }
+    }
+
+    fn book(&mut self, event: &str) -> ws::Result<()> {
+        let symbol = self.symbol;
+        self.send_request("spot.order_book", event, &[symbol.name(), BOOK_DEPTH, "100ms"], false)
+    }
+
+    fn trades(&mut self, event: &str) -> ws::Result<()> {
+        let symbol = self.symbol;
+        self.send_request("spot.trades", event, &[symbol.name()], false)
+    }
+
+    fn convert_gateio_level(&self, level: (&str, &str), side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        Ok(LimitUpdate {
+            side,
+            price: self.symbol.price_tick().ticked(level.0)?,
+            size: self.symbol.size_tick().ticked(level.1)?,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let book: GateioMessage<GateioBook<'_>> = serde_json::from_str(json)?;
+
+        let asks = book.result.asks.iter().map(|l| self.convert_gateio_level(*l, Side::Ask));
+        let bids = book.result.bids.iter().map(|l| self.convert_gateio_level(*l, Side::Bid));
+        let levels = asks.chain(bids).collect::<Result<Vec<_>, _>>()?;
+
+        // Levels which are not part of the new top of the book either were emptied or
+        // went beyond the subscribed depth.
+        let mut updates = Vec::new();
+        {
+            let contains = |side: Side, price: TickUnit| {
+                levels.iter().any(|level| level.side == side && level.price == price)
+            };
+            let asks = self.order_book.ask()
+                .filter(|(price, _)| !contains(Side::Ask, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+            let bids = self.order_book.bid()
+                .filter(|(price, _)| !contains(Side::Bid, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+            updates.extend(asks.chain(bids));
+        }
+
+        updates.extend(levels.into_iter().filter(|level| {
+            self.order_book.size_at_limit(level.side, level.price) != level.size
+        }));
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.with_timestamp(book.result.t))
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trade(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trade: GateioMessage<GateioTrade<'_>> = serde_json::from_str(json)?;
+        let trade = trade.result;
+
+        // The side is the one of the taker.
+        let maker_side = match trade.side {
+            "buy" => Side::Ask,
+            "sell" => Side::Bid,
+            other => bail!("wrong side: `{}`", other),
+        };
+
+        let trade = Notification::Trade(Trade {
+            price: self.symbol.price_tick().ticked(trade.price)?,
+            size: self.symbol.size_tick().ticked(trade.amount)?,
+            maker_side,
+        }.with_timestamp(convert_gateio_timestamp(trade.create_time_ms)?));
+
+        out.unbounded_send(trade).unwrap();
+        Ok(())
+    }
+
+    /// Fills are recovered from the decrease of the size left: their price is the
+    /// average price of the fills since the previous update.
+    fn parse_orders(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let orders: GateioMessage<Vec<GateioOrder<'_>>> = serde_json::from_str(json)?;
+        let size_tick = self.symbol.size_tick();
+
+        for order in orders.result {
+            if order.currency_pair != self.symbol.name() {
+                continue;
+            }
+
+            let timestamp = convert_gateio_timestamp(order.update_time_ms)?;
+            let order_id = order_id(order.id, order.text);
+            let size = size_tick.ticked(order.amount)?;
+            let current = OrderState {
+                left: size_tick.ticked(order.left)?,
+                filled_total: order.filled_total.parse()?,
+                fee: order.fee.parse()?,
+            };
+
+            let previous = match self.orders.get(order.id) {
+                Some(previous) => *previous,
+                None => {
+                    let side = match order.side {
+                        "buy" => Side::Bid,
+                        "sell" => Side::Ask,
+                        other => bail!("wrong side: `{}`", other),
+                    };
+
+                    let confirmation = OrderConfirmation {
+                        order_id: order_id.clone(),
+                        price: self.symbol.price_tick().ticked(order.price)?,
+                        size,
+                        side,
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderConfirmation(confirmation)).unwrap();
+
+                    OrderState {
+                        left: size,
+                        filled_total: 0.,
+                        fee: 0.,
+                    }
+                }
+            };
+
+            if current.left < previous.left {
+                let consumed_size = previous.left - current.left;
+                let consumed_units = consumed_size as f64 / size_tick.ticks_per_unit() as f64;
+                let consumed_price = self.symbol.price_tick().rounded(
+                    (current.filled_total - previous.filled_total) / consumed_units
+                );
+
+                let update = OrderUpdate {
+                    order_id: order_id.clone(),
+                    consumed_size,
+                    remaining_size: current.left,
+                    consumed_price,
+                    commission: self.symbol.commission_tick().rounded(
+                        (current.fee - previous.fee).abs()
+                    ),
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+            }
+
+            if order.event != "finish" {
+                self.orders.insert(order.id.to_owned(), current);
+                continue;
+            }
+
+            self.orders.remove(order.id);
+            if order.finish_as != Some("filled") {
+                let expiration = OrderExpiration {
+                    order_id,
+                    filled_size: Some(size.saturating_sub(current.left)),
+                    remaining_size: Some(current.left),
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: GateioEvent<'_> = serde_json::from_str(json)?;
+
+        if let Some(error) = event.error {
+            bail!("{} (code = {})", error.message, error.code);
+        }
+
+        // Responses to our requests.
+        if event.event != "update" {
+            return Ok(());
+        }
+
+        match event.channel {
+            "spot.order_book" if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book(json, out)?;
+            }
+            "spot.trades" if self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_trade(json, out)?;
+            }
+            "spot.orders" if self.flags.contains(NotificationFlags::ORDERS) => {
+                self.parse_orders(json, out)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.book("subscribe")?;
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            self.trades("subscribe")?;
+        }
+        if self.keys.is_some() {
+            let symbol = self.symbol;
+            self.send_request("spot.orders", "subscribe", &[symbol.name()], true)?;
+        }
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The private channel is kept, `ORDERS` only acts as a filter.
+        match (previous.contains(NotificationFlags::TRADES), flags.contains(NotificationFlags::TRADES)) {
+            (false, true) => self.trades("subscribe")?,
+            (true, false) => self.trades("unsubscribe")?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => self.book("subscribe")?,
+
+            // The book will be forwarded again from scratch.
+            (true, false) => {
+                self.order_book = OrderBook::new();
+                self.book("unsubscribe")?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
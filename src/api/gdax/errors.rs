@@ -27,6 +27,10 @@ impl api::errors::ErrorKinded<!> for RestError {
             return api::errors::RestErrorKind::TooManyRequests;
         }
 
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
         if self.kind == RestErrorKind::Timeout {
             return api::errors::RestErrorKind::UnknownStatus;
         }
@@ -1,8 +1,10 @@
-//! Implementation of `ApiClient` for the GDAX API.
+//! Implementation of `ApiClient` for the GDAX API, i.e. the legacy Coinbase Pro API.
+//! See `api::coinbase` for the Advanced Trade API which replaces it.
 
 pub mod errors;
 mod wss;
 mod rest;
+mod test;
 
 use openssl::pkey::{PKey, Private};
 use chashmap::CHashMap;
@@ -16,16 +18,22 @@ use crate::api::{
     Params,
     ApiClient,
     GenerateOrderId,
-    Notification,
     NotificationFlags,
     Order,
     OrderAck,
     Cancel,
     CancelAck,
-    Balances
+    OrderStatus,
+    Fill,
+    Balances,
+    ExchangeStatus,
+    Trade,
 };
+use crate::api::stream::NotificationStream;
 use crate::api::symbol::{Symbol, WithSymbol};
-use crate::api::timestamp::{Timestamped, IntoTimestamped};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::Bar;
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// A GDAX key pair: api key + secret key, along with a pass phrase.
@@ -65,6 +73,20 @@ pub struct Client {
     http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
 }
 
+impl Params {
+    /// Return the parameters of the Coinbase Pro sandbox, where keys are generated at
+    /// `https://public.sandbox.pro.coinbase.com`.
+    ///
+    /// # Note
+    /// `system_status` still reports the status of the production exchange.
+    pub fn gdax_sandbox() -> Self {
+        Params {
+            streaming_endpoint: "wss://ws-feed-public.sandbox.pro.coinbase.com".to_owned(),
+            rest_endpoint: "https://api-public.sandbox.pro.coinbase.com".to_owned(),
+        }
+    }
+}
+
 impl Client {
     /// Create a new GDAX API client with given `params`. If `key_pair` is not
     /// `None`, this will enable performing requests to the REST API and will forward
@@ -109,7 +131,11 @@ impl Client {
 }
 
 impl ApiClient for Client {
-    type Stream = futures::sync::mpsc::UnboundedReceiver<Notification>;
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Gdax
+    }
 
     fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
         self.symbols.get(&symbol.to_lowercase()).cloned()
@@ -122,13 +148,21 @@ impl ApiClient for Client {
     fn order(&self, order: WithSymbol<&Order>)
         -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
     {
-        Box::new(self.order_impl(order))
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
     }
 
     fn cancel(&self, cancel: WithSymbol<&Cancel>)
         -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
     {
-       self.cancel_impl(cancel)
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.cancel_impl(cancel)
     }
 
     fn ping(&self)
@@ -137,11 +171,53 @@ impl ApiClient for Client {
         Box::new(Ok(().timestamped()).into_future())
     }
 
+    fn order_status(&self, symbol: Symbol, order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.order_status_impl(symbol, order_id))
+    }
+
+    fn my_trades(&self, symbol: Symbol, since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.my_trades_impl(symbol, since))
+    }
+
+    fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.recent_trades_impl(symbol, limit))
+    }
+
+    fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = api::errors::Error> + Send + 'static>
+    {
+        self.recent_bars_impl(symbol, interval, limit)
+    }
+
     fn balances(&self)
         -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
     {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
         Box::new(self.balances_impl())
     }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
 }
 
 impl GenerateOrderId for Client {
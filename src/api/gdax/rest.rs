@@ -1,12 +1,12 @@
 use openssl::{sign::Signer, hash::MessageDigest};
 use hyper::{Method, Request};
 use futures::prelude::*;
-use failure::Fail;
+use failure::{Fail, bail};
 use log::{warn, debug, error};
 use std::collections::HashMap;
 use serde_derive::{Serialize, Deserialize};
 use crate::Side;
-use crate::tick::Tick;
+use crate::tick::{Tick, ConversionError};
 use crate::api::{
     self,
     TimeInForce,
@@ -16,24 +16,144 @@ use crate::api::{
     Cancel,
     CancelAck,
     Balance,
-    Balances
+    Balances,
+    OrderState,
+    OrderStatus,
+    Fill,
+    ExchangeStatus,
+    Trade,
 };
 use crate::api::errors::ErrorKinded;
+use crate::api::fees::Liquidity;
 use crate::api::symbol::{Symbol, WithSymbol};
-use crate::api::timestamp::{convert_str_timestamp, timestamp_ms, Timestamped, IntoTimestamped};
-use crate::api::gdax::Client;
+use crate::api::timestamp::{convert_str_timestamp, timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::Bar;
+use crate::api::gdax::{Keys, Client};
 use crate::api::gdax::errors::RestError;
 
+/// Coinbase publishes the status of its services on a Statuspage instance, which is
+/// not part of the REST API.
+const STATUS_ENDPOINT: &str = "https://status.coinbase.com/api/v2/status.json";
+
+/// Maximum number of entries returned by `products/<id>/trades`.
+const TRADES_LIMIT: usize = 1000;
+
+/// Candle granularities accepted by `products/<id>/candles`, in seconds.
+const GRANULARITIES: [u64; 6] = [60, 300, 900, 3600, 21600, 86400];
+
+fn convert_trade(symbol: Symbol, trade: GdaxTrade<'_>)
+    -> Result<Timestamped<Trade>, failure::Error>
+{
+    // The side of a GDAX trade is the side of the maker order.
+    let maker_side = match trade.side {
+        "buy" => Side::Bid,
+        "sell" => Side::Ask,
+        other => bail!("wrong side: `{}`", other),
+    };
+
+    Ok(Trade {
+        price: symbol.price_tick().ticked(trade.price)?,
+        size: symbol.size_tick().ticked(trade.size)?,
+        maker_side,
+    }.with_timestamp(convert_str_timestamp(trade.time)?))
+}
+
+fn convert_fill(symbol: Symbol, fill: GdaxFill<'_>) -> Result<Timestamped<Fill>, failure::Error> {
+    // Unlike for public trades, this is the side of the filled order.
+    let side = match fill.side {
+        "buy" => Side::Bid,
+        "sell" => Side::Ask,
+        other => bail!("wrong side: `{}`", other),
+    };
+
+    let liquidity = match fill.liquidity {
+        "M" => Some(Liquidity::Maker),
+        "T" => Some(Liquidity::Taker),
+        _ => None,
+    };
+
+    Ok(Fill {
+        trade_id: fill.trade_id.to_string(),
+        order_id: fill.order_id.to_owned(),
+        side,
+        price: symbol.price_tick().ticked(fill.price)?,
+        size: symbol.size_tick().ticked(fill.size)?,
+        fee: fill.fee.to_owned(),
+        // Fees are paid in the quote currency, e.g. `USD` for `BTC-USD`.
+        fee_asset: symbol.name().split('-').nth(1).map(|quote| quote.to_owned()),
+        liquidity,
+    }.with_timestamp(convert_str_timestamp(fill.created_at)?))
+}
+
+fn convert_order_status(symbol: Symbol, order: GdaxOrderStatus<'_>)
+    -> Result<Timestamped<OrderStatus>, failure::Error>
+{
+    let filled_size = symbol.size_tick().ticked(order.filled_size)?;
+    let state = match (order.status, order.done_reason) {
+        ("rejected", _) => OrderState::Rejected,
+        ("done", Some("filled")) => OrderState::Filled,
+        ("done", _) => OrderState::Canceled,
+        _ if filled_size > 0 => OrderState::PartiallyFilled,
+        _ => OrderState::New,
+    };
+
+    let average_price = if filled_size == 0 {
+        None
+    } else {
+        let average_price = order.executed_value.parse::<f64>()?
+            / order.filled_size.parse::<f64>()?;
+        Some(symbol.price_tick().rounded(average_price))
+    };
+
+    let timestamp = order.done_at.unwrap_or(order.created_at);
+    Ok(OrderStatus {
+        order_id: order.client_oid.to_owned(),
+        state,
+        filled_size,
+        average_price,
+        commission: Some(symbol.commission_tick().ticked(order.fill_fees)?),
+    }.with_timestamp(convert_str_timestamp(timestamp)?))
+}
+
+fn convert_candle(symbol: Symbol, candle: GdaxCandle)
+    -> Result<Timestamped<Bar>, ConversionError>
+{
+    // Candles are sent as JSON numbers.
+    let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+    Ok(Bar {
+        low: price_tick.ticked(&candle.1.to_string())?,
+        high: price_tick.ticked(&candle.2.to_string())?,
+        open: price_tick.ticked(&candle.3.to_string())?,
+        close: price_tick.ticked(&candle.4.to_string())?,
+        volume: size_tick.ticked(&candle.5.to_string())?,
+        // Not provided by GDAX.
+        count: 0,
+    }.with_timestamp(candle.0 * 1000))
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
 struct GdaxOrder<'a> {
-    size: &'a str,
-    price: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    funds: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price: Option<&'a str>,
     side: &'a str,
     product_id: &'a str,
     #[serde(borrow)]
     client_oid: Option<&'a str>,
-    time_in_force: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_in_force: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancel_after: Option<&'a str>,
     post_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_price: Option<&'a str>,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
@@ -44,6 +164,30 @@ struct GdaxOrderAck<'a> {
     reject_reason: Option<&'a str>,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxOrderStatus<'a> {
+    client_oid: &'a str,
+    status: &'a str,
+    done_reason: Option<&'a str>,
+    filled_size: &'a str,
+    executed_value: &'a str,
+    fill_fees: &'a str,
+    created_at: &'a str,
+    done_at: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxFill<'a> {
+    trade_id: u64,
+    order_id: &'a str,
+    created_at: &'a str,
+    price: &'a str,
+    size: &'a str,
+    fee: &'a str,
+    liquidity: &'a str,
+    side: &'a str,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 struct GdaxAccount<'a> {
     currency: &'a str,
@@ -64,6 +208,29 @@ struct GdaxCurrency<'a> {
     min_size: &'a str,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxTrade<'a> {
+    time: &'a str,
+    price: &'a str,
+    size: &'a str,
+    side: &'a str,
+}
+
+/// Time in seconds, low, high, open, close, volume.
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct GdaxCandle(u64, f64, f64, f64, f64, f64);
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxStatusIndicator<'a> {
+    indicator: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxStatusPage<'a> {
+    #[serde(borrow)]
+    status: GdaxStatusIndicator<'a>,
+}
+
 trait AsStr {
     fn as_str(self) -> &'static str;
 }
@@ -83,6 +250,120 @@ impl AsStr for TimeInForce {
             TimeInForce::GoodTilCanceled => "GTC",
             TimeInForce::FillOrKilll => "FOK",
             TimeInForce::ImmediateOrCancel => "IOC",
+            TimeInForce::GoodTilDate(_) => "GTT",
+        }
+    }
+}
+
+type HttpClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+/// Send a request to `path`, signed with `keys` if any.
+fn send_request<K: api::errors::ErrorKind>(
+    http_client: &HttpClient,
+    rest_endpoint: &str,
+    keys: Option<&Keys>,
+    path: &str,
+    method: Method,
+    body: String
+) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+        where RestError: ErrorKinded<K>
+{
+    let address = format!(
+        "{}/{}",
+        rest_endpoint,
+        path,
+    );
+
+    let mut request = Request::builder();
+
+    if let Some(keys) = keys {
+        let timestamp = timestamp_ms() as f64 / 1000.;
+        let mut signer = Signer::new(MessageDigest::sha256(), &keys.secret_key).unwrap();
+        let what = format!("{}{}/{}{}", timestamp, method, path, body);
+        signer.update(what.as_bytes()).unwrap();
+        let signature = base64::encode(&signer.sign_to_vec().unwrap());
+
+        request.header("CB-ACCESS-KEY", keys.api_key.as_bytes())
+            .header("CB-ACCESS-SIGN", signature.as_bytes())
+            .header("CB-ACCESS-TIMESTAMP", format!("{}", timestamp).as_bytes())
+            .header("CB-ACCESS-PASSPHRASE", keys.pass_phrase.as_bytes());
+    }
+
+    request.method(method)
+        .uri(&address)
+        .header("User-Agent", &b"hyper"[..])
+        .header("Content-Type", &b"application/json"[..]);
+    
+    // Unwrap because it is a bug if this fails (header failed to parse or something)
+    let request = request.body(body.into()).unwrap();
+    http_client.request(request).and_then(|res| {
+        let status = res.status();
+        res.into_body().concat2().and_then(move |body| {
+            Ok((status, body))
+        })
+    })
+    .map_err(api::errors::RequestError::new)
+    .map_err(api::errors::ApiError::RequestError).and_then(|(status, body)| {
+        if status != hyper::StatusCode::OK {
+            let gdax_error = serde_json::from_slice(&body);
+            let error = RestError::from_gdax_error(status, gdax_error.ok());
+            let kind = error.kind();
+            Err(
+                api::errors::ApiError::RestError(error.context(kind).into())
+            )?;
+        }
+        Ok(body)
+    })
+}
+
+/// Return the shortest expiration among those supported by GDAX, i.e. a minute, an hour
+/// or a day, which does not expire an order before `remaining` ms, or `None` if
+/// `remaining` is more than a day.
+crate fn cancel_after(remaining: u64) -> Option<&'static str> {
+    [(60_000, "min"), (3_600_000, "hour"), (86_400_000, "day")]
+        .iter()
+        .find(|(duration, _)| remaining <= *duration)
+        .map(|(_, cancel_after)| *cancel_after)
+}
+
+/// The cancel of a good til date order, sent once its date is reached. GDAX cannot
+/// expire the order at an arbitrary date by itself.
+struct Expiry {
+    http_client: HttpClient,
+    rest_endpoint: String,
+    keys: Option<Keys>,
+    expiration: Timestamp,
+}
+
+impl Expiry {
+    /// Schedule the cancel of the order of server id `id` on the current tokio executor.
+    /// The stream then notifies the cancel as an `OrderExpiration`.
+    fn schedule(self, id: String) {
+        use std::time::{Duration, Instant};
+        use tokio::executor::Executor;
+
+        let Expiry { http_client, rest_endpoint, keys, expiration } = self;
+        let delay = Duration::from_millis(expiration.saturating_sub(timestamp_ms()));
+
+        let fut = tokio::timer::Delay::new(Instant::now() + delay)
+            .map_err(|err| error!("expiration timer encountered error: `{}`", err))
+            .and_then(move |()| {
+                send_request::<api::errors::CancelErrorKind>(
+                    &http_client,
+                    &rest_endpoint,
+                    keys.as_ref(),
+                    &format!("orders/{}", id),
+                    Method::DELETE,
+                    String::new()
+                )
+                .map(|_| ())
+                // The order may have been filled, canceled or expired by GDAX in the
+                // meantime.
+                .map_err(move |err| warn!("cannot expire order `{}`: `{}`", id, err))
+            });
+
+        if let Err(err) = tokio::executor::DefaultExecutor::current().spawn(Box::new(fut)) {
+            error!("cannot schedule the expiration of an order: `{}`", err);
         }
     }
 }
@@ -96,58 +377,55 @@ impl Client {
     ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
             where RestError: ErrorKinded<K>
     {
-        let address = format!(
-            "{}/{}",
-            self.params.rest_endpoint,
+        send_request(
+            &self.http_client,
+            &self.params.rest_endpoint,
+            self.keys.as_ref(),
             path,
-        );
+            method,
+            body
+        )
+    }
 
-        let mut request = Request::builder();
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
 
-        if let Some(keys) = self.keys.as_ref() {
-            let timestamp = timestamp_ms() as f64 / 1000.;
-            let mut signer = Signer::new(MessageDigest::sha256(), &keys.secret_key).unwrap();
-            let what = format!("{}{}/{}{}", timestamp, method, path, body);
-            signer.update(what.as_bytes()).unwrap();
-            let signature = base64::encode(&signer.sign_to_vec().unwrap());
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
 
-            request.header("CB-ACCESS-KEY", keys.api_key.as_bytes())
-                .header("CB-ACCESS-SIGN", signature.as_bytes())
-                .header("CB-ACCESS-TIMESTAMP", format!("{}", timestamp).as_bytes())
-                .header("CB-ACCESS-PASSPHRASE", keys.pass_phrase.as_bytes());
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
         }
 
-        request.method(method)
-            .uri(&address)
-            .header("User-Agent", &b"hyper"[..])
-            .header("Content-Type", &b"application/json"[..]);
-        
-        // Unwrap because it is a bug if this fails (header failed to parse or something)
-        let request = request.body(body.into()).unwrap();
-        self.http_client.request(request).and_then(|res| {
-            let status = res.status();
-            res.into_body().concat2().and_then(move |body| {
-                Ok((status, body))
-            })
-        })
-        .map_err(api::errors::RequestError::new)
-        .map_err(api::errors::ApiError::RequestError).and_then(|(status, body)| {
-            if status != hyper::StatusCode::OK {
-                let gdax_error = serde_json::from_slice(&body);
-                let error = RestError::from_gdax_error(status, gdax_error.ok());
-                let kind = error.kind();
-                Err(
-                    api::errors::ApiError::RestError(error.context(kind).into())
-                )?;
-            }
-            Ok(body)
-        })
-    }
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
 
-    crate fn order_impl(&self, order: WithSymbol<&Order>)
-        -> impl Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static
-    {
-        use std::borrow::Borrow;
+        if order.quote_size && order.type_ != OrderType::Market {
+            warn!("called `order` with a quote size, which is only supported by market orders");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() && order.trigger_price.is_none() {
+            warn!("called `order` with a stop order but no trigger price");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
 
         // Note that GDAX only accepts custom client ids in the form of UUIDs, so there can
         // never be duplicate orders inserted in the `order_ids` map. This is actually quite
@@ -157,26 +435,70 @@ impl Client {
         let symbol = order.symbol();
 
         let client_oid = order.order_id.clone();
-        let time_in_force = order.time_in_force;
+        let market = order.type_ == OrderType::Market || order.type_ == OrderType::StopMarket;
+
+        // Good til time orders expire after a minute, an hour or a day: the client cancels
+        // the order at its date, and the shortest expiration not cancelling it before is
+        // kept as a backstop, e.g. if the executor is shut down in the meantime. Orders
+        // more than a day ahead are sent as good til canceled.
+        let expiry = match order.time_in_force.expiration() {
+            Some(_) if market => None,
+            Some(expiration) => Some(Expiry {
+                http_client: self.http_client.clone(),
+                rest_endpoint: self.params.rest_endpoint.clone(),
+                keys: self.keys.clone(),
+                expiration,
+            }),
+            None => None,
+        };
+        let cancel_after = expiry.as_ref()
+            .and_then(|expiry| cancel_after(expiry.expiration.saturating_sub(timestamp_ms())));
+        let time_in_force = match order.time_in_force {
+            TimeInForce::GoodTilDate(_) if cancel_after.is_none() => TimeInForce::GoodTilCanceled,
+            time_in_force => time_in_force,
+        };
 
-        let size = order.size.unticked(symbol.size_tick());
+        // The size of market orders can be expressed in the quote currency, as `funds`.
+        let size = if order.quote_size {
+            order.size.unticked(symbol.price_tick())
+        } else {
+            order.size.unticked(symbol.size_tick())
+        };
         let price = order.price.unticked(symbol.price_tick());
 
+        // A sell stop is a stop loss, triggered when the price falls to the stop price,
+        // and a buy stop an entry, triggered when the price rises to the stop price.
+        let stop = match order.side {
+            Side::Ask => "loss",
+            Side::Bid => "entry",
+        };
+        let stop_price = match &order.trigger_price {
+            Some(trigger_price) if order.type_.is_stop() => {
+                Some(trigger_price.unticked(symbol.price_tick()))
+            }
+            _ => None,
+        };
+
         let order = GdaxOrder {
-            size: size.borrow(),
-            price: price.borrow(),
+            type_: if market { "market" } else { "limit" },
+            size: if order.quote_size { None } else { Some(size.borrow()) },
+            funds: if order.quote_size { Some(size.borrow()) } else { None },
+            price: if market { None } else { Some(price.borrow()) },
             side: order.side.as_str(),
             product_id: symbol.name(),
             client_oid: client_oid.as_ref().map(|oid| oid.as_ref()),
-            time_in_force: time_in_force.as_str(),
+            time_in_force: if market { None } else { Some(time_in_force.as_str()) },
+            cancel_after,
             post_only: order.type_ == OrderType::LimitMaker,
+            stop: stop_price.as_ref().map(|_| stop),
+            stop_price: stop_price.as_ref().map(|stop_price| stop_price.borrow()),
         };
 
         let body = serde_json::to_string(&order).expect("invalid json");
 
         let order_ids = self.order_ids.clone();
 
-        self.request("orders", Method::POST, body).and_then(move |body| {
+        let fut = self.request("orders", Method::POST, body).and_then(move |body| {
             let ack: GdaxOrderAck<'_> = serde_json::from_slice(&body)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
@@ -204,10 +526,15 @@ impl Client {
             order_ids.insert(order_id.clone(), ack.id.to_owned());
             debug!("insert order id {} (from REST)", order_id);
 
+            if let Some(expiry) = expiry {
+                expiry.schedule(ack.id.to_owned());
+            }
+
             Ok(OrderAck {
                 order_id,
             }.with_timestamp(timestamp))
-        })
+        });
+        Box::new(fut)
     }
 
     crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
@@ -233,6 +560,44 @@ impl Client {
         Box::new(fut)
     }
 
+    crate fn order_status_impl(&self, symbol: Symbol, order_id: &str)
+        -> impl Future<Item = Timestamped<OrderStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let endpoint = format!("orders/client:{}", order_id);
+
+        self.request(&endpoint, Method::GET, String::new()).and_then(move |body| {
+            let order: GdaxOrderStatus<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = convert_order_status(symbol, order)
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(status)
+        })
+    }
+
+    crate fn my_trades_impl(&self, symbol: Symbol, since: Timestamp)
+        -> impl Future<Item = Vec<Timestamped<Fill>>, Error = api::errors::Error> + Send + 'static
+    {
+        let endpoint = format!("fills?product_id={}", symbol.name());
+
+        self.request(&endpoint, Method::GET, String::new()).and_then(move |body| {
+            let gdax_fills: Vec<GdaxFill<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Fills are sent most recent first.
+            let fills: Vec<_> = gdax_fills.into_iter()
+                .rev()
+                .map(|fill| convert_fill(symbol, fill))
+                .collect::<Result<_, _>>()
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(fills.into_iter().filter(|fill| fill.timestamp() >= since).collect())
+        })
+    }
+
     crate fn balances_impl(&self)
         -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
     {
@@ -251,6 +616,98 @@ impl Client {
         })
     }
 
+    crate fn recent_trades_impl(&self, symbol: Symbol, limit: usize)
+        -> impl Future<Item = Vec<Timestamped<Trade>>, Error = api::errors::Error> + Send + 'static
+    {
+        let endpoint = format!(
+            "products/{}/trades?limit={}",
+            symbol.name(),
+            std::cmp::min(limit, TRADES_LIMIT)
+        );
+
+        self.request(&endpoint, Method::GET, String::new()).and_then(move |body| {
+            let gdax_trades: Vec<GdaxTrade<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Trades are sent newest first.
+            let trades = gdax_trades.into_iter()
+                .rev()
+                .map(|trade| convert_trade(symbol, trade))
+                .collect::<Result<_, _>>()
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(trades)
+        })
+    }
+
+    crate fn recent_bars_impl(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = api::errors::Error> + Send + 'static>
+    {
+        let granularity = interval / 1000;
+        if granularity * 1000 != interval || !GRANULARITIES.contains(&granularity) {
+            warn!("called `recent_bars` with an unsupported interval of {} ms", interval);
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(
+                    api::errors::RestErrorKind::InvalidRequest.into()
+                )
+            ));
+        }
+
+        let endpoint = format!("products/{}/candles?granularity={}", symbol.name(), granularity);
+        let fut = self.request(&endpoint, Method::GET, String::new()).and_then(move |body| {
+            let candles: Vec<GdaxCandle> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Candles are sent newest first.
+            let mut bars = candles.into_iter()
+                .take(limit)
+                .map(|candle| convert_candle(symbol, candle))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            bars.reverse();
+            Ok(bars)
+        });
+        Box::new(fut)
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = STATUS_ENDPOINT.parse().expect("invalid address");
+
+        self.http_client.get(address).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError).and_then(|(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let error = RestError::from_gdax_error(status, None);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+
+            let page: GdaxStatusPage<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match page.status.indicator {
+                "none" => ExchangeStatus::Normal,
+                "minor" => ExchangeStatus::Degraded,
+                "maintenance" => ExchangeStatus::Maintenance,
+                _ => ExchangeStatus::Outage,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
     crate fn get_symbols(&self)
         -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
     {
@@ -0,0 +1,145 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::api::{Notification, NotificationFlags, OrderConfirmation, OrderUpdate, OrderExpiration, Trade};
+use crate::api::gdax::rest::cancel_after;
+use crate::api::gdax::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+const TIME: &str = "2018-06-04T10:15:30.123Z";
+const TIMESTAMP: u64 = 1_528_107_330_123;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC-USD", Tick::new(100), Tick::new(1000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming `flags`, and return the notifications.
+fn parse_with(flags: NotificationFlags, frames: &[String]) -> Vec<Notification> {
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+/// Feed `frames` to a handler streaming orders, and return the notifications.
+fn parse(frames: &[String]) -> Vec<Notification> {
+    parse_with(NotificationFlags::ORDERS, frames)
+}
+
+fn received(size: &str) -> String {
+    format!(
+        r#"{{"type":"received","time":"{}","product_id":"BTC-USD","order_id":"server",
+            "client_oid":"client","size":"{}","price":"100.00","side":"buy","order_type":"limit"}}"#,
+        TIME,
+        size
+    )
+}
+
+fn fill(size: &str) -> String {
+    format!(
+        r#"{{"type":"match","time":"{}","product_id":"BTC-USD","size":"{}","price":"100.00",
+            "side":"sell","maker_order_id":"other","taker_order_id":"server","profile_id":"me"}}"#,
+        TIME,
+        size
+    )
+}
+
+fn canceled(remaining_size: &str) -> String {
+    format!(
+        r#"{{"type":"done","time":"{}","product_id":"BTC-USD","order_id":"server",
+            "reason":"canceled","remaining_size":"{}","side":"buy"}}"#,
+        TIME,
+        remaining_size
+    )
+}
+
+#[test]
+fn partially_filled() {
+    let notifs = parse(&[received("10.000"), fill("4.000"), canceled("6.000")]);
+    assert_eq!(notifs, vec![
+        Notification::OrderConfirmation(OrderConfirmation {
+            order_id: "client".to_owned(),
+            price: 10000,
+            size: 10000,
+            side: Side::Bid,
+        }.with_timestamp(TIMESTAMP)),
+        Notification::OrderUpdate(OrderUpdate {
+            order_id: "client".to_owned(),
+            consumed_size: 4000,
+            remaining_size: 6000,
+            consumed_price: 10000,
+            commission: 0,
+        }.with_timestamp(TIMESTAMP)),
+        Notification::OrderExpiration(OrderExpiration {
+            order_id: "client".to_owned(),
+            filled_size: Some(4000),
+            remaining_size: Some(6000),
+        }.with_timestamp(TIMESTAMP)),
+    ]);
+
+    match &notifs[2] {
+        Notification::OrderExpiration(expiration) => {
+            assert_eq!(expiration.is_partially_filled(), Some(true));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn unfilled() {
+    let notifs = parse(&[received("10.000"), canceled("10.000")]);
+    match notifs.last() {
+        Some(Notification::OrderExpiration(expiration)) => {
+            assert_eq!(expiration.filled_size, Some(0));
+            assert_eq!(expiration.is_partially_filled(), Some(false));
+        }
+        other => panic!("expected an expiration, got {:?}", other),
+    }
+}
+
+#[test]
+fn match_flags() {
+    // A stream of orders alone still reports the fills of the user.
+    let notifs = parse_with(NotificationFlags::ORDERS, &[received("10.000"), fill("4.000")]);
+    assert_eq!(notifs.len(), 2);
+    match &notifs[1] {
+        Notification::OrderUpdate(update) => assert_eq!(update.consumed_size, 4000),
+        other => panic!("expected an order update, got {:?}", other),
+    }
+
+    // A stream of trades alone still reports the trades.
+    let notifs = parse_with(NotificationFlags::TRADES, &[fill("4.000")]);
+    assert_eq!(notifs, vec![
+        Notification::Trade(Trade {
+            size: 4000,
+            price: 10000,
+            maker_side: Side::Ask,
+        }.with_timestamp(TIMESTAMP)),
+    ]);
+}
+
+#[test]
+fn good_til_date() {
+    const MINUTE: u64 = 60_000;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    // Dates which are not a whole minute, hour or day ahead never expire early.
+    assert_eq!(cancel_after(30_000), Some("min"));
+    assert_eq!(cancel_after(59 * MINUTE), Some("hour"));
+    assert_eq!(cancel_after(HOUR + 1), Some("day"));
+    assert_eq!(cancel_after(23 * HOUR), Some("day"));
+    assert_eq!(cancel_after(DAY + 1), None);
+
+    assert_eq!(cancel_after(MINUTE), Some("min"));
+    assert_eq!(cancel_after(HOUR), Some("hour"));
+    assert_eq!(cancel_after(DAY), Some("day"));
+}
@@ -1,5 +1,5 @@
-use futures::sync::mpsc::{unbounded, UnboundedReceiver};
-use std::thread;
+use futures::sync::mpsc::unbounded;
+use std::{mem, thread};
 use std::collections::HashMap;
 use chashmap::CHashMap;
 use std::sync::Arc;
@@ -7,47 +7,61 @@ use log::{debug, error};
 use failure::bail;
 use serde_derive::{Serialize, Deserialize};
 use crate::{tick, Side};
+use crate::tick::TickUnit;
 use crate::order_book::LimitUpdate;
 use crate::api::{
+    AuctionUpdate,
     Notification,
     NotificationFlags,
     OrderConfirmation,
     OrderUpdate,
     Trade,
     OrderExpiration,
+    TradingStatus,
 };
 use crate::api::symbol::Symbol;
+use crate::api::stream::NotificationStream;
 use crate::api::wss;
 use crate::api::timestamp::{convert_str_timestamp, timestamp_ms, IntoTimestamped};
 use crate::api::gdax::{Keys, Client};
 
 impl Client {
     crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
-        -> UnboundedReceiver<Notification>
+        -> NotificationStream
     {
         let streaming_endpoint = self.params.streaming_endpoint.clone();
         let keys = self.keys.clone();
         let order_ids = self.order_ids.clone();
         let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
         thread::spawn(move || {
             debug!("initiating WebSocket connection at {}", streaming_endpoint);
             
             if let Err(err) = ws::connect(streaming_endpoint, |out| {
-                wss::Handler::new(out, snd.clone(), wss::KeepAlive::False, HandlerImpl {
-                    symbol,
-                    flags,
-                    state: SubscriptionState::NotSubscribed,
-                    keys: keys.clone(),
-                    orders: HashMap::new(),
-                    order_ids: order_ids.clone(),
-                })
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::False,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        state: SubscriptionState::NotSubscribed,
+                        keys: keys.clone(),
+                        orders: HashMap::new(),
+                        order_ids: order_ids.clone(),
+                        halted: None,
+                        auction: false,
+                    }
+                )
             })
             {
                 error!("WebSocket connection terminated with error: `{}`", err);
             }
         });
         
-        rcv
+        NotificationStream::new(rcv, control)
     }
 }
 
@@ -57,17 +71,35 @@ enum SubscriptionState {
     Subscribed,
 }
 
-struct HandlerImpl {
+crate struct HandlerImpl {
     symbol: Symbol,
     flags: NotificationFlags,
     state: SubscriptionState,
     keys: Option<Keys>,
 
-    /// server order id => client order
-    orders: HashMap<String, OrderConfirmation>,
+    /// server order id => order of the user
+    orders: HashMap<String, GdaxOrder>,
 
     /// client order id => server order id (shared with `Client`)
     order_ids: Arc<CHashMap<String, String>>,
+
+    /// Whether trading on `symbol` is halted, as of the latest `status` message.
+    halted: Option<bool>,
+
+    /// Whether `symbol` is in an auction phase.
+    auction: bool,
+}
+
+/// An order of the user, as tracked by the stream.
+struct GdaxOrder {
+    /// Client order id.
+    order_id: String,
+
+    /// Size at insertion.
+    size: TickUnit,
+
+    /// Size filled so far.
+    filled: TickUnit,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
@@ -129,9 +161,11 @@ struct GdaxReceived<'a> {
     time: &'a str,
     client_oid: Option<&'a str>,
     order_id: &'a str,
-    size: &'a str,
-    price: &'a str,
     side: &'a str,
+
+    /// Market orders have no price, and no size if placed with `funds`.
+    size: Option<&'a str>,
+    price: Option<&'a str>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
@@ -139,6 +173,34 @@ struct GdaxDone<'a> {
     reason: &'a str,
     order_id: &'a str,
     time: &'a str,
+    remaining_size: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxProductStatus<'a> {
+    id: &'a str,
+    status: &'a str,
+    status_message: Option<&'a str>,
+    #[serde(default)]
+    trading_disabled: bool,
+    #[serde(default)]
+    cancel_only: bool,
+    #[serde(default)]
+    auction_mode: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxAuction<'a> {
+    product_id: &'a str,
+    auction_state: &'a str,
+    open_price: Option<&'a str>,
+    open_size: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GdaxStatus<'a> {
+    #[serde(borrow)]
+    products: Vec<GdaxProductStatus<'a>>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
@@ -154,6 +216,21 @@ struct EventType<'a> {
 }
 
 impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            state: SubscriptionState::NotSubscribed,
+            keys: None,
+            orders: HashMap::new(),
+            order_ids: Arc::new(CHashMap::new()),
+            halted: None,
+            auction: false,
+        }
+    }
+
     fn convert_gdax_update(&self, l: (&str, &str), side: Side)
         -> Result<LimitUpdate, tick::ConversionError>
     {
@@ -175,7 +252,7 @@ impl HandlerImpl {
         Ok(side)
     }
 
-    fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
         let event_type: EventType<'_> = serde_json::from_str(json)?;
 
         match event_type.type_ {
@@ -224,7 +301,7 @@ impl HandlerImpl {
             },
 
             "match"
-                if self.flags.contains(NotificationFlags::TRADES | NotificationFlags::ORDERS) =>
+                if self.flags.intersects(NotificationFlags::TRADES | NotificationFlags::ORDERS) =>
             {
                 let trade: GdaxMatch<'_> = serde_json::from_str(json)?;
                 let timestamp = convert_str_timestamp(trade.time)?;
@@ -234,15 +311,16 @@ impl HandlerImpl {
 
                 // An order which is about us
                 if self.flags.contains(NotificationFlags::ORDERS) && trade.profile_id.is_some() {
-                    let update_order = |order: &mut OrderConfirmation| {
-                        order.size -= size;
+                    let update_order = |order: &mut GdaxOrder| {
+                        // The size of market orders placed with `funds` is unknown.
+                        order.filled += size;
 
                         out.unbounded_send(
                             Notification::OrderUpdate(OrderUpdate {
                                 order_id: order.order_id.clone(),
                                 consumed_size: size,
                                 consumed_price: price,
-                                remaining_size: order.size,
+                                remaining_size: order.size.saturating_sub(order.filled),
                                 commission: 0,
                             }.with_timestamp(timestamp))
                         ).unwrap();
@@ -272,8 +350,14 @@ impl HandlerImpl {
                 let received: GdaxReceived<'_> = serde_json::from_str(json)?;
                 let timestamp = convert_str_timestamp(received.time)?;
 
-                let size = self.symbol.size_tick().ticked(received.size)?;
-                let price = self.symbol.price_tick().ticked(received.price)?;
+                let size = match received.size {
+                    Some(size) => self.symbol.size_tick().ticked(size)?,
+                    None => 0,
+                };
+                let price = match received.price {
+                    Some(price) => self.symbol.price_tick().ticked(price)?,
+                    None => 0,
+                };
                 let side = self.convert_gdax_side(received.side)?;
 
                 // The order id specified by the user, which defaults to the server order id
@@ -293,7 +377,11 @@ impl HandlerImpl {
                     order_id,
                 };
 
-                self.orders.insert(received.order_id.to_owned(), order.clone());
+                self.orders.insert(received.order_id.to_owned(), GdaxOrder {
+                    order_id: order.order_id.clone(),
+                    size,
+                    filled: 0,
+                });
 
                 out.unbounded_send(
                     Notification::OrderConfirmation(order.with_timestamp(timestamp))
@@ -308,18 +396,94 @@ impl HandlerImpl {
                     return Ok(());
                 }
 
-                let order_id = match self.orders.get(done.order_id) {
-                    Some(order) => order.order_id.to_owned(),
+                // `size` is the size at insertion, not the size left after the fills.
+                let (order_id, size) = match self.orders.get(done.order_id) {
+                    Some(order) => (order.order_id.to_owned(), order.size),
                     None => return Ok(()),
                 };
 
+                let remaining_size = match done.remaining_size {
+                    Some(remaining_size) => Some(self.symbol.size_tick().ticked(remaining_size)?),
+                    None => None,
+                };
+
                 out.unbounded_send(
                     Notification::OrderExpiration(OrderExpiration {
                         order_id,
+                        filled_size: remaining_size.map(|remaining_size| size.saturating_sub(remaining_size)),
+                        remaining_size,
                     }.with_timestamp(timestamp))
                 ).unwrap();
             }
 
+            "status" => {
+                let status: GdaxStatus<'_> = serde_json::from_str(json)?;
+                let product = match status.products
+                    .into_iter()
+                    .find(|product| product.id == self.symbol.name())
+                {
+                    Some(product) => product,
+                    None => return Ok(()),
+                };
+
+                // The `auctionfeed` channel does not tell when the auction is over.
+                if self.auction && !product.auction_mode {
+                    self.auction = false;
+                    out.unbounded_send(Notification::AuctionUpdate(AuctionUpdate {
+                        phase: None,
+                        indicative_price: None,
+                        indicative_size: None,
+                    }.timestamped())).unwrap();
+                }
+
+                let halted = product.status != "online"
+                    || product.trading_disabled
+                    || product.cancel_only;
+
+                // The first `status` message only reports a halt, not the fact that
+                // trading is open.
+                let previous = self.halted.replace(halted);
+                if previous.unwrap_or(false) == halted {
+                    return Ok(());
+                }
+
+                let trading_status = TradingStatus {
+                    status: product.status.to_owned(),
+                    message: product.status_message
+                        .filter(|message| !message.is_empty())
+                        .map(|message| message.to_owned()),
+                };
+                let notif = if halted {
+                    Notification::TradingHalted(trading_status.timestamped())
+                } else {
+                    Notification::TradingResumed(trading_status.timestamped())
+                };
+                out.unbounded_send(notif).unwrap();
+            }
+
+            "auction" => {
+                let auction: GdaxAuction<'_> = serde_json::from_str(json)?;
+                if auction.product_id != self.symbol.name() {
+                    return Ok(());
+                }
+
+                let indicative_price = match auction.open_price {
+                    Some(price) => Some(self.symbol.price_tick().ticked(price)?),
+                    None => None,
+                };
+                let indicative_size = match auction.open_size {
+                    Some(size) => Some(self.symbol.size_tick().ticked(size)?),
+                    None => None,
+                };
+
+                self.auction = true;
+                out.unbounded_send(Notification::AuctionUpdate(AuctionUpdate {
+                    phase: Some(auction.auction_state.to_owned()),
+                    indicative_price,
+                    indicative_size,
+                }.timestamped())).unwrap();
+            }
+
             "error" => {
                 let error: GdaxError<'_> = serde_json::from_str(json)?;
                 bail!("{}: {:?}", error.message, error.reason);
@@ -341,6 +505,8 @@ impl wss::HandlerImpl for HandlerImpl {
                 name: "heartbeat",
                 product_ids: &product_ids,
             },
+            GdaxChannel::Channel("status"),
+            GdaxChannel::Channel("auctionfeed"),
         ];
 
         let auth = self.keys.as_ref().map(|keys| {
@@ -379,4 +545,34 @@ impl wss::HandlerImpl for HandlerImpl {
     fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
         self.parse_message(text, out)
     }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The `matches` and `user` channels carry both trades and order updates, so
+        // `TRADES` and `ORDERS` only act as filters. On the other hand, subscribing
+        // to `level2` again yields a fresh snapshot.
+        let type_ = match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => "subscribe",
+            (true, false) => "unsubscribe",
+            _ => return Ok(()),
+        };
+
+        let subscription = GdaxSubscription {
+            type_,
+            product_ids: &[self.symbol.name()],
+            channels: vec![GdaxChannel::Channel("level2")],
+            auth: None,
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `Subscription`: `{}`", err);
+            }
+        }
+    }
 }
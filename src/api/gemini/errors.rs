@@ -0,0 +1,175 @@
+//! A module defining error types specific to Gemini.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Gemini REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Gemini error reason, e.g. `InsufficientFunds`: see API documentation.
+    pub error_reason: Option<String>,
+
+    /// Description of the error.
+    pub error_msg: Option<String>,
+}
+
+impl RestError {
+    fn has_reason(&self, reasons: &[&str]) -> bool {
+        self.error_reason.as_ref()
+            .map(|reason| reasons.contains(&reason.as_str()))
+            .unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_reason(&["OrderNotFound"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_reason(&["InsufficientFunds"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error_msg) = &self.error_msg {
+            write!(f, ": `{}`", error_msg)?;
+        }
+        if let Some(error_reason) = &self.error_reason {
+            write!(f, " (error_reason = {})", error_reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Gemini reports errors with `"result": "error"` in the response body, along with
+    /// a `reason` and a `message`.
+    pub(super) fn from_gemini_error(status: StatusCode, gemini_error: Option<(String, String)>)
+        -> Self
+    {
+        let kind = match &gemini_error {
+            Some((reason, _)) => RestErrorKind::from_reason(reason)
+                .unwrap_or_else(|| RestErrorKind::from_status_code(status)),
+            None => RestErrorKind::from_status_code(status),
+        };
+
+        RestError {
+            kind,
+            error_reason: gemini_error.as_ref().map(|error| error.0.clone()),
+            error_msg: gemini_error.map(|error| error.1),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Gemini error reason to a Gemini error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key, signature or nonce, or missing role for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Gemini. See Gemini API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Gemini side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            // Rejected requests, along with an error `reason`.
+            StatusCode::OK | StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_reason(reason: &str) -> Option<Self> {
+        use self::RestErrorKind::*;
+        let kind = match reason {
+            "RateLimit" | "RateLimited" => TooManyRequests,
+            "InvalidSignature" |
+            "InvalidNonce" |
+            "InvalidApiKey" |
+            "MissingApikeyHeader" |
+            "MissingPayloadHeader" |
+            "MissingSignatureHeader" |
+            "MissingRole" |
+            "AccountClosed" => Unauthorized,
+            "System" => InternalError,
+            "Maintenance" => ServiceUnavailable,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
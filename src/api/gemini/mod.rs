@@ -0,0 +1,229 @@
+//! Implementation of `ApiClient` for the Gemini API.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Gemini key pair: api key + secret key.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+impl Keys {
+    /// Private requests, including the opening handshake of the order events feed, carry
+    /// their parameters as a base64 JSON payload, signed with a hex HMAC-SHA384.
+    /// Return the headers to send along.
+    fn signed_headers(&self, payload: &str) -> Vec<(&'static str, String)> {
+        let payload = base64::encode(payload);
+        let mut signer = Signer::new(MessageDigest::sha384(), &self.secret_key).unwrap();
+        signer.update(payload.as_bytes()).unwrap();
+        let signature = hex::encode(&signer.sign_to_vec().unwrap());
+
+        vec![
+            ("X-GEMINI-APIKEY", self.api_key.clone()),
+            ("X-GEMINI-PAYLOAD", payload),
+            ("X-GEMINI-SIGNATURE", signature),
+        ]
+    }
+}
+
+/// Return the next nonce, which must be strictly increasing across all the private
+/// requests performed with a given key.
+fn next_nonce(nonce: &Mutex<u64>) -> u64 {
+    let mut last = nonce.lock().unwrap();
+    *last = std::cmp::max(*last + 1, timestamp_ms());
+    *last
+}
+
+/// A Gemini API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// Last nonce sent along a private request, shared with the order events feed.
+    nonce: Arc<Mutex<u64>>,
+
+    /// client order id => server order id, since Gemini only cancels orders by their
+    /// server order id
+    order_ids: Arc<CHashMap<String, u64>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Params {
+    /// Return the parameters of the Gemini sandbox, where keys are generated at
+    /// `https://exchange.sandbox.gemini.com`.
+    ///
+    /// # Note
+    /// `system_status` still reports the status of the production exchange.
+    pub fn gemini_sandbox() -> Self {
+        Params {
+            streaming_endpoint: "wss://api.sandbox.gemini.com".to_owned(),
+            rest_endpoint: "https://api.sandbox.gemini.com".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new Gemini API client with given `params`. If `key_pair` is not `None`,
+    /// this will enable performing requests to the REST API and will forward the order
+    /// events feed.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Gemini. The ticks
+    /// are only available symbol by symbol, so this performs one request per symbol.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            nonce: Arc::new(Mutex::new(0)),
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+        debug!("requesting symbols");
+        let names = runtime.block_on(client.get_symbol_names())?;
+        for name in names {
+            if let Some(symbol) = runtime.block_on(client.get_symbol(&name))? {
+                client.symbols.insert(symbol.name().to_lowercase(), symbol);
+            }
+        }
+        debug!("received symbols");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Gemini
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.cancel_impl(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(Ok(().timestamped()).into_future())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Gemini accepts client order ids of at most 100 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 100 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use serde::Serialize;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::api::gemini::{Client, next_nonce};
+use crate::api::gemini::errors::RestError;
+
+/// Gemini publishes the status of its services on a Statuspage instance, which is
+/// not part of the REST API.
+const STATUS_ENDPOINT: &str = "https://status.gemini.com/api/v2/status.json";
+
+/// Ticks per unit used for balances: Gemini amounts have at most 8 decimals for most
+/// currencies, extra decimals are truncated.
+const AMOUNT_TICK: TickUnit = 100_000_000;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiStatus {
+    result: Option<String>,
+    reason: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+/// Parameters of a private request, sent in the `X-GEMINI-PAYLOAD` header.
+struct GeminiPayload<T> {
+    request: String,
+    nonce: String,
+    #[serde(flatten)]
+    params: T,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GeminiNoParams {}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GeminiOrder<'a> {
+    client_order_id: &'a str,
+    symbol: &'a str,
+    amount: &'a str,
+    price: &'a str,
+    side: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    options: Vec<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiOrderAck<'a> {
+    order_id: &'a str,
+    timestampms: Timestamp,
+    is_cancelled: bool,
+    reason: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GeminiCancel {
+    order_id: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiBalance<'a> {
+    currency: &'a str,
+    amount: &'a str,
+    available: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct GeminiSymbol<'a> {
+    symbol: &'a str,
+    tick_size: f64,
+    quote_increment: f64,
+    status: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiStatusIndicator<'a> {
+    indicator: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiStatusPage<'a> {
+    #[serde(borrow)]
+    status: GeminiStatusIndicator<'a>,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+/// Increments are sent as JSON numbers, e.g. `1e-8`.
+fn convert_gemini_increment(increment: f64) -> Option<Tick> {
+    Tick::tick_size(&format!("{:.12}", increment))
+}
+
+impl Client {
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        payload: Option<String>,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let address = format!("{}/{}", self.params.rest_endpoint, endpoint);
+
+        let mut request = Request::builder();
+
+        if let (Some(keys), Some(payload)) = (self.keys.as_ref(), payload) {
+            for (name, value) in keys.signed_headers(&payload) {
+                request.header(name, value.as_bytes());
+            }
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"text/plain"[..])
+            .header("Cache-Control", &b"no-cache"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(hyper::Body::empty()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let gemini_status: Option<GeminiStatus> = serde_json::from_slice(&body).ok();
+            let failed = gemini_status.as_ref()
+                .and_then(|gemini_status| gemini_status.result.as_ref())
+                .map(|result| result == "error")
+                .unwrap_or(false);
+
+            if status != hyper::StatusCode::OK || failed {
+                let gemini_error = gemini_status.and_then(|gemini_status| {
+                    Some((gemini_status.reason?, gemini_status.message.unwrap_or_default()))
+                });
+                let error = RestError::from_gemini_error(status, gemini_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    /// Private endpoints are reached through POST requests, the parameters being sent
+    /// in a signed payload along with the endpoint and a nonce.
+    fn private_request<K: api::errors::ErrorKind, T: Serialize>(&self, endpoint: &str, params: T)
+        -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let payload = GeminiPayload {
+            request: format!("/{}", endpoint),
+            nonce: next_nonce(&self.nonce).to_string(),
+            params,
+        };
+        let payload = serde_json::to_string(&payload).expect("invalid json");
+        self.request(endpoint, Method::POST, Some(payload))
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Gemini");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Gemini");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        // Gemini accepts at most one execution option.
+        let option = match (order.type_, order.time_in_force) {
+            (OrderType::Limit, TimeInForce::GoodTilCanceled) => None,
+            (OrderType::Limit, TimeInForce::ImmediateOrCancel) => Some("immediate-or-cancel"),
+            (OrderType::Limit, TimeInForce::FillOrKilll) => Some("fill-or-kill"),
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => Some("maker-or-cancel"),
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by Gemini",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // A client order id is always sent, so that notifications can be matched to the
+        // order before the REST response is received.
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let order_body = GeminiOrder {
+            client_order_id: &order_id,
+            symbol: symbol.name(),
+            amount: size.borrow(),
+            price: price.borrow(),
+            side: order.side.as_str(),
+            type_: "exchange limit",
+            options: option.into_iter().collect(),
+        };
+
+        let order_ids = self.order_ids.clone();
+        let fut = self.private_request("v1/order/new", order_body).and_then(move |body| {
+            let ack: GeminiOrderAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Maker-or-cancel orders which would take liquidity are canceled right away.
+            if ack.is_cancelled && ack.reason == Some("MakerOrCancelWouldTake") {
+                Err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::Specific(
+                            api::errors::OrderErrorKind::WouldTakeLiquidity
+                        ).into()
+                    )
+                )?;
+            }
+
+            let id = ack.order_id.parse()
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            order_ids.insert(order_id.clone(), id);
+            debug!("insert order id {} (from REST)", order_id);
+
+            Ok(OrderAck {
+                order_id,
+            }.with_timestamp(ack.timestampms))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        // Orders inserted from elsewhere are known by their server order id.
+        let order_id = match self.order_ids.get(&cancel.order_id) {
+            Some(id) => *id,
+            None => match cancel.order_id.parse() {
+                Ok(id) => id,
+                Err(_) => {
+                    warn!("called `cancel` with a not yet inserted order id");
+                    return Box::new(futures::future::err(
+                        api::errors::ApiError::RestError(
+                            api::errors::RestErrorKind::Specific(
+                                api::errors::CancelErrorKind::UnknownOrder
+                            ).into()
+                        )
+                    ));
+                }
+            },
+        };
+
+        let fut = self.private_request("v1/order/cancel", GeminiCancel { order_id })
+            .and_then(|_| Ok(CancelAck.timestamped()));
+        Box::new(fut)
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.private_request("v1/balances", GeminiNoParams {}).and_then(|body| {
+            let gemini_balances: Vec<GeminiBalance<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let tick = Tick::new(AMOUNT_TICK);
+            let mut balances = Balances::new();
+            for balance in gemini_balances {
+                let locked = tick.ticked(balance.amount)
+                    .and_then(|amount| {
+                        let available = tick.ticked(balance.available)?;
+                        tick.unticked(amount.saturating_sub(available))
+                    })
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                balances.insert(balance.currency.to_owned(), Balance {
+                    free: balance.available.to_owned(),
+                    locked,
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = STATUS_ENDPOINT.parse().expect("invalid address");
+
+        self.http_client.get(address).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError).and_then(|(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let error = RestError::from_gemini_error(status, None);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+
+            let page: GeminiStatusPage<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match page.status.indicator {
+                "none" => ExchangeStatus::Normal,
+                "minor" => ExchangeStatus::Degraded,
+                "maintenance" => ExchangeStatus::Maintenance,
+                _ => ExchangeStatus::Outage,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    /// Return the names of the available symbols, see `get_symbol` for their details.
+    crate fn get_symbol_names(&self)
+        -> impl Future<Item = Vec<String>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v1/symbols", Method::GET, None).and_then(|body| {
+            let names: Vec<String> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(names)
+        })
+    }
+
+    /// Return the symbol named `name`, or `None` if it is not open for trading or if
+    /// its ticks cannot be read.
+    crate fn get_symbol(&self, name: &str)
+        -> impl Future<Item = Option<Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        let endpoint = format!("v1/symbols/details/{}", name);
+
+        self.request(&endpoint, Method::GET, None).and_then(|body| {
+            let gemini_symbol: GeminiSymbol<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            if gemini_symbol.status != "open" {
+                return Ok(None);
+            }
+
+            let (price_tick, size_tick) = match (
+                convert_gemini_increment(gemini_symbol.quote_increment),
+                convert_gemini_increment(gemini_symbol.tick_size)
+            ) {
+                (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                _ => {
+                    error!("cannot read ticks for symbol `{}`", gemini_symbol.symbol);
+                    return Ok(None);
+                }
+            };
+
+            let symbol = Symbol::new(gemini_symbol.symbol, price_tick, size_tick);
+            if symbol.is_none() {
+                error!("symbol name too long: `{}`", gemini_symbol.symbol);
+            }
+            Ok(symbol)
+        })
+    }
+}
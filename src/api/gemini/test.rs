@@ -0,0 +1,72 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::gemini::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTCUSD", Tick::new(100), Tick::new(100_000_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+/// The initial snapshot carries no timestamp.
+const SNAPSHOT: &str = r#"{"type":"update","eventId":5375461993,"socket_sequence":0,"events":[
+    {"type":"change","reason":"initial","price":"3641.61","delta":"0.83372051",
+        "remaining":"0.83372051","side":"bid"},
+    {"type":"change","reason":"initial","price":"3641.62","delta":"4.072",
+        "remaining":"4.072","side":"ask"}
+]}"#;
+
+#[test]
+fn book_snapshot_and_update() {
+    let heartbeat = r#"{"type":"heartbeat","socket_sequence":1}"#;
+    let update = r#"{"type":"update","eventId":5375503736,"timestamp":1547760288,
+        "timestampms":1547760288001,"socket_sequence":2,"events":[
+        {"type":"trade","tid":5375503736,"price":"3641.61","amount":"0.5","makerSide":"bid"},
+        {"type":"change","side":"bid","price":"3641.61","remaining":"0.33372051",
+            "delta":"-0.5","reason":"trade"}
+    ]}"#;
+
+    let notifs = parse(&[SNAPSHOT, heartbeat, update]);
+    assert_eq!(notifs.len(), 2);
+    match &notifs[0] {
+        Notification::LimitUpdates(updates) => {
+            assert_eq!(updates.iter().map(|u| **u).collect::<Vec<_>>(), vec![
+                LimitUpdate::new(364_161, 83_372_051, Side::Bid),
+                LimitUpdate::new(364_162, 407_200_000, Side::Ask),
+            ]);
+        }
+        other => panic!("expected limit updates, got {:?}", other),
+    }
+    match &notifs[1] {
+        Notification::LimitUpdates(updates) => {
+            assert_eq!(updates.len(), 1);
+            assert_eq!(*updates[0], LimitUpdate::new(364_161, 33_372_051, Side::Bid));
+            assert_eq!(updates[0].timestamp(), 1_547_760_288_001);
+        }
+        other => panic!("expected limit updates, got {:?}", other),
+    }
+}
+
+#[test]
+#[should_panic(expected = "socket sequence gap")]
+fn sequence_gap() {
+    parse(&[SNAPSHOT, r#"{"type":"heartbeat","socket_sequence":2}"#]);
+}
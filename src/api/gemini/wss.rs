@@ -0,0 +1,432 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use std::sync::{Arc, Mutex};
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::gemini::{Keys, Client, next_nonce};
+
+/// Endpoint of the order events feed, also sent in the signed payload.
+const ORDER_EVENTS: &str = "/v1/order/events";
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        // Gemini serves the market data and the order events on distinct connections,
+        // whose parameters are set in the address.
+        if let Some(keys) = self.keys.clone() {
+            let address = format!(
+                "{}{}?symbolFilter={}",
+                self.params.streaming_endpoint,
+                ORDER_EVENTS,
+                symbol.name().to_lowercase(),
+            );
+            let snd = snd.clone();
+            let nonce = self.nonce.clone();
+            let order_ids = self.order_ids.clone();
+            let public_control = control.clone();
+            thread::spawn(move || {
+                // The flags of the private connection are those of the public one,
+                // see `PrivateHandlerImpl::control`.
+                let private_control = public_control.linked();
+
+                debug!("initiating WebSocket connection at {}", address);
+                if let Err(err) = ws::connect(address, |out| {
+                    wss::Handler::new(
+                        out,
+                        snd.clone(),
+                        private_control.clone(),
+                        wss::KeepAlive::True,
+                        PrivateHandlerImpl {
+                            symbol,
+                            keys: keys.clone(),
+                            nonce: nonce.clone(),
+                            control: public_control.clone(),
+                            order_ids: order_ids.clone(),
+                        }
+                    )
+                })
+                {
+                    error!("WebSocket connection terminated with error: `{}`", err);
+                }
+            });
+        }
+
+        let address = format!(
+            "{}/v1/marketdata/{}?heartbeat=true",
+            self.params.streaming_endpoint,
+            symbol.name(),
+        );
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", address);
+
+            if let Err(err) = ws::connect(address, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        last_sequence: None,
+                        order_book: OrderBook::new(),
+                        send_book: false,
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+fn convert_gemini_side(side: &str) -> Result<Side, failure::Error> {
+    let side = match side {
+        "bid" | "buy" => Side::Bid,
+        "ask" | "sell" => Side::Ask,
+        other => bail!("wrong side: `{}`", other),
+    };
+    Ok(side)
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiMessage<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    socket_sequence: u64,
+
+    /// Missing from the initial snapshot.
+    timestampms: Option<Timestamp>,
+
+    #[serde(borrow, default)]
+    events: Vec<GeminiEvent<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct GeminiEvent<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    price: Option<&'a str>,
+    side: Option<&'a str>,
+    remaining: Option<&'a str>,
+    amount: Option<&'a str>,
+    makerSide: Option<&'a str>,
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+
+    /// Sequence number of the last message, which must increase by one with each
+    /// message, heartbeats included.
+    last_sequence: Option<u64>,
+
+    /// Local copy of the order book: the market data feed cannot be unsubscribed from,
+    /// so the book is maintained even when `ORDER_BOOK` is not set, and sent in full
+    /// once it is set again.
+    order_book: OrderBook,
+
+    /// Set when the whole book must be sent along with the next changes.
+    send_book: bool,
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            last_sequence: None,
+            order_book: OrderBook::new(),
+            send_book: false,
+        }
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: GeminiMessage<'_> = serde_json::from_str(json)?;
+
+        if let Some(last_sequence) = self.last_sequence {
+            if message.socket_sequence != last_sequence + 1 {
+                panic!(
+                    "socket sequence gap: expected {}, received {}",
+                    last_sequence + 1,
+                    message.socket_sequence
+                );
+            }
+        }
+        self.last_sequence = Some(message.socket_sequence);
+
+        if message.type_ != "update" {
+            return Ok(());
+        }
+
+        let timestamp = message.timestampms.unwrap_or_else(timestamp_ms);
+        let mut updates = Vec::new();
+        for event in message.events {
+            match event.type_ {
+                "change" => {
+                    let update = LimitUpdate {
+                        side: convert_gemini_side(event.side.unwrap_or_default())?,
+                        price: self.symbol.price_tick().ticked(event.price.unwrap_or_default())?,
+                        size: self.symbol.size_tick().ticked(event.remaining.unwrap_or_default())?,
+                    };
+                    self.order_book.update(update);
+                    updates.push(update);
+                }
+
+                "trade" if self.flags.contains(NotificationFlags::TRADES) => {
+                    // Auction trades have no maker.
+                    let maker_side = match event.makerSide {
+                        Some("auction") => continue,
+                        side => convert_gemini_side(side.unwrap_or_default())?,
+                    };
+
+                    let trade = Notification::Trade(Trade {
+                        price: self.symbol.price_tick().ticked(event.price.unwrap_or_default())?,
+                        size: self.symbol.size_tick().ticked(event.amount.unwrap_or_default())?,
+                        maker_side,
+                    }.with_timestamp(timestamp));
+                    out.unbounded_send(trade).unwrap();
+                }
+
+                _ => (),
+            }
+        }
+
+        if !self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            return Ok(());
+        }
+
+        if mem::replace(&mut self.send_book, false) {
+            let asks = self.order_book.ask().map(|(price, size)| {
+                LimitUpdate::new(*price, *size, Side::Ask)
+            });
+            let bids = self.order_book.bid().map(|(price, size)| {
+                LimitUpdate::new(*price, *size, Side::Bid)
+            });
+            updates = asks.chain(bids).collect();
+        }
+
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.with_timestamp(timestamp))
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, _: &ws::Sender) -> ws::Result<()> {
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The subscriptions are fixed by the address, the flags only act as filters.
+        if !previous.contains(NotificationFlags::ORDER_BOOK)
+            && flags.contains(NotificationFlags::ORDER_BOOK)
+        {
+            self.send_book = true;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct GeminiPayload<'a> {
+    request: &'a str,
+    nonce: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiFill<'a> {
+    price: &'a str,
+    amount: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct GeminiOrderEvent<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    order_id: &'a str,
+    client_order_id: Option<&'a str>,
+    symbol: &'a str,
+    side: &'a str,
+    timestampms: Timestamp,
+    price: Option<&'a str>,
+    original_amount: Option<&'a str>,
+    executed_amount: Option<&'a str>,
+    remaining_amount: Option<&'a str>,
+    #[serde(borrow)]
+    fill: Option<GeminiFill<'a>>,
+}
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    keys: Keys,
+
+    /// Nonce shared with `Client`.
+    nonce: Arc<Mutex<u64>>,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// client order id => server order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, u64>>,
+}
+
+impl PrivateHandlerImpl {
+    fn ticked_size(&self, size: Option<&str>) -> Result<TickUnit, failure::Error> {
+        Ok(self.symbol.size_tick().ticked(size.unwrap_or("0"))?)
+    }
+
+    /// Return the order id specified by the user, which defaults to the server order id
+    /// in case it was left unspecified.
+    fn order_id(&self, server_order_id: &str, client_order_id: Option<&str>)
+        -> Result<String, failure::Error>
+    {
+        let client_order_id = match client_order_id {
+            Some(client_order_id) if !client_order_id.is_empty() => client_order_id,
+            _ => return Ok(server_order_id.to_owned()),
+        };
+
+        // Don't forget to update the concurrent map `client order id => server order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(client_order_id) {
+            self.order_ids.insert(client_order_id.to_owned(), server_order_id.parse()?);
+            debug!("insert order id {} (from WSS)", client_order_id);
+        }
+        Ok(client_order_id.to_owned())
+    }
+
+    fn parse_order(&self, event: &GeminiOrderEvent<'_>, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        if !event.symbol.eq_ignore_ascii_case(self.symbol.name()) {
+            return Ok(());
+        }
+
+        let order_id = self.order_id(event.order_id, event.client_order_id)?;
+
+        match event.type_ {
+            "accepted" => {
+                let order = OrderConfirmation {
+                    order_id,
+                    price: self.symbol.price_tick().ticked(event.price.unwrap_or("0"))?,
+                    size: self.ticked_size(event.original_amount)?,
+                    side: convert_gemini_side(event.side)?,
+                }.with_timestamp(event.timestampms);
+                out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+            }
+
+            "fill" => {
+                let fill = match event.fill {
+                    Some(fill) => fill,
+                    None => bail!("fill event without fill: `{}`", event.order_id),
+                };
+
+                let update = OrderUpdate {
+                    order_id,
+                    consumed_size: self.symbol.size_tick().ticked(fill.amount)?,
+                    remaining_size: self.ticked_size(event.remaining_amount)?,
+                    consumed_price: self.symbol.price_tick().ticked(fill.price)?,
+                    commission: 0,
+                }.with_timestamp(event.timestampms);
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+            }
+
+            "cancelled" => {
+                let expiration = OrderExpiration {
+                    order_id,
+                    filled_size: Some(self.ticked_size(event.executed_amount)?),
+                    remaining_size: Some(self.ticked_size(event.remaining_amount)?),
+                }.with_timestamp(event.timestampms);
+                out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+            }
+
+            // `initial`, `booked`, `rejected`, `cancel_rejected` and `closed`.
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn parse_message(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        // Order events are sent in arrays, while `subscription_ack` and `heartbeat`
+        // messages are sent alone.
+        if !json.starts_with('[') {
+            return Ok(());
+        }
+
+        if !self.control.flags().contains(NotificationFlags::ORDERS) {
+            return Ok(());
+        }
+
+        let events: Vec<GeminiOrderEvent<'_>> = serde_json::from_str(json)?;
+        for event in &events {
+            self.parse_order(event, out)?;
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, _: &ws::Sender) -> ws::Result<()> {
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+
+    fn handshake_headers(&mut self) -> Vec<(String, Vec<u8>)> {
+        let payload = GeminiPayload {
+            request: ORDER_EVENTS,
+            nonce: next_nonce(&self.nonce).to_string(),
+        };
+        let payload = serde_json::to_string(&payload).expect("invalid json");
+
+        self.keys.signed_headers(&payload)
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value.into_bytes()))
+            .collect()
+    }
+}
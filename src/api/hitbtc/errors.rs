@@ -36,6 +36,10 @@ impl api::errors::ErrorKinded<!> for RestError {
             return api::errors::RestErrorKind::TooManyRequests;
         }
 
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
         if self.kind == RestErrorKind::Timeout {
             return api::errors::RestErrorKind::UnknownStatus;
         }
@@ -79,6 +83,12 @@ impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
     }
 }
 
+impl api::errors::ErrorKinded<api::errors::ReplaceErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::ReplaceErrorKind> {
+        api::errors::replace_kind(self)
+    }
+}
+
 impl fmt::Display for RestError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}: `{}` (", self.kind, self.error_msg)?;
@@ -13,16 +13,23 @@ use crate::api::{
     Params,
     ApiClient,
     GenerateOrderId,
-    Notification,
     NotificationFlags,
     Order,
     OrderAck,
     Cancel,
     CancelAck,
+    OrderStatus,
+    Fill,
+    Replace,
     Balances,
+    ExchangeStatus,
+    Trade,
 };
+use crate::api::stream::NotificationStream;
 use crate::api::symbol::{Symbol, WithSymbol};
-use crate::api::timestamp::{Timestamped, IntoTimestamped};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::Bar;
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// An HitBTC key pair: public key + secret key.
@@ -56,6 +63,17 @@ pub struct Client {
     http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
 }
 
+impl Params {
+    /// Return the parameters of the HitBTC demo exchange, where keys are generated at
+    /// `https://demo.hitbtc.com`.
+    pub fn hitbtc_demo() -> Self {
+        Params {
+            streaming_endpoint: "wss://api.demo.hitbtc.com".to_owned(),
+            rest_endpoint: "https://api.demo.hitbtc.com".to_owned(),
+        }
+    }
+}
+
 impl Client {
     /// Create a new HitBTC API client with given `params`. If `key_pair` is not
     /// `None`, this will enable performing requests to the REST API and will forward
@@ -94,7 +112,11 @@ impl Client {
 }
 
 impl ApiClient for Client {
-    type Stream = futures::sync::mpsc::UnboundedReceiver<Notification>;
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Hitbtc
+    }
 
     fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
         self.symbols.get(&symbol.to_lowercase()).cloned()
@@ -107,12 +129,34 @@ impl ApiClient for Client {
     fn order(&self, order: WithSymbol<&Order>)
         -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
     {
-        Box::new(self.order_impl(order))
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn supports_atomic_replace(&self) -> bool {
+        true
+    }
+
+    fn replace(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::ReplaceError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.replace_impl(replace)
     }
 
     fn cancel(&self, cancel: WithSymbol<&Cancel>)
         -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
     {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
         Box::new(self.cancel_impl(cancel))
     }
 
@@ -122,11 +166,53 @@ impl ApiClient for Client {
         Box::new(Ok(().timestamped()).into_future())
     }
 
+    fn order_status(&self, symbol: Symbol, order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.order_status_impl(symbol, order_id))
+    }
+
+    fn my_trades(&self, symbol: Symbol, since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.my_trades_impl(symbol, since))
+    }
+
+    fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.recent_trades_impl(symbol, limit))
+    }
+
+    fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = api::errors::Error> + Send + 'static>
+    {
+        self.recent_bars_impl(symbol, interval, limit)
+    }
+
     fn balances(&self)
         -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
     {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
         Box::new(self.balances_impl())
     }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
 }
 
 impl GenerateOrderId for Client {
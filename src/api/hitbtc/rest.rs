@@ -1,9 +1,9 @@
 use serde_derive::Deserialize;
-use failure::Fail;
+use failure::{Fail, bail};
 use futures::prelude::*;
 use std::collections::HashMap;
 use hyper::Method;
-use log::error;
+use log::{warn, error};
 use crate::Side;
 use crate::tick::Tick;
 use crate::api::{
@@ -14,13 +14,20 @@ use crate::api::{
     OrderAck,
     Cancel,
     CancelAck,
+    Replace,
+    OrderState,
+    OrderStatus,
+    Fill,
+    ExchangeStatus,
+    Trade,
 };
-use crate::api::timestamp::{convert_str_timestamp, Timestamped, IntoTimestamped};
+use crate::api::timestamp::{convert_str_timestamp, format_timestamp, Timestamp, Timestamped, IntoTimestamped};
 use crate::api::query_string::QueryString;
 use crate::api::errors::ErrorKinded;
 use crate::api::symbol::{Symbol, WithSymbol};
 use crate::api::hitbtc::Client;
 use crate::api::hitbtc::errors::RestError;
+use crate::data::resample::Bar;
 
 trait AsStr {
     fn as_str(self) -> &'static str;
@@ -40,6 +47,9 @@ impl AsStr for OrderType {
         match self {
             OrderType::Limit => "limit",
             OrderType::LimitMaker => "limit",
+            OrderType::Market => "market",
+            OrderType::StopLimit => "stopLimit",
+            OrderType::StopMarket => "stopMarket",
         }
     }
 }
@@ -50,6 +60,7 @@ impl AsStr for TimeInForce {
             TimeInForce::GoodTilCanceled => "GTC",
             TimeInForce::FillOrKilll => "FOK",
             TimeInForce::ImmediateOrCancel => "IOC",
+            TimeInForce::GoodTilDate(_) => "GTD",
         }
     }
 }
@@ -68,6 +79,28 @@ struct HitBtcCancelAck<'a> {
     updatedAt: &'a str,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct HitBtcOrder<'a> {
+    clientOrderId: &'a str,
+    status: &'a str,
+    cumQuantity: &'a str,
+    avgPrice: Option<&'a str>,
+    updatedAt: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct HitBtcAccountTrade<'a> {
+    id: u64,
+    clientOrderId: &'a str,
+    side: &'a str,
+    quantity: &'a str,
+    price: &'a str,
+    fee: &'a str,
+    timestamp: &'a str,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct HitBtcSymbol<'a> {
@@ -83,12 +116,140 @@ struct HitBtcBalance<'a> {
     reserved: &'a str,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HitBtcTrade<'a> {
+    price: &'a str,
+    quantity: &'a str,
+    side: &'a str,
+    timestamp: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HitBtcCandle<'a> {
+    timestamp: &'a str,
+    open: &'a str,
+    close: &'a str,
+    min: &'a str,
+    max: &'a str,
+    volume: &'a str,
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 struct HitBtcError<'a> {
     #[serde(borrow)]
     error: crate::api::hitbtc::errors::HitBtcRestError<'a>,
 }
 
+/// Maximum number of entries returned by `api/2/public/trades` and `api/2/public/candles`.
+const HISTORY_LIMIT: usize = 1000;
+
+/// Name of the candle period lasting `interval` milliseconds, if supported.
+fn candle_period(interval: Timestamp) -> Option<&'static str> {
+    const MINUTE: Timestamp = 60 * 1000;
+    const HOUR: Timestamp = 60 * MINUTE;
+    const DAY: Timestamp = 24 * HOUR;
+
+    let name = match interval {
+        i if i == MINUTE => "M1",
+        i if i == 3 * MINUTE => "M3",
+        i if i == 5 * MINUTE => "M5",
+        i if i == 15 * MINUTE => "M15",
+        i if i == 30 * MINUTE => "M30",
+        i if i == HOUR => "H1",
+        i if i == 4 * HOUR => "H4",
+        i if i == DAY => "D1",
+        i if i == 7 * DAY => "D7",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn convert_trade(symbol: Symbol, trade: HitBtcTrade<'_>)
+    -> Result<Timestamped<Trade>, failure::Error>
+{
+    let maker_side = match trade.side {
+        "buy" => Side::Bid,
+        "sell" => Side::Ask,
+        other => bail!("wrong side: `{}`", other),
+    };
+
+    Ok(Trade {
+        price: symbol.price_tick().ticked(trade.price)?,
+        size: symbol.size_tick().ticked(trade.quantity)?,
+        maker_side,
+    }.with_timestamp(convert_str_timestamp(trade.timestamp)?))
+}
+
+fn convert_fill(symbol: Symbol, trade: HitBtcAccountTrade<'_>)
+    -> Result<Timestamped<Fill>, failure::Error>
+{
+    // Unlike for public trades, this is the side of the filled order.
+    let side = match trade.side {
+        "buy" => Side::Bid,
+        "sell" => Side::Ask,
+        other => bail!("wrong side: `{}`", other),
+    };
+
+    Ok(Fill {
+        trade_id: trade.id.to_string(),
+        order_id: trade.clientOrderId.to_owned(),
+        side,
+        price: symbol.price_tick().ticked(trade.price)?,
+        size: symbol.size_tick().ticked(trade.quantity)?,
+        fee: trade.fee.to_owned(),
+        // Neither reported by HitBTC.
+        fee_asset: None,
+        liquidity: None,
+    }.with_timestamp(convert_str_timestamp(trade.timestamp)?))
+}
+
+fn convert_order_status(symbol: Symbol, order: HitBtcOrder<'_>)
+    -> Result<Timestamped<OrderStatus>, failure::Error>
+{
+    let state = match order.status {
+        // Stop orders are `suspended` until triggered.
+        "new" | "suspended" => OrderState::New,
+        "partiallyFilled" => OrderState::PartiallyFilled,
+        "filled" => OrderState::Filled,
+        "canceled" => OrderState::Canceled,
+        "expired" => OrderState::Expired,
+        other => bail!("wrong order status: `{}`", other),
+    };
+
+    let filled_size = symbol.size_tick().ticked(order.cumQuantity)?;
+    let average_price = match order.avgPrice {
+        // The average price may be more precise than the price tick.
+        Some(average_price) if filled_size > 0 => {
+            Some(symbol.price_tick().rounded(average_price.parse()?))
+        }
+        _ => None,
+    };
+
+    Ok(OrderStatus {
+        order_id: order.clientOrderId.to_owned(),
+        state,
+        filled_size,
+        average_price,
+        // Only reported with the trades of the order.
+        commission: None,
+    }.with_timestamp(convert_str_timestamp(order.updatedAt)?))
+}
+
+fn convert_candle(symbol: Symbol, candle: HitBtcCandle<'_>)
+    -> Result<Timestamped<Bar>, failure::Error>
+{
+    let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+    Ok(Bar {
+        open: price_tick.ticked(candle.open)?,
+        high: price_tick.ticked(candle.max)?,
+        low: price_tick.ticked(candle.min)?,
+        close: price_tick.ticked(candle.close)?,
+        volume: size_tick.ticked(candle.volume)?,
+        // Not provided by HitBTC.
+        count: 0,
+    }.with_timestamp(convert_str_timestamp(candle.timestamp)?))
+}
+
 impl Client {
     fn request<K: api::errors::ErrorKind>(
         &self,
@@ -143,24 +304,89 @@ impl Client {
     }
 
     crate fn order_impl(&self, order: WithSymbol<&Order>)
-        -> impl Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
     {
         use std::borrow::Borrow;
 
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.quote_size {
+            warn!("called `order` with a quote size, which is not supported by HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() && order.trigger_price.is_none() {
+            warn!("called `order` with a stop order but no trigger price");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if !order.valid_display_size(order.symbol()) {
+            warn!("called `order` with a display size not complying with the iceberg rules of `{}`", order.symbol().name());
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
         let mut query = QueryString::new();
         let symbol = order.symbol();
         query.push_str("symbol", symbol.name());
         query.push_str("side", order.side.as_str());
         query.push_str("type", order.type_.as_str());
-        query.push_str("timeInForce", order.time_in_force.as_str());
+
+        // Market orders only accept `IOC` and `FOK`.
+        let time_in_force = match (order.type_, order.time_in_force) {
+            (OrderType::Market, TimeInForce::GoodTilCanceled)
+                | (OrderType::Market, TimeInForce::GoodTilDate(_))
+                | (OrderType::StopMarket, TimeInForce::GoodTilCanceled) => {
+                TimeInForce::ImmediateOrCancel
+            }
+            (_, time_in_force) => time_in_force,
+        };
+        query.push_str("timeInForce", time_in_force.as_str());
+        if let Some(expiration) = time_in_force.expiration() {
+            query.push_str("expireTime", &format_timestamp(expiration));
+        }
         query.push_str(
             "quantity",
             order.size.unticked(symbol.size_tick()).borrow() as &str
         );
-        query.push_str(
-            "price",
-            order.price.unticked(symbol.price_tick()).borrow() as &str
-        );
+        if order.type_ != OrderType::Market && order.type_ != OrderType::StopMarket {
+            query.push_str(
+                "price",
+                order.price.unticked(symbol.price_tick()).borrow() as &str
+            );
+        }
+        match &order.trigger_price {
+            Some(trigger_price) if order.type_.is_stop() => {
+                query.push_str(
+                    "stopPrice",
+                    trigger_price.unticked(symbol.price_tick()).borrow() as &str
+                );
+            }
+            _ => (),
+        }
+        if let Some(display_size) = &order.display_size {
+            query.push_str(
+                "displayQuantity",
+                display_size.unticked(symbol.size_tick()).borrow() as &str
+            );
+        }
 
         if let Some(order_id) = &order.order_id {
             query.push_str("clientOrderId", order_id);
@@ -170,11 +396,23 @@ impl Client {
             query.push_str("postOnly", "true");
         }
 
-        self.request("api/2/order", Method::POST, query).and_then(|body| {
+        let post_only = order.is_post_only();
+        let fut = self.request("api/2/order", Method::POST, query).and_then(move |body| {
             let ack: HitBtcOrderAck<'_> = serde_json::from_slice(&body)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
 
+            // Post only orders which would cross the book are expired right away.
+            if post_only && ack.status == "expired" {
+                Err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::Specific(
+                            api::errors::OrderErrorKind::WouldTakeLiquidity
+                        ).into()
+                    )
+                )?;
+            }
+
             let timestamp = convert_str_timestamp(ack.createdAt)
                 .map_err(api::errors::RequestError::new)
                 .map_err(api::errors::ApiError::RequestError)?;
@@ -182,7 +420,8 @@ impl Client {
             Ok(OrderAck {
                 order_id: ack.clientOrderId.to_owned(),
             }.with_timestamp(timestamp))
-        })
+        });
+        Box::new(fut)
     }
 
     crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
@@ -204,6 +443,109 @@ impl Client {
         })
     }
 
+    /// The order is amended in place, keeping its side and type.
+    crate fn replace_impl(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::ReplaceError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        let order = replace.order();
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id,
+            None => {
+                warn!("called `replace` without an id for the new order, which HitBTC requires");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let mut query = QueryString::new();
+        let symbol = replace.symbol();
+        query.push_str(
+            "quantity",
+            order.size.unticked(symbol.size_tick()).borrow() as &str
+        );
+        if order.type_ != OrderType::Market && order.type_ != OrderType::StopMarket {
+            query.push_str(
+                "price",
+                order.price.unticked(symbol.price_tick()).borrow() as &str
+            );
+        }
+        query.push_str("requestClientId", order_id);
+
+        let endpoint = format!("api/2/order/{}", replace.cancel().order_id());
+        let fut = self.request(&endpoint, Method::PATCH, query).and_then(|body| {
+            let ack: HitBtcOrderAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ReplaceError::RequestError)?;
+
+            let timestamp = convert_str_timestamp(ack.createdAt)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ReplaceError::RequestError)?;
+
+            Ok(OrderAck {
+                order_id: ack.clientOrderId.to_owned(),
+            }.with_timestamp(timestamp))
+        });
+        Box::new(fut)
+    }
+
+    crate fn order_status_impl(&self, symbol: Symbol, order_id: &str)
+        -> impl Future<Item = Timestamped<OrderStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        // The history also lists the open orders. `request` sends its query string as
+        // the body, which a GET request ignores.
+        let endpoint = format!(
+            "api/2/history/order?symbol={}&clientOrderId={}",
+            symbol.name(),
+            order_id
+        );
+
+        self.request(&endpoint, Method::GET, QueryString::new()).and_then(move |body| {
+            let orders: Vec<HitBtcOrder<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let order = match orders.into_iter().next() {
+                Some(order) => order,
+                None => return Err(api::errors::ApiError::RestError(
+                    api::errors::RestErrorKind::InvalidRequest.into()
+                )),
+            };
+
+            let status = convert_order_status(symbol, order)
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(status)
+        })
+    }
+
+    crate fn my_trades_impl(&self, symbol: Symbol, since: Timestamp)
+        -> impl Future<Item = Vec<Timestamped<Fill>>, Error = api::errors::Error> + Send + 'static
+    {
+        // `request` sends its query string as the body, which a GET request ignores.
+        let endpoint = format!(
+            "api/2/history/trades?symbol={}&sort=ASC&by=timestamp&from={}&limit={}",
+            symbol.name(),
+            since,
+            HISTORY_LIMIT
+        );
+
+        self.request(&endpoint, Method::GET, QueryString::new()).and_then(move |body| {
+            let hit_btc_trades: Vec<HitBtcAccountTrade<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let fills = hit_btc_trades.into_iter()
+                .map(|trade| convert_fill(symbol, trade))
+                .collect::<Result<_, _>>()
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(fills)
+        })
+    }
+
     crate fn balances_impl(&self)
         -> impl Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static
     {
@@ -224,6 +566,90 @@ impl Client {
         })
     }
 
+    crate fn recent_trades_impl(&self, symbol: Symbol, limit: usize)
+        -> impl Future<Item = Vec<Timestamped<Trade>>, Error = api::errors::Error> + Send + 'static
+    {
+        // `request` sends its query string as the body, which a GET request ignores.
+        let endpoint = format!(
+            "api/2/public/trades/{}?sort=DESC&limit={}",
+            symbol.name(),
+            std::cmp::min(limit, HISTORY_LIMIT)
+        );
+
+        self.request(&endpoint, Method::GET, QueryString::new()).and_then(move |body| {
+            let hit_btc_trades: Vec<HitBtcTrade<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let trades = hit_btc_trades.into_iter()
+                .rev()
+                .map(|trade| convert_trade(symbol, trade))
+                .collect::<Result<_, _>>()
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(trades)
+        })
+    }
+
+    crate fn recent_bars_impl(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = api::errors::Error> + Send + 'static>
+    {
+        let period = match candle_period(interval) {
+            Some(period) => period,
+            None => {
+                warn!("called `recent_bars` with an unsupported interval of {} ms", interval);
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::InvalidRequest.into()
+                    )
+                ));
+            }
+        };
+
+        let endpoint = format!(
+            "api/2/public/candles/{}?period={}&sort=DESC&limit={}",
+            symbol.name(),
+            period,
+            std::cmp::min(limit, HISTORY_LIMIT)
+        );
+
+        let fut = self.request(&endpoint, Method::GET, QueryString::new()).and_then(move |body| {
+            let candles: Vec<HitBtcCandle<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let bars = candles.into_iter()
+                .rev()
+                .map(|candle| convert_candle(symbol, candle))
+                .collect::<Result<_, _>>()
+                .map_err(|err| api::errors::RequestError::new(err.compat()))
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(bars)
+        });
+        Box::new(fut)
+    }
+
+    /// HitBTC does not publish a status endpoint: consider the exchange to be
+    /// operating normally as long as a light public request succeeds, and to be
+    /// experiencing an outage if the request fails on the exchange side.
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("api/2/public/currency/BTC", Method::GET, query).then(|res| {
+            match res {
+                Ok(_) => Ok(ExchangeStatus::Normal.timestamped()),
+                Err(api::errors::ApiError::RestError(ref err))
+                    if err.kind() == api::errors::RestErrorKind::OtherSide =>
+                {
+                    Ok(ExchangeStatus::Outage.timestamped())
+                }
+                Err(err) => Err(err),
+            }
+        })
+    }
+
     crate fn get_symbols(&self)
         -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
     {
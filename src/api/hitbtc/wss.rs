@@ -1,6 +1,6 @@
-use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use futures::sync::mpsc::unbounded;
 use failure::{bail, format_err};
-use std::thread;
+use std::{mem, thread};
 use serde_derive::{Deserialize, Serialize};
 use log::{debug, error};
 use crate::Side;
@@ -15,17 +15,20 @@ use crate::api::{
     OrderUpdate,
 };
 use crate::api::wss;
+use crate::api::stream::NotificationStream;
 use crate::api::symbol::Symbol;
 use crate::api::timestamp::{convert_str_timestamp, IntoTimestamped};
 use crate::api::hitbtc::{Keys, Client};
 
 impl Client {
     crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
-        -> UnboundedReceiver<Notification>
+        -> NotificationStream
     {
         let streaming_endpoint = self.params.streaming_endpoint.clone();
         let keys = self.keys.clone();
         let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
         thread::spawn(move || {
             let address = format!(
                "{}/api/2/ws",
@@ -35,20 +38,26 @@ impl Client {
             debug!("initiating WebSocket connection at {}", address);
             
             if let Err(err) = ws::connect(address, |out| {
-                wss::Handler::new(out, snd.clone(), wss::KeepAlive::False, HandlerImpl {
-                    symbol,
-                    flags,
-                    state: SubscriptionState::new(),
-                    keys: keys.clone(),
-                    last_sequence: None,
-                })
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::False,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        state: SubscriptionState::new(),
+                        keys: keys.clone(),
+                        last_sequence: None,
+                    }
+                )
             })
             {
                 error!("WebSocket connection terminated with error: `{}`", err);
             }
         });
         
-        rcv
+        NotificationStream::new(rcv, control)
     }
 }
 
@@ -162,7 +171,9 @@ struct HitBtcReportParams<'a> {
     side: &'a str,
     status: &'a str,
     quantity: &'a str,
-    price: &'a str,
+
+    /// Market orders have no price.
+    price: Option<&'a str>,
     cumQuantity: &'a str,
     #[serde(borrow)]
     tradeQuantity: Option<&'a str>,
@@ -275,7 +286,10 @@ impl HandlerImpl {
                     "new" => {
                         let order = OrderConfirmation {
                             size: self.symbol.size_tick().ticked(report.params.quantity)?,
-                            price: self.symbol.price_tick().ticked(report.params.price)?,
+                            price: match report.params.price {
+                                Some(price) => self.symbol.price_tick().ticked(price)?,
+                                None => 0,
+                            },
                             side: self.convert_hit_btc_side(report.params.side)?,
                             order_id: report.params.clientOrderId.to_owned(),
                         }.with_timestamp(timestamp);
@@ -301,8 +315,14 @@ impl HandlerImpl {
                     }
 
                     "canceled" | "expired" | "suspended" => {
+                        let size_tick = self.symbol.size_tick();
+                        let filled_size = size_tick.ticked(report.params.cumQuantity)?;
                         let expiration = OrderExpiration {
                             order_id: report.params.clientOrderId.to_owned(),
+                            filled_size: Some(filled_size),
+                            remaining_size: Some(
+                                size_tick.ticked(report.params.quantity)? - filled_size
+                            ),
                         }.with_timestamp(timestamp);
                         out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
                     }
@@ -317,35 +337,28 @@ impl HandlerImpl {
     }
 }
 
-impl wss::HandlerImpl for HandlerImpl {
-    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
-        let params = HitBtcSymbol {
-            symbol: self.symbol.name()
-        };
-
+impl HandlerImpl {
+    fn send_subscription(&self, method: &str, out: &ws::Sender) -> ws::Result<()> {
         let subscription = HitBtcSubscription {
-            method: "subscribeOrderbook",
-            params,
+            method,
+            params: HitBtcSymbol {
+                symbol: self.symbol.name()
+            },
         };
-        
-        match serde_json::to_string(&subscription) {
-            Ok(value) => out.send(value)?,
-            Err(err) => {
-                panic!("failed to serialize `HitBtcSubscription`: `{}`", err);
-            }
-        }
 
-        let subscription = HitBtcSubscription {
-            method: "subscribeTrades",
-            params,
-        };
-        
         match serde_json::to_string(&subscription) {
-            Ok(value) => out.send(value)?,
+            Ok(value) => out.send(value),
             Err(err) => {
                 panic!("failed to serialize `HitBtcSubscription`: `{}`", err);
             }
         }
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.send_subscription("subscribeOrderbook", out)?;
+        self.send_subscription("subscribeTrades", out)?;
 
         if let Some(keys) = self.keys.as_ref() {
             let auth = HitBtcAuthentication {
@@ -383,4 +396,40 @@ impl wss::HandlerImpl for HandlerImpl {
     fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
         self.parse_message(text, out)
     }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // Reports cannot be unsubscribed from, `ORDERS` only acts as a filter.
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => {
+                // A new snapshot will be sent, with a sequence number unrelated
+                // to the last one we saw.
+                self.last_sequence = None;
+                self.send_subscription("subscribeOrderbook", out)?;
+            }
+            (true, false) => {
+                self.state.order_book = false;
+                self.send_subscription("unsubscribeOrderbook", out)?;
+            }
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => self.send_subscription("subscribeTrades", out)?,
+            (true, false) => {
+                self.state.trades = false;
+                self.send_subscription("unsubscribeTrades", out)?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,181 @@
+//! A module defining error types specific to Huobi.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Huobi REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Huobi error code, e.g. `order-accountbalance-error`: see API documentation.
+    pub err_code: Option<String>,
+
+    /// Description of the error.
+    pub err_msg: Option<String>,
+}
+
+impl RestError {
+    fn has_code(&self, codes: &[&str]) -> bool {
+        self.err_code.as_ref()
+            .map(|code| codes.contains(&code.as_str()))
+            .unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[
+            "order-orderstate-error",
+            "order-queryorder-invalid",
+            "base-record-invalid",
+        ]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[
+            "account-frozen-balance-insufficient-error",
+            "order-accountbalance-error",
+            "insufficient-balance",
+        ]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(err_msg) = &self.err_msg {
+            write!(f, ": `{}`", err_msg)?;
+        }
+        if let Some(err_code) = &self.err_code {
+            write!(f, " (err-code = {})", err_code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Huobi reports most errors with an HTTP status code 200, along with
+    /// `"status": "error"`, an `err-code` and an `err-msg` in the response body.
+    pub(super) fn from_huobi_error(status: StatusCode, huobi_error: Option<(String, String)>)
+        -> Self
+    {
+        let kind = match &huobi_error {
+            Some((code, _)) => RestErrorKind::from_err_code(code)
+                .unwrap_or_else(|| RestErrorKind::from_status_code(status)),
+            None => RestErrorKind::from_status_code(status),
+        };
+
+        RestError {
+            kind,
+            err_code: huobi_error.as_ref().map(|error| error.0.clone()),
+            err_msg: huobi_error.map(|error| error.1),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Huobi error code to a Huobi error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid access key or signature, or expired timestamp.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Huobi. See Huobi API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Huobi side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            // Rejected requests, along with an `err-code`.
+            StatusCode::OK | StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_err_code(code: &str) -> Option<Self> {
+        use self::RestErrorKind::*;
+        let kind = match code {
+            "api-signature-not-valid" |
+            "api-signature-check-failed" |
+            "login-required" |
+            "invalid-access-key" |
+            "api-key-invalid" |
+            "gateway-internal-error-timestamp" => Unauthorized,
+            "too-many-request" | "1002" => TooManyRequests,
+            "base-system-error" | "system-error" => InternalError,
+            "system-maintenance" | "base-system-maintenance" => ServiceUnavailable,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
@@ -0,0 +1,222 @@
+//! Implementation of `ApiClient` for the Huobi Global API.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Huobi key pair: access key + secret key.
+pub struct KeyPair {
+    access_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(access_key: String, secret_key: String) -> Self {
+        KeyPair {
+            access_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    access_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+impl Keys {
+    /// Signed requests, including the authentication of the private WebSocket feed,
+    /// carry the base64 HMAC-SHA256 of the method, the host, the path and the sorted
+    /// query string, separated by new lines.
+    fn signature(&self, method: &str, host: &str, path: &str, query: &str) -> String {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.secret_key).unwrap();
+        let what = format!("{}\n{}\n{}\n{}", method, host, path, query);
+        signer.update(what.as_bytes()).unwrap();
+        base64::encode(&signer.sign_to_vec().unwrap())
+    }
+}
+
+/// Timestamp format expected along with signed requests, in UTC.
+fn huobi_timestamp() -> String {
+    chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Percent-encode `value` for use in a query string.
+fn url_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Host part of `address`, which is part of the signed string.
+fn host(address: &str) -> String {
+    address.parse::<hyper::Uri>()
+        .ok()
+        .and_then(|uri| uri.host().map(|host| host.to_owned()))
+        .unwrap_or_default()
+}
+
+/// A Huobi Global API client, trading on the spot account.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// Id of the spot account, needed for placing orders and reading balances.
+    account_id: Option<u64>,
+
+    /// server order id => client order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new Huobi API client with given `params`. The private WebSocket feed is
+    /// reached at `params.streaming_endpoint` followed by `/v2`, e.g. with the usual
+    /// `wss://api.huobi.pro/ws` endpoint. If `key_pair` is not `None`, this will enable
+    /// performing requests to the REST API and will forward the order updates.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Huobi, along with
+    /// the id of the spot account if `key_pair` is not `None`.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    access_key: pair.access_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            account_id: None,
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+        debug!("requesting symbols");
+        client.symbols = runtime.block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        if client.keys.is_some() {
+            debug!("requesting account id");
+            client.account_id = Some(runtime.block_on(client.get_account_id())?);
+            debug!("received account id");
+        }
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Huobi
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.balances_impl()
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Huobi accepts client order ids of at most 64 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 64 &&
+            hint.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use std::collections::HashMap;
+use log::{warn, debug, error};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::api::query_string::QueryString;
+use crate::api::huobi::{Client, huobi_timestamp, url_encode, host};
+use crate::api::huobi::errors::RestError;
+
+/// Code of successful responses from the v2 endpoints.
+const SUCCESS_CODE: u32 = 200;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+/// Responses from the v1 endpoints carry a `status`, responses from the v2 endpoints
+/// carry a `code`.
+struct HuobiStatus {
+    status: Option<String>,
+    code: Option<u32>,
+    #[serde(rename = "err-code")]
+    err_code: Option<String>,
+    #[serde(rename = "err-msg")]
+    err_msg: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiResponse<T> {
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct HuobiOrder<'a> {
+    #[serde(rename = "account-id")]
+    account_id: String,
+    symbol: &'a str,
+    #[serde(rename = "type")]
+    type_: String,
+    amount: &'a str,
+    price: &'a str,
+    #[serde(rename = "client-order-id")]
+    client_order_id: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct HuobiCancelClientOrder<'a> {
+    #[serde(rename = "client-order-id")]
+    client_order_id: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiAccount<'a> {
+    id: u64,
+    #[serde(rename = "type")]
+    type_: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiBalance<'a> {
+    currency: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    balance: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiBalances<'a> {
+    #[serde(borrow)]
+    list: Vec<HuobiBalance<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiMarketStatus {
+    #[serde(rename = "marketStatus")]
+    market_status: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiSymbol<'a> {
+    symbol: &'a str,
+    state: &'a str,
+    #[serde(rename = "price-precision")]
+    price_precision: u32,
+    #[serde(rename = "amount-precision")]
+    amount_precision: u32,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+/// Ticks are given as a number of decimals, e.g. `2` for a tick of `0.01`.
+fn convert_huobi_precision(precision: u32) -> Option<Tick> {
+    10u64.checked_pow(precision).map(Tick::new)
+}
+
+impl Client {
+    /// If a key pair was provided, requests are signed: the authentication parameters
+    /// are merged with `params`, and the base64 HMAC-SHA256 of the method, the host,
+    /// the path and the sorted query string is appended as a `Signature` parameter.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        mut params: Vec<(&'static str, String)>,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let path = format!("/{}", endpoint);
+
+        if let Some(keys) = self.keys.as_ref() {
+            params.push(("AccessKeyId", keys.access_key.clone()));
+            params.push(("SignatureMethod", "HmacSHA256".to_owned()));
+            params.push(("SignatureVersion", "2".to_owned()));
+            params.push(("Timestamp", url_encode(&huobi_timestamp())));
+        }
+        params.sort();
+
+        let mut query = QueryString::new();
+        for (name, value) in &params {
+            query.push_str(name, value);
+        }
+        let mut query = query.into_string();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let host = host(&self.params.rest_endpoint);
+            let signature = keys.signature(method.as_str(), &host, &path, &query);
+            let mut signed = QueryString::new();
+            signed.push_str("Signature", &url_encode(&signature));
+            query = format!("{}&{}", query, signed.into_string());
+        }
+
+        let address = if query.is_empty() {
+            format!("{}{}", self.params.rest_endpoint, path)
+        } else {
+            format!("{}{}?{}", self.params.rest_endpoint, path, query)
+        };
+
+        let mut request = Request::builder();
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let huobi_status: Option<HuobiStatus> = serde_json::from_slice(&body).ok();
+            let failed = match &huobi_status {
+                Some(HuobiStatus { status: Some(status), .. }) => status != "ok",
+                Some(HuobiStatus { code: Some(code), .. }) => *code != SUCCESS_CODE,
+                _ => true,
+            };
+
+            if status != hyper::StatusCode::OK || failed {
+                let huobi_error = huobi_status.and_then(|huobi_status| {
+                    let v2_code = huobi_status.code.map(|code| code.to_string());
+                    let code = huobi_status.err_code.or(v2_code)?;
+                    let msg = huobi_status.err_msg.or(huobi_status.message);
+                    Some((code, msg.unwrap_or_default()))
+                });
+                let error = RestError::from_huobi_error(status, huobi_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Huobi");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Huobi");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let account_id = match self.account_id {
+            Some(account_id) => account_id,
+            None => return Box::new(futures::future::err(api::errors::ApiError::unauthorized())),
+        };
+
+        let type_ = match (order.type_, order.time_in_force) {
+            (OrderType::Limit, TimeInForce::GoodTilCanceled) => "limit",
+            (OrderType::Limit, TimeInForce::ImmediateOrCancel) => "ioc",
+            (OrderType::Limit, TimeInForce::FillOrKilll) => "limit-fok",
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => "limit-maker",
+            (order_type, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by Huobi",
+                    order_type,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // A client order id is always sent, so that notifications can be matched to the
+        // order before the REST response is received.
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let order_body = HuobiOrder {
+            account_id: account_id.to_string(),
+            symbol: symbol.name(),
+            type_: format!("{}-{}", order.side.as_str(), type_),
+            amount: size.borrow(),
+            price: price.borrow(),
+            client_order_id: &order_id,
+        };
+
+        let body = serde_json::to_string(&order_body).expect("invalid json");
+
+        let order_ids = self.order_ids.clone();
+        let fut = self.request("v1/order/orders/place", Method::POST, Vec::new(), body)
+            .and_then(move |body| {
+                let ack: HuobiResponse<&str> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                order_ids.insert(ack.data.to_owned(), order_id.clone());
+                debug!("insert order id {} (from REST)", order_id);
+
+                Ok(OrderAck {
+                    order_id,
+                }.timestamped())
+            });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        // Orders inserted from elsewhere may be known by their server order id only.
+        let (endpoint, body) = if self.order_ids.contains_key(&cancel.order_id) {
+            (format!("v1/order/orders/{}/submitcancel", cancel.order_id), String::new())
+        } else {
+            let body = HuobiCancelClientOrder {
+                client_order_id: &cancel.order_id,
+            };
+            (
+                "v1/order/orders/submitCancelClientOrder".to_owned(),
+                serde_json::to_string(&body).expect("invalid json")
+            )
+        };
+
+        self.request(&endpoint, Method::POST, Vec::new(), body).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v1/common/timestamp", Method::GET, Vec::new(), String::new())
+            .and_then(|body| {
+                let time: HuobiResponse<Timestamp> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                Ok(().with_timestamp(time.data))
+            })
+    }
+
+    crate fn balances_impl(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        let account_id = match self.account_id {
+            Some(account_id) => account_id,
+            None => return Box::new(futures::future::err(api::errors::ApiError::unauthorized())),
+        };
+
+        let endpoint = format!("v1/account/accounts/{}/balance", account_id);
+        let fut = self.request(&endpoint, Method::GET, Vec::new(), String::new())
+            .and_then(|body| {
+                let huobi_balances: HuobiResponse<HuobiBalances<'_>> =
+                    serde_json::from_slice(&body)
+                        .map_err(api::errors::RequestError::new)
+                        .map_err(api::errors::ApiError::RequestError)?;
+
+                // Each currency is listed twice: once for the `trade` balance, once for
+                // the `frozen` balance.
+                let mut balances = Balances::new();
+                for balance in huobi_balances.data.list {
+                    let entry = balances.entry(balance.currency.to_owned())
+                        .or_insert_with(|| Balance {
+                            free: "0".to_owned(),
+                            locked: "0".to_owned(),
+                        });
+                    match balance.type_ {
+                        "trade" => entry.free = balance.balance.to_owned(),
+                        "frozen" => entry.locked = balance.balance.to_owned(),
+                        _ => (),
+                    }
+                }
+                Ok(balances)
+            });
+        Box::new(fut)
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v2/market-status", Method::GET, Vec::new(), String::new())
+            .and_then(|body| {
+                let status: HuobiResponse<HuobiMarketStatus> = serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                // 1: normal, 2: halted, 3: cancel only.
+                let status = match status.data.market_status {
+                    1 => ExchangeStatus::Normal,
+                    3 => ExchangeStatus::Degraded,
+                    _ => ExchangeStatus::Maintenance,
+                };
+                Ok(status.timestamped())
+            })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v1/common/symbols", Method::GET, Vec::new(), String::new())
+            .and_then(|body| {
+                let huobi_symbols: HuobiResponse<Vec<HuobiSymbol<'_>>> =
+                    serde_json::from_slice(&body)
+                        .map_err(api::errors::RequestError::new)
+                        .map_err(api::errors::ApiError::RequestError)?;
+
+                let mut symbols = HashMap::new();
+                for huobi_symbol in huobi_symbols.data {
+                    if huobi_symbol.state != "online" {
+                        continue;
+                    }
+
+                    let (price_tick, size_tick) = match (
+                        convert_huobi_precision(huobi_symbol.price_precision),
+                        convert_huobi_precision(huobi_symbol.amount_precision)
+                    ) {
+                        (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                        _ => {
+                            error!("cannot read ticks for symbol `{}`", huobi_symbol.symbol);
+                            continue;
+                        }
+                    };
+
+                    if let Some(symbol) = Symbol::new(huobi_symbol.symbol, price_tick, size_tick) {
+                        symbols.insert(symbol.name().to_lowercase(), symbol);
+                    } else {
+                        error!("symbol name too long: `{}`", huobi_symbol.symbol);
+                    }
+                }
+                Ok(symbols)
+            })
+    }
+
+    /// Return the id of the spot account.
+    crate fn get_account_id(&self)
+        -> impl Future<Item = u64, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v1/account/accounts", Method::GET, Vec::new(), String::new())
+            .and_then(|body| {
+                let accounts: HuobiResponse<Vec<HuobiAccount<'_>>> =
+                    serde_json::from_slice(&body)
+                        .map_err(api::errors::RequestError::new)
+                        .map_err(api::errors::ApiError::RequestError)?;
+
+                match accounts.data.iter().find(|account| account.type_ == "spot") {
+                    Some(account) => Ok(account.id),
+                    None => {
+                        error!("no spot account found");
+                        Err(api::errors::ApiError::unauthorized())
+                    }
+                }
+            })
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(test)]
+
+use std::io::Write;
+use flate2::{Compression, write::GzEncoder};
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::huobi::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{self, Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("btcusdt", Tick::new(100), Tick::new(1_000_000)).unwrap()
+}
+
+fn gzip(frame: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(frame.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Feed `frames`, compressed as sent by Huobi, to a handler streaming the order book,
+/// and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        wss::HandlerImpl::on_binary(&mut handler, &gzip(frame), &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let ping = r#"{"ping":1630983549000}"#;
+    let snapshot = r#"{"ch":"market.btcusdt.depth.step0","ts":1630983549503,"tick":{
+        "bids":[[52690.69,0.36281],[52690.68,0.2]],
+        "asks":[[52690.70,0.372591],[52691.00,0.2]],
+        "version":136998124622,"ts":1630983549500}}"#;
+
+    // Only the changes of the top of the book are forwarded.
+    let update = r#"{"ch":"market.btcusdt.depth.step0","ts":1630983549603,"tick":{
+        "bids":[[52690.69,0.5]],
+        "asks":[[52691.00,0.2],[52692.00,0.1]],
+        "version":136998124623,"ts":1630983549600}}"#;
+
+    let notifs = parse(&[ping, snapshot, update]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(5_269_070, 372_591, Side::Ask).with_timestamp(1_630_983_549_500),
+            LimitUpdate::new(5_269_100, 200_000, Side::Ask).with_timestamp(1_630_983_549_500),
+            LimitUpdate::new(5_269_069, 362_810, Side::Bid).with_timestamp(1_630_983_549_500),
+            LimitUpdate::new(5_269_068, 200_000, Side::Bid).with_timestamp(1_630_983_549_500),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(5_269_070, 0, Side::Ask).with_timestamp(1_630_983_549_600),
+            LimitUpdate::new(5_269_068, 0, Side::Bid).with_timestamp(1_630_983_549_600),
+            LimitUpdate::new(5_269_200, 100_000, Side::Ask).with_timestamp(1_630_983_549_600),
+            LimitUpdate::new(5_269_069, 500_000, Side::Bid).with_timestamp(1_630_983_549_600),
+        ]),
+    ]);
+}
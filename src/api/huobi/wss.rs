@@ -0,0 +1,643 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use flate2::read::GzDecoder;
+use std::{mem, thread};
+use std::io::Read;
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::huobi::{Client, Keys, huobi_timestamp, url_encode, host};
+
+/// Code of successful responses on the private connection.
+const SUCCESS_CODE: u32 = 200;
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let public_endpoint = self.params.streaming_endpoint.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        if let Some(keys) = self.keys.clone() {
+            let private_endpoint = format!("{}/v2", self.params.streaming_endpoint);
+            let snd = snd.clone();
+            let order_ids = self.order_ids.clone();
+            let public_control = control.clone();
+
+            // The flags of the private connection are those of the public one,
+            // see `PrivateHandlerImpl::control`.
+            let private_control = control.linked();
+
+            thread::spawn(move || {
+                debug!("initiating WebSocket connection at {}", private_endpoint);
+
+                let address = private_endpoint.clone();
+                if let Err(err) = ws::connect(private_endpoint, |out| {
+                    wss::Handler::new(
+                        out.clone(),
+                        snd.clone(),
+                        private_control.clone(),
+                        wss::KeepAlive::True,
+                        PrivateHandlerImpl {
+                            symbol,
+                            keys: keys.clone(),
+                            address: address.clone(),
+                            out,
+                            control: public_control.clone(),
+                            order_ids: order_ids.clone(),
+                        }
+                    )
+                })
+                {
+                    error!("WebSocket connection terminated with error: `{}`", err);
+                }
+            });
+        }
+
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", public_endpoint);
+
+            if let Err(err) = ws::connect(public_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        out: None,
+                        last_id: 0,
+                        order_book: OrderBook::new(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+
+    /// Set once the connection is open, needed for answering the pings sent by Huobi
+    /// from within `on_binary`.
+    out: Option<ws::Sender>,
+
+    /// Id of the last request, echoed by Huobi in its responses.
+    last_id: u64,
+
+    /// Local copy of the order book: Huobi sends the top levels of the book each time,
+    /// only the changes are forwarded.
+    order_book: OrderBook,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct HuobiRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unsub: Option<&'a str>,
+    id: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct HuobiPong {
+    pong: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiEvent<'a> {
+    ping: Option<u64>,
+    status: Option<&'a str>,
+    #[serde(rename = "err-code")]
+    err_code: Option<String>,
+    #[serde(rename = "err-msg")]
+    err_msg: Option<String>,
+    ch: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct HuobiMessage<T> {
+    tick: T,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct HuobiBook {
+    asks: Vec<(f64, f64)>,
+    bids: Vec<(f64, f64)>,
+    ts: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct HuobiTrades<'a> {
+    #[serde(borrow)]
+    data: Vec<HuobiTrade<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct HuobiTrade<'a> {
+    ts: Timestamp,
+    amount: f64,
+    price: f64,
+    direction: &'a str,
+}
+
+fn book_topic(symbol: &Symbol) -> String {
+    format!("market.{}.depth.step0", symbol.name())
+}
+
+fn trades_topic(symbol: &Symbol) -> String {
+    format!("market.{}.trade.detail", symbol.name())
+}
+
+/// Prices and sizes of the market data are sent as JSON numbers.
+fn convert_huobi_number(tick: Tick, value: f64) -> Result<TickUnit, failure::Error> {
+    Ok(tick.ticked(&format!("{}", value))?)
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, which is not connected, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            out: None,
+            last_id: 0,
+            order_book: OrderBook::new(),
+        }
+    }
+
+    fn send_request(&mut self, sub: Option<&str>, unsub: Option<&str>) -> ws::Result<()> {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        self.last_id += 1;
+        let request = HuobiRequest {
+            sub,
+            unsub,
+            id: self.last_id.to_string(),
+        };
+
+        match serde_json::to_string(&request) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `HuobiRequest`: `{}`", err);
+            }
+        }
+    }
+
+    fn subscribe(&mut self, topic: &str) -> ws::Result<()> {
+        self.send_request(Some(topic), None)
+    }
+
+    fn unsubscribe(&mut self, topic: &str) -> ws::Result<()> {
+        self.send_request(None, Some(topic))
+    }
+
+    fn convert_huobi_level(&self, level: (f64, f64), side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        Ok(LimitUpdate {
+            side,
+            price: convert_huobi_number(self.symbol.price_tick(), level.0)?,
+            size: convert_huobi_number(self.symbol.size_tick(), level.1)?,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let book: HuobiMessage<HuobiBook> = serde_json::from_str(json)?;
+
+        let asks = book.tick.asks.iter().map(|l| self.convert_huobi_level(*l, Side::Ask));
+        let bids = book.tick.bids.iter().map(|l| self.convert_huobi_level(*l, Side::Bid));
+        let levels = asks.chain(bids).collect::<Result<Vec<_>, _>>()?;
+
+        // Levels which are not part of the new top of the book either were emptied or
+        // went beyond the subscribed depth.
+        let mut updates = Vec::new();
+        {
+            let contains = |side: Side, price: TickUnit| {
+                levels.iter().any(|level| level.side == side && level.price == price)
+            };
+            let asks = self.order_book.ask()
+                .filter(|(price, _)| !contains(Side::Ask, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+            let bids = self.order_book.bid()
+                .filter(|(price, _)| !contains(Side::Bid, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+            updates.extend(asks.chain(bids));
+        }
+
+        updates.extend(levels.into_iter().filter(|level| {
+            self.order_book.size_at_limit(level.side, level.price) != level.size
+        }));
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.with_timestamp(book.tick.ts))
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trades: HuobiMessage<HuobiTrades<'_>> = serde_json::from_str(json)?;
+
+        for trade in trades.tick.data {
+            // The direction is the one of the taker.
+            let maker_side = match trade.direction {
+                "buy" => Side::Ask,
+                "sell" => Side::Bid,
+                other => bail!("wrong side: `{}`", other),
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: convert_huobi_number(self.symbol.price_tick(), trade.price)?,
+                size: convert_huobi_number(self.symbol.size_tick(), trade.amount)?,
+                maker_side,
+            }.with_timestamp(trade.ts));
+
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: HuobiEvent<'_> = serde_json::from_str(json)?;
+
+        // Huobi closes the connections which did not answer two consecutive pings.
+        if let Some(ping) = event.ping {
+            if let Some(ws_out) = &self.out {
+                ws_out.send(serde_json::to_string(&HuobiPong { pong: ping })?)?;
+            }
+            return Ok(());
+        }
+
+        if event.status == Some("error") {
+            bail!("{:?}: {:?}", event.err_code, event.err_msg);
+        }
+
+        match event.ch {
+            Some(ch) if ch.ends_with(".depth.step0") &&
+                self.flags.contains(NotificationFlags::ORDER_BOOK) =>
+            {
+                self.parse_book(json, out)?;
+            }
+
+            Some(ch) if ch.ends_with(".trade.detail") &&
+                self.flags.contains(NotificationFlags::TRADES) =>
+            {
+                self.parse_trades(json, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.subscribe(&book_topic(&self.symbol))?;
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            self.subscribe(&trades_topic(&self.symbol))?;
+        }
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    /// Every message of the market data feed is gzip compressed.
+    fn on_binary(&mut self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let mut json = String::new();
+        GzDecoder::new(data).read_to_string(&mut json)?;
+        self.parse_message(&json, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // Orders are streamed through the private connection, where `ORDERS` only
+        // acts as a filter.
+        let book = book_topic(&self.symbol);
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => {
+                self.order_book = OrderBook::new();
+                self.subscribe(&book)?;
+            }
+            (true, false) => self.unsubscribe(&book)?,
+            _ => (),
+        }
+
+        let trades = trades_topic(&self.symbol);
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => self.subscribe(&trades)?,
+            (true, false) => self.unsubscribe(&trades)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    keys: Keys,
+
+    /// Address of the connection, part of the signed string.
+    address: String,
+
+    /// Needed for subscribing once authenticated.
+    out: ws::Sender,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// server order id => client order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct HuobiAuthParams {
+    authType: &'static str,
+    accessKey: String,
+    signatureMethod: &'static str,
+    signatureVersion: &'static str,
+    timestamp: String,
+    signature: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct HuobiPrivateRequest<'a, T> {
+    action: &'a str,
+    ch: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<T>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+struct HuobiPingData {
+    ts: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct HuobiPrivatePong<'a> {
+    action: &'a str,
+    data: HuobiPingData,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiPrivateEvent<'a> {
+    action: &'a str,
+    ch: Option<&'a str>,
+    code: Option<u32>,
+    message: Option<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct HuobiPrivateMessage<T> {
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct HuobiOrder<'a> {
+    eventType: &'a str,
+    symbol: &'a str,
+    orderId: u64,
+    clientOrderId: Option<&'a str>,
+    #[serde(rename = "type")]
+    type_: Option<&'a str>,
+    orderPrice: Option<&'a str>,
+    orderSize: Option<&'a str>,
+    tradePrice: Option<&'a str>,
+    tradeVolume: Option<&'a str>,
+    remainAmt: Option<&'a str>,
+    execAmt: Option<&'a str>,
+    orderCreateTime: Option<Timestamp>,
+    tradeTime: Option<Timestamp>,
+    lastActTime: Option<Timestamp>,
+}
+
+fn orders_topic(symbol: &Symbol) -> String {
+    format!("orders#{}", symbol.name())
+}
+
+impl PrivateHandlerImpl {
+    fn send_request<T: serde::Serialize>(&self, action: &str, ch: &str, params: Option<T>)
+        -> ws::Result<()>
+    {
+        match serde_json::to_string(&HuobiPrivateRequest { action, ch, params }) {
+            Ok(value) => self.out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `HuobiPrivateRequest`: `{}`", err);
+            }
+        }
+    }
+
+    /// The signature covers the same string as the REST requests, with the host and
+    /// the path of the connection and the authentication parameters.
+    fn auth_params(&self) -> HuobiAuthParams {
+        let timestamp = huobi_timestamp();
+        let query = format!(
+            "accessKey={}&signatureMethod=HmacSHA256&signatureVersion=2.1&timestamp={}",
+            self.keys.access_key,
+            url_encode(&timestamp)
+        );
+        let path = self.address.parse::<hyper::Uri>()
+            .map(|uri| uri.path().to_owned())
+            .unwrap_or_default();
+        let signature = self.keys.signature("GET", &host(&self.address), &path, &query);
+
+        HuobiAuthParams {
+            authType: "api",
+            accessKey: self.keys.access_key.clone(),
+            signatureMethod: "HmacSHA256",
+            signatureVersion: "2.1",
+            timestamp,
+            signature,
+        }
+    }
+
+    fn convert_huobi_side(&self, type_: Option<&str>) -> Result<Side, failure::Error> {
+        let side = match type_ {
+            Some(type_) if type_.starts_with("buy-") => Side::Bid,
+            Some(type_) if type_.starts_with("sell-") => Side::Ask,
+            other => bail!("wrong order type: `{:?}`", other),
+        };
+        Ok(side)
+    }
+
+    fn ticked_size(&self, size: Option<&str>) -> Result<TickUnit, failure::Error> {
+        Ok(self.symbol.size_tick().ticked(size.unwrap_or("0"))?)
+    }
+
+    fn ticked_price(&self, price: Option<&str>) -> Result<TickUnit, failure::Error> {
+        Ok(self.symbol.price_tick().ticked(price.unwrap_or("0"))?)
+    }
+
+    /// Return the order id specified by the user, which defaults to the server order id
+    /// in case it was left unspecified.
+    fn order_id(&self, server_order_id: u64, client_order_id: Option<&str>) -> String {
+        let server_order_id = server_order_id.to_string();
+        let order_id = match client_order_id {
+            Some(client_order_id) if !client_order_id.is_empty() => client_order_id.to_owned(),
+            _ => self.order_ids.get(&server_order_id)
+                .map(|order_id| order_id.clone())
+                .unwrap_or_else(|| server_order_id.clone()),
+        };
+
+        // Don't forget to update the concurrent map `server order id => client order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(&server_order_id) {
+            self.order_ids.insert(server_order_id, order_id.clone());
+            debug!("insert order id {} (from WSS)", order_id);
+        }
+        order_id
+    }
+
+    fn parse_order(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let order: HuobiPrivateMessage<HuobiOrder<'_>> = serde_json::from_str(json)?;
+        let order = order.data;
+
+        if order.symbol != self.symbol.name() {
+            return Ok(());
+        }
+
+        let order_id = self.order_id(order.orderId, order.clientOrderId);
+
+        match order.eventType {
+            "creation" => {
+                let order = OrderConfirmation {
+                    size: self.ticked_size(order.orderSize)?,
+                    price: self.ticked_price(order.orderPrice)?,
+                    side: self.convert_huobi_side(order.type_)?,
+                    order_id,
+                }.with_timestamp(order.orderCreateTime.unwrap_or_else(timestamp_ms));
+                out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+            }
+
+            "trade" => {
+                let update = OrderUpdate {
+                    order_id,
+                    consumed_size: self.ticked_size(order.tradeVolume)?,
+                    remaining_size: self.ticked_size(order.remainAmt)?,
+                    consumed_price: self.ticked_price(order.tradePrice)?,
+                    commission: 0,
+                }.with_timestamp(order.tradeTime.unwrap_or_else(timestamp_ms));
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+            }
+
+            // Post only orders which would take liquidity are canceled as well.
+            "cancellation" => {
+                let expiration = OrderExpiration {
+                    order_id,
+                    filled_size: Some(self.ticked_size(order.execAmt)?),
+                    remaining_size: Some(self.ticked_size(order.remainAmt)?),
+                }.with_timestamp(order.lastActTime.unwrap_or_else(timestamp_ms));
+                out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+            }
+
+            // `deletion` is sent for conditional orders only.
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn parse_message(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: HuobiPrivateEvent<'_> = serde_json::from_str(json)?;
+
+        match event.action {
+            "ping" => {
+                let ping: HuobiPrivateMessage<HuobiPingData> = serde_json::from_str(json)?;
+                let pong = HuobiPrivatePong {
+                    action: "pong",
+                    data: ping.data,
+                };
+                self.out.send(serde_json::to_string(&pong)?)?;
+                return Ok(());
+            }
+
+            "req" | "sub" => {
+                if event.code != Some(SUCCESS_CODE) {
+                    bail!("{:?}: {:?} (code = {:?})", event.ch, event.message, event.code);
+                }
+
+                // Subscriptions are only accepted once authenticated.
+                if event.action == "req" && event.ch == Some("auth") {
+                    self.send_request::<()>("sub", &orders_topic(&self.symbol), None)?;
+                }
+                return Ok(());
+            }
+
+            _ => (),
+        }
+
+        if !self.control.flags().contains(NotificationFlags::ORDERS) {
+            return Ok(());
+        }
+
+        if event.action == "push" && event.ch.map(|ch| ch.starts_with("orders#")) == Some(true) {
+            self.parse_order(json, out)?;
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, _: &ws::Sender) -> ws::Result<()> {
+        self.send_request("req", "auth", Some(self.auth_params()))
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! A module defining typed identifiers for exchanges and for the symbols traded on
+//! them. Symbol names are only unique within an exchange, e.g. `BTCUSD` may refer to
+//! different markets on two exchanges, so code dealing with several exchanges should
+//! key its data with a `SymbolId` rather than with the symbol name.
+
+mod test;
+
+use std::fmt;
+use std::str::FromStr;
+use failure_derive::Fail;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::symbol::{Symbol, SymbolName};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// An exchange supported by this crate, whether or not its client is enabled, or
+/// `ExchangeId::Other` for the clients implemented outside of this crate.
+pub enum ExchangeId {
+    /// See `api::alpaca`.
+    Alpaca,
+
+    /// See `api::binance`.
+    Binance,
+
+    /// See `api::bitfinex`.
+    Bitfinex,
+
+    /// See `api::bitmex`.
+    Bitmex,
+
+    /// See `api::bybit`.
+    Bybit,
+
+    /// See `api::coinbase`.
+    Coinbase,
+
+    /// See `api::deribit`.
+    Deribit,
+
+    /// See `api::dydx`.
+    Dydx,
+
+    /// See `api::gateio`.
+    Gateio,
+
+    /// See `api::gdax`.
+    Gdax,
+
+    /// See `api::gemini`.
+    Gemini,
+
+    /// See `api::hitbtc`.
+    Hitbtc,
+
+    /// See `api::huobi`.
+    Huobi,
+
+    /// See `api::kraken`.
+    Kraken,
+
+    /// See `api::kraken_futures`.
+    #[serde(rename = "kraken_futures")]
+    KrakenFutures,
+
+    /// See `api::kucoin`.
+    Kucoin,
+
+    /// See `api::mexc`.
+    Mexc,
+
+    /// See `api::okx`.
+    Okx,
+
+    /// See `api::testkit`.
+    Testkit,
+
+    /// See `api::upbit`.
+    Upbit,
+
+    /// An exchange whose client is implemented outside of this crate, returned by the
+    /// default implementation of `ApiClient::exchange_id`.
+    Other,
+}
+
+impl ExchangeId {
+    /// Return the name of the exchange, which is also the name of its module and
+    /// of its feature, e.g. `"binance"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExchangeId::Alpaca => "alpaca",
+            ExchangeId::Binance => "binance",
+            ExchangeId::Bitfinex => "bitfinex",
+            ExchangeId::Bitmex => "bitmex",
+            ExchangeId::Bybit => "bybit",
+            ExchangeId::Coinbase => "coinbase",
+            ExchangeId::Deribit => "deribit",
+            ExchangeId::Dydx => "dydx",
+            ExchangeId::Gateio => "gateio",
+            ExchangeId::Gdax => "gdax",
+            ExchangeId::Gemini => "gemini",
+            ExchangeId::Hitbtc => "hitbtc",
+            ExchangeId::Huobi => "huobi",
+            ExchangeId::Kraken => "kraken",
+            ExchangeId::KrakenFutures => "kraken_futures",
+            ExchangeId::Kucoin => "kucoin",
+            ExchangeId::Mexc => "mexc",
+            ExchangeId::Okx => "okx",
+            ExchangeId::Testkit => "testkit",
+            ExchangeId::Upbit => "upbit",
+            ExchangeId::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ExchangeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+#[fail(display = "unknown exchange: `{}`", _0)]
+/// An error returned when parsing an unknown exchange name.
+pub struct ParseExchangeIdError(String);
+
+impl FromStr for ExchangeId {
+    type Err = ParseExchangeIdError;
+
+    /// Parse the name of an exchange, case insensitively. `coinbase_pro` is accepted
+    /// as an alias of `gdax`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let id = match name.to_lowercase().as_ref() {
+            "alpaca" => ExchangeId::Alpaca,
+            "binance" => ExchangeId::Binance,
+            "bitfinex" => ExchangeId::Bitfinex,
+            "bitmex" => ExchangeId::Bitmex,
+            "bybit" => ExchangeId::Bybit,
+            "coinbase" => ExchangeId::Coinbase,
+            "deribit" => ExchangeId::Deribit,
+            "dydx" => ExchangeId::Dydx,
+            "gateio" => ExchangeId::Gateio,
+            "gdax" | "coinbase_pro" => ExchangeId::Gdax,
+            "gemini" => ExchangeId::Gemini,
+            "hitbtc" => ExchangeId::Hitbtc,
+            "huobi" => ExchangeId::Huobi,
+            "kraken" => ExchangeId::Kraken,
+            "kraken_futures" => ExchangeId::KrakenFutures,
+            "kucoin" => ExchangeId::Kucoin,
+            "mexc" => ExchangeId::Mexc,
+            "okx" => ExchangeId::Okx,
+            "testkit" => ExchangeId::Testkit,
+            "upbit" => ExchangeId::Upbit,
+            "other" => ExchangeId::Other,
+            _ => return Err(ParseExchangeIdError(name.to_owned())),
+        };
+        Ok(id)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+/// A symbol on a given exchange.
+pub struct SymbolId {
+    exchange: ExchangeId,
+    name: SymbolName,
+}
+
+impl SymbolId {
+    /// Return the id of `symbol` on `exchange`.
+    pub fn new(exchange: ExchangeId, symbol: &Symbol) -> Self {
+        SymbolId {
+            exchange,
+            // Cannot fail, since `symbol` already holds a `SymbolName`.
+            name: SymbolName::from(symbol.name()).unwrap(),
+        }
+    }
+
+    /// Return the id of the symbol named `name` on `exchange`, or `None` if the name is
+    /// too long for being a symbol name.
+    pub fn from_name(exchange: ExchangeId, name: &str) -> Option<Self> {
+        Some(SymbolId {
+            exchange,
+            name: SymbolName::from(name).ok()?,
+        })
+    }
+
+    /// Exchange on which the symbol is traded.
+    pub fn exchange(&self) -> ExchangeId {
+        self.exchange
+    }
+
+    /// Symbol name, as used by the exchange.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for SymbolId {
+    /// Format as `exchange:name`, e.g. `binance:BTCUSDT`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.exchange, self.name)
+    }
+}
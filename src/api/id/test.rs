@@ -0,0 +1,33 @@
+#![cfg(test)]
+
+use crate::api::id::{ExchangeId, SymbolId};
+
+#[test]
+fn exchange_names() {
+    for id in &[ExchangeId::Binance, ExchangeId::Gdax, ExchangeId::Okx, ExchangeId::Other] {
+        assert_eq!(id.as_str().parse::<ExchangeId>().unwrap(), *id);
+    }
+
+    assert_eq!("Kraken".parse::<ExchangeId>().unwrap(), ExchangeId::Kraken);
+    assert_eq!("coinbase_pro".parse::<ExchangeId>().unwrap(), ExchangeId::Gdax);
+    assert!("mtgox".parse::<ExchangeId>().is_err());
+
+    assert_eq!(serde_json::to_string(&ExchangeId::Kucoin).unwrap(), "\"kucoin\"");
+    assert_eq!(
+        serde_json::to_string(&ExchangeId::KrakenFutures).unwrap(),
+        "\"kraken_futures\""
+    );
+}
+
+#[test]
+fn symbol_namespaces() {
+    let binance = SymbolId::from_name(ExchangeId::Binance, "BTCUSDT").unwrap();
+    let bybit = SymbolId::from_name(ExchangeId::Bybit, "BTCUSDT").unwrap();
+
+    assert_ne!(binance, bybit);
+    assert_eq!(binance.name(), bybit.name());
+    assert_eq!(binance.exchange(), ExchangeId::Binance);
+    assert_eq!(binance.to_string(), "binance:BTCUSDT");
+
+    assert!(SymbolId::from_name(ExchangeId::Binance, "NOT-A-SYMBOL-NAME-SINCE-IT-IS-TOO-LONG").is_none());
+}
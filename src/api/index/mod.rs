@@ -0,0 +1,169 @@
+//! A module defining index prices, either published by venues as
+//! `Notification::IndexPrice`, or computed client-side from the prices of several
+//! venues by a `CompositeIndex`.
+//!
+//! The composite index only considers the prices received within the last `max_age`
+//! milliseconds. The median of these prices is used as a reference: prices deviating
+//! from it by more than `max_deviation` are rejected as outliers, and the index is the
+//! mean of the remaining ones.
+
+mod test;
+
+use std::collections::HashMap;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::id::SymbolId;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::TopOfBook;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A price index published by a venue, e.g. the spot index underlying a perpetual
+/// contract.
+pub struct IndexPrice {
+    /// Name of the index on the venue, e.g. `btc_usd` on Deribit.
+    pub index: String,
+
+    /// Price of the index, unticked.
+    pub price: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// A value of a `CompositeIndex`.
+pub struct CompositePrice {
+    /// Mean of the accepted prices.
+    pub price: f64,
+
+    /// Sources whose price was accepted.
+    pub sources: Vec<SymbolId>,
+
+    /// Sources whose price was rejected as an outlier.
+    pub rejected: Vec<SymbolId>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// Compute an index from the latest prices of several sources, rejecting outliers.
+pub struct CompositeIndex {
+    max_deviation: f64,
+    max_age: Timestamp,
+    min_sources: usize,
+    prices: HashMap<SymbolId, Timestamped<f64>>,
+}
+
+impl CompositeIndex {
+    /// Return a new `CompositeIndex`, rejecting prices deviating by more than 1% from
+    /// the median, ignoring prices older than 10 seconds, and requiring at least one
+    /// accepted source.
+    pub fn new() -> Self {
+        CompositeIndex {
+            max_deviation: 0.01,
+            max_age: 10_000,
+            min_sources: 1,
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Reject prices deviating from the median by more than `max_deviation`, relatively
+    /// to the median, e.g. `0.01` for 1%.
+    pub fn with_max_deviation(mut self, max_deviation: f64) -> Self {
+        self.max_deviation = max_deviation;
+        self
+    }
+
+    /// Ignore prices received more than `max_age` ms ago.
+    pub fn with_max_age(mut self, max_age: Timestamp) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Do not output a value unless at least `min_sources` prices are accepted.
+    pub fn with_min_sources(mut self, min_sources: usize) -> Self {
+        self.min_sources = min_sources;
+        self
+    }
+
+    /// Record the latest `price` of `source`, replacing its previous one.
+    pub fn on_price(&mut self, source: SymbolId, price: Timestamped<f64>) {
+        self.prices.insert(source, price);
+    }
+
+    /// Record the mid price of `top`, the top of the book of `symbol` on `source`.
+    /// Tops of the book with an empty side are ignored.
+    pub fn on_top_of_book(
+        &mut self,
+        source: SymbolId,
+        symbol: &Symbol,
+        top: &Timestamped<TopOfBook>
+    ) {
+        if top.bid_price == 0 || top.ask_price == 0 {
+            return;
+        }
+
+        let ticks_per_unit = symbol.price_tick().ticks_per_unit() as f64;
+        let mid = (top.bid_price + top.ask_price) as f64 / 2. / ticks_per_unit;
+        self.on_price(source, mid.with_timestamp(top.timestamp()));
+    }
+
+    /// Record an index price published by `source`, ignoring unparsable prices.
+    pub fn on_index_price(&mut self, source: SymbolId, index: &Timestamped<IndexPrice>) {
+        if let Ok(price) = index.price.parse::<f64>() {
+            self.on_price(source, price.with_timestamp(index.timestamp()));
+        }
+    }
+
+    /// Remove the price of `source`, e.g. once its stream has ended.
+    pub fn remove(&mut self, source: SymbolId) {
+        self.prices.remove(&source);
+    }
+
+    /// Return the value of the index at local time `now` (in ms), or `None` if not
+    /// enough prices are accepted.
+    pub fn value(&self, now: Timestamp) -> Option<CompositePrice> {
+        let mut prices = self.prices.iter()
+            .filter(|(_, price)| now.saturating_sub(price.timestamp()) <= self.max_age)
+            .map(|(source, price)| (*source, **price))
+            .collect::<Vec<_>>();
+
+        if prices.is_empty() {
+            return None;
+        }
+
+        // Sorting by source as well keeps the output deterministic.
+        prices.sort_by(|(a_source, a), (b_source, b)| {
+            a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal).then(a_source.cmp(b_source))
+        });
+        let middle = prices.len() / 2;
+        let median = if prices.len() % 2 == 0 {
+            (prices[middle - 1].1 + prices[middle].1) / 2.
+        } else {
+            prices[middle].1
+        };
+
+        let mut sources = Vec::new();
+        let mut rejected = Vec::new();
+        let mut sum = 0.;
+        for (source, price) in prices {
+            if (price - median).abs() > self.max_deviation * median.abs() {
+                rejected.push(source);
+            } else {
+                sources.push(source);
+                sum += price;
+            }
+        }
+
+        if sources.is_empty() || sources.len() < self.min_sources {
+            return None;
+        }
+
+        Some(CompositePrice {
+            price: sum / sources.len() as f64,
+            sources,
+            rejected,
+        })
+    }
+}
+
+impl Default for CompositeIndex {
+    fn default() -> Self {
+        CompositeIndex::new()
+    }
+}
@@ -0,0 +1,74 @@
+#![cfg(test)]
+
+use crate::api::id::{ExchangeId, SymbolId};
+use crate::api::index::{CompositeIndex, IndexPrice};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::data::resample::TopOfBook;
+use crate::tick::Tick;
+
+fn source(exchange: ExchangeId) -> SymbolId {
+    let symbol = Symbol::new("BTCUSDT", Tick::new(100), Tick::new(1000)).unwrap();
+    SymbolId::new(exchange, &symbol)
+}
+
+#[test]
+fn rejects_outliers() {
+    let mut index = CompositeIndex::new().with_max_deviation(0.01);
+    assert_eq!(index.value(0), None);
+
+    index.on_price(source(ExchangeId::Binance), 100.0.with_timestamp(0));
+    index.on_price(source(ExchangeId::Bybit), 101.0.with_timestamp(0));
+    index.on_price(source(ExchangeId::Okx), 100.5.with_timestamp(0));
+    index.on_price(source(ExchangeId::Kraken), 120.0.with_timestamp(0));
+
+    let value = index.value(0).unwrap();
+    assert_eq!(value.price, 100.5);
+    assert_eq!(value.sources, vec![
+        source(ExchangeId::Binance),
+        source(ExchangeId::Okx),
+        source(ExchangeId::Bybit),
+    ]);
+    assert_eq!(value.rejected, vec![source(ExchangeId::Kraken)]);
+
+    // A newer price replaces the previous one.
+    index.on_price(source(ExchangeId::Kraken), 100.5.with_timestamp(10));
+    assert_eq!(index.value(10).unwrap().rejected, vec![]);
+
+    index.remove(source(ExchangeId::Kraken));
+    assert_eq!(index.value(10).unwrap().sources.len(), 3);
+}
+
+#[test]
+fn stale_prices() {
+    let mut index = CompositeIndex::new().with_max_age(1000).with_min_sources(2);
+    index.on_price(source(ExchangeId::Binance), 100.0.with_timestamp(0));
+    index.on_price(source(ExchangeId::Bybit), 102.0.with_timestamp(500));
+    assert_eq!(index.value(1000).unwrap().price, 101.0);
+
+    // Only one price left.
+    assert_eq!(index.value(1001), None);
+}
+
+#[test]
+fn venue_inputs() {
+    let symbol = Symbol::new("BTCUSDT", Tick::new(100), Tick::new(1000)).unwrap();
+    let mut index = CompositeIndex::new();
+
+    let top = TopOfBook { bid_price: 10_000, bid_size: 1, ask_price: 10_002, ask_size: 1 };
+    index.on_top_of_book(source(ExchangeId::Binance), &symbol, &top.with_timestamp(0));
+
+    // Empty side.
+    let top = TopOfBook { bid_price: 0, bid_size: 0, ask_price: 10_002, ask_size: 1 };
+    index.on_top_of_book(source(ExchangeId::Bybit), &symbol, &top.with_timestamp(0));
+
+    let price = IndexPrice {
+        index: "btc_usd".to_owned(),
+        price: "100.03".to_owned(),
+    };
+    index.on_index_price(source(ExchangeId::Deribit), &price.with_timestamp(0));
+
+    let value = index.value(0).unwrap();
+    assert_eq!(value.sources, vec![source(ExchangeId::Binance), source(ExchangeId::Deribit)]);
+    assert!((value.price - 100.02).abs() < 1e-9);
+}
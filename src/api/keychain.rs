@@ -0,0 +1,53 @@
+//! A module defining helpers for storing the key pairs of the various exchanges in the
+//! OS keychain (macOS Keychain, Windows Credential Vault, Secret Service on Linux),
+//! so that they never need to be written in plain text on disk.
+//!
+//! Key pairs are stored serialized as JSON, under a service name and an account name
+//! chosen by the user, e.g. `("trade-rs", "binance-main")`.
+//!
+//! # Example
+//! ```no_run
+//! # extern crate trade;
+//! # use trade::api::{binance, keychain};
+//! # fn main() -> Result<(), failure::Error> {
+//! let key_pair: binance::KeyPair = keychain::load("trade-rs", "binance-main")?
+//!     .expect("no key pair stored for this account");
+//! # Ok(())
+//! # }
+//! ```
+
+use failure::format_err;
+use serde::{Serialize, de::DeserializeOwned};
+use keyring::{Keyring, KeyringError};
+
+fn convert_error(err: KeyringError) -> failure::Error {
+    // `KeyringError` is not `Sync` on every platform.
+    format_err!("keychain error: {}", err)
+}
+
+/// Store `key_pair` in the keychain, replacing any key pair previously stored for
+/// `service` and `account`.
+pub fn store<K: Serialize>(service: &str, account: &str, key_pair: &K)
+    -> Result<(), failure::Error>
+{
+    let value = serde_json::to_string(key_pair)?;
+    Keyring::new(service, account).set_password(&value).map_err(convert_error)
+}
+
+/// Load the key pair stored for `service` and `account`, return `Ok(None)` if there
+/// is none.
+pub fn load<K: DeserializeOwned>(service: &str, account: &str)
+    -> Result<Option<K>, failure::Error>
+{
+    let value = match Keyring::new(service, account).get_password() {
+        Ok(value) => value,
+        Err(KeyringError::NoPasswordFound) => return Ok(None),
+        Err(err) => return Err(convert_error(err)),
+    };
+    Ok(Some(serde_json::from_str(&value)?))
+}
+
+/// Delete the key pair stored for `service` and `account`.
+pub fn delete(service: &str, account: &str) -> Result<(), failure::Error> {
+    Keyring::new(service, account).delete_password().map_err(convert_error)
+}
@@ -0,0 +1,181 @@
+//! A module defining error types specific to Kraken.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Kraken REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Error messages, e.g. `EOrder:Insufficient funds`: see API documentation.
+    pub error_msgs: Vec<String>,
+}
+
+impl RestError {
+    fn has_error(&self, msg: &str) -> bool {
+        self.error_msgs.iter().any(|error_msg| error_msg.starts_with(msg))
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_error("EOrder:Unknown order") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_error("EOrder:Insufficient funds") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest && self.has_error("EOrder:Duplicate order") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest && self.has_error("EOrder:Post only") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.error_msgs.is_empty() {
+            write!(f, ": `{}`", self.error_msgs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Kraken mostly reports errors with an HTTP 200 status code, the errors being listed
+    /// in the response body.
+    pub(super) fn from_kraken_errors(status: StatusCode, error_msgs: Vec<String>) -> Self {
+        let kind = if status != StatusCode::OK {
+            RestErrorKind::from_status_code(status)
+        } else {
+            error_msgs.first()
+                .map(|msg| RestErrorKind::from_error_msg(msg))
+                .unwrap_or(RestErrorKind::BadRequest)
+        };
+
+        RestError {
+            kind,
+            error_msgs,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Kraken error message to a Kraken error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key, signature or nonce, or missing permission for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Kraken. See Kraken API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Kraken side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is down or busy.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::OK => panic!("`RestErrorKind::from_status_code` with `StatusCode::Ok`"),
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_error_msg(msg: &str) -> Self {
+        use self::RestErrorKind::*;
+        match msg {
+            "EAPI:Rate limit exceeded" |
+            "EOrder:Rate limit exceeded" |
+            "EGeneral:Too many requests" => TooManyRequests,
+
+            "EAPI:Invalid key" |
+            "EAPI:Invalid signature" |
+            "EAPI:Invalid nonce" |
+            "EGeneral:Permission denied" => Unauthorized,
+
+            "EGeneral:Internal error" => InternalError,
+
+            "EService:Deadline elapsed" => Timeout,
+
+            msg if msg.starts_with("EService:") => ServiceUnavailable,
+
+            _ => BadRequest,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Implementation of `ApiClient` for the Kraken API.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::pkey::{PKey, Private};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Kraken key pair: api key + secret key (base64 encoded, as provided by Kraken).
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+/// A Kraken API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// Last nonce sent along a private request, nonces must be strictly increasing.
+    nonce: Arc<Mutex<u64>>,
+
+    /// server order id => client order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+
+    /// symbol name (lowercase) => pair name used by the WebSocket API, e.g. `XBT/USD`
+    ws_names: HashMap<String, String>,
+
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new Kraken API client with given `params`. If `key_pair` is not
+    /// `None`, this will enable performing requests to the REST API and will forward
+    /// the user data stream.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Kraken.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(&base64::decode(&pair.secret_key)?)?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            nonce: Arc::new(Mutex::new(0)),
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            ws_names: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        let (symbols, ws_names) = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        client.symbols = symbols;
+        client.ws_names = ws_names;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Kraken
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Kraken accepts free text client order ids of at most 18 characters, or UUIDs.
+    fn new_order_id(hint: &str) -> String {
+        if hint.len() <= 18 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().to_string()
+        }
+    }
+}
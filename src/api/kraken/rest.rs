@@ -0,0 +1,418 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::Deserialize;
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+use crate::api::kraken::Client;
+use crate::api::kraken::errors::RestError;
+
+/// Ticks used for computing free balances, Kraken balances have at most 10 decimals
+/// but extra decimals are truncated anyway.
+const BALANCE_TICK: u64 = 100_000_000;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenErrors {
+    #[serde(default)]
+    error: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenResult<T> {
+    result: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenOrderAck<'a> {
+    #[serde(borrow)]
+    txid: Vec<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenBalance<'a> {
+    balance: &'a str,
+    hold_trade: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenAssetPair<'a> {
+    altname: &'a str,
+    wsname: Option<&'a str>,
+    pair_decimals: u32,
+    lot_decimals: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenSystemStatus<'a> {
+    status: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenTime {
+    unixtime: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenToken<'a> {
+    token: &'a str,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+impl Client {
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let address = format!(
+            "{}/{}",
+            self.params.rest_endpoint,
+            endpoint,
+        );
+
+        let mut request = Request::builder();
+
+        // Private endpoints are reached through POST requests, signed with a nonce.
+        let body = if method == Method::POST {
+            let nonce = {
+                let mut last = self.nonce.lock().unwrap();
+                *last = std::cmp::max(*last + 1, timestamp_ms());
+                *last
+            };
+
+            let mut query = query;
+            query.push("nonce", nonce);
+            let body = query.into_string();
+
+            if let Some(keys) = self.keys.as_ref() {
+                let digest = openssl::sha::sha256(format!("{}{}", nonce, body).as_bytes());
+                let mut signer = Signer::new(MessageDigest::sha512(), &keys.secret_key).unwrap();
+                signer.update(format!("/{}", endpoint).as_bytes()).unwrap();
+                signer.update(&digest).unwrap();
+                let signature = base64::encode(&signer.sign_to_vec().unwrap());
+
+                request.header("API-Key", keys.api_key.as_bytes())
+                    .header("API-Sign", signature.as_bytes());
+            }
+            body
+        } else {
+            query.into_string()
+        };
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/x-www-form-urlencoded"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let errors = serde_json::from_slice(&body)
+                .map(|errors: KrakenErrors| errors.error)
+                .unwrap_or_default();
+
+            if status != hyper::StatusCode::OK || !errors.is_empty() {
+                let error = RestError::from_kraken_errors(status, errors);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Kraken");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Kraken");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let time_in_force = match order.time_in_force {
+            TimeInForce::GoodTilCanceled => "GTC",
+            TimeInForce::ImmediateOrCancel => "IOC",
+            TimeInForce::FillOrKilll => {
+                warn!("called `order` with `FillOrKill`, which is not supported by Kraken");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+            TimeInForce::GoodTilDate(_) => {
+                warn!("called `order` with `GoodTilDate`, which is not supported by Kraken");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let mut query = QueryString::new();
+        let symbol = order.symbol();
+        query.push_str("pair", symbol.name());
+        query.push_str("type", order.side.as_str());
+        query.push_str("ordertype", "limit");
+        query.push_str(
+            "price",
+            order.price.unticked(symbol.price_tick()).borrow() as &str
+        );
+        query.push_str(
+            "volume",
+            order.size.unticked(symbol.size_tick()).borrow() as &str
+        );
+        query.push_str("timeinforce", time_in_force);
+
+        if order.type_ == OrderType::LimitMaker {
+            query.push_str("oflags", "post");
+        }
+
+        if let Some(order_id) = &order.order_id {
+            query.push_str("cl_ord_id", order_id);
+        }
+
+        let client_order_id = order.order_id.clone();
+        let order_ids = self.order_ids.clone();
+
+        let fut = self.request("0/private/AddOrder", Method::POST, query).and_then(move |body| {
+            let ack: KrakenResult<KrakenOrderAck<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let txid = match ack.result.txid.first() {
+                Some(txid) => *txid,
+                None => {
+                    error!("`AddOrder` response is missing the order txid");
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::UnknownStatus.into()
+                    ));
+                }
+            };
+
+            // The order id specified by the user, which defaults to the server order id
+            // in case it was left unspecified.
+            let order_id = client_order_id.unwrap_or_else(|| txid.to_owned());
+            order_ids.insert(txid.to_owned(), order_id.clone());
+            debug!("insert order id {} (from REST)", order_id);
+
+            Ok(OrderAck {
+                order_id,
+            }.timestamped())
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+
+        // Orders inserted without a client order id are known by their server order id.
+        if self.order_ids.contains_key(&cancel.order_id) {
+            query.push_str("txid", &cancel.order_id);
+        } else {
+            query.push_str("cl_ord_id", &cancel.order_id);
+        }
+
+        self.request("0/private/CancelOrder", Method::POST, query).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("0/public/Time", Method::GET, QueryString::new()).and_then(|body| {
+            let time: KrakenResult<KrakenTime> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(time.result.unixtime * 1000))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("0/private/BalanceEx", Method::POST, query).and_then(|body| {
+            let balances: KrakenResult<HashMap<&str, KrakenBalance<'_>>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let tick = Tick::new(BALANCE_TICK);
+            let mut result = Balances::new();
+            for (currency, balance) in balances.result {
+                let locked = balance.hold_trade.unwrap_or("0");
+                let free = tick.ticked(balance.balance)
+                    .and_then(|total| tick.unticked(total.saturating_sub(tick.ticked(locked)?)))
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                result.insert(currency.to_owned(), Balance {
+                    free,
+                    locked: locked.to_owned(),
+                });
+            }
+            Ok(result)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("0/public/SystemStatus", Method::GET, query).and_then(|body| {
+            let status: KrakenResult<KrakenSystemStatus<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match status.result.status {
+                "online" => ExchangeStatus::Normal,
+                "cancel_only" | "post_only" | "limit_only" => ExchangeStatus::Degraded,
+                "maintenance" => ExchangeStatus::Maintenance,
+                _ => ExchangeStatus::Outage,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    /// Request a token for authenticating the private WebSocket connection.
+    crate fn ws_token(&self)
+        -> impl Future<Item = String, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("0/private/GetWebSocketsToken", Method::POST, query).and_then(|body| {
+            let token: KrakenResult<KrakenToken<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(token.result.token.to_owned())
+        })
+    }
+
+    /// Return the available symbols, along with their names in the WebSocket API.
+    crate fn get_symbols(&self)
+        -> impl Future<
+            Item = (HashMap<String, Symbol>, HashMap<String, String>),
+            Error = api::errors::Error
+        > + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("0/public/AssetPairs", Method::GET, query).and_then(|body| {
+            let pairs: KrakenResult<HashMap<&str, KrakenAssetPair<'_>>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            let mut ws_names = HashMap::new();
+            for (_, p) in pairs.result {
+                // Dark pools pairs have no WebSocket name, and cannot be streamed anyway.
+                let wsname = match p.wsname {
+                    Some(wsname) => wsname,
+                    None => continue,
+                };
+
+                let (price_tick, size_tick) = match (
+                    10u64.checked_pow(p.pair_decimals),
+                    10u64.checked_pow(p.lot_decimals)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => {
+                        (Tick::new(price_tick), Tick::new(size_tick))
+                    }
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", p.altname);
+                        continue;
+                    }
+                };
+
+                if let Some(symbol) = Symbol::new(p.altname, price_tick, size_tick) {
+                    symbols.insert(symbol.name().to_lowercase(), symbol);
+                    ws_names.insert(symbol.name().to_lowercase(), wsname.to_owned());
+                } else {
+                    error!("symbol name too long: `{}`", p.altname);
+                }
+            }
+            Ok((symbols, ws_names))
+        })
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::kraken::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{self, Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("XBT/USD", Tick::new(10), Tick::new(100_000_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+const SNAPSHOT: &str = r#"[0,{
+    "as":[["5541.3","2.50700000","1534614248.123678"],["5542.5","0.40000000","1534614248.456738"]],
+    "bs":[["5541.2","1.52900000","1534614248.765567"],["5539.9","0.30000000","1534614241.769870"]]
+},"book-100","XBT/USD"]"#;
+
+#[test]
+fn crc32() {
+    assert_eq!(wss::crc32(b""), 0);
+    assert_eq!(wss::crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    // The checksum of `"55413100000000" "5542540000000" "5539930000000"`.
+    let update = r#"[0,
+        {"a":[["5541.3","1.00000000","1534614335.345903"]]},
+        {"b":[["5541.2","0.00000000","1534614335.345903"]],"c":"3121652537"},
+        "book-100","XBT/USD"
+    ]"#;
+
+    let notifs = parse(&[SNAPSHOT, update]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(55413, 250_700_000, Side::Ask).with_timestamp(1_534_614_248_123),
+            LimitUpdate::new(55425, 40_000_000, Side::Ask).with_timestamp(1_534_614_248_456),
+            LimitUpdate::new(55412, 152_900_000, Side::Bid).with_timestamp(1_534_614_248_765),
+            LimitUpdate::new(55399, 30_000_000, Side::Bid).with_timestamp(1_534_614_241_769),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(55413, 100_000_000, Side::Ask).with_timestamp(1_534_614_335_345),
+            LimitUpdate::new(55412, 0, Side::Bid).with_timestamp(1_534_614_335_345),
+        ]),
+    ]);
+}
+
+#[test]
+#[should_panic(expected = "desynchronized order book")]
+fn book_checksum_mismatch() {
+    let update = r#"[0,
+        {"a":[["5541.3","1.00000000","1534614335.345903"]],"c":"3121652537"},
+        "book-100","XBT/USD"
+    ]"#;
+    parse(&[SNAPSHOT, update]);
+}
@@ -0,0 +1,696 @@
+use futures::sync::mpsc::unbounded;
+use failure::{bail, format_err};
+use std::{mem, thread};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_json::Value;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::{self, Tick, TickUnit};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::api::kraken::Client;
+
+/// Private feeds are served by a distinct WebSocket endpoint.
+const PRIVATE_STREAMING_ENDPOINT: &str = "wss://ws-auth.kraken.com";
+
+/// Depth of the subscribed order book.
+const BOOK_DEPTH: usize = 100;
+
+/// Number of levels per side covered by the order book checksum.
+const CHECKSUM_DEPTH: usize = 10;
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let streaming_endpoint = self.params.streaming_endpoint.clone();
+        let ws_name = self.ws_names.get(&symbol.name().to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| symbol.name().to_owned());
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        if self.keys.is_some() {
+            let token = self.ws_token();
+            let snd = snd.clone();
+            let ws_name = ws_name.clone();
+            let order_ids = self.order_ids.clone();
+            let public_control = control.clone();
+            thread::spawn(move || {
+                use tokio::runtime::current_thread;
+
+                debug!("requesting WebSocket token");
+                let token = match current_thread::Runtime::new() {
+                    Ok(mut runtime) => runtime.block_on(token),
+                    Err(err) => {
+                        error!("failed to start runtime: `{}`", err);
+                        return;
+                    }
+                };
+                let token = match token {
+                    Ok(token) => token,
+                    Err(err) => {
+                        error!("failed to request WebSocket token: `{}`", err);
+                        return;
+                    }
+                };
+                debug!("received WebSocket token");
+
+                // The flags of the private connection are those of the public one,
+                // see `PrivateHandlerImpl::control`.
+                let private_control = public_control.linked();
+
+                debug!("initiating WebSocket connection at {}", PRIVATE_STREAMING_ENDPOINT);
+
+                if let Err(err) = ws::connect(PRIVATE_STREAMING_ENDPOINT, |out| {
+                    wss::Handler::new(
+                        out,
+                        snd.clone(),
+                        private_control.clone(),
+                        wss::KeepAlive::True,
+                        PrivateHandlerImpl {
+                            symbol,
+                            ws_name: ws_name.clone(),
+                            token: token.clone(),
+                            control: public_control.clone(),
+                            orders_snapshot: true,
+                            trades_snapshot: true,
+                            orders: HashMap::new(),
+                            order_ids: order_ids.clone(),
+                        }
+                    )
+                })
+                {
+                    error!("WebSocket connection terminated with error: `{}`", err);
+                }
+            });
+        }
+
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", streaming_endpoint);
+
+            if let Err(err) = ws::connect(streaming_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        ws_name: ws_name.clone(),
+                        flags,
+                        order_book: OrderBook::new(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+
+    /// Pair name used by the WebSocket API, e.g. `XBT/USD`.
+    ws_name: String,
+
+    flags: NotificationFlags,
+
+    /// Local copy of the order book, needed for truncating the book to the subscribed
+    /// depth and for verifying the checksums sent by Kraken.
+    order_book: OrderBook,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct KrakenSubscriptionName<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct KrakenSubscription<'a> {
+    event: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pair: Option<&'a [&'a str]>,
+    subscription: KrakenSubscriptionName<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenEvent<'a> {
+    event: &'a str,
+    status: Option<&'a str>,
+    errorMessage: Option<&'a str>,
+}
+
+/// A price level: price, volume, timestamp and an optional `"r"` flag for republished
+/// levels.
+type KrakenLevel = Vec<String>;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenBookUpdate {
+    #[serde(rename = "as")]
+    snapshot_asks: Option<Vec<KrakenLevel>>,
+    #[serde(rename = "bs")]
+    snapshot_bids: Option<Vec<KrakenLevel>>,
+    #[serde(rename = "a")]
+    asks: Option<Vec<KrakenLevel>>,
+    #[serde(rename = "b")]
+    bids: Option<Vec<KrakenLevel>>,
+    #[serde(rename = "c")]
+    checksum: Option<String>,
+}
+
+/// A trade: price, volume, time, side, order type and miscellaneous info.
+type KrakenTrade = Vec<String>;
+
+/// Convert a Kraken timestamp, i.e. seconds with a decimal fraction, into milliseconds.
+fn convert_kraken_timestamp(timestamp: &str) -> Result<Timestamp, tick::ConversionError> {
+    Tick::new(1000).ticked(timestamp)
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            ws_name: symbol.name().to_owned(),
+            flags,
+            order_book: OrderBook::new(),
+        }
+    }
+
+    fn convert_kraken_level(&self, level: &[String], side: Side)
+        -> Result<Timestamped<LimitUpdate>, failure::Error>
+    {
+        if level.len() < 3 {
+            bail!("malformed price level: `{:?}`", level);
+        }
+
+        Ok(
+            LimitUpdate {
+                side,
+                price: self.symbol.price_tick().ticked(&level[0])?,
+                size: self.symbol.size_tick().ticked(&level[1])?,
+            }.with_timestamp(convert_kraken_timestamp(&level[2])?)
+        )
+    }
+
+    /// Remove the levels beyond the subscribed depth, which Kraken will not update
+    /// anymore, and return the corresponding limit updates.
+    fn truncate_order_book(&mut self, timestamp: Timestamp) -> Vec<Timestamped<LimitUpdate>> {
+        let asks = self.order_book.ask()
+            .skip(BOOK_DEPTH)
+            .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+        let bids = self.order_book.bid()
+            .skip(BOOK_DEPTH)
+            .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+        let updates: Vec<_> = asks.chain(bids).collect();
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+        updates.into_iter().map(|update| update.with_timestamp(timestamp)).collect()
+    }
+
+    /// The checksum covers the top levels of the book, asks first, each level being
+    /// represented by its price and volume without the decimal point nor the leading
+    /// zeros. Since Kraken formats prices and volumes with the precision of the pair,
+    /// these are exactly the ticked values.
+    fn checksum(&self) -> u32 {
+        let mut what = String::new();
+        let asks = self.order_book.ask().take(CHECKSUM_DEPTH);
+        let bids = self.order_book.bid().take(CHECKSUM_DEPTH);
+        for (price, size) in asks.chain(bids) {
+            write!(&mut what, "{}{}", price, size).unwrap();
+        }
+        wss::crc32(what.as_bytes())
+    }
+
+    fn parse_book(&mut self, payloads: &[Value], out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let mut updates = Vec::new();
+        let mut checksum = None;
+
+        for payload in payloads {
+            let book: KrakenBookUpdate = serde_json::from_value(payload.clone())?;
+
+            if book.snapshot_asks.is_some() || book.snapshot_bids.is_some() {
+                self.order_book = OrderBook::new();
+            }
+
+            let asks = book.snapshot_asks.iter().chain(book.asks.iter())
+                .flatten()
+                .map(|l| self.convert_kraken_level(l, Side::Ask));
+            let bids = book.snapshot_bids.iter().chain(book.bids.iter())
+                .flatten()
+                .map(|l| self.convert_kraken_level(l, Side::Bid));
+            updates.extend(asks.chain(bids).collect::<Result<Vec<_>, _>>()?);
+
+            if let Some(c) = book.checksum {
+                checksum = Some(c.parse::<u32>()?);
+            }
+        }
+
+        let mut timestamp = 0;
+        for update in &updates {
+            timestamp = std::cmp::max(timestamp, update.timestamp());
+            self.order_book.update(**update);
+        }
+        updates.extend(self.truncate_order_book(timestamp));
+
+        if let Some(checksum) = checksum {
+            if checksum != self.checksum() {
+                panic!("desynchronized order book");
+            }
+        }
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, trades: Value, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trades: Vec<KrakenTrade> = serde_json::from_value(trades)?;
+
+        for trade in trades {
+            if trade.len() < 4 {
+                bail!("malformed trade: `{:?}`", trade);
+            }
+
+            // The side is the one of the taker.
+            let maker_side = match trade[3].as_str() {
+                "b" => Side::Ask,
+                "s" => Side::Bid,
+                other => bail!("wrong side: `{}`", other),
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: self.symbol.price_tick().ticked(&trade[0])?,
+                size: self.symbol.size_tick().ticked(&trade[1])?,
+                maker_side,
+            }.with_timestamp(convert_kraken_timestamp(&trade[2])?));
+
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let value: Value = serde_json::from_str(json)?;
+
+        // Channel messages are arrays, whereas events are objects.
+        let mut message = match value {
+            Value::Array(message) => message,
+            _ => {
+                let event: KrakenEvent<'_> = serde_json::from_str(json)?;
+                if event.status == Some("error") {
+                    bail!("{}: {:?}", event.event, event.errorMessage);
+                }
+                return Ok(());
+            }
+        };
+
+        // `[channel id, payload..., channel name, pair]`
+        if message.len() < 4 {
+            bail!("malformed message: `{}`", json);
+        }
+
+        let channel_name = match &message[message.len() - 2] {
+            Value::String(name) => name.clone(),
+            _ => bail!("missing channel name: `{}`", json),
+        };
+        let payloads = &message[1..message.len() - 2];
+
+        match channel_name.as_str() {
+            name if name.starts_with("book") &&
+                self.flags.contains(NotificationFlags::ORDER_BOOK) =>
+            {
+                self.parse_book(payloads, out)?;
+            }
+
+            "trade" if self.flags.contains(NotificationFlags::TRADES) => {
+                let trades = message.swap_remove(1);
+                self.parse_trades(trades, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn send_subscription(&self, event: &str, name: &str, out: &ws::Sender) -> ws::Result<()> {
+        let subscription = KrakenSubscription {
+            event,
+            pair: Some(&[&self.ws_name]),
+            subscription: KrakenSubscriptionName {
+                name,
+                depth: if name == "book" { Some(BOOK_DEPTH) } else { None },
+                token: None,
+            },
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `KrakenSubscription`: `{}`", err);
+            }
+        }
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            self.send_subscription("subscribe", "book", out)?;
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            self.send_subscription("subscribe", "trade", out)?;
+        }
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // Orders are streamed through the private connection, where `ORDERS` only
+        // acts as a filter.
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            // A new snapshot will be sent.
+            (false, true) => self.send_subscription("subscribe", "book", out)?,
+            (true, false) => self.send_subscription("unsubscribe", "book", out)?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => self.send_subscription("subscribe", "trade", out)?,
+            (true, false) => self.send_subscription("unsubscribe", "trade", out)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct OrderState {
+    order_id: String,
+    price: TickUnit,
+    size: TickUnit,
+    side: Side,
+    remaining_size: TickUnit,
+}
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    ws_name: String,
+    token: String,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// The first message of each private feed is a snapshot.
+    orders_snapshot: bool,
+    trades_snapshot: bool,
+
+    /// server order id => client order
+    orders: HashMap<String, OrderState>,
+
+    /// server order id => client order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenOrderDescr {
+    pair: String,
+    #[serde(rename = "type")]
+    type_: String,
+    price: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenOrder {
+    status: Option<String>,
+    descr: Option<KrakenOrderDescr>,
+    vol: Option<String>,
+    vol_exec: Option<String>,
+    cl_ord_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenOwnTrade {
+    ordertxid: String,
+    pair: String,
+    time: String,
+    price: String,
+    vol: String,
+}
+
+impl PrivateHandlerImpl {
+    fn convert_kraken_side(&self, side: &str) -> Result<Side, failure::Error> {
+        let side = match side {
+            "buy" => Side::Bid,
+            "sell" => Side::Ask,
+            other => bail!("wrong side: `{}`", other),
+        };
+        Ok(side)
+    }
+
+    fn insert_order(&mut self, txid: &str, order: &KrakenOrder) -> Result<(), failure::Error> {
+        let descr = match &order.descr {
+            Some(descr) if descr.pair == self.ws_name => descr,
+            _ => return Ok(()),
+        };
+
+        let size = self.symbol.size_tick().ticked(
+            order.vol.as_ref().ok_or_else(|| format_err!("missing order volume"))?
+        )?;
+        let executed = match &order.vol_exec {
+            Some(vol_exec) => self.symbol.size_tick().ticked(vol_exec)?,
+            None => 0,
+        };
+
+        // The order id specified by the user, which defaults to the server order id
+        // in case it was left unspecified.
+        let order_id = order.cl_ord_id.clone()
+            .or_else(|| self.order_ids.get(txid).map(|order_id| order_id.clone()))
+            .unwrap_or_else(|| txid.to_owned());
+
+        // Don't forget to update the concurrent map `server order id => client order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(txid) {
+            self.order_ids.insert(txid.to_owned(), order_id.clone());
+            debug!("insert order id {} (from WSS)", order_id);
+        }
+
+        self.orders.insert(txid.to_owned(), OrderState {
+            order_id,
+            price: self.symbol.price_tick().ticked(&descr.price)?,
+            size,
+            side: self.convert_kraken_side(&descr.type_)?,
+            remaining_size: size.saturating_sub(executed),
+        });
+        Ok(())
+    }
+
+    fn parse_orders(&mut self, orders: Value, out: Option<&wss::NotifSender>)
+        -> Result<(), failure::Error>
+    {
+        let orders: Vec<HashMap<String, KrakenOrder>> = serde_json::from_value(orders)?;
+        let snapshot = mem::replace(&mut self.orders_snapshot, false);
+
+        for (txid, order) in orders.into_iter().flatten() {
+            self.insert_order(&txid, &order)?;
+
+            if snapshot {
+                continue;
+            }
+
+            match order.status.as_ref().map(|status| status.as_str()) {
+                Some("open") => {
+                    let state = match self.orders.get(&txid) {
+                        Some(state) => state,
+                        None => continue,
+                    };
+
+                    let order = OrderConfirmation {
+                        size: state.size,
+                        price: state.price,
+                        side: state.side,
+                        order_id: state.order_id.clone(),
+                    }.timestamped();
+                    if let Some(out) = out {
+                        out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+                    }
+                }
+
+                Some("canceled") | Some("expired") => {
+                    let state = match self.orders.remove(&txid) {
+                        Some(state) => state,
+                        None => continue,
+                    };
+
+                    let expiration = OrderExpiration {
+                        order_id: state.order_id,
+                        filled_size: Some(state.size - state.remaining_size),
+                        remaining_size: Some(state.remaining_size),
+                    }.timestamped();
+                    if let Some(out) = out {
+                        out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+                    }
+                }
+
+                Some("closed") => {
+                    self.orders.remove(&txid);
+                }
+
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_own_trades(&mut self, trades: Value, out: Option<&wss::NotifSender>)
+        -> Result<(), failure::Error>
+    {
+        let trades: Vec<HashMap<String, KrakenOwnTrade>> = serde_json::from_value(trades)?;
+        if mem::replace(&mut self.trades_snapshot, false) {
+            return Ok(());
+        }
+
+        for (_, trade) in trades.into_iter().flatten() {
+            if trade.pair != self.ws_name {
+                continue;
+            }
+
+            let consumed_size = self.symbol.size_tick().ticked(&trade.vol)?;
+            let consumed_price = self.symbol.price_tick().ticked(&trade.price)?;
+            let timestamp = convert_kraken_timestamp(&trade.time)?;
+
+            let state = match self.orders.get_mut(&trade.ordertxid) {
+                Some(state) => state,
+                None => continue,
+            };
+            state.remaining_size = state.remaining_size.saturating_sub(consumed_size);
+
+            let update = OrderUpdate {
+                order_id: state.order_id.clone(),
+                consumed_size,
+                remaining_size: state.remaining_size,
+                consumed_price,
+                commission: 0,
+            }.with_timestamp(timestamp);
+            if let Some(out) = out {
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let value: Value = serde_json::from_str(json)?;
+
+        let mut message = match value {
+            Value::Array(message) => message,
+            _ => {
+                let event: KrakenEvent<'_> = serde_json::from_str(json)?;
+                if event.status == Some("error") {
+                    bail!("{}: {:?}", event.event, event.errorMessage);
+                }
+                return Ok(());
+            }
+        };
+
+        // `[payload, channel name, sequence]`
+        if message.len() < 2 {
+            bail!("malformed message: `{}`", json);
+        }
+
+        // Orders must be tracked even when filtered out, so that they can be
+        // forwarded once `ORDERS` is set again.
+        let out = if self.control.flags().contains(NotificationFlags::ORDERS) {
+            Some(out)
+        } else {
+            None
+        };
+
+        let channel_name = message[1].as_str().map(|name| name.to_owned());
+        match channel_name.as_ref().map(|name| name.as_str()) {
+            Some("openOrders") => self.parse_orders(message.swap_remove(0), out)?,
+            Some("ownTrades") => self.parse_own_trades(message.swap_remove(0), out)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn send_subscription(&self, name: &str, out: &ws::Sender) -> ws::Result<()> {
+        let subscription = KrakenSubscription {
+            event: "subscribe",
+            pair: None,
+            subscription: KrakenSubscriptionName {
+                name,
+                depth: None,
+                token: Some(&self.token),
+            },
+        };
+
+        match serde_json::to_string(&subscription) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `KrakenSubscription`: `{}`", err);
+            }
+        }
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.send_subscription("openOrders", out)?;
+        self.send_subscription("ownTrades", out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+}
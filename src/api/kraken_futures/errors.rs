@@ -0,0 +1,183 @@
+//! A module defining error types specific to Kraken Futures.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Kraken Futures REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Error message or rejection status of an order or a cancel, e.g.
+    /// `insufficientAvailableFunds`: see API documentation.
+    pub error: Option<String>,
+}
+
+impl RestError {
+    fn is_error(&self, error: &str) -> bool {
+        self.error.as_ref().map(|e| e == error).unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.is_error("notFound") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.is_error("insufficientAvailableFunds") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest && self.is_error("clientOrderIdAlreadyExist") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest && self.is_error("postWouldExecute") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error) = &self.error {
+            write!(f, ": `{}`", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// Kraken Futures mostly reports errors with an HTTP 200 status code, the error
+    /// being described in the response body. Orders and cancels which are rejected
+    /// are reported with their status instead.
+    pub(super) fn from_kraken_futures_error(status: StatusCode, error: Option<String>) -> Self {
+        let kind = if status != StatusCode::OK {
+            RestErrorKind::from_status_code(status)
+        } else {
+            error.as_ref()
+                .map(|error| RestErrorKind::from_error(error))
+                .unwrap_or(RestErrorKind::BadRequest)
+        };
+
+        RestError {
+            kind,
+            error,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a Kraken Futures error to a Kraken Futures error
+/// category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key, signature or nonce, or missing permission for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Kraken. See Kraken Futures API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Kraken side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is down or busy, or the market is suspended.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::OK => panic!("`RestErrorKind::from_status_code` with `StatusCode::Ok`"),
+            StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_error(error: &str) -> Self {
+        use self::RestErrorKind::*;
+        match error {
+            "apiLimitExceeded" => TooManyRequests,
+
+            "authenticationError" |
+            "nonceBelowThreshold" |
+            "nonceDuplicate" |
+            "accountInactive" => Unauthorized,
+
+            "Server Error" => InternalError,
+
+            "Unavailable" |
+            "marketUnavailable" |
+            "marketSuspended" |
+            "marketInactive" => ServiceUnavailable,
+
+            _ => BadRequest,
+        }
+    }
+}
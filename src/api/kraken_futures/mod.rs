@@ -0,0 +1,267 @@
+//! Implementation of `ApiClient` for the Kraken Futures API, which is distinct from
+//! the spot API of `api::kraken`: keys, symbols and endpoints are not shared.
+//!
+//! Only perpetual contracts margined by the multi-collateral account, i.e. symbols
+//! named like `PF_XBTUSD`, are supported. `params.rest_endpoint` is the base of the
+//! versioned APIs, e.g. `https://futures.kraken.com/derivatives/api`.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::{debug, warn};
+use crate::tick::{Tick, TickUnit, ConversionError};
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::derivatives::{DerivativesApi, Position, FundingRate, OpenInterest};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamp, Timestamped};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A Kraken Futures key pair: api key + secret key (base64 encoded, as provided by
+/// Kraken).
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+}
+
+impl Keys {
+    /// Both REST requests and WebSocket challenges are signed by hashing the message
+    /// with SHA-256 first, then signing the digest with HMAC-SHA512.
+    fn signature(&self, message: &str) -> String {
+        let digest = openssl::sha::sha256(message.as_bytes());
+        let mut signer = Signer::new(MessageDigest::sha512(), &self.secret_key).unwrap();
+        signer.update(&digest).unwrap();
+        base64::encode(&signer.sign_to_vec().unwrap())
+    }
+}
+
+/// Prices and sizes are sent as JSON numbers.
+fn convert_kraken_number(tick: Tick, value: f64) -> Result<TickUnit, ConversionError> {
+    tick.ticked(&format!("{}", value))
+}
+
+/// A Kraken Futures API client.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// Last nonce sent along a private request, nonces must be strictly increasing.
+    nonce: Arc<Mutex<u64>>,
+
+    /// server order id => client order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+
+    /// symbol name (lowercase) => highest leverage allowed by the margin schedule,
+    /// which applies until a maximum leverage is set with `set_leverage`.
+    max_leverages: HashMap<String, u32>,
+
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Params {
+    /// Return the parameters of the Kraken Futures demo environment, where keys are
+    /// generated at `https://demo-futures.kraken.com`.
+    ///
+    /// # Note
+    /// `system_status` still reports the status of the production exchange.
+    pub fn kraken_futures_demo() -> Self {
+        Params {
+            streaming_endpoint: "wss://demo-futures.kraken.com/ws/v1".to_owned(),
+            rest_endpoint: "https://demo-futures.kraken.com/derivatives/api".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new Kraken Futures API client with given `params`. If `key_pair` is
+    /// not `None`, this will enable performing requests to the REST API and will
+    /// forward the order and position feeds.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Kraken.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(&base64::decode(&pair.secret_key)?)?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            nonce: Arc::new(Mutex::new(0)),
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            max_leverages: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        let (symbols, max_leverages) = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        client.symbols = symbols;
+        client.max_leverages = max_leverages;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::KrakenFutures
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl DerivativesApi for Client {
+    fn set_leverage(&self, symbol: &Symbol, leverage: u32)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.set_leverage_impl(symbol, leverage))
+    }
+
+    fn positions(&self)
+        -> Box<dyn Future<Item = Vec<Position>, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.positions_impl())
+    }
+
+    fn funding_rates(&self, symbol: &Symbol, start: Timestamp, end: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<FundingRate>>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.funding_rates_impl(symbol, start, end))
+    }
+
+    /// Kraken Futures only publishes the current open interest, hence the request is
+    /// always rejected with `RestErrorKind::InvalidRequest`.
+    fn open_interest(&self, _: &Symbol, _: Timestamp, _: Timestamp, _: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<OpenInterest>>, Error = api::errors::Error> + Send + 'static>
+    {
+        warn!("called `open_interest`, which has no history on Kraken Futures");
+        Box::new(futures::future::err(
+            api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Kraken Futures accepts free text client order ids of at most 100 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 100 {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().to_string()
+        }
+    }
+}
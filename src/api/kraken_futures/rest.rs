@@ -0,0 +1,674 @@
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::Deserialize;
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::derivatives::{Position, FundingRate};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{
+    convert_str_timestamp,
+    timestamp_ms,
+    Timestamp,
+    Timestamped,
+    IntoTimestamped,
+};
+use crate::api::kraken_futures::Client;
+use crate::api::kraken_futures::errors::RestError;
+
+/// Kraken publishes the status of its services on a Statuspage instance, which is
+/// not part of the REST API.
+const STATUS_ENDPOINT: &str = "https://status.kraken.com/api/v2/status.json";
+
+/// Ticks used for computing locked balances, collateral quantities being expressed
+/// with up to 8 decimals.
+const BALANCE_TICK: u64 = 100_000_000;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenFuturesResult {
+    result: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenServerTime<'a> {
+    serverTime: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenSendStatus<'a> {
+    order_id: Option<&'a str>,
+    status: &'a str,
+    receivedTime: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenOrderAck<'a> {
+    #[serde(borrow)]
+    sendStatus: KrakenSendStatus<'a>,
+    serverTime: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenCancelStatus<'a> {
+    status: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenCancelAck<'a> {
+    #[serde(borrow)]
+    cancelStatus: KrakenCancelStatus<'a>,
+    serverTime: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct KrakenFlexCurrency {
+    quantity: f64,
+    available: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenFlexAccount {
+    currencies: HashMap<String, KrakenFlexCurrency>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenAccountsByType {
+    flex: Option<KrakenFlexAccount>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenAccounts {
+    accounts: KrakenAccountsByType,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct KrakenOpenPosition<'a> {
+    side: &'a str,
+    symbol: &'a str,
+    price: f64,
+    size: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenOpenPositions<'a> {
+    #[serde(borrow)]
+    openPositions: Vec<KrakenOpenPosition<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenLeveragePreference<'a> {
+    symbol: &'a str,
+    maxLeverage: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenLeveragePreferences<'a> {
+    #[serde(borrow)]
+    leveragePreferences: Vec<KrakenLeveragePreference<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenTicker<'a> {
+    symbol: &'a str,
+    markPrice: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenTickers<'a> {
+    #[serde(borrow)]
+    tickers: Vec<KrakenTicker<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenFundingRate<'a> {
+    timestamp: &'a str,
+    relativeFundingRate: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenFundingRates<'a> {
+    #[serde(borrow)]
+    rates: Vec<KrakenFundingRate<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenMarginLevel {
+    initialMargin: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KrakenInstrument<'a> {
+    symbol: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    tradeable: bool,
+    tickSize: Option<f64>,
+    contractValueTradePrecision: Option<i32>,
+    #[serde(default)]
+    marginLevels: Vec<KrakenMarginLevel>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenInstruments<'a> {
+    #[serde(borrow)]
+    instruments: Vec<KrakenInstrument<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenStatusIndicator<'a> {
+    indicator: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenStatusPage<'a> {
+    #[serde(borrow)]
+    status: KrakenStatusIndicator<'a>,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+fn convert_timestamp<K: api::errors::ErrorKind>(timestamp: &str)
+    -> Result<Timestamp, api::errors::ApiError<K>>
+{
+    convert_str_timestamp(timestamp)
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+}
+
+impl Client {
+    /// Requests are signed whenever a key pair is available, public endpoints just
+    /// ignoring the signature. Parameters are sent in the body of POST requests, and
+    /// in the query string otherwise: both are signed the same way.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let address = if method == Method::POST || query.is_empty() {
+            format!("{}/{}", self.params.rest_endpoint, endpoint)
+        } else {
+            format!("{}/{}?{}", self.params.rest_endpoint, endpoint, query)
+        };
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let nonce = {
+                let mut last = self.nonce.lock().unwrap();
+                *last = std::cmp::max(*last + 1, timestamp_ms());
+                *last
+            };
+
+            // The signed path starts at `/api/`, without the `/derivatives` prefix of
+            // the REST endpoint.
+            let signature = keys.signature(&format!("{}{}/api/{}", query, nonce, endpoint));
+
+            request.header("APIKey", keys.api_key.as_bytes())
+                .header("Nonce", nonce.to_string().as_bytes())
+                .header("Authent", signature.as_bytes());
+        }
+
+        let body = if method == Method::POST { query } else { String::new() };
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/x-www-form-urlencoded"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let (result, error) = match serde_json::from_slice(&body) {
+                Ok(KrakenFuturesResult { result, error }) => (result, error),
+                Err(_) => (None, None),
+            };
+
+            if status != hyper::StatusCode::OK || result.as_ref().map(|r| r == "error") == Some(true) {
+                let error = RestError::from_kraken_futures_error(status, error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Kraken Futures");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Kraken Futures");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        // Post only orders rest in the book until canceled.
+        let order_type = match (order.type_, order.time_in_force) {
+            (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => "post",
+            (OrderType::Limit, TimeInForce::GoodTilCanceled) => "lmt",
+            (OrderType::Limit, TimeInForce::ImmediateOrCancel) => "ioc",
+            (_, time_in_force) => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported by Kraken Futures",
+                    order.type_,
+                    time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let mut query = QueryString::new();
+        let symbol = order.symbol();
+        query.push_str("orderType", order_type);
+        query.push_str("symbol", symbol.name());
+        query.push_str("side", order.side.as_str());
+        query.push_str(
+            "size",
+            order.size.unticked(symbol.size_tick()).borrow() as &str
+        );
+        query.push_str(
+            "limitPrice",
+            order.price.unticked(symbol.price_tick()).borrow() as &str
+        );
+
+        if let Some(order_id) = &order.order_id {
+            query.push_str("cliOrdId", order_id);
+        }
+
+        if order.reduce_only {
+            query.push_str("reduceOnly", "true");
+        }
+
+        let client_order_id = order.order_id.clone();
+        let order_ids = self.order_ids.clone();
+
+        let fut = self.request("v3/sendorder", Method::POST, query).and_then(move |body| {
+            let ack: KrakenOrderAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // Rejected orders are reported along with a successful result.
+            let status = ack.sendStatus;
+            let order_id = match (status.status, status.order_id) {
+                ("placed", Some(order_id)) |
+                ("partiallyFilled", Some(order_id)) |
+                ("filled", Some(order_id)) => order_id,
+                (status, _) => {
+                    let error = RestError::from_kraken_futures_error(
+                        hyper::StatusCode::OK,
+                        Some(status.to_owned())
+                    );
+                    let kind = error.kind();
+                    return Err(api::errors::ApiError::RestError(error.context(kind).into()));
+                }
+            };
+
+            let timestamp = convert_timestamp(status.receivedTime.unwrap_or(ack.serverTime))?;
+
+            // The order id specified by the user, which defaults to the server order id
+            // in case it was left unspecified.
+            let client_order_id = client_order_id.unwrap_or_else(|| order_id.to_owned());
+            order_ids.insert(order_id.to_owned(), client_order_id.clone());
+            debug!("insert order id {} (from REST)", client_order_id);
+
+            Ok(OrderAck {
+                order_id: client_order_id,
+            }.with_timestamp(timestamp))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+
+        // Orders inserted without a client order id are known by their server order id.
+        if self.order_ids.contains_key(&cancel.order_id) {
+            query.push_str("order_id", &cancel.order_id);
+        } else {
+            query.push_str("cliOrdId", &cancel.order_id);
+        }
+
+        self.request("v3/cancelorder", Method::POST, query).and_then(|body| {
+            let ack: KrakenCancelAck<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            if ack.cancelStatus.status != "cancelled" {
+                let error = RestError::from_kraken_futures_error(
+                    hyper::StatusCode::OK,
+                    Some(ack.cancelStatus.status.to_owned())
+                );
+                let kind = error.kind();
+                return Err(api::errors::ApiError::RestError(error.context(kind).into()));
+            }
+
+            Ok(CancelAck.with_timestamp(convert_timestamp(ack.serverTime)?))
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v3/feeschedules", Method::GET, QueryString::new()).and_then(|body| {
+            let time: KrakenServerTime<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(convert_timestamp(time.serverTime)?))
+        })
+    }
+
+    /// Balances are the collaterals of the multi-collateral account, the part which is
+    /// not available being reported as locked.
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("v3/accounts", Method::GET, QueryString::new()).and_then(|body| {
+            let accounts: KrakenAccounts = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let currencies = match accounts.accounts.flex {
+                Some(flex) => flex.currencies,
+                None => HashMap::new(),
+            };
+
+            let tick = Tick::new(BALANCE_TICK);
+            let mut result = Balances::new();
+            for (currency, balance) in currencies {
+                let free = format!("{}", balance.available);
+                let locked = tick.ticked(&format!("{}", balance.quantity))
+                    .and_then(|quantity| tick.unticked(quantity.saturating_sub(tick.ticked(&free)?)))
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+                result.insert(currency, Balance {
+                    free,
+                    locked,
+                });
+            }
+            Ok(result)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let address = STATUS_ENDPOINT.parse().expect("invalid address");
+
+        self.http_client.get(address).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError).and_then(|(status, body)| {
+            if !status.is_success() {
+                let error = RestError::from_kraken_futures_error(status, None);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+
+            let page: KrakenStatusPage<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let status = match page.status.indicator {
+                "none" => ExchangeStatus::Normal,
+                "minor" => ExchangeStatus::Degraded,
+                "maintenance" => ExchangeStatus::Maintenance,
+                _ => ExchangeStatus::Outage,
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn set_leverage_impl(&self, symbol: &Symbol, leverage: u32)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        query.push("maxLeverage", leverage);
+
+        self.request("v3/leveragepreferences", Method::PUT, query).and_then(|body| {
+            let time: KrakenServerTime<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(convert_timestamp(time.serverTime)?))
+        })
+    }
+
+    /// Kraken does not report the unrealized profit of a position, which is computed
+    /// from the mark price of the contract. The leverage is the maximum leverage set for
+    /// the contract, or the highest one allowed if none was set.
+    crate fn positions_impl(&self)
+        -> impl Future<Item = Vec<Position>, Error = api::errors::Error> + Send + 'static
+    {
+        let positions = self.request("v3/openpositions", Method::GET, QueryString::new());
+        let preferences = self.request("v3/leveragepreferences", Method::GET, QueryString::new());
+        let tickers = self.request("v3/tickers", Method::GET, QueryString::new());
+        let max_leverages = self.max_leverages.clone();
+
+        positions.join3(preferences, tickers).and_then(move |(positions, preferences, tickers)| {
+            let positions: KrakenOpenPositions<'_> = serde_json::from_slice(&positions)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let preferences: KrakenLeveragePreferences<'_> = serde_json::from_slice(&preferences)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let tickers: KrakenTickers<'_> = serde_json::from_slice(&tickers)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let leverages: HashMap<_, _> = preferences.leveragePreferences.iter()
+                .map(|p| (p.symbol.to_lowercase(), p.maxLeverage.round() as u32))
+                .collect();
+            let mark_prices: HashMap<_, _> = tickers.tickers.iter()
+                .filter_map(|t| t.markPrice.map(|price| (t.symbol.to_lowercase(), price)))
+                .collect();
+
+            let mut result = Vec::new();
+            for position in positions.openPositions {
+                if position.size == 0. {
+                    continue;
+                }
+
+                let name = position.symbol.to_lowercase();
+                let side = match position.side {
+                    "long" => Side::Bid,
+                    "short" => Side::Ask,
+                    other => {
+                        error!("wrong position side: `{}`", other);
+                        continue;
+                    }
+                };
+
+                let unrealized_pnl = match mark_prices.get(&name) {
+                    Some(mark_price) if side == Side::Bid => {
+                        (mark_price - position.price) * position.size
+                    }
+                    Some(mark_price) => (position.price - mark_price) * position.size,
+                    None => 0.,
+                };
+
+                let leverage = leverages.get(&name)
+                    .or_else(|| max_leverages.get(&name))
+                    .cloned()
+                    .unwrap_or(1);
+
+                result.push(Position {
+                    symbol: position.symbol.to_uppercase(),
+                    side,
+                    size: format!("{}", position.size),
+                    entry_price: format!("{}", position.price),
+                    unrealized_pnl: format!("{}", unrealized_pnl),
+                    leverage,
+                });
+            }
+            Ok(result)
+        })
+    }
+
+    /// The whole history is returned at once, and filtered afterwards.
+    crate fn funding_rates_impl(&self, symbol: &Symbol, start: Timestamp, end: Timestamp)
+        -> impl Future<Item = Vec<Timestamped<FundingRate>>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("symbol", symbol.name());
+        let name = symbol.name().to_owned();
+
+        self.request("v4/historicalfundingrates", Method::GET, query).and_then(move |body| {
+            let kraken_rates: KrakenFundingRates<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut rates = Vec::new();
+            for rate in kraken_rates.rates {
+                let timestamp = convert_timestamp(rate.timestamp)?;
+                if timestamp < start || timestamp > end {
+                    continue;
+                }
+
+                rates.push(FundingRate {
+                    symbol: name.clone(),
+                    rate: format!("{}", rate.relativeFundingRate),
+                }.with_timestamp(timestamp));
+            }
+            Ok(rates)
+        })
+    }
+
+    /// Return the available symbols, along with the highest leverage allowed on each.
+    crate fn get_symbols(&self)
+        -> impl Future<
+            Item = (HashMap<String, Symbol>, HashMap<String, u32>),
+            Error = api::errors::Error
+        > + Send + 'static
+    {
+        self.request("v3/instruments", Method::GET, QueryString::new()).and_then(|body| {
+            let instruments: KrakenInstruments<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            let mut max_leverages = HashMap::new();
+            for i in instruments.instruments {
+                if !i.tradeable || i.type_ != "flexible_futures" || !i.symbol.starts_with("PF_") {
+                    continue;
+                }
+
+                let price_tick = i.tickSize.and_then(|size| Tick::tick_size(&format!("{}", size)));
+                let size_tick = i.contractValueTradePrecision
+                    .filter(|precision| *precision >= 0)
+                    .and_then(|precision| 10u64.checked_pow(precision as u32))
+                    .map(Tick::new);
+
+                let (price_tick, size_tick) = match (price_tick, size_tick) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", i.symbol);
+                        continue;
+                    }
+                };
+
+                if let Some(symbol) = Symbol::new(i.symbol, price_tick, size_tick) {
+                    let name = symbol.name().to_lowercase();
+                    let initial_margin = i.marginLevels.first().map(|l| l.initialMargin);
+                    if let Some(margin) = initial_margin.filter(|margin| *margin > 0.) {
+                        max_leverages.insert(name.clone(), (1. / margin).round() as u32);
+                    }
+                    symbols.insert(name, symbol);
+                } else {
+                    error!("symbol name too long: `{}`", i.symbol);
+                }
+            }
+            Ok((symbols, max_leverages))
+        })
+    }
+}
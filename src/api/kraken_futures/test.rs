@@ -0,0 +1,101 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::kraken_futures::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("PI_XBTUSD", Tick::new(2), Tick::new(1)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the results of the
+/// parsing along with the notifications.
+fn parse(frames: &[String]) -> (Vec<bool>, Vec<Notification>) {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    let parsed = frames.iter().map(|frame| handler.parse_message(frame, &out).is_ok()).collect();
+    drop(out);
+    (parsed, receiver.wait().map(|notif| notif.unwrap()).collect())
+}
+
+fn snapshot(seq: u64, timestamp: u64, bids: &str, asks: &str) -> String {
+    format!(
+        r#"{{"feed":"book_snapshot","product_id":"PI_XBTUSD","timestamp":{},"seq":{},
+            "tickSize":null,"bids":[{}],"asks":[{}]}}"#,
+        timestamp,
+        seq,
+        bids,
+        asks
+    )
+}
+
+fn first_snapshot() -> String {
+    snapshot(
+        1,
+        1_612_269_825_817,
+        r#"{"price":34892.5,"qty":6385},{"price":34892.0,"qty":10924}"#,
+        r#"{"price":34911.5,"qty":20598},{"price":34912.0,"qty":2300}"#
+    )
+}
+
+fn update(seq: u64, side: &str, price: f64, qty: f64) -> String {
+    format!(
+        r#"{{"feed":"book","product_id":"PI_XBTUSD","side":"{}","seq":{},"price":{},
+            "qty":{},"timestamp":1612269825830}}"#,
+        side,
+        seq,
+        price,
+        qty
+    )
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let (parsed, notifs) = parse(&[first_snapshot(), update(2, "sell", 34911.5, 0.)]);
+    assert_eq!(parsed, vec![true, true]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(69_784, 10_924, Side::Bid).with_timestamp(1_612_269_825_817),
+            LimitUpdate::new(69_785, 6385, Side::Bid).with_timestamp(1_612_269_825_817),
+            LimitUpdate::new(69_823, 20_598, Side::Ask).with_timestamp(1_612_269_825_817),
+            LimitUpdate::new(69_824, 2300, Side::Ask).with_timestamp(1_612_269_825_817),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(69_823, 0, Side::Ask).with_timestamp(1_612_269_825_830),
+        ]),
+    ]);
+}
+
+#[test]
+fn book_sequence_gap() {
+    // Updates are dropped until the next snapshot, both before the first one and after
+    // a gap, and the next snapshot is turned into updates.
+    let frames = [
+        update(1, "buy", 34892.5, 6000.),
+        first_snapshot(),
+        update(3, "buy", 34892.5, 6000.),
+        update(4, "buy", 34892.0, 0.),
+        snapshot(
+            10,
+            1_612_269_826_000,
+            r#"{"price":34892.5,"qty":6000}"#,
+            r#"{"price":34912.0,"qty":2300}"#
+        ),
+    ];
+    let (parsed, notifs) = parse(&frames);
+    assert_eq!(parsed, vec![true, true, false, true, true]);
+    assert_eq!(notifs.len(), 2);
+    assert_eq!(notifs[1], Notification::LimitUpdates(vec![
+        LimitUpdate::new(69_785, 6000, Side::Bid).with_timestamp(1_612_269_826_000),
+        LimitUpdate::new(69_784, 0, Side::Bid).with_timestamp(1_612_269_826_000),
+        LimitUpdate::new(69_823, 0, Side::Ask).with_timestamp(1_612_269_826_000),
+    ]));
+}
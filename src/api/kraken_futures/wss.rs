@@ -0,0 +1,689 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use std::collections::HashMap;
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::derivatives::Position;
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+use crate::api::kraken_futures::{Client, Keys, convert_kraken_number};
+
+const BOOK_FEED: &str = "book";
+const TRADE_FEED: &str = "trade";
+const ORDERS_FEED: &str = "open_orders";
+const FILLS_FEED: &str = "fills";
+const POSITIONS_FEED: &str = "open_positions";
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let endpoint = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+        let order_ids = self.order_ids.clone();
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", endpoint);
+
+            if let Err(err) = ws::connect(endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        out: None,
+                        challenge: None,
+                        order_book: OrderBook::new(),
+                        book_seq: None,
+                        orders: HashMap::new(),
+                        order_ids: order_ids.clone(),
+                        position: None,
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Public and private feeds are subscribed to over the same connection, the private
+/// ones once the challenge sent by Kraken has been signed.
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Set once the connection is open, needed for resubscribing to the book from
+    /// within `on_message`.
+    out: Option<ws::Sender>,
+
+    /// Challenge sent by Kraken along with its signature, sent back with each
+    /// subscription to a private feed.
+    challenge: Option<(String, String)>,
+
+    /// Local copy of the order book, needed for removing the levels which are not part
+    /// of a new snapshot.
+    order_book: OrderBook,
+
+    /// Sequence number of the last book message, updates are ignored until a snapshot
+    /// is received.
+    book_seq: Option<u64>,
+
+    /// server order id => order
+    orders: HashMap<String, OrderState>,
+
+    /// server order id => client order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+
+    /// Last position forwarded, so that its closing can be notified.
+    position: Option<Position>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct OrderState {
+    order_id: String,
+    size: TickUnit,
+    remaining_size: TickUnit,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct KrakenRequest<'a> {
+    event: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feed: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    product_ids: Option<&'a [&'a str]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_challenge: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signed_challenge: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KrakenEvent<'a> {
+    event: Option<&'a str>,
+    feed: Option<&'a str>,
+    message: Option<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct KrakenLevel {
+    price: f64,
+    qty: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenBookSnapshot {
+    seq: u64,
+    timestamp: u64,
+    bids: Vec<KrakenLevel>,
+    asks: Vec<KrakenLevel>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct KrakenBookUpdate<'a> {
+    side: &'a str,
+    seq: u64,
+    price: f64,
+    qty: f64,
+    timestamp: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+struct KrakenTrade<'a> {
+    side: &'a str,
+    time: u64,
+    qty: f64,
+    price: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenOrder {
+    instrument: String,
+    last_update_time: u64,
+    qty: f64,
+    filled: f64,
+    limit_price: f64,
+    order_id: String,
+    cli_ord_id: Option<String>,
+    direction: u8,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenOrdersSnapshot {
+    orders: Vec<KrakenOrder>,
+}
+
+/// Either an order which was inserted or updated, or the id of an order which was
+/// removed from the book.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenOrdersUpdate {
+    order: Option<KrakenOrder>,
+    order_id: Option<String>,
+    is_cancel: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenFill {
+    instrument: String,
+    time: u64,
+    price: f64,
+    qty: f64,
+    order_id: String,
+    cli_ord_id: Option<String>,
+    fee_paid: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenFills {
+    fills: Vec<KrakenFill>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenPosition {
+    instrument: String,
+    balance: f64,
+    entry_price: f64,
+    pnl: f64,
+    effective_leverage: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct KrakenPositions {
+    positions: Vec<KrakenPosition>,
+    timestamp: Option<u64>,
+}
+
+/// The side sent along book levels and trades, i.e. the side of the taker for trades.
+fn convert_kraken_side(side: &str) -> Result<Side, failure::Error> {
+    match side {
+        "buy" => Ok(Side::Bid),
+        "sell" => Ok(Side::Ask),
+        other => bail!("wrong side: `{}`", other),
+    }
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys and which is not
+    /// connected, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: None,
+            out: None,
+            challenge: None,
+            order_book: OrderBook::new(),
+            book_seq: None,
+            orders: HashMap::new(),
+            order_ids: Arc::new(CHashMap::new()),
+            position: None,
+        }
+    }
+
+    fn send_request(&self, request: &KrakenRequest<'_>) -> ws::Result<()> {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        match serde_json::to_string(request) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `KrakenRequest`: `{}`", err);
+            }
+        }
+    }
+
+    fn subscribe(&self, event: &str, feeds: &[&str]) -> ws::Result<()> {
+        let product_ids = [self.symbol.name()];
+
+        for feed in feeds {
+            let private = *feed != BOOK_FEED && *feed != TRADE_FEED;
+            let (api_key, challenge) = match (&self.keys, &self.challenge) {
+                (Some(keys), Some(challenge)) if private => {
+                    (Some(keys.api_key.as_str()), Some(challenge))
+                }
+                _ => (None, None),
+            };
+
+            self.send_request(&KrakenRequest {
+                event,
+                feed: Some(feed),
+                product_ids: if private { None } else { Some(&product_ids) },
+                api_key,
+                original_challenge: challenge.map(|(original, _)| original.as_str()),
+                signed_challenge: challenge.map(|(_, signed)| signed.as_str()),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Return the feeds enabled by `flags`, the private ones only if the challenge has
+    /// been signed.
+    fn feeds(&self, flags: NotificationFlags) -> Vec<&'static str> {
+        let mut feeds = Vec::new();
+        if flags.contains(NotificationFlags::ORDER_BOOK) {
+            feeds.push(BOOK_FEED);
+        }
+        if flags.contains(NotificationFlags::TRADES) {
+            feeds.push(TRADE_FEED);
+        }
+        if self.challenge.is_some() && flags.contains(NotificationFlags::ORDERS) {
+            feeds.push(ORDERS_FEED);
+            feeds.push(FILLS_FEED);
+        }
+        if self.challenge.is_some() && flags.contains(NotificationFlags::POSITIONS) {
+            feeds.push(POSITIONS_FEED);
+        }
+        feeds
+    }
+
+    /// Private feeds require signing a challenge, so that the secret key is never sent.
+    fn request_challenge(&self) -> ws::Result<()> {
+        let keys = match &self.keys {
+            Some(keys) => keys,
+            None => return Ok(()),
+        };
+
+        self.send_request(&KrakenRequest {
+            event: "challenge",
+            feed: None,
+            product_ids: None,
+            api_key: Some(&keys.api_key),
+            original_challenge: None,
+            signed_challenge: None,
+        })
+    }
+
+    /// The order id specified by the user, which defaults to the server order id in case
+    /// it was left unspecified.
+    fn order_id(&self, server_order_id: &str, cli_ord_id: Option<&String>) -> String {
+        let order_id = cli_ord_id.cloned()
+            .or_else(|| self.order_ids.get(server_order_id).map(|order_id| order_id.clone()))
+            .unwrap_or_else(|| server_order_id.to_owned());
+
+        // Don't forget to update the concurrent map `server order id => client order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(server_order_id) {
+            self.order_ids.insert(server_order_id.to_owned(), order_id.clone());
+            debug!("insert order id {} (from WSS)", order_id);
+        }
+        order_id
+    }
+
+    fn parse_book_snapshot(&mut self, json: &str, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let snapshot: KrakenBookSnapshot = serde_json::from_str(json)?;
+
+        let mut order_book = OrderBook::new();
+        for (levels, side) in &[(&snapshot.bids, Side::Bid), (&snapshot.asks, Side::Ask)] {
+            for level in levels.iter() {
+                order_book.update(LimitUpdate {
+                    side: *side,
+                    price: convert_kraken_number(self.symbol.price_tick(), level.price)?,
+                    size: convert_kraken_number(self.symbol.size_tick(), level.qty)?,
+                });
+            }
+        }
+
+        // Levels which are not part of the new snapshot are removed.
+        let updates: Vec<_> = self.order_book.diff(&order_book)
+            .map(|update| update.with_timestamp(snapshot.timestamp))
+            .collect();
+        self.order_book = order_book;
+        self.book_seq = Some(snapshot.seq);
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_book_update(&mut self, json: &str, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let update: KrakenBookUpdate<'_> = serde_json::from_str(json)?;
+
+        match self.book_seq {
+            // Still waiting for the snapshot.
+            None => return Ok(()),
+
+            // A message was missed, start again from a new snapshot.
+            Some(seq) if update.seq != seq + 1 => {
+                self.book_seq = None;
+                self.subscribe("unsubscribe", &[BOOK_FEED])?;
+                self.subscribe("subscribe", &[BOOK_FEED])?;
+                bail!("missed book message: expected seq {}, got {}", seq + 1, update.seq);
+            }
+
+            Some(_) => self.book_seq = Some(update.seq),
+        }
+
+        let update = LimitUpdate {
+            side: convert_kraken_side(update.side)?,
+            price: convert_kraken_number(self.symbol.price_tick(), update.price)?,
+            size: convert_kraken_number(self.symbol.size_tick(), update.qty)?,
+        }.with_timestamp(update.timestamp);
+        self.order_book.update(*update);
+
+        out.unbounded_send(Notification::LimitUpdates(vec![update])).unwrap();
+        Ok(())
+    }
+
+    fn parse_trade(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trade: KrakenTrade<'_> = serde_json::from_str(json)?;
+
+        // The side is the one of the taker.
+        let maker_side = match convert_kraken_side(trade.side)? {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let trade = Notification::Trade(Trade {
+            price: convert_kraken_number(self.symbol.price_tick(), trade.price)?,
+            size: convert_kraken_number(self.symbol.size_tick(), trade.qty)?,
+            maker_side,
+        }.with_timestamp(trade.time));
+
+        out.unbounded_send(trade).unwrap();
+        Ok(())
+    }
+
+    /// Insert or update the state of `order`, return the order confirmation if the order
+    /// was not known yet.
+    fn insert_order(&mut self, order: &KrakenOrder)
+        -> Result<Option<Timestamped<OrderConfirmation>>, failure::Error>
+    {
+        let size = convert_kraken_number(self.symbol.size_tick(), order.qty)?;
+        let filled = convert_kraken_number(self.symbol.size_tick(), order.filled)?;
+
+        if let Some(state) = self.orders.get_mut(&order.order_id) {
+            state.remaining_size = size.saturating_sub(filled);
+            return Ok(None);
+        }
+
+        let order_id = self.order_id(&order.order_id, order.cli_ord_id.as_ref());
+        self.orders.insert(order.order_id.clone(), OrderState {
+            order_id: order_id.clone(),
+            size,
+            remaining_size: size.saturating_sub(filled),
+        });
+
+        Ok(Some(OrderConfirmation {
+            order_id,
+            price: convert_kraken_number(self.symbol.price_tick(), order.limit_price)?,
+            size,
+            side: if order.direction == 0 { Side::Bid } else { Side::Ask },
+        }.with_timestamp(order.last_update_time)))
+    }
+
+    fn parse_orders_snapshot(&mut self, json: &str) -> Result<(), failure::Error> {
+        let snapshot: KrakenOrdersSnapshot = serde_json::from_str(json)?;
+
+        self.orders.clear();
+        for order in &snapshot.orders {
+            if order.instrument == self.symbol.name() {
+                self.insert_order(order)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_orders(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let update: KrakenOrdersUpdate = serde_json::from_str(json)?;
+
+        if let Some(order) = &update.order {
+            if order.instrument != self.symbol.name() {
+                return Ok(());
+            }
+
+            if let Some(confirmation) = self.insert_order(order)? {
+                out.unbounded_send(Notification::OrderConfirmation(confirmation)).unwrap();
+            }
+            return Ok(());
+        }
+
+        let state = match update.order_id.and_then(|order_id| self.orders.remove(&order_id)) {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+
+        // Orders which were fully filled are notified by the `fills` feed.
+        if update.is_cancel {
+            let expiration = OrderExpiration {
+                order_id: state.order_id,
+                filled_size: Some(state.size - state.remaining_size),
+                remaining_size: Some(state.remaining_size),
+            }.timestamped();
+            out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_fills(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let fills: KrakenFills = serde_json::from_str(json)?;
+
+        for fill in fills.fills {
+            if fill.instrument != self.symbol.name() {
+                continue;
+            }
+
+            let consumed_size = convert_kraken_number(self.symbol.size_tick(), fill.qty)?;
+
+            // The order may already have been removed by the `open_orders` feed if this
+            // fill completed it.
+            let (order_id, remaining_size) = match self.orders.get_mut(&fill.order_id) {
+                Some(state) => {
+                    state.remaining_size = state.remaining_size.saturating_sub(consumed_size);
+                    (state.order_id.clone(), state.remaining_size)
+                }
+                None => (self.order_id(&fill.order_id, fill.cli_ord_id.as_ref()), 0),
+            };
+            if remaining_size == 0 {
+                self.orders.remove(&fill.order_id);
+            }
+
+            // Rebates are reported as negative fees.
+            let update = OrderUpdate {
+                order_id,
+                consumed_size,
+                remaining_size,
+                consumed_price: convert_kraken_number(self.symbol.price_tick(), fill.price)?,
+                commission: convert_kraken_number(
+                    self.symbol.commission_tick(),
+                    fill.fee_paid.abs()
+                )?,
+            }.with_timestamp(fill.time);
+            out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Each message lists all the open positions of the account, a position which is
+    /// not listed anymore was closed.
+    fn parse_positions(&mut self, json: &str, out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let positions: KrakenPositions = serde_json::from_str(json)?;
+        let timestamp = positions.timestamp.unwrap_or_else(timestamp_ms);
+
+        let position = positions.positions.iter()
+            .find(|position| position.instrument == self.symbol.name())
+            .map(|position| Position {
+                symbol: self.symbol.name().to_owned(),
+                side: if position.balance < 0. { Side::Ask } else { Side::Bid },
+                size: format!("{}", position.balance.abs()),
+                entry_price: format!("{}", position.entry_price),
+                unrealized_pnl: format!("{}", position.pnl),
+                leverage: position.effective_leverage.unwrap_or(0.).round() as u32,
+            });
+
+        let position = match (position, self.position.take()) {
+            (Some(position), _) => position,
+            (None, Some(previous)) => Position {
+                size: "0".to_owned(),
+                unrealized_pnl: "0".to_owned(),
+                ..previous
+            },
+            (None, None) => return Ok(()),
+        };
+
+        if position.size != "0" {
+            self.position = Some(position.clone());
+        }
+        out.unbounded_send(Notification::PositionUpdate(position.with_timestamp(timestamp))).unwrap();
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: KrakenEvent<'_> = serde_json::from_str(json)?;
+
+        match event.event {
+            Some("challenge") => {
+                let challenge = match (&self.keys, event.message) {
+                    (Some(keys), Some(challenge)) => {
+                        let signed = keys.signature(&challenge);
+                        (challenge, signed)
+                    }
+                    _ => bail!("unexpected challenge: `{}`", json),
+                };
+                self.challenge = Some(challenge);
+
+                let mut feeds = Vec::new();
+                if self.flags.contains(NotificationFlags::ORDERS) {
+                    feeds.push(ORDERS_FEED);
+                    feeds.push(FILLS_FEED);
+                }
+                if self.flags.contains(NotificationFlags::POSITIONS) {
+                    feeds.push(POSITIONS_FEED);
+                }
+                self.subscribe("subscribe", &feeds)?;
+                return Ok(());
+            }
+
+            Some("error") | Some("alert") | Some("subscribed_failed") => {
+                bail!("{}: {:?}", event.event.unwrap_or_default(), event.message)
+            }
+
+            // Subscription acknowledgements, server info...
+            Some(_) => return Ok(()),
+
+            None => (),
+        }
+
+        let feed = match event.feed {
+            Some(feed) => feed,
+            None => return Ok(()),
+        };
+
+        match feed {
+            "book_snapshot" if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book_snapshot(json, out)?;
+            }
+            "book" if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book_update(json, out)?;
+            }
+
+            // The snapshot holds past trades.
+            "trade" if self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_trade(json, out)?;
+            }
+
+            "open_orders_snapshot" => self.parse_orders_snapshot(json)?,
+            "open_orders" if self.flags.contains(NotificationFlags::ORDERS) => {
+                self.parse_orders(json, out)?;
+            }
+
+            // The snapshot holds past fills.
+            "fills" if self.flags.contains(NotificationFlags::ORDERS) => {
+                self.parse_fills(json, out)?;
+            }
+
+            "open_positions" if self.flags.contains(NotificationFlags::POSITIONS) => {
+                self.parse_positions(json, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+
+        // Kraken closes connections which do not receive any message for a minute.
+        self.send_request(&KrakenRequest {
+            event: "subscribe",
+            feed: Some("heartbeat"),
+            product_ids: None,
+            api_key: None,
+            original_challenge: None,
+            signed_challenge: None,
+        })?;
+
+        let feeds = self.feeds(self.flags);
+        self.subscribe("subscribe", &feeds)?;
+        self.request_challenge()
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+        let (previous, current) = (self.feeds(previous), self.feeds(flags));
+
+        let subscribe = current.iter()
+            .filter(|feed| !previous.contains(feed))
+            .cloned()
+            .collect::<Vec<_>>();
+        let unsubscribe = previous.iter()
+            .filter(|feed| !current.contains(feed))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // A new subscription starts with a new snapshot.
+        if subscribe.contains(&BOOK_FEED) {
+            self.order_book = OrderBook::new();
+            self.book_seq = None;
+        }
+
+        self.subscribe("unsubscribe", &unsubscribe)?;
+        self.subscribe("subscribe", &subscribe)
+    }
+}
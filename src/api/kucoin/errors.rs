@@ -0,0 +1,182 @@
+//! A module defining error types specific to KuCoin.
+
+use failure_derive::Fail;
+use hyper::StatusCode;
+use std::fmt;
+use crate::api;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by KuCoin REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Internal KuCoin error code: see API documentation.
+    pub error_code: Option<i64>,
+
+    /// Description of the error.
+    pub error_msg: Option<String>,
+}
+
+impl RestError {
+    fn has_code(&self, codes: &[i64]) -> bool {
+        self.error_code.map(|code| codes.contains(&code)).unwrap_or(false)
+    }
+
+    fn msg_contains(&self, pattern: &str) -> bool {
+        self.error_msg.as_ref().map(|msg| msg.contains(pattern)).unwrap_or(false)
+    }
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Unauthorized {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        // The order does not exist, or was already filled or canceled. KuCoin reports
+        // this with the generic `400100` code.
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[400100]) &&
+            (self.msg_contains("not_exist") || self.msg_contains("not exist"))
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.kind == RestErrorKind::BadRequest && self.has_code(&[200004]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        if self.kind == RestErrorKind::BadRequest &&
+            self.has_code(&[400100]) && self.msg_contains("clientOid")
+        {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error_msg) = &self.error_msg {
+            write!(f, ": `{}`", error_msg)?;
+        }
+        if let Some(error_code) = self.error_code {
+            write!(f, " (error_code = {})", error_code)?;
+        }
+        Ok(())
+    }
+}
+
+impl RestError {
+    /// KuCoin reports errors with a `code` other than `200000` in the response body,
+    /// along with an HTTP status code which is most often 200 or 400.
+    pub(super) fn from_kucoin_error(status: StatusCode, kucoin_error: Option<(i64, String)>)
+        -> Self
+    {
+        let kind = match &kucoin_error {
+            Some((code, _)) => RestErrorKind::from_error_code(*code)
+                .unwrap_or_else(|| RestErrorKind::from_status_code(status)),
+            None => RestErrorKind::from_status_code(status),
+        };
+
+        RestError {
+            kind,
+            error_code: kucoin_error.as_ref().map(|error| error.0),
+            error_msg: kucoin_error.map(|error| error.1),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code or a KuCoin error code to a KuCoin error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key, signature, pass phrase or timestamp, or missing permission for the
+    /// API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by KuCoin. See KuCoin API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on KuCoin side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is busy or under maintenance.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            // Rejected requests, along with an error `code`.
+            StatusCode::OK | StatusCode::BAD_REQUEST => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+
+    fn from_error_code(code: i64) -> Option<Self> {
+        use self::RestErrorKind::*;
+        let kind = match code {
+            429000 => TooManyRequests,
+            400001 ..= 400007 | 411100 => Unauthorized,
+            500000 => InternalError,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
@@ -0,0 +1,203 @@
+//! Implementation of `ApiClient` for the KuCoin API.
+
+pub mod errors;
+mod rest;
+mod token;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+use self::token::{Channel, Tokens};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A KuCoin key pair: api key + secret key, along with a pass phrase.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+    pass_phrase: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair along with the associated pass phrase.
+    pub fn new(api_key: String, secret_key: String, pass_phrase: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+            pass_phrase,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+
+    /// Base64 HMAC-SHA256 of the pass phrase, as expected by version 2 of the API keys.
+    pass_phrase: String,
+}
+
+/// A KuCoin API client, trading spot pairs.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// server order id => client order id, for orders inserted without a client order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    /// Connect tokens of the WebSocket API, shared with the streams.
+    tokens: Arc<Tokens>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new KuCoin API client with given `params`. The streaming endpoint is
+    /// not used, since the WebSocket endpoints are obtained through the REST API along
+    /// with the connect tokens, which are refreshed as needed when opening streams. If
+    /// `key_pair` is not `None`, this will enable performing requests to the REST API
+    /// and will forward the user data stream.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols and a first connect
+    /// token from KuCoin.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                let mut signer = Signer::new(MessageDigest::sha256(), &secret_key)?;
+                signer.update(pair.pass_phrase.as_bytes())?;
+                let pass_phrase = base64::encode(&signer.sign_to_vec()?);
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                    pass_phrase,
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = Client {
+            params,
+            keys,
+            order_ids: Arc::new(CHashMap::new()),
+            tokens: Arc::new(Tokens::default()),
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        use tokio::runtime::current_thread;
+        let mut runtime = current_thread::Runtime::new()?;
+        debug!("requesting symbols");
+        client.symbols = runtime.block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        debug!("requesting WebSocket token");
+        runtime.block_on(client.bullet(Channel::Public))?;
+        debug!("received WebSocket token");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Kucoin
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// KuCoin accepts client order ids of at most 40 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 40 &&
+            hint.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use hyper::{Method, Request};
+use futures::prelude::*;
+use futures::future::Either;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::api::kucoin::Client;
+use crate::api::kucoin::errors::RestError;
+use crate::api::kucoin::token::{Bullet, Channel};
+
+/// Code of successful requests.
+const SUCCESS_CODE: &str = "200000";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinStatus {
+    code: String,
+    msg: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinResponse<T> {
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct KucoinOrder<'a> {
+    clientOid: &'a str,
+    side: &'a str,
+    symbol: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    price: &'a str,
+    size: &'a str,
+    timeInForce: &'a str,
+    postOnly: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KucoinOrderAck<'a> {
+    orderId: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinAccount<'a> {
+    currency: &'a str,
+    available: &'a str,
+    holds: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinServiceStatus<'a> {
+    status: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KucoinSymbol<'a> {
+    symbol: &'a str,
+    priceIncrement: &'a str,
+    baseIncrement: &'a str,
+    enableTrading: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KucoinBullet<'a> {
+    token: &'a str,
+    #[serde(borrow)]
+    instanceServers: Vec<KucoinInstanceServer<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinInstanceServer<'a> {
+    endpoint: &'a str,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+impl AsStr for TimeInForce {
+    fn as_str(self) -> &'static str {
+        match self {
+            TimeInForce::GoodTilCanceled => "GTC",
+            TimeInForce::ImmediateOrCancel => "IOC",
+            TimeInForce::FillOrKilll => "FOK",
+            TimeInForce::GoodTilDate(_) => unreachable!("rejected by `order_impl`"),
+        }
+    }
+}
+
+impl Client {
+    /// Signed requests carry the base64 HMAC-SHA256 of the timestamp, the method, the
+    /// request path including the query string, and the body.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let path = if query.is_empty() {
+            format!("/{}", endpoint)
+        } else {
+            format!("/{}?{}", endpoint, query)
+        };
+        let address = format!("{}{}", self.params.rest_endpoint, path);
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let timestamp = timestamp_ms().to_string();
+            let mut signer = Signer::new(MessageDigest::sha256(), &keys.secret_key).unwrap();
+            let what = format!("{}{}{}{}", timestamp, method, path, body);
+            signer.update(what.as_bytes()).unwrap();
+            let signature = base64::encode(&signer.sign_to_vec().unwrap());
+
+            request.header("KC-API-KEY", keys.api_key.as_bytes())
+                .header("KC-API-SIGN", signature.as_bytes())
+                .header("KC-API-TIMESTAMP", timestamp.as_bytes())
+                .header("KC-API-PASSPHRASE", keys.pass_phrase.as_bytes())
+                .header("KC-API-KEY-VERSION", &b"2"[..]);
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let kucoin_status: Option<KucoinStatus> = serde_json::from_slice(&body).ok();
+            let failed = match &kucoin_status {
+                Some(kucoin_status) => kucoin_status.code != SUCCESS_CODE,
+                None => true,
+            };
+
+            if status != hyper::StatusCode::OK || failed {
+                let kucoin_error = kucoin_status.and_then(|kucoin_status| {
+                    let code = kucoin_status.code.parse().ok()?;
+                    Some((code, kucoin_status.msg.unwrap_or_default()))
+                });
+                let error = RestError::from_kucoin_error(status, kucoin_error);
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.time_in_force.expiration().is_some() {
+            warn!("called `order` with `GoodTilDate`, which is not supported by KuCoin");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by KuCoin");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by KuCoin");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        // A client order id is mandatory.
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let order_body = KucoinOrder {
+            clientOid: &order_id,
+            side: order.side.as_str(),
+            symbol: symbol.name(),
+            type_: "limit",
+            price: price.borrow(),
+            size: size.borrow(),
+            timeInForce: order.time_in_force.as_str(),
+            postOnly: order.type_ == OrderType::LimitMaker,
+        };
+
+        let body = serde_json::to_string(&order_body).expect("invalid json");
+
+        let order_ids = self.order_ids.clone();
+
+        let query = QueryString::new();
+        let fut = self.request("api/v1/orders", Method::POST, query, body).and_then(move |body| {
+            let ack: KucoinResponse<KucoinOrderAck<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            order_ids.insert(ack.data.orderId.to_owned(), order_id.clone());
+            debug!("insert order id {} (from REST)", order_id);
+
+            Ok(OrderAck {
+                order_id,
+            }.timestamped())
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        // Orders inserted from elsewhere may be known by their server order id only.
+        let endpoint = if self.order_ids.contains_key(&cancel.order_id) {
+            format!("api/v1/orders/{}", cancel.order_id)
+        } else {
+            format!("api/v1/order/client-order/{}", cancel.order_id)
+        };
+
+        let query = QueryString::new();
+        self.request(&endpoint, Method::DELETE, query, String::new()).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("api/v1/timestamp", Method::GET, query, String::new()).and_then(|body| {
+            let time: KucoinResponse<Timestamp> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(().with_timestamp(time.data))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("type", "trade");
+
+        self.request("api/v1/accounts", Method::GET, query, String::new()).and_then(|body| {
+            let accounts: KucoinResponse<Vec<KucoinAccount<'_>>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut balances = Balances::new();
+            for account in accounts.data {
+                balances.insert(account.currency.to_owned(), Balance {
+                    free: account.available.to_owned(),
+                    locked: account.holds.to_owned(),
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("api/v1/status", Method::GET, query, String::new()).and_then(|body| {
+            let status: KucoinResponse<KucoinServiceStatus<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            // `cancelonly` or `close`.
+            let status = if status.data.status == "open" {
+                ExchangeStatus::Normal
+            } else {
+                ExchangeStatus::Maintenance
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("api/v2/symbols", Method::GET, query, String::new()).and_then(|body| {
+            let kucoin_symbols: KucoinResponse<Vec<KucoinSymbol<'_>>> =
+                serde_json::from_slice(&body)
+                    .map_err(api::errors::RequestError::new)
+                    .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            for kucoin_symbol in kucoin_symbols.data {
+                if !kucoin_symbol.enableTrading {
+                    continue;
+                }
+
+                let (price_tick, size_tick) = match (
+                    Tick::tick_size(kucoin_symbol.priceIncrement),
+                    Tick::tick_size(kucoin_symbol.baseIncrement)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", kucoin_symbol.symbol);
+                        continue;
+                    }
+                };
+
+                if let Some(symbol) = Symbol::new(kucoin_symbol.symbol, price_tick, size_tick) {
+                    symbols.insert(symbol.name().to_lowercase(), symbol);
+                } else {
+                    error!("symbol name too long: `{}`", kucoin_symbol.symbol);
+                }
+            }
+            Ok(symbols)
+        })
+    }
+
+    /// Return a connect token for `channel`, which is fetched again only if the cached
+    /// one is missing or stale.
+    crate fn bullet(&self, channel: Channel)
+        -> impl Future<Item = Bullet, Error = api::errors::Error> + Send + 'static
+    {
+        if let Some(bullet) = self.tokens.get(channel) {
+            return Either::A(futures::future::ok(bullet));
+        }
+
+        let tokens = self.tokens.clone();
+        let query = QueryString::new();
+        let endpoint = channel.endpoint();
+        let fut = self.request(endpoint, Method::POST, query, String::new()).and_then(move |body| {
+            let bullet: KucoinResponse<KucoinBullet<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let server = match bullet.data.instanceServers.first() {
+                Some(server) => *server,
+                None => {
+                    error!("no WebSocket endpoint returned along with the token");
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::OtherSide.into()
+                    ));
+                }
+            };
+
+            let bullet = Bullet::new(bullet.data.token.to_owned(), server.endpoint.to_owned());
+            tokens.store(channel, bullet.clone());
+            Ok(bullet)
+        });
+        Either::B(fut)
+    }
+}
@@ -0,0 +1,56 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::kucoin::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC-USDT", Tick::new(10), Tick::new(1000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+#[test]
+fn book_snapshot_and_update() {
+    let welcome = r#"{"id":"hQvf8jkno","type":"welcome"}"#;
+    let snapshot = r#"{"type":"message","topic":"/spotMarket/level2Depth50:BTC-USDT",
+        "subject":"level2","data":{"asks":[["9989.0","0.008"],["9990.0","0.032"]],
+        "bids":[["9988.0","0.056"],["9987.0","0.015"]],"timestamp":1586948108193}}"#;
+
+    // Only the changes of the top of the book are forwarded.
+    let update = r#"{"type":"message","topic":"/spotMarket/level2Depth50:BTC-USDT",
+        "subject":"level2","data":{"asks":[["9990.0","0.032"],["9991.0","0.010"]],
+        "bids":[["9988.0","0.060"],["9987.0","0.015"]],"timestamp":1586948108293}}"#;
+
+    let notifs = parse(&[welcome, snapshot, update]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(99890, 8, Side::Ask).with_timestamp(1_586_948_108_193),
+            LimitUpdate::new(99900, 32, Side::Ask).with_timestamp(1_586_948_108_193),
+            LimitUpdate::new(99880, 56, Side::Bid).with_timestamp(1_586_948_108_193),
+            LimitUpdate::new(99870, 15, Side::Bid).with_timestamp(1_586_948_108_193),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(99890, 0, Side::Ask).with_timestamp(1_586_948_108_293),
+            LimitUpdate::new(99910, 10, Side::Ask).with_timestamp(1_586_948_108_293),
+            LimitUpdate::new(99880, 60, Side::Bid).with_timestamp(1_586_948_108_293),
+        ]),
+    ]);
+}
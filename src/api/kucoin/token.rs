@@ -0,0 +1,87 @@
+//! Connect tokens of the WebSocket API. KuCoin does not publish fixed WebSocket
+//! endpoints: each connection must be initiated with a token, along with the endpoint
+//! returned with it, which are obtained through the REST API. Tokens are cached and
+//! shared between the streams of a client, and fetched again once stale.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// KuCoin does not advertise the validity of the tokens, which is 24 hours at the time
+/// of writing: tokens are refreshed well before, since fetching one is cheap.
+const TOKEN_VALIDITY_SECS: u64 = 3600;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Feeds granted by a token.
+crate enum Channel {
+    /// Market data.
+    Public,
+
+    /// User data, requires a signed request.
+    Private,
+}
+
+impl Channel {
+    crate fn endpoint(self) -> &'static str {
+        match self {
+            Channel::Public => "api/v1/bullet-public",
+            Channel::Private => "api/v1/bullet-private",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A connect token along with the WebSocket endpoint to use it with.
+crate struct Bullet {
+    crate token: String,
+    crate endpoint: String,
+
+    received_at: Instant,
+}
+
+impl Bullet {
+    crate fn new(token: String, endpoint: String) -> Self {
+        Bullet {
+            token,
+            endpoint,
+            received_at: Instant::now(),
+        }
+    }
+
+    /// Address to connect to, `connect_id` identifying the connection in its welcome
+    /// message and in the server logs.
+    crate fn address(&self, connect_id: &str) -> String {
+        format!("{}?token={}&connectId={}", self.endpoint, self.token, connect_id)
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.received_at.elapsed() < Duration::from_secs(TOKEN_VALIDITY_SECS)
+    }
+}
+
+#[derive(Debug, Default)]
+/// Tokens fetched so far by a client.
+crate struct Tokens {
+    public: Mutex<Option<Bullet>>,
+    private: Mutex<Option<Bullet>>,
+}
+
+impl Tokens {
+    fn slot(&self, channel: Channel) -> &Mutex<Option<Bullet>> {
+        match channel {
+            Channel::Public => &self.public,
+            Channel::Private => &self.private,
+        }
+    }
+
+    /// Return the cached token for `channel`, unless it is missing or stale.
+    crate fn get(&self, channel: Channel) -> Option<Bullet> {
+        self.slot(channel).lock().unwrap()
+            .as_ref()
+            .filter(|bullet| bullet.is_fresh())
+            .cloned()
+    }
+
+    crate fn store(&self, channel: Channel, bullet: Bullet) {
+        *self.slot(channel).lock().unwrap() = Some(bullet);
+    }
+}
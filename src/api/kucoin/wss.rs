@@ -0,0 +1,583 @@
+use futures::sync::mpsc::unbounded;
+use futures::prelude::*;
+use failure::bail;
+use std::{mem, thread};
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    self,
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::api::kucoin::Client;
+use crate::api::kucoin::token::{Bullet, Channel};
+
+/// Timestamps of the WebSocket API are expressed in ns.
+const NS_PER_MS: Timestamp = 1000000;
+
+/// Fetch a connect token, then connect with it. Tokens are fetched from the calling
+/// thread, since `bullet` may block.
+fn connect<F, H, T>(bullet: F, handler: H)
+    where F: Future<Item = Bullet, Error = api::errors::Error>,
+          H: FnMut(ws::Sender) -> wss::Handler<T>,
+          T: wss::HandlerImpl
+{
+    use tokio::runtime::current_thread;
+
+    debug!("requesting WebSocket token");
+    let bullet = match current_thread::Runtime::new() {
+        Ok(mut runtime) => runtime.block_on(bullet),
+        Err(err) => {
+            error!("failed to start runtime: `{}`", err);
+            return;
+        }
+    };
+    let bullet = match bullet {
+        Ok(bullet) => bullet,
+        Err(err) => {
+            error!("failed to request WebSocket token: `{}`", err);
+            return;
+        }
+    };
+    debug!("received WebSocket token");
+
+    use uuid::Uuid;
+    let address = bullet.address(&Uuid::new_v4().simple().to_string());
+    debug!("initiating WebSocket connection at {}", bullet.endpoint);
+
+    if let Err(err) = ws::connect(address, handler) {
+        error!("WebSocket connection terminated with error: `{}`", err);
+    }
+}
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        if self.keys.is_some() {
+            let bullet = self.bullet(Channel::Private);
+            let snd = snd.clone();
+            let order_ids = self.order_ids.clone();
+            let public_control = control.clone();
+            thread::spawn(move || {
+                // The flags of the private connection are those of the public one,
+                // see `PrivateHandlerImpl::control`.
+                let private_control = public_control.linked();
+
+                connect(bullet, |out| {
+                    wss::Handler::new(
+                        out,
+                        snd.clone(),
+                        private_control.clone(),
+                        wss::KeepAlive::True,
+                        PrivateHandlerImpl {
+                            symbol,
+                            session: Session::default(),
+                            control: public_control.clone(),
+                            order_ids: order_ids.clone(),
+                        }
+                    )
+                });
+            });
+        }
+
+        let bullet = self.bullet(Channel::Public);
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            connect(bullet, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        session: Session::default(),
+                        order_book: OrderBook::new(),
+                    }
+                )
+            });
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+#[derive(Default)]
+/// State of a connection common to the public and private feeds.
+struct Session {
+    /// Set once the connection is open, needed for subscribing after the welcome
+    /// message.
+    out: Option<ws::Sender>,
+
+    /// Subscriptions sent before the welcome message are ignored by KuCoin.
+    welcomed: bool,
+
+    /// Id of the last request, echoed by KuCoin in its responses.
+    last_id: u64,
+}
+
+impl Session {
+    fn send_request(&mut self, type_: &str, topic: Option<&str>, private_channel: bool)
+        -> ws::Result<()>
+    {
+        let out = match &self.out {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+
+        self.last_id += 1;
+        let request = KucoinRequest {
+            id: self.last_id.to_string(),
+            type_,
+            topic,
+            privateChannel: topic.map(|_| private_channel),
+            response: topic.map(|_| true),
+        };
+
+        match serde_json::to_string(&request) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `KucoinRequest`: `{}`", err);
+            }
+        }
+    }
+
+    fn subscribe(&mut self, topic: &str, private_channel: bool) -> ws::Result<()> {
+        self.send_request("subscribe", Some(topic), private_channel)
+    }
+
+    fn unsubscribe(&mut self, topic: &str, private_channel: bool) -> ws::Result<()> {
+        self.send_request("unsubscribe", Some(topic), private_channel)
+    }
+
+    /// KuCoin closes the connections which did not send a ping message within the
+    /// interval returned along with the token (about 20 seconds), WebSocket ping frames
+    /// do not count.
+    fn ping(&mut self) -> ws::Result<()> {
+        self.send_request("ping", None, false)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct KucoinRequest<'a> {
+    id: String,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    privateChannel: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinEvent<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    topic: Option<&'a str>,
+    code: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinMessage<T> {
+    data: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinBook<'a> {
+    #[serde(borrow)]
+    asks: Vec<(&'a str, &'a str)>,
+    #[serde(borrow)]
+    bids: Vec<(&'a str, &'a str)>,
+    timestamp: Timestamp,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct KucoinTrade<'a> {
+    side: &'a str,
+    price: &'a str,
+    size: &'a str,
+    time: &'a str,
+}
+
+fn book_topic(symbol: &Symbol) -> String {
+    format!("/spotMarket/level2Depth50:{}", symbol.name())
+}
+
+fn trades_topic(symbol: &Symbol) -> String {
+    format!("/market/match:{}", symbol.name())
+}
+
+/// Check a message sent by KuCoin. Return `Ok(Some(topic))` for data messages,
+/// `Ok(None)` otherwise.
+fn check_event<'a>(json: &'a str, event: &KucoinEvent<'a>)
+    -> Result<Option<&'a str>, failure::Error>
+{
+    match event.type_ {
+        "message" => Ok(event.topic),
+        "error" => bail!("{} (code = {:?})", json, event.code),
+        _ => Ok(None),
+    }
+}
+
+/// Convert a timestamp in ns, sent as a string, into ms.
+fn convert_kucoin_timestamp(timestamp: &str) -> Result<Timestamp, failure::Error> {
+    Ok(timestamp.parse::<Timestamp>()? / NS_PER_MS)
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    session: Session,
+
+    /// Local copy of the order book: KuCoin sends the top levels of the book each time,
+    /// only the changes are forwarded.
+    order_book: OrderBook,
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            session: Session::default(),
+            order_book: OrderBook::new(),
+        }
+    }
+
+    fn convert_kucoin_level(&self, level: (&str, &str), side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        Ok(LimitUpdate {
+            side,
+            price: self.symbol.price_tick().ticked(level.0)?,
+            size: self.symbol.size_tick().ticked(level.1)?,
+        })
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let book: KucoinMessage<KucoinBook<'_>> = serde_json::from_str(json)?;
+
+        let asks = book.data.asks.iter().map(|l| self.convert_kucoin_level(*l, Side::Ask));
+        let bids = book.data.bids.iter().map(|l| self.convert_kucoin_level(*l, Side::Bid));
+        let levels = asks.chain(bids).collect::<Result<Vec<_>, _>>()?;
+
+        // Levels which are not part of the new top of the book either were emptied or
+        // went beyond the subscribed depth.
+        let mut updates = Vec::new();
+        {
+            let contains = |side: Side, price: TickUnit| {
+                levels.iter().any(|level| level.side == side && level.price == price)
+            };
+            let asks = self.order_book.ask()
+                .filter(|(price, _)| !contains(Side::Ask, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Ask));
+            let bids = self.order_book.bid()
+                .filter(|(price, _)| !contains(Side::Bid, **price))
+                .map(|(price, _)| LimitUpdate::new(*price, 0, Side::Bid));
+            updates.extend(asks.chain(bids));
+        }
+
+        updates.extend(levels.into_iter().filter(|level| {
+            self.order_book.size_at_limit(level.side, level.price) != level.size
+        }));
+
+        for update in &updates {
+            self.order_book.update(*update);
+        }
+
+        if !updates.is_empty() {
+            let updates = updates.into_iter()
+                .map(|update| update.with_timestamp(book.data.timestamp))
+                .collect();
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trade(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trade: KucoinMessage<KucoinTrade<'_>> = serde_json::from_str(json)?;
+        let trade = trade.data;
+
+        // The side is the one of the taker.
+        let maker_side = match trade.side {
+            "buy" => Side::Ask,
+            "sell" => Side::Bid,
+            other => bail!("wrong side: `{}`", other),
+        };
+
+        let trade = Notification::Trade(Trade {
+            price: self.symbol.price_tick().ticked(trade.price)?,
+            size: self.symbol.size_tick().ticked(trade.size)?,
+            maker_side,
+        }.with_timestamp(convert_kucoin_timestamp(trade.time)?));
+
+        out.unbounded_send(trade).unwrap();
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: KucoinEvent<'_> = serde_json::from_str(json)?;
+
+        if event.type_ == "welcome" {
+            self.session.welcomed = true;
+            if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+                self.session.subscribe(&book_topic(&self.symbol), false)?;
+            }
+            if self.flags.contains(NotificationFlags::TRADES) {
+                self.session.subscribe(&trades_topic(&self.symbol), false)?;
+            }
+            return Ok(());
+        }
+
+        match check_event(json, &event)? {
+            Some(topic) if topic.starts_with("/spotMarket/level2Depth50:") &&
+                self.flags.contains(NotificationFlags::ORDER_BOOK) =>
+            {
+                self.parse_book(json, out)?;
+            }
+
+            Some(topic) if topic.starts_with("/market/match:") &&
+                self.flags.contains(NotificationFlags::TRADES) =>
+            {
+                self.parse_trade(json, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.session.out = Some(out.clone());
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The subscriptions will be sent along with the welcome message.
+        if !self.session.welcomed {
+            return Ok(());
+        }
+
+        // Orders are streamed through the private connection, where `ORDERS` only
+        // acts as a filter.
+        let book = book_topic(&self.symbol);
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            (false, true) => {
+                self.order_book = OrderBook::new();
+                self.session.subscribe(&book, false)?;
+            }
+            (true, false) => self.session.unsubscribe(&book, false)?,
+            _ => (),
+        }
+
+        let trades = trades_topic(&self.symbol);
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => self.session.subscribe(&trades, false)?,
+            (true, false) => self.session.unsubscribe(&trades, false)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn on_ping(&mut self, _: &ws::Sender) -> ws::Result<()> {
+        self.session.ping()
+    }
+}
+
+/// Topic of the order events of the account.
+const ORDERS_TOPIC: &str = "/spotMarket/tradeOrdersV2";
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    session: Session,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// server order id => client order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct KucoinOrder<'a> {
+    symbol: &'a str,
+    orderId: &'a str,
+    clientOid: Option<&'a str>,
+    side: &'a str,
+    #[serde(rename = "type")]
+    type_: &'a str,
+    price: Option<&'a str>,
+    size: Option<&'a str>,
+    filledSize: Option<&'a str>,
+    matchPrice: Option<&'a str>,
+    matchSize: Option<&'a str>,
+    remainSize: Option<&'a str>,
+    ts: Timestamp,
+}
+
+impl PrivateHandlerImpl {
+    fn convert_kucoin_side(&self, side: &str) -> Result<Side, failure::Error> {
+        let side = match side {
+            "buy" => Side::Bid,
+            "sell" => Side::Ask,
+            other => bail!("wrong side: `{}`", other),
+        };
+        Ok(side)
+    }
+
+    fn ticked_size(&self, size: Option<&str>) -> Result<TickUnit, failure::Error> {
+        Ok(self.symbol.size_tick().ticked(size.unwrap_or("0"))?)
+    }
+
+    /// Return the order id specified by the user, which defaults to the server order id
+    /// in case it was left unspecified.
+    fn order_id(&self, server_order_id: &str, client_order_id: Option<&str>) -> String {
+        let order_id = match client_order_id {
+            Some(client_order_id) if !client_order_id.is_empty() => client_order_id.to_owned(),
+            _ => self.order_ids.get(server_order_id)
+                .map(|order_id| order_id.clone())
+                .unwrap_or_else(|| server_order_id.to_owned()),
+        };
+
+        // Don't forget to update the concurrent map `server order id => client order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(server_order_id) {
+            self.order_ids.insert(server_order_id.to_owned(), order_id.clone());
+            debug!("insert order id {} (from WSS)", order_id);
+        }
+        order_id
+    }
+
+    fn parse_order(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let order: KucoinMessage<KucoinOrder<'_>> = serde_json::from_str(json)?;
+        let order = order.data;
+
+        // The topic carries the orders of every symbol.
+        if order.symbol != self.symbol.name() {
+            return Ok(());
+        }
+
+        let order_id = self.order_id(order.orderId, order.clientOid);
+        let timestamp = order.ts / NS_PER_MS;
+        let size = self.ticked_size(order.size)?;
+
+        match order.type_ {
+            "open" => {
+                let price = order.price.unwrap_or("0");
+                let order = OrderConfirmation {
+                    size,
+                    price: self.symbol.price_tick().ticked(price)?,
+                    side: self.convert_kucoin_side(order.side)?,
+                    order_id,
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+            }
+
+            "match" => {
+                let price = order.matchPrice.unwrap_or("0");
+                let update = OrderUpdate {
+                    order_id,
+                    consumed_size: self.ticked_size(order.matchSize)?,
+                    remaining_size: self.ticked_size(order.remainSize)?,
+                    consumed_price: self.symbol.price_tick().ticked(price)?,
+                    commission: 0,
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+            }
+
+            "canceled" => {
+                let filled_size = self.ticked_size(order.filledSize)?;
+                let expiration = OrderExpiration {
+                    order_id,
+                    filled_size: Some(filled_size),
+                    remaining_size: Some(size.saturating_sub(filled_size)),
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+            }
+
+            // `received`, `update` and `filled`, the fills being reported by `match`.
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: KucoinEvent<'_> = serde_json::from_str(json)?;
+
+        if event.type_ == "welcome" {
+            self.session.welcomed = true;
+            self.session.subscribe(ORDERS_TOPIC, true)?;
+            return Ok(());
+        }
+
+        let topic = check_event(json, &event)?;
+
+        if !self.control.flags().contains(NotificationFlags::ORDERS) {
+            return Ok(());
+        }
+
+        if topic == Some(ORDERS_TOPIC) {
+            self.parse_order(json, out)?;
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.session.out = Some(out.clone());
+        Ok(())
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+
+    fn on_ping(&mut self, _: &ws::Sender) -> ws::Result<()> {
+        self.session.ping()
+    }
+}
@@ -0,0 +1,208 @@
+//! A module defining latency histograms of the stages a notification goes through, see
+//! `NotificationStream::latency`.
+//!
+//! Each notification is traced from the receipt of the frame it was parsed from to its
+//! delivery to the consumer of the stream, which tells whether delays come from the
+//! network, from the handling of the messages, or from a consumer not keeping up:
+//! * network: from the exchange timestamp of the notification to the receipt of the
+//!   frame, which includes the offset between the exchange clock and the local clock
+//!   (see `api::drift`)
+//! * handling: from the receipt of the frame to the queuing of the notification, i.e.
+//!   parsing, book maintenance and filtering
+//! * delivery: from the queuing of the notification to its delivery by the stream
+//!
+//! Tracing is only compiled with the `latency` feature, and costs a lock and a few clock
+//! reads per notification.
+
+mod test;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use serde_derive::{Serialize, Deserialize};
+use crate::api::Notification;
+use crate::api::timestamp::Timestamp;
+
+/// Number of buckets of a `Histogram`, the last one spanning about 18 minutes and more.
+const BUCKETS: usize = 32;
+
+fn as_micros(duration: Duration) -> u64 {
+    duration.as_secs()
+        .saturating_mul(1_000_000)
+        .saturating_add(u64::from(duration.subsec_micros()))
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// A histogram of latencies with exponential buckets: the first bucket counts
+/// latencies below 1µs, and bucket `i` latencies in `[2^(i - 1), 2^i)` µs.
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+
+    /// Sum of the latencies, in µs.
+    sum: u64,
+
+    /// Maximum latency, in µs.
+    max: u64,
+}
+
+impl Histogram {
+    /// Return an empty histogram.
+    pub fn new() -> Self {
+        Histogram::default()
+    }
+
+    /// Record a latency.
+    pub fn record(&mut self, latency: Duration) {
+        let micros = as_micros(latency);
+        let bucket = (64 - micros.leading_zeros()) as usize;
+
+        self.buckets[std::cmp::min(bucket, BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(micros);
+        self.max = std::cmp::max(self.max, micros);
+    }
+
+    /// Return the number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return the mean latency, or `None` if no latency was recorded.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(Duration::from_micros(self.sum / self.count))
+    }
+
+    /// Return the maximum latency, or `None` if no latency was recorded.
+    pub fn max(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(Duration::from_micros(self.max))
+    }
+
+    /// Return the latency below which `p` percent of the latencies fall, rounded up to
+    /// the upper bound of its bucket, or `None` if no latency was recorded.
+    ///
+    /// # Panics
+    /// Panic if `p` is not in `(0, 100]`.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if !(p > 0. && p <= 100.) {
+            panic!("`p` must be in (0, 100]");
+        }
+
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = (p / 100. * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (upper_bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return Some(std::cmp::min(upper_bound, Duration::from_micros(self.max)));
+            }
+        }
+        self.max()
+    }
+
+    /// Return the exclusive upper bound of each bucket along with its count.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets.iter()
+            .enumerate()
+            .map(|(i, count)| (Duration::from_micros(1 << i), *count))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Latency histograms of the stages of a notification stream, see the module
+/// documentation for the definition of each stage.
+pub struct LatencyStats {
+    /// From the exchange timestamp to the receipt of the frame, with a millisecond
+    /// resolution. Notifications timestamped ahead of the local clock are not recorded.
+    pub network: Histogram,
+
+    /// From the receipt of the frame to the queuing of the notification.
+    /// Notifications which were not sent in response to a frame, e.g. on a timeout,
+    /// are not recorded.
+    pub handling: Histogram,
+
+    /// From the queuing of the notification to its delivery to the consumer.
+    pub delivery: Histogram,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Receipt of a frame by a connection.
+crate struct Receipt {
+    crate instant: Instant,
+
+    /// Local UTC timestamp in ms, comparable with the exchange timestamps.
+    crate timestamp: Timestamp,
+}
+
+/// Latest exchange timestamp carried by `notif`. Exchange statuses are timestamped
+/// locally and are thus ignored.
+fn exchange_timestamp(notif: &Notification) -> Option<Timestamp> {
+    match notif {
+        Notification::Trade(trade) => Some(trade.timestamp()),
+        Notification::LimitUpdates(updates) => {
+            updates.iter().map(|update| update.timestamp()).max()
+        }
+        Notification::OrderConfirmation(confirmation) => Some(confirmation.timestamp()),
+        Notification::OrderUpdate(update) => Some(update.timestamp()),
+        Notification::OrderExpiration(expiration) => Some(expiration.timestamp()),
+        Notification::PositionUpdate(position) => Some(position.timestamp()),
+        Notification::IndexPrice(price) => Some(price.timestamp()),
+        Notification::ExchangeStatus(..)
+            | Notification::TradingHalted(..)
+            | Notification::TradingResumed(..)
+            | Notification::AuctionUpdate(..) => None,
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+/// State shared by the connections of a stream and the stream itself, tracing the
+/// notifications queued and not delivered yet.
+crate struct Tracker {
+    stats: LatencyStats,
+
+    /// Times at which the notifications not delivered yet were queued, in the order of
+    /// the channel.
+    queued: VecDeque<Instant>,
+}
+
+impl Tracker {
+    crate fn new() -> Self {
+        Tracker::default()
+    }
+
+    crate fn stats(&self) -> &LatencyStats {
+        &self.stats
+    }
+
+    /// Account for `notif` being queued at `now`, `receipt` being the receipt of the
+    /// frame it was parsed from if any. Must be called while holding the lock on
+    /// `self`, right before sending `notif` over the channel.
+    crate fn on_queued(&mut self, notif: &Notification, receipt: Option<Receipt>, now: Instant) {
+        if let Some(receipt) = receipt {
+            self.stats.handling.record(now.duration_since(receipt.instant));
+
+            match exchange_timestamp(notif) {
+                Some(timestamp) if timestamp <= receipt.timestamp => {
+                    self.stats.network.record(Duration::from_millis(receipt.timestamp - timestamp));
+                }
+                _ => (),
+            }
+        }
+        self.queued.push_back(now);
+    }
+
+    /// Account for the oldest queued notification being delivered at `now`.
+    crate fn on_delivered(&mut self, now: Instant) {
+        if let Some(queued) = self.queued.pop_front() {
+            self.stats.delivery.record(now.duration_since(queued));
+        }
+    }
+}
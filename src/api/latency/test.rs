@@ -0,0 +1,84 @@
+#![cfg(test)]
+
+use std::time::{Duration, Instant};
+use crate::Side;
+use crate::api::{Notification, Trade, ExchangeStatus};
+use crate::api::latency::{Histogram, Receipt, Tracker};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+
+fn trade(timestamp: Timestamp) -> Notification {
+    Notification::Trade(Trade {
+        price: 100,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+#[test]
+fn histogram() {
+    let mut histogram = Histogram::new();
+    assert_eq!(histogram.percentile(50.), None);
+    assert_eq!(histogram.mean(), None);
+
+    for micros in &[0, 1, 3, 100, 100, 5000] {
+        histogram.record(Duration::from_micros(*micros));
+    }
+
+    assert_eq!(histogram.count(), 6);
+    assert_eq!(histogram.max(), Some(Duration::from_micros(5000)));
+    assert_eq!(histogram.mean(), Some(Duration::from_micros(867)));
+
+    // 3µs falls in `[2, 4)`, 100µs in `[64, 128)`.
+    assert_eq!(histogram.percentile(50.), Some(Duration::from_micros(4)));
+    assert_eq!(histogram.percentile(60.), Some(Duration::from_micros(128)));
+    assert_eq!(histogram.percentile(1.), Some(Duration::from_micros(1)));
+    assert_eq!(histogram.percentile(100.), Some(Duration::from_micros(5000)));
+
+    let counts: Vec<_> = histogram.buckets().filter(|(_, count)| *count > 0).collect();
+    assert_eq!(counts, vec![
+        (Duration::from_micros(1), 1),
+        (Duration::from_micros(2), 1),
+        (Duration::from_micros(4), 1),
+        (Duration::from_micros(128), 2),
+        (Duration::from_micros(8192), 1),
+    ]);
+}
+
+#[test]
+fn stages() {
+    let mut tracker = Tracker::new();
+    let start = Instant::now();
+    let receipt = Receipt {
+        instant: start,
+        timestamp: 1_000,
+    };
+
+    tracker.on_queued(&trade(990), Some(receipt), start + Duration::from_micros(50));
+
+    // Clocks are not synchronized: the exchange may be ahead of us.
+    tracker.on_queued(&trade(1_005), Some(receipt), start + Duration::from_micros(80));
+
+    // Sent on a timeout, i.e. not in response to a frame.
+    let status = Notification::ExchangeStatus(ExchangeStatus::Normal.with_timestamp(0));
+    tracker.on_queued(&status, None, start + Duration::from_micros(100));
+
+    tracker.on_delivered(start + Duration::from_micros(1_000));
+    tracker.on_delivered(start + Duration::from_micros(1_000));
+
+    let stats = tracker.stats();
+    assert_eq!(stats.network.count(), 1);
+    assert_eq!(stats.network.max(), Some(Duration::from_millis(10)));
+    assert_eq!(stats.handling.count(), 2);
+    assert_eq!(stats.handling.max(), Some(Duration::from_micros(80)));
+
+    // Notifications are delivered in the order they were queued.
+    assert_eq!(stats.delivery.count(), 2);
+    assert_eq!(stats.delivery.max(), Some(Duration::from_micros(950)));
+
+    tracker.on_delivered(start + Duration::from_micros(2_000));
+    assert_eq!(tracker.stats().delivery.max(), Some(Duration::from_micros(1_900)));
+
+    // Nothing left to deliver.
+    tracker.on_delivered(start + Duration::from_micros(3_000));
+    assert_eq!(tracker.stats().delivery.count(), 3);
+}
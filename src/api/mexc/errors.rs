@@ -0,0 +1,162 @@
+//! A module defining error types specific to MEXC.
+
+use std::fmt;
+use hyper::StatusCode;
+use failure_derive::Fail;
+use serde_derive::Deserialize;
+use crate::api;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub(super) struct MexcRestError<'a> {
+    code: i64,
+    msg: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by MEXC REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Internal MEXC error code: see API documentation.
+    pub error_code: Option<i64>,
+
+    /// Description of the error.
+    pub error_msg: Option<String>,
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        if self.error_code == Some(700003) {
+            return api::errors::RestErrorKind::OutsideTimeWindow;
+        }
+
+        // Invalid signature, invalid API key format or signature, IP address not
+        // whitelisted, missing permission.
+        if self.kind == RestErrorKind::Unauthorized ||
+            self.error_code == Some(602) ||
+            self.error_code == Some(700001) ||
+            self.error_code == Some(700002) ||
+            self.error_code == Some(700006) ||
+            self.error_code == Some(700007)
+        {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.error_code == Some(-2011) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    /// Post only orders which would take liquidity are accepted then canceled by MEXC,
+    /// they are reported as an `OrderExpiration` on the stream.
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.error_code == Some(10101) || self.error_code == Some(30004) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl RestError {
+    pub(super) fn from_mexc_error(status: StatusCode, mexc_error: Option<MexcRestError>)
+        -> Self
+    {
+        RestError {
+            kind: RestErrorKind::from_status_code(status),
+            error_code: mexc_error.as_ref().map(|error| error.code),
+            error_msg: mexc_error.map(|error| error.msg.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error_msg) = &self.error_msg {
+            write!(f, ": `{}`", error_msg)?;
+        }
+        if let Some(error_code) = self.error_code {
+            write!(f, " (error_code = {})", error_code)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code to a MEXC error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key or signature, or missing permission for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by MEXC. See MEXC API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on MEXC side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is down or busy.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::OK => panic!("`RestErrorKind::from_status_code` with `StatusCode::Ok`"),
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+}
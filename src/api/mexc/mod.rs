@@ -0,0 +1,196 @@
+//! Implementation of `ApiClient` for the MEXC spot API.
+
+pub mod errors;
+mod proto;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::pkey::{PKey, Private};
+use std::collections::HashMap;
+use futures::prelude::*;
+use log::debug;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{
+    self,
+    ApiClient,
+    GenerateOrderId,
+    Params,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    NotificationFlags,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A MEXC key pair: api key + secret key.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(api_key: String, secret_key: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+        }
+    }
+}
+
+struct Keys {
+    api_key: String,
+    secret_key: PKey<Private>,
+    listen_key: String,
+}
+
+/// A MEXC spot API client.
+///
+/// Market data and account events are pushed over the WebSocket API as protobuf
+/// messages. The order book is limited to the 20 best levels of each side.
+///
+/// As with binance, the listen key of the account stream is only valid for 60 minutes
+/// after its creation (through `Client::new`), and each `<Client as ApiClient>::ping`
+/// request extends its validity for 60 minutes. If the listen key becomes invalid, this
+/// client will stop forwarding the account events, and will need to be created again.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new MEXC API client with given `params`. If `key_pair` is not `None`,
+    /// this will enable performing requests to the REST API and will request a listen
+    /// key for the account stream.
+    ///
+    /// # Note
+    /// The request may block the thread if requesting a listen key for the account stream.
+    /// The method will also block when fetching the available symbols from MEXC.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        use tokio::runtime::current_thread;
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let mut client = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                let mut client = Client {
+                    params,
+                    keys: Some(Keys {
+                        api_key: pair.api_key,
+                        secret_key,
+                        listen_key: String::new(),
+                    }),
+                    symbols: HashMap::new(),
+                    http_client,
+                };
+
+                debug!("requesting listen key");
+                let listen_key = current_thread::Runtime::new()?
+                    .block_on(client.get_listen_key())?;
+                debug!("received listen key");
+
+                client.keys.as_mut().unwrap().listen_key = listen_key;
+                client
+            }
+            None => Client {
+                params,
+                keys: None,
+                symbols: HashMap::new(),
+                http_client,
+            }
+        };
+
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Mexc
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        self.ping_impl()
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Orders sent without an order id are given one, so that they can be canceled
+    /// by their client order id.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 32 &&
+            hint.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Decoding of the protobuf messages pushed by the MEXC WebSocket API. Market data and
+//! account events are sent as binary frames holding a `PushDataV3ApiWrapper`, whose body
+//! is one of the messages below. Only the fields we need are decoded, other fields are
+//! skipped.
+
+use failure::bail;
+
+/// Protobuf wire types.
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+const FIXED32: u64 = 5;
+
+/// Fields of `PushDataV3ApiWrapper` holding the body of the message.
+crate const PUBLIC_LIMIT_DEPTHS: u32 = 303;
+crate const PRIVATE_ORDERS: u32 = 304;
+crate const PRIVATE_DEALS: u32 = 306;
+crate const PUBLIC_AGGRE_DEALS: u32 = 314;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Value of a field, as read from the wire.
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed(u64),
+}
+
+impl<'a> Value<'a> {
+    fn as_u64(self) -> Result<u64, failure::Error> {
+        match self {
+            Value::Varint(value) | Value::Fixed(value) => Ok(value),
+            Value::Bytes(_) => bail!("expected an integer, got a length delimited field"),
+        }
+    }
+
+    fn as_bytes(self) -> Result<&'a [u8], failure::Error> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => bail!("expected a length delimited field, got an integer"),
+        }
+    }
+
+    fn as_str(self) -> Result<&'a str, failure::Error> {
+        Ok(std::str::from_utf8(self.as_bytes()?)?)
+    }
+}
+
+/// Iterator over the fields of an encoded message.
+struct Fields<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Fields {
+            bytes,
+        }
+    }
+
+    fn raw_varint(&mut self) -> Result<u64, failure::Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = match self.bytes.split_first() {
+                Some((byte, rest)) => (*byte, rest),
+                None => bail!("truncated varint"),
+            };
+            self.bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("varint too long")
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], failure::Error> {
+        if len > self.bytes.len() {
+            bail!("truncated field: expected {} bytes, got {}", len, self.bytes.len());
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn fixed(&mut self, len: usize) -> Result<u64, failure::Error> {
+        let bytes = self.take(len)?;
+        Ok(bytes.iter().rev().fold(0, |value, byte| value << 8 | u64::from(*byte)))
+    }
+
+    /// Read the next field number along with its value.
+    fn next_field(&mut self) -> Result<Option<(u32, Value<'a>)>, failure::Error> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+
+        let key = self.raw_varint()?;
+        let value = match key & 0x7 {
+            VARINT => Value::Varint(self.raw_varint()?),
+            FIXED64 => Value::Fixed(self.fixed(8)?),
+            LENGTH_DELIMITED => {
+                let len = self.raw_varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            FIXED32 => Value::Fixed(self.fixed(4)?),
+            other => bail!("unsupported wire type {}", other),
+        };
+        Ok(Some(((key >> 3) as u32, value)))
+    }
+}
+
+/// Call `f` on each field of the message encoded in `bytes`.
+fn for_each_field<'a, F>(bytes: &'a [u8], mut f: F) -> Result<(), failure::Error>
+    where F: FnMut(u32, Value<'a>) -> Result<(), failure::Error>
+{
+    let mut fields = Fields::new(bytes);
+    while let Some((field, value)) = fields.next_field()? {
+        f(field, value)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A `PushDataV3ApiWrapper` message.
+crate struct Wrapper<'a> {
+    crate channel: &'a str,
+    crate symbol: &'a str,
+    crate send_time: u64,
+
+    /// Field number and encoding of the body.
+    crate body: Option<(u32, &'a [u8])>,
+}
+
+impl<'a> Wrapper<'a> {
+    crate fn decode(bytes: &'a [u8]) -> Result<Self, failure::Error> {
+        let mut wrapper = Wrapper::default();
+        for_each_field(bytes, |field, value| {
+            match field {
+                1 => wrapper.channel = value.as_str()?,
+                3 => wrapper.symbol = value.as_str()?,
+                6 => wrapper.send_time = value.as_u64()?,
+                301..=399 => wrapper.body = Some((field, value.as_bytes()?)),
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(wrapper)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A `PublicLimitDepthV3ApiItem` message.
+crate struct DepthItem<'a> {
+    crate price: &'a str,
+    crate quantity: &'a str,
+}
+
+impl<'a> DepthItem<'a> {
+    fn decode(bytes: &'a [u8]) -> Result<Self, failure::Error> {
+        let mut item = DepthItem::default();
+        for_each_field(bytes, |field, value| {
+            match field {
+                1 => item.price = value.as_str()?,
+                2 => item.quantity = value.as_str()?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(item)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A `PublicLimitDepthsV3Api` message, i.e. a snapshot of the best levels of the book.
+crate struct LimitDepths<'a> {
+    crate asks: Vec<DepthItem<'a>>,
+    crate bids: Vec<DepthItem<'a>>,
+}
+
+impl<'a> LimitDepths<'a> {
+    crate fn decode(bytes: &'a [u8]) -> Result<Self, failure::Error> {
+        let mut depths = LimitDepths::default();
+        for_each_field(bytes, |field, value| {
+            match field {
+                1 => depths.asks.push(DepthItem::decode(value.as_bytes()?)?),
+                2 => depths.bids.push(DepthItem::decode(value.as_bytes()?)?),
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(depths)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A `PublicAggreDealsV3ApiItem` message.
+crate struct Deal<'a> {
+    crate price: &'a str,
+    crate quantity: &'a str,
+
+    /// Side of the taker: 1 for buy, 2 for sell.
+    crate trade_type: u64,
+
+    crate time: u64,
+}
+
+impl<'a> Deal<'a> {
+    fn decode(bytes: &'a [u8]) -> Result<Self, failure::Error> {
+        let mut deal = Deal::default();
+        for_each_field(bytes, |field, value| {
+            match field {
+                1 => deal.price = value.as_str()?,
+                2 => deal.quantity = value.as_str()?,
+                3 => deal.trade_type = value.as_u64()?,
+                4 => deal.time = value.as_u64()?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(deal)
+    }
+}
+
+/// Decode a `PublicAggreDealsV3Api` message.
+crate fn decode_deals(bytes: &[u8]) -> Result<Vec<Deal<'_>>, failure::Error> {
+    let mut deals = Vec::new();
+    for_each_field(bytes, |field, value| {
+        if field == 1 {
+            deals.push(Deal::decode(value.as_bytes()?)?);
+        }
+        Ok(())
+    })?;
+    Ok(deals)
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A `PrivateOrdersV3Api` message.
+crate struct PrivateOrder<'a> {
+    crate id: &'a str,
+    crate client_id: &'a str,
+    crate price: &'a str,
+    crate quantity: &'a str,
+
+    /// 1 for buy, 2 for sell.
+    crate trade_type: u64,
+
+    crate remain_quantity: &'a str,
+    crate cumulative_quantity: &'a str,
+
+    /// 1: new, 2: filled, 3: partially filled, 4: canceled, 5: partially filled then
+    /// canceled.
+    crate status: u64,
+}
+
+impl<'a> PrivateOrder<'a> {
+    crate fn decode(bytes: &'a [u8]) -> Result<Self, failure::Error> {
+        let mut order = PrivateOrder::default();
+        for_each_field(bytes, |field, value| {
+            match field {
+                1 => order.id = value.as_str()?,
+                2 => order.client_id = value.as_str()?,
+                3 => order.price = value.as_str()?,
+                4 => order.quantity = value.as_str()?,
+                8 => order.trade_type = value.as_u64()?,
+                11 => order.remain_quantity = value.as_str()?,
+                13 => order.cumulative_quantity = value.as_str()?,
+                15 => order.status = value.as_u64()?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(order)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// A `PrivateDealsV3Api` message, i.e. a fill of one of our orders.
+crate struct PrivateDeal<'a> {
+    crate price: &'a str,
+    crate quantity: &'a str,
+    crate client_order_id: &'a str,
+    crate order_id: &'a str,
+    crate fee_amount: &'a str,
+    crate time: u64,
+}
+
+impl<'a> PrivateDeal<'a> {
+    crate fn decode(bytes: &'a [u8]) -> Result<Self, failure::Error> {
+        let mut deal = PrivateDeal::default();
+        for_each_field(bytes, |field, value| {
+            match field {
+                1 => deal.price = value.as_str()?,
+                2 => deal.quantity = value.as_str()?,
+                8 => deal.client_order_id = value.as_str()?,
+                9 => deal.order_id = value.as_str()?,
+                10 => deal.fee_amount = value.as_str()?,
+                12 => deal.time = value.as_u64()?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(deal)
+    }
+}
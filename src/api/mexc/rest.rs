@@ -0,0 +1,347 @@
+use hyper::Method;
+use futures::prelude::*;
+use std::collections::HashMap;
+use failure::Fail;
+use serde_derive::Deserialize;
+use log::{warn, error};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    OrderType,
+    TimeInForce,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    ExchangeStatus,
+    GenerateOrderId,
+};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::mexc::Client;
+use crate::api::mexc::errors::RestError;
+use crate::api::timestamp::{timestamp_ms, Timestamped, IntoTimestamped};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct MexcOrderAck {
+    transactTime: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct MexcBalance<'a> {
+    asset: &'a str,
+    free: &'a str,
+    locked: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct MexcAccountInformation<'a> {
+    #[serde(borrow)]
+    balances: Vec<MexcBalance<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct MexcListenKey<'a> {
+    listenKey: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct MexcSymbol<'a> {
+    symbol: &'a str,
+    isSpotTradingAllowed: bool,
+
+    /// Number of decimals of the prices.
+    quotePrecision: u32,
+
+    /// Number of decimals of the sizes.
+    baseAssetPrecision: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct MexcExchangeInfo<'a> {
+    #[serde(borrow)]
+    symbols: Vec<MexcSymbol<'a>>,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "SELL",
+            Side::Bid => "BUY",
+        }
+    }
+}
+
+/// MEXC has no time in force parameter, each time in force being an order type.
+fn order_type(type_: OrderType, time_in_force: TimeInForce) -> Option<&'static str> {
+    match (type_, time_in_force) {
+        (OrderType::Limit, TimeInForce::GoodTilCanceled) => Some("LIMIT"),
+        (OrderType::Limit, TimeInForce::ImmediateOrCancel) => Some("IMMEDIATE_OR_CANCEL"),
+        (OrderType::Limit, TimeInForce::FillOrKilll) => Some("FILL_OR_KILL"),
+        (OrderType::LimitMaker, TimeInForce::GoodTilCanceled) => Some("LIMIT_MAKER"),
+        (OrderType::Limit, TimeInForce::GoodTilDate(_))
+            | (OrderType::LimitMaker, _)
+            | (OrderType::Market, _)
+            | (OrderType::StopLimit, _)
+            | (OrderType::StopMarket, _) => None,
+    }
+}
+
+impl Client {
+    /// Parameters of every request, signed ones included, are sent in the query string.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        path: &str,
+        method: Method,
+        query: QueryString
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        use hyper::Request;
+
+        let mut request = Request::builder();
+
+        let query = match self.keys.as_ref() {
+            None => query.into_string(),
+            Some(keys) => {
+                request.header("X-MEXC-APIKEY", keys.api_key.as_bytes());
+                query.into_string_with_signature(&keys.secret_key)
+            }
+        };
+
+        let address = format!(
+            "{}/{}?{}",
+            self.params.rest_endpoint,
+            path,
+            query,
+        );
+
+        request.method(method)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..])
+            .uri(&address);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(hyper::Body::empty()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let mexc_error = serde_json::from_slice(&body);
+                let error = RestError::from_mexc_error(status, mexc_error.ok());
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by MEXC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by MEXC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only || order.trailing_delta.is_some() || order.display_size.is_some() {
+            warn!("called `order` with reduce only, a trailing delta or a display size, which are not supported on MEXC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let type_ = match order_type(order.type_, order.time_in_force) {
+            Some(type_) => type_,
+            None => {
+                warn!(
+                    "called `order` with `{:?}` and `{:?}`, which is not supported on MEXC",
+                    order.type_,
+                    order.time_in_force
+                );
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let mut query = QueryString::new();
+        let symbol = order.symbol();
+        query.push_str("symbol", symbol.name());
+        query.push_str("side", order.side.as_str());
+        query.push_str("type", type_);
+        query.push_str(
+            "quantity",
+            order.size.unticked(symbol.size_tick()).borrow() as &str
+        );
+        query.push_str(
+            "price",
+            order.price.unticked(symbol.price_tick()).borrow() as &str
+        );
+        query.push_str("newClientOrderId", &order_id);
+        query.push("recvWindow", order.time_window);
+        query.push("timestamp", timestamp_ms());
+
+        let fut = self.request("api/v3/order", Method::POST, query).and_then(|body| {
+            let ack: MexcOrderAck = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(OrderAck {
+                order_id,
+            }.with_timestamp(ack.transactTime.unwrap_or_else(timestamp_ms)))
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        let symbol = cancel.symbol();
+        query.push_str("symbol", symbol.name());
+        query.push_str("origClientOrderId", &cancel.order_id);
+        query.push("recvWindow", cancel.time_window);
+        query.push("timestamp", timestamp_ms());
+
+        self.request("api/v3/order", Method::DELETE, query).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn get_listen_key(&self)
+        -> impl Future<Item = String, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push("recvWindow", 5000);
+        query.push("timestamp", timestamp_ms());
+
+        self.request("api/v3/userDataStream", Method::POST, query).and_then(|body| {
+            let key: MexcListenKey<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(key.listenKey.to_owned())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        let mut query = QueryString::new();
+
+        if let Some(listen_key) = self.keys.as_ref().map(|keys| &keys.listen_key) {
+            query.push_str("listenKey", listen_key);
+            query.push("recvWindow", 5000);
+            query.push("timestamp", timestamp_ms());
+
+            let fut = self.request("api/v3/userDataStream", Method::PUT, query)
+                .and_then(|_| Ok(().timestamped()));
+            Box::new(fut)
+        } else {
+            let fut = self.request("api/v3/ping", Method::GET, query)
+                .and_then(|_| Ok(().timestamped()));
+            Box::new(fut)
+        }
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push("recvWindow", 5000);
+        query.push("timestamp", timestamp_ms());
+
+        self.request("api/v3/account", Method::GET, query).and_then(|body| {
+            let info: MexcAccountInformation<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let balances = info.balances.into_iter().map(|balance| {
+                (balance.asset.to_owned(), api::Balance {
+                    free: balance.free.to_owned(),
+                    locked: balance.locked.to_owned(),
+                })
+            }).collect();
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        // MEXC has no status endpoint, being able to reach it is the best we can do.
+        self.request("api/v3/ping", Method::GET, QueryString::new()).and_then(|_| {
+            Ok(ExchangeStatus::Normal.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("api/v3/exchangeInfo", Method::GET, QueryString::new()).and_then(|body| {
+            let info: MexcExchangeInfo<'_> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            for symbol in info.symbols {
+                if !symbol.isSpotTradingAllowed {
+                    continue;
+                }
+
+                let (price_tick, size_tick) = match (
+                    10u64.checked_pow(symbol.quotePrecision),
+                    10u64.checked_pow(symbol.baseAssetPrecision)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => {
+                        (Tick::new(price_tick), Tick::new(size_tick))
+                    }
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", symbol.symbol);
+                        continue;
+                    }
+                };
+
+                match Symbol::new(symbol.symbol, price_tick, size_tick) {
+                    Some(symbol) => {
+                        symbols.insert(symbol.name().to_lowercase(), symbol);
+                    }
+                    None => error!("symbol name too long: `{}`", symbol.symbol),
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
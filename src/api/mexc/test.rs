@@ -0,0 +1,140 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::mexc::proto;
+use crate::api::mexc::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTCUSDT", Tick::new(100), Tick::new(1_000_000)).unwrap()
+}
+
+fn varint(mut value: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    bytes
+}
+
+/// Encode a length delimited field.
+fn field(number: u32, value: &[u8]) -> Vec<u8> {
+    let mut bytes = varint(u64::from(number) << 3 | 2);
+    bytes.extend(varint(value.len() as u64));
+    bytes.extend(value);
+    bytes
+}
+
+/// Encode a `PushDataV3ApiWrapper` holding a `PublicLimitDepthsV3Api` body.
+fn depths(symbol: &str, send_time: u64, asks: &[(&str, &str)], bids: &[(&str, &str)])
+    -> Vec<u8>
+{
+    let mut body = Vec::new();
+    for (number, levels) in &[(1, asks), (2, bids)] {
+        for (price, quantity) in levels.iter() {
+            let item = [field(1, price.as_bytes()), field(2, quantity.as_bytes())].concat();
+            body.extend(field(*number, &item));
+        }
+    }
+
+    let channel = format!("spot@public.limit.depth.v3.api.pb@{}@20", symbol);
+    let mut wrapper = field(1, channel.as_bytes());
+    wrapper.extend(field(3, symbol.as_bytes()));
+    wrapper.extend(varint(6 << 3));
+    wrapper.extend(varint(send_time));
+    wrapper.extend(field(proto::PUBLIC_LIMIT_DEPTHS, &body));
+    wrapper
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[Vec<u8>]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_binary(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+#[test]
+fn wrapper_decoding() {
+    let bytes = [
+        // channel
+        0x0a, 0x01, b'c',
+
+        // A fixed32 field which is skipped.
+        0x15, 0x01, 0x02, 0x03, 0x04,
+
+        // symbol
+        0x1a, 0x07, b'B', b'T', b'C', b'U', b'S', b'D', b'T',
+
+        // sendTime, as a multi-byte varint
+        0x30, 0xfb, 0xd0, 0x95, 0xff, 0xbc, 0x31,
+
+        // publicLimitDepths, holding an empty ask
+        0xfa, 0x12, 0x02, 0x0a, 0x00,
+    ];
+
+    let wrapper = proto::Wrapper::decode(&bytes).unwrap();
+    assert_eq!(wrapper, proto::Wrapper {
+        channel: "c",
+        symbol: "BTCUSDT",
+        send_time: 1_700_000_000_123,
+        body: Some((proto::PUBLIC_LIMIT_DEPTHS, &[0x0a, 0x00][..])),
+    });
+
+    let depths = proto::LimitDepths::decode(wrapper.body.unwrap().1).unwrap();
+    assert_eq!(depths.asks, vec![proto::DepthItem::default()]);
+    assert!(depths.bids.is_empty());
+
+    assert!(proto::Wrapper::decode(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn book_snapshots() {
+    let frames = [
+        depths(
+            "BTCUSDT",
+            1_700_000_000_000,
+            &[("43001.50", "0.5"), ("43002.00", "1.2")],
+            &[("43000.00", "0.25"), ("42999.99", "2")]
+        ),
+
+        // Snapshots of other symbols are skipped.
+        depths("ETHUSDT", 1_700_000_000_050, &[("2250.10", "3")], &[("2250.00", "1")]),
+
+        // Each snapshot is turned into the updates from the previous one.
+        depths(
+            "BTCUSDT",
+            1_700_000_000_100,
+            &[("43001.50", "0.4"), ("43002.00", "1.2")],
+            &[("43000.00", "0.25"), ("42999.50", "1")]
+        ),
+    ];
+
+    let notifs = parse(&frames);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(4_299_999, 2_000_000, Side::Bid).with_timestamp(1_700_000_000_000),
+            LimitUpdate::new(4_300_000, 250_000, Side::Bid).with_timestamp(1_700_000_000_000),
+            LimitUpdate::new(4_300_150, 500_000, Side::Ask).with_timestamp(1_700_000_000_000),
+            LimitUpdate::new(4_300_200, 1_200_000, Side::Ask).with_timestamp(1_700_000_000_000),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(4_299_950, 1_000_000, Side::Bid).with_timestamp(1_700_000_000_100),
+            LimitUpdate::new(4_299_999, 0, Side::Bid).with_timestamp(1_700_000_000_100),
+            LimitUpdate::new(4_300_150, 400_000, Side::Ask).with_timestamp(1_700_000_000_100),
+        ]),
+    ]);
+}
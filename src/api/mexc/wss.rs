@@ -0,0 +1,410 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use std::collections::HashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::mexc::Client;
+use crate::api::mexc::proto;
+
+const ORDERS_CHANNEL: &str = "spot@private.orders.v3.api.pb";
+const DEALS_CHANNEL: &str = "spot@private.deals.v3.api.pb";
+
+/// Number of levels of each side of the book snapshots.
+const BOOK_DEPTH: usize = 20;
+
+fn book_channel(symbol: &Symbol) -> String {
+    format!("spot@public.limit.depth.v3.api.pb@{}@{}", symbol.name(), BOOK_DEPTH)
+}
+
+fn trades_channel(symbol: &Symbol) -> String {
+    format!("spot@public.aggre.deals.v3.api.pb@100ms@{}", symbol.name())
+}
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let mut address = self.params.streaming_endpoint.clone();
+        let private = self.keys.is_some();
+        if let Some(keys) = &self.keys {
+            address += &format!("?listenKey={}", keys.listen_key);
+        }
+
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", address);
+
+            if let Err(err) = ws::connect(address, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        private,
+                        order_book: OrderBook::new(),
+                        orders: HashMap::new(),
+                        pending_fills: HashMap::new(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Public and private channels are subscribed to over the same connection, the private
+/// ones being available if the connection was opened with a listen key. Subscription
+/// acknowledgements are sent as JSON text messages, while the data is pushed as binary
+/// protobuf messages.
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+
+    /// Whether the connection was opened with a listen key.
+    private: bool,
+
+    /// Local copy of the order book, needed for turning the snapshots of the best levels
+    /// into limit updates.
+    order_book: OrderBook,
+
+    /// client order id => order
+    orders: HashMap<String, OrderState>,
+
+    /// Fills received before any event of their order, since the two channels are not
+    /// synchronized. client order id => fills
+    pending_fills: HashMap<String, Vec<Fill>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct OrderState {
+    size: TickUnit,
+    filled: TickUnit,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Fill {
+    price: TickUnit,
+    size: TickUnit,
+    commission: TickUnit,
+    time: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct MexcRequest<'a> {
+    method: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    params: &'a [String],
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct MexcResponse<'a> {
+    code: i64,
+    msg: &'a str,
+}
+
+/// The side sent along orders and trades, i.e. the side of the taker for trades.
+fn convert_trade_type(trade_type: u64) -> Result<Side, failure::Error> {
+    match trade_type {
+        1 => Ok(Side::Bid),
+        2 => Ok(Side::Ask),
+        other => bail!("wrong trade type: `{}`", other),
+    }
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without a listen key, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            private: false,
+            order_book: OrderBook::new(),
+            orders: HashMap::new(),
+            pending_fills: HashMap::new(),
+        }
+    }
+
+    fn send_request(&self, method: &str, params: &[String], out: &ws::Sender) -> ws::Result<()> {
+        match serde_json::to_string(&MexcRequest { method, params }) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `MexcRequest`: `{}`", err);
+            }
+        }
+    }
+
+    /// Return the channels enabled by `flags`, the private ones only if the connection
+    /// was opened with a listen key.
+    fn channels(&self, flags: NotificationFlags) -> Vec<String> {
+        let mut channels = Vec::new();
+        if flags.contains(NotificationFlags::ORDER_BOOK) {
+            channels.push(book_channel(&self.symbol));
+        }
+        if flags.contains(NotificationFlags::TRADES) {
+            channels.push(trades_channel(&self.symbol));
+        }
+        if self.private && flags.contains(NotificationFlags::ORDERS) {
+            channels.push(ORDERS_CHANNEL.to_owned());
+            channels.push(DEALS_CHANNEL.to_owned());
+        }
+        channels
+    }
+
+    fn parse_depths(&mut self, wrapper: &proto::Wrapper<'_>, body: &[u8], out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let depths = proto::LimitDepths::decode(body)?;
+
+        let mut order_book = OrderBook::new();
+        for (levels, side) in &[(&depths.bids, Side::Bid), (&depths.asks, Side::Ask)] {
+            for level in levels.iter() {
+                order_book.update(LimitUpdate {
+                    side: *side,
+                    price: self.symbol.price_tick().ticked(level.price)?,
+                    size: self.symbol.size_tick().ticked(level.quantity)?,
+                });
+            }
+        }
+
+        // Levels which are not part of the new snapshot are removed.
+        let updates: Vec<_> = self.order_book.diff(&order_book)
+            .map(|update| update.with_timestamp(wrapper.send_time))
+            .collect();
+        self.order_book = order_book;
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_deals(&self, body: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        for deal in proto::decode_deals(body)? {
+            // The side is the one of the taker.
+            let maker_side = match convert_trade_type(deal.trade_type)? {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid,
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: self.symbol.price_tick().ticked(deal.price)?,
+                size: self.symbol.size_tick().ticked(deal.quantity)?,
+                maker_side,
+            }.with_timestamp(deal.time));
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Account for `fill` of the order `order_id`, whose state is known.
+    fn apply_fill(&mut self, order_id: &str, fill: Fill, out: &wss::NotifSender) {
+        let remaining_size = match self.orders.get_mut(order_id) {
+            Some(state) => {
+                state.filled += fill.size;
+                state.size.saturating_sub(state.filled)
+            }
+            None => return,
+        };
+        if remaining_size == 0 {
+            self.orders.remove(order_id);
+        }
+
+        let update = OrderUpdate {
+            order_id: order_id.to_owned(),
+            consumed_size: fill.size,
+            remaining_size,
+            consumed_price: fill.price,
+            commission: fill.commission,
+        }.with_timestamp(fill.time);
+        out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+    }
+
+    fn parse_order(&mut self, wrapper: &proto::Wrapper<'_>, body: &[u8], out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let order = proto::PrivateOrder::decode(body)?;
+        let order_id = order.client_id;
+
+        match order.status {
+            // Canceled, possibly after being partially filled.
+            4 | 5 => {
+                self.orders.remove(order_id);
+                self.pending_fills.remove(order_id);
+
+                let expiration = OrderExpiration {
+                    order_id: order_id.to_owned(),
+                    filled_size: Some(self.symbol.size_tick().ticked(order.cumulative_quantity)?),
+                    remaining_size: Some(self.symbol.size_tick().ticked(order.remain_quantity)?),
+                }.with_timestamp(wrapper.send_time);
+                out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+            }
+
+            // New, filled or partially filled. Fills are notified by the deals channel.
+            _ => {
+                if self.orders.contains_key(order_id) {
+                    return Ok(());
+                }
+
+                let size = self.symbol.size_tick().ticked(order.quantity)?;
+                self.orders.insert(order_id.to_owned(), OrderState {
+                    size,
+                    filled: 0,
+                });
+
+                let confirmation = OrderConfirmation {
+                    order_id: order_id.to_owned(),
+                    price: self.symbol.price_tick().ticked(order.price)?,
+                    size,
+                    side: convert_trade_type(order.trade_type)?,
+                }.with_timestamp(wrapper.send_time);
+                out.unbounded_send(Notification::OrderConfirmation(confirmation)).unwrap();
+
+                if let Some(fills) = self.pending_fills.remove(order_id) {
+                    for fill in fills {
+                        self.apply_fill(order_id, fill, out);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_fill(&mut self, body: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let deal = proto::PrivateDeal::decode(body)?;
+
+        let fill = Fill {
+            price: self.symbol.price_tick().ticked(deal.price)?,
+            size: self.symbol.size_tick().ticked(deal.quantity)?,
+            commission: self.symbol.commission_tick().ticked(deal.fee_amount)?,
+            time: deal.time,
+        };
+
+        if self.orders.contains_key(deal.client_order_id) {
+            self.apply_fill(deal.client_order_id, fill, out);
+        } else {
+            self.pending_fills.entry(deal.client_order_id.to_owned())
+                .or_default()
+                .push(fill);
+        }
+        Ok(())
+    }
+
+    crate fn parse_binary(&mut self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let wrapper = proto::Wrapper::decode(data)?;
+
+        let (field, body) = match wrapper.body {
+            Some(body) => body,
+            None => return Ok(()),
+        };
+
+        // Account channels carry the events of every symbol.
+        if wrapper.symbol != self.symbol.name() {
+            return Ok(());
+        }
+
+        match field {
+            proto::PUBLIC_LIMIT_DEPTHS if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_depths(&wrapper, body, out)?;
+            }
+
+            proto::PUBLIC_AGGRE_DEALS if self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_deals(body, out)?;
+            }
+
+            proto::PRIVATE_ORDERS if self.flags.contains(NotificationFlags::ORDERS) => {
+                self.parse_order(&wrapper, body, out)?;
+            }
+
+            proto::PRIVATE_DEALS if self.flags.contains(NotificationFlags::ORDERS) => {
+                self.parse_fill(body, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        let channels = self.channels(self.flags);
+        if channels.is_empty() {
+            return Ok(());
+        }
+        self.send_request("SUBSCRIPTION", &channels, out)
+    }
+
+    /// Text messages are acknowledgements of the subscriptions and of the pings.
+    fn on_message(&mut self, text: &str, _: &wss::NotifSender) -> Result<(), failure::Error> {
+        let response: MexcResponse<'_> = serde_json::from_str(text)?;
+        if response.code != 0 {
+            bail!("request failed: `{}` (code = {})", response.msg, response.code);
+        }
+        debug!("received acknowledgement: `{}`", response.msg);
+        Ok(())
+    }
+
+    fn on_binary(&mut self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_binary(data, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+        let (previous, current) = (self.channels(previous), self.channels(flags));
+
+        let subscribe = current.iter()
+            .filter(|channel| !previous.contains(channel))
+            .cloned()
+            .collect::<Vec<_>>();
+        let unsubscribe = previous.iter()
+            .filter(|channel| !current.contains(channel))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // The book is sent again from the next snapshot.
+        if subscribe.contains(&book_channel(&self.symbol)) {
+            self.order_book = OrderBook::new();
+        }
+
+        if !unsubscribe.is_empty() {
+            self.send_request("UNSUBSCRIPTION", &unsubscribe, out)?;
+        }
+        if !subscribe.is_empty() {
+            self.send_request("SUBSCRIPTION", &subscribe, out)?;
+        }
+        Ok(())
+    }
+
+    /// MEXC does not answer ping frames, and closes connections which do not send any
+    /// message for a minute.
+    fn on_ping(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.send_request("PING", &[], out)
+    }
+}
@@ -0,0 +1,158 @@
+//! A module defining fair value estimates derived from the order book of a notification
+//! stream, e.g. for skewing quotes or choosing where to route an order.
+//!
+//! The estimate is computed by a `FairValueEstimator` each time a batch of limit
+//! updates is received, and output only when it actually changes. The default
+//! estimator is the imbalance-weighted microprice, which leans towards the side of the
+//! book with less size available, since that side is more likely to be consumed first.
+
+mod test;
+#[cfg(feature = "network")]
+mod stream;
+
+#[cfg(feature = "network")]
+pub use self::stream::MicropriceStream;
+
+use crate::api::Notification;
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+use crate::order_book::OrderBook;
+
+/// A fair value estimator over an order book, pluggable into `Microprice`.
+///
+/// Closures taking an `&OrderBook` and returning an `Option<f64>` are estimators.
+pub trait FairValueEstimator {
+    /// Return the fair value of `order_book` in price tick units, or `None` if it
+    /// cannot be estimated, e.g. because one side of the book is empty.
+    fn fair_value(&self, order_book: &OrderBook) -> Option<f64>;
+}
+
+impl<F> FairValueEstimator for F where F: Fn(&OrderBook) -> Option<f64> {
+    fn fair_value(&self, order_book: &OrderBook) -> Option<f64> {
+        self(order_book)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// The imbalance-weighted microprice: the best bid and ask prices weighted by the size
+/// available on the opposite side, i.e. `bid * I + ask * (1 - I)`, the imbalance `I`
+/// being the proportion of the size of the top levels which lies on the ask side.
+pub struct ImbalanceWeighted {
+    levels: usize,
+}
+
+impl ImbalanceWeighted {
+    /// Return a new `ImbalanceWeighted` estimator, computing the imbalance over the
+    /// best level of each side only.
+    pub fn new() -> Self {
+        ImbalanceWeighted {
+            levels: 1,
+        }
+    }
+
+    /// Compute the imbalance over the `levels` best levels of each side.
+    ///
+    /// # Panics
+    /// Panic if `levels` is `0`.
+    pub fn with_levels(mut self, levels: usize) -> Self {
+        if levels == 0 {
+            panic!("`levels` must be positive");
+        }
+        self.levels = levels;
+        self
+    }
+
+    /// Return the number of levels of each side the imbalance is computed over.
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+}
+
+impl Default for ImbalanceWeighted {
+    fn default() -> Self {
+        ImbalanceWeighted::new()
+    }
+}
+
+impl FairValueEstimator for ImbalanceWeighted {
+    fn fair_value(&self, order_book: &OrderBook) -> Option<f64> {
+        let bid_price = *order_book.bid().next()?.0;
+        let ask_price = *order_book.ask().next()?.0;
+
+        let bid_size: f64 = order_book.bid().take(self.levels).map(|(_, size)| *size as f64).sum();
+        let ask_size: f64 = order_book.ask().take(self.levels).map(|(_, size)| *size as f64).sum();
+
+        let imbalance = ask_size / (bid_size + ask_size);
+        Some(bid_price as f64 * imbalance + ask_price as f64 * (1. - imbalance))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// Derive fair value estimates from a sequence of notifications.
+pub struct Microprice<E = ImbalanceWeighted> {
+    order_book: OrderBook,
+    estimator: E,
+    last: Option<f64>,
+}
+
+impl Microprice<ImbalanceWeighted> {
+    /// Return a new `Microprice` using the default `ImbalanceWeighted` estimator.
+    pub fn new() -> Self {
+        Microprice::with_estimator(ImbalanceWeighted::new())
+    }
+}
+
+impl Default for Microprice<ImbalanceWeighted> {
+    fn default() -> Self {
+        Microprice::new()
+    }
+}
+
+impl<E: FairValueEstimator> Microprice<E> {
+    /// Return a new `Microprice` using `estimator`.
+    pub fn with_estimator(estimator: E) -> Self {
+        Microprice {
+            order_book: OrderBook::new(),
+            estimator,
+            last: None,
+        }
+    }
+
+    /// Return the estimator.
+    pub fn estimator(&self) -> &E {
+        &self.estimator
+    }
+
+    /// Return the maintained order book.
+    pub fn order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    /// Return the latest fair value output, in price tick units.
+    pub fn fair_value(&self) -> Option<f64> {
+        self.last
+    }
+
+    /// Apply the limit updates of `notif`, and return the new fair value in price tick
+    /// units if it has changed. The returned value carries the timestamp of the latest
+    /// limit update. Nothing is output while the fair value cannot be estimated.
+    pub fn on_notification(&mut self, notif: &Notification) -> Option<Timestamped<f64>> {
+        let updates = match notif {
+            Notification::LimitUpdates(updates) if !updates.is_empty() => updates,
+            _ => return None,
+        };
+
+        let mut timestamp = 0;
+        for update in updates {
+            timestamp = std::cmp::max(timestamp, update.timestamp());
+            self.order_book.update(**update);
+        }
+
+        let fair_value = self.estimator.fair_value(&self.order_book);
+        if fair_value == self.last {
+            return None;
+        }
+
+        self.last = fair_value;
+        fair_value.map(|fair_value| fair_value.with_timestamp(timestamp))
+    }
+}
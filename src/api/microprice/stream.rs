@@ -0,0 +1,51 @@
+use futures::prelude::*;
+use crate::api::Notification;
+use crate::api::microprice::{Microprice, FairValueEstimator};
+use crate::api::timestamp::Timestamped;
+
+impl<E: FairValueEstimator> Microprice<E> {
+    /// Drive `self` from a notification stream. The returned stream ends when
+    /// `notifications` ends.
+    pub fn stream<S>(self, notifications: S) -> MicropriceStream<S, E>
+        where S: Stream<Item = Notification, Error = ()>
+    {
+        MicropriceStream {
+            microprice: self,
+            notifications,
+        }
+    }
+}
+
+/// A stream of fair value changes, in price tick units, see `Microprice::stream`.
+pub struct MicropriceStream<S, E> {
+    microprice: Microprice<E>,
+    notifications: S,
+}
+
+impl<S, E> MicropriceStream<S, E> {
+    /// Return the underlying `Microprice`.
+    pub fn microprice(&self) -> &Microprice<E> {
+        &self.microprice
+    }
+}
+
+impl<S, E> Stream for MicropriceStream<S, E>
+    where S: Stream<Item = Notification, Error = ()>, E: FairValueEstimator
+{
+    type Item = Timestamped<f64>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Timestamped<f64>>, ()> {
+        loop {
+            match self.notifications.poll()? {
+                Async::Ready(Some(notif)) => {
+                    if let Some(fair_value) = self.microprice.on_notification(&notif) {
+                        return Ok(Async::Ready(Some(fair_value)));
+                    }
+                }
+                Async::Ready(None) => return Ok(Async::Ready(None)),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
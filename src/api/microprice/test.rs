@@ -0,0 +1,92 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::Notification;
+use crate::api::microprice::{Microprice, ImbalanceWeighted, FairValueEstimator};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::{OrderBook, LimitUpdate};
+use crate::tick::TickUnit;
+
+fn lu(timestamp: Timestamp, price: TickUnit, size: TickUnit, side: Side) -> Notification {
+    Notification::LimitUpdates(vec![LimitUpdate::new(price, size, side).with_timestamp(timestamp)])
+}
+
+fn book(updates: &[(TickUnit, TickUnit, Side)]) -> OrderBook {
+    let mut order_book = OrderBook::new();
+    for &(price, size, side) in updates {
+        order_book.update(LimitUpdate::new(price, size, side));
+    }
+    order_book
+}
+
+#[test]
+fn imbalance_weighted() {
+    let estimator = ImbalanceWeighted::new();
+    assert_eq!(estimator.fair_value(&book(&[(100, 5, Side::Bid)])), None);
+
+    let order_book = book(&[
+        (100, 3, Side::Bid),
+        (99, 9, Side::Bid),
+        (102, 1, Side::Ask),
+        (103, 1, Side::Ask),
+    ]);
+
+    // Much more size on the bid side: the fair value leans towards the ask.
+    assert_eq!(estimator.fair_value(&order_book), Some(100. * 0.25 + 102. * 0.75));
+
+    let estimator = ImbalanceWeighted::new().with_levels(2);
+    assert_eq!(estimator.levels(), 2);
+    let imbalance = 2. / 14.;
+    assert_eq!(estimator.fair_value(&order_book), Some(100. * imbalance + 102. * (1. - imbalance)));
+}
+
+#[test]
+#[should_panic]
+fn zero_levels() {
+    ImbalanceWeighted::new().with_levels(0);
+}
+
+#[test]
+fn changes() {
+    let mut microprice = Microprice::new();
+    assert_eq!(microprice.on_notification(&lu(10, 100, 1, Side::Bid)), None);
+    assert_eq!(
+        microprice.on_notification(&lu(20, 102, 1, Side::Ask)),
+        Some(101f64.with_timestamp(20))
+    );
+
+    // Deep book change.
+    assert_eq!(microprice.on_notification(&lu(30, 99, 5, Side::Bid)), None);
+
+    // Same imbalance.
+    let notif = Notification::LimitUpdates(vec![
+        LimitUpdate::new(100, 2, Side::Bid).with_timestamp(40),
+        LimitUpdate::new(102, 2, Side::Ask).with_timestamp(35),
+    ]);
+    assert_eq!(microprice.on_notification(&notif), None);
+
+    assert_eq!(
+        microprice.on_notification(&lu(50, 102, 6, Side::Ask)),
+        Some(100.5f64.with_timestamp(50))
+    );
+    assert_eq!(microprice.fair_value(), Some(100.5));
+
+    // The ask side is emptied then refilled.
+    assert_eq!(microprice.on_notification(&lu(60, 102, 0, Side::Ask)), None);
+    assert_eq!(microprice.fair_value(), None);
+    assert_eq!(
+        microprice.on_notification(&lu(70, 102, 6, Side::Ask)),
+        Some(100.5f64.with_timestamp(70))
+    );
+}
+
+#[test]
+fn custom_estimator() {
+    let best_bid = |order_book: &OrderBook| order_book.bid().next().map(|(price, _)| *price as f64);
+    let mut microprice = Microprice::with_estimator(best_bid);
+    assert_eq!(
+        microprice.on_notification(&lu(10, 100, 1, Side::Bid)),
+        Some(100f64.with_timestamp(10))
+    );
+    assert_eq!(microprice.on_notification(&lu(20, 102, 1, Side::Ask)), None);
+}
@@ -1,26 +1,147 @@
 //! A unified API for abstracting over various exchanges.
 
+#[cfg(feature = "alpaca")]
+pub mod alpaca;
+#[cfg(feature = "binance")]
 pub mod binance;
+#[cfg(feature = "bitfinex")]
+pub mod bitfinex;
+#[cfg(feature = "bitmex")]
+pub mod bitmex;
+#[cfg(feature = "bybit")]
+pub mod bybit;
+#[cfg(feature = "coinbase")]
+pub mod coinbase;
+#[cfg(feature = "deribit")]
+pub mod deribit;
+#[cfg(feature = "dydx")]
+pub mod dydx;
+#[cfg(feature = "gateio")]
+pub mod gateio;
+#[cfg(feature = "gdax")]
 pub mod gdax;
+#[cfg(feature = "gemini")]
+pub mod gemini;
+#[cfg(feature = "hitbtc")]
 pub mod hitbtc;
+#[cfg(feature = "huobi")]
+pub mod huobi;
+#[cfg(feature = "kraken")]
+pub mod kraken;
+#[cfg(feature = "kraken_futures")]
+pub mod kraken_futures;
+#[cfg(feature = "kucoin")]
+pub mod kucoin;
+#[cfg(feature = "mexc")]
+pub mod mexc;
+#[cfg(feature = "okx")]
+pub mod okx;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(feature = "upbit")]
+pub mod upbit;
+#[cfg(feature = "affinity")]
+pub mod affinity;
+pub mod backfill;
+pub mod blotter;
+pub mod budget;
+pub mod calendar;
+#[cfg(feature = "network")]
+pub mod conformance;
+pub mod dedup;
+pub mod derivatives;
+pub mod drift;
 pub mod errors;
+pub mod failover;
+pub mod fees;
+pub mod filter;
+#[cfg(feature = "testkit")]
+pub mod fixtures;
+pub mod id;
+pub mod index;
+#[cfg(feature = "keychain")]
+pub mod keychain;
+#[cfg(feature = "latency")]
+pub mod latency;
+pub mod microprice;
+pub mod options;
+pub mod paper;
 pub mod timestamp;
 pub mod symbol;
+#[cfg(feature = "network")]
 pub mod order_book;
+pub mod restricted;
+pub mod schema;
+pub mod sequencer;
+#[cfg(feature = "network")]
+pub mod status;
+#[cfg(feature = "network")]
+pub mod stream;
+pub mod ticker;
+pub mod throttle;
+pub mod wallet;
+#[cfg(any(
+    feature = "alpaca",
+    feature = "binance",
+    feature = "bitmex",
+    feature = "bybit",
+    feature = "coinbase",
+    feature = "deribit",
+    feature = "dydx",
+    feature = "gateio",
+    feature = "hitbtc",
+    feature = "huobi",
+    feature = "kraken",
+    feature = "kraken_futures",
+    feature = "kucoin",
+    feature = "mexc",
+    feature = "okx",
+    feature = "upbit",
+))]
 mod query_string;
+#[cfg(feature = "network")]
+#[cfg_attr(
+    not(any(
+        feature = "alpaca",
+        feature = "binance",
+        feature = "bitfinex",
+        feature = "bitmex",
+        feature = "bybit",
+        feature = "coinbase",
+        feature = "deribit",
+        feature = "dydx",
+        feature = "gateio",
+        feature = "gdax",
+        feature = "gemini",
+        feature = "hitbtc",
+        feature = "huobi",
+        feature = "kraken",
+        feature = "kraken_futures",
+        feature = "kucoin",
+        feature = "mexc",
+        feature = "okx",
+        feature = "testkit",
+        feature = "upbit",
+    )),
+    allow(dead_code)
+)]
 mod wss;
 
 use futures::prelude::*;
 use std::collections::HashMap;
 use serde_derive::{Serialize, Deserialize};
 use bitflags::bitflags;
+use log::warn;
 use crate::Side;
 use crate::tick::{TickUnit, Tickable};
 use crate::order_book::LimitUpdate;
 
-use self::timestamp::Timestamped;
-use self::symbol::{Symbol, WithSymbol};
+use self::timestamp::{Timestamp, Timestamped};
+use crate::data::resample::Bar;
+use self::symbol::{Symbol, WithSymbol, IntoWithSymbol};
+use self::id::{ExchangeId, SymbolId};
 
+#[cfg(feature = "gdax")]
 pub use self::gdax as coinbase_pro; // Just rename GDAX to its new name.
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
@@ -45,6 +166,28 @@ pub enum TimeInForce {
 
     /// If the order cannot be filled immediately in its entierety, it is rejected.
     FillOrKilll,
+
+    /// The order stays on the exchange until it is executed or canceled, or until the
+    /// given time (in ms) is reached, after which it expires.
+    ///
+    /// # Note
+    /// Native on binance futures and HitBTC. On binance spot and GDAX, the order is
+    /// canceled by a timer of the client, the cancel being notified as any other
+    /// `OrderExpiration`: the timer runs on the tokio executor of the order request,
+    /// which must still be running when the date is reached. GDAX only expires orders
+    /// after a minute, an hour or a day, the shortest one not expiring the order before
+    /// its date is also sent as a backstop.
+    GoodTilDate(Timestamp),
+}
+
+impl TimeInForce {
+    /// Return the time at which a `GoodTilDate` order expires.
+    pub fn expiration(self) -> Option<Timestamp> {
+        match self {
+            TimeInForce::GoodTilDate(expiration) => Some(expiration),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
@@ -56,6 +199,59 @@ pub enum OrderType {
     /// A limit order which cannot take liquidity, i.e. an error would be returned by
     /// the exchange if the order crosses the other side of the book.
     LimitMaker,
+
+    /// An order executed immediately against the book, whatever the price. The price of
+    /// the order is ignored.
+    ///
+    /// # Note
+    /// Supported on binance, GDAX and HitBTC, other exchanges reject the order with
+    /// `RestErrorKind::InvalidRequest`.
+    Market,
+
+    /// A limit order only sent to the book, at its limit price, once the market reaches
+    /// the trigger price of the order, see `Order::with_trigger_price`.
+    ///
+    /// # Note
+    /// Supported on binance, GDAX and HitBTC, other exchanges reject the order with
+    /// `RestErrorKind::InvalidRequest`.
+    StopLimit,
+
+    /// A market order only executed once the market reaches the trigger price of the
+    /// order, see `Order::with_trigger_price`. The price of the order is ignored.
+    ///
+    /// # Note
+    /// Supported on binance, GDAX and HitBTC, other exchanges reject the order with
+    /// `RestErrorKind::InvalidRequest`.
+    StopMarket,
+}
+
+impl OrderType {
+    /// Return `true` for `OrderType::StopLimit` and `OrderType::StopMarket`, which
+    /// require a trigger price.
+    pub fn is_stop(self) -> bool {
+        match self {
+            OrderType::StopLimit | OrderType::StopMarket => true,
+            OrderType::Limit | OrderType::LimitMaker | OrderType::Market => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A size expressed in the quote currency of a symbol, e.g. 500 USDT on `BTCUSDT`, as
+/// opposed to the size of an `Order`, expressed in the base currency. A ticked quote
+/// size is expressed in price ticks. See `Order::market_quote`.
+pub struct QuoteSize(Tickable);
+
+impl QuoteSize {
+    /// Return a new quote size.
+    pub fn new<T: Into<Tickable>>(size: T) -> Self {
+        QuoteSize(size.into())
+    }
+
+    /// Return the size, in the quote currency.
+    pub fn size(&self) -> &Tickable {
+        &self.0
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
@@ -69,6 +265,15 @@ pub struct Order {
     time_in_force: TimeInForce,
     time_window: u64,
     order_id: Option<String>,
+    trailing_delta: Option<u64>,
+    #[serde(default)]
+    reduce_only: bool,
+    #[serde(default)]
+    quote_size: bool,
+    #[serde(default)]
+    trigger_price: Option<Tickable>,
+    #[serde(default)]
+    display_size: Option<Tickable>,
 }
 
 impl Order {
@@ -87,15 +292,64 @@ impl Order {
             time_in_force: TimeInForce::GoodTilCanceled,
             time_window: 5000,
             order_id: None,
+            trailing_delta: None,
+            reduce_only: false,
+            quote_size: false,
+            trigger_price: None,
+            display_size: None,
         }
     }
 
+    /// Return a new market order, with:
+    /// * `size` being the order size
+    /// * `side` being `Side::Bid` (buy) or `Side::Ask` (sell)
+    pub fn market<U>(size: U, side: Side) -> Self where U: Into<Tickable> {
+        Order::new(Tickable::Ticked(0), size, side).with_order_type(OrderType::Market)
+    }
+
+    /// Return a new market order whose size is expressed in the quote currency, e.g.
+    /// buying for 500 USDT on `BTCUSDT`, with `side` being `Side::Bid` (buy) or
+    /// `Side::Ask` (sell).
+    ///
+    /// # Note
+    /// Usable only on binance spot, as `quoteOrderQty`, and GDAX, as `funds`. Other
+    /// exchanges reject the order with `RestErrorKind::InvalidRequest`, as well as
+    /// orders changed to another order type than `OrderType::Market`.
+    pub fn market_quote(size: QuoteSize, side: Side) -> Self {
+        let mut order = Order::market(size.0, side);
+        order.quote_size = true;
+        order
+    }
+
     /// Set the order type.
     pub fn with_order_type(mut self, order_type: OrderType) -> Self {
         self.type_ = order_type;
         self
     }
 
+    /// Set the price at which a stop order is triggered: a buy stop is triggered once
+    /// the market rises to `trigger_price`, a sell stop once it falls to `trigger_price`.
+    /// Required by `OrderType::StopLimit` and `OrderType::StopMarket`, ignored by the
+    /// other order types.
+    pub fn with_trigger_price<T: Into<Tickable>>(mut self, trigger_price: T) -> Self {
+        self.trigger_price = Some(trigger_price.into());
+        self
+    }
+
+    /// Mark the order as post only, i.e. turn a `OrderType::Limit` order into a
+    /// `OrderType::LimitMaker` one, or the opposite if `post_only` is `false`. The other
+    /// order types are left unchanged. A post only order which would cross the book is
+    /// rejected with `OrderErrorKind::WouldTakeLiquidity`, so that the strategy can
+    /// quote again at a less aggressive price.
+    pub fn post_only(mut self, post_only: bool) -> Self {
+        self.type_ = match (self.type_, post_only) {
+            (OrderType::Limit, true) => OrderType::LimitMaker,
+            (OrderType::LimitMaker, false) => OrderType::Limit,
+            (type_, _) => type_,
+        };
+        self
+    }
+
     /// Time in force, see https://www.investopedia.com/terms/t/timeinforce.asp.
     pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
         self.time_in_force = time_in_force;
@@ -112,6 +366,48 @@ impl Order {
         self
     }
 
+    /// Turn the order into a trailing stop: the order is only sent to the book, at its
+    /// limit price, once the market has moved `trailing_delta` basis points (1 bp = 0.01%)
+    /// against the most favorable price reached since insertion. The order type is
+    /// ignored.
+    ///
+    /// # Note
+    /// Usable only on binance, other exchanges reject the order with
+    /// `RestErrorKind::InvalidRequest`. See `execution::trailing_stop` for emulating
+    /// trailing stops on these exchanges. On binance futures, only market orders can
+    /// trail, by a multiple of 10 bps between 10 and 500 bps, the trigger price being
+    /// the activation price of the stop if provided.
+    pub fn with_trailing_delta(mut self, trailing_delta: u64) -> Self {
+        self.trailing_delta = Some(trailing_delta);
+        self
+    }
+
+    /// Turn the order into an iceberg: only `display_size` is shown in the book at any
+    /// time, and the hidden size is displayed again as the visible part gets filled.
+    /// The display size must be positive, less than the order size, and must not split
+    /// the order into more than `Symbol::iceberg_parts` parts, otherwise the order is
+    /// rejected with `RestErrorKind::InvalidRequest` before being sent.
+    ///
+    /// # Note
+    /// Usable only with good-til-canceled limit orders on binance spot and HitBTC, other
+    /// exchanges reject the order with `RestErrorKind::InvalidRequest`.
+    pub fn with_display_size<T: Into<Tickable>>(mut self, display_size: T) -> Self {
+        self.display_size = Some(display_size.into());
+        self
+    }
+
+    /// Mark the order as reduce only: it can only decrease the size of the current
+    /// position, and is rejected or shrunk by the exchange otherwise.
+    ///
+    /// # Note
+    /// Usable only on derivatives markets, i.e. binance USD-M futures, Bybit linear
+    /// contracts, Deribit and Kraken Futures, other markets reject the order with
+    /// `RestErrorKind::InvalidRequest`.
+    pub fn with_reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
     /// Generate an id for identifying this order. When possible, the order id will
     /// be equal to `hint`, otherwise it is assured that all ids generated by a call to
     /// this method are distinct.
@@ -130,7 +426,8 @@ impl Order {
         &self.price
     }
 
-    /// Return the order size.
+    /// Return the order size, in the base currency unless it is expressed in the quote
+    /// currency, see `Order::quote_size`.
     pub fn size(&self) -> &Tickable {
         &self.size
     }
@@ -140,6 +437,11 @@ impl Order {
         self.type_
     }
 
+    /// Return `true` if the order is post only, see `Order::post_only`.
+    pub fn is_post_only(&self) -> bool {
+        self.type_ == OrderType::LimitMaker
+    }
+
     /// Return the chosen time in force.
     pub fn time_in_force(&self) -> TimeInForce {
         self.time_in_force
@@ -149,6 +451,112 @@ impl Order {
     pub fn time_window(&self) -> u64 {
         self.time_window
     }
+
+    /// Return the order size if it is expressed in the quote currency, see
+    /// `Order::market_quote`.
+    pub fn quote_size(&self) -> Option<QuoteSize> {
+        if self.quote_size {
+            Some(QuoteSize(self.size.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Return the trigger price if one was provided.
+    pub fn trigger_price(&self) -> Option<&Tickable> {
+        self.trigger_price.as_ref()
+    }
+
+    /// Return the display size if one was provided, see `Order::with_display_size`.
+    pub fn display_size(&self) -> Option<&Tickable> {
+        self.display_size.as_ref()
+    }
+
+    /// Return the trailing delta if one was provided, in basis points.
+    pub fn trailing_delta(&self) -> Option<u64> {
+        self.trailing_delta
+    }
+
+    /// Return `true` if the order was marked as reduce only.
+    pub fn reduce_only(&self) -> bool {
+        self.reduce_only
+    }
+
+    /// Return `true` if the display size, if any, complies with the iceberg rules of
+    /// `symbol`, see `Order::with_display_size`. Icebergs must be limit orders staying
+    /// in the book.
+    #[cfg_attr(not(any(feature = "binance", feature = "hitbtc")), allow(dead_code))]
+    crate fn valid_display_size(&self, symbol: Symbol) -> bool {
+        let display_size = match &self.display_size {
+            Some(display_size) => display_size.ticked(symbol.size_tick()),
+            None => return true,
+        };
+
+        let resting = (self.type_ == OrderType::Limit || self.type_ == OrderType::LimitMaker)
+            && self.time_in_force == TimeInForce::GoodTilCanceled
+            && self.trailing_delta.is_none();
+        if !resting {
+            return false;
+        }
+        let size = self.size.ticked(symbol.size_tick());
+
+        if display_size == 0 || display_size >= size {
+            return false;
+        }
+
+        // At most `max` parts of the display size must cover the whole size.
+        symbol.iceberg_parts()
+            .map(|max| display_size.saturating_mul(max) >= size)
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A pair of orders handled natively by the exchange, such that the fill of one order
+/// expires the other one, see `ApiClient::oco`: typically a take profit limit order
+/// along with a stop loss.
+///
+/// Both orders must have the same side and the same size. The limit order must be of
+/// type `OrderType::Limit` or `OrderType::LimitMaker`, and is sent as a limit maker
+/// order. The stop order must be of type `OrderType::StopLimit` or
+/// `OrderType::StopMarket`, with a trigger price.
+pub struct OneCancelsOther {
+    limit: Order,
+    stop: Order,
+    list_id: Option<String>,
+}
+
+impl OneCancelsOther {
+    /// Return a new `OneCancelsOther` made of the `limit` and the `stop` orders. The
+    /// time window is the one of the limit order.
+    pub fn new(limit: Order, stop: Order) -> Self {
+        OneCancelsOther {
+            limit,
+            stop,
+            list_id: None,
+        }
+    }
+
+    /// Generate an id for identifying the pair of orders, see `Order::with_order_id`.
+    pub fn with_list_id<C: ApiClient>(mut self, hint: &str) -> Self {
+        self.list_id = Some(C::new_order_id(hint));
+        self
+    }
+
+    /// Return the limit order.
+    pub fn limit(&self) -> &Order {
+        &self.limit
+    }
+
+    /// Return the stop order.
+    pub fn stop(&self) -> &Order {
+        &self.stop
+    }
+
+    /// Return the list id if one was provided.
+    pub fn list_id(&self) -> Option<&str> {
+        self.list_id.as_ref().map(|s| s.as_ref())
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
@@ -188,6 +596,35 @@ impl Cancel {
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An order replacing a previous order, e.g. for moving a quote: the previous order is
+/// canceled and the new one is inserted, see `ApiClient::replace`.
+pub struct Replace {
+    cancel: Cancel,
+    order: Order,
+}
+
+impl Replace {
+    /// Return a new `Replace`, canceling the order identified by `order_id` and
+    /// inserting `order` instead. The time window is the one of `order`.
+    pub fn new(order_id: String, order: Order) -> Self {
+        Replace {
+            cancel: Cancel::new(order_id).with_time_window(order.time_window),
+            order,
+        }
+    }
+
+    /// Return the cancel of the previous order.
+    pub fn cancel(&self) -> &Cancel {
+        &self.cancel
+    }
+
+    /// Return the new order.
+    pub fn order(&self) -> &Order {
+        &self.order
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// An acknowledgment that an order has been treated by the server.
 pub struct OrderAck {
@@ -195,10 +632,102 @@ pub struct OrderAck {
     pub order_id: String,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An acknowledgment that a `OneCancelsOther` has been treated by the server.
+pub struct OcoAck {
+    /// ID identifying the pair of orders.
+    pub list_id: String,
+
+    /// Acknowledgment of the limit order.
+    pub limit: OrderAck,
+
+    /// Acknowledgment of the stop order.
+    pub stop: OrderAck,
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// An acknowledgment that a cancel order has been treated by the server.
 pub struct CancelAck;
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// State of an order, as reported by `ApiClient::order_status`.
+pub enum OrderState {
+    /// The order is live on the book and was not filled yet.
+    New,
+
+    /// The order is live on the book and was partially filled.
+    PartiallyFilled,
+
+    /// The order was entirely filled.
+    Filled,
+
+    /// The order was canceled, possibly after being partially filled.
+    Canceled,
+
+    /// The order was rejected by the exchange.
+    Rejected,
+
+    /// The order expired, e.g. an immediate or cancel order which could not be
+    /// entirely filled.
+    Expired,
+}
+
+impl OrderState {
+    /// Return `true` if the order is still live on the book.
+    pub fn is_open(self) -> bool {
+        self == OrderState::New || self == OrderState::PartiallyFilled
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// The status of an order queried over REST, e.g. for reconciling after a disconnection
+/// of the user stream, see `ApiClient::order_status`.
+pub struct OrderStatus {
+    /// ID identifying the order.
+    pub order_id: String,
+
+    /// Current state of the order.
+    pub state: OrderState,
+
+    /// Size filled so far.
+    pub filled_size: TickUnit,
+
+    /// Average price of the fills, `None` if the order was not filled.
+    pub average_price: Option<TickUnit>,
+
+    /// Commission paid so far, in commission ticks, if reported by the exchange.
+    pub commission: Option<TickUnit>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A past fill of an order of the account, see `ApiClient::my_trades`.
+pub struct Fill {
+    /// ID identifying the trade on the exchange.
+    pub trade_id: String,
+
+    /// ID of the filled order, as reported by the exchange: the client order id on
+    /// HitBTC, but the id assigned by the exchange on binance and GDAX.
+    pub order_id: String,
+
+    /// Side of the filled order.
+    pub side: Side,
+
+    /// Price of the fill, in ticks.
+    pub price: TickUnit,
+
+    /// Size of the fill, in ticks.
+    pub size: TickUnit,
+
+    /// Fee paid (or rebate received if negative), unticked, in `fee_asset`.
+    pub fee: String,
+
+    /// Asset in which the fee was paid, if reported by the exchange.
+    pub fee_asset: Option<String>,
+
+    /// Whether the order was resting in the book, if reported by the exchange.
+    pub liquidity: Option<fees::Liquidity>,
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// A notification that some order has been updated, i.e. a trade crossed through this order.
 pub struct OrderUpdate {
@@ -242,6 +771,21 @@ pub struct Trade {
 pub struct OrderExpiration {
     /// Expired order.
     pub order_id: String,
+
+    /// Size which was filled before the order expired, if reported by the exchange.
+    pub filled_size: Option<TickUnit>,
+
+    /// Size which was left unfilled when the order expired, if reported by the exchange.
+    pub remaining_size: Option<TickUnit>,
+}
+
+impl OrderExpiration {
+    /// Return `Some(true)` if the order was partially filled before expiring, e.g. an
+    /// immediate or cancel order which only consumed part of the available liquidity,
+    /// or `None` if the exchange did not report the filled size.
+    pub fn is_partially_filled(&self) -> Option<bool> {
+        self.filled_size.map(|filled_size| filled_size > 0)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
@@ -250,7 +794,7 @@ pub struct OrderConfirmation {
     /// Unique order id.
     pub order_id: String,
 
-    /// Price at which the order was inserted.
+    /// Price at which the order was inserted, zero for market orders.
     pub price: TickUnit,
 
     /// Size at which the order was inserted.
@@ -260,6 +804,70 @@ pub struct OrderConfirmation {
     pub side: Side,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Operational status of an exchange.
+pub enum ExchangeStatus {
+    /// The exchange is operating normally.
+    Normal,
+
+    /// The exchange is up, but some of its components are experiencing issues.
+    Degraded,
+
+    /// The exchange is under (possibly scheduled) maintenance.
+    Maintenance,
+
+    /// The exchange is experiencing a major outage.
+    Outage,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Trading status of a symbol, as announced by the exchange.
+pub struct TradingStatus {
+    /// Status as reported by the exchange, e.g. `BREAK` on binance or `delisted` on gdax.
+    pub status: String,
+
+    /// Description of the status, if provided by the exchange.
+    pub message: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// State of an auction phase of a symbol, e.g. the opening call of a new listing,
+/// during which orders are collected without being matched.
+pub struct AuctionUpdate {
+    /// Phase as reported by the exchange, e.g. `collection` on gdax, or `None` once the
+    /// auction is over and continuous trading has started.
+    pub phase: Option<String>,
+
+    /// Indicative price at which the book would open.
+    pub indicative_price: Option<TickUnit>,
+
+    /// Size which would be matched at the indicative price.
+    pub indicative_size: Option<TickUnit>,
+}
+
+impl AuctionUpdate {
+    /// Return `true` if the auction is over.
+    pub fn is_over(&self) -> bool {
+        self.phase.is_none()
+    }
+
+    /// Return `true` if `order` complies with the constraints of the auction: only
+    /// plain limit orders, resting on the book until the auction uncrosses, are
+    /// accepted.
+    pub fn accepts(&self, order: &Order) -> bool {
+        let limit = match order.order_type() {
+            OrderType::Limit | OrderType::LimitMaker => true,
+            OrderType::Market | OrderType::StopLimit | OrderType::StopMarket => false,
+        };
+
+        self.is_over() || (
+            limit
+                && order.time_in_force() == TimeInForce::GoodTilCanceled
+                && order.trailing_delta().is_none()
+        )
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 /// A notification that some event happened.
 pub enum Notification {
@@ -277,6 +885,27 @@ pub enum Notification {
 
     /// An order has expired or was canceled.
     OrderExpiration(Timestamped<OrderExpiration>),
+
+    /// The operational status of the exchange has changed, see `status::poll`.
+    ExchangeStatus(Timestamped<ExchangeStatus>),
+
+    /// A position on the streamed contract has changed. A position which was closed
+    /// is reported with a size of zero.
+    PositionUpdate(Timestamped<derivatives::Position>),
+
+    /// A price index published by the exchange has changed.
+    IndexPrice(Timestamped<index::IndexPrice>),
+
+    /// The exchange halted trading on the streamed symbol: orders will be rejected
+    /// until trading resumes, see `risk::CircuitBreaker`.
+    TradingHalted(Timestamped<TradingStatus>),
+
+    /// The exchange resumed trading on the streamed symbol after a halt.
+    TradingResumed(Timestamped<TradingStatus>),
+
+    /// The streamed symbol is in an auction phase, or has just left it. Orders must
+    /// comply with `AuctionUpdate::accepts` until the auction is over.
+    AuctionUpdate(Timestamped<AuctionUpdate>),
 }
 
 bitflags! {
@@ -291,8 +920,15 @@ bitflags! {
         /// Forward order confirmations and updates.
         const ORDERS = 0b0100;
 
+        /// Forward position updates, on exchanges trading derivatives.
+        const POSITIONS = 0b1000;
+
+        /// Forward index prices, on exchanges publishing them.
+        const INDEX_PRICES = 0b1_0000;
+
         /// Forward all notifications.
-        const ALL = Self::ORDER_BOOK.bits | Self::TRADES.bits | Self::ORDERS.bits;
+        const ALL = Self::ORDER_BOOK.bits | Self::TRADES.bits | Self::ORDERS.bits
+            | Self::POSITIONS.bits | Self::INDEX_PRICES.bits;
     }
 }
 
@@ -303,7 +939,7 @@ pub trait GenerateOrderId {
     fn new_order_id(hint: &str) -> String;
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// Account balance for one asset.
 pub struct Balance {
     /// Available amount, unticked.
@@ -316,12 +952,45 @@ pub struct Balance {
 /// A wrapper over a (currency name) => (balance) `HashMap`.
 pub type Balances = HashMap<String, Balance>;
 
+/// Emulate `ApiClient::replace` with a cancel followed by a new order.
+crate fn cancel_then_order<C: ApiClient + ?Sized>(client: &C, replace: WithSymbol<&Replace>)
+    -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::ReplaceError> + Send + 'static>
+{
+    let symbol = replace.symbol();
+
+    // Requests are only sent when polled, i.e. the order is sent once the cancel has
+    // succeeded.
+    let cancel = client.cancel(replace.cancel().with_symbol(symbol));
+    let order = client.order(replace.order().with_symbol(symbol));
+    let fut = cancel.map_err(errors::ReplaceError::from)
+        .and_then(move |_| order.map_err(errors::ReplaceError::from));
+    Box::new(fut)
+}
+
 /// A trait implemented by clients of various exchanges API.
+///
+/// Clients can be created without a key pair, in which case the public data
+/// (symbols, order book, trades, exchange status) is still available, and the private
+/// requests (`order`, `cancel`, `balances`) fail with `RestErrorKind::Unauthorized`.
 pub trait ApiClient: GenerateOrderId {
     /// Type returned by the `stream` implementor, used for continuously receiving
     /// notifications.
     type Stream: Stream<Item = Notification, Error = ()> + Send + 'static;
 
+    /// Return the exchange this client is connected to.
+    ///
+    /// # Note
+    /// Defaults to `ExchangeId::Other`, for clients implemented outside of this crate.
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Other
+    }
+
+    /// Return the id of `symbol` on this exchange, for keying data coming from
+    /// several exchanges.
+    fn symbol_id(&self, symbol: &Symbol) -> SymbolId {
+        SymbolId::new(self.exchange_id(), symbol)
+    }
+
     /// Find a symbol by name.
     fn find_symbol(&self, symbol: &str) -> Option<Symbol>;
 
@@ -337,6 +1006,117 @@ pub trait ApiClient: GenerateOrderId {
     fn order(&self, order: WithSymbol<&Order>)
         -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::OrderError> + Send + 'static>;
 
+    /// Return `true` if the exchange handles `OneCancelsOther` orders natively, i.e.
+    /// if `oco` is supported. Otherwise, see `execution::oco` for emulating them.
+    fn supports_oco(&self) -> bool {
+        false
+    }
+
+    /// Send a pair of orders to the exchange, such that the fill of one order expires
+    /// the other one. The stream then notifies the expiration of the other order as
+    /// soon as one of them is (even partially) filled.
+    ///
+    /// # Note
+    /// Only supported on binance spot, see `supports_oco`. Defaults to rejecting the
+    /// request with `RestErrorKind::InvalidRequest`.
+    fn oco(&self, _: WithSymbol<&OneCancelsOther>)
+        -> Box<dyn Future<Item = Timestamped<OcoAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        warn!("called `oco` on an exchange which does not support OCO orders");
+        Box::new(futures::future::err(
+            errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
+
+    /// Return `true` if the exchange replaces orders atomically, i.e. if `replace` does
+    /// not resort to a cancel followed by a new order.
+    fn supports_atomic_replace(&self) -> bool {
+        false
+    }
+
+    /// Cancel an order and insert a new one in its place. The new order is only sent
+    /// once the previous order is canceled: if the cancel fails, e.g. because the
+    /// previous order was filled in the meantime, the error is of kind
+    /// `ReplaceErrorKind::Cancel`.
+    ///
+    /// # Note
+    /// Atomic on binance spot and HitBTC, see `supports_atomic_replace`. On HitBTC, only
+    /// the price and the size are amended: the side and the type of the previous order
+    /// are kept. Defaults to a cancel followed by a new order, which relies on the
+    /// requests being sent only once polled: clients acting right away must override it.
+    fn replace(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::ReplaceError> + Send + 'static>
+    {
+        cancel_then_order(self, replace)
+    }
+
+    /// Retrieve the status of the order of `symbol` identified by `order_id`, timestamped
+    /// with its last update as reported by the exchange.
+    ///
+    /// # Note
+    /// Only supported on binance, GDAX and HitBTC. GDAX does not retain the orders
+    /// canceled without any fill, and is the only one to report the commission. Defaults
+    /// to rejecting the request with `RestErrorKind::InvalidRequest`.
+    fn order_status(&self, _symbol: Symbol, _order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = errors::Error> + Send + 'static>
+    {
+        warn!("called `order_status` on an exchange which does not support it");
+        Box::new(futures::future::err(
+            errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
+
+    /// Retrieve the fills of the orders of the account on `symbol` which happened at or
+    /// after `since`, oldest first and timestamped with the time of the fill.
+    ///
+    /// # Note
+    /// Exchanges limit the number of fills returned at once: a longer history is
+    /// downloaded by calling this method again with `since` set right after the last
+    /// timestamp received, until an empty vector is returned. GDAX only returns its
+    /// page of most recent fills.
+    ///
+    /// Only supported on binance, GDAX and HitBTC. Defaults to rejecting the request
+    /// with `RestErrorKind::InvalidRequest`.
+    fn my_trades(&self, _symbol: Symbol, _since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = errors::Error> + Send + 'static>
+    {
+        warn!("called `my_trades` on an exchange which does not support it");
+        Box::new(futures::future::err(
+            errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
+
+    /// Retrieve the `limit` most recent trades of `symbol`, oldest first. Exchanges
+    /// may return fewer trades than requested.
+    ///
+    /// # Note
+    /// Only supported on binance, GDAX and HitBTC. Defaults to rejecting the request
+    /// with `RestErrorKind::InvalidRequest`.
+    fn recent_trades(&self, _symbol: Symbol, _limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = errors::Error> + Send + 'static>
+    {
+        warn!("called `recent_trades` on an exchange which does not support it");
+        Box::new(futures::future::err(
+            errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
+
+    /// Retrieve the `limit` most recent bars of `symbol` lasting `interval` milliseconds,
+    /// oldest first and indexed by the start of their interval. The last bar is usually
+    /// still in progress. Each exchange only supports a fixed set of intervals.
+    ///
+    /// # Note
+    /// Only supported on binance, GDAX and HitBTC. Defaults to rejecting the request
+    /// with `RestErrorKind::InvalidRequest`.
+    fn recent_bars(&self, _symbol: Symbol, _interval: Timestamp, _limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = errors::Error> + Send + 'static>
+    {
+        warn!("called `recent_bars` on an exchange which does not support it");
+        Box::new(futures::future::err(
+            errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
+
     /// Send a cancel order to the exchange.
     ///
     /// # Note
@@ -356,4 +1136,18 @@ pub trait ApiClient: GenerateOrderId {
     /// Retrieve balances for this account.
     fn balances(&self)
         -> Box<dyn Future<Item = Balances, Error = errors::Error> + Send + 'static>;
+
+    /// Retrieve the current operational status of the exchange.
+    ///
+    /// # Note
+    /// Defaults to rejecting the request with `RestErrorKind::InvalidRequest`, for
+    /// exchanges which do not publish their status.
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = errors::Error> + Send + 'static>
+    {
+        warn!("called `system_status` on an exchange which does not support it");
+        Box::new(futures::future::err(
+            errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+        ))
+    }
 }
@@ -0,0 +1,207 @@
+//! Implementation of `ApiClient` for the OKX (v5) API.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::pkey::{PKey, Private};
+use chashmap::CHashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use log::debug;
+use crate::api::{
+    self,
+    Params,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+/// Host of the WebSocket API of the demo trading environment, see `Params::okx_demo`.
+const DEMO_STREAMING_HOST: &str = "wspap.okx.com:8443";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An OKX key pair: api key + secret key, along with a pass phrase.
+pub struct KeyPair {
+    api_key: String,
+    secret_key: String,
+    pass_phrase: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair along with the associated pass phrase.
+    pub fn new(api_key: String, secret_key: String, pass_phrase: String) -> Self {
+        KeyPair {
+            api_key,
+            secret_key,
+            pass_phrase,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    api_key: String,
+    secret_key: Arc<PKey<Private>>,
+    pass_phrase: String,
+}
+
+/// An OKX API client, trading spot pairs.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+
+    /// server order id => client order id, for orders inserted without a client order id
+    order_ids: Arc<CHashMap<String, String>>,
+
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+
+    /// Whether `params` point to the demo trading environment, whose REST requests
+    /// must be marked as simulated.
+    demo: bool,
+}
+
+impl Params {
+    /// Return the parameters of the OKX demo trading environment, which uses the
+    /// production REST API with the demo trading keys: the client then marks its requests
+    /// as simulated.
+    pub fn okx_demo() -> Self {
+        Params {
+            streaming_endpoint: format!("wss://{}/ws/v5", DEMO_STREAMING_HOST),
+            rest_endpoint: "https://www.okx.com".to_owned(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new OKX API client with given `params`. The streaming endpoint is the
+    /// base address of the WebSocket API, e.g. `wss://ws.okx.com:8443/ws/v5`. If
+    /// `key_pair` is not `None`, this will enable performing requests to the REST API
+    /// and will forward the user data stream. Demo trading is enabled by the parameters
+    /// returned by `Params::okx_demo`.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from OKX.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        let keys = match key_pair {
+            Some(pair) => {
+                let secret_key = PKey::hmac(pair.secret_key.as_bytes())?;
+
+                Some(Keys {
+                    api_key: pair.api_key,
+                    secret_key: Arc::new(secret_key),
+                    pass_phrase: pair.pass_phrase,
+                })
+            },
+            None => None,
+        };
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let demo = params.streaming_endpoint.contains(DEMO_STREAMING_HOST);
+        let mut client = Client {
+            params,
+            keys,
+            order_ids: Arc::new(CHashMap::new()),
+            symbols: HashMap::new(),
+            http_client,
+            demo,
+        };
+
+        use tokio::runtime::current_thread;
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Okx
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// OKX accepts alphanumeric client order ids of at most 32 characters.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 32 &&
+            hint.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
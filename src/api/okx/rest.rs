@@ -0,0 +1,441 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use hyper::{Method, Request};
+use futures::prelude::*;
+use failure::Fail;
+use log::{warn, debug, error};
+use std::collections::HashMap;
+use serde_json::Value;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    GenerateOrderId,
+    TimeInForce,
+    OrderType,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Balance,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+use crate::api::okx::Client;
+use crate::api::okx::errors::RestError;
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct OkxStatus {
+    code: String,
+    msg: String,
+    data: Option<Vec<Value>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxResponse<T> {
+    data: Vec<T>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct OkxOrder<'a> {
+    instId: &'a str,
+    tdMode: &'a str,
+    clOrdId: &'a str,
+    side: &'a str,
+    ordType: &'a str,
+    px: &'a str,
+    sz: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct OkxCancel<'a> {
+    instId: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ordId: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clOrdId: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct OkxOrderAck<'a> {
+    ordId: &'a str,
+    ts: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxTime<'a> {
+    ts: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxAccount<'a> {
+    #[serde(borrow)]
+    details: Vec<OkxBalance<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct OkxBalance<'a> {
+    ccy: &'a str,
+    availBal: &'a str,
+    frozenBal: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxMaintenance<'a> {
+    state: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct OkxInstrument<'a> {
+    instId: &'a str,
+    tickSz: &'a str,
+    lotSz: &'a str,
+    state: &'a str,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "sell",
+            Side::Bid => "buy",
+        }
+    }
+}
+
+/// Return the error code and message of a rejected request. Order requests carry an
+/// additional code per order (`sCode`), which is more specific.
+fn okx_error(status: OkxStatus) -> Option<(i64, String)> {
+    let item = status.data.as_ref().and_then(|data| data.first());
+    let specific = item.and_then(|item| {
+        let code = item.get("sCode")?.as_str()?.parse::<i64>().ok()?;
+        let msg = item.get("sMsg").and_then(Value::as_str).unwrap_or("");
+        if code != 0 { Some((code, msg.to_owned())) } else { None }
+    });
+
+    specific.or_else(|| Some((status.code.parse().ok()?, status.msg)))
+}
+
+impl Client {
+    /// Signed requests carry the base64 HMAC-SHA256 of the timestamp, the method, the
+    /// request path including the query string, and the body.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        query: QueryString,
+        body: String,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        let query = query.into_string();
+        let path = if query.is_empty() {
+            format!("/{}", endpoint)
+        } else {
+            format!("/{}?{}", endpoint, query)
+        };
+        let address = format!("{}{}", self.params.rest_endpoint, path);
+
+        let mut request = Request::builder();
+
+        if let Some(keys) = self.keys.as_ref() {
+            let timestamp = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+            let mut signer = Signer::new(MessageDigest::sha256(), &keys.secret_key).unwrap();
+            let what = format!("{}{}{}{}", timestamp, method, path, body);
+            signer.update(what.as_bytes()).unwrap();
+            let signature = base64::encode(&signer.sign_to_vec().unwrap());
+
+            request.header("OK-ACCESS-KEY", keys.api_key.as_bytes())
+                .header("OK-ACCESS-SIGN", signature.as_bytes())
+                .header("OK-ACCESS-TIMESTAMP", timestamp.as_bytes())
+                .header("OK-ACCESS-PASSPHRASE", keys.pass_phrase.as_bytes());
+        }
+
+        if self.demo {
+            request.header("x-simulated-trading", &b"1"[..]);
+        }
+
+        request.method(method)
+            .uri(&address)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..]);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.into()).unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            let okx_status: Option<OkxStatus> = serde_json::from_slice(&body).ok();
+            let failed = match &okx_status {
+                Some(okx_status) => okx_status.code != "0",
+                None => true,
+            };
+
+            if status != hyper::StatusCode::OK || failed {
+                let error = RestError::from_okx_error(status, okx_status.and_then(okx_error));
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by OKX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by OKX");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.trailing_delta.is_some() {
+            warn!("called `order` with a trailing delta, which is only supported on binance");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.display_size.is_some() {
+            warn!("called `order` with a display size, which is only supported on binance and HitBTC");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only {
+            warn!("called `order` with reduce only, which is only supported on derivatives");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let ord_type = match (order.type_, order.time_in_force) {
+            (OrderType::LimitMaker, _) => "post_only",
+            (_, TimeInForce::GoodTilCanceled) => "limit",
+            (_, TimeInForce::ImmediateOrCancel) => "ioc",
+            (_, TimeInForce::FillOrKilll) => "fok",
+            (_, TimeInForce::GoodTilDate(_)) => {
+                warn!("called `order` with `GoodTilDate`, which is not supported by OKX");
+                return Box::new(futures::future::err(
+                    api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+                ));
+            }
+        };
+
+        // Always send a client order id, so that orders can be canceled by client
+        // order id.
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let symbol = order.symbol();
+        let size = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+
+        let order_body = OkxOrder {
+            instId: symbol.name(),
+            tdMode: "cash",
+            clOrdId: &order_id,
+            side: order.side.as_str(),
+            ordType: ord_type,
+            px: price.borrow(),
+            sz: size.borrow(),
+        };
+
+        let body = serde_json::to_string(&order_body).expect("invalid json");
+
+        let order_ids = self.order_ids.clone();
+
+        let query = QueryString::new();
+        let endpoint = "api/v5/trade/order";
+        let fut = self.request(endpoint, Method::POST, query, body).and_then(move |body| {
+            let ack: OkxResponse<OkxOrderAck<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let ack = match ack.data.first() {
+                Some(ack) => *ack,
+                None => {
+                    error!("order response is missing the order id");
+                    return Err(api::errors::ApiError::RestError(
+                        api::errors::RestErrorKind::UnknownStatus.into()
+                    ));
+                }
+            };
+            order_ids.insert(ack.ordId.to_owned(), order_id.clone());
+            debug!("insert order id {} (from REST)", order_id);
+
+            let ack_timestamp = ack.ts.and_then(|ts| ts.parse().ok());
+            let ack = OrderAck {
+                order_id,
+            };
+            Ok(match ack_timestamp {
+                Some(timestamp) => ack.with_timestamp(timestamp),
+                None => ack.timestamped(),
+            })
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        // Orders inserted without a client order id are known by their server order id.
+        let server_order_id = self.order_ids.contains_key(&cancel.order_id);
+
+        let symbol = cancel.symbol();
+        let cancel_body = OkxCancel {
+            instId: symbol.name(),
+            ordId: if server_order_id { Some(&cancel.order_id) } else { None },
+            clOrdId: if server_order_id { None } else { Some(&cancel.order_id) },
+        };
+
+        let body = serde_json::to_string(&cancel_body).expect("invalid json");
+
+        let query = QueryString::new();
+        self.request("api/v5/trade/cancel-order", Method::POST, query, body).and_then(|body| {
+            let ack: OkxResponse<OkxOrderAck<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let ack_timestamp = ack.data.first()
+                .and_then(|ack| ack.ts)
+                .and_then(|ts| ts.parse().ok());
+            Ok(match ack_timestamp {
+                Some(timestamp) => CancelAck.with_timestamp(timestamp),
+                None => CancelAck.timestamped(),
+            })
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        self.request("api/v5/public/time", Method::GET, query, String::new()).and_then(|body| {
+            let time: OkxResponse<OkxTime<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let timestamp = time.data.first()
+                .and_then(|time| time.ts.parse().ok())
+                .unwrap_or(0);
+            Ok(().with_timestamp(timestamp))
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = Balances, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        let endpoint = "api/v5/account/balance";
+        self.request(endpoint, Method::GET, query, String::new()).and_then(|body| {
+            let accounts: OkxResponse<OkxAccount<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut balances = Balances::new();
+            for balance in accounts.data.into_iter().flat_map(|account| account.details) {
+                balances.insert(balance.ccy.to_owned(), Balance {
+                    free: balance.availBal.to_owned(),
+                    locked: balance.frozenBal.to_owned(),
+                });
+            }
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        let query = QueryString::new();
+
+        let endpoint = "api/v5/system/status";
+        self.request(endpoint, Method::GET, query, String::new()).and_then(|body| {
+            let maintenances: OkxResponse<OkxMaintenance<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let ongoing = maintenances.data.iter().any(|m| m.state == "ongoing");
+            let status = if ongoing {
+                ExchangeStatus::Maintenance
+            } else {
+                ExchangeStatus::Normal
+            };
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("instType", "SPOT");
+
+        let endpoint = "api/v5/public/instruments";
+        self.request(endpoint, Method::GET, query, String::new()).and_then(|body| {
+            let instruments: OkxResponse<OkxInstrument<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            for instrument in instruments.data {
+                if instrument.state != "live" {
+                    continue;
+                }
+
+                let (price_tick, size_tick) = match (
+                    Tick::tick_size(instrument.tickSz),
+                    Tick::tick_size(instrument.lotSz)
+                ) {
+                    (Some(price_tick), Some(size_tick)) => (price_tick, size_tick),
+                    _ => {
+                        error!("cannot read ticks for symbol `{}`", instrument.instId);
+                        continue;
+                    }
+                };
+
+                if let Some(symbol) = Symbol::new(instrument.instId, price_tick, size_tick) {
+                    symbols.insert(symbol.name().to_lowercase(), symbol);
+                } else {
+                    error!("symbol name too long: `{}`", instrument.instId);
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
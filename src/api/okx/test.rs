@@ -0,0 +1,68 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::okx::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTC-USDT", Tick::new(10), Tick::new(1)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[&str]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame, &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+// The checksum of `"3366.1:7:3366.8:9:3366:6:3368:8"`.
+const SNAPSHOT: &str = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot",
+    "data":[{"asks":[["3366.8","9","0","3"],["3368","8","0","4"]],
+    "bids":[["3366.1","7","0","3"],["3366","6","0","4"]],
+    "ts":"1597026383085","checksum":-1881014294,"prevSeqId":-1,"seqId":100}]}"#;
+
+#[test]
+fn book_snapshot_and_update() {
+    let subscribed = r#"{"event":"subscribe","arg":{"channel":"books","instId":"BTC-USDT"},
+        "connId":"a4d3ae55"}"#;
+
+    // The checksum of `"3366.1:8:3368:8:3366:6"`.
+    let update = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update",
+        "data":[{"asks":[["3366.8","0","0","0"]],"bids":[["3366.1","8","0","4"]],
+        "ts":"1597026383185","checksum":-1135441092,"prevSeqId":100,"seqId":101}]}"#;
+
+    let notifs = parse(&[subscribed, SNAPSHOT, update]);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(33668, 9, Side::Ask).with_timestamp(1_597_026_383_085),
+            LimitUpdate::new(33680, 8, Side::Ask).with_timestamp(1_597_026_383_085),
+            LimitUpdate::new(33661, 7, Side::Bid).with_timestamp(1_597_026_383_085),
+            LimitUpdate::new(33660, 6, Side::Bid).with_timestamp(1_597_026_383_085),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(33668, 0, Side::Ask).with_timestamp(1_597_026_383_185),
+            LimitUpdate::new(33661, 8, Side::Bid).with_timestamp(1_597_026_383_185),
+        ]),
+    ]);
+}
+
+#[test]
+#[should_panic(expected = "desynchronized order book")]
+fn book_sequence_gap() {
+    let update = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update",
+        "data":[{"asks":[],"bids":[["3366.1","8","0","4"]],
+        "ts":"1597026383185","checksum":-1135441092,"prevSeqId":101,"seqId":102}]}"#;
+    parse(&[SNAPSHOT, update]);
+}
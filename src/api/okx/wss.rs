@@ -0,0 +1,578 @@
+use openssl::{sign::Signer, hash::MessageDigest};
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use chashmap::CHashMap;
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::okx::{Client, Keys};
+
+/// Number of levels per side covered by the order book checksum.
+const CHECKSUM_DEPTH: usize = 25;
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let public_endpoint = format!("{}/public", self.params.streaming_endpoint);
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+
+        if let Some(keys) = self.keys.clone() {
+            let private_endpoint = format!("{}/private", self.params.streaming_endpoint);
+            let snd = snd.clone();
+            let order_ids = self.order_ids.clone();
+            let public_control = control.clone();
+
+            // The flags of the private connection are those of the public one,
+            // see `PrivateHandlerImpl::control`.
+            let private_control = control.linked();
+
+            thread::spawn(move || {
+                debug!("initiating WebSocket connection at {}", private_endpoint);
+
+                if let Err(err) = ws::connect(private_endpoint, |out| {
+                    wss::Handler::new(
+                        out,
+                        snd.clone(),
+                        private_control.clone(),
+                        wss::KeepAlive::True,
+                        PrivateHandlerImpl {
+                            symbol,
+                            keys: keys.clone(),
+                            out: None,
+                            control: public_control.clone(),
+                            order_ids: order_ids.clone(),
+                        }
+                    )
+                })
+                {
+                    error!("WebSocket connection terminated with error: `{}`", err);
+                }
+            });
+        }
+
+        let handler_control = control.clone();
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", public_endpoint);
+
+            if let Err(err) = ws::connect(public_endpoint, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        book: Book::default(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// Local copy of the order book, keeping the levels as formatted by OKX since the
+/// checksum is computed over these strings.
+struct Book {
+    /// price => (price, size)
+    asks: BTreeMap<TickUnit, (String, String)>,
+
+    /// price => (price, size)
+    bids: BTreeMap<TickUnit, (String, String)>,
+
+    /// Sequence number of the last message applied to the book.
+    seq_id: Option<i64>,
+}
+
+impl Book {
+    /// The checksum covers the top levels of the book, bids and asks being interleaved,
+    /// each level being represented by `price:size`, all of these joined by `:`.
+    fn checksum(&self) -> i32 {
+        let mut bids = self.bids.values().rev().take(CHECKSUM_DEPTH);
+        let mut asks = self.asks.values().take(CHECKSUM_DEPTH);
+
+        let mut levels = Vec::new();
+        for _ in 0..CHECKSUM_DEPTH {
+            if let Some((price, size)) = bids.next() {
+                levels.push(format!("{}:{}", price, size));
+            }
+            if let Some((price, size)) = asks.next() {
+                levels.push(format!("{}:{}", price, size));
+            }
+        }
+        wss::crc32(levels.join(":").as_bytes()) as i32
+    }
+}
+
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    book: Book,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct OkxRequest<'a, T> {
+    op: &'a str,
+    args: Vec<T>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+struct OkxChannel<'a> {
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instType: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instId: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[allow(non_snake_case)]
+struct OkxLogin<'a> {
+    apiKey: &'a str,
+    passphrase: &'a str,
+    timestamp: String,
+    sign: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxEvent<'a> {
+    event: Option<&'a str>,
+    code: Option<&'a str>,
+    msg: Option<String>,
+    #[serde(borrow)]
+    arg: Option<OkxChannel<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxMessage<'a, T> {
+    action: Option<&'a str>,
+    data: Vec<T>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct OkxBook<'a> {
+    /// `[price, size, deprecated, number of orders]`
+    #[serde(borrow)]
+    asks: Vec<(&'a str, &'a str, &'a str, &'a str)>,
+    #[serde(borrow)]
+    bids: Vec<(&'a str, &'a str, &'a str, &'a str)>,
+    ts: &'a str,
+    checksum: Option<i32>,
+    prevSeqId: Option<i64>,
+    seqId: Option<i64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct OkxTrade<'a> {
+    px: &'a str,
+    sz: &'a str,
+    side: &'a str,
+    ts: &'a str,
+}
+
+fn channel<'a>(channel: &'a str, symbol: &'a Symbol) -> OkxChannel<'a> {
+    OkxChannel {
+        channel,
+        instType: None,
+        instId: Some(symbol.name()),
+    }
+}
+
+fn send_request<T>(op: &str, args: Vec<T>, out: &ws::Sender) -> ws::Result<()>
+    where T: serde::Serialize
+{
+    match serde_json::to_string(&OkxRequest { op, args }) {
+        Ok(value) => out.send(value),
+        Err(err) => {
+            panic!("failed to serialize `OkxRequest`: `{}`", err);
+        }
+    }
+}
+
+/// Check the response to a request sent by the client. Return `Ok(None)` for such
+/// responses, `Ok(Some(channel))` for data messages.
+fn check_event<'a>(event: &OkxEvent<'a>) -> Result<Option<&'a str>, failure::Error> {
+    if let Some(name) = event.event {
+        if name == "error" || event.code.map(|code| code != "0").unwrap_or(false) {
+            bail!("{}: {:?} (code = {:?})", name, event.msg, event.code);
+        }
+        return Ok(None);
+    }
+    Ok(event.arg.map(|arg| arg.channel))
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            book: Book::default(),
+        }
+    }
+
+    fn apply_level(&mut self, level: (&str, &str), side: Side)
+        -> Result<LimitUpdate, failure::Error>
+    {
+        let update = LimitUpdate {
+            side,
+            price: self.symbol.price_tick().ticked(level.0)?,
+            size: self.symbol.size_tick().ticked(level.1)?,
+        };
+
+        let levels = match side {
+            Side::Ask => &mut self.book.asks,
+            Side::Bid => &mut self.book.bids,
+        };
+        if update.size == 0 {
+            levels.remove(&update.price);
+        } else {
+            levels.insert(update.price, (level.0.to_owned(), level.1.to_owned()));
+        }
+        Ok(update)
+    }
+
+    fn parse_book(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let books: OkxMessage<'_, OkxBook<'_>> = serde_json::from_str(json)?;
+
+        let mut updates = Vec::new();
+
+        // A snapshot is sent after subscribing, and replaces the whole book.
+        if books.action == Some("snapshot") {
+            let timestamp = books.data.first()
+                .and_then(|book| book.ts.parse::<Timestamp>().ok())
+                .unwrap_or_else(timestamp_ms);
+            let asks = self.book.asks.keys()
+                .map(|price| LimitUpdate::new(*price, 0, Side::Ask));
+            let bids = self.book.bids.keys()
+                .map(|price| LimitUpdate::new(*price, 0, Side::Bid));
+            updates.extend(asks.chain(bids).map(|update| update.with_timestamp(timestamp)));
+            self.book = Book::default();
+        }
+
+        for book in books.data {
+            if let (Some(prev_seq_id), Some(seq_id)) = (book.prevSeqId, self.book.seq_id) {
+                if prev_seq_id != seq_id {
+                    panic!("desynchronized order book");
+                }
+            }
+            if book.seqId.is_some() {
+                self.book.seq_id = book.seqId;
+            }
+
+            let timestamp = book.ts.parse::<Timestamp>().unwrap_or_else(|_| timestamp_ms());
+            for level in &book.asks {
+                let update = self.apply_level((level.0, level.1), Side::Ask)?;
+                updates.push(update.with_timestamp(timestamp));
+            }
+            for level in &book.bids {
+                let update = self.apply_level((level.0, level.1), Side::Bid)?;
+                updates.push(update.with_timestamp(timestamp));
+            }
+
+            if let Some(checksum) = book.checksum {
+                if checksum != self.book.checksum() {
+                    panic!("desynchronized order book");
+                }
+            }
+        }
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trades(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trades: OkxMessage<'_, OkxTrade<'_>> = serde_json::from_str(json)?;
+
+        for trade in trades.data {
+            // The side is the one of the taker.
+            let maker_side = match trade.side {
+                "buy" => Side::Ask,
+                "sell" => Side::Bid,
+                other => bail!("wrong side: `{}`", other),
+            };
+
+            let trade = Notification::Trade(Trade {
+                price: self.symbol.price_tick().ticked(trade.px)?,
+                size: self.symbol.size_tick().ticked(trade.sz)?,
+                maker_side,
+            }.with_timestamp(trade.ts.parse::<Timestamp>()?));
+
+            out.unbounded_send(trade).unwrap();
+        }
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: OkxEvent<'_> = serde_json::from_str(json)?;
+
+        match check_event(&event)? {
+            Some("books") if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_book(json, out)?;
+            }
+
+            Some("trades") if self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_trades(json, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        let mut channels = Vec::new();
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            channels.push(channel("books", &self.symbol));
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            channels.push(channel("trades", &self.symbol));
+        }
+
+        if channels.is_empty() {
+            return Ok(());
+        }
+        send_request("subscribe", channels, out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+        let book = channel("books", &self.symbol);
+        let trades = channel("trades", &self.symbol);
+
+        // Orders are streamed through the private connection, where `ORDERS` only
+        // acts as a filter.
+        match (
+            previous.contains(NotificationFlags::ORDER_BOOK),
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        ) {
+            // A new snapshot will be sent.
+            (false, true) => {
+                self.book = Book::default();
+                send_request("subscribe", vec![book], out)?;
+            }
+            (true, false) => send_request("unsubscribe", vec![book], out)?,
+            _ => (),
+        }
+
+        match (
+            previous.contains(NotificationFlags::TRADES),
+            flags.contains(NotificationFlags::TRADES)
+        ) {
+            (false, true) => send_request("subscribe", vec![trades], out)?,
+            (true, false) => send_request("unsubscribe", vec![trades], out)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+struct PrivateHandlerImpl {
+    symbol: Symbol,
+    keys: Keys,
+
+    /// Needed for subscribing once logged in.
+    out: Option<ws::Sender>,
+
+    /// Control of the public connection, which holds the flags of the stream.
+    control: Arc<wss::Control>,
+
+    /// server order id => client order id (shared with `Client`)
+    order_ids: Arc<CHashMap<String, String>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct OkxOrder<'a> {
+    instId: &'a str,
+    ordId: &'a str,
+    clOrdId: &'a str,
+    side: &'a str,
+    state: &'a str,
+    px: &'a str,
+    sz: &'a str,
+    accFillSz: &'a str,
+    fillSz: &'a str,
+    fillPx: &'a str,
+    tradeId: &'a str,
+    uTime: &'a str,
+}
+
+impl PrivateHandlerImpl {
+    fn convert_okx_side(&self, side: &str) -> Result<Side, failure::Error> {
+        let side = match side {
+            "buy" => Side::Bid,
+            "sell" => Side::Ask,
+            other => bail!("wrong side: `{}`", other),
+        };
+        Ok(side)
+    }
+
+    /// Return the order id specified by the user, which defaults to the server order id
+    /// in case it was left unspecified.
+    fn order_id(&self, server_order_id: &str, client_order_id: &str) -> String {
+        let order_id = if client_order_id.is_empty() {
+            self.order_ids.get(server_order_id)
+                .map(|order_id| order_id.clone())
+                .unwrap_or_else(|| server_order_id.to_owned())
+        } else {
+            client_order_id.to_owned()
+        };
+
+        // Don't forget to update the concurrent map `server order id => client order id`
+        // in case the WebSocket notif arrives before the HTTP response
+        if !self.order_ids.contains_key(server_order_id) {
+            self.order_ids.insert(server_order_id.to_owned(), order_id.clone());
+            debug!("insert order id {} (from WSS)", order_id);
+        }
+        order_id
+    }
+
+    fn parse_orders(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let orders: OkxMessage<'_, OkxOrder<'_>> = serde_json::from_str(json)?;
+
+        for order in orders.data {
+            if order.instId != self.symbol.name() {
+                continue;
+            }
+
+            let order_id = self.order_id(order.ordId, order.clOrdId);
+            let timestamp = order.uTime.parse::<Timestamp>()?;
+            let size = self.symbol.size_tick().ticked(order.sz)?;
+            let filled_size = self.symbol.size_tick().ticked(order.accFillSz)?;
+
+            // Each fill is reported along with the resulting state of the order.
+            if !order.tradeId.is_empty() {
+                let update = OrderUpdate {
+                    order_id: order_id.clone(),
+                    consumed_size: self.symbol.size_tick().ticked(order.fillSz)?,
+                    remaining_size: size.saturating_sub(filled_size),
+                    consumed_price: self.symbol.price_tick().ticked(order.fillPx)?,
+                    commission: 0,
+                }.with_timestamp(timestamp);
+                out.unbounded_send(Notification::OrderUpdate(update)).unwrap();
+                continue;
+            }
+
+            match order.state {
+                "live" => {
+                    let order = OrderConfirmation {
+                        size,
+                        price: self.symbol.price_tick().ticked(order.px)?,
+                        side: self.convert_okx_side(order.side)?,
+                        order_id,
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderConfirmation(order)).unwrap();
+                }
+
+                // Post only orders which would take liquidity are canceled as well.
+                "canceled" | "mmp_canceled" => {
+                    let expiration = OrderExpiration {
+                        order_id,
+                        filled_size: Some(filled_size),
+                        remaining_size: Some(size.saturating_sub(filled_size)),
+                    }.with_timestamp(timestamp);
+                    out.unbounded_send(Notification::OrderExpiration(expiration)).unwrap();
+                }
+
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_message(&self, json: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let event: OkxEvent<'_> = serde_json::from_str(json)?;
+        let channel = check_event(&event)?;
+
+        // Subscriptions are only accepted once logged in.
+        if event.event == Some("login") {
+            if let Some(out) = &self.out {
+                let orders = OkxChannel {
+                    channel: "orders",
+                    instType: Some("SPOT"),
+                    instId: Some(self.symbol.name()),
+                };
+                send_request("subscribe", vec![orders], out)?;
+            }
+            return Ok(());
+        }
+
+        if !self.control.flags().contains(NotificationFlags::ORDERS) {
+            return Ok(());
+        }
+
+        if channel == Some("orders") {
+            self.parse_orders(json, out)?;
+        }
+        Ok(())
+    }
+
+    /// The signature covers the timestamp in seconds followed by `GET/users/self/verify`.
+    fn login_args(&self) -> OkxLogin<'_> {
+        let timestamp = (timestamp_ms() / 1000).to_string();
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.keys.secret_key).unwrap();
+        signer.update(format!("{}GET/users/self/verify", timestamp).as_bytes()).unwrap();
+        let sign = base64::encode(&signer.sign_to_vec().unwrap());
+
+        OkxLogin {
+            apiKey: &self.keys.api_key,
+            passphrase: &self.keys.pass_phrase,
+            timestamp,
+            sign,
+        }
+    }
+}
+
+impl wss::HandlerImpl for PrivateHandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.out = Some(out.clone());
+        send_request("login", vec![self.login_args()], out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text, out)
+    }
+
+    fn on_flags_changed(&mut self, _: NotificationFlags, _: &ws::Sender) -> ws::Result<()> {
+        // `ORDERS` is read from the public connection when receiving messages.
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! A module defining operations on options, which are only available on some exchanges.
+
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use crate::tick::{Tick, TickUnit};
+use crate::api::{errors, Order, OrderAck};
+use crate::api::symbol::{Symbol, SymbolName, WithSymbol};
+use crate::api::timestamp::{Timestamp, Timestamped};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Type of an option contract.
+pub enum OptionType {
+    /// The right to buy the underlying at the strike price.
+    Call,
+
+    /// The right to sell the underlying at the strike price.
+    Put,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// The terms of an option contract, carried by its `Symbol`.
+pub struct OptionSpec {
+    underlying: SymbolName,
+    expiration: Timestamp,
+    strike: TickUnit,
+    strike_tick: Tick,
+    option_type: OptionType,
+}
+
+impl OptionSpec {
+    #[cfg_attr(not(feature = "deribit"), allow(dead_code))]
+    crate fn new(
+        underlying: &str,
+        expiration: Timestamp,
+        strike: TickUnit,
+        strike_tick: Tick,
+        option_type: OptionType
+    ) -> Option<Self> {
+        Some(OptionSpec {
+            underlying: SymbolName::from(underlying).ok()?,
+            expiration,
+            strike,
+            strike_tick,
+            option_type,
+        })
+    }
+
+    /// Underlying asset, e.g. `BTC`.
+    pub fn underlying(&self) -> &str {
+        &self.underlying
+    }
+
+    /// Expiration time of the option.
+    pub fn expiration(&self) -> Timestamp {
+        self.expiration
+    }
+
+    /// Strike price, in tick units of `self.strike_tick()`.
+    pub fn strike(&self) -> TickUnit {
+        self.strike
+    }
+
+    /// Tick of the strike price, which is unrelated to the price tick of the option
+    /// quotes.
+    pub fn strike_tick(&self) -> Tick {
+        self.strike_tick
+    }
+
+    /// Call or put.
+    pub fn option_type(&self) -> OptionType {
+        self.option_type
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// Sensitivities of the price of an option, as computed by the exchange.
+pub struct Greeks {
+    /// Sensitivity to the price of the underlying.
+    pub delta: f64,
+
+    /// Sensitivity of the delta to the price of the underlying.
+    pub gamma: f64,
+
+    /// Sensitivity to the implied volatility.
+    pub vega: f64,
+
+    /// Sensitivity to the passage of time.
+    pub theta: f64,
+
+    /// Sensitivity to the interest rate.
+    pub rho: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// A snapshot of the market of an option. Prices are expressed in the same unit as
+/// the option quotes, implied volatilities are in percent, e.g. `65.2`.
+pub struct OptionTicker {
+    /// Name of the option.
+    pub symbol: String,
+
+    /// Price of the underlying used for computing the greeks.
+    pub underlying_price: f64,
+
+    /// Fair price of the option, as computed by the exchange.
+    pub mark_price: f64,
+
+    /// Implied volatility of the mark price.
+    pub mark_iv: f64,
+
+    /// Best bid price and its implied volatility, if any.
+    pub best_bid: Option<(f64, f64)>,
+
+    /// Best ask price and its implied volatility, if any.
+    pub best_ask: Option<(f64, f64)>,
+
+    /// Open interest, in contracts.
+    pub open_interest: f64,
+
+    /// Greeks at the mark price.
+    pub greeks: Greeks,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Unit in which the price of an option order is expressed.
+pub enum OptionPricing {
+    /// The usual option premium, as for `ApiClient::order`.
+    Premium,
+
+    /// Premium in USD, converted by the exchange.
+    Usd,
+
+    /// Implied volatility in percent, e.g. `65.2`: the order is repriced by the
+    /// exchange as the underlying moves.
+    ImpliedVolatility,
+}
+
+/// A trait implemented by clients of exchanges which provide trading on options,
+/// on top of `ApiClient`.
+pub trait OptionsApi {
+    /// Return the options on `underlying`, e.g. `BTC`, sorted by expiration, strike
+    /// and type. Each symbol carries its `OptionSpec`.
+    fn option_chain(&self, underlying: &str) -> Vec<Symbol>;
+
+    /// Retrieve the ticker of the option `symbol`, with its implied volatilities and
+    /// greeks. The request does not require a key pair.
+    fn option_ticker(&self, symbol: &Symbol)
+        -> Box<dyn Future<Item = Timestamped<OptionTicker>, Error = errors::Error> + Send + 'static>;
+
+    /// Send an order on an option, its price being expressed in `pricing`. Unless
+    /// `pricing` is `OptionPricing::Premium`, the price of `order` must be unticked.
+    fn option_order(&self, order: WithSymbol<&Order>, pricing: OptionPricing)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::OrderError> + Send + 'static>;
+}
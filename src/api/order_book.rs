@@ -3,6 +3,10 @@
 use std::sync::{Arc, Mutex, MutexGuard};
 use crate::order_book::OrderBook;
 use crate::api::ApiClient;
+#[cfg(feature = "affinity")]
+use log::error;
+#[cfg(feature = "affinity")]
+use crate::api::affinity::ThreadConfig;
 
 /// A self-maintained live order book, updated in the background each time
 /// the underlying exchange stream sends an update.
@@ -29,6 +33,31 @@ impl LiveOrderBook {
     /// The call will block until the initial snapshot of the order book has been
     /// received.
     pub fn new<C: ApiClient>(stream: C::Stream) -> LiveOrderBook {
+        Self::spawn::<C, _>(stream, || ())
+    }
+
+    /// Build a self-maintained live order book from an exchange data stream, the
+    /// thread maintaining the book being configured with `config`, see `api::affinity`.
+    /// An error in applying `config` is logged, and the book is maintained anyway.
+    ///
+    /// # Note
+    /// The call will block until the initial snapshot of the order book has been
+    /// received.
+    #[cfg(feature = "affinity")]
+    pub fn with_thread_config<C: ApiClient>(stream: C::Stream, config: ThreadConfig)
+        -> LiveOrderBook
+    {
+        Self::spawn::<C, _>(stream, move || {
+            if let Err(err) = config.apply() {
+                error!("failed to apply thread config: `{}`", err);
+            }
+        })
+    }
+
+    /// Spawn the thread maintaining the book, which starts by calling `setup`.
+    fn spawn<C: ApiClient, F>(stream: C::Stream, setup: F) -> LiveOrderBook
+        where F: FnOnce() + Send + 'static
+    {
         use std::thread;
         use futures::prelude::*;
         use crate::api::Notification;
@@ -39,6 +68,8 @@ impl LiveOrderBook {
         let (sender, receiver) = std::sync::mpsc::sync_channel(0);
 
         thread::spawn(move || {
+            setup();
+
             let weak = Arc::downgrade(&weak);
             let mut snapshot = false;
 
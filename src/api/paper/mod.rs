@@ -0,0 +1,570 @@
+//! A module defining a paper-trading client, which simulates the orders of a strategy
+//! against the live market data of another client, see `Client`.
+//!
+//! Orders never reach the exchange. Each symbol is simulated by matching the orders
+//! against the last known order book and trades of the live feed:
+//! * an incoming order trades against the opposite levels it crosses, as a taker,
+//!   consuming their liquidity from the simulated book until the next update of the
+//!   corresponding level
+//! * a resting order is filled, as a maker, by the trades at a better price than its
+//!   own, or at its own price once the size resting ahead of it when it was placed
+//!   has been traded; levels shrinking below that size move the order up the queue
+//! * a resting order crossed by the opposite side of the book is filled at its own
+//!   price, up to the crossing liquidity
+//!
+//! The simulation ignores the latency to the exchange and the impact of the simulated
+//! orders on the other participants.
+
+mod test;
+mod stream;
+
+pub use self::stream::PaperStream;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::prelude::*;
+use futures::task::{self, Task};
+use log::warn;
+use crate::Side;
+use crate::api::{
+    errors,
+    ApiClient,
+    GenerateOrderId,
+    Notification,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    OrderType,
+    TimeInForce,
+    Cancel,
+    CancelAck,
+    Replace,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+    Balances,
+    ExchangeStatus,
+    Trade,
+};
+use crate::api::errors::{OrderErrorKind, CancelErrorKind};
+use crate::api::fees::{FeeSchedule, Liquidity};
+use crate::api::symbol::{Symbol, WithSymbol, IntoWithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::Bar;
+use crate::order_book::{OrderBook, LimitUpdate};
+use crate::sim::LimitOrder;
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct PaperOrder {
+    order_id: String,
+    side: Side,
+    price: TickUnit,
+    filled: TickUnit,
+    remaining: TickUnit,
+
+    /// Size of the live orders resting ahead of this one at the same level.
+    queue_ahead: TickUnit,
+
+    /// Time at which a good til date order expires.
+    expiration: Option<Timestamp>,
+}
+
+impl PaperOrder {
+    /// Whether this order would trade against the opposite side at `price`.
+    fn crosses(&self, price: TickUnit) -> bool {
+        match self.side {
+            Side::Bid => self.price >= price,
+            Side::Ask => self.price <= price,
+        }
+    }
+}
+
+fn opposite(side: Side) -> Side {
+    match side {
+        Side::Bid => Side::Ask,
+        Side::Ask => Side::Bid,
+    }
+}
+
+#[derive(Debug, Default)]
+/// Simulation of the orders of one symbol.
+crate struct Simulator {
+    /// Last known live order book, minus the liquidity taken by the simulated orders.
+    book: OrderBook,
+
+    /// Resting orders, in the order they were placed.
+    orders: Vec<PaperOrder>,
+
+    fee_schedule: FeeSchedule,
+
+    /// Traded volume, in units of the notional.
+    volume: TickUnit,
+
+    /// Notifications resulting from the simulation, not delivered yet.
+    notifications: VecDeque<Notification>,
+
+    /// Stream waiting for notifications.
+    waiting: Option<Task>,
+}
+
+impl Simulator {
+    crate fn new(fee_schedule: FeeSchedule) -> Self {
+        Simulator {
+            fee_schedule,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, notif: Notification) {
+        self.notifications.push_back(notif);
+        if let Some(task) = self.waiting.take() {
+            task.notify();
+        }
+    }
+
+    /// Return the next notification resulting from the simulation, if any.
+    crate fn pop(&mut self) -> Option<Notification> {
+        self.notifications.pop_front()
+    }
+
+    /// Whether all the simulated notifications have been delivered.
+    crate fn is_idle(&self) -> bool {
+        self.notifications.is_empty()
+    }
+
+    /// Register the current task for being notified of the next simulated
+    /// notification. Must be called from within a task.
+    crate fn park(&mut self) {
+        self.waiting = Some(task::current());
+    }
+
+    /// Notify a fill of `size` at `price` of the order `index` of `self.orders`, which
+    /// is removed once filled.
+    fn fill(
+        &mut self,
+        index: usize,
+        price: TickUnit,
+        size: TickUnit,
+        liquidity: Liquidity,
+        timestamp: Timestamp
+    ) {
+        let notional = price.saturating_mul(size);
+        let fee = self.fee_schedule.fee(self.volume, liquidity, notional);
+        self.volume = self.volume.saturating_add(notional);
+
+        let order = &mut self.orders[index];
+        order.filled += size;
+        order.remaining -= size;
+
+        // Rebates cannot be represented by a commission.
+        let update = OrderUpdate {
+            order_id: order.order_id.clone(),
+            consumed_size: size,
+            remaining_size: order.remaining,
+            consumed_price: price,
+            commission: if fee > 0 { fee as TickUnit } else { 0 },
+        }.with_timestamp(timestamp);
+
+        if order.remaining == 0 {
+            self.orders.remove(index);
+        }
+        self.push(Notification::OrderUpdate(update));
+    }
+
+    /// Take the liquidity of the opposite levels crossing the order `index`, up to its
+    /// remaining size. Trades happen at the price of the order if `at_own_price`, at
+    /// the price of the levels otherwise.
+    fn take_liquidity(
+        &mut self,
+        index: usize,
+        liquidity: Liquidity,
+        at_own_price: bool,
+        timestamp: Timestamp
+    ) {
+        let order = self.orders[index].clone();
+        let side = opposite(order.side);
+        let levels: Vec<_> = match side {
+            Side::Ask => self.book.ask().map(|(price, size)| (*price, *size)).collect(),
+            Side::Bid => self.book.bid().map(|(price, size)| (*price, *size)).collect(),
+        };
+
+        let mut remaining = order.remaining;
+        for (price, size) in levels {
+            if remaining == 0 || !order.crosses(price) {
+                break;
+            }
+
+            let traded = std::cmp::min(remaining, size);
+            remaining -= traded;
+            self.book.update(LimitUpdate::new(price, size - traded, side));
+
+            let price = if at_own_price { order.price } else { price };
+            self.fill(index, price, traded, liquidity, timestamp);
+        }
+    }
+
+    /// Simulate a new order, the owner of which is ignored.
+    crate fn submit(&mut self, order: LimitOrder, timestamp: Timestamp)
+        -> Result<(), OrderErrorKind>
+    {
+        if self.orders.iter().any(|resting| resting.order_id == order.order_id) {
+            return Err(OrderErrorKind::DuplicateOrder);
+        }
+
+        let LimitOrder { side, price, size, order_type, time_in_force, .. } = order;
+        let order = PaperOrder {
+            order_id: order.order_id,
+            side,
+            price,
+            filled: 0,
+            remaining: size,
+            queue_ahead: self.book.size_at_limit(side, price),
+            expiration: time_in_force.expiration(),
+        };
+
+        let available: TickUnit = match opposite(side) {
+            Side::Ask => self.book.ask()
+                .take_while(|(price, _)| order.crosses(**price))
+                .map(|(_, size)| *size)
+                .sum(),
+            Side::Bid => self.book.bid()
+                .take_while(|(price, _)| order.crosses(**price))
+                .map(|(_, size)| *size)
+                .sum(),
+        };
+
+        if order_type == OrderType::LimitMaker && available > 0 {
+            return Err(OrderErrorKind::WouldTakeLiquidity);
+        }
+
+        self.push(Notification::OrderConfirmation(OrderConfirmation {
+            order_id: order.order_id.clone(),
+            price,
+            size,
+            side,
+        }.with_timestamp(timestamp)));
+
+        if time_in_force == TimeInForce::FillOrKilll && available < size {
+            self.push(Notification::OrderExpiration(OrderExpiration {
+                order_id: order.order_id,
+                filled_size: Some(0),
+                remaining_size: Some(size),
+            }.with_timestamp(timestamp)));
+            return Ok(());
+        }
+
+        let order_id = order.order_id.clone();
+        self.orders.push(order);
+        let index = self.orders.len() - 1;
+        self.take_liquidity(index, Liquidity::Taker, false, timestamp);
+
+        let immediate = time_in_force == TimeInForce::ImmediateOrCancel ||
+            time_in_force == TimeInForce::FillOrKilll;
+        let position = self.orders.iter().position(|order| order.order_id == order_id);
+        match position {
+            Some(index) if immediate => {
+                let order = self.orders.remove(index);
+                self.push(Notification::OrderExpiration(OrderExpiration {
+                    order_id: order.order_id,
+                    filled_size: Some(order.filled),
+                    remaining_size: Some(order.remaining),
+                }.with_timestamp(timestamp)));
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Cancel a resting order.
+    crate fn cancel(&mut self, order_id: &str, timestamp: Timestamp)
+        -> Result<(), CancelErrorKind>
+    {
+        let index = self.orders.iter()
+            .position(|order| order.order_id == order_id)
+            .ok_or(CancelErrorKind::UnknownOrder)?;
+
+        let order = self.orders.remove(index);
+        self.push(Notification::OrderExpiration(OrderExpiration {
+            order_id: order.order_id,
+            filled_size: Some(order.filled),
+            remaining_size: Some(order.remaining),
+        }.with_timestamp(timestamp)));
+        Ok(())
+    }
+
+    /// Expire the good til date orders whose date has been reached at `now`.
+    fn expire(&mut self, now: Timestamp) {
+        let mut index = 0;
+        while index < self.orders.len() {
+            match self.orders[index].expiration {
+                Some(expiration) if expiration <= now => {
+                    let order = self.orders.remove(index);
+                    self.push(Notification::OrderExpiration(OrderExpiration {
+                        order_id: order.order_id,
+                        filled_size: Some(order.filled),
+                        remaining_size: Some(order.remaining),
+                    }.with_timestamp(expiration)));
+                }
+                _ => index += 1,
+            }
+        }
+    }
+
+    /// Update the simulation with a notification of the live feed. The time of the
+    /// simulation is that of the live feed, which expires the good til date orders.
+    crate fn on_market_data(&mut self, notif: &Notification) {
+        match notif {
+            Notification::LimitUpdates(updates) => {
+                if let Some(timestamp) = updates.iter().map(|update| update.timestamp()).max() {
+                    self.expire(timestamp);
+                }
+
+                let mut timestamp = 0;
+                for update in updates {
+                    for order in &mut self.orders {
+                        if order.side == update.side && order.price == update.price {
+                            order.queue_ahead = std::cmp::min(order.queue_ahead, update.size);
+                        }
+                    }
+                    self.book.update(**update);
+                    timestamp = std::cmp::max(timestamp, update.timestamp());
+                }
+
+                // Orders are removed once filled, hence the reverse order.
+                for index in (0..self.orders.len()).rev() {
+                    self.take_liquidity(index, Liquidity::Maker, true, timestamp);
+                }
+            }
+
+            Notification::Trade(trade) => {
+                self.expire(trade.timestamp());
+
+                let mut size = trade.size;
+                let mut index = 0;
+                while index < self.orders.len() && size > 0 {
+                    let order = &mut self.orders[index];
+                    if order.side != trade.maker_side || !order.crosses(trade.price) {
+                        index += 1;
+                        continue;
+                    }
+
+                    // Trades at the price of the order first consume the size ahead of it.
+                    let mut through = size;
+                    if order.price == trade.price {
+                        let consumed = std::cmp::min(order.queue_ahead, through);
+                        order.queue_ahead -= consumed;
+                        through -= consumed;
+                    }
+
+                    let traded = std::cmp::min(order.remaining, through);
+                    if traded == 0 {
+                        index += 1;
+                        continue;
+                    }
+                    size -= traded;
+
+                    let (price, filled) = (order.price, traded == order.remaining);
+                    self.fill(index, price, traded, Liquidity::Maker, trade.timestamp());
+                    if !filled {
+                        index += 1;
+                    }
+                }
+            }
+
+            _ => (),
+        }
+    }
+}
+
+/// A client simulating the orders sent through it against the market data of `C`,
+/// see the module documentation.
+///
+/// Market data, symbols and the exchange status are those of `C`, which does not need
+/// any key pair. The streams yield the live market data along with the notifications
+/// of the simulated orders, so that a strategy can be dry-run without any change. At
+/// most one stream should be opened per symbol, since the simulation of a symbol is
+/// driven by its stream.
+pub struct Client<C> {
+    inner: C,
+    fee_schedule: FeeSchedule,
+    balances: Balances,
+
+    /// symbol name => simulation
+    simulators: Mutex<HashMap<String, Arc<Mutex<Simulator>>>>,
+
+    /// Number of orders sent without an order id so far.
+    order_ids: AtomicUsize,
+}
+
+impl<C: ApiClient> Client<C> {
+    /// Simulate orders against the market data of `client`, without fees.
+    pub fn new(client: C) -> Self {
+        Client {
+            inner: client,
+            fee_schedule: FeeSchedule::zero(),
+            balances: Balances::new(),
+            simulators: Mutex::new(HashMap::new()),
+            order_ids: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set the fee schedule applied to the simulated fills, the commission of which is
+    /// expressed in units of the notional (see `api::fees`). Rebates are not reported.
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = fee_schedule;
+        self
+    }
+
+    /// Set the balances returned by `balances`. They are not updated by the simulated
+    /// fills, since symbols do not tell their assets.
+    pub fn with_balances(mut self, balances: Balances) -> Self {
+        self.balances = balances;
+        self
+    }
+
+    fn simulator(&self, symbol: Symbol) -> Arc<Mutex<Simulator>> {
+        let mut simulators = self.simulators.lock().unwrap();
+        let fee_schedule = &self.fee_schedule;
+        simulators.entry(symbol.name().to_owned())
+            .or_insert_with(|| Arc::new(Mutex::new(Simulator::new(fee_schedule.clone()))))
+            .clone()
+    }
+}
+
+impl<C: ApiClient> GenerateOrderId for Client<C> {
+    fn new_order_id(hint: &str) -> String {
+        C::new_order_id(hint)
+    }
+}
+
+impl<C: ApiClient> ApiClient for Client<C> {
+    type Stream = PaperStream<C::Stream>;
+
+    fn exchange_id(&self) -> ExchangeId {
+        self.inner.exchange_id()
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.inner.find_symbol(symbol)
+    }
+
+    /// The order book and the trades are always streamed from `C`, since they drive
+    /// the simulation, but only forwarded according to `flags`.
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        let inner = self.inner.stream_with_flags(
+            symbol,
+            NotificationFlags::ORDER_BOOK | NotificationFlags::TRADES
+        );
+        PaperStream::new(inner, flags, self.simulator(symbol))
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        if order.reduce_only || order.trailing_delta.is_some() || order.display_size.is_some() ||
+            order.type_ == OrderType::Market || order.type_.is_stop()
+        {
+            warn!("called `order` with reduce only, a trailing delta, a display size, a market or a stop order, which are not simulated");
+            return Box::new(futures::future::err(
+                errors::ApiError::RestError(errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let symbol = order.symbol();
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => {
+                let count = self.order_ids.fetch_add(1, Ordering::Relaxed);
+                Self::new_order_id(&format!("paper{}", count))
+            }
+        };
+
+        let limit_order = LimitOrder::new(
+            order_id.clone(),
+            0,
+            order.side,
+            order.price.ticked(symbol.price_tick()),
+            order.size.ticked(symbol.size_tick())
+        ).with_order_type(order.type_).with_time_in_force(order.time_in_force);
+
+        let timestamp = timestamp_ms();
+        let result = self.simulator(symbol).lock().unwrap().submit(limit_order, timestamp);
+
+        let result = match result {
+            Ok(()) => Ok(OrderAck { order_id }.with_timestamp(timestamp)),
+            Err(kind) => Err(
+                errors::ApiError::RestError(errors::RestErrorKind::Specific(kind).into())
+            ),
+        };
+        Box::new(futures::future::result(result))
+    }
+
+    fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_trades(symbol, limit)
+    }
+
+    fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_bars(symbol, interval, limit)
+    }
+
+    /// Unlike the requests of the exchange clients, the simulator acts right away, hence
+    /// the order is only submitted once the cancel has succeeded.
+    fn replace(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::ReplaceError> + Send + 'static>
+    {
+        let symbol = replace.symbol();
+        let result = self.simulator(symbol).lock().unwrap()
+            .cancel(replace.cancel().order_id(), timestamp_ms());
+
+        if let Err(kind) = result {
+            return Box::new(futures::future::err(
+                errors::ApiError::RestError(
+                    errors::RestErrorKind::Specific(errors::ReplaceErrorKind::Cancel(kind)).into()
+                )
+            ));
+        }
+
+        Box::new(self.order(replace.order().with_symbol(symbol)).map_err(errors::ReplaceError::from))
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = errors::CancelError> + Send + 'static>
+    {
+        let timestamp = timestamp_ms();
+        let result = self.simulator(cancel.symbol()).lock().unwrap()
+            .cancel(&cancel.order_id, timestamp);
+
+        let result = match result {
+            Ok(()) => Ok(CancelAck.with_timestamp(timestamp)),
+            Err(kind) => Err(
+                errors::ApiError::RestError(errors::RestErrorKind::Specific(kind).into())
+            ),
+        };
+        Box::new(futures::future::result(result))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.ping()
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = errors::Error> + Send + 'static>
+    {
+        Box::new(futures::future::ok(self.balances.clone()))
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.system_status()
+    }
+}
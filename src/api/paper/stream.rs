@@ -0,0 +1,81 @@
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::paper::Simulator;
+
+/// A stream yielding the market data of the wrapped stream, which drives the
+/// simulation of a symbol, along with the notifications of the simulated orders.
+pub struct PaperStream<S> {
+    inner: S,
+    flags: NotificationFlags,
+    simulator: Arc<Mutex<Simulator>>,
+}
+
+impl<S> PaperStream<S> {
+    crate fn new(inner: S, flags: NotificationFlags, simulator: Arc<Mutex<Simulator>>) -> Self {
+        PaperStream {
+            inner,
+            flags,
+            simulator,
+        }
+    }
+
+    /// Whether `notif`, coming from the wrapped stream, is forwarded.
+    fn forwards(&self, notif: &Notification) -> bool {
+        match notif {
+            Notification::LimitUpdates(..) => self.flags.contains(NotificationFlags::ORDER_BOOK),
+            Notification::Trade(..) => self.flags.contains(NotificationFlags::TRADES),
+
+            // Only the simulated orders are forwarded.
+            Notification::OrderConfirmation(..) |
+            Notification::OrderUpdate(..) |
+            Notification::OrderExpiration(..) |
+            Notification::PositionUpdate(..) => false,
+
+            Notification::ExchangeStatus(..) |
+            Notification::IndexPrice(..) |
+            Notification::TradingHalted(..) |
+            Notification::TradingResumed(..) |
+            Notification::AuctionUpdate(..) => true,
+        }
+    }
+}
+
+impl<S> Stream for PaperStream<S> where S: Stream<Item = Notification, Error = ()> {
+    type Item = Notification;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Notification>, ()> {
+        loop {
+            // Notifications of the simulated orders come first, since they result from
+            // the orders sent or the market data received so far.
+            if let Some(notif) = self.simulator.lock().unwrap().pop() {
+                if self.flags.contains(NotificationFlags::ORDERS) {
+                    return Ok(Async::Ready(Some(notif)));
+                }
+                continue;
+            }
+
+            match self.inner.poll()? {
+                Async::Ready(Some(notif)) => {
+                    self.simulator.lock().unwrap().on_market_data(&notif);
+                    if self.forwards(&notif) {
+                        return Ok(Async::Ready(Some(notif)));
+                    }
+                }
+
+                Async::Ready(None) => return Ok(Async::Ready(None)),
+
+                // An order may have been simulated in the meantime.
+                Async::NotReady => {
+                    let mut simulator = self.simulator.lock().unwrap();
+                    if !simulator.is_idle() {
+                        continue;
+                    }
+                    simulator.park();
+                    return Ok(Async::NotReady);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{
+    Notification,
+    Trade,
+    OrderType,
+    TimeInForce,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+};
+use crate::api::errors::{OrderErrorKind, CancelErrorKind};
+use crate::api::fees::FeeSchedule;
+use crate::api::paper::Simulator;
+use crate::api::timestamp::IntoTimestamped;
+use crate::order_book::LimitUpdate;
+use crate::sim::LimitOrder;
+use crate::tick::TickUnit;
+
+fn order(order_id: &str, side: Side, price: TickUnit, size: TickUnit) -> LimitOrder {
+    LimitOrder::new(order_id.to_owned(), 0, side, price, size)
+}
+
+fn limits(updates: &[(TickUnit, TickUnit, Side)], timestamp: u64) -> Notification {
+    Notification::LimitUpdates(
+        updates.iter()
+            .map(|(price, size, side)| LimitUpdate::new(*price, *size, *side).with_timestamp(timestamp))
+            .collect()
+    )
+}
+
+fn trade(price: TickUnit, size: TickUnit, maker_side: Side, timestamp: u64) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size,
+        maker_side,
+    }.with_timestamp(timestamp))
+}
+
+fn confirmation(order_id: &str, side: Side, price: TickUnit, size: TickUnit, timestamp: u64)
+    -> Notification
+{
+    Notification::OrderConfirmation(OrderConfirmation {
+        order_id: order_id.to_owned(),
+        price,
+        size,
+        side,
+    }.with_timestamp(timestamp))
+}
+
+fn update(order_id: &str, price: TickUnit, size: TickUnit, remaining: TickUnit, timestamp: u64)
+    -> Notification
+{
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: size,
+        remaining_size: remaining,
+        consumed_price: price,
+        commission: 0,
+    }.with_timestamp(timestamp))
+}
+
+fn expiration(order_id: &str, filled: TickUnit, remaining: TickUnit, timestamp: u64)
+    -> Notification
+{
+    Notification::OrderExpiration(OrderExpiration {
+        order_id: order_id.to_owned(),
+        filled_size: Some(filled),
+        remaining_size: Some(remaining),
+    }.with_timestamp(timestamp))
+}
+
+fn drain(simulator: &mut Simulator) -> Vec<Notification> {
+    let mut notifs = Vec::new();
+    while let Some(notif) = simulator.pop() {
+        notifs.push(notif);
+    }
+    notifs
+}
+
+fn simulator() -> Simulator {
+    let mut simulator = Simulator::new(FeeSchedule::zero());
+    simulator.on_market_data(&limits(&[
+        (98, 5, Side::Bid),
+        (99, 2, Side::Bid),
+        (101, 2, Side::Ask),
+        (102, 5, Side::Ask),
+    ], 0));
+    simulator
+}
+
+#[test]
+fn taker() {
+    let mut simulator = simulator();
+    simulator.submit(order("a", Side::Bid, 102, 3), 1).unwrap();
+    assert_eq!(drain(&mut simulator), vec![
+        confirmation("a", Side::Bid, 102, 3, 1),
+        update("a", 101, 2, 1, 1),
+        update("a", 102, 1, 0, 1),
+    ]);
+    assert!(simulator.is_idle());
+
+    // The liquidity taken is gone until the level is updated.
+    let ioc = order("b", Side::Bid, 101, 1).with_time_in_force(TimeInForce::ImmediateOrCancel);
+    simulator.submit(ioc, 2).unwrap();
+    assert_eq!(drain(&mut simulator), vec![
+        confirmation("b", Side::Bid, 101, 1, 2),
+        expiration("b", 0, 1, 2),
+    ]);
+
+    let fok = order("c", Side::Ask, 98, 10).with_time_in_force(TimeInForce::FillOrKilll);
+    simulator.submit(fok, 3).unwrap();
+    assert_eq!(drain(&mut simulator), vec![
+        confirmation("c", Side::Ask, 98, 10, 3),
+        expiration("c", 0, 10, 3),
+    ]);
+
+    let maker = order("d", Side::Ask, 99, 1).with_order_type(OrderType::LimitMaker);
+    assert_eq!(simulator.submit(maker, 4), Err(OrderErrorKind::WouldTakeLiquidity));
+    assert!(simulator.is_idle());
+}
+
+#[test]
+fn maker() {
+    let mut simulator = simulator();
+    simulator.submit(order("a", Side::Bid, 99, 3), 1).unwrap();
+    assert_eq!(
+        simulator.submit(order("a", Side::Bid, 99, 3), 1),
+        Err(OrderErrorKind::DuplicateOrder)
+    );
+    drain(&mut simulator);
+
+    // Two lots are resting ahead of the order.
+    simulator.on_market_data(&trade(99, 1, Side::Bid, 2));
+    assert!(simulator.is_idle());
+    simulator.on_market_data(&limits(&[(99, 1, Side::Bid)], 3));
+    simulator.on_market_data(&trade(99, 2, Side::Bid, 4));
+    assert_eq!(drain(&mut simulator), vec![update("a", 99, 1, 2, 4)]);
+
+    // Trades through the level fill the order at its own price.
+    simulator.on_market_data(&trade(98, 1, Side::Bid, 5));
+    simulator.on_market_data(&trade(98, 5, Side::Ask, 6));
+    assert_eq!(drain(&mut simulator), vec![update("a", 99, 1, 1, 5)]);
+
+    // So do the asks crossing the order.
+    simulator.on_market_data(&limits(&[(99, 4, Side::Ask)], 7));
+    assert_eq!(drain(&mut simulator), vec![update("a", 99, 1, 0, 7)]);
+}
+
+#[test]
+fn cancel() {
+    let mut simulator = simulator();
+    simulator.submit(order("a", Side::Ask, 105, 3), 1).unwrap();
+    drain(&mut simulator);
+
+    simulator.cancel("a", 2).unwrap();
+    assert_eq!(drain(&mut simulator), vec![expiration("a", 0, 3, 2)]);
+    assert_eq!(simulator.cancel("a", 3), Err(CancelErrorKind::UnknownOrder));
+}
+
+#[test]
+fn good_til_date() {
+    let mut simulator = simulator();
+    let gtd = order("a", Side::Bid, 99, 3).with_time_in_force(TimeInForce::GoodTilDate(10));
+    simulator.submit(gtd, 1).unwrap();
+    drain(&mut simulator);
+
+    simulator.on_market_data(&trade(99, 2, Side::Bid, 5));
+    simulator.on_market_data(&trade(99, 1, Side::Bid, 6));
+    assert_eq!(drain(&mut simulator), vec![update("a", 99, 1, 2, 6)]);
+
+    // Expired before the trade at its date.
+    simulator.on_market_data(&trade(99, 1, Side::Bid, 10));
+    assert_eq!(drain(&mut simulator), vec![expiration("a", 1, 2, 10)]);
+}
@@ -21,6 +21,15 @@ impl QueryString {
         self.query.push_str(arg);
     }
 
+    #[cfg_attr(
+        not(any(
+            feature = "binance",
+            feature = "kraken",
+            feature = "kraken_futures",
+            feature = "mexc",
+        )),
+        allow(dead_code)
+    )]
     crate fn push<P: fmt::Display>(&mut self, name: &str, arg: P) {
         use std::fmt::Write;
 
@@ -34,6 +43,7 @@ impl QueryString {
         self.query
     }
 
+    #[cfg_attr(not(any(feature = "binance", feature = "mexc")), allow(dead_code))]
     crate fn into_string_with_signature(mut self, key: &PKey<Private>) -> String {
         let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
         signer.update(self.query.as_bytes()).unwrap();
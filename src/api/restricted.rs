@@ -0,0 +1,221 @@
+//! A module defining wrappers around API clients which restrict the operations
+//! available to the code holding them, independently of the permissions of the
+//! underlying API keys.
+
+use futures::prelude::*;
+use crate::api::{
+    errors,
+    ApiClient,
+    GenerateOrderId,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    OneCancelsOther,
+    OcoAck,
+    Cancel,
+    CancelAck,
+    OrderStatus,
+    Fill,
+    Replace,
+    Balances,
+    ExchangeStatus,
+    Trade,
+};
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{Timestamp, Timestamped};
+use crate::data::resample::Bar;
+
+/// A client which can only read market and account data: it does not implement
+/// `ApiClient`, and has no way to send or cancel orders.
+///
+/// The wrapped client cannot be retrieved.
+pub struct ReadOnlyClient<C> {
+    inner: C,
+}
+
+impl<C: ApiClient> ReadOnlyClient<C> {
+    /// Wrap `client`.
+    pub fn new(client: C) -> Self {
+        ReadOnlyClient {
+            inner: client,
+        }
+    }
+
+    /// See `ApiClient::find_symbol`.
+    pub fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.inner.find_symbol(symbol)
+    }
+
+    /// See `ApiClient::stream_with_flags`.
+    pub fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> C::Stream {
+        self.inner.stream_with_flags(symbol, flags)
+    }
+
+    /// See `ApiClient::stream`.
+    pub fn stream(&self, symbol: Symbol) -> C::Stream {
+        self.inner.stream(symbol)
+    }
+
+    /// See `ApiClient::order_status`.
+    pub fn order_status(&self, symbol: Symbol, order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.order_status(symbol, order_id)
+    }
+
+    /// See `ApiClient::my_trades`.
+    pub fn my_trades(&self, symbol: Symbol, since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.my_trades(symbol, since)
+    }
+
+    /// See `ApiClient::recent_trades`.
+    pub fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_trades(symbol, limit)
+    }
+
+    /// See `ApiClient::recent_bars`.
+    pub fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_bars(symbol, interval, limit)
+    }
+
+    /// See `ApiClient::ping`.
+    pub fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.ping()
+    }
+
+    /// See `ApiClient::balances`.
+    pub fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.balances()
+    }
+
+    /// See `ApiClient::system_status`.
+    pub fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.system_status()
+    }
+}
+
+/// A client which can trade but not move funds: it implements `ApiClient`, but
+/// not the wallet operations of `WalletApi`.
+///
+/// The wrapped client cannot be retrieved.
+pub struct TradeOnlyClient<C> {
+    inner: C,
+}
+
+impl<C: ApiClient> TradeOnlyClient<C> {
+    /// Wrap `client`.
+    pub fn new(client: C) -> Self {
+        TradeOnlyClient {
+            inner: client,
+        }
+    }
+}
+
+impl<C: ApiClient> GenerateOrderId for TradeOnlyClient<C> {
+    fn new_order_id(hint: &str) -> String {
+        C::new_order_id(hint)
+    }
+}
+
+impl<C: ApiClient> ApiClient for TradeOnlyClient<C> {
+    type Stream = C::Stream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        self.inner.exchange_id()
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.inner.find_symbol(symbol)
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.inner.stream_with_flags(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        self.inner.order(order)
+    }
+
+    fn supports_oco(&self) -> bool {
+        self.inner.supports_oco()
+    }
+
+    fn oco(&self, oco: WithSymbol<&OneCancelsOther>)
+        -> Box<dyn Future<Item = Timestamped<OcoAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        self.inner.oco(oco)
+    }
+
+    fn order_status(&self, symbol: Symbol, order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.order_status(symbol, order_id)
+    }
+
+    fn my_trades(&self, symbol: Symbol, since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.my_trades(symbol, since)
+    }
+
+    fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_trades(symbol, limit)
+    }
+
+    fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_bars(symbol, interval, limit)
+    }
+
+    fn supports_atomic_replace(&self) -> bool {
+        self.inner.supports_atomic_replace()
+    }
+
+    fn replace(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::ReplaceError> + Send + 'static>
+    {
+        self.inner.replace(replace)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = errors::CancelError> + Send + 'static>
+    {
+        self.inner.cancel(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.ping()
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.balances()
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.system_status()
+    }
+}
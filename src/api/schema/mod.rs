@@ -0,0 +1,638 @@
+//! A module defining a stable JSON schema for notifications, meant for consumers
+//! outside of this crate, e.g. other processes reading a message bus or recorded files.
+//!
+//! The serde derives of `Notification` follow the layout of the internal types, and
+//! change along with them. An `Envelope` instead has an explicit, versioned layout:
+//! every event is tagged with its `type`, prices and sizes are unticked decimal strings,
+//! so that consumers do not need the ticks of the symbol, and each envelope carries the
+//! `version` of the schema it was written with.
+//!
+//! ```json
+//! {
+//!     "version": 1,
+//!     "symbol": "BTCUSDT",
+//!     "type": "trade",
+//!     "timestamp": 1546300800000,
+//!     "price": "3700.01",
+//!     "size": "0.500",
+//!     "maker_side": "ask"
+//! }
+//! ```
+//!
+//! Fields may be added to a version of the schema, but are never removed nor changed:
+//! any other change bumps `SCHEMA_VERSION`.
+
+mod test;
+
+use failure_derive::Fail;
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::{
+    self,
+    Notification,
+    Trade,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+    TradingStatus,
+    AuctionUpdate,
+};
+use crate::api::derivatives::Position;
+use crate::api::index::IndexPrice;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+use crate::tick::{Tick, TickUnit, ConversionError};
+
+/// Version of the schema written by `Envelope::from_notification`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Fail)]
+/// An error which occurred while converting between a notification and an `Envelope`.
+pub enum SchemaError {
+    #[fail(display = "unsupported schema version {}", _0)]
+    /// The envelope was written with a newer version of the schema.
+    UnsupportedVersion(u32),
+
+    #[fail(display = "envelope of symbol `{}`", _0)]
+    /// The envelope does not belong to the expected symbol.
+    SymbolMismatch(String),
+
+    #[fail(display = "{}", _0)]
+    /// A price or a size could not be converted with the ticks of the symbol.
+    Conversion(#[cause] ConversionError),
+}
+
+impl From<ConversionError> for SchemaError {
+    fn from(err: ConversionError) -> Self {
+        SchemaError::Conversion(err)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Side of an order or a limit, `bid` or `ask`.
+pub enum SchemaSide {
+    /// Buy side.
+    Bid,
+
+    /// Sell side.
+    Ask,
+}
+
+impl From<Side> for SchemaSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Bid => SchemaSide::Bid,
+            Side::Ask => SchemaSide::Ask,
+        }
+    }
+}
+
+impl From<SchemaSide> for Side {
+    fn from(side: SchemaSide) -> Self {
+        match side {
+            SchemaSide::Bid => Side::Bid,
+            SchemaSide::Ask => Side::Ask,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An event along with the time at which it happened, in ms.
+pub struct Stamped<T> {
+    /// Time of the event as registered by the crate.
+    pub timestamp: Timestamp,
+
+    /// Time of the event corrected for the clock drift of its source, see `api::drift`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrected_timestamp: Option<Timestamp>,
+
+    /// The event.
+    #[serde(flatten)]
+    pub data: T,
+}
+
+impl<T> Stamped<T> {
+    fn from_timestamped<U, F>(timestamped: &Timestamped<U>, convert: F)
+        -> Result<Self, SchemaError>
+            where F: FnOnce(&U) -> Result<T, SchemaError>
+    {
+        let timestamp = timestamped.timestamp();
+        let corrected_timestamp = timestamped.corrected_timestamp();
+        Ok(Stamped {
+            timestamp,
+            corrected_timestamp: if corrected_timestamp != timestamp {
+                Some(corrected_timestamp)
+            } else {
+                None
+            },
+            data: convert(timestamped)?,
+        })
+    }
+
+    fn into_timestamped<U, F>(self, convert: F) -> Result<Timestamped<U>, SchemaError>
+        where F: FnOnce(T) -> Result<U, SchemaError>
+    {
+        let timestamped = convert(self.data)?.with_timestamp(self.timestamp);
+        Ok(match self.corrected_timestamp {
+            Some(corrected_timestamp) => timestamped.with_corrected_timestamp(corrected_timestamp),
+            None => timestamped,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A trade, see `api::Trade`.
+pub struct TradeEvent {
+    /// Price of the trade.
+    pub price: String,
+
+    /// Traded size.
+    pub size: String,
+
+    /// Side of the maker.
+    pub maker_side: SchemaSide,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A limit update of the order book, see `order_book::LimitUpdate`.
+pub struct LimitEvent {
+    /// Price of the limit.
+    pub price: String,
+
+    /// Size now available at this price, `0` when the limit was removed.
+    pub size: String,
+
+    /// Side of the limit.
+    pub side: SchemaSide,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An order inserted by the exchange, see `api::OrderConfirmation`.
+pub struct OrderConfirmationEvent {
+    /// Id of the order.
+    pub order_id: String,
+
+    /// Price of the order, `0` for market orders.
+    pub price: String,
+
+    /// Size of the order.
+    pub size: String,
+
+    /// Side of the order.
+    pub side: SchemaSide,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A fill of an order, see `api::OrderUpdate`.
+pub struct OrderUpdateEvent {
+    /// Id of the order.
+    pub order_id: String,
+
+    /// Size filled by the last trade.
+    pub consumed_size: String,
+
+    /// Size remaining in the book.
+    pub remaining_size: String,
+
+    /// Price of the last trade.
+    pub consumed_price: String,
+
+    /// Commission paid on the last trade.
+    pub commission: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An order which expired or was canceled, see `api::OrderExpiration`.
+pub struct OrderExpirationEvent {
+    /// Id of the order.
+    pub order_id: String,
+
+    /// Size filled before the order expired, if reported by the exchange.
+    #[serde(default)]
+    pub filled_size: Option<String>,
+
+    /// Size left unfilled, if reported by the exchange.
+    #[serde(default)]
+    pub remaining_size: Option<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Operational status of an exchange.
+pub enum SchemaExchangeStatus {
+    /// The exchange is operating normally.
+    Normal,
+
+    /// Some components of the exchange are experiencing issues.
+    Degraded,
+
+    /// The exchange is under maintenance.
+    Maintenance,
+
+    /// The exchange is experiencing a major outage.
+    Outage,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A change of the operational status of the exchange, see `api::ExchangeStatus`.
+pub struct ExchangeStatusEvent {
+    /// The new status.
+    pub status: SchemaExchangeStatus,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A position on a contract, see `api::derivatives::Position`.
+pub struct PositionEvent {
+    /// Name of the contract.
+    pub contract: String,
+
+    /// `bid` for a long position, `ask` for a short one.
+    pub side: SchemaSide,
+
+    /// Size of the position.
+    pub size: String,
+
+    /// Average entry price.
+    pub entry_price: String,
+
+    /// Unrealized profit, or loss if negative, in the margin asset.
+    pub unrealized_pnl: String,
+
+    /// Leverage applied to the contract.
+    pub leverage: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A price index published by the exchange, see `api::index::IndexPrice`.
+pub struct IndexPriceEvent {
+    /// Name of the index on the exchange.
+    pub index: String,
+
+    /// Price of the index.
+    pub price: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Trading status of the symbol, see `api::TradingStatus`.
+pub struct TradingStatusEvent {
+    /// Status as reported by the exchange.
+    pub status: String,
+
+    /// Description of the status, if provided by the exchange.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// State of an auction phase, see `api::AuctionUpdate`.
+pub struct AuctionEvent {
+    /// Phase as reported by the exchange, or `null` once the auction is over.
+    #[serde(default)]
+    pub phase: Option<String>,
+
+    /// Indicative opening price.
+    #[serde(default)]
+    pub indicative_price: Option<String>,
+
+    /// Size matched at the indicative price.
+    #[serde(default)]
+    pub indicative_size: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// An event of the schema, one for each variant of `Notification`.
+pub enum Event {
+    /// See `Notification::Trade`.
+    Trade(Stamped<TradeEvent>),
+
+    /// See `Notification::LimitUpdates`.
+    LimitUpdates {
+        /// The updates of the batch, in order.
+        updates: Vec<Stamped<LimitEvent>>,
+    },
+
+    /// See `Notification::OrderConfirmation`.
+    OrderConfirmation(Stamped<OrderConfirmationEvent>),
+
+    /// See `Notification::OrderUpdate`.
+    OrderUpdate(Stamped<OrderUpdateEvent>),
+
+    /// See `Notification::OrderExpiration`.
+    OrderExpiration(Stamped<OrderExpirationEvent>),
+
+    /// See `Notification::ExchangeStatus`.
+    ExchangeStatus(Stamped<ExchangeStatusEvent>),
+
+    /// See `Notification::PositionUpdate`.
+    PositionUpdate(Stamped<PositionEvent>),
+
+    /// See `Notification::IndexPrice`.
+    IndexPrice(Stamped<IndexPriceEvent>),
+
+    /// See `Notification::TradingHalted`.
+    TradingHalted(Stamped<TradingStatusEvent>),
+
+    /// See `Notification::TradingResumed`.
+    TradingResumed(Stamped<TradingStatusEvent>),
+
+    /// See `Notification::AuctionUpdate`.
+    AuctionUpdate(Stamped<AuctionEvent>),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A notification of a symbol, in the external schema.
+pub struct Envelope {
+    /// Version of the schema the envelope was written with.
+    pub version: u32,
+
+    /// Name of the symbol, as returned by `Symbol::name`.
+    pub symbol: String,
+
+    /// The notified event.
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+fn untick(tick: Tick, value: TickUnit) -> Result<String, SchemaError> {
+    Ok(tick.unticked(value)?)
+}
+
+fn tick(tick: Tick, value: &str) -> Result<TickUnit, SchemaError> {
+    Ok(tick.ticked(value)?)
+}
+
+fn untick_option(tick: Tick, value: Option<TickUnit>) -> Result<Option<String>, SchemaError> {
+    match value {
+        Some(value) => Ok(Some(untick(tick, value)?)),
+        None => Ok(None),
+    }
+}
+
+fn tick_option(tick: Tick, value: Option<String>) -> Result<Option<TickUnit>, SchemaError> {
+    match value {
+        Some(value) => Ok(Some(self::tick(tick, &value)?)),
+        None => Ok(None),
+    }
+}
+
+impl Envelope {
+    /// Convert `notification`, received on the stream of `symbol`, to the current
+    /// version of the schema.
+    pub fn from_notification(notification: &Notification, symbol: Symbol)
+        -> Result<Self, SchemaError>
+    {
+        let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+
+        let event = match notification {
+            Notification::Trade(trade) => Event::Trade(Stamped::from_timestamped(trade, |trade| {
+                Ok(TradeEvent {
+                    price: untick(price_tick, trade.price)?,
+                    size: untick(size_tick, trade.size)?,
+                    maker_side: trade.maker_side.into(),
+                })
+            })?),
+
+            Notification::LimitUpdates(updates) => Event::LimitUpdates {
+                updates: updates.iter()
+                    .map(|update| Stamped::from_timestamped(update, |update| {
+                        Ok(LimitEvent {
+                            price: untick(price_tick, update.price)?,
+                            size: untick(size_tick, update.size)?,
+                            side: update.side.into(),
+                        })
+                    }))
+                    .collect::<Result<_, _>>()?,
+            },
+
+            Notification::OrderConfirmation(confirmation) => Event::OrderConfirmation(
+                Stamped::from_timestamped(confirmation, |confirmation| {
+                    Ok(OrderConfirmationEvent {
+                        order_id: confirmation.order_id.clone(),
+                        price: untick(price_tick, confirmation.price)?,
+                        size: untick(size_tick, confirmation.size)?,
+                        side: confirmation.side.into(),
+                    })
+                })?
+            ),
+
+            Notification::OrderUpdate(update) => Event::OrderUpdate(
+                Stamped::from_timestamped(update, |update| {
+                    Ok(OrderUpdateEvent {
+                        order_id: update.order_id.clone(),
+                        consumed_size: untick(size_tick, update.consumed_size)?,
+                        remaining_size: untick(size_tick, update.remaining_size)?,
+                        consumed_price: untick(price_tick, update.consumed_price)?,
+                        commission: untick(symbol.commission_tick(), update.commission)?,
+                    })
+                })?
+            ),
+
+            Notification::OrderExpiration(expiration) => Event::OrderExpiration(
+                Stamped::from_timestamped(expiration, |expiration| {
+                    Ok(OrderExpirationEvent {
+                        order_id: expiration.order_id.clone(),
+                        filled_size: untick_option(size_tick, expiration.filled_size)?,
+                        remaining_size: untick_option(size_tick, expiration.remaining_size)?,
+                    })
+                })?
+            ),
+
+            Notification::ExchangeStatus(status) => Event::ExchangeStatus(
+                Stamped::from_timestamped(status, |status| {
+                    let status = match status {
+                        api::ExchangeStatus::Normal => SchemaExchangeStatus::Normal,
+                        api::ExchangeStatus::Degraded => SchemaExchangeStatus::Degraded,
+                        api::ExchangeStatus::Maintenance => SchemaExchangeStatus::Maintenance,
+                        api::ExchangeStatus::Outage => SchemaExchangeStatus::Outage,
+                    };
+                    Ok(ExchangeStatusEvent { status })
+                })?
+            ),
+
+            Notification::PositionUpdate(position) => Event::PositionUpdate(
+                Stamped::from_timestamped(position, |position| {
+                    Ok(PositionEvent {
+                        contract: position.symbol.clone(),
+                        side: position.side.into(),
+                        size: position.size.clone(),
+                        entry_price: position.entry_price.clone(),
+                        unrealized_pnl: position.unrealized_pnl.clone(),
+                        leverage: position.leverage,
+                    })
+                })?
+            ),
+
+            Notification::IndexPrice(index) => Event::IndexPrice(
+                Stamped::from_timestamped(index, |index| {
+                    Ok(IndexPriceEvent {
+                        index: index.index.clone(),
+                        price: index.price.clone(),
+                    })
+                })?
+            ),
+
+            Notification::TradingHalted(status) => Event::TradingHalted(
+                Stamped::from_timestamped(status, trading_status_event)?
+            ),
+
+            Notification::TradingResumed(status) => Event::TradingResumed(
+                Stamped::from_timestamped(status, trading_status_event)?
+            ),
+
+            Notification::AuctionUpdate(auction) => Event::AuctionUpdate(
+                Stamped::from_timestamped(auction, |auction| {
+                    Ok(AuctionEvent {
+                        phase: auction.phase.clone(),
+                        indicative_price: untick_option(price_tick, auction.indicative_price)?,
+                        indicative_size: untick_option(size_tick, auction.indicative_size)?,
+                    })
+                })?
+            ),
+        };
+
+        Ok(Envelope {
+            version: SCHEMA_VERSION,
+            symbol: symbol.name().to_owned(),
+            event,
+        })
+    }
+
+    /// Convert `self` back to a notification of `symbol`. Envelopes written with any
+    /// version up to `SCHEMA_VERSION` are supported.
+    pub fn into_notification(self, symbol: Symbol) -> Result<Notification, SchemaError> {
+        if self.version > SCHEMA_VERSION {
+            return Err(SchemaError::UnsupportedVersion(self.version));
+        }
+
+        if self.symbol != symbol.name() {
+            return Err(SchemaError::SymbolMismatch(self.symbol));
+        }
+
+        let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+
+        let notification = match self.event {
+            Event::Trade(trade) => Notification::Trade(trade.into_timestamped(|trade| {
+                Ok(Trade {
+                    price: tick(price_tick, &trade.price)?,
+                    size: tick(size_tick, &trade.size)?,
+                    maker_side: trade.maker_side.into(),
+                })
+            })?),
+
+            Event::LimitUpdates { updates } => Notification::LimitUpdates(
+                updates.into_iter()
+                    .map(|update| update.into_timestamped(|update| {
+                        Ok(LimitUpdate::new(
+                            tick(price_tick, &update.price)?,
+                            tick(size_tick, &update.size)?,
+                            update.side.into(),
+                        ))
+                    }))
+                    .collect::<Result<_, _>>()?
+            ),
+
+            Event::OrderConfirmation(confirmation) => Notification::OrderConfirmation(
+                confirmation.into_timestamped(|confirmation| {
+                    Ok(OrderConfirmation {
+                        price: tick(price_tick, &confirmation.price)?,
+                        size: tick(size_tick, &confirmation.size)?,
+                        side: confirmation.side.into(),
+                        order_id: confirmation.order_id,
+                    })
+                })?
+            ),
+
+            Event::OrderUpdate(update) => Notification::OrderUpdate(
+                update.into_timestamped(|update| {
+                    Ok(OrderUpdate {
+                        consumed_size: tick(size_tick, &update.consumed_size)?,
+                        remaining_size: tick(size_tick, &update.remaining_size)?,
+                        consumed_price: tick(price_tick, &update.consumed_price)?,
+                        commission: tick(symbol.commission_tick(), &update.commission)?,
+                        order_id: update.order_id,
+                    })
+                })?
+            ),
+
+            Event::OrderExpiration(expiration) => Notification::OrderExpiration(
+                expiration.into_timestamped(|expiration| {
+                    Ok(OrderExpiration {
+                        filled_size: tick_option(size_tick, expiration.filled_size)?,
+                        remaining_size: tick_option(size_tick, expiration.remaining_size)?,
+                        order_id: expiration.order_id,
+                    })
+                })?
+            ),
+
+            Event::ExchangeStatus(status) => Notification::ExchangeStatus(
+                status.into_timestamped(|status| {
+                    Ok(match status.status {
+                        SchemaExchangeStatus::Normal => api::ExchangeStatus::Normal,
+                        SchemaExchangeStatus::Degraded => api::ExchangeStatus::Degraded,
+                        SchemaExchangeStatus::Maintenance => api::ExchangeStatus::Maintenance,
+                        SchemaExchangeStatus::Outage => api::ExchangeStatus::Outage,
+                    })
+                })?
+            ),
+
+            Event::PositionUpdate(position) => Notification::PositionUpdate(
+                position.into_timestamped(|position| {
+                    Ok(Position {
+                        symbol: position.contract,
+                        side: position.side.into(),
+                        size: position.size,
+                        entry_price: position.entry_price,
+                        unrealized_pnl: position.unrealized_pnl,
+                        leverage: position.leverage,
+                    })
+                })?
+            ),
+
+            Event::IndexPrice(index) => Notification::IndexPrice(
+                index.into_timestamped(|index| {
+                    Ok(IndexPrice {
+                        index: index.index,
+                        price: index.price,
+                    })
+                })?
+            ),
+
+            Event::TradingHalted(status) => Notification::TradingHalted(
+                status.into_timestamped(trading_status)?
+            ),
+
+            Event::TradingResumed(status) => Notification::TradingResumed(
+                status.into_timestamped(trading_status)?
+            ),
+
+            Event::AuctionUpdate(auction) => Notification::AuctionUpdate(
+                auction.into_timestamped(|auction| {
+                    Ok(AuctionUpdate {
+                        phase: auction.phase,
+                        indicative_price: tick_option(price_tick, auction.indicative_price)?,
+                        indicative_size: tick_option(size_tick, auction.indicative_size)?,
+                    })
+                })?
+            ),
+        };
+
+        Ok(notification)
+    }
+}
+
+fn trading_status_event(status: &TradingStatus) -> Result<TradingStatusEvent, SchemaError> {
+    Ok(TradingStatusEvent {
+        status: status.status.clone(),
+        message: status.message.clone(),
+    })
+}
+
+fn trading_status(status: TradingStatusEvent) -> Result<TradingStatus, SchemaError> {
+    Ok(TradingStatus {
+        status: status.status,
+        message: status.message,
+    })
+}
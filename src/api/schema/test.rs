@@ -0,0 +1,149 @@
+#![cfg(test)]
+
+use serde_json::json;
+use crate::Side;
+use crate::api::{
+    Notification,
+    Trade,
+    OrderUpdate,
+    OrderExpiration,
+    ExchangeStatus,
+    TradingStatus,
+    AuctionUpdate,
+};
+use crate::api::derivatives::Position;
+use crate::api::schema::{Envelope, SchemaError, SCHEMA_VERSION};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::order_book::LimitUpdate;
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTCUSDT", Tick::new(100), Tick::new(1000)).unwrap()
+}
+
+fn round_trip(notification: Notification) {
+    let envelope = Envelope::from_notification(&notification, symbol()).unwrap();
+    let json = serde_json::to_string(&envelope).unwrap();
+    let envelope: Envelope = serde_json::from_str(&json).unwrap();
+    assert_eq!(envelope.into_notification(symbol()).unwrap(), notification);
+}
+
+#[test]
+fn layout() {
+    let trade = Notification::Trade(Trade {
+        price: 370_001,
+        size: 500,
+        maker_side: Side::Ask,
+    }.with_timestamp(1000));
+
+    let envelope = Envelope::from_notification(&trade, symbol()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&envelope).unwrap(),
+        json!({
+            "version": SCHEMA_VERSION,
+            "symbol": "BTCUSDT",
+            "type": "trade",
+            "timestamp": 1000,
+            "price": "3700.01",
+            "size": "0.500",
+            "maker_side": "ask",
+        })
+    );
+
+    let updates = Notification::LimitUpdates(vec![
+        LimitUpdate::new(370_000, 1000, Side::Bid).with_timestamp(10).with_corrected_timestamp(8),
+    ]);
+    let envelope = Envelope::from_notification(&updates, symbol()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&envelope).unwrap(),
+        json!({
+            "version": SCHEMA_VERSION,
+            "symbol": "BTCUSDT",
+            "type": "limit_updates",
+            "updates": [{
+                "timestamp": 10,
+                "corrected_timestamp": 8,
+                "price": "3700.00",
+                "size": "1.000",
+                "side": "bid",
+            }],
+        })
+    );
+}
+
+#[test]
+fn round_trips() {
+    round_trip(Notification::LimitUpdates(vec![
+        LimitUpdate::new(370_000, 1000, Side::Bid).with_timestamp(10).with_corrected_timestamp(8),
+        LimitUpdate::new(370_100, 0, Side::Ask).with_timestamp(11),
+    ]));
+
+    round_trip(Notification::OrderUpdate(OrderUpdate {
+        order_id: "order".to_owned(),
+        consumed_size: 250,
+        remaining_size: 750,
+        consumed_price: 370_050,
+        commission: 1,
+    }.with_timestamp(20)));
+
+    round_trip(Notification::OrderExpiration(OrderExpiration {
+        order_id: "order".to_owned(),
+        filled_size: Some(250),
+        remaining_size: None,
+    }.with_timestamp(30)));
+
+    round_trip(Notification::ExchangeStatus(ExchangeStatus::Maintenance.with_timestamp(40)));
+
+    round_trip(Notification::PositionUpdate(Position {
+        symbol: "BTCUSDT".to_owned(),
+        side: Side::Bid,
+        size: "0.5".to_owned(),
+        entry_price: "3700".to_owned(),
+        unrealized_pnl: "-12.5".to_owned(),
+        leverage: 10,
+    }.with_timestamp(50)));
+
+    round_trip(Notification::TradingHalted(TradingStatus {
+        status: "BREAK".to_owned(),
+        message: None,
+    }.with_timestamp(60)));
+
+    round_trip(Notification::AuctionUpdate(AuctionUpdate {
+        phase: Some("collection".to_owned()),
+        indicative_price: Some(370_000),
+        indicative_size: None,
+    }.with_timestamp(70)));
+}
+
+#[test]
+fn rejected_envelopes() {
+    let status = Notification::ExchangeStatus(ExchangeStatus::Normal.with_timestamp(0));
+    let mut envelope = Envelope::from_notification(&status, symbol()).unwrap();
+
+    let other = Symbol::new("ETHUSDT", Tick::new(100), Tick::new(1000)).unwrap();
+    match envelope.clone().into_notification(other) {
+        Err(SchemaError::SymbolMismatch(symbol)) => assert_eq!(symbol, "BTCUSDT"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    envelope.version = SCHEMA_VERSION + 1;
+    match envelope.into_notification(symbol()) {
+        Err(SchemaError::UnsupportedVersion(version)) => assert_eq!(version, SCHEMA_VERSION + 1),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let envelope: Envelope = serde_json::from_value(json!({
+        "version": 1,
+        "symbol": "BTCUSDT",
+        "type": "trade",
+        "timestamp": 0,
+        "price": "not a price",
+        "size": "1",
+        "maker_side": "bid",
+    })).unwrap();
+    match envelope.into_notification(symbol()) {
+        Err(SchemaError::Conversion(..)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,206 @@
+//! A module defining a sequencer merging the notifications of several venues into
+//! a single time-ordered tape.
+//!
+//! Notifications are ordered by their corrected timestamps (see `api::drift`), which
+//! are expressed in local time. Since notifications from different venues do not
+//! arrive in order, each notification is held for a reorder window before being
+//! released: a notification timestamped `t` is released once the local time reaches
+//! `t + window`, after all the buffered notifications timestamped before `t`.
+//!
+//! A notification arriving after a later one was already released cannot be put back
+//! in order. It is released as soon as possible and flagged as late.
+
+mod test;
+#[cfg(feature = "network")]
+mod stream;
+
+#[cfg(feature = "network")]
+pub use self::stream::SequencerStream;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use crate::api::Notification;
+use crate::api::timestamp::Timestamp;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A notification released by a `Sequencer`.
+pub struct SequencedNotification {
+    /// Index of the venue the notification comes from, as given to `Sequencer::push`.
+    pub venue: usize,
+
+    /// Time used for ordering the notification.
+    pub time: Timestamp,
+
+    /// `true` if a notification with a greater time was released before this one.
+    pub late: bool,
+
+    /// The notification.
+    pub notification: Notification,
+}
+
+#[derive(Debug)]
+struct Entry {
+    time: Timestamp,
+
+    /// Arrival order, for breaking ties.
+    seq: u64,
+
+    venue: usize,
+    notification: Notification,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        (self.time, self.seq) == (other.time, other.seq)
+    }
+}
+
+impl Eq for Entry { }
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> Ordering {
+        (self.time, self.seq).cmp(&(other.time, other.seq))
+    }
+}
+
+/// Return the time used for ordering `notif`, i.e. its latest corrected timestamp.
+fn time(notif: &Notification) -> Option<Timestamp> {
+    match notif {
+        Notification::Trade(trade) => Some(trade.corrected_timestamp()),
+        Notification::LimitUpdates(updates) => {
+            updates.iter().map(|update| update.corrected_timestamp()).max()
+        }
+        Notification::OrderConfirmation(confirmation) => {
+            Some(confirmation.corrected_timestamp())
+        }
+        Notification::OrderUpdate(update) => Some(update.corrected_timestamp()),
+        Notification::OrderExpiration(expiration) => Some(expiration.corrected_timestamp()),
+        Notification::PositionUpdate(position) => Some(position.corrected_timestamp()),
+        Notification::IndexPrice(price) => Some(price.corrected_timestamp()),
+        Notification::ExchangeStatus(status) => Some(status.corrected_timestamp()),
+        Notification::TradingHalted(status) => Some(status.corrected_timestamp()),
+        Notification::TradingResumed(status) => Some(status.corrected_timestamp()),
+        Notification::AuctionUpdate(auction) => Some(auction.corrected_timestamp()),
+    }
+}
+
+#[derive(Debug)]
+/// Merge notifications from several venues into a single time-ordered sequence.
+pub struct Sequencer {
+    window: Timestamp,
+    buffer: BinaryHeap<Reverse<Entry>>,
+    next_seq: u64,
+    released: Option<Timestamp>,
+    late: usize,
+}
+
+impl Sequencer {
+    /// Return a new `Sequencer` holding each notification during `window` ms.
+    pub fn new(window: Timestamp) -> Self {
+        Sequencer {
+            window,
+            buffer: BinaryHeap::new(),
+            next_seq: 0,
+            released: None,
+            late: 0,
+        }
+    }
+
+    /// Return the reorder window, in ms.
+    pub fn window(&self) -> Timestamp {
+        self.window
+    }
+
+    /// Return the number of buffered notifications.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Return `true` if no notification is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Return the number of notifications released late so far.
+    pub fn late(&self) -> usize {
+        self.late
+    }
+
+    /// Buffer `notif`, coming from the venue indexed by `venue`. A `LimitUpdates`
+    /// notification without any update is ignored.
+    pub fn push(&mut self, venue: usize, notif: Notification) {
+        let time = match time(&notif) {
+            Some(time) => time,
+            None => return,
+        };
+
+        self.buffer.push(Reverse(Entry {
+            time,
+            seq: self.next_seq,
+            venue,
+            notification: notif,
+        }));
+        self.next_seq += 1;
+    }
+
+    /// Release the next notification if its reorder window has elapsed at local
+    /// time `now`.
+    pub fn pop(&mut self, now: Timestamp) -> Option<SequencedNotification> {
+        let ready = match self.buffer.peek() {
+            Some(Reverse(entry)) => entry.time.saturating_add(self.window) <= now,
+            None => false,
+        };
+
+        if ready {
+            self.release()
+        } else {
+            None
+        }
+    }
+
+    /// Release all the notifications whose reorder window has elapsed at local time `now`.
+    pub fn drain(&mut self, now: Timestamp) -> Vec<SequencedNotification> {
+        let mut released = Vec::new();
+        while let Some(notif) = self.pop(now) {
+            released.push(notif);
+        }
+        released
+    }
+
+    /// Release all the buffered notifications, e.g. once all the feeds have ended.
+    pub fn flush(&mut self) -> Vec<SequencedNotification> {
+        let mut released = Vec::new();
+        while let Some(notif) = self.release() {
+            released.push(notif);
+        }
+        released
+    }
+
+    fn release(&mut self) -> Option<SequencedNotification> {
+        let Reverse(entry) = self.buffer.pop()?;
+
+        let late = match self.released {
+            Some(released) if entry.time < released => true,
+            _ => {
+                self.released = Some(entry.time);
+                false
+            }
+        };
+        if late {
+            self.late += 1;
+        }
+
+        Some(SequencedNotification {
+            venue: entry.venue,
+            time: entry.time,
+            late,
+            notification: entry.notification,
+        })
+    }
+}
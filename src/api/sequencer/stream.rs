@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use futures::prelude::*;
+use log::error;
+use crate::api::Notification;
+use crate::api::sequencer::{Sequencer, SequencedNotification};
+use crate::api::timestamp::timestamp_ms;
+
+impl Sequencer {
+    /// Drive `self` from the notification streams of several venues, the venue of each
+    /// notification being the index of its stream in `feeds`. Buffered notifications are
+    /// checked every `window / 2`. The returned stream must be run inside a tokio runtime,
+    /// and ends once all the feeds have ended and the buffer has been flushed.
+    pub fn stream<S>(self, feeds: Vec<S>) -> SequencerStream<S>
+        where S: Stream<Item = Notification, Error = ()>
+    {
+        use tokio::timer::Interval;
+
+        let period = Duration::from_millis(std::cmp::max(self.window / 2, 1));
+        SequencerStream {
+            sequencer: self,
+            feeds: feeds.into_iter().map(Some).collect(),
+            interval: Interval::new_interval(period),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// A stream of `SequencedNotification`, see `Sequencer::stream`.
+pub struct SequencerStream<S> {
+    sequencer: Sequencer,
+    feeds: Vec<Option<S>>,
+    interval: tokio::timer::Interval,
+    pending: VecDeque<SequencedNotification>,
+}
+
+impl<S> SequencerStream<S> {
+    /// Return the underlying `Sequencer`.
+    pub fn sequencer(&self) -> &Sequencer {
+        &self.sequencer
+    }
+}
+
+impl<S> Stream for SequencerStream<S> where S: Stream<Item = Notification, Error = ()> {
+    type Item = SequencedNotification;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<SequencedNotification>, ()> {
+        loop {
+            if let Some(notif) = self.pending.pop_front() {
+                return Ok(Async::Ready(Some(notif)));
+            }
+
+            let mut progress = false;
+
+            for (venue, feed) in self.feeds.iter_mut().enumerate() {
+                if let Some(stream) = feed.as_mut() {
+                    match stream.poll()? {
+                        Async::Ready(Some(notif)) => {
+                            self.sequencer.push(venue, notif);
+                            progress = true;
+                        }
+                        Async::Ready(None) => *feed = None,
+                        Async::NotReady => (),
+                    }
+                }
+            }
+
+            match self.interval.poll() {
+                Ok(Async::Ready(_)) => progress = true,
+                Ok(Async::NotReady) => (),
+                Err(err) => error!("sequencer timer encountered error: `{}`", err),
+            }
+
+            if self.feeds.iter().all(|feed| feed.is_none()) {
+                self.pending.extend(self.sequencer.flush());
+                if self.pending.is_empty() {
+                    return Ok(Async::Ready(None));
+                }
+                continue;
+            }
+
+            self.pending.extend(self.sequencer.drain(timestamp_ms()));
+            if self.pending.is_empty() && !progress {
+                return Ok(Async::NotReady);
+            }
+        }
+    }
+}
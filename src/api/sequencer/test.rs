@@ -0,0 +1,76 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade};
+use crate::api::sequencer::Sequencer;
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+
+fn trade(timestamp: Timestamp, price: u64) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size: 1,
+        maker_side: Side::Ask,
+    }.with_timestamp(timestamp))
+}
+
+fn released(sequencer: &mut Sequencer, now: Timestamp) -> Vec<(usize, Timestamp, bool)> {
+    sequencer.drain(now)
+        .into_iter()
+        .map(|notif| (notif.venue, notif.time, notif.late))
+        .collect()
+}
+
+#[test]
+fn reorder_window() {
+    let mut sequencer = Sequencer::new(100);
+    sequencer.push(0, trade(1050, 1));
+    sequencer.push(1, trade(1000, 2));
+    sequencer.push(1, trade(1050, 3));
+
+    assert_eq!(released(&mut sequencer, 1099), vec![]);
+    assert_eq!(released(&mut sequencer, 1100), vec![(1, 1000, false)]);
+
+    sequencer.push(0, trade(1020, 4));
+    assert_eq!(
+        released(&mut sequencer, 1200),
+        vec![(0, 1020, false), (0, 1050, false), (1, 1050, false)]
+    );
+    assert!(sequencer.is_empty());
+}
+
+#[test]
+fn corrected_timestamps() {
+    let mut sequencer = Sequencer::new(10);
+    sequencer.push(0, Notification::Trade(Trade {
+        price: 1,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(1000).with_corrected_timestamp(1200)));
+    sequencer.push(1, Notification::LimitUpdates(vec![
+        LimitUpdate::new(100, 1, Side::Bid).with_timestamp(1090),
+        LimitUpdate::new(101, 1, Side::Ask).with_timestamp(1100),
+    ]));
+    sequencer.push(1, Notification::LimitUpdates(vec![]));
+    assert_eq!(sequencer.len(), 2);
+
+    assert_eq!(released(&mut sequencer, 1500), vec![(1, 1100, false), (0, 1200, false)]);
+}
+
+#[test]
+fn late_notifications() {
+    let mut sequencer = Sequencer::new(100);
+    sequencer.push(0, trade(1000, 1));
+    assert_eq!(released(&mut sequencer, 1100), vec![(0, 1000, false)]);
+
+    sequencer.push(1, trade(990, 2));
+    sequencer.push(1, trade(1010, 3));
+    assert_eq!(released(&mut sequencer, 1100), vec![(1, 990, true)]);
+    assert_eq!(sequencer.late(), 1);
+
+    let flushed: Vec<_> = sequencer.flush()
+        .into_iter()
+        .map(|notif| notif.notification)
+        .collect();
+    assert_eq!(flushed, vec![trade(1010, 3)]);
+}
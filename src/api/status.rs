@@ -0,0 +1,41 @@
+//! A module defining helpers for monitoring the operational status of an exchange.
+
+use std::sync::Arc;
+use std::time::Duration;
+use futures::prelude::*;
+use log::{error, warn};
+use crate::api::{ApiClient, Notification, ExchangeStatus};
+
+/// Poll `client.system_status()` every `period` and forward a
+/// `Notification::ExchangeStatus` each time the status changes. The first
+/// successfully retrieved status is always forwarded.
+///
+/// Failed requests are logged and skipped. The returned stream must be run inside
+/// a tokio runtime, and can be merged with a notification stream using `select`.
+pub fn poll<C>(client: Arc<C>, period: Duration)
+    -> impl Stream<Item = Notification, Error = ()> + Send + 'static
+        where C: ApiClient + Send + Sync + 'static
+{
+    use tokio::timer::Interval;
+
+    let mut last_status: Option<ExchangeStatus> = None;
+
+    Interval::new_interval(period)
+        .map_err(|err| error!("status polling timer encountered error: `{}`", err))
+        .and_then(move |_| client.system_status().then(Ok))
+        .filter_map(move |res| {
+            match res {
+                Ok(status) => {
+                    if last_status == Some(*status) {
+                        return None;
+                    }
+                    last_status = Some(*status);
+                    Some(Notification::ExchangeStatus(status))
+                }
+                Err(err) => {
+                    warn!("system status request encountered error: `{}`", err);
+                    None
+                }
+            }
+        })
+}
@@ -0,0 +1,197 @@
+//! A module defining the notification stream returned by the exchange clients of
+//! this crate.
+
+use std::sync::Arc;
+use std::time::Duration;
+use futures::prelude::*;
+use futures::sync::mpsc::UnboundedReceiver;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::budget;
+use crate::api::filter::NotificationFilter;
+#[cfg(feature = "latency")]
+use crate::api::latency::LatencyStats;
+#[cfg(feature = "affinity")]
+use crate::api::affinity::ThreadConfig;
+use crate::api::wss;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+/// Statistics about a `NotificationStream`.
+pub struct StreamStats {
+    /// Number of messages received from the exchange, including those which were
+    /// filtered out according to the notification flags.
+    pub messages: usize,
+
+    /// Number of messages which could not be handled and were dropped, e.g. because
+    /// of a parsing error.
+    pub errors: usize,
+
+    /// Number of notifications yielded by the stream so far.
+    pub notifications: usize,
+
+    /// Number of notifications dropped by the filter of the stream, see
+    /// `NotificationStream::set_filter`.
+    pub filtered: usize,
+
+    /// Number of limit updates merged into a later update of the same level under the
+    /// memory budget, see `api::budget`.
+    pub conflated: usize,
+
+    /// Number of notifications dropped under the memory budget, see `api::budget`.
+    pub shed: usize,
+
+    /// Time elapsed since the last message was received, or `None` if no message was
+    /// received yet.
+    pub last_message_age: Option<Duration>,
+}
+
+/// A stream of notifications for one symbol, backed by a WebSocket connection.
+///
+/// The type of notifications forwarded can be changed at any time through
+/// `set_flags`: the corresponding channels are subscribed to or unsubscribed from
+/// on the same connection, without reconnecting. Notifications can be further
+/// narrowed down through `set_filter`.
+///
+/// Under a memory budget, the stream degrades instead of queuing notifications
+/// indefinitely, see `api::budget`.
+pub struct NotificationStream {
+    receiver: UnboundedReceiver<Notification>,
+    control: Arc<wss::Control>,
+    notifications: usize,
+}
+
+impl NotificationStream {
+    #[cfg_attr(
+        not(any(
+            feature = "alpaca",
+            feature = "binance",
+            feature = "bitfinex",
+            feature = "bitmex",
+            feature = "bybit",
+            feature = "coinbase",
+            feature = "deribit",
+            feature = "dydx",
+            feature = "gateio",
+            feature = "gdax",
+            feature = "gemini",
+            feature = "hitbtc",
+            feature = "huobi",
+            feature = "kraken",
+            feature = "kraken_futures",
+            feature = "kucoin",
+            feature = "mexc",
+            feature = "okx",
+            feature = "testkit",
+            feature = "upbit",
+        )),
+        allow(dead_code)
+    )]
+    crate fn new(receiver: UnboundedReceiver<Notification>, control: Arc<wss::Control>) -> Self {
+        NotificationStream {
+            receiver,
+            control,
+            notifications: 0,
+        }
+    }
+
+    /// Return the flags indicating which type of notification is currently forwarded.
+    pub fn flags(&self) -> NotificationFlags {
+        self.control.flags()
+    }
+
+    /// Only forward the notifications indicated by `flags` from now on. The change is
+    /// applied asynchronously, hence a few notifications of a removed type may still be
+    /// received afterwards.
+    ///
+    /// Toggling `TRADES` or `ORDERS` leaves the order book untouched. However, enabling
+    /// `ORDER_BOOK` after it has been disabled restarts the order book from a fresh
+    /// snapshot, sent as a single `Notification::LimitUpdates`: the consumer must clear
+    /// its own copy of the book before applying it.
+    pub fn set_flags(&self, flags: NotificationFlags) {
+        self.control.set_flags(flags);
+    }
+
+    /// Return the filter currently applied to the notifications.
+    pub fn filter(&self) -> NotificationFilter {
+        self.control.filter()
+    }
+
+    /// Only forward the notifications meeting `filter` from now on. The filter is
+    /// evaluated before the notifications are queued, so that dropped notifications
+    /// never wake up the consumer.
+    ///
+    /// Changing the side or the depth of the forwarded book levels takes effect with
+    /// the next `Notification::LimitUpdates`, which then brings the consumer's copy of
+    /// the book in line with the new filter.
+    pub fn set_filter(&self, filter: NotificationFilter) {
+        self.control.set_filter(filter);
+    }
+
+    /// Pin the threads of the connections of this stream and set their priority, see
+    /// `api::affinity`. Each connection applies the configuration when it opens or upon
+    /// the next message it receives, errors being logged.
+    #[cfg(feature = "affinity")]
+    pub fn set_thread_config(&self, config: ThreadConfig) {
+        self.control.set_thread_config(config);
+    }
+
+    /// Return statistics about this stream. A growing `last_message_age` indicates that
+    /// the connection is stale.
+    pub fn stats(&self) -> StreamStats {
+        let shedder = self.control.shedder().lock().unwrap();
+        StreamStats {
+            messages: self.control.messages(),
+            errors: self.control.errors(),
+            notifications: self.notifications,
+            filtered: self.control.filtered(),
+            conflated: shedder.conflated(),
+            shed: shedder.shed(),
+            last_message_age: self.control.last_message().map(|instant| instant.elapsed()),
+        }
+    }
+
+    /// Return the latency histograms of the stages the notifications of this stream
+    /// went through, see `api::latency`.
+    #[cfg(feature = "latency")]
+    pub fn latency(&self) -> LatencyStats {
+        *self.control.latency().lock().unwrap().stats()
+    }
+}
+
+impl Stream for NotificationStream {
+    type Item = Notification;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Notification>, ()> {
+        let res = self.receiver.poll();
+        match &res {
+            Ok(Async::Ready(Some(notif))) => {
+                self.notifications += 1;
+
+                self.control.shedder().lock().unwrap()
+                    .on_delivered(budget::footprint(notif), budget::global());
+
+                #[cfg(feature = "latency")]
+                self.control.latency().lock().unwrap().on_delivered(std::time::Instant::now());
+            }
+
+            // Conflated limit updates are delivered once the queue is empty.
+            Ok(Async::NotReady) => {
+                let mut shedder = self.control.shedder().lock().unwrap();
+                if let Some(notif) = shedder.take_pending() {
+                    self.notifications += 1;
+                    return Ok(Async::Ready(Some(notif)));
+                }
+                shedder.park();
+            }
+
+            _ => (),
+        }
+        res
+    }
+}
+
+impl Drop for NotificationStream {
+    fn drop(&mut self) {
+        self.control.shedder().lock().unwrap().close(budget::global());
+    }
+}
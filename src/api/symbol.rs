@@ -4,9 +4,11 @@ use std::ops::Deref;
 use arrayvec::ArrayString;
 use serde_derive::{Serialize, Deserialize};
 use crate::tick::Tick;
+use crate::api::options::OptionSpec;
 
-/// A small string type used for symbol names.
-pub type SymbolName = ArrayString<[u8; 10]>;
+/// A small string type used for symbol names. Long enough for the names of options,
+/// e.g. `BTC-27DEC24-100000-C`.
+pub type SymbolName = ArrayString<[u8; 32]>;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 /// A type carrying information about a traded symbol.
@@ -15,18 +17,61 @@ pub struct Symbol {
     price_tick: Tick,
     size_tick: Tick,
     commission_tick: Tick,
+    #[serde(default)]
+    option_spec: Option<OptionSpec>,
+    #[serde(default)]
+    iceberg_parts: Option<u64>,
 }
 
 impl Symbol {
+    #[cfg_attr(
+        not(any(
+            feature = "alpaca",
+            feature = "binance",
+            feature = "bitfinex",
+            feature = "bitmex",
+            feature = "bybit",
+            feature = "coinbase",
+            feature = "deribit",
+            feature = "dydx",
+            feature = "gateio",
+            feature = "gdax",
+            feature = "gemini",
+            feature = "hitbtc",
+            feature = "huobi",
+            feature = "kraken",
+            feature = "kraken_futures",
+            feature = "kucoin",
+            feature = "mexc",
+            feature = "okx",
+            feature = "testkit",
+            feature = "upbit",
+        )),
+        allow(dead_code)
+    )]
     crate fn new(name: &str, price_tick: Tick, size_tick: Tick) -> Option<Self> {
         Some(Symbol {
             name: SymbolName::from(name).ok()?,
             price_tick,
             size_tick,
             commission_tick: Tick::new(1),
+            option_spec: None,
+            iceberg_parts: None,
         })
     }
 
+    #[cfg_attr(not(feature = "deribit"), allow(dead_code))]
+    crate fn with_option_spec(mut self, option_spec: OptionSpec) -> Self {
+        self.option_spec = Some(option_spec);
+        self
+    }
+
+    #[cfg_attr(not(feature = "binance"), allow(dead_code))]
+    crate fn with_iceberg_parts(mut self, iceberg_parts: u64) -> Self {
+        self.iceberg_parts = Some(iceberg_parts);
+        self
+    }
+
     /// Symbol name.
     pub fn name(&self) -> &str {
         &self.name
@@ -46,6 +91,18 @@ impl Symbol {
     pub fn commission_tick(&self) -> Tick {
         self.commission_tick
     }
+
+    /// Terms of the contract if the symbol is an option, see `api::options`.
+    pub fn option_spec(&self) -> Option<OptionSpec> {
+        self.option_spec
+    }
+
+    /// Maximum number of parts an iceberg order can be split into, i.e. the size of
+    /// the order divided by its display size rounded up, if the exchange enforces one.
+    /// See `Order::with_display_size`.
+    pub fn iceberg_parts(&self) -> Option<u64> {
+        self.iceberg_parts
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
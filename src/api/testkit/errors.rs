@@ -0,0 +1,92 @@
+//! A module defining error types specific to the mock exchange.
+
+use std::fmt;
+use hyper::StatusCode;
+use failure_derive::Fail;
+use crate::api;
+use crate::api::testkit::protocol::ErrorBody;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by the REST API of the mock exchange.
+pub struct RestError {
+    /// HTTP status code.
+    pub status: StatusCode,
+
+    /// Error code, e.g. `duplicate_order`: see `api::testkit::Server`.
+    pub code: Option<String>,
+
+    /// Description of the error.
+    pub message: Option<String>,
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.status == StatusCode::TOO_MANY_REQUESTS {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        // Maintenance or outage.
+        if self.status.is_server_error() {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.code_is("unknown_order") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.code_is("duplicate_order") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::DuplicateOrder
+            );
+        }
+
+        if self.code_is("would_take_liquidity") {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::WouldTakeLiquidity
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl RestError {
+    pub(super) fn from_error_body(status: StatusCode, body: Option<ErrorBody>) -> Self {
+        RestError {
+            status,
+            code: body.as_ref().map(|body| body.code.clone()),
+            message: body.map(|body| body.message),
+        }
+    }
+
+    fn code_is(&self, code: &str) -> bool {
+        self.code.as_ref().map(|own| own == code).unwrap_or(false)
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HTTP status code = {}", self.status)?;
+        if let Some(message) = &self.message {
+            write!(f, ": `{}`", message)?;
+        }
+        if let Some(code) = &self.code {
+            write!(f, " (code = {})", code)?;
+        }
+        Ok(())
+    }
+}
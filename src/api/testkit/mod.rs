@@ -0,0 +1,133 @@
+//! A module implementing a local mock exchange, along with a client connecting to it,
+//! for writing deterministic integration tests of strategies without reaching any
+//! live venue.
+//!
+//! A `MockExchange` is configured with its symbols, then started as a `Server`
+//! listening on the loopback interface. Orders are matched by a `sim::MatchingEngine`
+//! per symbol: the orders of the clients belong to the owner `CLIENT`, while the
+//! test drives the rest of the market by submitting orders of other owners through
+//! the `Server`, or by publishing notifications directly.
+//!
+//! The protocol is a simplified exchange protocol using the types of this crate:
+//! * over HTTP, `GET /symbols`, `/ping`, `/status` and `/balances`, and `POST /order`
+//!   and `/cancel` with a JSON body, prices and sizes being expressed in ticks
+//! * over WebSocket, the client subscribes to the channels of a symbol, and receives
+//!   the corresponding `Notification`s along with the symbol they belong to, starting
+//!   with a snapshot of the book
+//!
+//! Timestamps are those of the wall clock, unless the clock of the server is set with
+//! `Server::set_time`.
+
+pub mod errors;
+mod protocol;
+mod rest;
+mod server;
+mod wss;
+mod test;
+
+pub use self::server::{CLIENT, MockExchange, Server};
+
+use std::collections::HashMap;
+use futures::prelude::*;
+use log::debug;
+use crate::api::{
+    self,
+    ApiClient,
+    GenerateOrderId,
+    Params,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    NotificationFlags,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+/// A client of a mock exchange, see `Server::client`.
+pub struct Client {
+    params: Params,
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper::client::HttpConnector>,
+}
+
+impl Client {
+    /// Connect to the mock exchange at the endpoints of `params`, see `Server::params`.
+    /// The mock exchange does not need any key pair.
+    pub fn new(params: Params) -> Result<Self, failure::Error> {
+        use tokio::runtime::current_thread;
+
+        let mut client = Client {
+            params,
+            symbols: HashMap::new(),
+            http_client: hyper::Client::new(),
+        };
+
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Testkit
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
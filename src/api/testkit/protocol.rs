@@ -0,0 +1,55 @@
+//! Messages of the mock exchange protocol, shared by the server and the client.
+
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::{Notification, OrderType, TimeInForce};
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Body of `POST /order`.
+crate struct NewOrder {
+    crate symbol: String,
+    crate order_id: String,
+    crate side: Side,
+    crate price: TickUnit,
+    crate size: TickUnit,
+    crate order_type: OrderType,
+    crate time_in_force: TimeInForce,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Body of `POST /cancel`.
+crate struct CancelOrder {
+    crate symbol: String,
+    crate order_id: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Body of the responses with an error status code.
+crate struct ErrorBody {
+    /// Error code, e.g. `duplicate_order`.
+    crate code: String,
+    crate message: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+crate enum Channel {
+    Book,
+    Trades,
+    Orders,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Sent by the client for replacing its subscriptions to the channels of `symbol`.
+crate struct Subscribe {
+    crate symbol: String,
+    crate channels: Vec<Channel>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+/// Sent by the server on the channels the client is subscribed to.
+crate struct Message {
+    crate symbol: String,
+    crate notification: Notification,
+}
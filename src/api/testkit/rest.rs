@@ -0,0 +1,172 @@
+use hyper::Method;
+use futures::prelude::*;
+use std::collections::HashMap;
+use failure::Fail;
+use log::warn;
+use crate::api::{
+    self,
+    Order,
+    OrderAck,
+    OrderType,
+    Cancel,
+    CancelAck,
+    ExchangeStatus,
+    GenerateOrderId,
+};
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::testkit::Client;
+use crate::api::testkit::errors::RestError;
+use crate::api::testkit::protocol::{NewOrder, CancelOrder};
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+
+impl Client {
+    /// Parameters are sent in a JSON `body`, if any.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        path: &str,
+        method: Method,
+        body: Option<String>,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        use hyper::Request;
+
+        let address = format!("{}/{}", self.params.rest_endpoint, path);
+        let mut request = Request::builder();
+        request.method(method)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..])
+            .uri(&address);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.map(hyper::Body::from).unwrap_or_else(hyper::Body::empty))
+            .unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            if status != hyper::StatusCode::OK {
+                let error_body = serde_json::from_slice(&body);
+                let error = RestError::from_error_body(status, error_body.ok());
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if order.reduce_only || order.trailing_delta.is_some() || order.display_size.is_some() ||
+            order.type_ == OrderType::Market || order.type_.is_stop()
+        {
+            warn!("called `order` with reduce only, a trailing delta, a display size, a market or a stop order, which are not supported by the mock exchange");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let symbol = order.symbol();
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let body = NewOrder {
+            symbol: symbol.name().to_owned(),
+            order_id: order_id.clone(),
+            side: order.side,
+            price: order.price.ticked(symbol.price_tick()),
+            size: order.size.ticked(symbol.size_tick()),
+            order_type: order.type_,
+            time_in_force: order.time_in_force,
+        };
+
+        let body = match serde_json::to_string(&body) {
+            Ok(body) => body,
+            Err(err) => {
+                panic!("failed to serialize `NewOrder`: `{}`", err);
+            }
+        };
+
+        let fut = self.request("order", Method::POST, Some(body)).and_then(|_| {
+            Ok(OrderAck {
+                order_id,
+            }.timestamped())
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let body = CancelOrder {
+            symbol: cancel.symbol().name().to_owned(),
+            order_id: cancel.order_id.clone(),
+        };
+
+        let body = match serde_json::to_string(&body) {
+            Ok(body) => body,
+            Err(err) => {
+                panic!("failed to serialize `CancelOrder`: `{}`", err);
+            }
+        };
+
+        self.request("cancel", Method::POST, Some(body)).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("ping", Method::GET, None).and_then(|_| {
+            Ok(().timestamped())
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("balances", Method::GET, None).and_then(|body| {
+            let balances = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("status", Method::GET, None).and_then(|body| {
+            let status: ExchangeStatus = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+            Ok(status.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("symbols", Method::GET, None).and_then(|body| {
+            let symbols: Vec<Symbol> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            Ok(symbols.into_iter().map(|symbol| {
+                (symbol.name().to_lowercase(), symbol)
+            }).collect())
+        })
+    }
+}
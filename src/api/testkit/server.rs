@@ -0,0 +1,618 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use futures::prelude::*;
+use futures::sync::oneshot;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::service::service_fn;
+use serde::{Serialize, de::DeserializeOwned};
+use log::{debug, warn, error};
+use crate::api::{
+    Notification,
+    Params,
+    OrderAck,
+    CancelAck,
+    Trade,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::fees::FeeSchedule;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp, IntoTimestamped};
+use crate::api::testkit::Client;
+use crate::api::testkit::protocol::{NewOrder, CancelOrder, ErrorBody, Channel, Subscribe, Message};
+use crate::order_book::OrderBook;
+use crate::sim::{MatchingEngine, LimitOrder, OwnerId, Event, RejectReason};
+use crate::tick::{Tick, TickUnit};
+
+/// Owner of the orders sent by the clients of a mock exchange. The orders submitted
+/// through `Server::submit` should belong to other owners.
+pub const CLIENT: OwnerId = 0;
+
+#[derive(Clone, Debug, Default)]
+/// Configuration of a mock exchange, see the module documentation.
+pub struct MockExchange {
+    symbols: Vec<Symbol>,
+    fee_schedule: FeeSchedule,
+    balances: Balances,
+}
+
+impl MockExchange {
+    /// Return a mock exchange without any symbol, fee or balance.
+    pub fn new() -> Self {
+        MockExchange::default()
+    }
+
+    /// List a symbol, with prices and sizes being expressed in ticks of `price_tick`
+    /// and `size_tick`.
+    ///
+    /// # Panics
+    /// Panic if `name` is longer than a `SymbolName`.
+    pub fn with_symbol(mut self, name: &str, price_tick: Tick, size_tick: Tick) -> Self {
+        let symbol = Symbol::new(name, price_tick, size_tick)
+            .unwrap_or_else(|| panic!("symbol name too long: `{}`", name));
+        self.symbols.push(symbol);
+        self
+    }
+
+    /// Set the fee schedule of the matching engines, the commission of the fills being
+    /// expressed in units of the notional (see `api::fees`). Rebates are not reported.
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = fee_schedule;
+        self
+    }
+
+    /// Set the balances returned to the clients, see `Server::set_balances`.
+    pub fn with_balances(mut self, balances: Balances) -> Self {
+        self.balances = balances;
+        self
+    }
+
+    /// Start the HTTP and WebSocket servers on ephemeral ports of the loopback
+    /// interface, each in its own thread. They are shut down when the returned
+    /// `Server` is dropped.
+    pub fn start(self) -> Result<Server, failure::Error> {
+        let fee_schedule = self.fee_schedule;
+        let books = self.symbols.iter().map(|symbol| {
+            (symbol.name().to_owned(), Book {
+                engine: MatchingEngine::new().with_fee_schedule(fee_schedule.clone()),
+                sizes: HashMap::new(),
+            })
+        }).collect();
+
+        let state = Arc::new(Mutex::new(State {
+            symbols: self.symbols,
+            books,
+            balances: self.balances,
+            status: ExchangeStatus::Normal,
+            clock: None,
+            subscribers: HashMap::new(),
+        }));
+
+        let ws_state = state.clone();
+        let socket = ws::WebSocket::new(move |out| {
+            Connection {
+                out,
+                state: ws_state.clone(),
+            }
+        })?.bind("127.0.0.1:0")?;
+        let ws_address = socket.local_addr()?;
+        let broadcaster = socket.broadcaster();
+
+        thread::spawn(move || {
+            if let Err(err) = socket.run() {
+                error!("mock exchange WebSocket server terminated with error: `{}`", err);
+            }
+        });
+
+        let http_state = state.clone();
+        let (shutdown, signal) = oneshot::channel();
+        let http_server = hyper::Server::try_bind(&([127, 0, 0, 1], 0).into())?
+            .serve(move || {
+                let state = http_state.clone();
+                service_fn(move |request| serve(&state, request))
+            });
+        let http_address = http_server.local_addr();
+        let http_server = http_server
+            .with_graceful_shutdown(signal.map_err(|_| ()))
+            .map_err(|err| error!("mock exchange HTTP server terminated with error: `{}`", err));
+
+        thread::spawn(move || hyper::rt::run(http_server));
+
+        debug!("mock exchange listening at {} and {}", http_address, ws_address);
+        Ok(Server {
+            params: Params {
+                streaming_endpoint: format!("ws://{}", ws_address),
+                rest_endpoint: format!("http://{}", http_address),
+            },
+            state,
+            broadcaster,
+            shutdown: Some(shutdown),
+        })
+    }
+}
+
+/// A running mock exchange, see `MockExchange::start`.
+pub struct Server {
+    params: Params,
+    state: Arc<Mutex<State>>,
+    broadcaster: ws::Sender,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Server {
+    /// Endpoints of the server.
+    pub fn params(&self) -> Params {
+        self.params.clone()
+    }
+
+    /// Return a new client connected to the server.
+    pub fn client(&self) -> Result<Client, failure::Error> {
+        Client::new(self.params())
+    }
+
+    /// Submit an order to the matching engine of `symbol`, and return the resulting
+    /// events. The streams receive the resulting limit updates and trades, and the
+    /// notifications of the orders of `CLIENT` crossed by `order`.
+    ///
+    /// # Panics
+    /// Panic if `symbol` is not listed.
+    pub fn submit(&self, symbol: &str, order: LimitOrder) -> Vec<Event> {
+        self.state.lock().unwrap().submit(symbol, order)
+            .unwrap_or_else(|| panic!("unknown symbol: `{}`", symbol))
+    }
+
+    /// Cancel an order resting in the book of `symbol`, of any owner. Return `None`
+    /// if it could not be found.
+    ///
+    /// # Panics
+    /// Panic if `symbol` is not listed.
+    pub fn cancel(&self, symbol: &str, order_id: &str) -> Option<Event> {
+        let mut state = self.state.lock().unwrap();
+        if !state.books.contains_key(symbol) {
+            panic!("unknown symbol: `{}`", symbol);
+        }
+        state.cancel(symbol, order_id)
+    }
+
+    /// Send `notification` as is to the streams of `symbol` subscribed to it, e.g. for
+    /// simulating trades which did not happen on the book of the server.
+    pub fn publish(&self, symbol: &str, notification: Notification) {
+        self.state.lock().unwrap().publish(symbol, notification);
+    }
+
+    /// Order book of `symbol`.
+    ///
+    /// # Panics
+    /// Panic if `symbol` is not listed.
+    pub fn order_book(&self, symbol: &str) -> OrderBook {
+        match self.state.lock().unwrap().books.get(symbol) {
+            Some(book) => book.engine.order_book().clone(),
+            None => panic!("unknown symbol: `{}`", symbol),
+        }
+    }
+
+    /// Number of streams subscribed to `symbol`. Notifications are only delivered to
+    /// the streams subscribed at the time they happen, so a test should wait for its
+    /// streams to be subscribed before driving the market.
+    pub fn subscribers(&self, symbol: &str) -> usize {
+        self.state.lock().unwrap().subscribers.values()
+            .filter(|subscriber| subscriber.symbol == symbol)
+            .count()
+    }
+
+    /// Freeze the clock of the server at `timestamp`, which is then used for timestamping
+    /// all the notifications.
+    pub fn set_time(&self, timestamp: Timestamp) {
+        self.state.lock().unwrap().clock = Some(timestamp);
+    }
+
+    /// Set the status returned to the clients. Orders and cancels are rejected with an
+    /// HTTP `503` status code under `ExchangeStatus::Maintenance` or
+    /// `ExchangeStatus::Outage`.
+    pub fn set_status(&self, status: ExchangeStatus) {
+        self.state.lock().unwrap().status = status;
+    }
+
+    /// Set the balances returned to the clients. They are not updated by the fills.
+    pub fn set_balances(&self, balances: Balances) {
+        self.state.lock().unwrap().balances = balances;
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Err(err) = self.broadcaster.shutdown() {
+            warn!("failed to shut down the mock exchange WebSocket server: `{}`", err);
+        }
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Status code, error code and description of a failed request.
+type Failure = (StatusCode, &'static str, String);
+
+struct Book {
+    engine: MatchingEngine,
+
+    /// order id => initial size of the orders of `CLIENT`, for reporting their filled
+    /// size when they expire.
+    sizes: HashMap<String, TickUnit>,
+}
+
+struct Subscriber {
+    out: ws::Sender,
+    symbol: String,
+    channels: Vec<Channel>,
+}
+
+struct State {
+    symbols: Vec<Symbol>,
+
+    /// symbol name => book
+    books: HashMap<String, Book>,
+
+    balances: Balances,
+    status: ExchangeStatus,
+
+    /// Frozen time, if any.
+    clock: Option<Timestamp>,
+
+    /// connection id => subscriber
+    subscribers: HashMap<u32, Subscriber>,
+}
+
+/// Channel on which `notification` is sent, or `None` if it is sent to every stream.
+fn channel(notification: &Notification) -> Option<Channel> {
+    match notification {
+        Notification::LimitUpdates(..) => Some(Channel::Book),
+        Notification::Trade(..) => Some(Channel::Trades),
+        Notification::OrderConfirmation(..) |
+        Notification::OrderUpdate(..) |
+        Notification::OrderExpiration(..) => Some(Channel::Orders),
+        _ => None,
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    match serde_json::to_string(value) {
+        Ok(json) => json,
+        Err(err) => panic!("failed to serialize mock exchange message: `{}`", err),
+    }
+}
+
+fn from_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, Failure> {
+    serde_json::from_slice(body).map_err(|err| {
+        (StatusCode::BAD_REQUEST, "invalid_request", err.to_string())
+    })
+}
+
+fn unknown_symbol(symbol: &str) -> Failure {
+    (StatusCode::BAD_REQUEST, "unknown_symbol", format!("unknown symbol: `{}`", symbol))
+}
+
+impl Book {
+    /// Notifications resulting from `event`, where `order` is the order whose
+    /// submission generated it, if any.
+    fn notifications(&mut self, order: Option<&LimitOrder>, event: &Event, timestamp: Timestamp)
+        -> Vec<Notification>
+    {
+        let confirmation = |order: &LimitOrder| Notification::OrderConfirmation(OrderConfirmation {
+            order_id: order.order_id.clone(),
+            price: order.price,
+            size: order.size,
+            side: order.side,
+        }.with_timestamp(timestamp));
+
+        let expiration = |order_id: &str, filled, remaining| {
+            Notification::OrderExpiration(OrderExpiration {
+                order_id: order_id.to_owned(),
+                filled_size: Some(filled),
+                remaining_size: Some(remaining),
+            }.with_timestamp(timestamp))
+        };
+
+        let mut notifs = Vec::new();
+        match (event, order) {
+            (Event::Accepted { owner, .. }, Some(order)) if *owner == CLIENT => {
+                self.sizes.insert(order.order_id.clone(), order.size);
+                notifs.push(confirmation(order));
+            }
+
+            // Like live exchanges do, fill-or-kill orders are confirmed then expired.
+            (Event::Rejected { owner, reason: RejectReason::CannotFill, .. }, Some(order))
+                if *owner == CLIENT =>
+            {
+                notifs.push(confirmation(order));
+                notifs.push(expiration(&order.order_id, 0, order.size));
+            }
+
+            (Event::Fill(fill), _) => {
+                notifs.push(Notification::Trade(Trade {
+                    price: fill.price,
+                    size: fill.size,
+                    maker_side: fill.maker_side,
+                }.with_timestamp(timestamp)));
+
+                let parties = [
+                    (fill.maker_owner, &fill.maker_order_id, fill.maker_remaining, fill.maker_fee),
+                    (fill.taker_owner, &fill.taker_order_id, fill.taker_remaining, fill.taker_fee),
+                ];
+                for (owner, order_id, remaining, fee) in &parties {
+                    if *owner != CLIENT {
+                        continue;
+                    }
+                    if *remaining == 0 {
+                        self.sizes.remove(*order_id);
+                    }
+
+                    // Rebates cannot be represented by a commission.
+                    notifs.push(Notification::OrderUpdate(OrderUpdate {
+                        order_id: (*order_id).clone(),
+                        consumed_size: fill.size,
+                        remaining_size: *remaining,
+                        consumed_price: fill.price,
+                        commission: if *fee > 0 { *fee as TickUnit } else { 0 },
+                    }.with_timestamp(timestamp)));
+                }
+            }
+
+            (Event::Expired { order_id, owner, remaining, .. }, _) if *owner == CLIENT => {
+                let size = self.sizes.remove(order_id).unwrap_or(*remaining);
+                notifs.push(expiration(order_id, size - remaining, *remaining));
+            }
+
+            _ => (),
+        }
+        notifs
+    }
+}
+
+impl State {
+    fn now(&self) -> Timestamp {
+        self.clock.unwrap_or_else(timestamp_ms)
+    }
+
+    fn publish(&self, symbol: &str, notification: Notification) {
+        let channel = channel(&notification);
+        let message = to_json(&Message {
+            symbol: symbol.to_owned(),
+            notification,
+        });
+
+        for subscriber in self.subscribers.values() {
+            let subscribed = subscriber.symbol == symbol && match channel {
+                Some(channel) => subscriber.channels.contains(&channel),
+                None => true,
+            };
+            if !subscribed {
+                continue;
+            }
+
+            if let Err(err) = subscriber.out.send(message.as_str()) {
+                warn!("failed to send a message to a mock exchange client: `{}`", err);
+            }
+        }
+    }
+
+    /// Publish the notifications resulting from `events`, given the book of `symbol`
+    /// was `before` prior to them.
+    fn publish_events(
+        &mut self,
+        symbol: &str,
+        order: Option<&LimitOrder>,
+        before: &OrderBook,
+        events: &[Event]
+    ) {
+        let timestamp = self.now();
+        let book = match self.books.get_mut(symbol) {
+            Some(book) => book,
+            None => return,
+        };
+
+        let mut notifs = Vec::new();
+        for event in events {
+            notifs.extend(book.notifications(order, event, timestamp));
+        }
+
+        let updates: Vec<_> = before.diff(book.engine.order_book())
+            .map(|update| update.with_timestamp(timestamp))
+            .collect();
+        if !updates.is_empty() {
+            notifs.push(Notification::LimitUpdates(updates));
+        }
+
+        for notif in notifs {
+            self.publish(symbol, notif);
+        }
+    }
+
+    fn submit(&mut self, symbol: &str, order: LimitOrder) -> Option<Vec<Event>> {
+        let book = self.books.get_mut(symbol)?;
+        let before = book.engine.order_book().clone();
+        let events = book.engine.submit(order.clone());
+        self.publish_events(symbol, Some(&order), &before, &events);
+        Some(events)
+    }
+
+    fn cancel(&mut self, symbol: &str, order_id: &str) -> Option<Event> {
+        let book = self.books.get_mut(symbol)?;
+        let before = book.engine.order_book().clone();
+        let event = book.engine.cancel(order_id)?;
+        self.publish_events(symbol, None, &before, std::slice::from_ref(&event));
+        Some(event)
+    }
+
+    fn check_status(&self) -> Result<(), Failure> {
+        match self.status {
+            ExchangeStatus::Maintenance | ExchangeStatus::Outage => Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "unavailable",
+                format!("exchange status: {:?}", self.status)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn order(&mut self, order: NewOrder) -> Result<OrderAck, Failure> {
+        self.check_status()?;
+
+        let NewOrder { symbol, order_id, side, price, size, order_type, time_in_force } = order;
+        let order = LimitOrder::new(order_id.clone(), CLIENT, side, price, size)
+            .with_order_type(order_type)
+            .with_time_in_force(time_in_force);
+
+        let events = self.submit(&symbol, order).ok_or_else(|| unknown_symbol(&symbol))?;
+        let code = match events.first() {
+            Some(Event::Rejected { reason, .. }) => match reason {
+                RejectReason::DuplicateOrder => "duplicate_order",
+                RejectReason::WouldTakeLiquidity => "would_take_liquidity",
+                RejectReason::InvalidSize => "invalid_size",
+                RejectReason::UnsupportedOrderType => "unsupported_order_type",
+                RejectReason::CannotFill => return Ok(OrderAck { order_id }),
+            },
+            _ => return Ok(OrderAck { order_id }),
+        };
+        Err((StatusCode::BAD_REQUEST, code, format!("order rejected: `{}`", order_id)))
+    }
+
+    fn cancel_order(&mut self, cancel: CancelOrder) -> Result<CancelAck, Failure> {
+        self.check_status()?;
+
+        let CancelOrder { symbol, order_id } = cancel;
+        let owned = self.books.get(&symbol)
+            .ok_or_else(|| unknown_symbol(&symbol))?
+            .engine.order(&order_id)
+            .map(|order| order.owner == CLIENT)
+            .unwrap_or(false);
+
+        // The orders of the other owners are not visible to the clients.
+        if !owned {
+            return Err((
+                StatusCode::NOT_FOUND,
+                "unknown_order",
+                format!("unknown order id: `{}`", order_id)
+            ));
+        }
+
+        self.cancel(&symbol, &order_id);
+        Ok(CancelAck)
+    }
+
+    /// Replace the subscriptions of the connection `out`. A snapshot of the book is sent
+    /// to it if the book was not part of its previous subscriptions.
+    fn subscribe(&mut self, out: &ws::Sender, subscribe: Subscribe) {
+        let Subscribe { symbol, channels } = subscribe;
+        if !self.books.contains_key(&symbol) {
+            warn!("mock exchange client subscribed to unknown symbol `{}`", symbol);
+        }
+
+        let had_book = self.subscribers.get(&out.connection_id())
+            .map(|subscriber| {
+                subscriber.symbol == symbol && subscriber.channels.contains(&Channel::Book)
+            })
+            .unwrap_or(false);
+        let snapshot = !had_book && channels.contains(&Channel::Book);
+
+        self.subscribers.insert(out.connection_id(), Subscriber {
+            out: out.clone(),
+            symbol: symbol.clone(),
+            channels,
+        });
+
+        let timestamp = self.now();
+        let updates: Vec<_> = match self.books.get(&symbol) {
+            Some(book) if snapshot => OrderBook::new().diff(book.engine.order_book())
+                .map(|update| update.with_timestamp(timestamp))
+                .collect(),
+            _ => return,
+        };
+        if updates.is_empty() {
+            return;
+        }
+
+        let message = to_json(&Message {
+            symbol,
+            notification: Notification::LimitUpdates(updates),
+        });
+        if let Err(err) = out.send(message) {
+            warn!("failed to send a message to a mock exchange client: `{}`", err);
+        }
+    }
+
+    fn route(&mut self, method: &Method, path: &str, body: &[u8]) -> Result<String, Failure> {
+        match (method, path) {
+            (&Method::GET, "/symbols") => Ok(to_json(&self.symbols)),
+            (&Method::GET, "/ping") => Ok(to_json(&())),
+            (&Method::GET, "/status") => Ok(to_json(&self.status)),
+            (&Method::GET, "/balances") => Ok(to_json(&self.balances)),
+            (&Method::POST, "/order") => {
+                let ack = self.order(from_json(body)?)?;
+                Ok(to_json(&ack))
+            }
+            (&Method::POST, "/cancel") => {
+                let ack = self.cancel_order(from_json(body)?)?;
+                Ok(to_json(&ack))
+            }
+            _ => Err((
+                StatusCode::NOT_FOUND,
+                "not_found",
+                format!("unknown endpoint: `{} {}`", method, path)
+            )),
+        }
+    }
+}
+
+fn serve(state: &Arc<Mutex<State>>, request: Request<Body>)
+    -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send
+{
+    let state = state.clone();
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+
+    request.into_body().concat2().map(move |body| {
+        let (status, body) = match state.lock().unwrap().route(&method, &path, &body) {
+            Ok(body) => (StatusCode::OK, body),
+            Err((status, code, message)) => (status, to_json(&ErrorBody {
+                code: code.to_owned(),
+                message,
+            })),
+        };
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    })
+}
+
+/// Connection of a client to the WebSocket server.
+struct Connection {
+    out: ws::Sender,
+    state: Arc<Mutex<State>>,
+}
+
+impl ws::Handler for Connection {
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let subscribe: Subscribe = match &msg {
+            ws::Message::Text(text) => serde_json::from_str(text),
+            ws::Message::Binary(data) => serde_json::from_slice(data),
+        }.map_err(|err| {
+            ws::Error::new(ws::ErrorKind::Protocol, format!("invalid subscription: `{}`", err))
+        })?;
+
+        self.state.lock().unwrap().subscribe(&self.out, subscribe);
+        Ok(())
+    }
+
+    fn on_close(&mut self, _: ws::CloseCode, _: &str) {
+        self.state.lock().unwrap().subscribers.remove(&self.out.connection_id());
+    }
+}
@@ -0,0 +1,172 @@
+#![cfg(test)]
+
+use std::thread;
+use std::time::Duration;
+use futures::prelude::*;
+use tokio::runtime::current_thread::Runtime;
+use crate::Side;
+use crate::api::{
+    ApiClient,
+    Notification,
+    Order,
+    OrderType,
+    Cancel,
+    Trade,
+    ExchangeStatus,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+};
+use crate::api::errors::{ApiError, ErrorKind, RestErrorKind, OrderErrorKind, CancelErrorKind};
+use crate::api::symbol::IntoWithSymbol;
+use crate::api::testkit::{Client, MockExchange, Server};
+use crate::api::timestamp::IntoTimestamped;
+use crate::order_book::LimitUpdate;
+use crate::sim::LimitOrder;
+use crate::tick::{Tick, TickUnit};
+
+const SYMBOL: &str = "BTCUSD";
+
+fn server() -> Server {
+    let server = MockExchange::new()
+        .with_symbol(SYMBOL, Tick::new(100), Tick::new(1000))
+        .start()
+        .unwrap();
+    server.set_time(1);
+    server
+}
+
+fn limits(updates: &[(TickUnit, TickUnit, Side)]) -> Notification {
+    Notification::LimitUpdates(
+        updates.iter()
+            .map(|(price, size, side)| LimitUpdate::new(*price, *size, *side).with_timestamp(1))
+            .collect()
+    )
+}
+
+fn update(order_id: &str, price: TickUnit, size: TickUnit, remaining: TickUnit) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: size,
+        remaining_size: remaining,
+        consumed_price: price,
+        commission: 0,
+    }.with_timestamp(1))
+}
+
+fn trade(price: TickUnit, size: TickUnit, maker_side: Side) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size,
+        maker_side,
+    }.with_timestamp(1))
+}
+
+fn kind<K: ErrorKind>(err: ApiError<K>) -> Option<RestErrorKind<K>> {
+    match err {
+        ApiError::RestError(err) => Some(err.kind()),
+        ApiError::RequestError(..) => None,
+    }
+}
+
+/// Wait for the streams of the clients of `server` to be subscribed.
+fn wait_subscribed(server: &Server, count: usize) {
+    while server.subscribers(SYMBOL) < count {
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn taker() {
+    let server = server();
+    server.submit(SYMBOL, LimitOrder::new("ask".to_owned(), 1, Side::Ask, 101, 5));
+
+    let client = server.client().unwrap();
+    let symbol = client.find_symbol("btcusd").unwrap();
+    let mut stream = client.stream(symbol).wait();
+    wait_subscribed(&server, 1);
+
+    // The stream starts with a snapshot of the book.
+    assert_eq!(stream.next(), Some(Ok(limits(&[(101, 5, Side::Ask)]))));
+
+    let mut runtime = Runtime::new().unwrap();
+    let order = Order::new(102_u64, 2_u64, Side::Bid).with_order_id::<Client>("bid");
+    let ack = runtime.block_on(client.order(order.with_symbol(symbol))).unwrap();
+    assert_eq!(ack.order_id, "bid");
+
+    assert_eq!(stream.next(), Some(Ok(Notification::OrderConfirmation(OrderConfirmation {
+        order_id: "bid".to_owned(),
+        price: 102,
+        size: 2,
+        side: Side::Bid,
+    }.with_timestamp(1)))));
+    assert_eq!(stream.next(), Some(Ok(trade(101, 2, Side::Ask))));
+    assert_eq!(stream.next(), Some(Ok(update("bid", 101, 2, 0))));
+    assert_eq!(stream.next(), Some(Ok(limits(&[(101, 3, Side::Ask)]))));
+
+    let order = Order::new(101_u64, 1_u64, Side::Bid)
+        .with_order_type(OrderType::LimitMaker)
+        .with_order_id::<Client>("maker");
+    let err = runtime.block_on(client.order(order.with_symbol(symbol))).unwrap_err();
+    assert_eq!(kind(err), Some(RestErrorKind::Specific(OrderErrorKind::WouldTakeLiquidity)));
+}
+
+#[test]
+fn maker() {
+    let server = server();
+    let client = server.client().unwrap();
+    let symbol = client.find_symbol(SYMBOL).unwrap();
+    let mut stream = client.stream(symbol).wait();
+    wait_subscribed(&server, 1);
+
+    let mut runtime = Runtime::new().unwrap();
+    let order = Order::new(99_u64, 3_u64, Side::Bid).with_order_id::<Client>("bid");
+    runtime.block_on(client.order(order.with_symbol(symbol))).unwrap();
+    let err = runtime.block_on(client.order(order.with_symbol(symbol))).unwrap_err();
+    assert_eq!(kind(err), Some(RestErrorKind::Specific(OrderErrorKind::DuplicateOrder)));
+
+    assert_eq!(stream.next(), Some(Ok(Notification::OrderConfirmation(OrderConfirmation {
+        order_id: "bid".to_owned(),
+        price: 99,
+        size: 3,
+        side: Side::Bid,
+    }.with_timestamp(1)))));
+    assert_eq!(stream.next(), Some(Ok(limits(&[(99, 3, Side::Bid)]))));
+
+    server.submit(SYMBOL, LimitOrder::new("ask".to_owned(), 1, Side::Ask, 99, 1));
+    assert_eq!(stream.next(), Some(Ok(trade(99, 1, Side::Bid))));
+    assert_eq!(stream.next(), Some(Ok(update("bid", 99, 1, 2))));
+    assert_eq!(stream.next(), Some(Ok(limits(&[(99, 2, Side::Bid)]))));
+
+    let cancel = Cancel::new("bid".to_owned());
+    runtime.block_on(client.cancel(cancel.with_symbol(symbol))).unwrap();
+    assert_eq!(stream.next(), Some(Ok(Notification::OrderExpiration(OrderExpiration {
+        order_id: "bid".to_owned(),
+        filled_size: Some(1),
+        remaining_size: Some(2),
+    }.with_timestamp(1)))));
+    assert_eq!(stream.next(), Some(Ok(limits(&[(99, 0, Side::Bid)]))));
+
+    let err = runtime.block_on(client.cancel(cancel.with_symbol(symbol))).unwrap_err();
+    assert_eq!(kind(err), Some(RestErrorKind::Specific(CancelErrorKind::UnknownOrder)));
+}
+
+#[test]
+fn status() {
+    let server = server();
+    let client = server.client().unwrap();
+    let symbol = client.find_symbol(SYMBOL).unwrap();
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime.block_on(client.ping()).unwrap();
+    let status = runtime.block_on(client.system_status()).unwrap();
+    assert_eq!(*status, ExchangeStatus::Normal);
+
+    server.set_status(ExchangeStatus::Maintenance);
+    let status = runtime.block_on(client.system_status()).unwrap();
+    assert_eq!(*status, ExchangeStatus::Maintenance);
+
+    let order = Order::new(99_u64, 3_u64, Side::Bid);
+    let err = runtime.block_on(client.order(order.with_symbol(symbol))).unwrap_err();
+    assert_eq!(kind(err), Some(RestErrorKind::OtherSide));
+}
@@ -0,0 +1,100 @@
+use futures::sync::mpsc::unbounded;
+use std::{mem, thread};
+use log::{debug, error};
+use crate::api::NotificationFlags;
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::testkit::Client;
+use crate::api::testkit::protocol::{Channel, Subscribe, Message};
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let address = self.params.streaming_endpoint.clone();
+
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", address);
+
+            if let Err(err) = ws::connect(address, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// The notifications are sent as is by the server, which already filters them
+/// according to the subscribed channels.
+struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+}
+
+impl HandlerImpl {
+    /// Each subscription replaces the previous one of the connection.
+    fn send_subscription(&self, out: &ws::Sender) -> ws::Result<()> {
+        let mut channels = Vec::new();
+        if self.flags.contains(NotificationFlags::ORDER_BOOK) {
+            channels.push(Channel::Book);
+        }
+        if self.flags.contains(NotificationFlags::TRADES) {
+            channels.push(Channel::Trades);
+        }
+        if self.flags.contains(NotificationFlags::ORDERS) {
+            channels.push(Channel::Orders);
+        }
+
+        let subscribe = Subscribe {
+            symbol: self.symbol.name().to_owned(),
+            channels,
+        };
+
+        match serde_json::to_string(&subscribe) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `Subscribe`: `{}`", err);
+            }
+        }
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.send_subscription(out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: Message = serde_json::from_str(text)?;
+        if message.symbol == self.symbol.name() {
+            out.unbounded_send(message.notification).unwrap();
+        }
+        Ok(())
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+        if previous != flags {
+            self.send_subscription(out)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! A module defining an outbound queue for the requests sent to an exchange, which
+//! keeps them under the rate limit of the exchange.
+//!
+//! Requests are released in the order they were pushed as long as the rate limit
+//! allows it. Once the limit is saturated, the requests are held in three lanes, and
+//! the next one to be released is taken from the most critical non empty lane:
+//! cancels first, then the orders reducing the risk of the account (e.g. closing a
+//! position), then the other orders. Whether an order reduces the risk is left to the
+//! caller, which knows about its positions.
+
+mod test;
+#[cfg(feature = "network")]
+mod stream;
+
+#[cfg(feature = "network")]
+pub use self::stream::ThrottleStream;
+
+use std::collections::VecDeque;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::timestamp::Timestamp;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+/// Priority class of a request, in increasing order of priority.
+pub enum Priority {
+    /// Any other order.
+    Normal,
+
+    /// An order reducing the exposure of the account.
+    RiskReducing,
+
+    /// A cancel request.
+    Cancel,
+}
+
+impl Priority {
+    fn lane(self) -> usize {
+        match self {
+            Priority::Cancel => 0,
+            Priority::RiskReducing => 1,
+            Priority::Normal => 2,
+        }
+    }
+
+    fn from_lane(lane: usize) -> Self {
+        match lane {
+            0 => Priority::Cancel,
+            1 => Priority::RiskReducing,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A rate limit, as enforced by most exchanges: at most `requests` requests within any
+/// window of `interval` ms.
+pub struct RateLimit {
+    /// Maximum number of requests per window.
+    pub requests: usize,
+
+    /// Length of the window, in ms.
+    pub interval: Timestamp,
+}
+
+impl RateLimit {
+    /// Return a limit of `requests` requests per `interval` ms.
+    pub fn new(requests: usize, interval: Timestamp) -> Self {
+        RateLimit {
+            requests,
+            interval,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A queue of outbound requests, releasing them within a rate limit and by priority
+/// once the limit is saturated.
+pub struct Throttle<T> {
+    limit: RateLimit,
+
+    /// Indexed by `Priority::lane`.
+    lanes: [VecDeque<T>; 3],
+
+    /// Release times within the current window, oldest first.
+    released: VecDeque<Timestamp>,
+}
+
+impl<T> Throttle<T> {
+    /// Return a new empty queue enforcing `limit`.
+    ///
+    /// # Panics
+    /// Panic if `limit.requests` is zero.
+    pub fn new(limit: RateLimit) -> Self {
+        assert!(limit.requests > 0, "a rate limit must allow at least one request");
+
+        Throttle {
+            limit,
+            lanes: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            released: VecDeque::new(),
+        }
+    }
+
+    /// Return the enforced rate limit.
+    pub fn rate_limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Return the number of requests waiting to be released.
+    pub fn len(&self) -> usize {
+        self.lanes.iter().map(|lane| lane.len()).sum()
+    }
+
+    /// Return `true` if no request is waiting to be released.
+    pub fn is_empty(&self) -> bool {
+        self.lanes.iter().all(|lane| lane.is_empty())
+    }
+
+    /// Return the number of requests of the given priority waiting to be released.
+    pub fn len_of(&self, priority: Priority) -> usize {
+        self.lanes[priority.lane()].len()
+    }
+
+    /// Number of requests released within the window ending at local time `now`.
+    fn in_window(&self, now: Timestamp) -> usize {
+        self.released.iter()
+            .filter(|time| time.saturating_add(self.limit.interval) > now)
+            .count()
+    }
+
+    /// Return `true` if no more request can be released at local time `now`.
+    pub fn is_saturated(&self, now: Timestamp) -> bool {
+        self.in_window(now) >= self.limit.requests
+    }
+
+    /// Return the local time from which the next request can be released, or `None`
+    /// if no request is waiting.
+    pub fn next_release(&self, now: Timestamp) -> Option<Timestamp> {
+        if self.is_empty() {
+            return None;
+        }
+
+        if !self.is_saturated(now) {
+            return Some(now);
+        }
+
+        // The window must slide past the oldest release still counted.
+        let in_window = self.in_window(now);
+        let oldest = self.released.len() - in_window;
+        self.released.get(oldest).map(|time| time.saturating_add(self.limit.interval))
+    }
+
+    /// Queue `request` with the given priority.
+    pub fn push(&mut self, priority: Priority, request: T) {
+        self.lanes[priority.lane()].push_back(request);
+    }
+
+    /// Release the next request if the rate limit allows it at local time `now`.
+    pub fn pop(&mut self, now: Timestamp) -> Option<(Priority, T)> {
+        if self.is_saturated(now) {
+            return None;
+        }
+
+        let lane = self.lanes.iter().position(|lane| !lane.is_empty())?;
+        let request = self.lanes[lane].pop_front()?;
+
+        while let Some(&time) = self.released.front() {
+            if time.saturating_add(self.limit.interval) > now {
+                break;
+            }
+            self.released.pop_front();
+        }
+        self.released.push_back(now);
+
+        Some((Priority::from_lane(lane), request))
+    }
+
+    /// Release all the requests allowed by the rate limit at local time `now`.
+    pub fn drain(&mut self, now: Timestamp) -> Vec<(Priority, T)> {
+        let mut released = Vec::new();
+        while let Some(request) = self.pop(now) {
+            released.push(request);
+        }
+        released
+    }
+}
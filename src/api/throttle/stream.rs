@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+use futures::prelude::*;
+use log::error;
+use crate::api::throttle::{Throttle, Priority};
+use crate::api::timestamp::timestamp_ms;
+
+impl<T> Throttle<T> {
+    /// Drive `self` from a stream of requests along with their priority, releasing them
+    /// within the rate limit. The returned stream must be run inside a tokio runtime,
+    /// and ends once `requests` has ended and all the requests have been released.
+    pub fn stream<S>(self, requests: S) -> ThrottleStream<S, T>
+        where S: Stream<Item = (Priority, T), Error = ()>
+    {
+        ThrottleStream {
+            throttle: self,
+            requests: Some(requests),
+            delay: None,
+        }
+    }
+}
+
+/// A stream of requests released within a rate limit, see `Throttle::stream`.
+pub struct ThrottleStream<S, T> {
+    throttle: Throttle<T>,
+    requests: Option<S>,
+
+    /// Set while the rate limit is saturated.
+    delay: Option<tokio::timer::Delay>,
+}
+
+impl<S, T> ThrottleStream<S, T> {
+    /// Return the underlying `Throttle`.
+    pub fn throttle(&self) -> &Throttle<T> {
+        &self.throttle
+    }
+}
+
+impl<S, T> Stream for ThrottleStream<S, T> where S: Stream<Item = (Priority, T), Error = ()> {
+    type Item = (Priority, T);
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<(Priority, T)>, ()> {
+        // Queue all the requests received so far, so that the priorities apply to them.
+        while let Some(requests) = self.requests.as_mut() {
+            match requests.poll()? {
+                Async::Ready(Some((priority, request))) => self.throttle.push(priority, request),
+                Async::Ready(None) => self.requests = None,
+                Async::NotReady => break,
+            }
+        }
+
+        loop {
+            let now = timestamp_ms();
+            if let Some(request) = self.throttle.pop(now) {
+                self.delay = None;
+                return Ok(Async::Ready(Some(request)));
+            }
+
+            let next_release = match self.throttle.next_release(now) {
+                Some(next_release) => next_release,
+                None if self.requests.is_none() => return Ok(Async::Ready(None)),
+                None => return Ok(Async::NotReady),
+            };
+
+            let deadline = Instant::now() + Duration::from_millis(next_release - now);
+            let delay = self.delay.get_or_insert_with(|| tokio::timer::Delay::new(deadline));
+            delay.reset(deadline);
+
+            match delay.poll() {
+                Ok(Async::Ready(())) => continue,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => {
+                    error!("throttle timer encountered error: `{}`", err);
+                    return Ok(Async::NotReady);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+#![cfg(test)]
+
+use crate::api::throttle::*;
+
+#[test]
+fn fifo_within_limit() {
+    let mut throttle = Throttle::new(RateLimit::new(3, 1000));
+    throttle.push(Priority::Normal, 1);
+    throttle.push(Priority::Cancel, 2);
+    throttle.push(Priority::Normal, 3);
+
+    assert_eq!(throttle.next_release(0), Some(0));
+    assert_eq!(throttle.pop(0), Some((Priority::Cancel, 2)));
+    assert_eq!(throttle.pop(0), Some((Priority::Normal, 1)));
+    assert_eq!(throttle.pop(0), Some((Priority::Normal, 3)));
+    assert_eq!(throttle.pop(0), None);
+    assert!(throttle.is_empty());
+    assert_eq!(throttle.next_release(0), None);
+}
+
+#[test]
+fn priority_when_saturated() {
+    let mut throttle = Throttle::new(RateLimit::new(2, 1000));
+    throttle.push(Priority::Normal, "order 1");
+    throttle.push(Priority::Normal, "order 2");
+    throttle.push(Priority::Normal, "order 3");
+    assert_eq!(throttle.drain(0).len(), 2);
+    assert!(throttle.is_saturated(500));
+
+    throttle.push(Priority::RiskReducing, "close");
+    throttle.push(Priority::Cancel, "cancel");
+    throttle.push(Priority::Normal, "order 4");
+    assert_eq!(throttle.len(), 4);
+    assert_eq!(throttle.len_of(Priority::Normal), 2);
+    assert_eq!(throttle.pop(500), None);
+
+    assert_eq!(
+        throttle.drain(1000),
+        vec![(Priority::Cancel, "cancel"), (Priority::RiskReducing, "close")]
+    );
+    assert_eq!(
+        throttle.drain(2000),
+        vec![(Priority::Normal, "order 3"), (Priority::Normal, "order 4")]
+    );
+}
+
+#[test]
+fn sliding_window() {
+    let mut throttle = Throttle::new(RateLimit::new(2, 1000));
+    for i in 0..4 {
+        throttle.push(Priority::Normal, i);
+    }
+
+    assert_eq!(throttle.pop(0), Some((Priority::Normal, 0)));
+    assert_eq!(throttle.pop(600), Some((Priority::Normal, 1)));
+    assert!(throttle.is_saturated(600));
+
+    // The first release leaves the window at 1000, the second one at 1600.
+    assert_eq!(throttle.next_release(700), Some(1000));
+    assert_eq!(throttle.pop(999), None);
+    assert_eq!(throttle.pop(1000), Some((Priority::Normal, 2)));
+    assert_eq!(throttle.next_release(1000), Some(1600));
+    assert_eq!(throttle.pop(1599), None);
+    assert_eq!(throttle.pop(1600), Some((Priority::Normal, 3)));
+}
@@ -0,0 +1,154 @@
+//! A module defining a best bid / offer ticker derived from the limit updates of a
+//! notification stream, for venues which do not provide a native ticker channel.
+//!
+//! The order book is maintained from the limit updates, and a new top of the book is
+//! output only when it actually changes: updates deeper in the book are ignored. The
+//! ticker can additionally be debounced, so that at most one top of the book is output
+//! per interval, the latest one being output at the end of the interval.
+
+mod test;
+#[cfg(feature = "network")]
+mod stream;
+
+#[cfg(feature = "network")]
+pub use self::stream::TickerStream;
+
+use crate::api::Notification;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::TopOfBook;
+use crate::order_book::OrderBook;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Which changes of the top of the book trigger a new output.
+pub enum Trigger {
+    /// A change of the best bid or ask price.
+    Price,
+
+    /// A change of the best bid or ask price, or of the size available at these prices.
+    PriceOrSize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// Derive the top of the book from a sequence of notifications.
+pub struct Ticker {
+    order_book: OrderBook,
+    trigger: Trigger,
+    min_interval: Timestamp,
+    last: Option<TopOfBook>,
+    last_output_at: Option<Timestamp>,
+    pending: Option<Timestamped<TopOfBook>>,
+}
+
+impl Ticker {
+    /// Return a new `Ticker` triggered by price or size changes, without debouncing.
+    pub fn new() -> Self {
+        Ticker {
+            order_book: OrderBook::new(),
+            trigger: Trigger::PriceOrSize,
+            min_interval: 0,
+            last: None,
+            last_output_at: None,
+            pending: None,
+        }
+    }
+
+    /// Set which changes of the top of the book trigger a new output.
+    pub fn with_trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Output at most one top of the book every `min_interval` ms.
+    pub fn with_min_interval(mut self, min_interval: Timestamp) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Return the chosen minimum interval between two outputs, in ms.
+    pub fn min_interval(&self) -> Timestamp {
+        self.min_interval
+    }
+
+    /// Return the maintained order book.
+    pub fn order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    /// Return `true` if a change of the top of the book is waiting for the end of the
+    /// current interval, see `check`.
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn triggers(&self, top: &TopOfBook) -> bool {
+        let last = match &self.last {
+            Some(last) => last,
+            None => return true,
+        };
+
+        match self.trigger {
+            Trigger::Price => {
+                (top.bid_price, top.ask_price) != (last.bid_price, last.ask_price)
+            }
+            Trigger::PriceOrSize => top != last,
+        }
+    }
+
+    /// Apply the limit updates of `notif`, received at local time `now` (in ms), and
+    /// return the new top of the book if it has changed and the minimum interval since
+    /// the last output has elapsed. The returned value carries the timestamp of the
+    /// latest limit update.
+    pub fn on_notification(&mut self, notif: &Notification, now: Timestamp)
+        -> Option<Timestamped<TopOfBook>>
+    {
+        let updates = match notif {
+            Notification::LimitUpdates(updates) if !updates.is_empty() => updates,
+            _ => return None,
+        };
+
+        let mut timestamp = 0;
+        for update in updates {
+            timestamp = std::cmp::max(timestamp, update.timestamp());
+            self.order_book.update(**update);
+        }
+
+        let top = TopOfBook::from_order_book(&self.order_book);
+        if self.triggers(&top) {
+            self.pending = Some(top.with_timestamp(timestamp));
+        } else {
+            // The top of the book went back to the last output before the end of
+            // the interval.
+            self.pending = None;
+        }
+        self.check(now)
+    }
+
+    /// Return the pending top of the book if the minimum interval since the last output
+    /// has elapsed at local time `now`. Should be called regularly when debouncing.
+    pub fn check(&mut self, now: Timestamp) -> Option<Timestamped<TopOfBook>> {
+        if let Some(last_output_at) = self.last_output_at {
+            if now < last_output_at.saturating_add(self.min_interval) {
+                return None;
+            }
+        }
+
+        let top = self.pending.take()?;
+        self.last = Some(*top);
+        self.last_output_at = Some(now);
+        Some(top)
+    }
+
+    /// Return the pending top of the book regardless of the minimum interval, e.g. once
+    /// the underlying stream has ended.
+    pub fn flush(&mut self) -> Option<Timestamped<TopOfBook>> {
+        let top = self.pending.take()?;
+        self.last = Some(*top);
+        Some(top)
+    }
+}
+
+impl Default for Ticker {
+    fn default() -> Self {
+        Ticker::new()
+    }
+}
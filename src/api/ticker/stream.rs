@@ -0,0 +1,87 @@
+use std::time::Duration;
+use futures::prelude::*;
+use log::error;
+use crate::api::Notification;
+use crate::api::ticker::Ticker;
+use crate::api::timestamp::{timestamp_ms, Timestamped};
+use crate::data::resample::TopOfBook;
+
+impl Ticker {
+    /// Drive `self` from a notification stream. When debouncing, the pending top of the
+    /// book is checked every `min_interval`. The returned stream must be run inside a
+    /// tokio runtime, and ends when `notifications` ends.
+    pub fn stream<S>(self, notifications: S) -> TickerStream<S>
+        where S: Stream<Item = Notification, Error = ()>
+    {
+        use tokio::timer::Interval;
+
+        let interval = if self.min_interval > 0 {
+            Some(Interval::new_interval(Duration::from_millis(self.min_interval)))
+        } else {
+            None
+        };
+
+        TickerStream {
+            ticker: self,
+            notifications: Some(notifications),
+            interval,
+        }
+    }
+}
+
+/// A stream of top of the book changes, see `Ticker::stream`.
+pub struct TickerStream<S> {
+    ticker: Ticker,
+    notifications: Option<S>,
+    interval: Option<tokio::timer::Interval>,
+}
+
+impl<S> TickerStream<S> {
+    /// Return the underlying `Ticker`.
+    pub fn ticker(&self) -> &Ticker {
+        &self.ticker
+    }
+}
+
+impl<S> Stream for TickerStream<S> where S: Stream<Item = Notification, Error = ()> {
+    type Item = Timestamped<TopOfBook>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Timestamped<TopOfBook>>, ()> {
+        loop {
+            let notif = match self.notifications.as_mut() {
+                Some(notifications) => notifications.poll()?,
+                None => return Ok(Async::Ready(None)),
+            };
+
+            match notif {
+                Async::Ready(Some(notif)) => {
+                    if let Some(top) = self.ticker.on_notification(&notif, timestamp_ms()) {
+                        return Ok(Async::Ready(Some(top)));
+                    }
+                    continue;
+                }
+                Async::Ready(None) => {
+                    self.notifications = None;
+                    return Ok(Async::Ready(self.ticker.flush()));
+                }
+                Async::NotReady => (),
+            }
+
+            if let Some(interval) = self.interval.as_mut() {
+                match interval.poll() {
+                    Ok(Async::Ready(_)) => {
+                        if let Some(top) = self.ticker.check(timestamp_ms()) {
+                            return Ok(Async::Ready(Some(top)));
+                        }
+                        continue;
+                    }
+                    Ok(Async::NotReady) => (),
+                    Err(err) => error!("ticker timer encountered error: `{}`", err),
+                }
+            }
+
+            return Ok(Async::NotReady);
+        }
+    }
+}
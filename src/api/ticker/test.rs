@@ -0,0 +1,78 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::Notification;
+use crate::api::ticker::{Ticker, Trigger};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::data::resample::TopOfBook;
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn lu(timestamp: Timestamp, price: TickUnit, size: TickUnit, side: Side) -> Notification {
+    Notification::LimitUpdates(vec![LimitUpdate::new(price, size, side).with_timestamp(timestamp)])
+}
+
+fn top(bid_price: TickUnit, bid_size: TickUnit, ask_price: TickUnit, ask_size: TickUnit)
+    -> TopOfBook
+{
+    TopOfBook { bid_price, bid_size, ask_price, ask_size }
+}
+
+#[test]
+fn top_changes() {
+    let mut ticker = Ticker::new();
+    let snapshot = Notification::LimitUpdates(vec![
+        LimitUpdate::new(100, 5, Side::Bid).with_timestamp(10),
+        LimitUpdate::new(99, 5, Side::Bid).with_timestamp(10),
+        LimitUpdate::new(102, 3, Side::Ask).with_timestamp(10),
+    ]);
+    assert_eq!(ticker.on_notification(&snapshot, 0), Some(top(100, 5, 102, 3).with_timestamp(10)));
+
+    // Deep book change.
+    assert_eq!(ticker.on_notification(&lu(20, 99, 1, Side::Bid), 0), None);
+
+    assert_eq!(
+        ticker.on_notification(&lu(30, 100, 4, Side::Bid), 0),
+        Some(top(100, 4, 102, 3).with_timestamp(30))
+    );
+
+    let mut ticker = Ticker::new().with_trigger(Trigger::Price);
+    ticker.on_notification(&snapshot, 0);
+    assert_eq!(ticker.on_notification(&lu(30, 100, 4, Side::Bid), 0), None);
+    assert_eq!(
+        ticker.on_notification(&lu(40, 101, 1, Side::Bid), 0),
+        Some(top(101, 1, 102, 3).with_timestamp(40))
+    );
+}
+
+#[test]
+fn debouncing() {
+    let mut ticker = Ticker::new().with_min_interval(100);
+    assert_eq!(
+        ticker.on_notification(&lu(10, 100, 5, Side::Bid), 1000),
+        Some(top(100, 5, TickUnit::max_value(), 0).with_timestamp(10))
+    );
+
+    assert_eq!(ticker.on_notification(&lu(20, 100, 4, Side::Bid), 1020), None);
+    assert_eq!(ticker.on_notification(&lu(30, 100, 3, Side::Bid), 1030), None);
+    assert!(ticker.has_pending());
+    assert_eq!(ticker.check(1099), None);
+    assert_eq!(
+        ticker.check(1100),
+        Some(top(100, 3, TickUnit::max_value(), 0).with_timestamp(30))
+    );
+    assert!(!ticker.has_pending());
+
+    // The top of the book changes then goes back to the last output.
+    assert_eq!(ticker.on_notification(&lu(40, 100, 2, Side::Bid), 1150), None);
+    assert_eq!(ticker.on_notification(&lu(50, 100, 3, Side::Bid), 1160), None);
+    assert_eq!(ticker.check(1300), None);
+
+    assert_eq!(
+        ticker.on_notification(&lu(60, 100, 1, Side::Bid), 1310),
+        Some(top(100, 1, TickUnit::max_value(), 0).with_timestamp(60))
+    );
+    assert_eq!(ticker.on_notification(&lu(70, 101, 1, Side::Bid), 1320), None);
+    assert_eq!(ticker.flush(), Some(top(101, 1, TickUnit::max_value(), 0).with_timestamp(70)));
+    assert_eq!(ticker.flush(), None);
+}
@@ -20,6 +20,8 @@ pub fn timestamp_ms() -> Timestamp {
 /// Wrapper around a type carrying an additional timestamp. Deref to `T`.
 pub struct Timestamped<T> {
     timestamp: Timestamp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    corrected_timestamp: Option<Timestamp>,
     #[serde(flatten)]
     inner: T,
 }
@@ -30,6 +32,19 @@ impl<T> Timestamped<T> {
         self.timestamp
     }
 
+    /// Registered timestamp corrected for the clock drift of its source, see
+    /// `api::drift`. Equal to `self.timestamp()` if no correction was applied.
+    pub fn corrected_timestamp(&self) -> Timestamp {
+        self.corrected_timestamp.unwrap_or(self.timestamp)
+    }
+
+    /// Attach a timestamp corrected for the clock drift of its source, the registered
+    /// timestamp is kept as is.
+    pub fn with_corrected_timestamp(mut self, corrected_timestamp: Timestamp) -> Self {
+        self.corrected_timestamp = Some(corrected_timestamp);
+        self
+    }
+
     /// Return the wrapped value.
     pub fn into_inner(self) -> T {
         self.inner
@@ -50,6 +65,7 @@ pub trait IntoTimestamped: Sized {
     fn timestamped(self) -> Timestamped<Self> {
         Timestamped {
             timestamp: timestamp_ms(),
+            corrected_timestamp: None,
             inner: self,
         }
     }
@@ -58,6 +74,7 @@ pub trait IntoTimestamped: Sized {
     fn with_timestamp(self, timestamp: Timestamp) -> Timestamped<Self> {
         Timestamped {
             timestamp,
+            corrected_timestamp: None,
             inner: self,
         }
     }
@@ -65,9 +82,29 @@ pub trait IntoTimestamped: Sized {
 
 impl<T: Sized> IntoTimestamped for T { }
 
+#[cfg(any(
+    feature = "alpaca",
+    feature = "bitmex",
+    feature = "coinbase",
+    feature = "dydx",
+    feature = "gdax",
+    feature = "hitbtc",
+    feature = "kraken_futures",
+))]
 crate fn convert_str_timestamp(timestamp: &str) -> Result<u64, chrono::ParseError> {
     use chrono::{DateTime, Utc};
 
     let time = timestamp.parse::<DateTime<Utc>>()?;
     Ok((time.timestamp() as u64) * 1000 + u64::from(time.timestamp_subsec_millis()))
 }
+
+/// Format `timestamp` as an RFC 3339 date with a millisecond precision, e.g.
+/// `2018-06-04T10:15:30.123Z`.
+#[cfg(feature = "hitbtc")]
+crate fn format_timestamp(timestamp: Timestamp) -> String {
+    use chrono::{SecondsFormat, TimeZone, Utc};
+
+    Utc.timestamp_millis_opt(timestamp as i64)
+        .unwrap()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
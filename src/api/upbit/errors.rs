@@ -0,0 +1,171 @@
+//! A module defining error types specific to Upbit.
+
+use std::fmt;
+use hyper::StatusCode;
+use failure_derive::Fail;
+use serde_derive::Deserialize;
+use crate::api;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct UpbitErrorBody<'a> {
+    name: &'a str,
+    message: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub(super) struct UpbitRestError<'a> {
+    #[serde(borrow)]
+    error: UpbitErrorBody<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// An error returned by Upbit REST API.
+pub struct RestError {
+    /// Error kind.
+    pub kind: RestErrorKind,
+
+    /// Upbit error name, e.g. `insufficient_funds_bid`: see API documentation.
+    pub error_name: Option<String>,
+
+    /// Description of the error.
+    pub error_msg: Option<String>,
+}
+
+impl api::errors::ErrorKinded<!> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<!> {
+        if self.kind == RestErrorKind::TooManyRequests {
+            return api::errors::RestErrorKind::TooManyRequests;
+        }
+
+        if self.kind == RestErrorKind::Timeout {
+            return api::errors::RestErrorKind::UnknownStatus;
+        }
+
+        // Invalid or expired key, invalid token, reused nonce, IP address not
+        // whitelisted, missing permission.
+        if self.kind == RestErrorKind::Unauthorized || self.name_is(&[
+            "invalid_access_key",
+            "expired_access_key",
+            "jwt_verification",
+            "invalid_query_payload",
+            "nonce_used",
+            "no_authorization_i_p",
+            "out_of_scope",
+        ]) {
+            return api::errors::RestErrorKind::Unauthorized;
+        }
+
+        if self.kind == RestErrorKind::InternalError
+            || self.kind == RestErrorKind::ServiceUnavailable
+        {
+            return api::errors::RestErrorKind::OtherSide;
+        }
+
+        api::errors::RestErrorKind::InvalidRequest
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::CancelErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::CancelErrorKind> {
+        if self.name_is(&["order_not_found"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::CancelErrorKind::UnknownOrder
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl api::errors::ErrorKinded<api::errors::OrderErrorKind> for RestError {
+    fn kind(&self) -> api::errors::RestErrorKind<api::errors::OrderErrorKind> {
+        if self.name_is(&["insufficient_funds_bid", "insufficient_funds_ask"]) {
+            return api::errors::RestErrorKind::Specific(
+                api::errors::OrderErrorKind::InsufficientBalance
+            );
+        }
+
+        <Self as api::errors::ErrorKinded<!>>::kind(self).into()
+    }
+}
+
+impl RestError {
+    pub(super) fn from_upbit_error(status: StatusCode, upbit_error: Option<UpbitRestError>)
+        -> Self
+    {
+        RestError {
+            kind: RestErrorKind::from_status_code(status),
+            error_name: upbit_error.as_ref().map(|error| error.error.name.to_owned()),
+            error_msg: upbit_error.map(|error| error.error.message.to_owned()),
+        }
+    }
+
+    fn name_is(&self, names: &[&str]) -> bool {
+        match &self.error_name {
+            Some(name) => names.contains(&name.as_str()),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(error_msg) = &self.error_msg {
+            write!(f, ": `{}`", error_msg)?;
+        }
+        if let Some(error_name) = &self.error_name {
+            write!(f, " (error_name = {})", error_name)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Fail)]
+/// Translate an HTTP error code to an Upbit error category.
+pub enum RestErrorKind {
+    #[fail(display = "bad request")]
+    /// Malformed or rejected request, issue on the lib side or consumer side.
+    BadRequest,
+
+    #[fail(display = "unauthorized")]
+    /// Invalid key or signature, or missing permission for the API key.
+    Unauthorized,
+
+    #[fail(display = "too many requests")]
+    /// The client broke the request rate limit set by Upbit. See Upbit API
+    /// documentation for the rate limits.
+    TooManyRequests,
+
+    #[fail(display = "internal server error")]
+    /// Issue on Upbit side.
+    InternalError,
+
+    #[fail(display = "service unavailable")]
+    /// Service is down or busy.
+    ServiceUnavailable,
+
+    #[fail(display = "timeout")]
+    /// The server did not respond in time. The order may have been executed or may have not.
+    Timeout,
+
+    #[fail(display = "unknown error, HTTP status code = {}", _0)]
+    /// Unknown error.
+    Unknown(StatusCode),
+}
+
+impl RestErrorKind {
+    fn from_status_code(code: StatusCode) -> Self {
+        use self::RestErrorKind::*;
+        match code {
+            StatusCode::OK => panic!("`RestErrorKind::from_status_code` with `StatusCode::Ok`"),
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => BadRequest,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => InternalError,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ServiceUnavailable,
+            StatusCode::GATEWAY_TIMEOUT => Timeout,
+            other => Unknown(other),
+        }
+    }
+}
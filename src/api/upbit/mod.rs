@@ -0,0 +1,271 @@
+//! Implementation of `ApiClient` for the Upbit API, restricted to the KRW markets.
+
+pub mod errors;
+mod rest;
+mod wss;
+mod test;
+
+use openssl::{sign::Signer, hash::MessageDigest, pkey::{PKey, Private}};
+use std::collections::HashMap;
+use futures::prelude::*;
+use log::debug;
+use serde_derive::{Serialize, Deserialize};
+use crate::tick::TickUnit;
+use crate::api::{
+    self,
+    ApiClient,
+    GenerateOrderId,
+    Params,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    NotificationFlags,
+    Balances,
+    ExchangeStatus,
+};
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::Timestamped;
+
+/// Number of ticks per KRW of the prices of every market, i.e. the finest price unit
+/// of the tiers, see `price_unit`.
+const PRICE_TICK: u64 = 100_000_000;
+
+/// Number of ticks per unit of the sizes of every market.
+const SIZE_TICK: u64 = 100_000_000;
+
+/// Price units of the KRW markets, as `(lowest price, unit)` in KRW ticks, from the
+/// highest tier to the lowest one.
+const PRICE_UNITS: [(TickUnit, TickUnit); 16] = [
+    (2_000_000 * PRICE_TICK, 1_000 * PRICE_TICK),
+    (1_000_000 * PRICE_TICK, 1_000 * PRICE_TICK),
+    (500_000 * PRICE_TICK, 500 * PRICE_TICK),
+    (100_000 * PRICE_TICK, 100 * PRICE_TICK),
+    (50_000 * PRICE_TICK, 50 * PRICE_TICK),
+    (10_000 * PRICE_TICK, 10 * PRICE_TICK),
+    (5_000 * PRICE_TICK, 5 * PRICE_TICK),
+    (1_000 * PRICE_TICK, PRICE_TICK),
+    (100 * PRICE_TICK, PRICE_TICK),
+    (10 * PRICE_TICK, PRICE_TICK / 10),
+    (PRICE_TICK, PRICE_TICK / 100),
+    (PRICE_TICK / 10, PRICE_TICK / 1_000),
+    (PRICE_TICK / 100, PRICE_TICK / 10_000),
+    (PRICE_TICK / 1_000, PRICE_TICK / 100_000),
+    (PRICE_TICK / 10_000, PRICE_TICK / 1_000_000),
+    (PRICE_TICK / 100_000, PRICE_TICK / 10_000_000),
+];
+
+/// Return the price unit of the KRW markets at `price`, both expressed in the price
+/// ticks of the symbols returned by this client.
+///
+/// Upbit does not have a fixed tick size per market: the unit of a price depends on
+/// the tier it falls in, e.g. 1,000 KRW above 1,000,000 KRW, but 1 KRW between 100
+/// and 5,000 KRW. The symbols thus carry the finest unit of all the tiers, and order
+/// prices must additionally be a multiple of the unit returned by this function,
+/// orders not meeting this requirement being rejected without reaching Upbit.
+pub fn price_unit(price: TickUnit) -> TickUnit {
+    PRICE_UNITS.iter()
+        .find(|(lowest, _)| price >= *lowest)
+        .map(|(_, unit)| *unit)
+        .unwrap_or(1)
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An Upbit key pair: access key + secret key.
+pub struct KeyPair {
+    access_key: String,
+    secret_key: String,
+}
+
+impl KeyPair {
+    /// Return a new key pair.
+    pub fn new(access_key: String, secret_key: String) -> Self {
+        KeyPair {
+            access_key,
+            secret_key,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Keys {
+    access_key: String,
+    secret_key: PKey<Private>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct JwtHeader<'a> {
+    alg: &'a str,
+    typ: &'a str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct JwtClaims<'a> {
+    access_key: &'a str,
+    nonce: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_hash_alg: Option<&'a str>,
+}
+
+impl Keys {
+    /// Return a JSON Web Token signed with HS256. Requests with parameters carry a
+    /// token bound to the SHA512 hash of their `query`, i.e. of the parameters in the
+    /// query string format, be they sent in the query string or in a JSON body.
+    fn jwt(&self, query: Option<&str>) -> String {
+        use uuid::Uuid;
+
+        let header = JwtHeader {
+            alg: "HS256",
+            typ: "JWT",
+        };
+        let claims = JwtClaims {
+            access_key: &self.access_key,
+            nonce: Uuid::new_v4().to_string(),
+            query_hash: query.map(|query| {
+                hex::encode(&openssl::sha::sha512(query.as_bytes())[..])
+            }),
+            query_hash_alg: query.map(|_| "SHA512"),
+        };
+
+        let encode = |value: &[u8]| base64::encode_config(value, base64::URL_SAFE_NO_PAD);
+        let message = format!(
+            "{}.{}",
+            encode(serde_json::to_string(&header).unwrap().as_bytes()),
+            encode(serde_json::to_string(&claims).unwrap().as_bytes())
+        );
+
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.secret_key).unwrap();
+        signer.update(message.as_bytes()).unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+
+        format!("{}.{}", message, encode(&signature))
+    }
+}
+
+/// An Upbit API client, restricted to the KRW markets, e.g. `KRW-BTC`.
+///
+/// Prices are subject to tiered units, see `price_unit`. Market data and order events
+/// are pushed over a single WebSocket connection, authenticated when a key pair is
+/// given. The order book is limited to the 15 best levels of each side.
+pub struct Client {
+    params: Params,
+    keys: Option<Keys>,
+    symbols: HashMap<String, Symbol>,
+    http_client: hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+}
+
+impl Client {
+    /// Create a new Upbit API client with given `params`, e.g. with the usual
+    /// `https://api.upbit.com/v1` and `wss://api.upbit.com/websocket/v1` endpoints.
+    /// If `key_pair` is not `None`, this will enable performing requests to the REST
+    /// API and will forward the order events.
+    ///
+    /// # Note
+    /// This method will block, fetching the available symbols from Upbit.
+    pub fn new(params: Params, key_pair: Option<KeyPair>) -> Result<Self, failure::Error> {
+        use tokio::runtime::current_thread;
+
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+
+        let keys = match key_pair {
+            Some(pair) => Some(Keys {
+                access_key: pair.access_key,
+                secret_key: PKey::hmac(pair.secret_key.as_bytes())?,
+            }),
+            None => None,
+        };
+
+        let mut client = Client {
+            params,
+            keys,
+            symbols: HashMap::new(),
+            http_client,
+        };
+
+        debug!("requesting symbols");
+        client.symbols = current_thread::Runtime::new()?
+            .block_on(client.get_symbols())?;
+        debug!("received symbols");
+        Ok(client)
+    }
+}
+
+impl ApiClient for Client {
+    type Stream = NotificationStream;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Upbit
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.symbols.get(&symbol.to_lowercase()).cloned()
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        self.new_stream(symbol, flags)
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        self.order_impl(order)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.cancel_impl(cancel))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.ping_impl())
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = api::errors::Error> + Send + 'static>
+    {
+        if self.keys.is_none() {
+            return Box::new(futures::future::err(api::errors::ApiError::unauthorized()));
+        }
+
+        Box::new(self.balances_impl())
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static>
+    {
+        Box::new(self.system_status_impl())
+    }
+}
+
+impl GenerateOrderId for Client {
+    /// Orders sent without an order id are given one, so that they can be canceled
+    /// by their identifier. Upbit requires identifiers to be unique over the lifetime
+    /// of the account, not only among the open orders.
+    fn new_order_id(hint: &str) -> String {
+        if !hint.is_empty() && hint.len() <= 64 &&
+            hint.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            hint.to_owned()
+        } else {
+            use uuid::Uuid;
+            Uuid::new_v4().simple().to_string()
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use hyper::Method;
+use futures::prelude::*;
+use std::collections::HashMap;
+use failure::Fail;
+use serde_derive::{Serialize, Deserialize};
+use log::{warn, error};
+use crate::Side;
+use crate::tick::Tick;
+use crate::api::{
+    self,
+    OrderType,
+    TimeInForce,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    ExchangeStatus,
+    GenerateOrderId,
+};
+use crate::api::query_string::QueryString;
+use crate::api::errors::ErrorKinded;
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::upbit::{Client, PRICE_TICK, SIZE_TICK, price_unit};
+use crate::api::upbit::errors::RestError;
+use crate::api::timestamp::{Timestamped, IntoTimestamped};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+struct UpbitOrder<'a> {
+    market: &'a str,
+    side: &'a str,
+    volume: &'a str,
+    price: &'a str,
+    ord_type: &'a str,
+    identifier: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_in_force: Option<&'a str>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct UpbitBalance<'a> {
+    currency: &'a str,
+    balance: &'a str,
+    locked: &'a str,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct UpbitMarket<'a> {
+    market: &'a str,
+}
+
+trait AsStr {
+    fn as_str(self) -> &'static str;
+}
+
+impl AsStr for Side {
+    fn as_str(self) -> &'static str {
+        match self {
+            Side::Ask => "ask",
+            Side::Bid => "bid",
+        }
+    }
+}
+
+impl AsStr for TimeInForce {
+    fn as_str(self) -> &'static str {
+        match self {
+            TimeInForce::GoodTilCanceled => "gtc",
+            TimeInForce::ImmediateOrCancel => "ioc",
+            TimeInForce::FillOrKilll => "fok",
+            TimeInForce::GoodTilDate(_) => unreachable!("rejected by `order_impl`"),
+        }
+    }
+}
+
+impl Client {
+    /// Parameters are sent in the query string, or in a JSON `body` if any, the token
+    /// of `signed` requests being bound to their query string form in both cases.
+    fn request<K: api::errors::ErrorKind>(
+        &self,
+        path: &str,
+        method: Method,
+        query: QueryString,
+        body: Option<String>,
+        signed: bool,
+    ) -> impl Future<Item = hyper::Chunk, Error = api::errors::ApiError<K>> + Send + 'static
+            where RestError: ErrorKinded<K>
+    {
+        use hyper::Request;
+
+        let mut request = Request::builder();
+        let query = query.into_string();
+
+        if signed {
+            let keys = self.keys.as_ref().expect("signed request without keys");
+            let token = if query.is_empty() {
+                keys.jwt(None)
+            } else {
+                keys.jwt(Some(&query))
+            };
+            request.header("Authorization", format!("Bearer {}", token).as_bytes());
+        }
+
+        let address = if body.is_some() || query.is_empty() {
+            format!("{}/{}", self.params.rest_endpoint, path)
+        } else {
+            format!("{}/{}?{}", self.params.rest_endpoint, path, query)
+        };
+
+        request.method(method)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..])
+            .uri(&address);
+
+        // Unwrap because it is a bug if this fails (header failed to parse or something)
+        let request = request.body(body.map(hyper::Body::from).unwrap_or_else(hyper::Body::empty))
+            .unwrap();
+        self.http_client.request(request).and_then(|res| {
+            let status = res.status();
+            res.into_body().concat2().and_then(move |body| {
+                Ok((status, body))
+            })
+        })
+        .map_err(api::errors::RequestError::new)
+        .map_err(api::errors::ApiError::RequestError)
+        .and_then(|(status, body)| {
+            // New orders are answered with `201 Created`.
+            if !status.is_success() {
+                let upbit_error = serde_json::from_slice(&body);
+                let error = RestError::from_upbit_error(status, upbit_error.ok());
+                let kind = error.kind();
+                Err(
+                    api::errors::ApiError::RestError(error.context(kind).into())
+                )?;
+            }
+            Ok(body)
+        })
+    }
+
+    crate fn order_impl(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = api::errors::OrderError> + Send + 'static>
+    {
+        use std::borrow::Borrow;
+
+        if order.time_in_force.expiration().is_some() {
+            warn!("called `order` with `GoodTilDate`, which is not supported by Upbit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_ == OrderType::Market || order.quote_size {
+            warn!("called `order` with a market order or a quote size, which are not supported by Upbit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_.is_stop() {
+            warn!("called `order` with a stop order, which is not supported by Upbit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.reduce_only || order.trailing_delta.is_some() || order.display_size.is_some() {
+            warn!("called `order` with reduce only, a trailing delta or a display size, which are not supported on Upbit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        if order.type_ == OrderType::LimitMaker {
+            warn!("called `order` with `OrderType::LimitMaker`, which is not supported on Upbit");
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let symbol = order.symbol();
+        let ticked_price = order.price.ticked(symbol.price_tick());
+        if ticked_price % price_unit(ticked_price) != 0 {
+            warn!(
+                "called `order` with a price of {} ticks, which is not a multiple of the \
+                 price unit of its tier ({} ticks)",
+                ticked_price,
+                price_unit(ticked_price)
+            );
+            return Box::new(futures::future::err(
+                api::errors::ApiError::RestError(api::errors::RestErrorKind::InvalidRequest.into())
+            ));
+        }
+
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id.clone(),
+            None => Self::new_order_id(""),
+        };
+
+        let volume = order.size.unticked(symbol.size_tick());
+        let price = order.price.unticked(symbol.price_tick());
+        let time_in_force = match order.time_in_force {
+            TimeInForce::GoodTilCanceled => None,
+            other => Some(other.as_str()),
+        };
+
+        let body = UpbitOrder {
+            market: symbol.name(),
+            side: order.side.as_str(),
+            volume: volume.borrow() as &str,
+            price: price.borrow() as &str,
+            ord_type: "limit",
+            identifier: &order_id,
+            time_in_force,
+        };
+
+        let mut query = QueryString::new();
+        query.push_str("market", body.market);
+        query.push_str("side", body.side);
+        query.push_str("volume", body.volume);
+        query.push_str("price", body.price);
+        query.push_str("ord_type", body.ord_type);
+        query.push_str("identifier", body.identifier);
+        if let Some(time_in_force) = body.time_in_force {
+            query.push_str("time_in_force", time_in_force);
+        }
+
+        let body = match serde_json::to_string(&body) {
+            Ok(body) => body,
+            Err(err) => {
+                panic!("failed to serialize `UpbitOrder`: `{}`", err);
+            }
+        };
+
+        let fut = self.request("orders", Method::POST, query, Some(body), true).and_then(|_| {
+            Ok(OrderAck {
+                order_id,
+            }.timestamped())
+        });
+        Box::new(fut)
+    }
+
+    crate fn cancel_impl(&self, cancel: WithSymbol<&Cancel>)
+        -> impl Future<Item = Timestamped<CancelAck>, Error = api::errors::CancelError> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("identifier", &cancel.order_id);
+
+        self.request("order", Method::DELETE, query, None, true).and_then(|_| {
+            Ok(CancelAck.timestamped())
+        })
+    }
+
+    crate fn ping_impl(&self)
+        -> impl Future<Item = Timestamped<()>, Error = api::errors::Error> + Send + 'static
+    {
+        let mut query = QueryString::new();
+        query.push_str("markets", "KRW-BTC");
+
+        self.request("ticker", Method::GET, query, None, false).and_then(|_| {
+            Ok(().timestamped())
+        })
+    }
+
+    crate fn balances_impl(&self)
+        -> impl Future<Item = api::Balances, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("accounts", Method::GET, QueryString::new(), None, true).and_then(|body| {
+            let balances: Vec<UpbitBalance<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let balances = balances.into_iter().map(|balance| {
+                (balance.currency.to_owned(), api::Balance {
+                    free: balance.balance.to_owned(),
+                    locked: balance.locked.to_owned(),
+                })
+            }).collect();
+            Ok(balances)
+        })
+    }
+
+    crate fn system_status_impl(&self)
+        -> impl Future<Item = Timestamped<ExchangeStatus>, Error = api::errors::Error> + Send + 'static
+    {
+        // Upbit has no public status endpoint, being able to reach it is the best we can do.
+        self.ping_impl().and_then(|_| {
+            Ok(ExchangeStatus::Normal.timestamped())
+        })
+    }
+
+    crate fn get_symbols(&self)
+        -> impl Future<Item = HashMap<String, Symbol>, Error = api::errors::Error> + Send + 'static
+    {
+        self.request("market/all", Method::GET, QueryString::new(), None, false).and_then(|body| {
+            let markets: Vec<UpbitMarket<'_>> = serde_json::from_slice(&body)
+                .map_err(api::errors::RequestError::new)
+                .map_err(api::errors::ApiError::RequestError)?;
+
+            let mut symbols = HashMap::new();
+            for market in markets {
+                // Only the KRW markets are subject to the price units of `price_unit`.
+                if !market.market.starts_with("KRW-") {
+                    continue;
+                }
+
+                match Symbol::new(market.market, Tick::new(PRICE_TICK), Tick::new(SIZE_TICK)) {
+                    Some(symbol) => {
+                        symbols.insert(symbol.name().to_lowercase(), symbol);
+                    }
+                    None => error!("symbol name too long: `{}`", market.market),
+                }
+            }
+            Ok(symbols)
+        })
+    }
+}
@@ -0,0 +1,82 @@
+#![cfg(test)]
+
+use futures::prelude::*;
+use crate::Side;
+use crate::order_book::LimitUpdate;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::upbit::wss::HandlerImpl;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::wss::{Control, NotifSender};
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("KRW-BTC", Tick::new(1), Tick::new(100_000_000)).unwrap()
+}
+
+/// Feed `frames` to a handler streaming the order book, and return the notifications.
+fn parse(frames: &[String]) -> Vec<Notification> {
+    let flags = NotificationFlags::ORDER_BOOK;
+    let control = Control::new(flags);
+    let (out, receiver) = NotifSender::test(&control);
+    let mut handler = HandlerImpl::test(symbol(), flags);
+    for frame in frames {
+        handler.parse_message(frame.as_bytes(), &out).unwrap();
+    }
+    drop(out);
+    receiver.wait().map(|notif| notif.unwrap()).collect()
+}
+
+/// Each unit holds the ask and the bid of the same rank.
+fn book(code: &str, timestamp: u64, units: &[(f64, f64, f64, f64)]) -> String {
+    let units = units.iter().map(|(ask_price, ask_size, bid_price, bid_size)| format!(
+        r#"{{"ask_price":{},"bid_price":{},"ask_size":{},"bid_size":{}}}"#,
+        ask_price,
+        bid_price,
+        ask_size,
+        bid_size
+    )).collect::<Vec<_>>();
+
+    format!(
+        r#"{{"type":"orderbook","code":"{}","timestamp":{},"total_ask_size":1.7,
+            "total_bid_size":0.13,"orderbook_units":[{}],"stream_type":"REALTIME"}}"#,
+        code,
+        timestamp,
+        units.join(",")
+    )
+}
+
+#[test]
+fn book_snapshots() {
+    let frames = [
+        book("KRW-BTC", 1_704_067_200_000, &[
+            (55_010_000., 0.5, 55_000_000., 0.1),
+            (55_020_000., 1.2, 54_990_000., 0.03),
+        ]),
+
+        // Trades are dropped when not requested, and books of other codes are skipped.
+        r#"{"type":"trade","code":"KRW-BTC","trade_price":55010000.0,"trade_volume":0.01,
+            "ask_bid":"BID","trade_timestamp":1704067200050}"#.to_owned(),
+        book("KRW-ETH", 1_704_067_200_080, &[(3_100_000., 2., 3_099_000., 1.)]),
+
+        // Each snapshot is turned into the updates from the previous one.
+        book("KRW-BTC", 1_704_067_200_100, &[
+            (55_010_000., 0.5, 55_005_000., 0.2),
+            (55_020_000., 1.2, 55_000_000., 0.1),
+        ]),
+    ];
+
+    let notifs = parse(&frames);
+    assert_eq!(notifs, vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(54_990_000, 3_000_000, Side::Bid).with_timestamp(1_704_067_200_000),
+            LimitUpdate::new(55_000_000, 10_000_000, Side::Bid).with_timestamp(1_704_067_200_000),
+            LimitUpdate::new(55_010_000, 50_000_000, Side::Ask).with_timestamp(1_704_067_200_000),
+            LimitUpdate::new(55_020_000, 120_000_000, Side::Ask).with_timestamp(1_704_067_200_000),
+        ]),
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(55_005_000, 20_000_000, Side::Bid).with_timestamp(1_704_067_200_100),
+            LimitUpdate::new(54_990_000, 0, Side::Bid).with_timestamp(1_704_067_200_100),
+        ]),
+    ]);
+}
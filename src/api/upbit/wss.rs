@@ -0,0 +1,373 @@
+use futures::sync::mpsc::unbounded;
+use failure::bail;
+use std::{mem, thread};
+use serde_derive::{Deserialize, Serialize};
+use log::{debug, error};
+use crate::Side;
+use crate::order_book::{LimitUpdate, OrderBook};
+use crate::tick::{Tick, TickUnit};
+use crate::api::{
+    Notification,
+    NotificationFlags,
+    Trade,
+    OrderConfirmation,
+    OrderExpiration,
+    OrderUpdate,
+};
+use crate::api::wss;
+use crate::api::stream::NotificationStream;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::api::upbit::{Client, Keys};
+
+impl Client {
+    crate fn new_stream(&self, symbol: Symbol, flags: NotificationFlags)
+        -> NotificationStream
+    {
+        let address = self.params.streaming_endpoint.clone();
+        let keys = self.keys.clone();
+
+        let (snd, rcv) = unbounded();
+        let control = wss::Control::new(flags);
+        let handler_control = control.clone();
+
+        thread::spawn(move || {
+            debug!("initiating WebSocket connection at {}", address);
+
+            if let Err(err) = ws::connect(address, |out| {
+                wss::Handler::new(
+                    out,
+                    snd.clone(),
+                    handler_control.clone(),
+                    wss::KeepAlive::True,
+                    HandlerImpl {
+                        symbol,
+                        flags,
+                        keys: keys.clone(),
+                        order_book: OrderBook::new(),
+                    }
+                )
+            })
+            {
+                error!("WebSocket connection terminated with error: `{}`", err);
+            }
+        });
+
+        NotificationStream::new(rcv, control)
+    }
+}
+
+/// Public and private types are requested over the same connection, the private ones
+/// being available if the connection was authenticated by the headers of its opening
+/// handshake. Messages are JSON documents sent in binary frames.
+crate struct HandlerImpl {
+    symbol: Symbol,
+    flags: NotificationFlags,
+    keys: Option<Keys>,
+
+    /// Local copy of the order book, needed for turning the snapshots of the best levels
+    /// into limit updates.
+    order_book: OrderBook,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[serde(untagged)]
+enum UpbitRequestField<'a> {
+    Ticket {
+        ticket: String,
+    },
+    Type {
+        #[serde(rename = "type")]
+        type_: &'a str,
+        codes: [&'a str; 1],
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct UpbitType<'a> {
+    #[serde(rename = "type")]
+    type_: Option<&'a str>,
+    error: Option<UpbitError<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+struct UpbitError<'a> {
+    name: &'a str,
+    message: &'a str,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct UpbitOrderBookUnit {
+    ask_price: f64,
+    bid_price: f64,
+    ask_size: f64,
+    bid_size: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct UpbitOrderBook<'a> {
+    code: &'a str,
+    timestamp: u64,
+    orderbook_units: Vec<UpbitOrderBookUnit>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct UpbitTrade<'a> {
+    code: &'a str,
+    trade_price: f64,
+    trade_volume: f64,
+    ask_bid: &'a str,
+    trade_timestamp: u64,
+}
+
+/// Prices and sizes refer to the fill when `state` is `trade`, and to the order
+/// otherwise.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct UpbitMyOrder<'a> {
+    code: &'a str,
+    uuid: &'a str,
+    identifier: Option<&'a str>,
+    ask_bid: &'a str,
+    state: &'a str,
+    price: f64,
+    volume: f64,
+    remaining_volume: f64,
+    executed_volume: f64,
+    trade_fee: Option<f64>,
+    trade_timestamp: Option<u64>,
+    timestamp: u64,
+}
+
+fn convert_upbit_number(tick: Tick, value: f64) -> Result<TickUnit, failure::Error> {
+    Ok(tick.ticked(&format!("{}", value))?)
+}
+
+/// The side sent along orders and trades, i.e. the side of the taker for trades.
+fn convert_ask_bid(ask_bid: &str) -> Result<Side, failure::Error> {
+    match ask_bid {
+        "BID" => Ok(Side::Bid),
+        "ASK" => Ok(Side::Ask),
+        other => bail!("wrong side: `{}`", other),
+    }
+}
+
+impl HandlerImpl {
+    /// Return a handler of a stream of `symbol`, without keys, for the tests.
+    #[cfg(test)]
+    crate fn test(symbol: Symbol, flags: NotificationFlags) -> Self {
+        HandlerImpl {
+            symbol,
+            flags,
+            keys: None,
+            order_book: OrderBook::new(),
+        }
+    }
+
+    /// Each request replaces the types requested previously on the connection.
+    fn send_request(&self, out: &ws::Sender) -> ws::Result<()> {
+        use uuid::Uuid;
+
+        let types = self.types(self.flags);
+        if types.is_empty() {
+            return Ok(());
+        }
+
+        let mut request = vec![UpbitRequestField::Ticket {
+            ticket: Uuid::new_v4().to_string(),
+        }];
+        request.extend(types.into_iter().map(|type_| UpbitRequestField::Type {
+            type_,
+            codes: [self.symbol.name()],
+        }));
+
+        match serde_json::to_string(&request) {
+            Ok(value) => out.send(value),
+            Err(err) => {
+                panic!("failed to serialize `UpbitRequestField`: `{}`", err);
+            }
+        }
+    }
+
+    /// Return the types enabled by `flags`, the private one only if the connection
+    /// was authenticated.
+    fn types(&self, flags: NotificationFlags) -> Vec<&'static str> {
+        let mut types = Vec::new();
+        if flags.contains(NotificationFlags::ORDER_BOOK) {
+            types.push("orderbook");
+        }
+        if flags.contains(NotificationFlags::TRADES) {
+            types.push("trade");
+        }
+        if self.keys.is_some() && flags.contains(NotificationFlags::ORDERS) {
+            types.push("myOrder");
+        }
+        types
+    }
+
+    fn parse_order_book(&mut self, data: &[u8], out: &wss::NotifSender)
+        -> Result<(), failure::Error>
+    {
+        let book: UpbitOrderBook<'_> = serde_json::from_slice(data)?;
+        if book.code != self.symbol.name() {
+            return Ok(());
+        }
+
+        let (price_tick, size_tick) = (self.symbol.price_tick(), self.symbol.size_tick());
+        let mut order_book = OrderBook::new();
+        for unit in &book.orderbook_units {
+            order_book.update(LimitUpdate {
+                side: Side::Bid,
+                price: convert_upbit_number(price_tick, unit.bid_price)?,
+                size: convert_upbit_number(size_tick, unit.bid_size)?,
+            });
+            order_book.update(LimitUpdate {
+                side: Side::Ask,
+                price: convert_upbit_number(price_tick, unit.ask_price)?,
+                size: convert_upbit_number(size_tick, unit.ask_size)?,
+            });
+        }
+
+        // Levels which are not part of the new snapshot are removed.
+        let updates: Vec<_> = self.order_book.diff(&order_book)
+            .map(|update| update.with_timestamp(book.timestamp))
+            .collect();
+        self.order_book = order_book;
+
+        if !updates.is_empty() {
+            out.unbounded_send(Notification::LimitUpdates(updates)).unwrap();
+        }
+        Ok(())
+    }
+
+    fn parse_trade(&self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let trade: UpbitTrade<'_> = serde_json::from_slice(data)?;
+        if trade.code != self.symbol.name() {
+            return Ok(());
+        }
+
+        // The side is the one of the taker.
+        let maker_side = match convert_ask_bid(trade.ask_bid)? {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let trade = Notification::Trade(Trade {
+            price: convert_upbit_number(self.symbol.price_tick(), trade.trade_price)?,
+            size: convert_upbit_number(self.symbol.size_tick(), trade.trade_volume)?,
+            maker_side,
+        }.with_timestamp(trade.trade_timestamp));
+        out.unbounded_send(trade).unwrap();
+        Ok(())
+    }
+
+    fn parse_my_order(&self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let order: UpbitMyOrder<'_> = serde_json::from_slice(data)?;
+        if order.code != self.symbol.name() {
+            return Ok(());
+        }
+
+        // Orders placed from elsewhere, e.g. the website, have no identifier.
+        let order_id = order.identifier.unwrap_or(order.uuid).to_owned();
+        let (price_tick, size_tick) = (self.symbol.price_tick(), self.symbol.size_tick());
+
+        let notif = match order.state {
+            "wait" | "watch" => Notification::OrderConfirmation(OrderConfirmation {
+                order_id,
+                price: convert_upbit_number(price_tick, order.price)?,
+                size: convert_upbit_number(size_tick, order.volume)?,
+                side: convert_ask_bid(order.ask_bid)?,
+            }.with_timestamp(order.timestamp)),
+
+            "trade" => Notification::OrderUpdate(OrderUpdate {
+                order_id,
+                consumed_size: convert_upbit_number(size_tick, order.volume)?,
+                remaining_size: convert_upbit_number(size_tick, order.remaining_volume)?,
+                consumed_price: convert_upbit_number(price_tick, order.price)?,
+                commission: convert_upbit_number(
+                    self.symbol.commission_tick(),
+                    order.trade_fee.unwrap_or(0.)
+                )?,
+            }.with_timestamp(order.trade_timestamp.unwrap_or(order.timestamp))),
+
+            // Canceled, possibly after being partially filled, or canceled by the self
+            // match prevention.
+            "cancel" | "prevented" => Notification::OrderExpiration(OrderExpiration {
+                order_id,
+                filled_size: Some(convert_upbit_number(size_tick, order.executed_volume)?),
+                remaining_size: Some(convert_upbit_number(size_tick, order.remaining_volume)?),
+            }.with_timestamp(order.timestamp)),
+
+            // Fully filled, which was notified by the last `trade` event.
+            _ => return Ok(()),
+        };
+        out.unbounded_send(notif).unwrap();
+        Ok(())
+    }
+
+    crate fn parse_message(&mut self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        let message: UpbitType<'_> = serde_json::from_slice(data)?;
+
+        if let Some(error) = message.error {
+            bail!("request failed: `{}` ({})", error.message, error.name);
+        }
+
+        match message.type_ {
+            Some("orderbook") if self.flags.contains(NotificationFlags::ORDER_BOOK) => {
+                self.parse_order_book(data, out)?;
+            }
+
+            Some("trade") if self.flags.contains(NotificationFlags::TRADES) => {
+                self.parse_trade(data, out)?;
+            }
+
+            Some("myOrder") if self.flags.contains(NotificationFlags::ORDERS) => {
+                self.parse_my_order(data, out)?;
+            }
+
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl wss::HandlerImpl for HandlerImpl {
+    fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        self.send_request(out)
+    }
+
+    fn on_message(&mut self, text: &str, out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(text.as_bytes(), out)
+    }
+
+    fn on_binary(&mut self, data: &[u8], out: &wss::NotifSender) -> Result<(), failure::Error> {
+        self.parse_message(data, out)
+    }
+
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()> {
+        let previous = mem::replace(&mut self.flags, flags);
+
+        // The book is sent again from the next snapshot.
+        if !previous.contains(NotificationFlags::ORDER_BOOK) &&
+            flags.contains(NotificationFlags::ORDER_BOOK)
+        {
+            self.order_book = OrderBook::new();
+        }
+
+        // Upbit cannot unsubscribe from every type, the notifications of the
+        // disabled ones are dropped when parsing the messages instead.
+        if self.types(previous) != self.types(flags) {
+            self.send_request(out)?;
+        }
+        Ok(())
+    }
+
+    fn handshake_headers(&mut self) -> Vec<(String, Vec<u8>)> {
+        match &self.keys {
+            Some(keys) => vec![(
+                "Authorization".to_owned(),
+                format!("Bearer {}", keys.jwt(None)).into_bytes()
+            )],
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! A module defining wallet operations which are only available on some exchanges.
+
+use std::collections::HashMap;
+use futures::prelude::*;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{errors, Balances};
+use crate::api::timestamp::Timestamped;
+use crate::tick::Tick;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Conversion of the dust balance of one asset.
+pub struct DustTransfer {
+    /// Converted asset.
+    pub from_asset: String,
+
+    /// Converted amount, unticked.
+    pub amount: String,
+
+    /// Amount received in exchange, unticked.
+    pub transferred_amount: String,
+
+    /// Fee charged for the conversion, unticked.
+    pub service_charge: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Result of a dust conversion.
+pub struct DustConversion {
+    /// Total amount received in exchange, unticked.
+    pub total_transferred: String,
+
+    /// Total fees charged for the conversion, unticked.
+    pub total_service_charge: String,
+
+    /// Detail of the conversion for each asset.
+    pub transfers: Vec<DustTransfer>,
+}
+
+/// A trait implemented by clients of exchanges which provide wallet management
+/// operations.
+pub trait WalletApi {
+    /// Convert the small balances of the given `assets` into the exchange native
+    /// asset (e.g. BNB on binance).
+    fn convert_dust(&self, assets: &[&str])
+        -> Box<dyn Future<Item = Timestamped<DustConversion>, Error = errors::Error> + Send + 'static>;
+}
+
+/// Return the assets whose total balance (free + locked) is non-zero but strictly
+/// lower than the minimum amount given for this asset in `min_amounts`, sorted by name.
+/// Assets without a minimum amount are ignored. Amounts are unticked, and compared
+/// up to a precision of `1e-8`.
+///
+/// This can be used with the balances of any exchange in order to find which assets
+/// need to be swept, e.g. through `WalletApi::convert_dust`.
+///
+/// # Panics
+/// Panic if an amount cannot be converted to tick units.
+pub fn dust_balances(balances: &Balances, min_amounts: &HashMap<String, String>) -> Vec<String> {
+    let tick = Tick::new(100_000_000);
+
+    let mut dust: Vec<_> = balances.iter()
+        .filter_map(|(asset, balance)| {
+            let min_amount = tick.ticked(min_amounts.get(asset)?).unwrap();
+            let total = tick.ticked(&balance.free).unwrap() + tick.ticked(&balance.locked).unwrap();
+            if total > 0 && total < min_amount {
+                Some(asset.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    dust.sort();
+    dust
+}
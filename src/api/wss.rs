@@ -1,19 +1,251 @@
 // `Timeout`, `Token`
 #![allow(deprecated)]
 
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 use ws::util::{Timeout, Token};
-use futures::sync::mpsc::UnboundedSender;
+use futures::sync::mpsc::{SendError, UnboundedSender};
 use log::error;
-use crate::api::Notification;
+use crate::api::{Notification, NotificationFlags};
+use crate::api::budget::{self, Shedder};
+use crate::api::filter::{FilterState, NotificationFilter};
+#[cfg(feature = "latency")]
+use crate::api::latency;
+#[cfg(feature = "affinity")]
+use crate::api::affinity::{SharedThreadConfig, ThreadConfig};
 
-pub type NotifSender = UnboundedSender<Notification>;
+/// Sender of the notifications of a connection, applying the filter of the stream and
+/// the memory budget before queuing them.
+#[derive(Clone)]
+crate struct NotifSender {
+    sender: UnboundedSender<Notification>,
+    filter: Arc<Mutex<FilterState>>,
+    shedder: Arc<Mutex<Shedder>>,
+
+    #[cfg(feature = "latency")]
+    latency: Arc<Mutex<latency::Tracker>>,
+
+    /// Receipt of the frame being handled, if any.
+    #[cfg(feature = "latency")]
+    receipt: Option<latency::Receipt>,
+}
+
+impl NotifSender {
+    /// Queue `notif` unless it is dropped by the filter, or conflated or shed under the
+    /// memory budget, see `api::budget`.
+    crate fn unbounded_send(&self, notif: Notification) -> Result<(), SendError<Notification>> {
+        let notif = match self.filter.lock().unwrap().apply(notif) {
+            Some(notif) => notif,
+            None => return Ok(()),
+        };
+
+        // The lock is held while queuing, so that conflated limit updates keep their order.
+        let budget = budget::global();
+        let mut shedder = self.shedder.lock().unwrap();
+        for notif in shedder.admit(notif, budget) {
+            let bytes = budget::footprint(&notif);
+            shedder.on_queued(bytes, budget);
+            if let Err(err) = self.queue(notif) {
+                shedder.on_delivered(bytes, budget);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "latency"))]
+    fn queue(&self, notif: Notification) -> Result<(), SendError<Notification>> {
+        self.sender.unbounded_send(notif)
+    }
+
+    /// The lock on the tracker is held while sending, so that the connections of a
+    /// stream queue their notifications in the order of the channel.
+    #[cfg(feature = "latency")]
+    fn queue(&self, notif: Notification) -> Result<(), SendError<Notification>> {
+        let mut latency = self.latency.lock().unwrap();
+        latency.on_queued(&notif, self.receipt, Instant::now());
+        self.sender.unbounded_send(notif)
+    }
+}
+
+#[cfg(test)]
+impl NotifSender {
+    /// Return a sender applying the filter of `control`, along with its receiver, for
+    /// feeding frames directly to a `HandlerImpl` in the tests of the backends.
+    crate fn test(control: &Control) -> (Self, futures::sync::mpsc::UnboundedReceiver<Notification>) {
+        let (sender, receiver) = futures::sync::mpsc::unbounded();
+        let snd = NotifSender {
+            sender,
+            filter: control.filter.clone(),
+            shedder: control.shedder.clone(),
+            #[cfg(feature = "latency")]
+            latency: control.latency.clone(),
+            #[cfg(feature = "latency")]
+            receipt: None,
+        };
+        (snd, receiver)
+    }
+}
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 crate enum KeepAlive {
     True,
+    #[cfg_attr(not(any(feature = "gdax", feature = "hitbtc")), allow(dead_code))]
     False,
 }
 
+/// State shared between a `NotificationStream` and the handler of its connection.
+crate struct Control {
+    flags: Mutex<NotificationFlags>,
+
+    /// Shared by all the connections of a stream.
+    filter: Arc<Mutex<FilterState>>,
+
+    /// Shared by all the connections of a stream.
+    shedder: Arc<Mutex<Shedder>>,
+
+    /// Shared by all the connections of a stream.
+    #[cfg(feature = "latency")]
+    latency: Arc<Mutex<latency::Tracker>>,
+
+    /// Shared by all the connections of a stream.
+    #[cfg(feature = "affinity")]
+    thread_config: Arc<SharedThreadConfig>,
+
+    /// Set once the connection has been initiated.
+    out: Mutex<Option<ws::Sender>>,
+
+    messages: AtomicUsize,
+    errors: AtomicUsize,
+    last_message: Mutex<Option<Instant>>,
+}
+
+impl Control {
+    crate fn new(flags: NotificationFlags) -> Arc<Self> {
+        Arc::new(Control {
+            flags: Mutex::new(flags),
+            filter: Arc::new(Mutex::new(FilterState::new())),
+            shedder: Arc::new(Mutex::new(Shedder::new())),
+            #[cfg(feature = "latency")]
+            latency: Arc::new(Mutex::new(latency::Tracker::new())),
+            #[cfg(feature = "affinity")]
+            thread_config: Arc::new(SharedThreadConfig::new()),
+            out: Mutex::new(None),
+            messages: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            last_message: Mutex::new(None),
+        })
+    }
+
+    /// Return a new control for another connection of the same stream, e.g. the
+    /// private connection of exchanges streaming orders separately. It starts with
+    /// the current flags and shares the filter of `self`.
+    #[cfg_attr(
+        not(any(
+            feature = "alpaca",
+            feature = "bybit",
+            feature = "gemini",
+            feature = "huobi",
+            feature = "kraken",
+            feature = "kucoin",
+            feature = "okx",
+        )),
+        allow(dead_code)
+    )]
+    crate fn linked(&self) -> Arc<Self> {
+        Arc::new(Control {
+            flags: Mutex::new(self.flags()),
+            filter: self.filter.clone(),
+            shedder: self.shedder.clone(),
+            #[cfg(feature = "latency")]
+            latency: self.latency.clone(),
+            #[cfg(feature = "affinity")]
+            thread_config: self.thread_config.clone(),
+            out: Mutex::new(None),
+            messages: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            last_message: Mutex::new(None),
+        })
+    }
+
+    /// Number of messages received from the server.
+    crate fn messages(&self) -> usize {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages which could not be handled.
+    crate fn errors(&self) -> usize {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Time at which the last message was received.
+    crate fn last_message(&self) -> Option<Instant> {
+        *self.last_message.lock().unwrap()
+    }
+
+    crate fn flags(&self) -> NotificationFlags {
+        *self.flags.lock().unwrap()
+    }
+
+    crate fn filter(&self) -> NotificationFilter {
+        self.filter.lock().unwrap().filter().clone()
+    }
+
+    crate fn set_filter(&self, filter: NotificationFilter) {
+        self.filter.lock().unwrap().set_filter(filter);
+    }
+
+    /// Number of notifications dropped by the filter.
+    crate fn filtered(&self) -> usize {
+        self.filter.lock().unwrap().filtered()
+    }
+
+    crate fn shedder(&self) -> &Mutex<Shedder> {
+        &self.shedder
+    }
+
+    #[cfg(feature = "latency")]
+    crate fn latency(&self) -> &Mutex<latency::Tracker> {
+        &self.latency
+    }
+
+    /// Store the new thread configuration, which each connection applies to its own
+    /// thread when it opens or upon the next message it receives.
+    #[cfg(feature = "affinity")]
+    crate fn set_thread_config(&self, config: ThreadConfig) {
+        self.thread_config.set(config);
+    }
+
+    /// Store the new flags and wake up the handler so that it applies them.
+    crate fn set_flags(&self, flags: NotificationFlags) {
+        let previous = mem::replace(&mut *self.flags.lock().unwrap(), flags);
+
+        // The book is sent again from a fresh snapshot, see `NotificationStream::set_flags`.
+        if !previous.contains(NotificationFlags::ORDER_BOOK)
+            && flags.contains(NotificationFlags::ORDER_BOOK)
+        {
+            self.filter.lock().unwrap().reset_book();
+            self.shedder.lock().unwrap().reset_book();
+        }
+
+        self.refresh();
+    }
+
+    /// Wake up the handler so that it applies the current flags, along with any
+    /// other setting it shares with its client, e.g. the binance listen key.
+    crate fn refresh(&self) {
+        // If the connection has not been initiated yet, the flags will be applied
+        // when it opens.
+        if let Some(out) = self.out.lock().unwrap().as_ref() {
+            if let Err(err) = out.timeout(0, FLAGS) {
+                error!("failed to notify flags change: `{}`", err);
+            }
+        }
+    }
+}
+
 /// An object handling a WebSocket API connection.
 /// Inside handler functions, panicking can be used to terminate
 /// the connection easily (the connection always happen in a
@@ -21,22 +253,75 @@ crate enum KeepAlive {
 crate struct Handler<T> {
     out: ws::Sender,
     snd: NotifSender,
+    control: Arc<Control>,
     keep_alive: KeepAlive,
 
     /// We keep a reference to the `EXPIRE` timeout so that we can cancel it when we receive
     /// something from the server.
     timeout: Option<Timeout>,
 
+    /// Version of the thread configuration applied to the thread of the connection.
+    #[cfg(feature = "affinity")]
+    thread_config_version: usize,
+
     inner: T,
 }
 
 crate trait HandlerImpl {
     fn on_open(&mut self, out: &ws::Sender) -> ws::Result<()>;
     fn on_message(&mut self, text: &str, out: &NotifSender) -> Result<(), failure::Error>;
+
+    /// Called with the binary messages, which most servers never send. Defaults to
+    /// ignoring them.
+    fn on_binary(&mut self, _: &[u8], _: &NotifSender) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    /// Called with the flags requested through `NotificationStream::set_flags`, which may
+    /// be equal to the current ones. Implementors should update their subscriptions
+    /// without closing the connection.
+    fn on_flags_changed(&mut self, flags: NotificationFlags, out: &ws::Sender) -> ws::Result<()>;
+
+    /// Called periodically when the connection is kept alive. Defaults to sending a ping
+    /// frame, implementors can send a message instead for servers expecting pings at the
+    /// application level.
+    fn on_ping(&mut self, out: &ws::Sender) -> ws::Result<()> {
+        out.ping(vec![])
+    }
+
+    /// Called periodically along with `on_ping`, for implementors which need to poll
+    /// the exchange besides the stream. Defaults to doing nothing.
+    fn on_poll(&mut self, _: &NotifSender) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    /// Additional HTTP headers of the opening handshake, for servers authenticating the
+    /// connection at this point. Defaults to none.
+    fn handshake_headers(&mut self) -> Vec<(String, Vec<u8>)> {
+        Vec::new()
+    }
+}
+
+/// CRC32 (IEEE) of `bytes`, as used by some exchanges for the order book checksums.
+#[cfg_attr(not(any(feature = "kraken", feature = "okx")), allow(dead_code))]
+crate fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
 }
 
 const PING: Token = Token(1);
 const EXPIRE: Token = Token(2);
+const FLAGS: Token = Token(3);
 
 const PING_TIMEOUT: u64 = 10_000;
 const EXPIRE_TIMEOUT: u64 = 30_000;
@@ -45,24 +330,58 @@ impl<T> Handler<T> {
     crate fn new(
         out: ws::Sender,
         snd: UnboundedSender<Notification>,
+        control: Arc<Control>,
         keep_alive: KeepAlive,
         inner: T
     ) -> Self
     {
+        *control.out.lock().unwrap() = Some(out.clone());
+        let snd = NotifSender {
+            sender: snd,
+            filter: control.filter.clone(),
+            shedder: control.shedder.clone(),
+            #[cfg(feature = "latency")]
+            latency: control.latency.clone(),
+            #[cfg(feature = "latency")]
+            receipt: None,
+        };
         Handler {
             out,
             snd,
+            control,
             keep_alive,
             timeout: None,
+            #[cfg(feature = "affinity")]
+            thread_config_version: 0,
             inner,
         }
     }
+
+    #[cfg(feature = "affinity")]
+    fn apply_thread_config(&mut self) {
+        let result = self.control.thread_config.apply_if_changed(&mut self.thread_config_version);
+        if let Err(err) = result {
+            error!("failed to apply thread config: `{}`", err);
+        }
+    }
 }
 
 impl<T: HandlerImpl> ws::Handler for Handler<T> {
+    fn build_request(&mut self, url: &url::Url) -> ws::Result<ws::Request> {
+        let mut request = ws::Request::from_url(url)?;
+        request.headers_mut().extend(self.inner.handshake_headers());
+        Ok(request)
+    }
+
     fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+        #[cfg(feature = "affinity")]
+        self.apply_thread_config();
+
         self.inner.on_open(&self.out)?;
 
+        // The flags may have been changed before the connection opened.
+        self.inner.on_flags_changed(self.control.flags(), &self.out)?;
+
         if self.keep_alive == KeepAlive::True {
             self.out.timeout(PING_TIMEOUT, PING)?;
         }
@@ -72,10 +391,15 @@ impl<T: HandlerImpl> ws::Handler for Handler<T> {
     fn on_timeout(&mut self, event: Token) -> ws::Result<()> {
         match event {
             PING => {
-                self.out.ping(vec![])?;
+                self.inner.on_ping(&self.out)?;
+                if let Err(err) = self.inner.on_poll(&self.snd) {
+                    self.control.errors.fetch_add(1, Ordering::Relaxed);
+                    error!("polling encountered error: `{}`", err)
+                }
                 self.out.timeout(PING_TIMEOUT, PING)
             }
             EXPIRE => self.out.close(ws::CloseCode::Away),
+            FLAGS => self.inner.on_flags_changed(self.control.flags(), &self.out),
             _ => Err(ws::Error::new(ws::ErrorKind::Internal, "invalid timeout token encountered")),
         }
     }
@@ -96,10 +420,34 @@ impl<T: HandlerImpl> ws::Handler for Handler<T> {
     }
 
     fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
-        if let ws::Message::Text(text) = msg {
-            if let Err(err) = self.inner.on_message(&text, &self.snd) {
-                error!("message handling encountered error: `{}`", err)
-            }
+        #[cfg(feature = "affinity")]
+        self.apply_thread_config();
+
+        let now = Instant::now();
+        self.control.messages.fetch_add(1, Ordering::Relaxed);
+        *self.control.last_message.lock().unwrap() = Some(now);
+
+        #[cfg(feature = "latency")]
+        {
+            use crate::api::timestamp::timestamp_ms;
+            self.snd.receipt = Some(latency::Receipt {
+                instant: now,
+                timestamp: timestamp_ms(),
+            });
+        }
+
+        let result = match msg {
+            ws::Message::Text(text) => self.inner.on_message(&text, &self.snd),
+            ws::Message::Binary(data) => self.inner.on_binary(&data, &self.snd),
+        };
+
+        #[cfg(feature = "latency")]
+        {
+            self.snd.receipt = None;
+        }
+        if let Err(err) = result {
+            self.control.errors.fetch_add(1, Ordering::Relaxed);
+            error!("message handling encountered error: `{}`", err)
         }
         Ok(())
     }
@@ -0,0 +1,269 @@
+//! Terminal viewer of the live order book, recent trades, open orders and positions of
+//! a symbol, e.g.:
+//!
+//! ```text
+//! trade-tui -p params.json -k keys.json binance BTCUSDT
+//! ```
+//!
+//! The notification stream of the client feeds an `api::blotter::Blotter`, which is
+//! rendered periodically. The params and the keys are read from JSON files, see
+//! `examples/prompt` for samples: without keys, the open orders and positions stay
+//! empty. Quit with `q` or `Esc`.
+//!
+//! Built with the `tui` feature, for the exchanges enabled at build time among
+//! `binance`, `gdax` and `hitbtc`.
+
+use std::fs::File;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use failure::{bail, format_err};
+use futures::prelude::*;
+use serde::de::DeserializeOwned;
+use tokio::runtime::current_thread;
+use clap::{clap_app, ArgMatches};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::{Frame, Terminal};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Row, Table};
+use trade::Side;
+use trade::api::{self, ApiClient};
+use trade::api::blotter::Blotter;
+use trade::api::symbol::Symbol;
+use trade::tick::{Tick, TickUnit};
+
+/// Interval between two renderings of the blotter.
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Number of recent trades kept by the blotter.
+const MAX_TRADES: usize = 200;
+
+/// Load a JSON file, `what` naming its expected content in error messages.
+fn load<T: DeserializeOwned>(path: &str, what: &str) -> Result<T, failure::Error> {
+    let file = File::open(path)
+        .map_err(|err| format_err!("cannot open {} file `{}`: {}", what, path, err))?;
+    serde_json::from_reader(file)
+        .map_err(|err| format_err!("expected valid JSON for {} in `{}`: {}", what, path, err))
+}
+
+fn load_keys<K: DeserializeOwned>(matches: &ArgMatches<'_>) -> Result<Option<K>, failure::Error> {
+    match matches.value_of("keys") {
+        Some(path) => Ok(Some(load(path, "keys")?)),
+        None => Ok(None),
+    }
+}
+
+fn unticked(tick: Tick, value: TickUnit) -> String {
+    tick.unticked(value).unwrap_or_else(|_| "?".to_owned())
+}
+
+fn side_color(side: Side) -> Style {
+    match side {
+        Side::Bid => Style::default().fg(Color::Green),
+        Side::Ask => Style::default().fg(Color::Red),
+    }
+}
+
+fn draw_book(frame: &mut Frame<'_>, area: Rect, symbol: Symbol, blotter: &Blotter) {
+    let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+    let order_book = blotter.order_book();
+
+    // Asks above bids, best prices in the middle.
+    let depth = (area.height.saturating_sub(3) / 2) as usize;
+    let asks: Vec<_> = order_book.ask().take(depth).collect();
+    let asks = asks.into_iter().rev().map(|(price, size)| {
+        Row::new(vec![unticked(price_tick, *price), unticked(size_tick, *size)])
+            .style(side_color(Side::Ask))
+    });
+    let bids = order_book.bid().take(depth).map(|(price, size)| {
+        Row::new(vec![unticked(price_tick, *price), unticked(size_tick, *size)])
+            .style(side_color(Side::Bid))
+    });
+
+    let table = Table::new(asks.chain(bids), [Constraint::Percentage(50), Constraint::Percentage(50)])
+        .header(Row::new(vec!["Price", "Size"]))
+        .block(Block::default().borders(Borders::ALL).title("Order book"));
+    frame.render_widget(table, area);
+}
+
+fn draw_trades(frame: &mut Frame<'_>, area: Rect, symbol: Symbol, blotter: &Blotter) {
+    let rows = blotter.trades().take(area.height as usize).map(|trade| {
+        // Color by the side of the taker.
+        let taker_side = match trade.maker_side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+        Row::new(vec![
+            trade.timestamp().to_string(),
+            unticked(symbol.price_tick(), trade.price),
+            unticked(symbol.size_tick(), trade.size),
+        ]).style(side_color(taker_side))
+    });
+
+    let widths = [Constraint::Percentage(40), Constraint::Percentage(30), Constraint::Percentage(30)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["Time", "Price", "Size"]))
+        .block(Block::default().borders(Borders::ALL).title("Trades"));
+    frame.render_widget(table, area);
+}
+
+fn draw_orders(frame: &mut Frame<'_>, area: Rect, symbol: Symbol, blotter: &Blotter) {
+    let rows = blotter.open_orders().iter().map(|order| {
+        Row::new(vec![
+            order.order_id.clone(),
+            unticked(symbol.price_tick(), order.price),
+            unticked(symbol.size_tick(), order.remaining_size),
+            unticked(symbol.size_tick(), order.size),
+        ]).style(side_color(order.side))
+    });
+
+    let widths = [
+        Constraint::Percentage(40),
+        Constraint::Percentage(20),
+        Constraint::Percentage(20),
+        Constraint::Percentage(20),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["Id", "Price", "Remaining", "Size"]))
+        .block(Block::default().borders(Borders::ALL).title("Open orders"));
+    frame.render_widget(table, area);
+}
+
+fn draw_positions(frame: &mut Frame<'_>, area: Rect, blotter: &Blotter) {
+    let rows = blotter.positions().iter().map(|position| {
+        Row::new(vec![
+            position.symbol.clone(),
+            position.size.clone(),
+            position.entry_price.clone(),
+            position.unrealized_pnl.clone(),
+        ]).style(side_color(position.side))
+    });
+
+    let widths = [Constraint::Percentage(25); 4];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["Contract", "Size", "Entry", "PnL"]))
+        .block(Block::default().borders(Borders::ALL).title("Positions"));
+    frame.render_widget(table, area);
+}
+
+fn draw(frame: &mut Frame<'_>, symbol: Symbol, blotter: &Blotter, connected: bool) {
+    let title = if connected {
+        format!(" {} ", symbol.name())
+    } else {
+        format!(" {} (disconnected) ", symbol.name())
+    };
+    let outer = Block::default().title(title);
+    let area = outer.inner(frame.size());
+    frame.render_widget(outer, frame.size());
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(30), Constraint::Percentage(40)])
+        .split(area);
+    let account = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(columns[2]);
+
+    draw_book(frame, columns[0], symbol, blotter);
+    draw_trades(frame, columns[1], symbol, blotter);
+    draw_orders(frame, account[0], symbol, blotter);
+    draw_positions(frame, account[1], blotter);
+}
+
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    symbol: Symbol,
+    blotter: &Mutex<Blotter>,
+    connected: &AtomicBool,
+) -> Result<(), failure::Error>
+{
+    loop {
+        terminal.draw(|frame| {
+            let blotter = blotter.lock().unwrap();
+            draw(frame, symbol, &blotter, connected.load(Ordering::Relaxed));
+        })?;
+
+        if event::poll(REFRESH)? {
+            if let Event::Key(key) = event::read()? {
+                let quit = key.code == KeyCode::Char('q') || key.code == KeyCode::Esc;
+                if key.kind == KeyEventKind::Press && quit {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn run<C: ApiClient>(client: C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    let name = matches.value_of("symbol").unwrap();
+    let symbol = client.find_symbol(name)
+        .ok_or_else(|| format_err!("cannot find symbol `{}`", name))?;
+
+    let blotter = Arc::new(Mutex::new(Blotter::new(MAX_TRADES)));
+    let connected = Arc::new(AtomicBool::new(true));
+
+    let stream = client.stream(symbol);
+    {
+        let blotter = blotter.clone();
+        let connected = connected.clone();
+        thread::spawn(move || {
+            let fut = stream.for_each(move |notif| {
+                blotter.lock().unwrap().on_notification(&notif);
+                Ok(())
+            });
+            let _ = current_thread::block_on_all(fut);
+            connected.store(false, Ordering::Relaxed);
+        });
+    }
+
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let result = event_loop(&mut terminal, symbol, &blotter, &connected);
+
+    // Restore the terminal even if the event loop failed.
+    terminal::disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn main() {
+    if let Err(err) = try_main() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn try_main() -> Result<(), failure::Error> {
+    let matches = clap_app!(trade_tui =>
+        (about: "View the order book, trades, open orders and positions of a symbol")
+        (@arg params: -p --params +takes_value "Params file (default = `params.json`)")
+        (@arg keys: -k --keys +takes_value "Keys file, required for the open orders and positions")
+        (@arg exchange: +required "Exchange name, e.g. `binance`")
+        (@arg symbol: +required "Symbol name")
+    ).get_matches();
+
+    let params: api::Params = load(matches.value_of("params").unwrap_or("params.json"), "params")?;
+
+    match matches.value_of("exchange").unwrap() {
+        #[cfg(feature = "binance")]
+        "binance" => run(api::binance::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "gdax")]
+        "gdax" => run(api::gdax::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "hitbtc")]
+        "hitbtc" => run(api::hitbtc::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        other => bail!("unsupported exchange: `{}`", other),
+    }
+}
@@ -0,0 +1,397 @@
+//! Command-line utility acting on an exchange through the unified API, for operators who
+//! need to look at a market or act on an account without writing a program, e.g.:
+//!
+//! ```text
+//! trade -p params.json binance book BTCUSDT
+//! trade -p params.json -k keys.json binance order BTCUSDT buy 0.01 25000.00
+//! ```
+//!
+//! The params and the keys are read from JSON files, see `examples/prompt` for samples.
+//! Without keys, only the commands reading market data are available.
+//!
+//! Built with the `cli` feature, for the exchanges enabled at build time, i.e. the
+//! exchange name is that of the exchange feature, e.g. `kraken_futures`. `bybit`
+//! trades spot pairs, and `dydx` additionally reads the params of its validator node
+//! from the file given by `--node`.
+
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use failure::{bail, format_err};
+use futures::prelude::*;
+use serde::de::DeserializeOwned;
+use tokio::runtime::current_thread;
+use clap::{clap_app, ArgMatches};
+use trade::Side;
+use trade::api::{self, ApiClient, Cancel, NotificationFlags, Notification, Order, TimeInForce};
+use trade::api::order_book::{LiveOrderBook, BookState};
+use trade::api::symbol::{Symbol, IntoWithSymbol};
+use trade::api::timestamp::Timestamped;
+use trade::tick::{Tick, TickUnit};
+
+/// Exchanges enabled at build time.
+const SUPPORTED: &[&str] = &[
+    #[cfg(feature = "alpaca")] "alpaca",
+    #[cfg(feature = "binance")] "binance",
+    #[cfg(feature = "bitfinex")] "bitfinex",
+    #[cfg(feature = "bitmex")] "bitmex",
+    #[cfg(feature = "bybit")] "bybit",
+    #[cfg(feature = "coinbase")] "coinbase",
+    #[cfg(feature = "deribit")] "deribit",
+    #[cfg(feature = "dydx")] "dydx",
+    #[cfg(feature = "gateio")] "gateio",
+    #[cfg(feature = "gdax")] "gdax",
+    #[cfg(feature = "gemini")] "gemini",
+    #[cfg(feature = "hitbtc")] "hitbtc",
+    #[cfg(feature = "huobi")] "huobi",
+    #[cfg(feature = "kraken")] "kraken",
+    #[cfg(feature = "kraken_futures")] "kraken_futures",
+    #[cfg(feature = "kucoin")] "kucoin",
+    #[cfg(feature = "mexc")] "mexc",
+    #[cfg(feature = "okx")] "okx",
+    #[cfg(feature = "upbit")] "upbit",
+];
+
+/// Load a JSON file, `what` naming its expected content in error messages.
+fn load<T: DeserializeOwned>(path: &str, what: &str) -> Result<T, failure::Error> {
+    let file = File::open(path)
+        .map_err(|err| format_err!("cannot open {} file `{}`: {}", what, path, err))?;
+    serde_json::from_reader(file)
+        .map_err(|err| format_err!("expected valid JSON for {} in `{}`: {}", what, path, err))
+}
+
+fn load_keys<K: DeserializeOwned>(matches: &ArgMatches<'_>) -> Result<Option<K>, failure::Error> {
+    match matches.value_of("keys") {
+        Some(path) => Ok(Some(load(path, "keys")?)),
+        None => Ok(None),
+    }
+}
+
+fn find_symbol<C: ApiClient>(client: &C, matches: &ArgMatches<'_>) -> Result<Symbol, failure::Error> {
+    let name = matches.value_of("symbol").unwrap();
+    client.find_symbol(name).ok_or_else(|| format_err!("cannot find symbol `{}`", name))
+}
+
+fn unticked(tick: Tick, value: TickUnit) -> Result<String, failure::Error> {
+    Ok(tick.unticked(value)?)
+}
+
+fn book<C: ApiClient>(client: &C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    let symbol = find_symbol(client, matches)?;
+    let depth: usize = matches.value_of("depth").unwrap_or("10").parse()?;
+
+    let live_order_book = LiveOrderBook::new::<C>(
+        client.stream_with_flags(symbol, NotificationFlags::ORDER_BOOK)
+    );
+    let order_book = match live_order_book.order_book() {
+        BookState::Live(order_book) => order_book,
+        BookState::Disconnected => bail!("stream has disconnected"),
+    };
+
+    // Asks are printed above bids, best prices in the middle.
+    let asks: Vec<_> = order_book.ask().take(depth).collect();
+    for (price, size) in asks.into_iter().rev() {
+        println!(
+            "ask {:>20} {:>20}",
+            unticked(symbol.price_tick(), *price)?,
+            unticked(symbol.size_tick(), *size)?
+        );
+    }
+    for (price, size) in order_book.bid().take(depth) {
+        println!(
+            "bid {:>20} {:>20}",
+            unticked(symbol.price_tick(), *price)?,
+            unticked(symbol.size_tick(), *size)?
+        );
+    }
+    Ok(())
+}
+
+fn print_trade(symbol: Symbol, trade: &Timestamped<api::Trade>) -> Result<(), failure::Error> {
+    // Print the side of the taker.
+    let side = match trade.maker_side {
+        Side::Bid => "sell",
+        Side::Ask => "buy",
+    };
+    println!(
+        "{} {:<4} {:>20} {:>20}",
+        trade.timestamp(),
+        side,
+        unticked(symbol.price_tick(), trade.price)?,
+        unticked(symbol.size_tick(), trade.size)?
+    );
+    Ok(())
+}
+
+fn trades<C: ApiClient>(client: &C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    let symbol = find_symbol(client, matches)?;
+
+    if let Some(count) = matches.value_of("recent") {
+        let trades = current_thread::block_on_all(client.recent_trades(symbol, count.parse()?))?;
+        for trade in &trades {
+            print_trade(symbol, trade)?;
+        }
+        return Ok(());
+    }
+
+    let stream = client.stream_with_flags(symbol, NotificationFlags::TRADES);
+    let fut = stream.for_each(|notif| {
+        if let Notification::Trade(trade) = notif {
+            if let Err(err) = print_trade(symbol, &trade) {
+                eprintln!("{}", err);
+            }
+        }
+        Ok(())
+    });
+    current_thread::block_on_all(fut).map_err(|()| format_err!("stream has disconnected"))
+}
+
+fn order<C: ApiClient>(client: &C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    let symbol = find_symbol(client, matches)?;
+
+    let side = match matches.value_of("side").unwrap().to_lowercase().as_ref() {
+        "buy" => Side::Bid,
+        "sell" => Side::Ask,
+        other => bail!("expected `buy` or `sell`, got `{}`", other),
+    };
+    let size = matches.value_of("size").unwrap();
+
+    let mut order = match matches.value_of("price") {
+        Some(price) => {
+            let time_in_force = match matches.value_of("tif").unwrap_or("gtc") {
+                "gtc" => TimeInForce::GoodTilCanceled,
+                "ioc" => TimeInForce::ImmediateOrCancel,
+                "fok" => TimeInForce::FillOrKilll,
+                other => bail!("expected time in force, got `{}`", other),
+            };
+            Order::new(price, size, side)
+                .with_time_in_force(time_in_force)
+                .post_only(matches.is_present("post_only"))
+        }
+        None => Order::market(size, side),
+    };
+    if let Some(hint) = matches.value_of("id") {
+        order = order.with_order_id::<C>(hint);
+    }
+
+    let ack = current_thread::block_on_all(client.order(order.with_symbol(symbol)))?;
+    println!("{}", ack.order_id);
+    Ok(())
+}
+
+fn cancel<C: ApiClient>(client: &C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    let symbol = find_symbol(client, matches)?;
+
+    // The unified API does not list open orders, hence the ids are given by the caller,
+    // e.g. by piping the output of the `order` command.
+    let order_ids: Vec<String> = match matches.values_of("order_ids") {
+        Some(order_ids) => order_ids.map(|order_id| order_id.to_owned()).collect(),
+        None => {
+            let stdin = io::stdin();
+            let lines: Result<Vec<_>, _> = stdin.lock().lines().collect();
+            lines?.into_iter()
+                .map(|line| line.trim().to_owned())
+                .filter(|line| !line.is_empty())
+                .collect()
+        }
+    };
+
+    let mut failed = 0;
+    for order_id in order_ids {
+        let cancel = Cancel::new(order_id.clone());
+        match current_thread::block_on_all(client.cancel(cancel.with_symbol(symbol))) {
+            Ok(_) => println!("{}", order_id),
+            Err(err) => {
+                eprintln!("cannot cancel `{}`: {}", order_id, err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} cancel(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn balances<C: ApiClient>(client: &C) -> Result<(), failure::Error> {
+    let balances = current_thread::block_on_all(client.balances())?;
+
+    let mut balances: Vec<_> = balances.into_iter().collect();
+    balances.sort_by(|a, b| a.0.cmp(&b.0));
+    for (asset, balance) in balances {
+        println!("{:<10} {:>20} {:>20}", asset, balance.free, balance.locked);
+    }
+    Ok(())
+}
+
+/// Write the notifications of the stream as JSON lines, until the stream ends.
+fn record<C: ApiClient>(client: &C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    let symbol = find_symbol(client, matches)?;
+
+    let mut output: Box<dyn Write> = match matches.value_of("output") {
+        Some(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+
+    let stream = client.stream_with_flags(symbol, NotificationFlags::ALL);
+    let fut = stream.for_each(|notif| {
+        let result = serde_json::to_writer(&mut output, &notif)
+            .map_err(failure::Error::from)
+            .and_then(|()| Ok(writeln!(output)?));
+        if let Err(err) = result {
+            eprintln!("cannot write notification: {}", err);
+            return Err(());
+        }
+        Ok(())
+    });
+    let result = current_thread::block_on_all(fut);
+    output.flush()?;
+    result.map_err(|()| format_err!("stream has disconnected"))
+}
+
+fn run<C: ApiClient>(client: C, matches: &ArgMatches<'_>) -> Result<(), failure::Error> {
+    match matches.subcommand() {
+        ("book", Some(matches)) => book(&client, matches),
+        ("trades", Some(matches)) => trades(&client, matches),
+        ("order", Some(matches)) => order(&client, matches),
+        ("cancel", Some(matches)) => cancel(&client, matches),
+        ("balances", Some(_)) => balances(&client),
+        ("record", Some(matches)) => record(&client, matches),
+        _ => bail!("expected a command, see `--help`"),
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    if let Err(err) = try_main() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn try_main() -> Result<(), failure::Error> {
+    let supported = format!("SUPPORTED EXCHANGES:\n    {}", SUPPORTED.join(", "));
+    let matches = clap_app!(trade =>
+        (about: "Act on an exchange through the `trade` crate unified API")
+        (@arg params: -p --params +takes_value "Params file (default = `params.json`)")
+        (@arg keys: -k --keys +takes_value "Keys file, required by `order`, `cancel` and `balances`")
+        (@arg node: --node +takes_value "Node params file, required by `dydx`")
+        (@arg exchange: +required "Exchange name, among those listed below")
+        (@subcommand book =>
+            (about: "Print the order book")
+            (@arg symbol: +required "Symbol name")
+            (@arg depth: -d --depth +takes_value "Number of levels on each side (default = 10)")
+        )
+        (@subcommand trades =>
+            (about: "Print the live trades, or the most recent ones")
+            (@arg symbol: +required "Symbol name")
+            (@arg recent: -n --recent +takes_value "Print that many recent trades and exit")
+        )
+        (@subcommand order =>
+            (about: "Send an order and print its id, a market order if no price is given")
+            (@arg symbol: +required "Symbol name")
+            (@arg side: +required "`buy` or `sell`")
+            (@arg size: +required "Size of the order")
+            (@arg price: "Limit price")
+            (@arg tif: --tif +takes_value "Time in force of a limit order: `gtc`, `ioc` or `fok` (default = `gtc`)")
+            (@arg post_only: --("post-only") "Reject a limit order which would take liquidity")
+            (@arg id: --id +takes_value "Hint for the order id")
+        )
+        (@subcommand cancel =>
+            (about: "Cancel the given orders, whose ids are read from stdin if none are given")
+            (@arg symbol: +required "Symbol name")
+            (@arg order_ids: ... "Ids of the orders")
+        )
+        (@subcommand balances =>
+            (about: "Print the free and locked balances")
+        )
+        (@subcommand record =>
+            (about: "Write the notifications of a symbol as JSON lines")
+            (@arg symbol: +required "Symbol name")
+            (@arg output: -o --output +takes_value "Output file (default = stdout)")
+        )
+    ).after_help(supported.as_str()).get_matches();
+
+    let params: api::Params = load(matches.value_of("params").unwrap_or("params.json"), "params")?;
+
+    match matches.value_of("exchange").unwrap() {
+        #[cfg(feature = "alpaca")]
+        "alpaca" => {
+            let keys = load_keys(&matches)?
+                .ok_or_else(|| format_err!("`alpaca` requires a keys file"))?;
+            run(api::alpaca::Client::new(params, keys)?, &matches)
+        }
+
+        #[cfg(feature = "binance")]
+        "binance" => run(api::binance::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "bitfinex")]
+        "bitfinex" => run(api::bitfinex::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "bitmex")]
+        "bitmex" => run(api::bitmex::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "bybit")]
+        "bybit" => run(
+            api::bybit::Client::new(params, api::bybit::Category::Spot, load_keys(&matches)?)?,
+            &matches
+        ),
+
+        #[cfg(feature = "coinbase")]
+        "coinbase" => run(api::coinbase::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "deribit")]
+        "deribit" => run(api::deribit::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "dydx")]
+        "dydx" => {
+            let node = matches.value_of("node")
+                .ok_or_else(|| format_err!("`dydx` requires a node params file"))?;
+            let node = load(node, "node params")?;
+            run(api::dydx::Client::new(params, node, load_keys(&matches)?)?, &matches)
+        }
+
+        #[cfg(feature = "gateio")]
+        "gateio" => run(api::gateio::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "gdax")]
+        "gdax" => run(api::gdax::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "gemini")]
+        "gemini" => run(api::gemini::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "hitbtc")]
+        "hitbtc" => run(api::hitbtc::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "huobi")]
+        "huobi" => run(api::huobi::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "kraken")]
+        "kraken" => run(api::kraken::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "kraken_futures")]
+        "kraken_futures" => run(
+            api::kraken_futures::Client::new(params, load_keys(&matches)?)?,
+            &matches
+        ),
+
+        #[cfg(feature = "kucoin")]
+        "kucoin" => run(api::kucoin::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "mexc")]
+        "mexc" => run(api::mexc::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "okx")]
+        "okx" => run(api::okx::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        #[cfg(feature = "upbit")]
+        "upbit" => run(api::upbit::Client::new(params, load_keys(&matches)?)?, &matches),
+
+        other => bail!(
+            "unsupported exchange: `{}`, expected one of: {}",
+            other,
+            SUPPORTED.join(", ")
+        ),
+    }
+}
@@ -0,0 +1,84 @@
+//! A module defining helpers for coordinating several instances of a same program,
+//! e.g. a strategy running on two hosts sharing a file system, or started twice by
+//! mistake on the same host.
+//!
+//! Exclusivity is granted through an advisory lock on a file: use one lock file per
+//! account / symbol pair, e.g. `/var/run/strategy-binance-BTCUSDT.lock`. Standby
+//! instances can keep their market data streams open while waiting for the lease,
+//! so that they are ready to trade as soon as they acquire it.
+
+mod test;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use futures::prelude::*;
+use fs2::FileExt;
+
+#[derive(Debug)]
+/// An exclusive right to trade, held until the value is dropped or the process exits.
+pub struct Lease {
+    file: File,
+    path: PathBuf,
+}
+
+impl Lease {
+    /// Try to acquire the lease associated with the lock file at `path`, creating the file
+    /// if needed. Return `Ok(None)` if the lease is held by another instance.
+    ///
+    /// Once acquired, the id of the current process is written into the file for
+    /// information purposes.
+    pub fn try_acquire<P: AsRef<Path>>(path: P) -> io::Result<Option<Lease>> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        match file.try_lock_exclusive() {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == fs2::lock_contended_error().kind() => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+
+        Ok(Some(Lease {
+            file,
+            path: path.to_owned(),
+        }))
+    }
+
+    /// Wait until the lease associated with the lock file at `path` is acquired, trying
+    /// every `period`. The returned future must be run inside a tokio runtime.
+    pub fn acquire<P: AsRef<Path>>(path: P, period: Duration)
+        -> impl Future<Item = Lease, Error = io::Error> + Send + 'static
+    {
+        use tokio::timer::Interval;
+
+        let path = path.as_ref().to_owned();
+        Interval::new_interval(period)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            .and_then(move |_| Lease::try_acquire(&path))
+            .filter_map(|lease| lease)
+            .into_future()
+            .map(|(lease, _)| lease.unwrap())
+            .map_err(|(err, _)| err)
+    }
+
+    /// Return the path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        // The lock is released anyway when the file is closed.
+        let _ = self.file.unlock();
+    }
+}
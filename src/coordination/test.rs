@@ -0,0 +1,19 @@
+#![cfg(test)]
+
+use crate::coordination::Lease;
+
+#[test]
+fn exclusive_lease() {
+    let path = std::env::temp_dir().join(format!("trade-rs-lease-{}.lock", std::process::id()));
+
+    let lease = Lease::try_acquire(&path).unwrap().unwrap();
+    assert_eq!(lease.path(), path.as_path());
+    assert!(Lease::try_acquire(&path).unwrap().is_none());
+
+    drop(lease);
+    let lease = Lease::try_acquire(&path).unwrap();
+    assert!(lease.is_some());
+
+    drop(lease);
+    std::fs::remove_file(&path).unwrap();
+}
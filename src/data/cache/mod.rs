@@ -0,0 +1,251 @@
+//! A module defining an in-memory store of recent trades per symbol, which can be
+//! queried in a declarative way instead of re-aggregating notification streams.
+//!
+//! # Example
+//! ```
+//! # extern crate trade;
+//! # use trade::data::cache::MarketDataCache;
+//! # use trade::api::id::{ExchangeId, SymbolId};
+//! # fn main() {
+//! # let cache = MarketDataCache::new(600_000);
+//! # let now = 1_000_000;
+//! let btcusd = SymbolId::from_name(ExchangeId::Gdax, "BTC-USD").unwrap();
+//!
+//! // Last 10 trades.
+//! let trades = cache.trades(btcusd).last(10).collect();
+//!
+//! // VWAP over the last 5 minutes.
+//! let vwap = cache.trades(btcusd).since(now - 300_000).vwap();
+//! # let _ = (trades, vwap);
+//! # }
+//! ```
+
+mod test;
+
+use std::collections::{HashMap, VecDeque};
+use crate::Side;
+use crate::api::{Notification, Trade};
+use crate::api::id::SymbolId;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::{Resampler, Bar};
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+/// Trades of one symbol, stored column by column and sorted by timestamp.
+struct TradeColumns {
+    timestamps: VecDeque<Timestamp>,
+    prices: VecDeque<TickUnit>,
+    sizes: VecDeque<TickUnit>,
+    maker_sides: VecDeque<Side>,
+}
+
+impl TradeColumns {
+    fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Insert `trade` after the trades with a lower or equal timestamp, which is at the
+    /// back unless the trade arrived late.
+    fn insert(&mut self, trade: &Timestamped<Trade>) {
+        let index = self.timestamps.iter()
+            .rposition(|&timestamp| timestamp <= trade.timestamp())
+            .map(|index| index + 1)
+            .unwrap_or(0);
+
+        self.timestamps.insert(index, trade.timestamp());
+        self.prices.insert(index, trade.price);
+        self.sizes.insert(index, trade.size);
+        self.maker_sides.insert(index, trade.maker_side);
+    }
+
+    fn pop_front(&mut self) {
+        self.timestamps.pop_front();
+        self.prices.pop_front();
+        self.sizes.pop_front();
+        self.maker_sides.pop_front();
+    }
+
+    fn row(&self, index: usize) -> Timestamped<Trade> {
+        Trade {
+            price: self.prices[index],
+            size: self.sizes[index],
+            maker_side: self.maker_sides[index],
+        }.with_timestamp(self.timestamps[index])
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// An in-memory store of recent trades, keyed by symbol id so that symbols of several
+/// exchanges can be stored together.
+///
+/// Trades older than the retention window, measured from the most recent trade of
+/// the same symbol, are evicted on insertion.
+pub struct MarketDataCache {
+    max_age: Timestamp,
+    max_len: Option<usize>,
+    trades: HashMap<SymbolId, TradeColumns>,
+}
+
+impl MarketDataCache {
+    /// Return an empty cache keeping trades for `max_age` ms.
+    pub fn new(max_age: Timestamp) -> Self {
+        MarketDataCache {
+            max_age,
+            max_len: None,
+            trades: HashMap::new(),
+        }
+    }
+
+    /// Additionally bound the number of trades kept per symbol.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Insert a trade for the given symbol.
+    ///
+    /// # Note
+    /// Trades are expected to be inserted in timestamp order, which is how exchange
+    /// streams deliver them. A trade older than the last one is stored at its place in
+    /// timestamp order, so that it is evicted before the more recent trades.
+    pub fn insert_trade(&mut self, symbol: SymbolId, trade: &Timestamped<Trade>) {
+        let max_age = self.max_age;
+        let max_len = self.max_len;
+        let columns = self.trades.entry(symbol).or_default();
+        columns.insert(trade);
+
+        let newest = columns.timestamps[columns.len() - 1];
+        while !columns.timestamps.is_empty() {
+            let too_old = columns.timestamps[0] + max_age < newest;
+            let too_many = max_len.map(|max_len| columns.len() > max_len).unwrap_or(false);
+            if !too_old && !too_many {
+                break;
+            }
+            columns.pop_front();
+        }
+    }
+
+    /// Insert the data carried by a notification of the given symbol. Only trades are
+    /// kept, other notifications are ignored.
+    pub fn insert(&mut self, symbol: SymbolId, notification: &Notification) {
+        if let Notification::Trade(trade) = notification {
+            self.insert_trade(symbol, trade);
+        }
+    }
+
+    /// Remove all the data stored for the given symbol.
+    pub fn clear(&mut self, symbol: SymbolId) {
+        self.trades.remove(&symbol);
+    }
+
+    /// Start a query over the trades of the given symbol. If the symbol is unknown,
+    /// the query will select nothing.
+    pub fn trades(&self, symbol: SymbolId) -> TradeQuery<'_> {
+        TradeQuery {
+            columns: self.trades.get(&symbol),
+            since: None,
+            until: None,
+            maker_side: None,
+            last: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+/// A query over the trades of one symbol, built by chaining filters and consumed by
+/// either collecting the selected rows or aggregating them.
+pub struct TradeQuery<'a> {
+    columns: Option<&'a TradeColumns>,
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    maker_side: Option<Side>,
+    last: Option<usize>,
+}
+
+impl<'a> TradeQuery<'a> {
+    /// Only select trades with a timestamp greater or equal to `timestamp`.
+    pub fn since(mut self, timestamp: Timestamp) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+
+    /// Only select trades with a timestamp strictly lower than `timestamp`.
+    pub fn until(mut self, timestamp: Timestamp) -> Self {
+        self.until = Some(timestamp);
+        self
+    }
+
+    /// Only select trades where the maker was on the given side.
+    pub fn maker_side(mut self, side: Side) -> Self {
+        self.maker_side = Some(side);
+        self
+    }
+
+    /// Only select the `n` most recent trades among those matching the other filters.
+    pub fn last(mut self, n: usize) -> Self {
+        self.last = Some(n);
+        self
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        let columns = match self.columns {
+            Some(columns) => columns,
+            None => return Vec::new(),
+        };
+
+        let mut indices: Vec<_> = (0..columns.len())
+            .filter(|&i| self.since.map(|since| columns.timestamps[i] >= since).unwrap_or(true))
+            .filter(|&i| self.until.map(|until| columns.timestamps[i] < until).unwrap_or(true))
+            .filter(|&i| self.maker_side.map(|side| columns.maker_sides[i] == side).unwrap_or(true))
+            .collect();
+
+        if let Some(last) = self.last {
+            let skip = indices.len().saturating_sub(last);
+            indices.drain(..skip);
+        }
+        indices
+    }
+
+    /// Return the selected trades, sorted by timestamp.
+    pub fn collect(&self) -> Vec<Timestamped<Trade>> {
+        match self.columns {
+            Some(columns) => self.indices().into_iter().map(|i| columns.row(i)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Return the number of selected trades.
+    pub fn count(&self) -> usize {
+        self.indices().len()
+    }
+
+    /// Return the total size of the selected trades.
+    pub fn volume(&self) -> TickUnit {
+        match self.columns {
+            Some(columns) => self.indices().into_iter().map(|i| columns.sizes[i]).sum(),
+            None => 0,
+        }
+    }
+
+    /// Return the volume weighted average price of the selected trades in tick units,
+    /// or `None` if the total volume is zero.
+    pub fn vwap(&self) -> Option<f64> {
+        let columns = self.columns?;
+
+        let (mut notional, mut volume) = (0u128, 0u128);
+        for i in self.indices() {
+            notional += u128::from(columns.prices[i]) * u128::from(columns.sizes[i]);
+            volume += u128::from(columns.sizes[i]);
+        }
+
+        if volume == 0 {
+            return None;
+        }
+        Some(notional as f64 / volume as f64)
+    }
+
+    /// Aggregate the selected trades into bars.
+    pub fn bars(&self, resampler: &Resampler) -> Vec<Timestamped<Bar>> {
+        resampler.bars(self.collect())
+    }
+}
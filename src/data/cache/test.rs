@@ -0,0 +1,99 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::Trade;
+use crate::api::id::{ExchangeId, SymbolId};
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::cache::MarketDataCache;
+use crate::data::resample::Resampler;
+use crate::tick::TickUnit;
+
+fn trade(timestamp: Timestamp, price: TickUnit, size: TickUnit, maker_side: Side)
+    -> Timestamped<Trade>
+{
+    Trade {
+        price,
+        size,
+        maker_side,
+    }.with_timestamp(timestamp)
+}
+
+fn symbol(name: &str) -> SymbolId {
+    SymbolId::from_name(ExchangeId::Gdax, name).unwrap()
+}
+
+fn filled_cache() -> MarketDataCache {
+    let mut cache = MarketDataCache::new(1000);
+    cache.insert_trade(symbol("BTCUSD"), &trade(100, 10, 1, Side::Bid));
+    cache.insert_trade(symbol("BTCUSD"), &trade(200, 20, 3, Side::Ask));
+    cache.insert_trade(symbol("BTCUSD"), &trade(300, 30, 2, Side::Bid));
+    cache.insert_trade(symbol("ETHUSD"), &trade(300, 5, 1, Side::Bid));
+    cache
+}
+
+#[test]
+fn last_trades() {
+    let cache = filled_cache();
+
+    assert_eq!(
+        cache.trades(symbol("BTCUSD")).last(2).collect(),
+        vec![trade(200, 20, 3, Side::Ask), trade(300, 30, 2, Side::Bid)]
+    );
+
+    assert_eq!(
+        cache.trades(symbol("BTCUSD")).maker_side(Side::Bid).last(1).collect(),
+        vec![trade(300, 30, 2, Side::Bid)]
+    );
+
+    assert_eq!(cache.trades(symbol("ETHUSD")).count(), 1);
+    assert!(cache.trades(symbol("XRPUSD")).collect().is_empty());
+}
+
+#[test]
+fn aggregations() {
+    let cache = filled_cache();
+
+    assert_eq!(cache.trades(symbol("BTCUSD")).volume(), 6);
+    assert_eq!(cache.trades(symbol("BTCUSD")).since(200).volume(), 5);
+    assert_eq!(cache.trades(symbol("BTCUSD")).until(300).count(), 2);
+    assert_eq!(cache.trades(symbol("BTCUSD")).vwap(), Some(130. / 6.));
+    assert_eq!(cache.trades(symbol("BTCUSD")).since(1000).vwap(), None);
+}
+
+#[test]
+fn retention() {
+    let mut cache = filled_cache();
+    cache.insert_trade(symbol("BTCUSD"), &trade(1150, 40, 1, Side::Ask));
+    assert_eq!(
+        cache.trades(symbol("BTCUSD")).collect(),
+        vec![trade(200, 20, 3, Side::Ask), trade(300, 30, 2, Side::Bid), trade(1150, 40, 1, Side::Ask)]
+    );
+
+    let mut cache = filled_cache().with_max_len(1);
+    cache.insert_trade(symbol("BTCUSD"), &trade(400, 40, 1, Side::Ask));
+    assert_eq!(cache.trades(symbol("BTCUSD")).collect(), vec![trade(400, 40, 1, Side::Ask)]);
+}
+
+#[test]
+fn late_trade() {
+    let mut cache = filled_cache();
+    cache.insert_trade(symbol("BTCUSD"), &trade(150, 15, 1, Side::Ask));
+
+    assert_eq!(
+        cache.trades(symbol("BTCUSD")).last(3).collect(),
+        vec![trade(150, 15, 1, Side::Ask), trade(200, 20, 3, Side::Ask), trade(300, 30, 2, Side::Bid)]
+    );
+
+    let bars = cache.trades(symbol("BTCUSD")).bars(&Resampler::new(100));
+    assert_eq!(bars.len(), 3);
+    assert_eq!(bars[0].open, 10);
+    assert_eq!(bars[0].close, 15);
+    assert_eq!(bars[0].volume, 2);
+
+    // The late trade is evicted with the trades of its time.
+    cache.insert_trade(symbol("BTCUSD"), &trade(1180, 40, 1, Side::Ask));
+    assert_eq!(
+        cache.trades(symbol("BTCUSD")).collect(),
+        vec![trade(200, 20, 3, Side::Ask), trade(300, 30, 2, Side::Bid), trade(1180, 40, 1, Side::Ask)]
+    );
+}
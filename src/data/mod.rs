@@ -0,0 +1,33 @@
+//! A module defining utilities for working with previously collected market data,
+//! e.g. a `Vec<Notification>` which was dumped to disk and loaded back.
+
+pub mod resample;
+pub mod snapshot;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+
+use crate::api::Notification;
+use crate::api::Trade;
+use crate::api::timestamp::Timestamped;
+use crate::order_book::LimitUpdate;
+
+/// Extract the trades out of a sequence of notifications, preserving their order.
+pub fn trades<I>(notifications: I) -> impl Iterator<Item = Timestamped<Trade>>
+    where I: IntoIterator<Item = Notification>
+{
+    notifications.into_iter().filter_map(|notif| match notif {
+        Notification::Trade(trade) => Some(trade),
+        _ => None,
+    })
+}
+
+/// Extract the limit updates out of a sequence of notifications, preserving their order.
+pub fn limit_updates<I>(notifications: I) -> impl Iterator<Item = Timestamped<LimitUpdate>>
+    where I: IntoIterator<Item = Notification>
+{
+    notifications.into_iter().flat_map(|notif| match notif {
+        Notification::LimitUpdates(updates) => updates,
+        _ => Vec::new(),
+    })
+}
@@ -0,0 +1,287 @@
+//! A module defining utilities for converting irregularly spaced trades and limit
+//! updates into fixed-interval series.
+//!
+//! All the series produced here are indexed by the *start* of each interval, and
+//! contain one entry per interval between the first and the last event, including
+//! intervals during which nothing happened.
+
+mod test;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::api::Trade;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::order_book::{OrderBook, LimitUpdate};
+use crate::tick::TickUnit;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// How interval boundaries are chosen.
+pub enum Alignment {
+    /// Intervals start at a multiple of the interval length since the UNIX epoch,
+    /// e.g. one minute bars start at `hh:mm:00.000`.
+    Epoch,
+
+    /// Intervals start at the timestamp of the first event, then every interval length.
+    FirstEvent,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An OHLCV bar, prices and sizes are expressed in tick units.
+pub struct Bar {
+    /// Price of the first trade of the interval.
+    pub open: TickUnit,
+
+    /// Highest traded price of the interval.
+    pub high: TickUnit,
+
+    /// Lowest traded price of the interval.
+    pub low: TickUnit,
+
+    /// Price of the last trade of the interval.
+    pub close: TickUnit,
+
+    /// Total traded size during the interval.
+    pub volume: TickUnit,
+
+    /// Number of trades during the interval.
+    pub count: u64,
+}
+
+impl Bar {
+    fn new(trade: &Trade) -> Self {
+        Bar {
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.size,
+            count: 1,
+        }
+    }
+
+    /// A bar for an interval without any trade: all prices are equal to `price`.
+    fn flat(price: TickUnit) -> Self {
+        Bar {
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 0,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, trade: &Trade) {
+        self.high = std::cmp::max(self.high, trade.price);
+        self.low = std::cmp::min(self.low, trade.price);
+        self.close = trade.price;
+        self.volume += trade.size;
+        self.count += 1;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// State of the top of the book. Follows the same conventions as `OrderBook`: if the
+/// bid side is empty, `bid_price` is `0`, and if the ask side is empty, `ask_price`
+/// is `TickUnit::max_value()`.
+pub struct TopOfBook {
+    /// Best bid price.
+    pub bid_price: TickUnit,
+
+    /// Size available at the best bid price.
+    pub bid_size: TickUnit,
+
+    /// Best ask price.
+    pub ask_price: TickUnit,
+
+    /// Size available at the best ask price.
+    pub ask_size: TickUnit,
+}
+
+impl TopOfBook {
+    /// Return the top of the given order book.
+    pub fn from_order_book(order_book: &OrderBook) -> Self {
+        let (bid_price, bid_size) = order_book.bid()
+            .next()
+            .map(|(price, size)| (*price, *size))
+            .unwrap_or((0, 0));
+        let (ask_price, ask_size) = order_book.ask()
+            .next()
+            .map(|(price, size)| (*price, *size))
+            .unwrap_or((TickUnit::max_value(), 0));
+
+        TopOfBook {
+            bid_price,
+            bid_size,
+            ask_price,
+            ask_size,
+        }
+    }
+
+    /// Return the mid price in tick units, or `None` if one side of the book is empty.
+    pub fn mid_price(&self) -> Option<f64> {
+        if self.bid_size == 0 || self.ask_size == 0 {
+            return None;
+        }
+        Some((self.bid_price as f64 + self.ask_price as f64) / 2.)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// Converts timestamped events into fixed-interval series.
+pub struct Resampler {
+    interval: Timestamp,
+    alignment: Alignment,
+}
+
+impl Resampler {
+    /// Return a new `Resampler` producing one entry every `interval` ms, with
+    /// intervals aligned on the UNIX epoch.
+    ///
+    /// # Panics
+    /// Panic if `interval` is `0`.
+    pub fn new(interval: Timestamp) -> Self {
+        if interval == 0 {
+            panic!("`interval` cannot be 0");
+        }
+
+        Resampler {
+            interval,
+            alignment: Alignment::Epoch,
+        }
+    }
+
+    /// Set how interval boundaries are chosen.
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Return the chosen interval length, in ms.
+    pub fn interval(&self) -> Timestamp {
+        self.interval
+    }
+
+    /// Return the chosen alignment.
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Return the start of the interval containing `timestamp`, given the timestamp
+    /// of the very first event, or `None` if `timestamp` is older than that event.
+    fn interval_start(&self, first: Timestamp, timestamp: Timestamp) -> Option<Timestamp> {
+        if timestamp < first {
+            return None;
+        }
+
+        let origin = match self.alignment {
+            Alignment::Epoch => 0,
+            Alignment::FirstEvent => first,
+        };
+        Some(origin + (timestamp - origin) / self.interval * self.interval)
+    }
+
+    /// Aggregate `trades` into OHLCV bars. Intervals without trades yield a flat bar
+    /// at the previous close with a zero volume.
+    ///
+    /// # Panics
+    /// Panic if `trades` are not sorted by timestamp.
+    pub fn bars<I>(&self, trades: I) -> Vec<Timestamped<Bar>>
+        where I: IntoIterator<Item = Timestamped<Trade>>
+    {
+        let mut bars: Vec<Timestamped<Bar>> = Vec::new();
+        let mut first = None;
+
+        for trade in trades {
+            let first = *first.get_or_insert(trade.timestamp());
+            let start = self.interval_start(first, trade.timestamp())
+                .expect("trades are not sorted by timestamp");
+
+            let (last_start, last_close) = match bars.last_mut() {
+                Some(bar) if bar.timestamp() == start => {
+                    let mut inner = **bar;
+                    inner.push(&trade);
+                    *bar = inner.with_timestamp(start);
+                    continue;
+                }
+                Some(bar) if bar.timestamp() > start => {
+                    panic!("trades are not sorted by timestamp");
+                }
+                Some(bar) => (bar.timestamp(), bar.close),
+                None => {
+                    bars.push(Bar::new(&trade).with_timestamp(start));
+                    continue;
+                }
+            };
+
+            let mut empty = last_start + self.interval;
+            while empty < start {
+                bars.push(Bar::flat(last_close).with_timestamp(empty));
+                empty += self.interval;
+            }
+            bars.push(Bar::new(&trade).with_timestamp(start));
+        }
+
+        bars
+    }
+
+    /// Replay `updates` on an initially empty order book and sample the top of the
+    /// book as it stands at the end of each interval.
+    ///
+    /// # Panics
+    /// Panic if `updates` are not sorted by timestamp.
+    pub fn top_of_book<I>(&self, updates: I) -> Vec<Timestamped<TopOfBook>>
+        where I: IntoIterator<Item = Timestamped<LimitUpdate>>
+    {
+        let mut series = Vec::new();
+        let mut order_book = OrderBook::new();
+        let mut first = None;
+        let mut current: Option<Timestamp> = None;
+
+        for update in updates {
+            let first = *first.get_or_insert(update.timestamp());
+            let start = self.interval_start(first, update.timestamp())
+                .expect("limit updates are not sorted by timestamp");
+
+            if let Some(mut current_start) = current {
+                if start < current_start {
+                    panic!("limit updates are not sorted by timestamp");
+                }
+
+                // Close all the intervals before the one containing this update.
+                while current_start < start {
+                    series.push(
+                        TopOfBook::from_order_book(&order_book).with_timestamp(current_start)
+                    );
+                    current_start += self.interval;
+                }
+            }
+            current = Some(start);
+            order_book.update(update.into_inner());
+        }
+
+        if let Some(current_start) = current {
+            series.push(TopOfBook::from_order_book(&order_book).with_timestamp(current_start));
+        }
+
+        series
+    }
+
+    /// Same as `top_of_book`, but only keep the mid price. Intervals at the end of
+    /// which one side of the book was empty carry the previous mid price forward, those
+    /// before the first mid price are skipped.
+    pub fn mid_prices<I>(&self, updates: I) -> Vec<Timestamped<f64>>
+        where I: IntoIterator<Item = Timestamped<LimitUpdate>>
+    {
+        let mut last = None;
+        self.top_of_book(updates)
+            .into_iter()
+            .filter_map(|top| {
+                let timestamp = top.timestamp();
+                if let Some(mid) = top.mid_price() {
+                    last = Some(mid);
+                }
+                last.map(|mid| mid.with_timestamp(timestamp))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::Trade;
+use crate::api::timestamp::{Timestamp, Timestamped, IntoTimestamped};
+use crate::data::resample::{Resampler, Alignment, Bar, TopOfBook};
+use crate::order_book::LimitUpdate;
+use crate::tick::TickUnit;
+
+fn trade(timestamp: Timestamp, price: TickUnit, size: TickUnit) -> Timestamped<Trade> {
+    Trade {
+        price,
+        size,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp)
+}
+
+fn lu(timestamp: Timestamp, price: TickUnit, size: TickUnit, side: Side)
+    -> Timestamped<LimitUpdate>
+{
+    LimitUpdate::new(price, size, side).with_timestamp(timestamp)
+}
+
+#[test]
+fn bars() {
+    let trades = vec![
+        trade(1005, 100, 1),
+        trade(1500, 102, 2),
+        trade(1999, 99, 3),
+        trade(3200, 101, 4),
+    ];
+
+    let bars = Resampler::new(1000).bars(trades);
+    assert_eq!(
+        bars,
+        vec![
+            Bar { open: 100, high: 102, low: 99, close: 99, volume: 6, count: 3 }
+                .with_timestamp(1000),
+            Bar { open: 99, high: 99, low: 99, close: 99, volume: 0, count: 0 }
+                .with_timestamp(2000),
+            Bar { open: 101, high: 101, low: 101, close: 101, volume: 4, count: 1 }
+                .with_timestamp(3000),
+        ]
+    );
+}
+
+#[test]
+fn bars_first_event_alignment() {
+    let trades = vec![
+        trade(1005, 100, 1),
+        trade(1999, 99, 3),
+        trade(2005, 101, 4),
+    ];
+
+    let bars = Resampler::new(1000)
+        .with_alignment(Alignment::FirstEvent)
+        .bars(trades);
+    assert_eq!(
+        bars,
+        vec![
+            Bar { open: 100, high: 100, low: 99, close: 99, volume: 4, count: 2 }
+                .with_timestamp(1005),
+            Bar { open: 101, high: 101, low: 101, close: 101, volume: 4, count: 1 }
+                .with_timestamp(2005),
+        ]
+    );
+}
+
+#[test]
+fn top_of_book() {
+    let updates = vec![
+        lu(10, 100, 5, Side::Bid),
+        lu(20, 102, 3, Side::Ask),
+        lu(120, 101, 2, Side::Bid),
+        lu(350, 102, 0, Side::Ask),
+    ];
+
+    let resampler = Resampler::new(100);
+    assert_eq!(
+        resampler.top_of_book(updates.clone()),
+        vec![
+            TopOfBook { bid_price: 100, bid_size: 5, ask_price: 102, ask_size: 3 }
+                .with_timestamp(0),
+            TopOfBook { bid_price: 101, bid_size: 2, ask_price: 102, ask_size: 3 }
+                .with_timestamp(100),
+            TopOfBook { bid_price: 101, bid_size: 2, ask_price: 102, ask_size: 3 }
+                .with_timestamp(200),
+            TopOfBook { bid_price: 101, bid_size: 2, ask_price: TickUnit::max_value(), ask_size: 0 }
+                .with_timestamp(300),
+        ]
+    );
+
+    assert_eq!(
+        resampler.mid_prices(updates),
+        vec![
+            101.0.with_timestamp(0),
+            101.5.with_timestamp(100),
+            101.5.with_timestamp(200),
+            101.5.with_timestamp(300),
+        ]
+    );
+}
+
+#[test]
+fn mid_prices_gap() {
+    let updates = vec![
+        lu(10, 100, 5, Side::Bid),
+        lu(150, 102, 3, Side::Ask),
+        lu(320, 102, 0, Side::Ask),
+        lu(510, 101, 1, Side::Ask),
+    ];
+
+    // No mid price yet at 0, the book is untouched at 200 and 400 and one-sided at 300.
+    assert_eq!(
+        Resampler::new(100).mid_prices(updates),
+        vec![
+            101.0.with_timestamp(100),
+            101.0.with_timestamp(200),
+            101.0.with_timestamp(300),
+            101.0.with_timestamp(400),
+            100.5.with_timestamp(500),
+        ]
+    );
+}
+
+#[test]
+#[should_panic]
+fn unsorted_trades() {
+    let _ = Resampler::new(10).bars(vec![trade(100, 1, 1), trade(50, 1, 1)]);
+}
+
+#[test]
+#[should_panic(expected = "not sorted by timestamp")]
+fn unsorted_trades_first_event_alignment() {
+    let _ = Resampler::new(10)
+        .with_alignment(Alignment::FirstEvent)
+        .bars(vec![trade(100, 1, 1), trade(50, 1, 1)]);
+}
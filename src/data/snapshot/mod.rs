@@ -0,0 +1,73 @@
+//! A module defining a canonical text representation of notification sequences, meant
+//! for snapshot testing: the output of a handler or a strategy can be compared against
+//! a reference file, and changes in behavior show up as readable diffs.
+
+mod test;
+
+use serde_json::Value;
+use crate::api::Notification;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// How timestamps are written.
+pub enum Timestamps {
+    /// Keep timestamps as is.
+    Keep,
+
+    /// Write timestamps as an offset from the first timestamp of the sequence, so that
+    /// recordings shifted in time compare equal.
+    Relative,
+
+    /// Replace all timestamps by `0`, e.g. for notifications timestamped with the
+    /// current time.
+    Erase,
+}
+
+fn normalize(value: &mut Value, timestamps: Timestamps, origin: &mut Option<i64>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match (key.as_ref(), value.as_i64()) {
+                    ("timestamp", Some(timestamp)) |
+                    ("corrected_timestamp", Some(timestamp)) => {
+                        *value = match timestamps {
+                            Timestamps::Keep => continue,
+                            Timestamps::Relative => {
+                                (timestamp - *origin.get_or_insert(timestamp)).into()
+                            }
+                            Timestamps::Erase => 0.into(),
+                        };
+                    }
+                    _ => normalize(value, timestamps, origin),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                normalize(value, timestamps, origin);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Return the canonical text representation of `notifications`: one JSON line per
+/// notification, with object fields sorted by name.
+///
+/// # Panics
+/// Panic if a notification cannot be serialized.
+pub fn canonical_text<'a, I>(notifications: I, timestamps: Timestamps) -> String
+    where I: IntoIterator<Item = &'a Notification>
+{
+    let mut origin = None;
+    let mut text = String::new();
+
+    for notification in notifications {
+        let mut value = serde_json::to_value(notification)
+            .expect("failed to serialize notification");
+        normalize(&mut value, timestamps, &mut origin);
+
+        text += &value.to_string();
+        text.push('\n');
+    }
+    text
+}
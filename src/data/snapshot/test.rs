@@ -0,0 +1,44 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade};
+use crate::api::timestamp::IntoTimestamped;
+use crate::data::snapshot::{canonical_text, Timestamps};
+use crate::order_book::LimitUpdate;
+
+fn notifications() -> Vec<Notification> {
+    vec![
+        Notification::LimitUpdates(vec![
+            LimitUpdate::new(100, 5, Side::Bid).with_timestamp(1500),
+            LimitUpdate::new(101, 0, Side::Ask).with_timestamp(1500),
+        ]),
+        Notification::Trade(Trade {
+            price: 101,
+            size: 2,
+            maker_side: Side::Ask,
+        }.with_timestamp(1510)),
+    ]
+}
+
+#[test]
+fn relative_timestamps() {
+    assert_eq!(
+        canonical_text(&notifications(), Timestamps::Relative),
+        concat!(
+            r#"{"LimitUpdates":[{"price":100,"side":"Bid","size":5,"timestamp":0},"#,
+            r#"{"price":101,"side":"Ask","size":0,"timestamp":0}]}"#, "\n",
+            r#"{"Trade":{"maker_side":"Ask","price":101,"size":2,"timestamp":10}}"#, "\n",
+        )
+    );
+}
+
+#[test]
+fn erased_timestamps() {
+    let text = canonical_text(&notifications(), Timestamps::Erase);
+    assert!(text.ends_with(
+        "{\"Trade\":{\"maker_side\":\"Ask\",\"price\":101,\"size\":2,\"timestamp\":0}}\n"
+    ));
+
+    let text = canonical_text(&notifications(), Timestamps::Keep);
+    assert!(text.contains("\"timestamp\":1510"));
+}
@@ -0,0 +1,232 @@
+//! A module defining baskets of orders over several symbols, executed as a unit.
+
+mod test;
+
+use std::sync::Arc;
+use futures::prelude::*;
+use futures::future::{self, Either};
+use failure_derive::Fail;
+use crate::api::{ApiClient, Order, Cancel, Notification};
+use crate::api::errors::{OrderError, CancelError};
+use crate::api::symbol::{Symbol, IntoWithSymbol};
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Debug)]
+struct Leg {
+    symbol: Symbol,
+    order: Order,
+    weight: f64,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+/// A weighted list of orders, possibly on different symbols, which are sent together.
+/// If any of them is rejected, the others are canceled.
+///
+/// Each leg is a limit order, hence its price acts as the limit for this leg.
+pub struct Basket {
+    legs: Vec<Leg>,
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "basket leg {} was rejected", leg)]
+/// An error indicating that a basket was aborted because some legs were rejected.
+pub struct BasketError {
+    /// Index of the first rejected leg.
+    pub leg: usize,
+
+    /// Error returned for this leg.
+    #[cause]
+    pub error: OrderError,
+
+    /// Indices of all the rejected legs.
+    pub rejected: Vec<usize>,
+
+    /// Legs which were accepted but could not be canceled, along with the cancel error.
+    pub not_canceled: Vec<(usize, CancelError)>,
+}
+
+impl Basket {
+    /// Return an empty basket.
+    pub fn new() -> Self {
+        Basket {
+            legs: Vec::new(),
+        }
+    }
+
+    /// Add a leg sending `order` on `symbol`, with a relative `weight` used for
+    /// computing the progress of the whole basket.
+    ///
+    /// # Panics
+    /// Panic if `order` has no order id, since it is needed for tracking the fills,
+    /// or if `weight` is not positive.
+    pub fn with_leg(mut self, symbol: Symbol, order: Order, weight: f64) -> Self {
+        if order.order_id().is_none() {
+            panic!("basket orders must have an order id");
+        }
+        if weight.is_nan() || weight <= 0. {
+            panic!("`weight` must be positive");
+        }
+
+        self.legs.push(Leg {
+            symbol,
+            order,
+            weight,
+        });
+        self
+    }
+
+    /// Return the number of legs.
+    pub fn len(&self) -> usize {
+        self.legs.len()
+    }
+
+    /// Return `true` if the basket has no legs.
+    pub fn is_empty(&self) -> bool {
+        self.legs.is_empty()
+    }
+
+    /// Send all the legs concurrently. If all of them are accepted, return a
+    /// `BasketExecution` for tracking their progress. Otherwise, cancel the accepted
+    /// legs and return an error.
+    pub fn submit<C>(self, client: Arc<C>)
+        -> impl Future<Item = BasketExecution, Error = BasketError> + Send + 'static
+            where C: ApiClient + Send + Sync + 'static
+    {
+        let orders: Vec<_> = self.legs.iter()
+            .map(|leg| client.order(leg.order.with_symbol(leg.symbol)).then(Ok))
+            .collect();
+
+        future::join_all(orders).and_then(move |results| {
+            if results.iter().all(|res| res.is_ok()) {
+                let order_ids = results.into_iter()
+                    .map(|res| res.unwrap().into_inner().order_id)
+                    .collect();
+                return Either::A(future::ok(BasketExecution::new(&self, order_ids)));
+            }
+
+            let mut accepted = Vec::new();
+            let mut errors = Vec::new();
+            for (index, res) in results.into_iter().enumerate() {
+                match res {
+                    Ok(ack) => accepted.push((index, ack.into_inner().order_id)),
+                    Err(err) => errors.push((index, err)),
+                }
+            }
+
+            let cancels: Vec<_> = accepted.into_iter()
+                .map(|(index, order_id)| {
+                    let symbol = self.legs[index].symbol;
+                    client.cancel(Cancel::new(order_id).with_symbol(symbol))
+                        .then(move |res| Ok((index, res)))
+                })
+                .collect();
+
+            Either::B(future::join_all(cancels).and_then(move |results| {
+                let not_canceled = results.into_iter()
+                    .filter_map(|(index, res)| res.err().map(|err| (index, err)))
+                    .collect();
+                let rejected = errors.iter().map(|(index, _)| *index).collect();
+                let (leg, error) = errors.into_iter().next().unwrap();
+
+                Err(BasketError {
+                    leg,
+                    error,
+                    rejected,
+                    not_canceled,
+                })
+            }))
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct LegProgress {
+    symbol: Symbol,
+    order_id: String,
+    weight: f64,
+    size: TickUnit,
+    filled: TickUnit,
+    expired: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// Progress of a basket whose legs have all been accepted.
+pub struct BasketExecution {
+    legs: Vec<LegProgress>,
+}
+
+impl BasketExecution {
+    fn new(basket: &Basket, order_ids: Vec<String>) -> Self {
+        let legs = basket.legs.iter()
+            .zip(order_ids)
+            .map(|(leg, order_id)| LegProgress {
+                symbol: leg.symbol,
+                order_id,
+                weight: leg.weight,
+                size: leg.order.size().ticked(leg.symbol.size_tick()),
+                filled: 0,
+                expired: false,
+            })
+            .collect();
+
+        BasketExecution {
+            legs,
+        }
+    }
+
+    /// Process a notification from the stream of any of the basket symbols.
+    pub fn on_notification(&mut self, notification: &Notification) {
+        match notification {
+            Notification::OrderUpdate(update) => {
+                if let Some(leg) = self.legs.iter_mut().find(|l| l.order_id == update.order_id) {
+                    leg.filled = leg.size.saturating_sub(update.remaining_size);
+                }
+            }
+            Notification::OrderExpiration(expiration) => {
+                if let Some(leg) = self.legs.iter_mut().find(|l| l.order_id == expiration.order_id) {
+                    leg.expired = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Return the size filled so far for the leg at index `leg`, in tick units.
+    pub fn filled(&self, leg: usize) -> TickUnit {
+        self.legs[leg].filled
+    }
+
+    /// Return the weighted fraction of the basket which has been filled, between `0` and `1`.
+    pub fn progress(&self) -> f64 {
+        let total: f64 = self.legs.iter().map(|leg| leg.weight).sum();
+        let filled: f64 = self.legs.iter()
+            .map(|leg| leg.weight * leg.filled as f64 / leg.size as f64)
+            .sum();
+        filled / total
+    }
+
+    /// Return `true` if all the legs have been entirely filled.
+    pub fn is_complete(&self) -> bool {
+        self.legs.iter().all(|leg| leg.filled == leg.size)
+    }
+
+    /// Return `true` if some leg expired before being entirely filled, in which case the
+    /// basket cannot complete and should be aborted.
+    pub fn is_broken(&self) -> bool {
+        self.legs.iter().any(|leg| leg.expired && leg.filled < leg.size)
+    }
+
+    /// Cancel all the legs which are still live. Fills which already happened are not
+    /// unwound.
+    pub fn abort<C>(&self, client: Arc<C>)
+        -> impl Future<Item = (), Error = CancelError> + Send + 'static
+            where C: ApiClient + Send + Sync + 'static
+    {
+        let cancels: Vec<_> = self.legs.iter()
+            .filter(|leg| !leg.expired && leg.filled < leg.size)
+            .map(|leg| client.cancel(Cancel::new(leg.order_id.clone()).with_symbol(leg.symbol)))
+            .collect();
+
+        future::join_all(cancels).map(|_| ())
+    }
+}
@@ -0,0 +1,77 @@
+#![cfg(all(test, feature = "binance"))]
+
+use crate::Side;
+use crate::api::binance;
+use crate::api::{Notification, Order, OrderUpdate, OrderExpiration};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::execution::basket::{Basket, BasketExecution};
+use crate::tick::Tick;
+
+fn symbol(name: &str) -> Symbol {
+    Symbol::new(name, Tick::new(100), Tick::new(100)).unwrap()
+}
+
+fn order(order_id: &str, size: &str) -> Order {
+    Order::new(100, size, Side::Bid).with_order_id::<binance::Client>(order_id)
+}
+
+fn update(order_id: &str, remaining_size: u64) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: 0,
+        remaining_size,
+        consumed_price: 100,
+        commission: 0,
+    }.timestamped())
+}
+
+fn execution() -> BasketExecution {
+    let basket = Basket::new()
+        .with_leg(symbol("BTCUSD"), order("a", "1"), 3.)
+        .with_leg(symbol("ETHUSD"), order("b", "2"), 1.);
+    BasketExecution::new(&basket, vec!["a".to_owned(), "b".to_owned()])
+}
+
+#[test]
+fn progress() {
+    let mut execution = execution();
+    assert_eq!(execution.progress(), 0.);
+
+    execution.on_notification(&update("b", 100));
+    assert_eq!(execution.filled(1), 100);
+    assert_eq!(execution.progress(), 0.125);
+
+    execution.on_notification(&update("a", 0));
+    assert_eq!(execution.progress(), 0.875);
+    assert!(!execution.is_complete());
+
+    execution.on_notification(&update("b", 0));
+    assert!(execution.is_complete());
+    assert_eq!(execution.progress(), 1.);
+}
+
+#[test]
+fn broken() {
+    let mut execution = execution();
+    execution.on_notification(&update("a", 0));
+    execution.on_notification(&Notification::OrderExpiration(OrderExpiration {
+        order_id: "a".to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.timestamped()));
+    assert!(!execution.is_broken());
+
+    execution.on_notification(&Notification::OrderExpiration(OrderExpiration {
+        order_id: "b".to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.timestamped()));
+    assert!(execution.is_broken());
+}
+
+#[test]
+#[should_panic]
+fn missing_order_id() {
+    let _ = Basket::new().with_leg(symbol("BTCUSD"), Order::new(100, 1, Side::Bid), 1.);
+}
@@ -0,0 +1,323 @@
+//! A module defining a scheduler of recurring purchases, i.e. dollar cost averaging:
+//! buying a fixed quote value of a symbol at regular times, e.g. 100 USDT of `BTCUSDT`
+//! every day at 14:00 UTC, through any `ApiClient`.
+//!
+//! The scheduler does not deal with time by itself. The caller periodically takes the
+//! purchases due through `DcaScheduler::take_due`, executes each one with `execute`, and
+//! records the resulting `ExecutionReport` through `DcaScheduler::on_report`. A purchase
+//! is skipped, and reported as such, when the free balance of the quote asset does not
+//! cover it.
+//!
+//! The state of the scheduler, i.e. the plans, the time of their next purchase and the
+//! reports, can be saved with `DcaState::save` after each step, so that a restarted
+//! process neither repeats nor forgets purchases. A purchase is marked as done once
+//! taken, before its order is sent: purchases are executed at most once, and those
+//! missed while the process was stopped are coalesced into a single one.
+
+mod test;
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use futures::prelude::*;
+use futures::future::{self, Either};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::{ApiClient, Balances, Order, QuoteSize};
+use crate::api::symbol::IntoWithSymbol;
+use crate::api::timestamp::{timestamp_ms, Timestamp};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Times at which a purchase recurs: every `period`, shifted by `offset` from the Unix
+/// epoch (a Thursday), both in ms.
+pub struct Recurrence {
+    period: Timestamp,
+    offset: Timestamp,
+}
+
+impl Recurrence {
+    /// Return a recurrence every `period`, shifted by `offset`, e.g. a period of
+    /// `calendar::DAY` and an offset of `14 * calendar::HOUR` for every day at 14:00 UTC.
+    ///
+    /// # Panics
+    /// Panic if `period` is zero.
+    pub fn new(period: Timestamp, offset: Timestamp) -> Self {
+        if period == 0 {
+            panic!("`period` must be positive");
+        }
+
+        Recurrence {
+            period,
+            offset: offset % period,
+        }
+    }
+
+    /// Return the period of the recurrence, in ms.
+    pub fn period(&self) -> Timestamp {
+        self.period
+    }
+
+    /// Return the offset of the recurrence from the Unix epoch, in ms.
+    pub fn offset(&self) -> Timestamp {
+        self.offset
+    }
+
+    /// Return the first time of the recurrence strictly after `after`.
+    pub fn next_after(&self, after: Timestamp) -> Timestamp {
+        if after < self.offset {
+            return self.offset;
+        }
+        (after - self.offset) / self.period * self.period + self.offset + self.period
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A recurring purchase.
+pub struct DcaPlan {
+    /// Unique id of the plan, used as the hint of the order ids.
+    pub id: String,
+
+    /// Name of the symbol bought.
+    pub symbol: String,
+
+    /// Quote value bought at each purchase, unticked, e.g. `"100"` for 100 USDT of
+    /// `BTCUSDT`. Purchases are market orders with a quote size, see
+    /// `Order::market_quote` for the exchanges supporting them.
+    pub amount: String,
+
+    /// Quote asset of the symbol, e.g. `USDT`, whose free balance must cover `amount`.
+    pub quote_asset: String,
+
+    /// Times of the purchases.
+    pub recurrence: Recurrence,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Outcome of a purchase.
+pub enum Outcome {
+    /// The order was accepted by the exchange.
+    Submitted {
+        /// Id of the order.
+        order_id: String,
+    },
+
+    /// The purchase was skipped, the free balance of the quote asset being below the
+    /// amount of the plan.
+    InsufficientBalance {
+        /// Free balance of the quote asset, unticked.
+        free: String,
+    },
+
+    /// The balances or the symbol could not be retrieved, or the order was rejected.
+    Failed {
+        /// Description of the error.
+        error: String,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Report of a purchase, as returned by `execute`.
+pub struct ExecutionReport {
+    /// Id of the plan.
+    pub plan_id: String,
+
+    /// Time at which the purchase was scheduled.
+    pub scheduled: Timestamp,
+
+    /// Time at which the purchase was executed.
+    pub executed: Timestamp,
+
+    /// Outcome of the purchase.
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+struct PlanState {
+    plan: DcaPlan,
+
+    /// Time of the next purchase.
+    next_run: Timestamp,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Persistent state of a `DcaScheduler`.
+pub struct DcaState {
+    plans: Vec<PlanState>,
+    reports: Vec<ExecutionReport>,
+}
+
+impl DcaState {
+    /// Load a state saved with `DcaState::save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, failure::Error> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Save the state to `path` as JSON. The file is replaced atomically, so that a
+    /// crash never leaves a truncated state behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), failure::Error> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.into_inner()?.sync_all()?;
+
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+/// Schedule the purchases of several plans, see the module documentation.
+pub struct DcaScheduler {
+    state: DcaState,
+}
+
+impl DcaScheduler {
+    /// Return a scheduler without any plan.
+    pub fn new() -> Self {
+        DcaScheduler {
+            state: DcaState::default(),
+        }
+    }
+
+    /// Return a scheduler resuming from `state`, e.g. loaded with `DcaState::load`.
+    pub fn from_state(state: DcaState) -> Self {
+        DcaScheduler {
+            state,
+        }
+    }
+
+    /// Return the state of the scheduler, to be saved after each change.
+    pub fn state(&self) -> &DcaState {
+        &self.state
+    }
+
+    /// Add a plan, whose first purchase is the first time of its recurrence after `now`.
+    /// A plan with the same id is replaced.
+    pub fn add_plan(&mut self, plan: DcaPlan, now: Timestamp) {
+        self.remove_plan(&plan.id);
+        let next_run = plan.recurrence.next_after(now);
+        self.state.plans.push(PlanState {
+            plan,
+            next_run,
+        });
+    }
+
+    /// Remove the plan with the given id, if any.
+    pub fn remove_plan(&mut self, id: &str) -> Option<DcaPlan> {
+        let index = self.state.plans.iter().position(|state| state.plan.id == id)?;
+        Some(self.state.plans.remove(index).plan)
+    }
+
+    /// Return an iterator over the plans, in the order they were added.
+    pub fn plans(&self) -> impl Iterator<Item = &DcaPlan> {
+        self.state.plans.iter().map(|state| &state.plan)
+    }
+
+    /// Return the time of the next purchase of any plan.
+    pub fn next_run(&self) -> Option<Timestamp> {
+        self.state.plans.iter().map(|state| state.next_run).min()
+    }
+
+    /// Return the purchases due at `now`, along with the time at which they were
+    /// scheduled, and schedule the next purchase of their plans.
+    pub fn take_due(&mut self, now: Timestamp) -> Vec<(DcaPlan, Timestamp)> {
+        let mut due = Vec::new();
+        for state in &mut self.state.plans {
+            if state.next_run <= now {
+                // Purchases missed in the meantime are coalesced into this one.
+                due.push((state.plan.clone(), state.next_run));
+                state.next_run = state.plan.recurrence.next_after(now);
+            }
+        }
+        due
+    }
+
+    /// Record the report of a purchase.
+    pub fn on_report(&mut self, report: ExecutionReport) {
+        self.state.reports.push(report);
+    }
+
+    /// Return the reports recorded so far, oldest first.
+    pub fn reports(&self) -> &[ExecutionReport] {
+        &self.state.reports
+    }
+
+    /// Forget the reports recorded so far, e.g. once they have been exported.
+    pub fn clear_reports(&mut self) {
+        self.state.reports.clear();
+    }
+}
+
+/// Return `Outcome::InsufficientBalance` if the free balance of the quote asset of
+/// `plan` does not cover its amount.
+fn check_balance(plan: &DcaPlan, balances: &Balances) -> Option<Outcome> {
+    let free = balances.get(&plan.quote_asset)
+        .map(|balance| balance.free.clone())
+        .unwrap_or_else(|| "0".to_owned());
+
+    let covered = match (free.parse::<f64>(), plan.amount.parse::<f64>()) {
+        (Ok(free), Ok(amount)) => free >= amount,
+        _ => false,
+    };
+
+    if covered {
+        None
+    } else {
+        Some(Outcome::InsufficientBalance { free })
+    }
+}
+
+/// Execute the purchase of `plan` scheduled at `scheduled`: check the free balance of
+/// the quote asset, then send a market order for the amount of the plan. Errors are
+/// reported in the `ExecutionReport` rather than returned.
+pub fn execute<C>(client: Arc<C>, plan: DcaPlan, scheduled: Timestamp)
+    -> impl Future<Item = ExecutionReport, Error = ()> + Send + 'static
+        where C: ApiClient + Send + Sync + 'static
+{
+    let report = move |plan_id: String, outcome| ExecutionReport {
+        plan_id,
+        scheduled,
+        executed: timestamp_ms(),
+        outcome,
+    };
+
+    client.balances().then(move |balances| {
+        let balances = match balances {
+            Ok(balances) => balances,
+            Err(err) => {
+                let outcome = Outcome::Failed { error: err.to_string() };
+                return Either::A(future::ok(report(plan.id, outcome)));
+            }
+        };
+
+        if let Some(outcome) = check_balance(&plan, &balances) {
+            return Either::A(future::ok(report(plan.id, outcome)));
+        }
+
+        let symbol = match client.find_symbol(&plan.symbol) {
+            Some(symbol) => symbol,
+            None => {
+                let outcome = Outcome::Failed {
+                    error: format!("unknown symbol `{}`", plan.symbol),
+                };
+                return Either::A(future::ok(report(plan.id, outcome)));
+            }
+        };
+
+        let order = Order::market_quote(QuoteSize::new(plan.amount.as_str()), Side::Bid)
+            .with_order_id::<C>(&format!("dca-{}-{}", plan.id, scheduled));
+
+        Either::B(client.order(order.with_symbol(symbol)).then(move |res| {
+            let outcome = match res {
+                Ok(ack) => Outcome::Submitted { order_id: ack.into_inner().order_id },
+                Err(err) => Outcome::Failed { error: err.to_string() },
+            };
+            Ok(report(plan.id, outcome))
+        }))
+    })
+}
@@ -0,0 +1,116 @@
+#![cfg(test)]
+
+use crate::api::{Balance, Balances};
+use crate::api::calendar::{DAY, HOUR};
+use crate::execution::dca::{
+    check_balance,
+    DcaPlan,
+    DcaScheduler,
+    DcaState,
+    ExecutionReport,
+    Outcome,
+    Recurrence,
+};
+
+fn plan(id: &str, recurrence: Recurrence) -> DcaPlan {
+    DcaPlan {
+        id: id.to_owned(),
+        symbol: "BTCUSDT".to_owned(),
+        amount: "100".to_owned(),
+        quote_asset: "USDT".to_owned(),
+        recurrence,
+    }
+}
+
+fn balances(free: &str) -> Balances {
+    let mut balances = Balances::new();
+    balances.insert("USDT".to_owned(), Balance {
+        free: free.to_owned(),
+        locked: "0".to_owned(),
+    });
+    balances
+}
+
+#[test]
+fn recurrence() {
+    let daily = Recurrence::new(DAY, 14 * HOUR);
+    assert_eq!(daily.next_after(0), 14 * HOUR);
+    assert_eq!(daily.next_after(14 * HOUR - 1), 14 * HOUR);
+    assert_eq!(daily.next_after(14 * HOUR), DAY + 14 * HOUR);
+    assert_eq!(daily.next_after(3 * DAY + 20 * HOUR), 4 * DAY + 14 * HOUR);
+
+    // Offsets beyond the period wrap around.
+    assert_eq!(Recurrence::new(DAY, DAY + HOUR), Recurrence::new(DAY, HOUR));
+}
+
+#[test]
+#[should_panic]
+fn zero_period() {
+    Recurrence::new(0, 0);
+}
+
+#[test]
+fn take_due() {
+    let mut scheduler = DcaScheduler::new();
+    scheduler.add_plan(plan("daily", Recurrence::new(DAY, 14 * HOUR)), 0);
+    scheduler.add_plan(plan("hourly", Recurrence::new(HOUR, 0)), 0);
+    assert_eq!(scheduler.next_run(), Some(HOUR));
+
+    assert!(scheduler.take_due(HOUR - 1).is_empty());
+    let due: Vec<_> = scheduler.take_due(HOUR).into_iter()
+        .map(|(plan, scheduled)| (plan.id, scheduled))
+        .collect();
+    assert_eq!(due, vec![("hourly".to_owned(), HOUR)]);
+    assert_eq!(scheduler.next_run(), Some(2 * HOUR));
+
+    // Purchases missed while stopped are coalesced.
+    let due: Vec<_> = scheduler.take_due(2 * DAY).into_iter()
+        .map(|(plan, scheduled)| (plan.id, scheduled))
+        .collect();
+    assert_eq!(due, vec![("daily".to_owned(), 14 * HOUR), ("hourly".to_owned(), 2 * HOUR)]);
+    assert_eq!(scheduler.next_run(), Some(2 * DAY + HOUR));
+
+    // Replacing a plan reschedules it.
+    scheduler.add_plan(plan("hourly", Recurrence::new(DAY, 0)), 2 * DAY);
+    assert_eq!(scheduler.plans().count(), 2);
+    assert_eq!(scheduler.next_run(), Some(2 * DAY + 14 * HOUR));
+
+    assert!(scheduler.remove_plan("hourly").is_some());
+    assert!(scheduler.remove_plan("hourly").is_none());
+}
+
+#[test]
+fn balance() {
+    let plan = plan("daily", Recurrence::new(DAY, 0));
+    assert_eq!(check_balance(&plan, &balances("100.5")), None);
+    assert_eq!(
+        check_balance(&plan, &balances("99.99")),
+        Some(Outcome::InsufficientBalance { free: "99.99".to_owned() })
+    );
+    assert_eq!(
+        check_balance(&plan, &Balances::new()),
+        Some(Outcome::InsufficientBalance { free: "0".to_owned() })
+    );
+}
+
+#[test]
+fn persistence() {
+    let mut scheduler = DcaScheduler::new();
+    scheduler.add_plan(plan("daily", Recurrence::new(DAY, 14 * HOUR)), 0);
+    scheduler.take_due(DAY);
+    scheduler.on_report(ExecutionReport {
+        plan_id: "daily".to_owned(),
+        scheduled: 14 * HOUR,
+        executed: DAY,
+        outcome: Outcome::Submitted { order_id: "a".to_owned() },
+    });
+
+    let path = std::env::temp_dir().join(format!("trade-dca-{}.json", std::process::id()));
+    scheduler.state().save(&path).unwrap();
+    let restored = DcaScheduler::from_state(DcaState::load(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(restored, scheduler);
+    assert_eq!(restored.next_run(), Some(DAY + 14 * HOUR));
+    assert_eq!(restored.reports().len(), 1);
+}
@@ -0,0 +1,10 @@
+//! A module defining client-side emulations of order types which are not natively
+//! supported by every exchange, along with helpers executing operations made of
+//! several orders.
+
+pub mod basket;
+pub mod dca;
+pub mod oco;
+pub mod roller;
+pub mod rules;
+pub mod trailing_stop;
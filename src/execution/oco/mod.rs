@@ -0,0 +1,115 @@
+//! A module defining an emulation of OCO (one-cancels-the-other) orders.
+//!
+//! The emulator only keeps track of which orders are linked together: it is fed with
+//! the notifications of the symbol stream, and returns the cancel orders which must
+//! be sent to the exchange. Cancels should be sent as soon as they are returned,
+//! since the sibling order remains live until then.
+//!
+//! Exchanges for which `ApiClient::supports_oco` returns `true` handle OCO orders
+//! natively through `ApiClient::oco`, without such a delay.
+
+mod test;
+
+use std::collections::{HashMap, HashSet};
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{Notification, Cancel};
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+/// Links pairs of orders so that a fill or an expiration on one of them cancels the
+/// other one.
+///
+/// The emulator can be serialized, so that the links survive a restart. After a
+/// restart, call `reconcile` with the orders which are still open on the exchange.
+pub struct OcoEmulator {
+    /// order id => sibling order id
+    siblings: HashMap<String, String>,
+}
+
+impl OcoEmulator {
+    /// Return a new `OcoEmulator` without any linked orders.
+    pub fn new() -> Self {
+        OcoEmulator {
+            siblings: HashMap::new(),
+        }
+    }
+
+    /// Link the orders identified by `first` and `second`: as soon as one of them is
+    /// (even partially) filled or expires, the other one gets canceled.
+    ///
+    /// # Panics
+    /// Panic if one of the orders is already linked.
+    pub fn link(&mut self, first: String, second: String) {
+        if self.siblings.contains_key(&first) || self.siblings.contains_key(&second) {
+            panic!("order is already linked");
+        }
+
+        self.siblings.insert(first.clone(), second.clone());
+        self.siblings.insert(second, first);
+    }
+
+    /// Remove the link between `order_id` and its sibling, return the sibling order id.
+    pub fn unlink(&mut self, order_id: &str) -> Option<String> {
+        let sibling = self.siblings.remove(order_id)?;
+        self.siblings.remove(&sibling);
+        Some(sibling)
+    }
+
+    /// Return the order id linked with `order_id`, if any.
+    pub fn sibling(&self, order_id: &str) -> Option<&str> {
+        self.siblings.get(order_id).map(|sibling| sibling.as_ref())
+    }
+
+    /// Return the number of linked pairs.
+    pub fn len(&self) -> usize {
+        self.siblings.len() / 2
+    }
+
+    /// Return `true` if there are no linked orders.
+    pub fn is_empty(&self) -> bool {
+        self.siblings.is_empty()
+    }
+
+    /// Process a notification from the stream of the symbol the linked orders belong to.
+    /// If an order is filled or expires, return the cancel order to send for its sibling,
+    /// and unlink both orders.
+    pub fn on_notification(&mut self, notification: &Notification) -> Option<Cancel> {
+        let order_id = match notification {
+            Notification::OrderUpdate(update) => &update.order_id,
+            Notification::OrderExpiration(expiration) => &expiration.order_id,
+            _ => return None,
+        };
+
+        self.unlink(order_id).map(Cancel::new)
+    }
+
+    /// Reconcile the links with the orders which are still open on the exchange, e.g.
+    /// after a restart during which notifications may have been missed. For each pair
+    /// where only one order is still open, return a cancel order for it. Pairs where at
+    /// least one order is not open anymore are unlinked.
+    ///
+    /// # Note
+    /// A pair where both orders are still open is kept as is, even if one of them was
+    /// partially filled in the meantime.
+    pub fn reconcile<'a, I>(&mut self, open_orders: I) -> Vec<Cancel>
+        where I: IntoIterator<Item = &'a str>
+    {
+        let open_orders: HashSet<_> = open_orders.into_iter().collect();
+        let is_open = |order_id: &str| open_orders.contains(order_id);
+
+        let mut closed: Vec<_> = self.siblings.keys()
+            .filter(|order_id| !is_open(order_id))
+            .cloned()
+            .collect();
+        closed.sort();
+
+        let mut cancels = Vec::new();
+        for order_id in closed {
+            if let Some(sibling) = self.unlink(&order_id) {
+                if is_open(&sibling) {
+                    cancels.push(Cancel::new(sibling));
+                }
+            }
+        }
+        cancels
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(test)]
+
+use crate::api::{Notification, OrderUpdate, OrderExpiration};
+use crate::api::timestamp::IntoTimestamped;
+use crate::execution::oco::OcoEmulator;
+
+fn update(order_id: &str) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: 1,
+        remaining_size: 1,
+        consumed_price: 100,
+        commission: 0,
+    }.timestamped())
+}
+
+fn expiration(order_id: &str) -> Notification {
+    Notification::OrderExpiration(OrderExpiration {
+        order_id: order_id.to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.timestamped())
+}
+
+#[test]
+fn fill_cancels_sibling() {
+    let mut oco = OcoEmulator::new();
+    oco.link("take_profit".to_owned(), "stop".to_owned());
+    assert_eq!(oco.sibling("stop"), Some("take_profit"));
+
+    assert!(oco.on_notification(&update("other")).is_none());
+
+    let cancel = oco.on_notification(&update("take_profit")).unwrap();
+    assert_eq!(cancel.order_id(), "stop");
+    assert!(oco.is_empty());
+
+    // The expiration caused by the cancel must not trigger anything.
+    assert!(oco.on_notification(&expiration("stop")).is_none());
+}
+
+#[test]
+fn expiration_cancels_sibling() {
+    let mut oco = OcoEmulator::new();
+    oco.link("a".to_owned(), "b".to_owned());
+    oco.link("c".to_owned(), "d".to_owned());
+
+    let cancel = oco.on_notification(&expiration("b")).unwrap();
+    assert_eq!(cancel.order_id(), "a");
+    assert_eq!(oco.len(), 1);
+}
+
+#[test]
+fn reconcile() {
+    let mut oco = OcoEmulator::new();
+    oco.link("a".to_owned(), "b".to_owned());
+    oco.link("c".to_owned(), "d".to_owned());
+    oco.link("e".to_owned(), "f".to_owned());
+
+    let cancels = oco.reconcile(vec!["a", "c", "d"]);
+    let cancels: Vec<_> = cancels.iter().map(|cancel| cancel.order_id()).collect();
+    assert_eq!(cancels, vec!["a"]);
+
+    assert_eq!(oco.len(), 1);
+    assert_eq!(oco.sibling("c"), Some("d"));
+}
+
+#[test]
+#[should_panic]
+fn already_linked() {
+    let mut oco = OcoEmulator::new();
+    oco.link("a".to_owned(), "b".to_owned());
+    oco.link("b".to_owned(), "c".to_owned());
+}
@@ -0,0 +1,359 @@
+//! A module defining a helper for rolling a position on a dated future into the next
+//! contract before the former expires.
+//!
+//! The position is closed on the front contract and opened on the next one slice by
+//! slice, each slice being made of two immediate-or-cancel orders at the best prices of
+//! both books. A slice is only sent while the cost of rolling, i.e. the calendar spread
+//! paid when crossing both books, is acceptable, unless the expiry is close.
+
+mod test;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::{ApiClient, Notification, Order, TimeInForce};
+use crate::api::calendar::{RollSuggestion, DAY, HOUR};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::Timestamp;
+use crate::order_book::OrderBook;
+use crate::tick::TickUnit;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// State of a roll.
+pub enum RollState {
+    /// The trigger window has not been reached yet.
+    Waiting,
+
+    /// The position is being rolled.
+    Rolling,
+
+    /// The position has been entirely rolled.
+    Complete,
+
+    /// The front contract expired before the position was entirely rolled.
+    Expired,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Progress of a roll, sizes being in tick units.
+pub struct RollProgress {
+    /// State of the roll.
+    pub state: RollState,
+
+    /// Size of the position to roll.
+    pub size: TickUnit,
+
+    /// Size closed on the front contract.
+    pub closed: TickUnit,
+
+    /// Size opened on the next contract.
+    pub opened: TickUnit,
+}
+
+impl RollProgress {
+    /// Fraction of the position which has been rolled, between `0` and `1`: a size
+    /// only counts as rolled once both closed and opened.
+    pub fn fraction(&self) -> f64 {
+        if self.size == 0 {
+            return 1.;
+        }
+        std::cmp::min(self.closed, self.opened) as f64 / self.size as f64
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// Orders to be sent for a slice of the roll. Either one may be missing when the
+/// previous slices left the legs unbalanced.
+pub struct RollSlice {
+    /// Order closing the position, to be sent on the front contract.
+    pub close: Option<Order>,
+
+    /// Order opening the position, to be sent on the next contract.
+    pub open: Option<Order>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Leg {
+    order_id: String,
+    size: TickUnit,
+    filled: TickUnit,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// Migrates a position from a front contract to the next one ahead of the expiry of
+/// the front contract.
+///
+/// The roller must be fed with the order books of both contracts through `on_books`,
+/// which returns the orders to send, and with the notifications of both streams
+/// through `on_notification`, for tracking the fills. A new slice is only returned
+/// once both orders of the previous one are done.
+pub struct Roller {
+    front: Symbol,
+    next: Symbol,
+    side: Side,
+    size: TickUnit,
+    expiry: Timestamp,
+    window: Timestamp,
+    deadline: Timestamp,
+    max_spread: Option<i64>,
+    slice_size: Option<TickUnit>,
+    closed: TickUnit,
+    opened: TickUnit,
+    slices: usize,
+    close_leg: Option<Leg>,
+    open_leg: Option<Leg>,
+}
+
+impl Roller {
+    /// Return a new `Roller`, with:
+    /// * `front` and `next` being the contract to roll from and the contract to roll into
+    /// * `side` being the side of the position, i.e. `Side::Bid` for a long position
+    /// * `size` being the size of the position, in tick units
+    /// * `expiry` being the expiry time of the front contract
+    ///
+    /// The roll starts one day before the expiry, without any spread limit.
+    ///
+    /// # Panics
+    /// Panic if the contracts do not share the same ticks, since their prices are
+    /// compared.
+    pub fn new(front: Symbol, next: Symbol, side: Side, size: TickUnit, expiry: Timestamp)
+        -> Self
+    {
+        if front.price_tick() != next.price_tick() || front.size_tick() != next.size_tick() {
+            panic!("contracts must share the same ticks");
+        }
+
+        Roller {
+            front,
+            next,
+            side,
+            size,
+            expiry,
+            window: DAY,
+            deadline: HOUR,
+            max_spread: None,
+            slice_size: None,
+            closed: 0,
+            opened: 0,
+            slices: 0,
+            close_leg: None,
+            open_leg: None,
+        }
+    }
+
+    /// Return a new `Roller` following `suggestion`, see `ExpirySchedule::roll_suggestion`.
+    /// `front` must be the contract expiring at `suggestion.from` and `next` the
+    /// contract expiring at `suggestion.to`.
+    pub fn from_suggestion(
+        front: Symbol,
+        next: Symbol,
+        side: Side,
+        size: TickUnit,
+        suggestion: &RollSuggestion
+    ) -> Self
+    {
+        Roller::new(front, next, side, size, suggestion.from)
+            .with_window(suggestion.from - suggestion.roll_at)
+    }
+
+    /// Start rolling `window` milliseconds before the expiry. Default to one day.
+    pub fn with_window(mut self, window: Timestamp) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Only send a slice if rolling costs at most `max_spread` price ticks per unit,
+    /// see `Roller::spread`.
+    pub fn with_max_spread(mut self, max_spread: i64) -> Self {
+        self.max_spread = Some(max_spread);
+        self
+    }
+
+    /// Ignore the spread limit during the last `deadline` milliseconds before the
+    /// expiry, so that the position is rolled whatever the cost. Default to one hour.
+    pub fn with_deadline(mut self, deadline: Timestamp) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Roll at most `slice_size` (in tick units) at a time. By default, the whole
+    /// remaining size is sent at once.
+    ///
+    /// # Panics
+    /// Panic if `slice_size` is zero.
+    pub fn with_slice_size(mut self, slice_size: TickUnit) -> Self {
+        if slice_size == 0 {
+            panic!("`slice_size` must be positive");
+        }
+        self.slice_size = Some(slice_size);
+        self
+    }
+
+    /// Contract to roll from.
+    pub fn front(&self) -> Symbol {
+        self.front
+    }
+
+    /// Contract to roll into.
+    pub fn next(&self) -> Symbol {
+        self.next
+    }
+
+    /// Time from which the position is rolled.
+    pub fn roll_at(&self) -> Timestamp {
+        self.expiry.saturating_sub(self.window)
+    }
+
+    /// Cost of rolling one unit of the position at the best prices of `front_book` and
+    /// `next_book`, in price ticks: the price paid minus the price received. A negative
+    /// cost is a gain. Return `None` if a side needed is empty.
+    pub fn spread(&self, front_book: &OrderBook, next_book: &OrderBook) -> Option<i64> {
+        let (close_price, open_price) = self.prices(front_book, next_book)?;
+        let cost = match self.side {
+            // Sell the front contract, buy the next one.
+            Side::Bid => open_price as i128 - close_price as i128,
+
+            // Buy the front contract, sell the next one.
+            Side::Ask => close_price as i128 - open_price as i128,
+        };
+        Some(cost as i64)
+    }
+
+    /// Return the progress of the roll at time `now`.
+    pub fn progress(&self, now: Timestamp) -> RollProgress {
+        let state = if self.closed == self.size && self.opened == self.size {
+            RollState::Complete
+        } else if now >= self.expiry {
+            RollState::Expired
+        } else if now >= self.roll_at() {
+            RollState::Rolling
+        } else {
+            RollState::Waiting
+        };
+
+        RollProgress {
+            state,
+            size: self.size,
+            closed: self.closed,
+            opened: self.opened,
+        }
+    }
+
+    /// Process the order books of both contracts at time `now`. Return the orders of
+    /// the next slice if it should be sent right away. Order ids are generated with
+    /// `C::new_order_id`, from hints made of the front contract name, the leg and the
+    /// index of the slice, e.g. `BTCUSD_240329-close-0`.
+    pub fn on_books<C: ApiClient>(
+        &mut self,
+        now: Timestamp,
+        front_book: &OrderBook,
+        next_book: &OrderBook
+    ) -> Option<RollSlice>
+    {
+        if self.progress(now).state != RollState::Rolling {
+            return None;
+        }
+
+        if self.close_leg.is_some() || self.open_leg.is_some() {
+            return None;
+        }
+
+        let (close_price, open_price) = self.prices(front_book, next_book)?;
+        let forced = now >= self.expiry.saturating_sub(self.deadline);
+        match self.max_spread {
+            Some(max_spread) if !forced && self.spread(front_book, next_book)? > max_spread => {
+                return None;
+            }
+            _ => (),
+        }
+
+        // The open leg catches up with the close leg if they got unbalanced.
+        let slice_size = self.slice_size.unwrap_or(self.size);
+        let close_size = std::cmp::min(slice_size, self.size - self.closed);
+        let open_size = (self.closed + close_size).saturating_sub(self.opened);
+
+        let close_side = match self.side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let close = if close_size > 0 {
+            let order = Order::new(close_price, close_size, close_side)
+                .with_time_in_force(TimeInForce::ImmediateOrCancel)
+                .with_reduce_only()
+                .with_order_id::<C>(&format!("{}-close-{}", self.front.name(), self.slices));
+            self.close_leg = Some(Leg::new(&order, close_size));
+            Some(order)
+        } else {
+            None
+        };
+
+        let open = if open_size > 0 {
+            let order = Order::new(open_price, open_size, self.side)
+                .with_time_in_force(TimeInForce::ImmediateOrCancel)
+                .with_order_id::<C>(&format!("{}-open-{}", self.front.name(), self.slices));
+            self.open_leg = Some(Leg::new(&order, open_size));
+            Some(order)
+        } else {
+            None
+        };
+
+        self.slices += 1;
+        Some(RollSlice {
+            close,
+            open,
+        })
+    }
+
+    /// Process a notification from the stream of either contract.
+    pub fn on_notification(&mut self, notification: &Notification) {
+        Leg::update(&mut self.close_leg, &mut self.closed, notification);
+        Leg::update(&mut self.open_leg, &mut self.opened, notification);
+    }
+
+    /// Prices of the close order and of the open order.
+    fn prices(&self, front_book: &OrderBook, next_book: &OrderBook)
+        -> Option<(TickUnit, TickUnit)>
+    {
+        let (close_price, open_price) = match self.side {
+            Side::Bid => (front_book.best_bid(), next_book.best_ask()),
+            Side::Ask => (front_book.best_ask(), next_book.best_bid()),
+        };
+
+        if close_price == 0 || close_price == TickUnit::max_value()
+            || open_price == 0 || open_price == TickUnit::max_value()
+        {
+            return None;
+        }
+        Some((close_price, open_price))
+    }
+}
+
+impl Leg {
+    fn new(order: &Order, size: TickUnit) -> Self {
+        Leg {
+            order_id: order.order_id().unwrap().to_owned(),
+            size,
+            filled: 0,
+        }
+    }
+
+    /// Add the new fills of `leg` to `total`, and clear `leg` once the order is done.
+    fn update(leg: &mut Option<Leg>, total: &mut TickUnit, notification: &Notification) {
+        let done = match (leg.as_mut(), notification) {
+            (Some(leg), Notification::OrderUpdate(update)) if update.order_id == leg.order_id => {
+                let filled = leg.size.saturating_sub(update.remaining_size);
+                *total += filled.saturating_sub(leg.filled);
+                leg.filled = std::cmp::max(filled, leg.filled);
+                leg.filled == leg.size
+            }
+            (Some(leg), Notification::OrderExpiration(expiration)) => {
+                expiration.order_id == leg.order_id
+            }
+            _ => false,
+        };
+
+        if done {
+            *leg = None;
+        }
+    }
+}
@@ -0,0 +1,128 @@
+#![cfg(all(test, feature = "binance"))]
+
+use crate::Side;
+use crate::api::binance;
+use crate::api::{Notification, OrderUpdate, OrderExpiration};
+use crate::api::calendar::{Cycle, ExpirySchedule, DAY, HOUR};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::execution::roller::{Roller, RollState};
+use crate::order_book::{OrderBook, LimitUpdate};
+use crate::tick::{Tick, Tickable, TickUnit};
+
+const EXPIRY: u64 = 10 * DAY;
+
+fn symbol(name: &str) -> Symbol {
+    Symbol::new(name, Tick::new(10), Tick::new(1000)).unwrap()
+}
+
+fn book(bid: TickUnit, ask: TickUnit) -> OrderBook {
+    let mut book = OrderBook::new();
+    book.update(LimitUpdate::new(bid, 10_000, Side::Bid));
+    book.update(LimitUpdate::new(ask, 10_000, Side::Ask));
+    book
+}
+
+fn update(order_id: &str, remaining_size: u64) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: 0,
+        remaining_size,
+        consumed_price: 100,
+        commission: 0,
+    }.timestamped())
+}
+
+fn expiration(order_id: &str) -> Notification {
+    Notification::OrderExpiration(OrderExpiration {
+        order_id: order_id.to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.timestamped())
+}
+
+fn roller() -> Roller {
+    Roller::new(symbol("BTCUSD_240329"), symbol("BTCUSD_240628"), Side::Bid, 3000, EXPIRY)
+        .with_window(2 * DAY)
+        .with_max_spread(50)
+        .with_slice_size(2000)
+}
+
+#[test]
+fn trigger_window() {
+    let mut roller = roller();
+    let (front, next) = (book(1000, 1010), book(1030, 1040));
+
+    assert_eq!(roller.roll_at(), 8 * DAY);
+    assert_eq!(roller.progress(8 * DAY - 1).state, RollState::Waiting);
+    assert!(roller.on_books::<binance::Client>(8 * DAY - 1, &front, &next).is_none());
+    assert_eq!(roller.progress(8 * DAY).state, RollState::Rolling);
+    assert_eq!(roller.progress(EXPIRY).state, RollState::Expired);
+
+    let schedule = ExpirySchedule::new(8, vec![Cycle::Quarterly]);
+    let suggestion = schedule.roll_suggestion(Cycle::Quarterly, 0, 3 * DAY);
+    let roller = Roller::from_suggestion(
+        symbol("BTCUSD_240329"),
+        symbol("BTCUSD_240628"),
+        Side::Bid,
+        3000,
+        &suggestion
+    );
+    assert_eq!(roller.roll_at(), suggestion.roll_at);
+}
+
+#[test]
+fn spread() {
+    let mut roller = roller();
+
+    // Sell the front contract at 1000, buy the next one at 1060.
+    let (front, next) = (book(1000, 1010), book(1050, 1060));
+    assert_eq!(roller.spread(&front, &next), Some(60));
+    assert!(roller.on_books::<binance::Client>(8 * DAY, &front, &next).is_none());
+
+    // The spread limit is ignored close to the expiry.
+    let slice = roller.on_books::<binance::Client>(EXPIRY - HOUR, &front, &next).unwrap();
+    assert!(slice.close.is_some() && slice.open.is_some());
+
+    let short = Roller::new(symbol("A"), symbol("B"), Side::Ask, 1000, EXPIRY);
+    assert_eq!(short.spread(&front, &next), Some(-40));
+    assert_eq!(short.spread(&OrderBook::new(), &next), None);
+}
+
+#[test]
+fn slices() {
+    let mut roller = roller();
+    let (front, next) = (book(1000, 1010), book(1030, 1040));
+
+    let slice = roller.on_books::<binance::Client>(8 * DAY, &front, &next).unwrap();
+    let close = slice.close.unwrap();
+    let open = slice.open.unwrap();
+    assert_eq!(close.price(), &Tickable::Ticked(1000));
+    assert_eq!(close.size(), &Tickable::Ticked(2000));
+    assert!(close.reduce_only());
+    assert_eq!(open.price(), &Tickable::Ticked(1040));
+
+    // Nothing is sent while the previous slice is live.
+    assert!(roller.on_books::<binance::Client>(8 * DAY, &front, &next).is_none());
+
+    // The open order is only partially filled.
+    roller.on_notification(&update(close.order_id().unwrap(), 0));
+    roller.on_notification(&update(open.order_id().unwrap(), 500));
+    roller.on_notification(&expiration(open.order_id().unwrap()));
+
+    let progress = roller.progress(8 * DAY);
+    assert_eq!((progress.closed, progress.opened), (2000, 1500));
+    assert_eq!(progress.fraction(), 0.5);
+
+    // The open leg catches up.
+    let slice = roller.on_books::<binance::Client>(8 * DAY, &front, &next).unwrap();
+    let close = slice.close.unwrap();
+    let open = slice.open.unwrap();
+    assert_eq!(close.size(), &Tickable::Ticked(1000));
+    assert_eq!(open.size(), &Tickable::Ticked(1500));
+
+    roller.on_notification(&update(close.order_id().unwrap(), 0));
+    roller.on_notification(&update(open.order_id().unwrap(), 0));
+    assert_eq!(roller.progress(EXPIRY).state, RollState::Complete);
+    assert!(roller.on_books::<binance::Client>(8 * DAY, &front, &next).is_none());
+}
@@ -0,0 +1,157 @@
+//! A module defining a small rules engine binding conditions over market data to
+//! actions, e.g. "buy 1 BTC if the price goes above 7000".
+//!
+//! The engine does not perform the actions itself: it is fed with notifications and
+//! returns the actions whose condition has been met, which the caller then executes.
+
+mod test;
+
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{Notification, Order};
+use crate::api::timestamp::Timestamp;
+use crate::tick::TickUnit;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A condition over the trades of a symbol.
+pub enum Condition {
+    /// Met by the first trade at or above the given price.
+    PriceAbove(TickUnit),
+
+    /// Met by the first trade at or below the given price.
+    PriceBelow(TickUnit),
+
+    /// Met once the given time, in ms, has been reached.
+    TimeAfter(Timestamp),
+
+    /// Met once the given size has been traded since the rule was added.
+    VolumeAbove(TickUnit),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An action to perform when a condition is met.
+pub enum Action {
+    /// Send an order.
+    Submit(Order),
+
+    /// Cancel the orders with the given ids.
+    Cancel(Vec<String>),
+
+    /// Notify the user with the given message.
+    Alert(String),
+}
+
+/// Identifier of a rule inside a `RuleSet`.
+pub type RuleId = u64;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+struct Rule {
+    id: RuleId,
+    condition: Condition,
+    actions: Vec<Action>,
+    volume: TickUnit,
+}
+
+impl Rule {
+    fn on_trade(&mut self, price: TickUnit, size: TickUnit, timestamp: Timestamp) -> bool {
+        self.volume += size;
+        match self.condition {
+            Condition::PriceAbove(threshold) => price >= threshold,
+            Condition::PriceBelow(threshold) => price <= threshold,
+            Condition::TimeAfter(time) => timestamp >= time,
+            Condition::VolumeAbove(volume) => self.volume >= volume,
+        }
+    }
+
+    fn on_time(&self, now: Timestamp) -> bool {
+        match self.condition {
+            Condition::TimeAfter(time) => now >= time,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// A set of rules over the notifications of one symbol. Each rule fires at most once:
+/// it is removed as soon as its condition is met.
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    next_id: RuleId,
+}
+
+impl RuleSet {
+    /// Return an empty `RuleSet`.
+    pub fn new() -> Self {
+        RuleSet {
+            rules: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Add a rule performing `actions` once `condition` is met, return its id.
+    pub fn add(&mut self, condition: Condition, actions: Vec<Action>) -> RuleId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.rules.push(Rule {
+            id,
+            condition,
+            actions,
+            volume: 0,
+        });
+        id
+    }
+
+    /// Remove the rule identified by `id`, return `false` if there was no such rule.
+    pub fn remove(&mut self, id: RuleId) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|rule| rule.id != id);
+        self.rules.len() != len
+    }
+
+    /// Return the number of rules which have not fired yet.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Return `true` if all the rules have fired.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Remove the rules for which `fired` returns `true`, and return their actions in
+    /// the order in which the rules were added.
+    fn fire<F>(&mut self, mut fired: F) -> Vec<(RuleId, Action)>
+        where F: FnMut(&mut Rule) -> bool
+    {
+        let mut actions = Vec::new();
+        let mut remaining = Vec::with_capacity(self.rules.len());
+        for mut rule in self.rules.drain(..) {
+            if fired(&mut rule) {
+                let id = rule.id;
+                actions.extend(rule.actions.into_iter().map(|action| (id, action)));
+            } else {
+                remaining.push(rule);
+            }
+        }
+        self.rules = remaining;
+        actions
+    }
+
+    /// Evaluate the rules against a notification of the symbol stream. Return the
+    /// actions of the rules which fired, along with the rule ids.
+    pub fn on_notification(&mut self, notification: &Notification) -> Vec<(RuleId, Action)> {
+        match notification {
+            Notification::Trade(trade) => {
+                let timestamp = trade.timestamp();
+                self.fire(|rule| rule.on_trade(trade.price, trade.size, timestamp))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Evaluate the time conditions against the current time `now`, in ms. This should
+    /// be called periodically, since time conditions are otherwise only evaluated
+    /// when a trade happens.
+    pub fn on_time(&mut self, now: Timestamp) -> Vec<(RuleId, Action)> {
+        self.fire(|rule| rule.on_time(now))
+    }
+}
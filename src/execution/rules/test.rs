@@ -0,0 +1,67 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade, Order};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::execution::rules::{RuleSet, Condition, Action};
+use crate::tick::TickUnit;
+
+fn trade(timestamp: Timestamp, price: TickUnit, size: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+#[test]
+fn price_conditions() {
+    let mut rules = RuleSet::new();
+    let above = rules.add(
+        Condition::PriceAbove(110),
+        vec![Action::Submit(Order::new(110, 1, Side::Bid))]
+    );
+    let below = rules.add(
+        Condition::PriceBelow(90),
+        vec![Action::Cancel(vec!["a".to_owned()]), Action::Alert("below".to_owned())]
+    );
+
+    assert!(rules.on_notification(&trade(0, 100, 1)).is_empty());
+    assert_eq!(
+        rules.on_notification(&trade(1, 111, 1)),
+        vec![(above, Action::Submit(Order::new(110, 1, Side::Bid)))]
+    );
+    assert!(rules.on_notification(&trade(2, 112, 1)).is_empty());
+    assert_eq!(
+        rules.on_notification(&trade(3, 90, 1)),
+        vec![
+            (below, Action::Cancel(vec!["a".to_owned()])),
+            (below, Action::Alert("below".to_owned())),
+        ]
+    );
+    assert!(rules.is_empty());
+}
+
+#[test]
+fn time_and_volume_conditions() {
+    let mut rules = RuleSet::new();
+    let time = rules.add(Condition::TimeAfter(1000), vec![Action::Alert("time".to_owned())]);
+    let volume = rules.add(Condition::VolumeAbove(5), vec![Action::Alert("volume".to_owned())]);
+
+    assert!(rules.on_notification(&trade(10, 100, 3)).is_empty());
+    assert!(rules.on_time(999).is_empty());
+    assert_eq!(rules.on_time(1000), vec![(time, Action::Alert("time".to_owned()))]);
+    assert_eq!(
+        rules.on_notification(&trade(1100, 100, 2)),
+        vec![(volume, Action::Alert("volume".to_owned()))]
+    );
+}
+
+#[test]
+fn remove() {
+    let mut rules = RuleSet::new();
+    let id = rules.add(Condition::PriceAbove(110), vec![Action::Alert("above".to_owned())]);
+    assert!(rules.remove(id));
+    assert!(!rules.remove(id));
+    assert!(rules.on_notification(&trade(0, 120, 1)).is_empty());
+}
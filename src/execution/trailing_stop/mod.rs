@@ -0,0 +1,217 @@
+//! A module defining an emulation of trailing stop orders.
+//!
+//! A trailing stop follows the market price at a fixed distance: a sell stop tracks the
+//! highest bid price since activation, and triggers when the bid falls below that
+//! extreme price minus the trail. A buy stop mirrors this with the lowest ask price.
+//!
+//! The stop can also follow the price of the trades, and be left to `TrailingStop::watch`
+//! which sends the order by itself once the trail is hit.
+
+mod test;
+
+use std::sync::Arc;
+use futures::prelude::*;
+use futures::future::{self, Either};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::{ApiClient, Order, OrderAck, Notification, Trade};
+use crate::api::errors::OrderError;
+use crate::api::symbol::{Symbol, IntoWithSymbol};
+use crate::api::timestamp::Timestamped;
+use crate::order_book::OrderBook;
+use crate::tick::TickUnit;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Distance between the extreme price and the stop price.
+pub enum Trail {
+    /// Fixed distance, in price tick units.
+    Ticks(TickUnit),
+
+    /// Distance proportional to the extreme price, in basis points (1 bp = 0.01%).
+    BasisPoints(TickUnit),
+}
+
+impl Trail {
+    fn distance(self, extreme: TickUnit) -> TickUnit {
+        match self {
+            Trail::Ticks(ticks) => ticks,
+            Trail::BasisPoints(bps) => extreme * bps / 10_000,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A client-side trailing stop, for exchanges which do not support them natively.
+///
+/// The stop must be fed with market prices, e.g. through `on_order_book` each time the
+/// book of a `LiveOrderBook` changes, or through `on_trade`. When the trail is breached,
+/// it returns a limit order, or a market order if chosen, which should be sent to the
+/// exchange right away; the stop is then disarmed.
+pub struct TrailingStop {
+    side: Side,
+    size: TickUnit,
+    trail: Trail,
+    activation_price: Option<TickUnit>,
+    limit_offset: TickUnit,
+    market: bool,
+    extreme: Option<TickUnit>,
+    triggered: bool,
+}
+
+impl TrailingStop {
+    /// Return a new `TrailingStop`, with:
+    /// * `side` being the side of the order sent when the stop triggers, i.e. `Side::Ask`
+    ///   for protecting a long position
+    /// * `size` being the size of this order, in tick units
+    /// * `trail` being the distance between the extreme price and the stop price
+    ///
+    /// The stop is active right away, unless an activation price is set.
+    pub fn new(side: Side, size: TickUnit, trail: Trail) -> Self {
+        TrailingStop {
+            side,
+            size,
+            trail,
+            activation_price: None,
+            limit_offset: 0,
+            market: false,
+            extreme: None,
+            triggered: false,
+        }
+    }
+
+    /// Only start tracking the price once it reaches `price`: at or above for a sell
+    /// stop, at or below for a buy stop.
+    pub fn with_activation_price(mut self, price: TickUnit) -> Self {
+        self.activation_price = Some(price);
+        self
+    }
+
+    /// When triggering, send the order `offset` ticks through the price which breached
+    /// the trail, so that it is more likely to be filled. Default to `0`.
+    pub fn with_limit_offset(mut self, offset: TickUnit) -> Self {
+        self.limit_offset = offset;
+        self
+    }
+
+    /// When triggering, send a market order instead of a limit order. The limit offset
+    /// is then ignored.
+    pub fn with_market_order(mut self) -> Self {
+        self.market = true;
+        self
+    }
+
+    /// Return the most favorable price seen since activation, or `None` if the stop
+    /// is not active yet.
+    pub fn extreme_price(&self) -> Option<TickUnit> {
+        self.extreme
+    }
+
+    /// Return the price at which the stop triggers, or `None` if the stop is not
+    /// active yet.
+    pub fn stop_price(&self) -> Option<TickUnit> {
+        let extreme = self.extreme?;
+        let distance = self.trail.distance(extreme);
+        match self.side {
+            Side::Ask => Some(extreme.saturating_sub(distance)),
+            Side::Bid => Some(extreme.saturating_add(distance)),
+        }
+    }
+
+    /// Return an order implementing this stop natively on exchanges which support
+    /// trailing stops (see `Order::with_trailing_delta`), with `price` as limit price,
+    /// ignored for a market order. Return `None` if the trail is not expressed in basis
+    /// points or if an activation price is set, in which case the stop must be emulated.
+    pub fn native_order(&self, price: TickUnit) -> Option<Order> {
+        match (self.trail, self.activation_price) {
+            (Trail::BasisPoints(bps), None) if self.market => {
+                Some(Order::market(self.size, self.side).with_trailing_delta(bps))
+            }
+            (Trail::BasisPoints(bps), None) => {
+                Some(Order::new(price, self.size, self.side).with_trailing_delta(bps))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return `true` if the stop has already triggered.
+    pub fn is_triggered(&self) -> bool {
+        self.triggered
+    }
+
+    /// Process a new market price: the best bid price for a sell stop, the best ask price
+    /// for a buy stop. Return the order to send if the stop triggers.
+    pub fn on_price(&mut self, price: TickUnit) -> Option<Order> {
+        if self.triggered {
+            return None;
+        }
+
+        let side = self.side;
+        let better = |a: TickUnit, b: TickUnit| match side {
+            Side::Ask => a >= b,
+            Side::Bid => a <= b,
+        };
+
+        let extreme = match (self.extreme, self.activation_price) {
+            (Some(extreme), _) => if better(price, extreme) { price } else { extreme },
+            (None, Some(activation_price)) if !better(price, activation_price) => return None,
+            (None, _) => price,
+        };
+        self.extreme = Some(extreme);
+
+        let stop_price = self.stop_price().unwrap();
+        if price == extreme || !better(stop_price, price) {
+            return None;
+        }
+
+        self.triggered = true;
+        if self.market {
+            return Some(Order::market(self.size, self.side));
+        }
+
+        let limit_price = match self.side {
+            Side::Ask => price.saturating_sub(self.limit_offset),
+            Side::Bid => price.saturating_add(self.limit_offset),
+        };
+        Some(Order::new(limit_price, self.size, self.side))
+    }
+
+    /// Same as `on_price`, with the relevant side of `order_book`. Do nothing if that
+    /// side is empty.
+    pub fn on_order_book(&mut self, order_book: &OrderBook) -> Option<Order> {
+        match self.side {
+            Side::Ask if order_book.best_bid() != 0 => self.on_price(order_book.best_bid()),
+            Side::Bid if order_book.best_ask() != TickUnit::max_value() => {
+                self.on_price(order_book.best_ask())
+            }
+            _ => None,
+        }
+    }
+
+    /// Same as `on_price`, with the price of `trade`.
+    pub fn on_trade(&mut self, trade: &Trade) -> Option<Order> {
+        self.on_price(trade.price)
+    }
+
+    /// Follow the trades of `notifications`, the stream of `symbol`, and send the order
+    /// through `client` once the stop triggers. Resolve to `None` if the stream ends
+    /// before that. The returned future must be run inside a tokio runtime.
+    pub fn watch<C, S>(self, client: Arc<C>, symbol: Symbol, notifications: S)
+        -> impl Future<Item = Option<Timestamped<OrderAck>>, Error = OrderError> + Send + 'static
+            where C: ApiClient + Send + Sync + 'static,
+                  S: Stream<Item = Notification, Error = ()> + Send + 'static
+    {
+        let mut stop = self;
+        notifications
+            .filter_map(move |notif| match notif {
+                Notification::Trade(trade) => stop.on_trade(&trade),
+                _ => None,
+            })
+            .into_future()
+            .then(move |res| match res {
+                Ok((Some(order), _)) => {
+                    Either::A(client.order(order.with_symbol(symbol)).map(Some))
+                }
+                _ => Either::B(future::ok(None)),
+            })
+    }
+}
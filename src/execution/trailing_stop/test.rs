@@ -0,0 +1,107 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Order, Trade};
+use crate::execution::trailing_stop::{TrailingStop, Trail};
+use crate::order_book::{OrderBook, LimitUpdate};
+
+#[test]
+fn sell_stop() {
+    let mut stop = TrailingStop::new(Side::Ask, 5, Trail::Ticks(10));
+
+    assert!(stop.on_price(100).is_none());
+    assert_eq!(stop.stop_price(), Some(90));
+
+    assert!(stop.on_price(120).is_none());
+    assert!(stop.on_price(111).is_none());
+    assert_eq!(stop.extreme_price(), Some(120));
+    assert_eq!(stop.stop_price(), Some(110));
+
+    assert_eq!(stop.on_price(109), Some(Order::new(109, 5, Side::Ask)));
+    assert!(stop.is_triggered());
+    assert!(stop.on_price(100).is_none());
+}
+
+#[test]
+fn buy_stop() {
+    let mut stop = TrailingStop::new(Side::Bid, 5, Trail::BasisPoints(100))
+        .with_limit_offset(2);
+
+    assert!(stop.on_price(1000).is_none());
+    assert!(stop.on_price(900).is_none());
+    assert_eq!(stop.stop_price(), Some(909));
+
+    assert!(stop.on_price(908).is_none());
+    assert_eq!(stop.on_price(910), Some(Order::new(912, 5, Side::Bid)));
+}
+
+#[test]
+fn activation_price() {
+    let mut stop = TrailingStop::new(Side::Ask, 5, Trail::Ticks(10))
+        .with_activation_price(150);
+
+    assert!(stop.on_price(100).is_none());
+    assert!(stop.on_price(80).is_none());
+    assert_eq!(stop.extreme_price(), None);
+
+    assert!(stop.on_price(150).is_none());
+    assert_eq!(stop.on_price(140), Some(Order::new(140, 5, Side::Ask)));
+}
+
+#[test]
+fn order_book() {
+    let mut stop = TrailingStop::new(Side::Ask, 5, Trail::Ticks(10));
+    let mut order_book = OrderBook::new();
+
+    order_book.update(LimitUpdate::new(100, 1, Side::Ask));
+    assert!(stop.on_order_book(&order_book).is_none());
+    assert_eq!(stop.extreme_price(), None);
+
+    order_book.update(LimitUpdate::new(95, 1, Side::Bid));
+    assert!(stop.on_order_book(&order_book).is_none());
+    assert_eq!(stop.extreme_price(), Some(95));
+
+    order_book.update(LimitUpdate::new(95, 0, Side::Bid));
+    order_book.update(LimitUpdate::new(80, 1, Side::Bid));
+    assert_eq!(stop.on_order_book(&order_book), Some(Order::new(80, 5, Side::Ask)));
+}
+
+#[test]
+fn native_order() {
+    let stop = TrailingStop::new(Side::Ask, 5, Trail::BasisPoints(50));
+    let order = stop.native_order(95).unwrap();
+    assert_eq!(order.trailing_delta(), Some(50));
+    assert_eq!(order, Order::new(95, 5, Side::Ask).with_trailing_delta(50));
+
+    assert!(TrailingStop::new(Side::Ask, 5, Trail::Ticks(10)).native_order(95).is_none());
+    assert!(
+        TrailingStop::new(Side::Ask, 5, Trail::BasisPoints(50))
+            .with_activation_price(150)
+            .native_order(95)
+            .is_none()
+    );
+}
+
+#[test]
+fn market_order_on_trades() {
+    let trade = |price| Trade {
+        price,
+        size: 1,
+        maker_side: Side::Bid,
+    };
+
+    let mut stop = TrailingStop::new(Side::Ask, 5, Trail::Ticks(10))
+        .with_market_order()
+        .with_limit_offset(2);
+
+    assert!(stop.on_trade(&trade(100)).is_none());
+    assert!(stop.on_trade(&trade(110)).is_none());
+    assert_eq!(stop.on_trade(&trade(99)), Some(Order::market(5, Side::Ask)));
+    assert!(stop.on_trade(&trade(90)).is_none());
+
+    let stop = TrailingStop::new(Side::Bid, 5, Trail::BasisPoints(50)).with_market_order();
+    assert_eq!(
+        stop.native_order(95),
+        Some(Order::market(5, Side::Bid).with_trailing_delta(50))
+    );
+}
@@ -1,6 +1,12 @@
 //! Utilities for trading on crypto-currencies exchanges. Long term goal is to
 //! provide a general enough, unified API for abstracting over various exchanges,
 //! hence making it easier to develop cross exchange automated trading strategies.
+//!
+//! Without the default `std` feature, the crate is `no_std` and only provides the
+//! `tick` and `order_book` modules, which then only depend on `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(feature = "std"), feature(alloc))]
 
 #![feature(nll)]
 #![feature(try_from)]
@@ -9,10 +15,33 @@
 #![feature(no_panic_pow)]
 #![warn(missing_docs)]
 
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+// Allow using `alloc::` paths in modules which must build both with and without `std`.
+#[cfg(feature = "std")]
+extern crate std as alloc;
+
+#[cfg(feature = "std")]
 pub mod api;
+#[cfg(feature = "coordination")]
+pub mod coordination;
+#[cfg(feature = "std")]
+pub mod data;
+#[cfg(feature = "std")]
+pub mod execution;
+#[cfg(feature = "std")]
+pub mod notify;
 pub mod order_book;
+#[cfg(feature = "std")]
+pub mod reports;
+#[cfg(feature = "std")]
+pub mod risk;
+#[cfg(feature = "std")]
+pub mod sim;
 pub mod tick;
 
+#[cfg(feature = "std")]
 pub mod prelude {
     //! A prelude for crates using this library. Re-exports the most used types
     //! and traits.
@@ -20,6 +49,7 @@ pub mod prelude {
     pub use crate::tick::TickUnit;
     pub use crate::api::{ApiClient, Notification, NotificationFlags};
     pub use crate::api::symbol::{Symbol, IntoWithSymbol};
+    #[cfg(feature = "network")]
     pub use crate::api::order_book::{LiveOrderBook, BookState};
     pub use crate::Side;
 }
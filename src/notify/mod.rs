@@ -0,0 +1,304 @@
+//! A module defining notifications sent to webhooks, so that an operator is told about
+//! the fills, expirations and risk alerts of a running strategy, e.g. on a phone through
+//! Slack or Telegram, without running a separate service.
+//!
+//! Events are collected into batches: a batch is sent once it holds
+//! `NotifyConfig::max_batch` events, or once its oldest event has waited for
+//! `NotifyConfig::batch_delay`. Each batch is posted to every webhook, and a failed post
+//! is retried with an exponential backoff. The sending side, `Notifier`, requires the
+//! `network` feature.
+
+mod test;
+#[cfg(feature = "network")]
+mod send;
+
+#[cfg(feature = "network")]
+pub use self::send::{Notifier, NotifyFuture};
+
+use std::fmt;
+use std::time::Duration;
+use serde_derive::{Serialize, Deserialize};
+use serde_json::json;
+use crate::api::Notification;
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::Timestamp;
+use crate::risk::BreakerState;
+use crate::risk::anomaly::Anomaly;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A webhook to which the batches of events are posted.
+pub enum Webhook {
+    /// A Slack incoming webhook, receiving the events as one text message.
+    Slack {
+        /// Url of the incoming webhook.
+        url: String,
+    },
+
+    /// A Telegram bot, sending the events as one text message to a chat.
+    Telegram {
+        /// Token of the bot.
+        bot_token: String,
+
+        /// Id of the chat to which the messages are sent.
+        chat_id: String,
+    },
+
+    /// Any HTTP endpoint, receiving the events as JSON: `{"events": [...]}`.
+    Http {
+        /// Url of the endpoint.
+        url: String,
+    },
+}
+
+impl Webhook {
+    /// Return the url to post `events` to, along with the JSON body of the request.
+    pub fn request(&self, events: &[Event]) -> (String, String) {
+        let text = || {
+            let lines: Vec<_> = events.iter().map(|event| event.to_string()).collect();
+            lines.join("\n")
+        };
+
+        match self {
+            Webhook::Slack { url } => (url.clone(), json!({ "text": text() }).to_string()),
+            Webhook::Telegram { bot_token, chat_id } => (
+                format!("https://api.telegram.org/bot{}/sendMessage", bot_token),
+                json!({ "chat_id": chat_id, "text": text() }).to_string(),
+            ),
+            Webhook::Http { url } => (url.clone(), json!({ "events": events }).to_string()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An event worth telling the operator about. Prices and sizes are formatted as
+/// decimal strings.
+pub enum Event {
+    /// An order was filled, partially or fully.
+    Fill {
+        /// Symbol of the order.
+        symbol: String,
+
+        /// Id of the order.
+        order_id: String,
+
+        /// Price of the fill.
+        price: String,
+
+        /// Size of the fill.
+        size: String,
+
+        /// Size which remains to be filled.
+        remaining_size: String,
+
+        /// Timestamp of the fill.
+        timestamp: Timestamp,
+    },
+
+    /// An order expired, e.g. it was canceled.
+    Expiration {
+        /// Symbol of the order.
+        symbol: String,
+
+        /// Id of the order.
+        order_id: String,
+
+        /// Timestamp of the expiration.
+        timestamp: Timestamp,
+    },
+
+    /// A risk alert, e.g. a tripped circuit breaker or an anomaly on the account.
+    Alert {
+        /// Description of the alert.
+        message: String,
+
+        /// Timestamp of the alert.
+        timestamp: Timestamp,
+    },
+}
+
+impl Event {
+    /// Return the event corresponding to a notification of the user stream of `symbol`,
+    /// if any: order updates give fills, order expirations give expirations.
+    pub fn from_notification(symbol: Symbol, notification: &Notification) -> Option<Self> {
+        let (price_tick, size_tick) = (symbol.price_tick(), symbol.size_tick());
+
+        match notification {
+            Notification::OrderUpdate(update) => Some(Event::Fill {
+                symbol: symbol.name().to_owned(),
+                order_id: update.order_id.clone(),
+                price: price_tick.unticked(update.consumed_price).ok()?,
+                size: size_tick.unticked(update.consumed_size).ok()?,
+                remaining_size: size_tick.unticked(update.remaining_size).ok()?,
+                timestamp: update.timestamp(),
+            }),
+
+            Notification::OrderExpiration(expiration) => Some(Event::Expiration {
+                symbol: symbol.name().to_owned(),
+                order_id: expiration.order_id.clone(),
+                timestamp: expiration.timestamp(),
+            }),
+
+            _ => None,
+        }
+    }
+
+    /// Return an alert with the given message.
+    pub fn alert<S: Into<String>>(message: S, timestamp: Timestamp) -> Self {
+        Event::Alert {
+            message: message.into(),
+            timestamp,
+        }
+    }
+
+    /// Return an alert for a change of state of a `CircuitBreaker` on `symbol`, as
+    /// returned by `CircuitBreaker::on_notification` or `CircuitBreaker::on_time`.
+    pub fn breaker_state(symbol: Symbol, state: BreakerState, timestamp: Timestamp) -> Self {
+        let state = match state {
+            BreakerState::Normal => "back to normal",
+            BreakerState::Cautious => "cautious",
+            BreakerState::Halted => "halted",
+        };
+        Event::alert(format!("{}: circuit breaker {}", symbol.name(), state), timestamp)
+    }
+
+    /// Return an alert for an anomaly reported by an `AnomalyWatcher`.
+    pub fn anomaly(anomaly: &Anomaly, timestamp: Timestamp) -> Self {
+        let message = match anomaly {
+            Anomaly::UnknownOrder { order_id, .. } => {
+                format!("unknown order `{}` on the account", order_id)
+            }
+            Anomaly::BalanceChange { asset, previous, current } => {
+                format!("unexplained {} balance change: {} -> {}", asset, previous, current)
+            }
+        };
+        Event::alert(message, timestamp)
+    }
+
+    /// Return the timestamp of the event.
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            Event::Fill { timestamp, .. }
+            | Event::Expiration { timestamp, .. }
+            | Event::Alert { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Fill { symbol, order_id, price, size, remaining_size, .. } => write!(
+                f,
+                "{}: order `{}` filled {} @ {}, {} remaining",
+                symbol,
+                order_id,
+                size,
+                price,
+                remaining_size
+            ),
+            Event::Expiration { symbol, order_id, .. } => {
+                write!(f, "{}: order `{}` expired", symbol, order_id)
+            }
+            Event::Alert { message, .. } => write!(f, "ALERT {}", message),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Parameters of a `Notifier`.
+pub struct NotifyConfig {
+    /// Webhooks to which each batch is posted.
+    pub webhooks: Vec<Webhook>,
+
+    /// Maximum number of events in a batch.
+    pub max_batch: usize,
+
+    /// Maximum time during which an event waits for a batch to fill up, in ms.
+    pub batch_delay: Timestamp,
+
+    /// Number of times a failed post is retried before the batch is dropped for that
+    /// webhook.
+    pub max_retries: u32,
+
+    /// Delay before the first retry, in ms, doubled on each subsequent retry.
+    pub retry_delay: Timestamp,
+}
+
+impl NotifyConfig {
+    /// Return the delay before the `retry`-th retry of a post, starting from 1, or
+    /// `None` if no more retries are allowed.
+    pub fn backoff(&self, retry: u32) -> Option<Duration> {
+        if retry == 0 || retry > self.max_retries {
+            return None;
+        }
+        let factor = 1u64.checked_shl(retry - 1).unwrap_or(u64::max_value());
+        Some(Duration::from_millis(self.retry_delay.saturating_mul(factor)))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// Collect events into batches, see `NotifyConfig::max_batch` and
+/// `NotifyConfig::batch_delay`.
+pub struct Batcher {
+    events: Vec<Event>,
+    max_batch: usize,
+    batch_delay: Timestamp,
+
+    /// Time at which the oldest pending event was pushed.
+    since: Option<Timestamp>,
+}
+
+impl Batcher {
+    /// Return a new, empty `Batcher`.
+    pub fn new(max_batch: usize, batch_delay: Timestamp) -> Self {
+        Batcher {
+            events: Vec::new(),
+            max_batch: max_batch.max(1),
+            batch_delay,
+            since: None,
+        }
+    }
+
+    /// Return the number of pending events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Return `true` if no event is pending.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Push an event received at time `now`, and return the pending batch if it is
+    /// full.
+    pub fn push(&mut self, event: Event, now: Timestamp) -> Option<Vec<Event>> {
+        self.since.get_or_insert(now);
+        self.events.push(event);
+
+        if self.events.len() >= self.max_batch {
+            return Some(self.flush());
+        }
+        None
+    }
+
+    /// Return the pending batch if its oldest event has waited long enough at time
+    /// `now`.
+    pub fn pop(&mut self, now: Timestamp) -> Option<Vec<Event>> {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => Some(self.flush()),
+            _ => None,
+        }
+    }
+
+    /// Return the time at which the pending batch is due, if any.
+    pub fn deadline(&self) -> Option<Timestamp> {
+        self.since.map(|since| since + self.batch_delay)
+    }
+
+    /// Return the pending events, however many they are.
+    pub fn flush(&mut self) -> Vec<Event> {
+        self.since = None;
+        self.events.drain(..).collect()
+    }
+}
+
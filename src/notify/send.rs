@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::prelude::*;
+use futures::future::{self, Either, Loop};
+use futures::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use hyper::{Method, Request};
+use log::{error, warn};
+use crate::api::timestamp::timestamp_ms;
+use crate::notify::{Batcher, Event, NotifyConfig};
+
+type HttpClient = hyper::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>;
+
+#[derive(Clone, Debug)]
+/// A handle queuing events for the webhooks, see `Notifier::new`. Clones share the
+/// same batches.
+pub struct Notifier {
+    sender: UnboundedSender<Event>,
+}
+
+impl Notifier {
+    /// Return a new `Notifier`, along with the future batching its events and posting
+    /// them to the webhooks of `config`. The future must be run inside a tokio runtime,
+    /// and completes once all the clones of the `Notifier` have been dropped and the
+    /// last batch has been handed over for posting.
+    pub fn new(config: NotifyConfig) -> Result<(Self, NotifyFuture), failure::Error> {
+        let http_client = hyper::Client::builder().build::<_, hyper::Body>(
+            hyper_tls::HttpsConnector::new(2)?
+        );
+        let (sender, receiver) = mpsc::unbounded();
+
+        let future = NotifyFuture {
+            events: Some(receiver),
+            batcher: Batcher::new(config.max_batch, config.batch_delay),
+            config: Arc::new(config),
+            http_client,
+            delay: None,
+        };
+        Ok((Notifier { sender }, future))
+    }
+
+    /// Queue an event. The event is lost if the `NotifyFuture` has been dropped.
+    pub fn notify(&self, event: Event) {
+        if self.sender.unbounded_send(event).is_err() {
+            warn!("notifier has stopped, dropping event");
+        }
+    }
+}
+
+/// A future batching the events of a `Notifier` and posting them, see `Notifier::new`.
+pub struct NotifyFuture {
+    events: Option<UnboundedReceiver<Event>>,
+    batcher: Batcher,
+    config: Arc<NotifyConfig>,
+    http_client: HttpClient,
+
+    /// Set while a batch is pending.
+    delay: Option<tokio::timer::Delay>,
+}
+
+impl NotifyFuture {
+    fn dispatch(&self, batch: Vec<Event>) {
+        for webhook in &self.config.webhooks {
+            let (url, body) = webhook.request(&batch);
+            tokio::spawn(post(self.http_client.clone(), self.config.clone(), url, body));
+        }
+    }
+}
+
+impl Future for NotifyFuture {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        while let Some(events) = self.events.as_mut() {
+            match events.poll()? {
+                Async::Ready(Some(event)) => {
+                    if let Some(batch) = self.batcher.push(event, timestamp_ms()) {
+                        self.dispatch(batch);
+                    }
+                }
+                Async::Ready(None) => self.events = None,
+                Async::NotReady => break,
+            }
+        }
+
+        if self.events.is_none() {
+            if !self.batcher.is_empty() {
+                let batch = self.batcher.flush();
+                self.dispatch(batch);
+            }
+            return Ok(Async::Ready(()));
+        }
+
+        loop {
+            let now = timestamp_ms();
+            if let Some(batch) = self.batcher.pop(now) {
+                self.delay = None;
+                self.dispatch(batch);
+            }
+
+            let deadline = match self.batcher.deadline() {
+                Some(deadline) => deadline,
+                None => return Ok(Async::NotReady),
+            };
+
+            let deadline = Instant::now() + Duration::from_millis(deadline.saturating_sub(now));
+            let delay = self.delay.get_or_insert_with(|| tokio::timer::Delay::new(deadline));
+            delay.reset(deadline);
+
+            match delay.poll() {
+                Ok(Async::Ready(())) => continue,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => {
+                    error!("notifier timer encountered error: `{}`", err);
+                    return Ok(Async::NotReady);
+                }
+            }
+        }
+    }
+}
+
+/// Post `body` to `url`, retrying as configured. Urls are not logged since they may
+/// contain a token.
+fn post(http_client: HttpClient, config: Arc<NotifyConfig>, url: String, body: String)
+    -> impl Future<Item = (), Error = ()> + Send + 'static
+{
+    future::loop_fn(0, move |retry| {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&url)
+            .header("User-Agent", &b"hyper"[..])
+            .header("Content-Type", &b"application/json"[..])
+            .body(body.clone().into());
+
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                error!("invalid webhook request: `{}`", err);
+                return Either::A(future::ok(Loop::Break(())));
+            }
+        };
+
+        let config = config.clone();
+        Either::B(http_client.request(request).then(move |res| {
+            let err = match res {
+                Ok(ref res) if res.status().is_success() => {
+                    return Either::A(future::ok(Loop::Break(())));
+                }
+                Ok(res) => format!("status {}", res.status()),
+                Err(err) => err.to_string(),
+            };
+
+            match config.backoff(retry + 1) {
+                Some(backoff) => {
+                    warn!("webhook post failed: `{}`, retrying in {:?}", err, backoff);
+                    Either::B(
+                        tokio::timer::Delay::new(Instant::now() + backoff)
+                            .map(move |()| Loop::Continue(retry + 1))
+                            .map_err(|err| error!("notifier timer encountered error: `{}`", err))
+                    )
+                }
+                None => {
+                    error!("webhook post failed: `{}`, dropping batch", err);
+                    Either::A(future::ok(Loop::Break(())))
+                }
+            }
+        }))
+    })
+}
@@ -0,0 +1,111 @@
+#![cfg(test)]
+
+use std::time::Duration;
+use crate::api::{Notification, OrderUpdate, OrderExpiration};
+use crate::api::symbol::Symbol;
+use crate::api::timestamp::IntoTimestamped;
+use crate::notify::{Batcher, Event, NotifyConfig, Webhook};
+use crate::risk::BreakerState;
+use crate::risk::anomaly::Anomaly;
+use crate::tick::Tick;
+
+fn symbol() -> Symbol {
+    Symbol::new("BTCUSDT", Tick::new(100), Tick::new(1000)).unwrap()
+}
+
+fn config() -> NotifyConfig {
+    NotifyConfig {
+        webhooks: vec![],
+        max_batch: 3,
+        batch_delay: 1000,
+        max_retries: 3,
+        retry_delay: 500,
+    }
+}
+
+#[test]
+fn events() {
+    let fill = Notification::OrderUpdate(OrderUpdate {
+        order_id: "a".to_owned(),
+        consumed_size: 250,
+        remaining_size: 750,
+        consumed_price: 2500050,
+        commission: 0,
+    }.with_timestamp(10));
+    let event = Event::from_notification(symbol(), &fill).unwrap();
+    assert_eq!(event.timestamp(), 10);
+    assert_eq!(event.to_string(), "BTCUSDT: order `a` filled 0.250 @ 25000.50, 0.750 remaining");
+
+    let expiration = Notification::OrderExpiration(OrderExpiration {
+        order_id: "a".to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.with_timestamp(20));
+    let event = Event::from_notification(symbol(), &expiration).unwrap();
+    assert_eq!(event.to_string(), "BTCUSDT: order `a` expired");
+
+    let event = Event::breaker_state(symbol(), BreakerState::Halted, 30);
+    assert_eq!(event.to_string(), "ALERT BTCUSDT: circuit breaker halted");
+
+    let anomaly = Anomaly::UnknownOrder {
+        order_id: "b".to_owned(),
+        timestamp: 40,
+    };
+    assert_eq!(
+        Event::anomaly(&anomaly, 40).to_string(),
+        "ALERT unknown order `b` on the account"
+    );
+}
+
+#[test]
+fn requests() {
+    let events = vec![Event::alert("first", 0), Event::alert("second", 1)];
+
+    let slack = Webhook::Slack { url: "https://hooks.slack.com/x".to_owned() };
+    assert_eq!(
+        slack.request(&events),
+        ("https://hooks.slack.com/x".to_owned(), r#"{"text":"ALERT first\nALERT second"}"#.to_owned())
+    );
+
+    let telegram = Webhook::Telegram { bot_token: "token".to_owned(), chat_id: "42".to_owned() };
+    let (url, body) = telegram.request(&events);
+    assert_eq!(url, "https://api.telegram.org/bottoken/sendMessage");
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["chat_id"], "42");
+    assert_eq!(body["text"], "ALERT first\nALERT second");
+
+    let http = Webhook::Http { url: "http://localhost/events".to_owned() };
+    let (_, body) = http.request(&events);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["events"][1]["Alert"]["message"], "second");
+}
+
+#[test]
+fn batches() {
+    let mut batcher = Batcher::new(3, 1000);
+    assert_eq!(batcher.deadline(), None);
+
+    assert_eq!(batcher.push(Event::alert("a", 0), 100), None);
+    assert_eq!(batcher.push(Event::alert("b", 0), 500), None);
+    assert_eq!(batcher.deadline(), Some(1100));
+    assert_eq!(batcher.pop(1099), None);
+    assert_eq!(batcher.pop(1100).map(|batch| batch.len()), Some(2));
+    assert!(batcher.is_empty());
+    assert_eq!(batcher.pop(5000), None);
+
+    // A full batch is sent right away.
+    assert_eq!(batcher.push(Event::alert("c", 0), 2000), None);
+    assert_eq!(batcher.push(Event::alert("d", 0), 2000), None);
+    assert_eq!(batcher.push(Event::alert("e", 0), 2000).map(|batch| batch.len()), Some(3));
+    assert_eq!(batcher.deadline(), None);
+}
+
+#[test]
+fn backoff() {
+    let config = config();
+    assert_eq!(config.backoff(0), None);
+    assert_eq!(config.backoff(1), Some(Duration::from_millis(500)));
+    assert_eq!(config.backoff(2), Some(Duration::from_millis(1000)));
+    assert_eq!(config.backoff(3), Some(Duration::from_millis(2000)));
+    assert_eq!(config.backoff(4), None);
+}
@@ -1,9 +1,11 @@
 //! A module defining a simple data structure representing an order book.
 
+#[cfg(feature = "std")]
 pub mod display;
 mod test;
 
-use std::collections::btree_map::BTreeMap;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
 use serde_derive::{Serialize, Deserialize};
 use crate::Side;
 use crate::tick::TickUnit;
@@ -70,7 +72,7 @@ impl OrderBook {
     /// # Complexity
     /// `O(log(n))` where `n` is the number of limits at the given side.
     pub fn update(&mut self, update: LimitUpdate) {
-        use std::collections::btree_map::Entry;
+        use alloc::collections::btree_map::Entry;
 
         let entry = match update.side {
             Side::Bid if update.size == 0 => {
@@ -119,7 +121,7 @@ impl OrderBook {
     /// order to be equal to `other`.
     /// 
     /// # Complexity
-    /// `O(n + m)` where `n` is `self`'s length and `m` is `other`'s length.
+    /// `O((n + m) log(n))` where `n` is `self`'s length and `m` is `other`'s length.
     /// 
     /// # Example
     /// ```
@@ -135,12 +137,10 @@ impl OrderBook {
     /// # }
     /// ```
     pub fn diff(&self, other: &OrderBook) -> impl Iterator<Item = LimitUpdate> {
-        use std::collections::HashMap;
-
         let mut updates = Vec::new();
 
         let mut compute_diff = |entries: &BTreeMap<_, _>, other_entries, side| {
-            let mut entries: HashMap<_, _> = entries.iter().map(|(x, y)| (*x, *y)).collect();
+            let mut entries = entries.clone();
 
             for (&price, &other_size) in other_entries {
                 let need_update = entries.remove(&price)
@@ -0,0 +1,244 @@
+//! A module defining live markouts of fills, i.e. how the mid price moved after each
+//! fill, a measure of adverse selection which is key for maker strategies.
+//!
+//! A `MarkoutTracker` is fed with the notifications of the stream of a symbol: it
+//! maintains the mid price out of the limit updates, learns the side of the orders from
+//! their confirmations and records a `FillRecord` for each order update. Once the
+//! market data reaches a horizon after a fill, the mid price at that time is stored
+//! with the record. Like in `reports::tca`, markouts are expressed in basis points of
+//! the fill price, and are positive when the mid price moved in favor of the order.
+
+mod test;
+
+use std::collections::{HashMap, VecDeque};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::Notification;
+use crate::api::timestamp::Timestamp;
+use crate::data::resample::TopOfBook;
+use crate::order_book::OrderBook;
+use crate::reports::{WeightedMean, cost_sign};
+use crate::tick::TickUnit;
+
+/// Default markout horizons: 1s, 10s and 1min.
+pub const DEFAULT_HORIZONS: [Timestamp; 3] = [1_000, 10_000, 60_000];
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// A fill along with its markouts.
+pub struct FillRecord {
+    /// Id of the filled order.
+    pub order_id: String,
+
+    /// Side of the filled order.
+    pub side: Side,
+
+    /// Time of the fill.
+    pub timestamp: Timestamp,
+
+    /// Price of the fill, in ticks.
+    pub price: TickUnit,
+
+    /// Size of the fill, in ticks.
+    pub size: TickUnit,
+
+    /// Mid price at the time of the fill in ticks, or `None` if one side of the book
+    /// was empty.
+    pub mid_price: Option<f64>,
+
+    /// Markout at each horizon reached so far, in bps, by increasing horizon. A
+    /// markout is `None` if one side of the book was empty at that time.
+    pub markouts: Vec<(Timestamp, Option<f64>)>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+/// Summary of the markouts of all the fills recorded by a `MarkoutTracker`.
+pub struct Summary {
+    /// Number of fills.
+    pub fills: usize,
+
+    /// Total filled size, in ticks.
+    pub filled_size: TickUnit,
+
+    /// Filled size weighted average markout at each horizon, in bps, over the fills
+    /// which reached this horizon.
+    pub markouts: Vec<(Timestamp, Option<f64>)>,
+}
+
+/// Compute the markouts of fills out of the live notifications of a symbol.
+pub struct MarkoutTracker {
+    horizons: Vec<Timestamp>,
+    order_book: OrderBook,
+
+    /// order id => side, for the orders confirmed and not yet closed
+    sides: HashMap<String, Side>,
+
+    /// Fills by order of arrival, the ones with pending markouts not being drained.
+    fills: VecDeque<FillRecord>,
+
+    fill_count: usize,
+    filled_size: TickUnit,
+    means: Vec<WeightedMean>,
+}
+
+impl MarkoutTracker {
+    /// Return a new `MarkoutTracker` computing markouts at `DEFAULT_HORIZONS`.
+    pub fn new() -> Self {
+        MarkoutTracker::with_horizons(&DEFAULT_HORIZONS)
+    }
+
+    /// Return a new `MarkoutTracker` computing markouts at the given horizons, in ms
+    /// after each fill.
+    pub fn with_horizons(horizons: &[Timestamp]) -> Self {
+        let mut horizons = horizons.to_vec();
+        horizons.sort();
+        horizons.dedup();
+
+        MarkoutTracker {
+            means: vec![WeightedMean::default(); horizons.len()],
+            horizons,
+            order_book: OrderBook::new(),
+            sides: HashMap::new(),
+            fills: VecDeque::new(),
+            fill_count: 0,
+            filled_size: 0,
+        }
+    }
+
+    /// Return the markout horizons, in increasing order.
+    pub fn horizons(&self) -> &[Timestamp] {
+        &self.horizons
+    }
+
+    /// Return the current mid price in ticks, or `None` if one side of the book is empty.
+    pub fn mid_price(&self) -> Option<f64> {
+        TopOfBook::from_order_book(&self.order_book).mid_price()
+    }
+
+    /// Register the side of an order whose confirmation is not part of the stream, e.g.
+    /// an order inserted before the stream was started.
+    pub fn add_order(&mut self, order_id: &str, side: Side) {
+        self.sides.insert(order_id.to_owned(), side);
+    }
+
+    /// Feed a notification received by the stream. Fills of orders whose side is
+    /// unknown are ignored.
+    pub fn on_notification(&mut self, notification: &Notification) {
+        match notification {
+            Notification::LimitUpdates(updates) => {
+                if let Some(time) = updates.iter().map(|update| update.timestamp()).max() {
+                    // Markouts due before these updates see the book as it was.
+                    self.resolve(|due| due < time);
+                }
+
+                for update in updates {
+                    self.order_book.update(**update);
+                }
+            }
+
+            Notification::Trade(trade) => {
+                let time = trade.timestamp();
+                self.resolve(|due| due < time);
+            }
+
+            Notification::OrderConfirmation(confirmation) => {
+                self.sides.insert(confirmation.order_id.clone(), confirmation.side);
+            }
+
+            Notification::OrderUpdate(update) => {
+                let side = match self.sides.get(&update.order_id) {
+                    Some(side) => *side,
+                    None => return,
+                };
+
+                if update.remaining_size == 0 {
+                    self.sides.remove(&update.order_id);
+                }
+
+                if update.consumed_size == 0 {
+                    return;
+                }
+
+                self.fill_count += 1;
+                self.filled_size += update.consumed_size;
+                self.fills.push_back(FillRecord {
+                    order_id: update.order_id.clone(),
+                    side,
+                    timestamp: update.timestamp(),
+                    price: update.consumed_price,
+                    size: update.consumed_size,
+                    mid_price: self.mid_price(),
+                    markouts: Vec::with_capacity(self.horizons.len()),
+                });
+            }
+
+            Notification::OrderExpiration(expiration) => {
+                self.sides.remove(&expiration.order_id);
+            }
+
+            _ => (),
+        }
+    }
+
+    /// Account for the passing of time when no market data is received, the book being
+    /// known to be unchanged up to `now`.
+    pub fn on_time(&mut self, now: Timestamp) {
+        self.resolve(|due| due <= now);
+    }
+
+    /// Store the markouts which are due according to `is_due`, using the current mid.
+    fn resolve<F: Fn(Timestamp) -> bool>(&mut self, is_due: F) {
+        let mid = self.mid_price();
+        let horizons = &self.horizons;
+        let means = &mut self.means;
+
+        for fill in self.fills.iter_mut().filter(|fill| fill.markouts.len() < horizons.len()) {
+            while let Some(&horizon) = horizons.get(fill.markouts.len()) {
+                if !is_due(fill.timestamp + horizon) {
+                    break;
+                }
+
+                let price = fill.price as f64;
+                let markout = mid.map(|mid| {
+                    cost_sign(fill.side) * (mid - price) / price * 10_000.
+                });
+                means[fill.markouts.len()].push(markout, fill.size);
+                fill.markouts.push((horizon, markout));
+            }
+        }
+    }
+
+    /// Return the fills not drained yet, by order of arrival.
+    pub fn fills(&self) -> impl Iterator<Item = &FillRecord> + '_ {
+        self.fills.iter()
+    }
+
+    /// Remove and return the oldest fills whose markouts have all been computed, e.g.
+    /// for storing them. Drained fills are still accounted for by `summary`.
+    pub fn drain_completed(&mut self) -> Vec<FillRecord> {
+        let horizons = self.horizons.len();
+        let count = self.fills
+            .iter()
+            .take_while(|fill| fill.markouts.len() == horizons)
+            .count();
+        self.fills.drain(..count).collect()
+    }
+
+    /// Return the summary of the markouts of all the fills recorded so far.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            fills: self.fill_count,
+            filled_size: self.filled_size,
+            markouts: self.horizons
+                .iter()
+                .zip(&self.means)
+                .map(|(horizon, mean)| (*horizon, mean.mean()))
+                .collect(),
+        }
+    }
+}
+
+impl Default for MarkoutTracker {
+    fn default() -> Self {
+        MarkoutTracker::new()
+    }
+}
@@ -0,0 +1,139 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade, OrderConfirmation, OrderUpdate, OrderExpiration};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+use crate::reports::markout::{MarkoutTracker, DEFAULT_HORIZONS};
+use crate::tick::TickUnit;
+
+fn quote(timestamp: Timestamp, bid: TickUnit, ask: TickUnit) -> Notification {
+    Notification::LimitUpdates(vec![
+        LimitUpdate::new(bid, 10, Side::Bid).with_timestamp(timestamp),
+        LimitUpdate::new(ask, 10, Side::Ask).with_timestamp(timestamp),
+    ])
+}
+
+fn moved(timestamp: Timestamp, from: (TickUnit, TickUnit), to: (TickUnit, TickUnit))
+    -> Notification
+{
+    Notification::LimitUpdates(vec![
+        LimitUpdate::new(from.0, 0, Side::Bid).with_timestamp(timestamp),
+        LimitUpdate::new(from.1, 0, Side::Ask).with_timestamp(timestamp),
+        LimitUpdate::new(to.0, 10, Side::Bid).with_timestamp(timestamp),
+        LimitUpdate::new(to.1, 10, Side::Ask).with_timestamp(timestamp),
+    ])
+}
+
+fn trade(timestamp: Timestamp) -> Notification {
+    Notification::Trade(Trade {
+        price: 100,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+fn confirmation(order_id: &str, side: Side) -> Notification {
+    Notification::OrderConfirmation(OrderConfirmation {
+        order_id: order_id.to_owned(),
+        price: 100,
+        size: 10,
+        side,
+    }.with_timestamp(0))
+}
+
+fn fill(timestamp: Timestamp, order_id: &str, price: TickUnit, size: TickUnit, remaining: TickUnit)
+    -> Notification
+{
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: size,
+        remaining_size: remaining,
+        consumed_price: price,
+        commission: 0,
+    }.with_timestamp(timestamp))
+}
+
+fn bps(from: f64, to: f64) -> f64 {
+    (to - from) / from * 10_000.
+}
+
+#[test]
+fn default_horizons() {
+    let tracker = MarkoutTracker::new();
+    assert_eq!(tracker.horizons(), &DEFAULT_HORIZONS);
+
+    let tracker = MarkoutTracker::with_horizons(&[100, 10, 100]);
+    assert_eq!(tracker.horizons(), &[10, 100]);
+}
+
+#[test]
+fn markouts() {
+    let mut tracker = MarkoutTracker::with_horizons(&[10, 100]);
+    tracker.on_notification(&quote(0, 99, 101));
+    tracker.on_notification(&confirmation("bid", Side::Bid));
+    tracker.on_notification(&confirmation("ask", Side::Ask));
+
+    tracker.on_notification(&fill(5, "bid", 99, 2, 8));
+    tracker.on_notification(&fill(6, "ask", 101, 1, 9));
+    tracker.on_notification(&fill(7, "unknown", 101, 1, 0));
+
+    // The mid price at 15 accounts for the updates timestamped at 15.
+    tracker.on_notification(&moved(15, (99, 101), (93, 95)));
+    tracker.on_notification(&trade(16));
+
+    let fills: Vec<_> = tracker.fills().cloned().collect();
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills[0].mid_price, Some(100.));
+    assert_eq!(fills[0].markouts, vec![(10, Some(bps(99., 94.)))]);
+    assert!(fills[1].markouts.is_empty());
+
+    tracker.on_time(16);
+    let markouts = &tracker.fills().nth(1).unwrap().markouts;
+    assert_eq!(markouts, &vec![(10, Some(-bps(101., 94.)))]);
+    assert!(tracker.drain_completed().is_empty());
+
+    tracker.on_notification(&moved(50, (93, 95), (103, 105)));
+    tracker.on_time(105);
+    let completed = tracker.drain_completed();
+    assert_eq!(completed.len(), 1);
+    assert_eq!(completed[0].markouts[1], (100, Some(bps(99., 104.))));
+
+    tracker.on_time(106);
+    assert_eq!(tracker.drain_completed().len(), 1);
+    assert_eq!(tracker.fills().count(), 0);
+
+    let summary = tracker.summary();
+    assert_eq!(summary.fills, 2);
+    assert_eq!(summary.filled_size, 3);
+    let expected = (2. * bps(99., 94.) - bps(101., 94.)) / 3.;
+    assert!((summary.markouts[0].1.unwrap() - expected).abs() < 1e-9);
+    let expected = (2. * bps(99., 104.) - bps(101., 104.)) / 3.;
+    assert!((summary.markouts[1].1.unwrap() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn closed_orders() {
+    let mut tracker = MarkoutTracker::with_horizons(&[10]);
+    tracker.on_notification(&confirmation("filled", Side::Bid));
+    tracker.on_notification(&confirmation("expired", Side::Ask));
+    tracker.add_order("older", Side::Ask);
+
+    tracker.on_notification(&fill(1, "filled", 100, 10, 0));
+    tracker.on_notification(&fill(2, "filled", 100, 1, 0));
+    tracker.on_notification(&Notification::OrderExpiration(OrderExpiration {
+        order_id: "expired".to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.with_timestamp(3)));
+    tracker.on_notification(&fill(4, "expired", 100, 1, 0));
+    tracker.on_notification(&fill(5, "older", 100, 1, 0));
+
+    let fills: Vec<_> = tracker.fills().map(|fill| fill.order_id.as_str()).collect();
+    assert_eq!(fills, vec!["filled", "older"]);
+
+    // No book: the markouts are computed but unavailable.
+    tracker.on_time(20);
+    assert_eq!(tracker.drain_completed()[0].markouts, vec![(10, None)]);
+    assert_eq!(tracker.summary().markouts, vec![(10, None)]);
+}
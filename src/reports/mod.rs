@@ -0,0 +1,39 @@
+//! A module defining reports on the execution of orders, computed either after the fact
+//! out of recorded notifications, or live while the notifications are received.
+
+pub mod markout;
+pub mod tca;
+
+use crate::Side;
+use crate::tick::TickUnit;
+
+/// A running size weighted mean.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+crate struct WeightedMean {
+    sum: f64,
+    weight: f64,
+}
+
+impl WeightedMean {
+    crate fn push(&mut self, value: Option<f64>, weight: TickUnit) {
+        if let Some(value) = value {
+            self.sum += value * weight as f64;
+            self.weight += weight as f64;
+        }
+    }
+
+    crate fn mean(&self) -> Option<f64> {
+        if self.weight == 0. {
+            return None;
+        }
+        Some(self.sum / self.weight)
+    }
+}
+
+/// Sign applied to price differences so that a higher price is a cost for `side`.
+crate fn cost_sign(side: Side) -> f64 {
+    match side {
+        Side::Bid => 1.,
+        Side::Ask => -1.,
+    }
+}
@@ -0,0 +1,405 @@
+//! A module defining post-trade transaction cost analysis (TCA).
+//!
+//! A `Tca` replays the recorded notifications of one symbol on one venue, i.e. the
+//! market data along with the order updates of the orders being analyzed, and compares
+//! each fill against benchmarks taken from the market data:
+//! * arrival price: the mid price at decision time
+//! * interval VWAP: the volume weighted average price of the trades printed between
+//!   decision time and the last fill of the order
+//! * opposite touch: the best ask at decision time for a buy order, the best bid for a
+//!   sell order, i.e. the price the order would have paid by crossing the spread
+//!
+//! Slippages are expressed in basis points of the benchmark and are signed so that a
+//! positive slippage is a cost. Markouts measure how the mid price moved after each
+//! fill, in basis points of the fill price, and are signed so that a positive markout
+//! is in favor of the order.
+//!
+//! Per order reports are then aggregated per strategy or per venue with `summarize`.
+
+mod test;
+
+use std::collections::{BTreeMap, HashMap};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::Notification;
+use crate::api::timestamp::Timestamp;
+use crate::data::resample::TopOfBook;
+use crate::reports::{WeightedMean, cost_sign};
+use crate::order_book::OrderBook;
+use crate::tick::TickUnit;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An order to be analyzed.
+pub struct OrderInfo {
+    /// Order id, as found in the order updates.
+    pub order_id: String,
+
+    /// Name of the strategy which sent the order.
+    pub strategy: String,
+
+    /// Side of the order.
+    pub side: Side,
+
+    /// Time at which the strategy decided to send the order.
+    pub decision_time: Timestamp,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A fill of an order.
+pub struct Fill {
+    /// Time of the fill.
+    pub timestamp: Timestamp,
+
+    /// Price of the fill, in ticks.
+    pub price: TickUnit,
+
+    /// Size of the fill, in ticks.
+    pub size: TickUnit,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// Transaction costs of a single order.
+pub struct OrderReport {
+    /// Order id.
+    pub order_id: String,
+
+    /// Name of the strategy which sent the order.
+    pub strategy: String,
+
+    /// Venue the order was sent to.
+    pub venue: String,
+
+    /// Side of the order.
+    pub side: Side,
+
+    /// Total filled size, in ticks.
+    pub filled_size: TickUnit,
+
+    /// Size weighted average fill price in ticks, or `None` if the order was not filled.
+    pub average_price: Option<f64>,
+
+    /// Mid price at decision time in ticks, or `None` if one side of the book was empty.
+    pub arrival_price: Option<f64>,
+
+    /// Slippage against the arrival price, in bps.
+    pub arrival_slippage: Option<f64>,
+
+    /// Slippage against the interval VWAP, in bps, or `None` if no trade was printed
+    /// over the interval.
+    pub vwap_slippage: Option<f64>,
+
+    /// Slippage against the opposite touch at decision time, in bps.
+    pub touch_slippage: Option<f64>,
+
+    /// Size weighted average markout of the fills at each horizon, in bps, or `None` if
+    /// the recorded market data does not cover the horizon after each fill.
+    pub markouts: Vec<(Timestamp, Option<f64>)>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+/// Transaction costs of a group of orders, each statistic being the filled size
+/// weighted average of the corresponding statistic of the orders defining it.
+pub struct Summary {
+    /// Number of orders in the group.
+    pub orders: usize,
+
+    /// Total filled size, in ticks.
+    pub filled_size: TickUnit,
+
+    /// Slippage against the arrival price, in bps.
+    pub arrival_slippage: Option<f64>,
+
+    /// Slippage against the interval VWAP, in bps.
+    pub vwap_slippage: Option<f64>,
+
+    /// Slippage against the opposite touch at decision time, in bps.
+    pub touch_slippage: Option<f64>,
+
+    /// Markout at each horizon, in bps.
+    pub markouts: Vec<(Timestamp, Option<f64>)>,
+}
+
+/// Slippage of `price` against `benchmark` for `side`, in bps.
+fn slippage(side: Side, price: f64, benchmark: f64) -> f64 {
+    cost_sign(side) * (price - benchmark) / benchmark * 10_000.
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct TrackedOrder {
+    info: OrderInfo,
+    fills: Vec<Fill>,
+}
+
+/// Transaction cost analysis of the orders sent on one symbol of one venue.
+pub struct Tca {
+    venue: String,
+    markout_horizons: Vec<Timestamp>,
+    order_book: OrderBook,
+
+    /// Top of the book after each batch of limit updates which modified it.
+    quotes: Vec<(Timestamp, TopOfBook)>,
+
+    /// Trades printed on the symbol, as `(timestamp, price, size)`.
+    trades: Vec<(Timestamp, TickUnit, TickUnit)>,
+
+    /// Latest timestamp of the recorded market data.
+    last_timestamp: Option<Timestamp>,
+
+    orders: Vec<TrackedOrder>,
+
+    /// order id => index in `orders`
+    index: HashMap<String, usize>,
+}
+
+impl Tca {
+    /// Return a new `Tca` for orders sent to `venue`, computing markouts at each of the
+    /// given horizons, in ms after each fill.
+    pub fn new(venue: &str, markout_horizons: &[Timestamp]) -> Self {
+        Tca {
+            venue: venue.to_owned(),
+            markout_horizons: markout_horizons.to_vec(),
+            order_book: OrderBook::new(),
+            quotes: Vec::new(),
+            trades: Vec::new(),
+            last_timestamp: None,
+            orders: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Return the venue the analyzed orders were sent to.
+    pub fn venue(&self) -> &str {
+        &self.venue
+    }
+
+    /// Register an order to analyze. Its fills are then taken from the order updates
+    /// carrying the same order id. Registering an order id twice replaces the
+    /// previous order and drops its fills.
+    pub fn add_order(&mut self, order: OrderInfo) {
+        let tracked = TrackedOrder {
+            info: order,
+            fills: Vec::new(),
+        };
+
+        match self.index.get(&tracked.info.order_id) {
+            Some(&i) => self.orders[i] = tracked,
+            None => {
+                self.index.insert(tracked.info.order_id.clone(), self.orders.len());
+                self.orders.push(tracked);
+            }
+        }
+    }
+
+    /// Record a fill of a registered order, e.g. when fills are not available as order
+    /// updates. Fills of unknown orders are ignored.
+    pub fn add_fill(&mut self, order_id: &str, fill: Fill) {
+        if let Some(&i) = self.index.get(order_id) {
+            self.orders[i].fills.push(fill);
+        }
+    }
+
+    /// Replay a recorded notification. Notifications must be replayed in the order
+    /// they were received.
+    pub fn on_notification(&mut self, notif: &Notification) {
+        match notif {
+            Notification::LimitUpdates(updates) => {
+                for update in updates {
+                    self.order_book.update(**update);
+                }
+
+                let timestamp = match updates.iter().map(|update| update.timestamp()).max() {
+                    Some(timestamp) => timestamp,
+                    None => return,
+                };
+                self.seen(timestamp);
+
+                let top = TopOfBook::from_order_book(&self.order_book);
+                if self.quotes.last().map(|(_, last)| *last != top).unwrap_or(true) {
+                    self.quotes.push((timestamp, top));
+                }
+            }
+
+            Notification::Trade(trade) => {
+                self.seen(trade.timestamp());
+                self.trades.push((trade.timestamp(), trade.price, trade.size));
+            }
+
+            Notification::OrderUpdate(update) => {
+                let fill = Fill {
+                    timestamp: update.timestamp(),
+                    price: update.consumed_price,
+                    size: update.consumed_size,
+                };
+                self.add_fill(&update.order_id, fill);
+            }
+
+            _ => (),
+        }
+    }
+
+    fn seen(&mut self, timestamp: Timestamp) {
+        self.last_timestamp = Some(
+            std::cmp::max(self.last_timestamp.unwrap_or(timestamp), timestamp)
+        );
+    }
+
+    /// Return the top of the book as of `timestamp`, or `None` if no book update was
+    /// recorded by then.
+    fn top_at(&self, timestamp: Timestamp) -> Option<&TopOfBook> {
+        use std::cmp::Ordering;
+
+        // Never returns `Ok`, the error being the number of quotes up to `timestamp`.
+        let count = self.quotes
+            .binary_search_by(|(quote_time, _)| {
+                if *quote_time <= timestamp { Ordering::Less } else { Ordering::Greater }
+            })
+            .unwrap_or_else(|count| count);
+
+        if count == 0 {
+            return None;
+        }
+        Some(&self.quotes[count - 1].1)
+    }
+
+    /// Return the VWAP of the trades printed in `[start, end]`.
+    fn vwap(&self, start: Timestamp, end: Timestamp) -> Option<f64> {
+        let mut mean = WeightedMean::default();
+        for &(timestamp, price, size) in &self.trades {
+            if timestamp >= start && timestamp <= end {
+                mean.push(Some(price as f64), size);
+            }
+        }
+        mean.mean()
+    }
+
+    fn order_report(&self, order: &TrackedOrder) -> OrderReport {
+        let side = order.info.side;
+        let decision_time = order.info.decision_time;
+
+        let mut average = WeightedMean::default();
+        for fill in &order.fills {
+            average.push(Some(fill.price as f64), fill.size);
+        }
+        let average_price = average.mean();
+        let filled_size = order.fills.iter().map(|fill| fill.size).sum();
+
+        let top = self.top_at(decision_time);
+        let arrival_price = top.and_then(|top| top.mid_price());
+        let touch = top.and_then(|top| match side {
+            Side::Bid if top.ask_size > 0 => Some(top.ask_price as f64),
+            Side::Ask if top.bid_size > 0 => Some(top.bid_price as f64),
+            _ => None,
+        });
+
+        let vwap = order.fills
+            .iter()
+            .map(|fill| fill.timestamp)
+            .max()
+            .and_then(|last_fill| self.vwap(decision_time, last_fill));
+
+        let versus = |benchmark: Option<f64>| match (average_price, benchmark) {
+            (Some(price), Some(benchmark)) => Some(slippage(side, price, benchmark)),
+            _ => None,
+        };
+
+        let markouts = self.markout_horizons
+            .iter()
+            .map(|&horizon| (horizon, self.markout(order, horizon)))
+            .collect();
+
+        OrderReport {
+            order_id: order.info.order_id.clone(),
+            strategy: order.info.strategy.clone(),
+            venue: self.venue.clone(),
+            side,
+            filled_size,
+            average_price,
+            arrival_price,
+            arrival_slippage: versus(arrival_price),
+            vwap_slippage: versus(vwap),
+            touch_slippage: versus(touch),
+            markouts,
+        }
+    }
+
+    /// Size weighted markout of the fills of `order` at `horizon`, `None` as soon as
+    /// one of the fills cannot be marked out.
+    fn markout(&self, order: &TrackedOrder, horizon: Timestamp) -> Option<f64> {
+        let last_timestamp = self.last_timestamp?;
+
+        let mut mean = WeightedMean::default();
+        for fill in &order.fills {
+            let time = fill.timestamp + horizon;
+            if time > last_timestamp {
+                return None;
+            }
+
+            let mid = self.top_at(time)?.mid_price()?;
+            let price = fill.price as f64;
+
+            mean.push(Some(cost_sign(order.info.side) * (mid - price) / price * 10_000.), fill.size);
+        }
+        mean.mean()
+    }
+
+    /// Return the report of each registered order, in the order they were registered.
+    pub fn report(&self) -> Vec<OrderReport> {
+        self.orders.iter().map(|order| self.order_report(order)).collect()
+    }
+}
+
+/// Aggregate `reports` into one summary per key, as given by `key`, e.g.
+/// `|report| &report.strategy` or `|report| &report.venue`. Unfilled orders are
+/// counted but do not weigh on the statistics.
+pub fn summarize<'a, I, F>(reports: I, key: F) -> BTreeMap<String, Summary>
+    where I: IntoIterator<Item = &'a OrderReport>, F: Fn(&OrderReport) -> &str
+{
+    #[derive(Default)]
+    struct Accumulator {
+        orders: usize,
+        filled_size: TickUnit,
+        arrival: WeightedMean,
+        vwap: WeightedMean,
+        touch: WeightedMean,
+        markouts: Vec<(Timestamp, WeightedMean)>,
+    }
+
+    let mut groups: BTreeMap<String, Accumulator> = BTreeMap::new();
+    for report in reports {
+        let acc = groups.entry(key(report).to_owned()).or_default();
+        let weight = report.filled_size;
+
+        acc.orders += 1;
+        acc.filled_size += weight;
+        acc.arrival.push(report.arrival_slippage, weight);
+        acc.vwap.push(report.vwap_slippage, weight);
+        acc.touch.push(report.touch_slippage, weight);
+
+        for &(horizon, markout) in &report.markouts {
+            let position = acc.markouts.iter().position(|(h, _)| *h == horizon);
+            let i = match position {
+                Some(i) => i,
+                None => {
+                    acc.markouts.push((horizon, WeightedMean::default()));
+                    acc.markouts.len() - 1
+                }
+            };
+            acc.markouts[i].1.push(markout, weight);
+        }
+    }
+
+    groups.into_iter().map(|(key, acc)| {
+        let summary = Summary {
+            orders: acc.orders,
+            filled_size: acc.filled_size,
+            arrival_slippage: acc.arrival.mean(),
+            vwap_slippage: acc.vwap.mean(),
+            touch_slippage: acc.touch.mean(),
+            markouts: acc.markouts
+                .into_iter()
+                .map(|(horizon, mean)| (horizon, mean.mean()))
+                .collect(),
+        };
+        (key, summary)
+    }).collect()
+}
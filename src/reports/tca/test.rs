@@ -0,0 +1,170 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{Notification, Trade, OrderUpdate};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+use crate::reports::tca::{Tca, OrderInfo, Fill, summarize};
+use crate::tick::TickUnit;
+
+fn quote(timestamp: Timestamp, bid: TickUnit, ask: TickUnit) -> Notification {
+    Notification::LimitUpdates(vec![
+        LimitUpdate::new(bid, 10, Side::Bid).with_timestamp(timestamp),
+        LimitUpdate::new(ask, 10, Side::Ask).with_timestamp(timestamp),
+    ])
+}
+
+fn clear(timestamp: Timestamp, bid: TickUnit, ask: TickUnit) -> Notification {
+    Notification::LimitUpdates(vec![
+        LimitUpdate::new(bid, 0, Side::Bid).with_timestamp(timestamp),
+        LimitUpdate::new(ask, 0, Side::Ask).with_timestamp(timestamp),
+    ])
+}
+
+fn trade(timestamp: Timestamp, price: TickUnit, size: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size,
+        maker_side: Side::Ask,
+    }.with_timestamp(timestamp))
+}
+
+fn fill(timestamp: Timestamp, order_id: &str, price: TickUnit, size: TickUnit) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: size,
+        remaining_size: 0,
+        consumed_price: price,
+        commission: 0,
+    }.with_timestamp(timestamp))
+}
+
+fn order(order_id: &str, strategy: &str, side: Side, decision_time: Timestamp) -> OrderInfo {
+    OrderInfo {
+        order_id: order_id.to_owned(),
+        strategy: strategy.to_owned(),
+        side,
+        decision_time,
+    }
+}
+
+fn assert_close(value: Option<f64>, expected: f64) {
+    let value = value.expect("missing value");
+    assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+}
+
+#[test]
+fn slippage_and_markouts() {
+    let mut tca = Tca::new("binance", &[1000, 60_000]);
+    tca.add_order(order("buy", "momentum", Side::Bid, 1000));
+    tca.add_order(order("sell", "momentum", Side::Ask, 1000));
+
+    let notifs = vec![
+        quote(500, 9990, 10010),
+        trade(1100, 10000, 3),
+        fill(1200, "buy", 10010, 1),
+        fill(1300, "buy", 10020, 1),
+        fill(1300, "sell", 9990, 2),
+        trade(1300, 10030, 1),
+        clear(2000, 9990, 10010),
+        quote(2000, 10040, 10060),
+        trade(5000, 9000, 100),
+    ];
+    for notif in &notifs {
+        tca.on_notification(notif);
+    }
+
+    let reports = tca.report();
+    assert_eq!(reports.len(), 2);
+
+    let buy = &reports[0];
+    assert_eq!(buy.venue, "binance");
+    assert_eq!(buy.filled_size, 2);
+    assert_close(buy.average_price, 10015.);
+    assert_close(buy.arrival_price, 10000.);
+    assert_close(buy.arrival_slippage, 15.);
+    assert_close(buy.touch_slippage, 5. / 10010. * 10_000.);
+    assert_close(buy.vwap_slippage, 7.5 / 10007.5 * 10_000.);
+    assert_eq!(buy.markouts.len(), 2);
+    assert_close(
+        buy.markouts[0].1,
+        ((10050. - 10010.) / 10010. + (10050. - 10020.) / 10020.) / 2. * 10_000.
+    );
+
+    // The recorded market data does not cover one minute after the fills.
+    assert_eq!(buy.markouts[1], (60_000, None));
+
+    let sell = &reports[1];
+    assert_close(sell.arrival_slippage, 10.);
+    assert_close(sell.touch_slippage, 0.);
+    assert_close(sell.markouts[0].1, (9990. - 10050.) / 9990. * 10_000.);
+}
+
+#[test]
+fn unknown_and_unfilled_orders() {
+    let mut tca = Tca::new("gdax", &[100]);
+    tca.add_order(order("early", "mm", Side::Bid, 0));
+    tca.add_order(order("unfilled", "mm", Side::Ask, 20));
+
+    tca.on_notification(&fill(5, "unknown", 100, 1));
+    tca.on_notification(&quote(10, 99, 101));
+    tca.add_fill("early", Fill { timestamp: 30, price: 101, size: 1 });
+
+    let reports = tca.report();
+    assert_eq!(reports.len(), 2);
+
+    // No book was recorded at decision time, nor any trade over the interval.
+    let early = &reports[0];
+    assert_close(early.average_price, 101.);
+    assert_eq!(early.arrival_price, None);
+    assert_eq!(early.arrival_slippage, None);
+    assert_eq!(early.touch_slippage, None);
+    assert_eq!(early.vwap_slippage, None);
+
+    let unfilled = &reports[1];
+    assert_eq!(unfilled.filled_size, 0);
+    assert_close(unfilled.arrival_price, 100.);
+    assert_eq!(unfilled.arrival_slippage, None);
+    assert_eq!(unfilled.markouts, vec![(100, None)]);
+}
+
+#[test]
+fn summaries() {
+    let mut tca = Tca::new("hitbtc", &[10]);
+    tca.add_order(order("a", "momentum", Side::Bid, 0));
+    tca.add_order(order("b", "momentum", Side::Bid, 0));
+    tca.add_order(order("c", "mean_reversion", Side::Ask, 0));
+    tca.add_order(order("d", "mean_reversion", Side::Ask, 0));
+
+    let notifs = vec![
+        quote(0, 99, 101),
+        fill(1, "a", 101, 1),
+        fill(1, "b", 102, 3),
+        fill(1, "c", 99, 2),
+        trade(1, 100, 1),
+        quote(20, 100, 102),
+    ];
+    for notif in &notifs {
+        tca.on_notification(notif);
+    }
+
+    let reports = tca.report();
+    let by_strategy = summarize(&reports, |report| &report.strategy);
+    assert_eq!(by_strategy.len(), 2);
+
+    let momentum = &by_strategy["momentum"];
+    assert_eq!(momentum.orders, 2);
+    assert_eq!(momentum.filled_size, 4);
+    assert_close(momentum.arrival_slippage, (100. + 3. * 200.) / 4.);
+
+    let mean_reversion = &by_strategy["mean_reversion"];
+    assert_eq!(mean_reversion.orders, 2);
+    assert_eq!(mean_reversion.filled_size, 2);
+    assert_close(mean_reversion.arrival_slippage, 100.);
+    assert_close(mean_reversion.markouts[0].1, (99. - 100.) / 99. * 10_000.);
+
+    let by_venue = summarize(&reports, |report| &report.venue);
+    assert_eq!(by_venue.len(), 1);
+    assert_eq!(by_venue["hitbtc"].orders, 4);
+    assert_eq!(by_venue["hitbtc"].filled_size, 6);
+}
@@ -0,0 +1,163 @@
+//! A module defining a watcher flagging account activity which cannot be attributed
+//! to the orders of this client, e.g. orders placed from another session with the
+//! same keys, or funds moved out of the account. This is a cheap first layer for
+//! detecting compromised keys.
+//!
+//! Like the `ComplianceCounters`, the watcher does not act by itself: the caller
+//! records the orders it sends and the transfers it makes, feeds the notifications of
+//! the user stream and the balances it polls, and gets an `Anomaly` for each event
+//! which is not its own. Anomalies should be handled as high-priority alerts.
+
+mod test;
+
+use std::collections::{HashMap, HashSet};
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{Balances, Notification};
+use crate::api::timestamp::Timestamp;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// Account activity which cannot be attributed to this client.
+pub enum Anomaly {
+    /// The user stream notified an order whose id was never recorded. Reported once
+    /// per order id.
+    UnknownOrder {
+        /// Id of the unknown order.
+        order_id: String,
+
+        /// Timestamp of the first notification about this order.
+        timestamp: Timestamp,
+    },
+
+    /// The total balance of an asset changed between two snapshots, while neither a
+    /// fill of a recorded order nor a transfer was seen in the meantime.
+    BalanceChange {
+        /// Name of the asset.
+        asset: String,
+
+        /// Total balance, i.e. free and locked, in the previous snapshot.
+        previous: f64,
+
+        /// Total balance in the current snapshot.
+        current: f64,
+    },
+}
+
+/// Watch the user stream and the balances of an account for activity which is not
+/// the doing of this client.
+///
+/// Orders must be sent with an id, see `GenerateOrderId`, and recorded through
+/// `on_order` before being sent.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AnomalyWatcher {
+    /// Ids of the live orders sent by this client.
+    orders: HashSet<String>,
+
+    /// Ids of the unknown orders already reported.
+    reported: HashSet<String>,
+
+    /// Total balance of each asset in the latest snapshot, if any.
+    balances: Option<HashMap<String, f64>>,
+
+    /// Whether balances are expected to change until the next snapshot.
+    expect_change: bool,
+}
+
+impl AnomalyWatcher {
+    /// Return a new watcher, which does not know any order yet.
+    pub fn new() -> Self {
+        AnomalyWatcher::default()
+    }
+
+    /// Record an order sent by this client.
+    pub fn on_order(&mut self, order_id: &str) {
+        self.orders.insert(order_id.to_owned());
+    }
+
+    /// Record a deposit, withdrawal or conversion made by this client, so that the
+    /// balance changes until the next snapshot are not reported.
+    pub fn on_transfer(&mut self) {
+        self.expect_change = true;
+    }
+
+    /// Return `true` if `order_id` is a live order sent by this client.
+    pub fn is_own_order(&self, order_id: &str) -> bool {
+        self.orders.contains(order_id)
+    }
+
+    /// Feed a notification of the user stream. Orders of this client are forgotten
+    /// once fully filled or expired.
+    pub fn on_notification(&mut self, notification: &Notification) -> Option<Anomaly> {
+        let (order_id, timestamp, done, filled) = match notification {
+            Notification::OrderConfirmation(confirmation) => {
+                (&confirmation.order_id, confirmation.timestamp(), false, false)
+            }
+            Notification::OrderUpdate(update) => (
+                &update.order_id,
+                update.timestamp(),
+                update.remaining_size == 0,
+                update.consumed_size > 0,
+            ),
+            Notification::OrderExpiration(expiration) => {
+                (&expiration.order_id, expiration.timestamp(), true, false)
+            }
+            _ => return None,
+        };
+
+        if !self.orders.contains(order_id) {
+            if !self.reported.insert(order_id.clone()) {
+                return None;
+            }
+            return Some(Anomaly::UnknownOrder {
+                order_id: order_id.clone(),
+                timestamp,
+            });
+        }
+
+        if filled {
+            self.expect_change = true;
+        }
+        if done {
+            self.orders.remove(order_id);
+        }
+        None
+    }
+
+    /// Feed a snapshot of the balances, e.g. as returned by `ApiClient::balances`.
+    /// The first snapshot is only recorded. Balances which cannot be parsed are
+    /// ignored.
+    pub fn on_balances(&mut self, balances: &Balances) -> Vec<Anomaly> {
+        let current: HashMap<_, _> = balances.iter().filter_map(|(asset, balance)| {
+            let free = balance.free.parse::<f64>().ok()?;
+            let locked = balance.locked.parse::<f64>().ok()?;
+            Some((asset.clone(), free + locked))
+        }).collect();
+
+        let previous = match self.balances.replace(current.clone()) {
+            Some(previous) => previous,
+            None => return Vec::new(),
+        };
+
+        if self.expect_change {
+            self.expect_change = false;
+            return Vec::new();
+        }
+
+        let mut assets: Vec<_> = previous.keys().chain(current.keys()).collect();
+        assets.sort();
+        assets.dedup();
+
+        assets.into_iter().filter_map(|asset| {
+            let previous = previous.get(asset).cloned().unwrap_or(0.);
+            let current = current.get(asset).cloned().unwrap_or(0.);
+
+            if previous == current {
+                return None;
+            }
+            Some(Anomaly::BalanceChange {
+                asset: asset.clone(),
+                previous,
+                current,
+            })
+        }).collect()
+    }
+}
@@ -0,0 +1,99 @@
+#![cfg(test)]
+
+use crate::api::{
+    Balance,
+    Balances,
+    Notification,
+    OrderConfirmation,
+    OrderUpdate,
+    OrderExpiration,
+};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::risk::anomaly::{AnomalyWatcher, Anomaly};
+use crate::tick::TickUnit;
+use crate::Side;
+
+fn confirmation(order_id: &str, timestamp: Timestamp) -> Notification {
+    Notification::OrderConfirmation(OrderConfirmation {
+        order_id: order_id.to_owned(),
+        price: 100,
+        size: 3,
+        side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+fn fill(order_id: &str, size: TickUnit, remaining: TickUnit) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: size,
+        remaining_size: remaining,
+        consumed_price: 100,
+        commission: 0,
+    }.with_timestamp(0))
+}
+
+fn expiration(order_id: &str) -> Notification {
+    Notification::OrderExpiration(OrderExpiration {
+        order_id: order_id.to_owned(),
+        filled_size: None,
+        remaining_size: None,
+    }.with_timestamp(0))
+}
+
+fn balances(values: &[(&str, &str, &str)]) -> Balances {
+    values.iter().map(|&(asset, free, locked)| {
+        (asset.to_owned(), Balance {
+            free: free.to_owned(),
+            locked: locked.to_owned(),
+        })
+    }).collect()
+}
+
+#[test]
+fn unknown_orders() {
+    let mut watcher = AnomalyWatcher::new();
+    watcher.on_order("own");
+    assert!(watcher.is_own_order("own"));
+
+    assert_eq!(watcher.on_notification(&confirmation("own", 10)), None);
+    assert_eq!(
+        watcher.on_notification(&confirmation("other", 20)),
+        Some(Anomaly::UnknownOrder { order_id: "other".to_owned(), timestamp: 20 })
+    );
+
+    // Only reported once.
+    assert_eq!(watcher.on_notification(&fill("other", 1, 2)), None);
+
+    assert_eq!(watcher.on_notification(&fill("own", 1, 2)), None);
+    assert_eq!(watcher.on_notification(&fill("own", 2, 0)), None);
+    assert!(!watcher.is_own_order("own"));
+
+    watcher.on_order("canceled");
+    assert_eq!(watcher.on_notification(&expiration("canceled")), None);
+    assert!(!watcher.is_own_order("canceled"));
+}
+
+#[test]
+fn balance_changes() {
+    let mut watcher = AnomalyWatcher::new();
+    assert!(watcher.on_balances(&balances(&[("BTC", "1", "0"), ("USDT", "100", "0")])).is_empty());
+
+    // Locking funds for an order does not change the totals.
+    assert!(watcher.on_balances(&balances(&[("BTC", "0.5", "0.5"), ("USDT", "100", "0")])).is_empty());
+
+    // A fill of an own order explains the next change.
+    watcher.on_order("own");
+    watcher.on_notification(&fill("own", 1, 0));
+    assert!(watcher.on_balances(&balances(&[("BTC", "0.5", "0"), ("USDT", "150", "0")])).is_empty());
+
+    watcher.on_transfer();
+    assert!(watcher.on_balances(&balances(&[("BTC", "0.5", "0"), ("USDT", "50", "0")])).is_empty());
+
+    assert_eq!(
+        watcher.on_balances(&balances(&[("ETH", "2", "0"), ("USDT", "50", "0")])),
+        vec![
+            Anomaly::BalanceChange { asset: "BTC".to_owned(), previous: 0.5, current: 0. },
+            Anomaly::BalanceChange { asset: "ETH".to_owned(), previous: 0., current: 2. },
+        ]
+    );
+}
@@ -0,0 +1,239 @@
+//! A module defining counters of the order flow of each symbol, for monitoring the
+//! ratios some venues enforce, e.g. a maximum order-to-trade ratio, and which
+//! compliance teams ask for.
+//!
+//! Like the `CircuitBreaker`, the counters do not act by themselves: the caller
+//! records the orders and cancels it sends, feeds the notifications of the stream, and
+//! gets an `Alarm` whenever a ratio crosses its threshold.
+
+mod test;
+
+use std::collections::{HashMap, HashSet};
+use serde_derive::{Serialize, Deserialize};
+use crate::api::Notification;
+use crate::api::id::SymbolId;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+/// Order flow of a symbol.
+pub struct Counts {
+    /// Number of orders submitted.
+    pub orders: u64,
+
+    /// Number of cancels submitted.
+    pub cancels: u64,
+
+    /// Number of fills, i.e. of order updates consuming some size.
+    pub fills: u64,
+
+    /// Number of orders which were at least partially filled.
+    pub filled_orders: u64,
+}
+
+impl Counts {
+    /// Return the number of orders submitted per fill, or `None` without any fill.
+    pub fn order_to_trade(&self) -> Option<f64> {
+        ratio(self.orders, self.fills)
+    }
+
+    /// Return the proportion of cancels among the orders submitted, or `None` without
+    /// any order.
+    pub fn cancel_rate(&self) -> Option<f64> {
+        ratio(self.cancels, self.orders)
+    }
+
+    /// Return the proportion of the orders submitted which were at least partially
+    /// filled, or `None` without any order.
+    pub fn fill_ratio(&self) -> Option<f64> {
+        ratio(self.filled_orders, self.orders)
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> Option<f64> {
+    if denominator == 0 {
+        return None;
+    }
+    Some(numerator as f64 / denominator as f64)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+/// A ratio watched by `ComplianceCounters`.
+pub enum Ratio {
+    /// See `Counts::order_to_trade`, breached when above its threshold. Breached without
+    /// any fill once `Thresholds::min_orders` orders have been submitted.
+    OrderToTrade,
+
+    /// See `Counts::cancel_rate`, breached when above its threshold.
+    CancelRate,
+
+    /// See `Counts::fill_ratio`, breached when below its threshold.
+    FillRatio,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+/// Thresholds of the alarms, `None` disabling the corresponding alarm.
+pub struct Thresholds {
+    /// Maximum number of orders per fill.
+    pub max_order_to_trade: Option<f64>,
+
+    /// Maximum proportion of cancels among the orders.
+    pub max_cancel_rate: Option<f64>,
+
+    /// Minimum proportion of filled orders.
+    pub min_fill_ratio: Option<f64>,
+
+    /// Number of orders below which no alarm is raised, the ratios being meaningless
+    /// over a handful of orders.
+    pub min_orders: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// A ratio of a symbol which just crossed its threshold.
+pub struct Alarm {
+    /// The symbol whose order flow breaches the threshold.
+    pub symbol: SymbolId,
+
+    /// The breached ratio.
+    pub ratio: Ratio,
+
+    /// Current value of the ratio, `None` for an order-to-trade ratio without any fill.
+    pub value: Option<f64>,
+
+    /// The breached threshold.
+    pub threshold: f64,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct SymbolState {
+    counts: Counts,
+
+    /// Orders filled so far which may still be filled.
+    live_filled: HashSet<String>,
+
+    /// Ratios currently breaching their threshold.
+    breached: HashSet<Ratio>,
+}
+
+/// Counters of the order flow of each symbol, raising alarms when a ratio crosses its
+/// threshold. An alarm is raised once per crossing: the ratio must come back within
+/// its threshold before raising a new alarm.
+///
+/// Venues usually compute these ratios over a trading day, see `reset`.
+pub struct ComplianceCounters {
+    thresholds: Thresholds,
+    symbols: HashMap<SymbolId, SymbolState>,
+}
+
+impl ComplianceCounters {
+    /// Return new counters raising alarms according to `thresholds`.
+    pub fn new(thresholds: Thresholds) -> Self {
+        ComplianceCounters {
+            thresholds,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Return the thresholds of the alarms.
+    pub fn thresholds(&self) -> &Thresholds {
+        &self.thresholds
+    }
+
+    /// Return the order flow of `symbol`.
+    pub fn counts(&self, symbol: SymbolId) -> Counts {
+        self.symbols.get(&symbol).map(|state| state.counts).unwrap_or_default()
+    }
+
+    /// Return the order flow of each symbol.
+    pub fn all_counts(&self) -> impl Iterator<Item = (SymbolId, Counts)> + '_ {
+        self.symbols.iter().map(|(symbol, state)| (*symbol, state.counts))
+    }
+
+    /// Record an order submitted on `symbol`.
+    pub fn on_order(&mut self, symbol: SymbolId) -> Vec<Alarm> {
+        self.symbols.entry(symbol).or_default().counts.orders += 1;
+        self.evaluate(symbol)
+    }
+
+    /// Record a cancel submitted on `symbol`.
+    pub fn on_cancel(&mut self, symbol: SymbolId) -> Vec<Alarm> {
+        self.symbols.entry(symbol).or_default().counts.cancels += 1;
+        self.evaluate(symbol)
+    }
+
+    /// Feed a notification of the stream of `symbol`, counting the fills.
+    pub fn on_notification(&mut self, symbol: SymbolId, notification: &Notification)
+        -> Vec<Alarm>
+    {
+        let state = self.symbols.entry(symbol).or_default();
+        match notification {
+            Notification::OrderUpdate(update) => {
+                if update.consumed_size > 0 {
+                    state.counts.fills += 1;
+                    if state.live_filled.insert(update.order_id.clone()) {
+                        state.counts.filled_orders += 1;
+                    }
+                }
+
+                if update.remaining_size == 0 {
+                    state.live_filled.remove(&update.order_id);
+                }
+            }
+
+            Notification::OrderExpiration(expiration) => {
+                state.live_filled.remove(&expiration.order_id);
+                return Vec::new();
+            }
+
+            _ => return Vec::new(),
+        }
+        self.evaluate(symbol)
+    }
+
+    /// Reset the counters of every symbol, e.g. at the start of a new trading day.
+    /// Ratios breaching their threshold raise a new alarm on their next breach.
+    pub fn reset(&mut self) {
+        self.symbols.clear();
+    }
+
+    fn evaluate(&mut self, symbol: SymbolId) -> Vec<Alarm> {
+        let thresholds = self.thresholds;
+        let state = match self.symbols.get_mut(&symbol) {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+        let counts = state.counts;
+
+        let checks = [
+            (Ratio::OrderToTrade, thresholds.max_order_to_trade, counts.order_to_trade()),
+            (Ratio::CancelRate, thresholds.max_cancel_rate, counts.cancel_rate()),
+            (Ratio::FillRatio, thresholds.min_fill_ratio, counts.fill_ratio()),
+        ];
+
+        let mut alarms = Vec::new();
+        for &(ratio, threshold, value) in &checks {
+            let threshold = match threshold {
+                Some(threshold) => threshold,
+                None => continue,
+            };
+
+            let enough_orders = counts.orders >= std::cmp::max(thresholds.min_orders, 1);
+            let breached = enough_orders && match (ratio, value) {
+                (Ratio::OrderToTrade, None) => true,
+                (Ratio::FillRatio, Some(value)) => value < threshold,
+                (_, Some(value)) => value > threshold,
+                (_, None) => false,
+            };
+
+            if !breached {
+                state.breached.remove(&ratio);
+            } else if state.breached.insert(ratio) {
+                alarms.push(Alarm {
+                    symbol,
+                    ratio,
+                    value,
+                    threshold,
+                });
+            }
+        }
+        alarms
+    }
+}
@@ -0,0 +1,134 @@
+#![cfg(test)]
+
+use crate::api::{Notification, OrderUpdate};
+use crate::api::id::{ExchangeId, SymbolId};
+use crate::api::timestamp::IntoTimestamped;
+use crate::risk::compliance::{ComplianceCounters, Counts, Thresholds, Ratio};
+use crate::tick::TickUnit;
+
+fn symbol(name: &str) -> SymbolId {
+    SymbolId::from_name(ExchangeId::Binance, name).unwrap()
+}
+
+fn fill(order_id: &str, size: TickUnit, remaining: TickUnit) -> Notification {
+    Notification::OrderUpdate(OrderUpdate {
+        order_id: order_id.to_owned(),
+        consumed_size: size,
+        remaining_size: remaining,
+        consumed_price: 100,
+        commission: 0,
+    }.with_timestamp(0))
+}
+
+#[test]
+fn ratios() {
+    let mut counters = ComplianceCounters::new(Thresholds::default());
+    let btc = symbol("BTCUSDT");
+    assert_eq!(counters.counts(btc).order_to_trade(), None);
+    assert_eq!(counters.counts(btc).cancel_rate(), None);
+
+    for _ in 0..4 {
+        assert!(counters.on_order(btc).is_empty());
+    }
+    counters.on_cancel(btc);
+    counters.on_notification(btc, &fill("a", 1, 2));
+    counters.on_notification(btc, &fill("a", 2, 0));
+    counters.on_notification(btc, &fill("b", 1, 0));
+
+    // Order "a" is done, so this is counted as a new filled order.
+    counters.on_notification(btc, &fill("a", 1, 0));
+
+    let counts = counters.counts(btc);
+    assert_eq!(counts, Counts { orders: 4, cancels: 1, fills: 4, filled_orders: 3 });
+    assert_eq!(counts.order_to_trade(), Some(1.));
+    assert_eq!(counts.cancel_rate(), Some(0.25));
+    assert_eq!(counts.fill_ratio(), Some(0.75));
+
+    assert_eq!(counters.counts(symbol("ETHUSDT")), Counts::default());
+    assert_eq!(counters.all_counts().count(), 1);
+
+    counters.reset();
+    assert_eq!(counters.counts(btc), Counts::default());
+}
+
+#[test]
+fn order_to_trade_alarm() {
+    let mut counters = ComplianceCounters::new(Thresholds {
+        max_order_to_trade: Some(2.),
+        min_orders: 3,
+        ..Thresholds::default()
+    });
+    let btc = symbol("BTCUSDT");
+
+    assert!(counters.on_order(btc).is_empty());
+    assert!(counters.on_order(btc).is_empty());
+
+    // No fill at all once enough orders were sent.
+    let alarms = counters.on_order(btc);
+    assert_eq!(alarms.len(), 1);
+    assert_eq!(alarms[0].symbol, btc);
+    assert_eq!(alarms[0].ratio, Ratio::OrderToTrade);
+    assert_eq!(alarms[0].value, None);
+    assert_eq!(alarms[0].threshold, 2.);
+
+    // An alarm is raised once per crossing.
+    assert!(counters.on_order(btc).is_empty());
+    assert!(counters.on_notification(btc, &fill("a", 1, 0)).is_empty());
+    assert!(counters.on_notification(btc, &fill("b", 1, 0)).is_empty());
+    assert_eq!(counters.counts(btc).order_to_trade(), Some(2.));
+
+    let alarms = counters.on_order(btc);
+    assert_eq!(alarms.len(), 1);
+    assert_eq!(alarms[0].value, Some(2.5));
+}
+
+#[test]
+fn cancel_rate_alarm() {
+    let mut counters = ComplianceCounters::new(Thresholds {
+        max_cancel_rate: Some(0.5),
+        min_orders: 3,
+        ..Thresholds::default()
+    });
+    let btc = symbol("BTCUSDT");
+
+    counters.on_order(btc);
+    counters.on_order(btc);
+    assert!(counters.on_cancel(btc).is_empty());
+    assert!(counters.on_cancel(btc).is_empty());
+
+    let alarms = counters.on_order(btc);
+    assert_eq!(alarms.len(), 1);
+    assert_eq!(alarms[0].ratio, Ratio::CancelRate);
+
+    // Other symbols are counted separately.
+    assert!(counters.on_cancel(symbol("ETHUSDT")).is_empty());
+
+    assert!(counters.on_cancel(btc).is_empty());
+    for _ in 0..3 {
+        assert!(counters.on_order(btc).is_empty());
+    }
+    assert_eq!(counters.counts(btc).cancel_rate(), Some(0.5));
+
+    let alarms = counters.on_cancel(btc);
+    assert_eq!(alarms.len(), 1);
+    assert_eq!(alarms[0].ratio, Ratio::CancelRate);
+}
+
+#[test]
+fn fill_ratio_alarm() {
+    let mut counters = ComplianceCounters::new(Thresholds {
+        min_fill_ratio: Some(0.5),
+        min_orders: 2,
+        ..Thresholds::default()
+    });
+    let btc = symbol("BTCUSDT");
+
+    assert!(counters.on_order(btc).is_empty());
+    assert!(counters.on_notification(btc, &fill("a", 1, 0)).is_empty());
+    assert!(counters.on_order(btc).is_empty());
+
+    let alarms = counters.on_order(btc);
+    assert_eq!(alarms.len(), 1);
+    assert_eq!(alarms[0].ratio, Ratio::FillRatio);
+    assert_eq!(alarms[0].value, Some(1. / 3.));
+}
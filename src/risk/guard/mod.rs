@@ -0,0 +1,235 @@
+//! A module defining a wrapper around API clients which enforces the decisions of
+//! circuit breakers, instead of leaving it to the caller to check them.
+//!
+//! A `GuardedClient` feeds the notifications of each stream it opens to a
+//! `CircuitBreaker` of the streamed symbol, and fails the orders sent on that symbol
+//! with `OrderErrorKind::TradingHalted` while the breaker does not allow them, e.g.
+//! while the exchange has halted trading on the symbol. Cancels are always sent.
+
+mod test;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::prelude::*;
+use crate::api::{
+    errors,
+    ApiClient,
+    GenerateOrderId,
+    Notification,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    OneCancelsOther,
+    OcoAck,
+    Cancel,
+    CancelAck,
+    OrderStatus,
+    Fill,
+    Replace,
+    Balances,
+    ExchangeStatus,
+    Trade,
+};
+use crate::api::symbol::{Symbol, WithSymbol};
+use crate::api::id::ExchangeId;
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped};
+use crate::data::resample::Bar;
+use crate::risk::{CircuitBreaker, BreakerConfig};
+
+type SharedBreaker = Arc<Mutex<CircuitBreaker>>;
+
+/// A client which blocks the orders of the symbols whose `CircuitBreaker` does not
+/// allow them, see `CircuitBreaker::order_allowed`.
+///
+/// A breaker is created with the first stream of a symbol, and orders on a symbol which
+/// was never streamed are not blocked. The notifications of all the streams of a symbol
+/// are fed to the same breaker, so a symbol should only be streamed once.
+///
+/// Before each order, the breaker is evaluated at the local time through
+/// `CircuitBreaker::on_time`, so that it steps down even if the market went quiet.
+pub struct GuardedClient<C> {
+    inner: C,
+    config: BreakerConfig,
+    breakers: Arc<Mutex<HashMap<Symbol, SharedBreaker>>>,
+}
+
+impl<C: ApiClient> GuardedClient<C> {
+    /// Wrap `client`, with breakers using `config`.
+    pub fn new(client: C, config: BreakerConfig) -> Self {
+        GuardedClient {
+            inner: client,
+            config,
+            breakers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Return a snapshot of the breaker of `symbol` evaluated at the current time, e.g.
+    /// for adjusting the quotes through `CircuitBreaker::widen_spread`, or `None` if
+    /// `symbol` was never streamed.
+    pub fn breaker(&self, symbol: Symbol) -> Option<CircuitBreaker> {
+        self.with_breaker(symbol, |breaker| breaker.clone())
+    }
+
+    /// Evaluate the breaker of `symbol` at the current time and call `f` with it, or
+    /// return `None` if `symbol` was never streamed.
+    fn with_breaker<T, F>(&self, symbol: Symbol, f: F) -> Option<T>
+        where F: FnOnce(&CircuitBreaker) -> T
+    {
+        let breakers = self.breakers.lock().unwrap();
+        let mut breaker = breakers.get(&symbol)?.lock().unwrap();
+        breaker.on_time(timestamp_ms());
+        Some(f(&breaker))
+    }
+
+    /// Return `true` if the breaker of `symbol` allows `order`.
+    fn allowed(&self, symbol: Symbol, order: &Order) -> bool {
+        self.with_breaker(symbol, |breaker| breaker.order_allowed(order)).unwrap_or(true)
+    }
+}
+
+/// Return an error of kind `OrderErrorKind::TradingHalted`.
+fn halted<T: Send + 'static>()
+    -> Box<dyn Future<Item = T, Error = errors::OrderError> + Send + 'static>
+{
+    Box::new(futures::future::err(
+        errors::ApiError::RestError(
+            errors::RestErrorKind::Specific(errors::OrderErrorKind::TradingHalted).into()
+        )
+    ))
+}
+
+/// A stream feeding its notifications to the breaker of its symbol.
+pub struct GuardedStream<S> {
+    inner: S,
+    breaker: SharedBreaker,
+}
+
+impl<S> Stream for GuardedStream<S> where S: Stream<Item = Notification, Error = ()> {
+    type Item = Notification;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let notif = self.inner.poll()?;
+        if let Async::Ready(Some(notif)) = &notif {
+            self.breaker.lock().unwrap().on_notification(notif);
+        }
+        Ok(notif)
+    }
+}
+
+impl<C: ApiClient> GenerateOrderId for GuardedClient<C> {
+    fn new_order_id(hint: &str) -> String {
+        C::new_order_id(hint)
+    }
+}
+
+impl<C: ApiClient> ApiClient for GuardedClient<C> {
+    type Stream = GuardedStream<C::Stream>;
+
+    fn exchange_id(&self) -> ExchangeId {
+        self.inner.exchange_id()
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Option<Symbol> {
+        self.inner.find_symbol(symbol)
+    }
+
+    fn stream_with_flags(&self, symbol: Symbol, flags: NotificationFlags) -> Self::Stream {
+        let config = self.config;
+        let breaker = self.breakers.lock().unwrap()
+            .entry(symbol)
+            .or_insert_with(|| Arc::new(Mutex::new(CircuitBreaker::new(config))))
+            .clone();
+
+        GuardedStream {
+            inner: self.inner.stream_with_flags(symbol, flags),
+            breaker,
+        }
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        if !self.allowed(order.symbol(), &order) {
+            return halted();
+        }
+        self.inner.order(order)
+    }
+
+    fn supports_oco(&self) -> bool {
+        self.inner.supports_oco()
+    }
+
+    fn oco(&self, oco: WithSymbol<&OneCancelsOther>)
+        -> Box<dyn Future<Item = Timestamped<OcoAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        let symbol = oco.symbol();
+        if !self.allowed(symbol, oco.limit()) || !self.allowed(symbol, oco.stop()) {
+            return halted();
+        }
+        self.inner.oco(oco)
+    }
+
+    fn recent_trades(&self, symbol: Symbol, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Trade>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_trades(symbol, limit)
+    }
+
+    fn recent_bars(&self, symbol: Symbol, interval: Timestamp, limit: usize)
+        -> Box<dyn Future<Item = Vec<Timestamped<Bar>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.recent_bars(symbol, interval, limit)
+    }
+
+    fn supports_atomic_replace(&self) -> bool {
+        self.inner.supports_atomic_replace()
+    }
+
+    /// While the new order is blocked, the previous order is still canceled and the
+    /// error is of kind `ReplaceErrorKind::Order`.
+    fn replace(&self, replace: WithSymbol<&Replace>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::ReplaceError> + Send + 'static>
+    {
+        if !self.allowed(replace.symbol(), replace.order()) {
+            return crate::api::cancel_then_order(self, replace);
+        }
+        self.inner.replace(replace)
+    }
+
+    fn order_status(&self, symbol: Symbol, order_id: &str)
+        -> Box<dyn Future<Item = Timestamped<OrderStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.order_status(symbol, order_id)
+    }
+
+    fn my_trades(&self, symbol: Symbol, since: Timestamp)
+        -> Box<dyn Future<Item = Vec<Timestamped<Fill>>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.my_trades(symbol, since)
+    }
+
+    fn cancel(&self, cancel: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = errors::CancelError> + Send + 'static>
+    {
+        self.inner.cancel(cancel)
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.ping()
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.balances()
+    }
+
+    fn system_status(&self)
+        -> Box<dyn Future<Item = Timestamped<ExchangeStatus>, Error = errors::Error> + Send + 'static>
+    {
+        self.inner.system_status()
+    }
+}
@@ -0,0 +1,207 @@
+#![cfg(test)]
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::prelude::*;
+use futures::sync::mpsc::{self, UnboundedSender, UnboundedReceiver};
+use crate::Side;
+use crate::api::{
+    errors,
+    ApiClient,
+    GenerateOrderId,
+    Notification,
+    NotificationFlags,
+    Order,
+    OrderAck,
+    Cancel,
+    CancelAck,
+    Replace,
+    Balances,
+    Trade,
+    TradingStatus,
+};
+use crate::api::errors::{ApiError, ErrorKind, RestErrorKind, OrderErrorKind, ReplaceErrorKind};
+use crate::api::id::ExchangeId;
+use crate::api::symbol::{Symbol, WithSymbol, IntoWithSymbol};
+use crate::api::timestamp::{timestamp_ms, Timestamp, Timestamped, IntoTimestamped};
+use crate::risk::{BreakerConfig, BreakerState, Thresholds};
+use crate::risk::guard::GuardedClient;
+use crate::tick::{Tick, TickUnit};
+
+/// A client counting the requests it receives, with a single stream fed by the test.
+struct MockClient {
+    stream: Mutex<Option<UnboundedReceiver<Notification>>>,
+    orders: AtomicUsize,
+    cancels: AtomicUsize,
+}
+
+impl GenerateOrderId for MockClient {
+    fn new_order_id(hint: &str) -> String {
+        hint.to_owned()
+    }
+}
+
+impl ApiClient for MockClient {
+    type Stream = UnboundedReceiver<Notification>;
+
+    fn exchange_id(&self) -> ExchangeId {
+        ExchangeId::Binance
+    }
+
+    fn find_symbol(&self, _: &str) -> Option<Symbol> {
+        None
+    }
+
+    fn stream_with_flags(&self, _: Symbol, _: NotificationFlags) -> Self::Stream {
+        self.stream.lock().unwrap().take().unwrap()
+    }
+
+    fn order(&self, order: WithSymbol<&Order>)
+        -> Box<dyn Future<Item = Timestamped<OrderAck>, Error = errors::OrderError> + Send + 'static>
+    {
+        self.orders.fetch_add(1, Ordering::SeqCst);
+        Box::new(futures::future::ok(OrderAck {
+            order_id: order.order_id().unwrap_or("").to_owned(),
+        }.with_timestamp(0)))
+    }
+
+    fn cancel(&self, _: WithSymbol<&Cancel>)
+        -> Box<dyn Future<Item = Timestamped<CancelAck>, Error = errors::CancelError> + Send + 'static>
+    {
+        self.cancels.fetch_add(1, Ordering::SeqCst);
+        Box::new(futures::future::ok(CancelAck.with_timestamp(0)))
+    }
+
+    fn ping(&self)
+        -> Box<dyn Future<Item = Timestamped<()>, Error = errors::Error> + Send + 'static>
+    {
+        Box::new(futures::future::ok(().with_timestamp(0)))
+    }
+
+    fn balances(&self)
+        -> Box<dyn Future<Item = Balances, Error = errors::Error> + Send + 'static>
+    {
+        Box::new(futures::future::ok(Balances::new()))
+    }
+}
+
+fn config() -> BreakerConfig {
+    BreakerConfig {
+        window: 1000,
+        caution: Thresholds {
+            volatility: 50,
+            message_rate: 5,
+        },
+        halt: Thresholds {
+            volatility: 200,
+            message_rate: 10,
+        },
+        cooldown: 2000,
+        spread_factor: 200,
+        size_factor: 50,
+    }
+}
+
+fn client() -> (GuardedClient<MockClient>, UnboundedSender<Notification>) {
+    let (sender, receiver) = mpsc::unbounded();
+    let client = MockClient {
+        stream: Mutex::new(Some(receiver)),
+        orders: AtomicUsize::new(0),
+        cancels: AtomicUsize::new(0),
+    };
+    (GuardedClient::new(client, config()), sender)
+}
+
+fn symbol(name: &str) -> Symbol {
+    Symbol::new(name, Tick::new(1), Tick::new(1)).unwrap()
+}
+
+fn status(status: &str) -> Timestamped<TradingStatus> {
+    TradingStatus {
+        status: status.to_owned(),
+        message: None,
+    }.with_timestamp(0)
+}
+
+fn trade(timestamp: Timestamp, price: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+fn kind<K: ErrorKind>(err: ApiError<K>) -> Option<RestErrorKind<K>> {
+    match err {
+        ApiError::RestError(err) => Some(err.kind()),
+        ApiError::RequestError(..) => None,
+    }
+}
+
+#[test]
+fn venue_halt() {
+    let (client, sender) = client();
+    let btc = symbol("BTCUSDT");
+    let mut stream = client.stream(btc);
+    let order = Order::new(100_u64, 1_u64, Side::Bid).with_order_id::<MockClient>("a");
+
+    assert!(client.order(order.with_symbol(btc)).wait().is_ok());
+
+    sender.unbounded_send(Notification::TradingHalted(status("BREAK"))).unwrap();
+    assert!((&mut stream).wait().next().is_some());
+    assert!(!client.breaker(btc).unwrap().trading_allowed());
+
+    let err = client.order(order.with_symbol(btc)).wait().unwrap_err();
+    assert_eq!(kind(err), Some(RestErrorKind::Specific(OrderErrorKind::TradingHalted)));
+    assert_eq!(client.inner.orders.load(Ordering::SeqCst), 1);
+
+    // Cancels still go through, the new order of a replace does not.
+    assert!(client.cancel(Cancel::new("a".to_owned()).with_symbol(btc)).wait().is_ok());
+    let replace = Replace::new("a".to_owned(), order.clone());
+    let err = client.replace(replace.with_symbol(btc)).wait().unwrap_err();
+    assert_eq!(
+        kind(err),
+        Some(RestErrorKind::Specific(ReplaceErrorKind::Order(OrderErrorKind::TradingHalted)))
+    );
+    assert_eq!(client.inner.cancels.load(Ordering::SeqCst), 2);
+    assert_eq!(client.inner.orders.load(Ordering::SeqCst), 1);
+
+    // Symbols which are not streamed are not guarded.
+    assert!(client.order(order.with_symbol(symbol("ETHUSDT"))).wait().is_ok());
+
+    sender.unbounded_send(Notification::TradingResumed(status("TRADING"))).unwrap();
+    assert!((&mut stream).wait().next().is_some());
+    assert!(client.order(order.with_symbol(btc)).wait().is_ok());
+    assert_eq!(client.inner.orders.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn quiet_market() {
+    let (client, sender) = client();
+    let btc = symbol("BTCUSDT");
+    let mut stream = client.stream(btc);
+    let order = Order::new(100_u64, 1_u64, Side::Bid).with_order_id::<MockClient>("a");
+    let state = || client.breakers.lock().unwrap()[&btc].lock().unwrap().state();
+
+    // A 10% drop 5s ago, no trades since then.
+    let now = timestamp_ms();
+    for notif in vec![trade(now - 5000, 10_000), trade(now - 4990, 9_000)] {
+        sender.unbounded_send(notif).unwrap();
+        assert!((&mut stream).wait().next().is_some());
+    }
+    assert_eq!(state(), BreakerState::Halted);
+
+    // The breaker is evaluated at the current time before the order, and steps down.
+    assert!(client.order(order.with_symbol(btc)).wait().is_ok());
+    assert_eq!(state(), BreakerState::Normal);
+
+    // A drop within the window still blocks the orders.
+    let now = timestamp_ms();
+    for notif in vec![trade(now, 9_000), trade(now + 10, 8_000)] {
+        sender.unbounded_send(notif).unwrap();
+        assert!((&mut stream).wait().next().is_some());
+    }
+    let err = client.order(order.with_symbol(btc)).wait().unwrap_err();
+    assert_eq!(kind(err), Some(RestErrorKind::Specific(OrderErrorKind::TradingHalted)));
+    assert_eq!(client.inner.orders.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,331 @@
+//! A module defining risk controls applied on top of a trading strategy.
+//!
+//! A `CircuitBreaker` watches the market data of a symbol and tells when quoting should
+//! become more cautious, or stop altogether, e.g. during a flash crash or a burst of
+//! book updates. Like `execution::rules`, it does not act by itself: it is fed with the
+//! notifications of the stream, and the caller adjusts its quotes through
+//! `CircuitBreaker::widen_spread` and `CircuitBreaker::reduce_size`, and checks
+//! `CircuitBreaker::trading_allowed` before sending new orders.
+//!
+//! The breaker is also halted while the exchange itself has halted trading on the
+//! symbol, as announced by `Notification::TradingHalted`, whatever the market data.
+//! During an auction phase, `CircuitBreaker::order_allowed` additionally checks that
+//! orders comply with the limit-only constraints of the auction.
+//!
+//! For blocking the orders automatically rather than checking the breaker before each
+//! order, see `guard::GuardedClient`.
+
+pub mod anomaly;
+pub mod compliance;
+pub mod guard;
+
+mod test;
+
+use std::collections::VecDeque;
+use serde_derive::{Serialize, Deserialize};
+use crate::api::{AuctionUpdate, Notification, Order, TradingStatus};
+use crate::api::timestamp::Timestamp;
+use crate::tick::TickUnit;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+/// State of a `CircuitBreaker`, ordered by increasing severity.
+pub enum BreakerState {
+    /// Trading as usual.
+    Normal,
+
+    /// Quotes are widened and sizes are reduced.
+    Cautious,
+
+    /// No new orders are to be sent.
+    Halted,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Thresholds triggering a state, each one being breached once reached.
+pub struct Thresholds {
+    /// Realized volatility of the trade prices over the window, in basis points.
+    pub volatility: u64,
+
+    /// Number of market data notifications, i.e. trades and batches of limit updates,
+    /// received over the window.
+    pub message_rate: usize,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Parameters of a `CircuitBreaker`.
+pub struct BreakerConfig {
+    /// Length of the sliding window over which the volatility and the message rate are
+    /// measured, in ms.
+    pub window: Timestamp,
+
+    /// Thresholds above which the breaker becomes `BreakerState::Cautious`.
+    pub caution: Thresholds,
+
+    /// Thresholds above which the breaker becomes `BreakerState::Halted`.
+    pub halt: Thresholds,
+
+    /// Time during which the measures must stay below the thresholds of the current
+    /// state before the breaker steps down, in ms.
+    pub cooldown: Timestamp,
+
+    /// Factor applied to the spread when cautious, in percent, e.g. `200` for doubling
+    /// the spread.
+    pub spread_factor: u64,
+
+    /// Factor applied to the sizes when cautious, in percent, e.g. `50` for halving
+    /// the sizes.
+    pub size_factor: u64,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// A circuit breaker over the market data of one symbol. The breaker trips as soon as
+/// a threshold is breached, and only steps down once the market has been calm for
+/// `BreakerConfig::cooldown`.
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+
+    /// State tripped by the market data.
+    state: BreakerState,
+
+    /// Status announced by the exchange while trading on the symbol is halted.
+    venue_halt: Option<TradingStatus>,
+
+    /// Latest update of the auction the symbol is in, if any.
+    auction: Option<AuctionUpdate>,
+
+    /// Latest time seen, in ms.
+    now: Timestamp,
+
+    /// Price of the last trade.
+    last_price: Option<TickUnit>,
+
+    /// Squared log returns between consecutive trades of the window, along with the
+    /// time of the second trade.
+    returns: VecDeque<(Timestamp, f64)>,
+    sum_squares: f64,
+
+    /// Times of the market data notifications of the window.
+    messages: VecDeque<Timestamp>,
+
+    /// Time since which the measures have been below the thresholds of the current
+    /// state.
+    calm_since: Option<Timestamp>,
+}
+
+impl CircuitBreaker {
+    /// Return a new breaker in the `BreakerState::Normal` state.
+    pub fn new(config: BreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            state: BreakerState::Normal,
+            venue_halt: None,
+            auction: None,
+            now: 0,
+            last_price: None,
+            returns: VecDeque::new(),
+            sum_squares: 0.,
+            messages: VecDeque::new(),
+            calm_since: None,
+        }
+    }
+
+    /// Return the parameters of the breaker.
+    pub fn config(&self) -> &BreakerConfig {
+        &self.config
+    }
+
+    /// Return the current state, which is `BreakerState::Halted` while the exchange has
+    /// halted trading.
+    pub fn state(&self) -> BreakerState {
+        if self.venue_halt.is_some() {
+            BreakerState::Halted
+        } else {
+            self.state
+        }
+    }
+
+    /// Return the status announced by the exchange, if it has halted trading.
+    pub fn venue_halt(&self) -> Option<&TradingStatus> {
+        self.venue_halt.as_ref()
+    }
+
+    /// Return the latest update of the auction the symbol is in, if any.
+    pub fn auction(&self) -> Option<&AuctionUpdate> {
+        self.auction.as_ref()
+    }
+
+    /// Return the realized volatility over the window, in basis points.
+    pub fn volatility(&self) -> u64 {
+        (self.sum_squares.sqrt() * 10_000.).round() as u64
+    }
+
+    /// Return the number of market data notifications received over the window.
+    pub fn message_rate(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Return `false` if the breaker is halted.
+    pub fn trading_allowed(&self) -> bool {
+        self.state() != BreakerState::Halted
+    }
+
+    /// Return `false` if the breaker is halted, or if `order` does not comply with the
+    /// constraints of the current auction, see `AuctionUpdate::accepts`.
+    pub fn order_allowed(&self, order: &Order) -> bool {
+        self.trading_allowed()
+            && self.auction.as_ref().map(|auction| auction.accepts(order)).unwrap_or(true)
+    }
+
+    /// Return the spread to quote in place of `spread`.
+    pub fn widen_spread(&self, spread: TickUnit) -> TickUnit {
+        match self.state() {
+            BreakerState::Normal => spread,
+            _ => spread.saturating_mul(self.config.spread_factor) / 100,
+        }
+    }
+
+    /// Return the size to quote in place of `size`, which is zero if the breaker is
+    /// halted.
+    pub fn reduce_size(&self, size: TickUnit) -> TickUnit {
+        match self.state() {
+            BreakerState::Normal => size,
+            BreakerState::Cautious => size.saturating_mul(self.config.size_factor) / 100,
+            BreakerState::Halted => 0,
+        }
+    }
+
+    /// Drop the measures which are out of the window ending at `self.now`. Return the
+    /// time at which the last dropped measure left the window, if any.
+    fn expire(&mut self) -> Option<Timestamp> {
+        let (now, window) = (self.now, self.config.window);
+        let expired = |time: Timestamp| time.saturating_add(window) <= now;
+        let mut expired_at = None;
+
+        while let Some(time) = self.messages.front().cloned() {
+            if !expired(time) {
+                break;
+            }
+            self.messages.pop_front();
+            expired_at = std::cmp::max(expired_at, Some(time + window));
+        }
+
+        while let Some((time, square)) = self.returns.front().cloned() {
+            if !expired(time) {
+                break;
+            }
+            self.returns.pop_front();
+            self.sum_squares -= square;
+            expired_at = std::cmp::max(expired_at, Some(time + window));
+        }
+
+        // Avoid accumulating rounding errors.
+        if self.returns.is_empty() {
+            self.sum_squares = 0.;
+        }
+        expired_at
+    }
+
+    /// Return the most severe state whose thresholds are breached.
+    fn measured_state(&self) -> BreakerState {
+        let breached = |thresholds: &Thresholds| {
+            self.volatility() >= thresholds.volatility
+                || self.message_rate() >= thresholds.message_rate
+        };
+
+        if breached(&self.config.halt) {
+            BreakerState::Halted
+        } else if breached(&self.config.caution) {
+            BreakerState::Cautious
+        } else {
+            BreakerState::Normal
+        }
+    }
+
+    /// Update the tripped state from the current measures.
+    fn evaluate(&mut self) {
+        let expired_at = self.expire();
+
+        let measured = self.measured_state();
+        if measured >= self.state {
+            self.calm_since = None;
+        } else {
+            // The measures dropped when they left the window, not when the breaker is
+            // evaluated, which may be long after while the market is quiet.
+            let since = *self.calm_since.get_or_insert(expired_at.unwrap_or(self.now));
+            if self.now.saturating_sub(since) < self.config.cooldown {
+                return;
+            }
+            self.calm_since = None;
+        }
+        self.state = measured;
+    }
+
+    /// Return the current state if it is not `previous`.
+    fn changed(&self, previous: BreakerState) -> Option<BreakerState> {
+        let state = self.state();
+        if state != previous {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    /// Account for a notification of the symbol stream. Return the new state if it
+    /// changed.
+    pub fn on_notification(&mut self, notification: &Notification) -> Option<BreakerState> {
+        let previous = self.state();
+        let time = match notification {
+            Notification::Trade(trade) => {
+                if let Some(last_price) = self.last_price {
+                    if last_price != 0 && trade.price != 0 {
+                        let ret = (trade.price as f64 / last_price as f64).ln();
+                        self.returns.push_back((trade.timestamp(), ret * ret));
+                        self.sum_squares += ret * ret;
+                    }
+                }
+                self.last_price = Some(trade.price);
+                trade.timestamp()
+            }
+
+            Notification::LimitUpdates(updates) => {
+                updates.last().map(|update| update.timestamp()).unwrap_or(self.now)
+            }
+
+            Notification::TradingHalted(status) => {
+                self.venue_halt = Some(TradingStatus::clone(status));
+                return self.changed(previous);
+            }
+
+            Notification::TradingResumed(..) => {
+                self.venue_halt = None;
+                return self.changed(previous);
+            }
+
+            Notification::AuctionUpdate(auction) => {
+                self.auction = if auction.is_over() {
+                    None
+                } else {
+                    Some(AuctionUpdate::clone(auction))
+                };
+                return None;
+            }
+
+            _ => return None,
+        };
+
+        self.messages.push_back(time);
+        self.now = std::cmp::max(self.now, time);
+        self.evaluate();
+        self.changed(previous)
+    }
+
+    /// Evaluate the breaker at the current time `now`, in ms. This should be called
+    /// periodically or before checking the breaker, since the breaker could otherwise
+    /// not step down while the market is quiet. Return the new state if it changed.
+    pub fn on_time(&mut self, now: Timestamp) -> Option<BreakerState> {
+        let previous = self.state();
+        self.now = std::cmp::max(self.now, now);
+        self.evaluate();
+        self.changed(previous)
+    }
+}
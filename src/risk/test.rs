@@ -0,0 +1,173 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{AuctionUpdate, Notification, Order, OrderType, TimeInForce, Trade, TradingStatus};
+use crate::api::timestamp::{Timestamp, IntoTimestamped};
+use crate::order_book::LimitUpdate;
+use crate::risk::{CircuitBreaker, BreakerConfig, BreakerState, Thresholds};
+use crate::tick::TickUnit;
+
+fn config() -> BreakerConfig {
+    BreakerConfig {
+        window: 1000,
+        caution: Thresholds {
+            volatility: 50,
+            message_rate: 5,
+        },
+        halt: Thresholds {
+            volatility: 200,
+            message_rate: 10,
+        },
+        cooldown: 2000,
+        spread_factor: 200,
+        size_factor: 50,
+    }
+}
+
+fn trade(timestamp: Timestamp, price: TickUnit) -> Notification {
+    Notification::Trade(Trade {
+        price,
+        size: 1,
+        maker_side: Side::Bid,
+    }.with_timestamp(timestamp))
+}
+
+fn limits(timestamp: Timestamp) -> Notification {
+    Notification::LimitUpdates(vec![LimitUpdate::new(100, 1, Side::Bid).with_timestamp(timestamp)])
+}
+
+#[test]
+fn message_burst() {
+    let mut breaker = CircuitBreaker::new(config());
+
+    for time in 0..4 {
+        assert_eq!(breaker.on_notification(&limits(time)), None);
+    }
+    assert_eq!(breaker.on_notification(&limits(4)), Some(BreakerState::Cautious));
+    assert_eq!(breaker.message_rate(), 5);
+
+    assert_eq!(breaker.widen_spread(10), 20);
+    assert_eq!(breaker.reduce_size(10), 5);
+    assert!(breaker.trading_allowed());
+
+    for time in 5..9 {
+        assert_eq!(breaker.on_notification(&limits(time)), None);
+    }
+    assert_eq!(breaker.on_notification(&limits(9)), Some(BreakerState::Halted));
+    assert_eq!(breaker.reduce_size(10), 0);
+    assert!(!breaker.trading_allowed());
+}
+
+#[test]
+fn volatility() {
+    let mut breaker = CircuitBreaker::new(config());
+
+    assert_eq!(breaker.on_notification(&trade(0, 10_000)), None);
+    assert_eq!(breaker.on_notification(&trade(1, 10_010)), None);
+    assert_eq!(breaker.volatility(), 10);
+
+    // A 3% drop.
+    assert_eq!(breaker.on_notification(&trade(2, 9_710)), Some(BreakerState::Halted));
+    assert!(breaker.volatility() >= 300);
+}
+
+#[test]
+fn cooldown() {
+    let mut breaker = CircuitBreaker::new(config());
+
+    breaker.on_notification(&trade(0, 10_000));
+    assert_eq!(breaker.on_notification(&trade(10, 9_000)), Some(BreakerState::Halted));
+
+    // The measures drop once the trades leave the window, the cooldown starts then.
+    assert_eq!(breaker.on_time(500), None);
+    assert_eq!(breaker.on_time(1010), None);
+    assert_eq!(breaker.volatility(), 0);
+    assert_eq!(breaker.on_time(3000), None);
+    assert_eq!(breaker.on_time(3010), Some(BreakerState::Normal));
+    assert_eq!(breaker.widen_spread(10), 10);
+    assert_eq!(breaker.reduce_size(10), 10);
+}
+
+#[test]
+fn cooldown_is_restarted_by_new_breaches() {
+    let mut breaker = CircuitBreaker::new(config());
+
+    for time in 0..5 {
+        breaker.on_notification(&limits(time));
+    }
+    assert_eq!(breaker.state(), BreakerState::Cautious);
+
+    assert_eq!(breaker.on_time(1500), None);
+    for time in 2000..2005 {
+        breaker.on_notification(&limits(time));
+    }
+    assert_eq!(breaker.on_time(3600), None);
+    assert_eq!(breaker.state(), BreakerState::Cautious);
+    assert_eq!(breaker.on_time(5003), None);
+    assert_eq!(breaker.on_time(5004), Some(BreakerState::Normal));
+}
+
+#[test]
+fn venue_halt() {
+    let mut breaker = CircuitBreaker::new(config());
+    let status = |status: &str| TradingStatus {
+        status: status.to_owned(),
+        message: None,
+    }.with_timestamp(0);
+
+    for time in 0..5 {
+        breaker.on_notification(&limits(time));
+    }
+    assert_eq!(breaker.state(), BreakerState::Cautious);
+
+    let halted = Notification::TradingHalted(status("BREAK"));
+    assert_eq!(breaker.on_notification(&halted), Some(BreakerState::Halted));
+    assert_eq!(breaker.venue_halt().map(|status| status.status.as_str()), Some("BREAK"));
+    assert!(!breaker.trading_allowed());
+    assert_eq!(breaker.reduce_size(10), 0);
+
+    // The market calms down in the meantime, but the venue halt prevails.
+    assert_eq!(breaker.on_time(1010), None);
+    assert_eq!(breaker.on_time(3010), None);
+    assert!(!breaker.trading_allowed());
+
+    let resumed = Notification::TradingResumed(status("TRADING"));
+    assert_eq!(breaker.on_notification(&resumed), Some(BreakerState::Normal));
+    assert_eq!(breaker.venue_halt(), None);
+    assert!(breaker.trading_allowed());
+}
+
+#[test]
+fn auction() {
+    let mut breaker = CircuitBreaker::new(config());
+    let auction = |phase: Option<&str>| Notification::AuctionUpdate(AuctionUpdate {
+        phase: phase.map(|phase| phase.to_owned()),
+        indicative_price: None,
+        indicative_size: None,
+    }.with_timestamp(0));
+
+    let limit = Order::new(100_u64, 1_u64, Side::Bid);
+    let ioc = limit.clone().with_time_in_force(TimeInForce::ImmediateOrCancel);
+    assert!(breaker.order_allowed(&ioc));
+
+    assert_eq!(breaker.on_notification(&auction(Some("collection"))), None);
+    assert!(breaker.trading_allowed());
+    assert!(breaker.order_allowed(&limit));
+    assert!(!breaker.order_allowed(&ioc));
+    assert!(!breaker.order_allowed(&limit.clone().with_trailing_delta(100)));
+    assert!(!breaker.order_allowed(&Order::market(1_u64, Side::Bid)));
+    assert!(!breaker.order_allowed(
+        &limit.clone().with_order_type(OrderType::StopLimit).with_trigger_price(90_u64)
+    ));
+    assert!(!breaker.order_allowed(
+        &Order::market(1_u64, Side::Bid)
+            .with_order_type(OrderType::StopMarket)
+            .with_trigger_price(90_u64)
+    ));
+    assert!(breaker.order_allowed(&limit.clone().with_order_type(OrderType::LimitMaker)));
+
+    assert_eq!(breaker.on_notification(&auction(None)), None);
+    assert_eq!(breaker.auction(), None);
+    assert!(breaker.order_allowed(&ioc));
+    assert!(breaker.order_allowed(&Order::market(1_u64, Side::Bid)));
+}
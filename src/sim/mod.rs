@@ -0,0 +1,584 @@
+//! A module defining building blocks for simulating an exchange.
+//!
+//! The `MatchingEngine` matches limit orders with a price-time priority, like most
+//! crypto-currencies exchanges do. It is not tied to any venue and does not deal
+//! with time: it can be embedded to model a venue which is not covered by this crate,
+//! or to cross orders internally.
+//!
+//! Fills are charged according to a `FeeSchedule`, the same type describing the fees
+//! of live venues, so that maker rebates are accounted for when backtesting. The
+//! engine tracks the traded volume of each owner for selecting its fee tier.
+
+mod test;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use serde_derive::{Serialize, Deserialize};
+use crate::Side;
+use crate::api::{OrderType, TimeInForce};
+use crate::api::fees::{FeeSchedule, Liquidity};
+use crate::order_book::{OrderBook, LimitUpdate};
+use crate::tick::TickUnit;
+
+/// Identify the owner of an order, e.g. an account. Used for self-trade prevention.
+pub type OwnerId = u64;
+
+/// Compute the fees of a trade between `maker` and `taker` with the tiers reached
+/// before the trade, then account for its volume.
+fn charge_fees(
+    fee_schedule: &FeeSchedule,
+    volumes: &mut HashMap<OwnerId, TickUnit>,
+    maker: OwnerId,
+    taker: OwnerId,
+    notional: TickUnit
+) -> (i64, i64)
+{
+    let volume = |owner| volumes.get(&owner).cloned().unwrap_or(0);
+    let maker_fee = fee_schedule.fee(volume(maker), Liquidity::Maker, notional);
+    let taker_fee = fee_schedule.fee(volume(taker), Liquidity::Taker, notional);
+
+    for owner in &[maker, taker] {
+        let volume = volumes.entry(*owner).or_insert(0);
+        *volume = volume.saturating_add(notional);
+    }
+    (maker_fee, taker_fee)
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A limit order submitted to the matching engine. Prices and sizes are expressed
+/// in tick units.
+pub struct LimitOrder {
+    /// Unique order id.
+    pub order_id: String,
+
+    /// Owner of the order.
+    pub owner: OwnerId,
+
+    /// Side of the order.
+    pub side: Side,
+
+    /// Limit price.
+    pub price: TickUnit,
+
+    /// Size of the order. For resting orders returned by the engine, this is the
+    /// remaining size.
+    pub size: TickUnit,
+
+    /// Order type, `OrderType::LimitMaker` orders are rejected if they would cross. Other
+    /// order types than `OrderType::Limit` and `OrderType::LimitMaker` are rejected.
+    pub order_type: OrderType,
+
+    /// Time in force.
+    pub time_in_force: TimeInForce,
+}
+
+impl LimitOrder {
+    /// Return a new good-til-canceled `OrderType::Limit` order.
+    pub fn new(order_id: String, owner: OwnerId, side: Side, price: TickUnit, size: TickUnit)
+        -> Self
+    {
+        LimitOrder {
+            order_id,
+            owner,
+            side,
+            price,
+            size,
+            order_type: OrderType::Limit,
+            time_in_force: TimeInForce::GoodTilCanceled,
+        }
+    }
+
+    /// Set the order type.
+    pub fn with_order_type(mut self, order_type: OrderType) -> Self {
+        self.order_type = order_type;
+        self
+    }
+
+    /// Set the time in force.
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    /// Whether this order would trade against a resting order at `price`.
+    fn crosses(&self, price: TickUnit) -> bool {
+        match self.side {
+            Side::Bid => self.price >= price,
+            Side::Ask => self.price <= price,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// What to do when an incoming order would trade against a resting order of the
+/// same owner.
+pub enum SelfTradePrevention {
+    /// Let the orders trade together.
+    Allow,
+
+    /// Expire the remaining size of the incoming order.
+    ExpireTaker,
+
+    /// Expire the resting order, and continue matching the incoming order.
+    ExpireMaker,
+
+    /// Expire both orders.
+    ExpireBoth,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Reason why an order was rejected.
+pub enum RejectReason {
+    /// An order with the same id is already resting in the book.
+    DuplicateOrder,
+
+    /// The order size is zero.
+    InvalidSize,
+
+    /// The order was marked as liquidity maker only but would have crossed.
+    WouldTakeLiquidity,
+
+    /// The fill-or-kill order could not be filled entirely.
+    CannotFill,
+
+    /// The order is neither an `OrderType::Limit` nor an `OrderType::LimitMaker` order.
+    UnsupportedOrderType,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// Reason why an order left the book without being entirely filled.
+pub enum ExpireReason {
+    /// The order was canceled.
+    Canceled,
+
+    /// The remaining size of an immediate-or-cancel order could not be filled.
+    Unfilled,
+
+    /// The order was expired by self-trade prevention.
+    SelfTrade,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// A trade between an incoming order and a resting order.
+pub struct Fill {
+    /// Price of the resting order.
+    pub price: TickUnit,
+
+    /// Traded size.
+    pub size: TickUnit,
+
+    /// Side of the resting order.
+    pub maker_side: Side,
+
+    /// Id of the resting order.
+    pub maker_order_id: String,
+
+    /// Owner of the resting order.
+    pub maker_owner: OwnerId,
+
+    /// Remaining size of the resting order after this trade.
+    pub maker_remaining: TickUnit,
+
+    /// Id of the incoming order.
+    pub taker_order_id: String,
+
+    /// Owner of the incoming order.
+    pub taker_owner: OwnerId,
+
+    /// Remaining size of the incoming order after this trade.
+    pub taker_remaining: TickUnit,
+
+    /// Fee charged to the owner of the resting order, in units of the notional (see
+    /// `api::fees`). Negative for a rebate.
+    pub maker_fee: i64,
+
+    /// Fee charged to the owner of the incoming order, in units of the notional.
+    /// Negative for a rebate.
+    pub taker_fee: i64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+/// An event generated by the matching engine.
+pub enum Event {
+    /// The order was accepted. It is always followed by the events resulting from
+    /// its matching.
+    Accepted {
+        /// Order id.
+        order_id: String,
+
+        /// Owner of the order.
+        owner: OwnerId,
+    },
+
+    /// The order was rejected and did not affect the book.
+    Rejected {
+        /// Order id.
+        order_id: String,
+
+        /// Owner of the order.
+        owner: OwnerId,
+
+        /// Why the order was rejected.
+        reason: RejectReason,
+    },
+
+    /// Two orders traded together.
+    Fill(Fill),
+
+    /// An order left the book before being entirely filled.
+    Expired {
+        /// Order id.
+        order_id: String,
+
+        /// Owner of the order.
+        owner: OwnerId,
+
+        /// Size which was left unfilled.
+        remaining: TickUnit,
+
+        /// Why the order expired.
+        reason: ExpireReason,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// A matching engine with a price-time priority.
+pub struct MatchingEngine {
+    ask: BTreeMap<TickUnit, VecDeque<LimitOrder>>,
+    bid: BTreeMap<TickUnit, VecDeque<LimitOrder>>,
+
+    /// order id => (side, price) of the resting order
+    index: HashMap<String, (Side, TickUnit)>,
+
+    /// Aggregated view of the resting orders.
+    order_book: OrderBook,
+
+    self_trade_prevention: SelfTradePrevention,
+
+    fee_schedule: FeeSchedule,
+
+    /// owner => traded volume, in units of the notional
+    volumes: HashMap<OwnerId, TickUnit>,
+}
+
+impl Default for MatchingEngine {
+    fn default() -> Self {
+        MatchingEngine::new()
+    }
+}
+
+impl MatchingEngine {
+    /// Return a new matching engine with an empty book, which lets orders of the same
+    /// owner trade together and charges no fees.
+    pub fn new() -> Self {
+        MatchingEngine {
+            ask: BTreeMap::new(),
+            bid: BTreeMap::new(),
+            index: HashMap::new(),
+            order_book: OrderBook::new(),
+            self_trade_prevention: SelfTradePrevention::Allow,
+            fee_schedule: FeeSchedule::zero(),
+            volumes: HashMap::new(),
+        }
+    }
+
+    /// Set the self-trade prevention mode.
+    pub fn with_self_trade_prevention(mut self, mode: SelfTradePrevention) -> Self {
+        self.self_trade_prevention = mode;
+        self
+    }
+
+    /// Return the self-trade prevention mode.
+    pub fn self_trade_prevention(&self) -> SelfTradePrevention {
+        self.self_trade_prevention
+    }
+
+    /// Set the fee schedule applied to the fills.
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = fee_schedule;
+        self
+    }
+
+    /// Return the fee schedule applied to the fills.
+    pub fn fee_schedule(&self) -> &FeeSchedule {
+        &self.fee_schedule
+    }
+
+    /// Set the volume traded by `owner` so far, e.g. over the trailing period
+    /// considered by the venue, which selects its fee tier. The volume of the fills
+    /// is then added to it.
+    pub fn set_volume(&mut self, owner: OwnerId, volume: TickUnit) {
+        self.volumes.insert(owner, volume);
+    }
+
+    /// Return the volume traded by `owner`, in units of the notional.
+    pub fn volume(&self, owner: OwnerId) -> TickUnit {
+        self.volumes.get(&owner).cloned().unwrap_or(0)
+    }
+
+    /// Aggregated view of the resting orders.
+    pub fn order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    /// Return the resting order with the given id, if any. The returned order size is
+    /// its remaining size.
+    pub fn order(&self, order_id: &str) -> Option<&LimitOrder> {
+        let (side, price) = self.index.get(order_id)?;
+        self.levels(*side)
+            .get(price)?
+            .iter()
+            .find(|order| order.order_id == order_id)
+    }
+
+    /// Iterator over the resting orders at the given side and price, in priority order.
+    pub fn orders_at_limit(&self, side: Side, price: TickUnit)
+        -> impl Iterator<Item = &LimitOrder>
+    {
+        self.levels(side).get(&price).into_iter().flat_map(|queue| queue.iter())
+    }
+
+    fn levels(&self, side: Side) -> &BTreeMap<TickUnit, VecDeque<LimitOrder>> {
+        match side {
+            Side::Bid => &self.bid,
+            Side::Ask => &self.ask,
+        }
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<TickUnit, VecDeque<LimitOrder>> {
+        match side {
+            Side::Bid => &mut self.bid,
+            Side::Ask => &mut self.ask,
+        }
+    }
+
+    /// Best price at the given side, if the side is not empty.
+    fn best_price(&self, side: Side) -> Option<TickUnit> {
+        match side {
+            Side::Bid => self.bid.keys().next_back().cloned(),
+            Side::Ask => self.ask.keys().next().cloned(),
+        }
+    }
+
+    fn update_order_book(&mut self, side: Side, price: TickUnit) {
+        let size = self.levels(side)
+            .get(&price)
+            .map(|queue| queue.iter().map(|order| order.size).sum())
+            .unwrap_or(0);
+        self.order_book.update(LimitUpdate::new(price, size, side));
+    }
+
+    /// Size which `order` could trade against, taking self-trade prevention into
+    /// account.
+    fn available_size(&self, order: &LimitOrder) -> TickUnit {
+        let opposite = match order.side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let levels: Box<dyn Iterator<Item = (&TickUnit, &VecDeque<LimitOrder>)>> = match opposite {
+            Side::Ask => Box::new(self.ask.iter()),
+            Side::Bid => Box::new(self.bid.iter().rev()),
+        };
+
+        let mut available = 0;
+        for (price, queue) in levels {
+            if !order.crosses(*price) {
+                break;
+            }
+
+            for resting in queue {
+                if resting.owner == order.owner {
+                    match self.self_trade_prevention {
+                        SelfTradePrevention::Allow => (),
+                        SelfTradePrevention::ExpireMaker => continue,
+                        SelfTradePrevention::ExpireTaker |
+                        SelfTradePrevention::ExpireBoth => return available,
+                    }
+                }
+                available += resting.size;
+                if available >= order.size {
+                    return available;
+                }
+            }
+        }
+        available
+    }
+
+    /// Submit a new order, and return the resulting events in the order they
+    /// happened.
+    pub fn submit(&mut self, mut order: LimitOrder) -> Vec<Event> {
+        let reject = |order: LimitOrder, reason| {
+            vec![Event::Rejected {
+                order_id: order.order_id,
+                owner: order.owner,
+                reason,
+            }]
+        };
+
+        match order.order_type {
+            OrderType::Limit | OrderType::LimitMaker => (),
+            OrderType::Market | OrderType::StopLimit | OrderType::StopMarket => {
+                return reject(order, RejectReason::UnsupportedOrderType);
+            }
+        }
+
+        if order.size == 0 {
+            return reject(order, RejectReason::InvalidSize);
+        }
+
+        if self.index.contains_key(&order.order_id) {
+            return reject(order, RejectReason::DuplicateOrder);
+        }
+
+        let opposite = match order.side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+
+        let crosses = self.best_price(opposite).map(|price| order.crosses(price)).unwrap_or(false);
+        if order.order_type == OrderType::LimitMaker && crosses {
+            return reject(order, RejectReason::WouldTakeLiquidity);
+        }
+
+        if order.time_in_force == TimeInForce::FillOrKilll && self.available_size(&order) < order.size {
+            return reject(order, RejectReason::CannotFill);
+        }
+
+        let mut events = vec![Event::Accepted {
+            order_id: order.order_id.clone(),
+            owner: order.owner,
+        }];
+
+        let mut taker_expired = false;
+        while order.size > 0 && !taker_expired {
+            let price = match self.best_price(opposite) {
+                Some(price) if order.crosses(price) => price,
+                _ => break,
+            };
+
+            let stp = self.self_trade_prevention;
+            let levels = match opposite {
+                Side::Bid => &mut self.bid,
+                Side::Ask => &mut self.ask,
+            };
+            let queue = levels.get_mut(&price).unwrap();
+            let mut maker = queue.pop_front().unwrap();
+
+            if stp != SelfTradePrevention::Allow && maker.owner == order.owner {
+                let expire_maker = stp == SelfTradePrevention::ExpireMaker ||
+                    stp == SelfTradePrevention::ExpireBoth;
+                taker_expired = stp == SelfTradePrevention::ExpireTaker ||
+                    stp == SelfTradePrevention::ExpireBoth;
+
+                if expire_maker {
+                    events.push(Event::Expired {
+                        order_id: maker.order_id.clone(),
+                        owner: maker.owner,
+                        remaining: maker.size,
+                        reason: ExpireReason::SelfTrade,
+                    });
+                    self.index.remove(&maker.order_id);
+                } else {
+                    queue.push_front(maker);
+                }
+            } else {
+                let size = std::cmp::min(maker.size, order.size);
+                maker.size -= size;
+                order.size -= size;
+
+                let (maker_fee, taker_fee) = charge_fees(
+                    &self.fee_schedule,
+                    &mut self.volumes,
+                    maker.owner,
+                    order.owner,
+                    price.saturating_mul(size)
+                );
+
+                events.push(Event::Fill(Fill {
+                    price,
+                    size,
+                    maker_side: opposite,
+                    maker_order_id: maker.order_id.clone(),
+                    maker_owner: maker.owner,
+                    maker_remaining: maker.size,
+                    taker_order_id: order.order_id.clone(),
+                    taker_owner: order.owner,
+                    taker_remaining: order.size,
+                    maker_fee,
+                    taker_fee,
+                }));
+
+                if maker.size > 0 {
+                    queue.push_front(maker);
+                } else {
+                    self.index.remove(&maker.order_id);
+                }
+            }
+
+            if queue.is_empty() {
+                levels.remove(&price);
+            }
+            self.update_order_book(opposite, price);
+        }
+
+        if order.size == 0 {
+            return events;
+        }
+
+        if taker_expired {
+            events.push(Event::Expired {
+                order_id: order.order_id,
+                owner: order.owner,
+                remaining: order.size,
+                reason: ExpireReason::SelfTrade,
+            });
+            return events;
+        }
+
+        match order.time_in_force {
+            // The engine does not deal with time, good til date orders are canceled by
+            // the venue embedding it.
+            TimeInForce::GoodTilCanceled | TimeInForce::GoodTilDate(_) => {
+                let (side, price) = (order.side, order.price);
+                self.index.insert(order.order_id.clone(), (side, price));
+                self.levels_mut(side).entry(price).or_default().push_back(order);
+                self.update_order_book(side, price);
+            }
+
+            // Fill-or-kill orders were checked to be fillable beforehand, but can
+            // still be left with a remaining size after self-trade prevention.
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKilll => {
+                events.push(Event::Expired {
+                    order_id: order.order_id,
+                    owner: order.owner,
+                    remaining: order.size,
+                    reason: ExpireReason::Unfilled,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Cancel the resting order with the given id. Return the corresponding
+    /// `Event::Expired`, or `None` if the order could not be found.
+    pub fn cancel(&mut self, order_id: &str) -> Option<Event> {
+        let (side, price) = self.index.remove(order_id)?;
+
+        let levels = self.levels_mut(side);
+        let queue = levels.get_mut(&price)?;
+        let position = queue.iter().position(|order| order.order_id == order_id)?;
+        let order = queue.remove(position)?;
+        if queue.is_empty() {
+            levels.remove(&price);
+        }
+        self.update_order_book(side, price);
+
+        Some(Event::Expired {
+            order_id: order.order_id,
+            owner: order.owner,
+            remaining: order.size,
+            reason: ExpireReason::Canceled,
+        })
+    }
+}
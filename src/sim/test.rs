@@ -0,0 +1,247 @@
+#![cfg(test)]
+
+use crate::Side;
+use crate::api::{OrderType, TimeInForce};
+use crate::api::fees::{FeeSchedule, FeeTier, FeeRates};
+use crate::sim::*;
+use crate::tick::TickUnit;
+
+fn order(order_id: &str, owner: OwnerId, side: Side, price: TickUnit, size: TickUnit)
+    -> LimitOrder
+{
+    LimitOrder::new(order_id.to_owned(), owner, side, price, size)
+}
+
+fn accepted(order_id: &str, owner: OwnerId) -> Event {
+    Event::Accepted {
+        order_id: order_id.to_owned(),
+        owner,
+    }
+}
+
+fn expired(order_id: &str, owner: OwnerId, remaining: TickUnit, reason: ExpireReason) -> Event {
+    Event::Expired {
+        order_id: order_id.to_owned(),
+        owner,
+        remaining,
+        reason,
+    }
+}
+
+fn fill(
+    price: TickUnit,
+    size: TickUnit,
+    maker: (&str, OwnerId, TickUnit),
+    taker: (&str, OwnerId, TickUnit),
+    maker_side: Side
+) -> Event
+{
+    Event::Fill(Fill {
+        price,
+        size,
+        maker_side,
+        maker_order_id: maker.0.to_owned(),
+        maker_owner: maker.1,
+        maker_remaining: maker.2,
+        taker_order_id: taker.0.to_owned(),
+        taker_owner: taker.1,
+        taker_remaining: taker.2,
+        maker_fee: 0,
+        taker_fee: 0,
+    })
+}
+
+#[test]
+fn price_time_priority() {
+    let mut engine = MatchingEngine::new();
+    engine.submit(order("a1", 1, Side::Ask, 101, 5));
+    engine.submit(order("a2", 2, Side::Ask, 100, 3));
+    engine.submit(order("a3", 3, Side::Ask, 100, 4));
+
+    assert_eq!(engine.order_book().best_ask(), 100);
+    assert_eq!(engine.order_book().size_at_limit(Side::Ask, 100), 7);
+
+    let events = engine.submit(order("b1", 4, Side::Bid, 101, 9));
+    assert_eq!(
+        events,
+        vec![
+            accepted("b1", 4),
+            fill(100, 3, ("a2", 2, 0), ("b1", 4, 6), Side::Ask),
+            fill(100, 4, ("a3", 3, 0), ("b1", 4, 2), Side::Ask),
+            fill(101, 2, ("a1", 1, 3), ("b1", 4, 0), Side::Ask),
+        ]
+    );
+
+    assert_eq!(engine.order_book().best_ask(), 101);
+    assert_eq!(engine.order("a1").map(|o| o.size), Some(3));
+    assert!(engine.order("a2").is_none());
+}
+
+#[test]
+fn rest_and_cancel() {
+    let mut engine = MatchingEngine::new();
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+
+    let events = engine.submit(order("b1", 2, Side::Bid, 100, 5));
+    assert_eq!(
+        events,
+        vec![
+            accepted("b1", 2),
+            fill(100, 2, ("a1", 1, 0), ("b1", 2, 3), Side::Ask),
+        ]
+    );
+    assert_eq!(engine.order_book().best_bid(), 100);
+    assert_eq!(engine.order_book().size_at_limit(Side::Bid, 100), 3);
+
+    assert_eq!(engine.cancel("b1"), Some(expired("b1", 2, 3, ExpireReason::Canceled)));
+    assert_eq!(engine.cancel("b1"), None);
+    assert_eq!(engine.order_book().best_bid(), 0);
+}
+
+#[test]
+fn time_in_force() {
+    let mut engine = MatchingEngine::new();
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+
+    let ioc = order("b1", 2, Side::Bid, 100, 5)
+        .with_time_in_force(TimeInForce::ImmediateOrCancel);
+    assert_eq!(
+        engine.submit(ioc),
+        vec![
+            accepted("b1", 2),
+            fill(100, 2, ("a1", 1, 0), ("b1", 2, 3), Side::Ask),
+            expired("b1", 2, 3, ExpireReason::Unfilled),
+        ]
+    );
+    assert_eq!(engine.order_book().best_bid(), 0);
+
+    engine.submit(order("a2", 1, Side::Ask, 100, 2));
+    let fok = order("b2", 2, Side::Bid, 100, 5)
+        .with_time_in_force(TimeInForce::FillOrKilll);
+    assert_eq!(
+        engine.submit(fok),
+        vec![Event::Rejected {
+            order_id: "b2".to_owned(),
+            owner: 2,
+            reason: RejectReason::CannotFill,
+        }]
+    );
+    assert_eq!(engine.order_book().size_at_limit(Side::Ask, 100), 2);
+}
+
+#[test]
+fn post_only() {
+    let mut engine = MatchingEngine::new();
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+
+    let maker = order("b1", 2, Side::Bid, 100, 1).with_order_type(OrderType::LimitMaker);
+    assert_eq!(
+        engine.submit(maker),
+        vec![Event::Rejected {
+            order_id: "b1".to_owned(),
+            owner: 2,
+            reason: RejectReason::WouldTakeLiquidity,
+        }]
+    );
+
+    let maker = order("b2", 2, Side::Bid, 99, 1).with_order_type(OrderType::LimitMaker);
+    assert_eq!(engine.submit(maker), vec![accepted("b2", 2)]);
+}
+
+#[test]
+fn unsupported_order_types() {
+    let mut engine = MatchingEngine::new();
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+
+    for (i, &order_type) in [OrderType::Market, OrderType::StopLimit, OrderType::StopMarket]
+        .iter()
+        .enumerate()
+    {
+        let order_id = format!("b{}", i);
+        assert_eq!(
+            engine.submit(order(&order_id, 2, Side::Bid, 0, 1).with_order_type(order_type)),
+            vec![Event::Rejected {
+                order_id,
+                owner: 2,
+                reason: RejectReason::UnsupportedOrderType,
+            }]
+        );
+    }
+    assert!(engine.order("b0").is_none());
+}
+
+#[test]
+fn self_trade_prevention() {
+    let mut engine = MatchingEngine::new()
+        .with_self_trade_prevention(SelfTradePrevention::ExpireTaker);
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+    engine.submit(order("a2", 2, Side::Ask, 100, 2));
+
+    assert_eq!(
+        engine.submit(order("b1", 2, Side::Bid, 100, 3)),
+        vec![
+            accepted("b1", 2),
+            fill(100, 2, ("a1", 1, 0), ("b1", 2, 1), Side::Ask),
+            expired("b1", 2, 1, ExpireReason::SelfTrade),
+        ]
+    );
+    assert_eq!(engine.order_book().size_at_limit(Side::Ask, 100), 2);
+
+    let mut engine = MatchingEngine::new()
+        .with_self_trade_prevention(SelfTradePrevention::ExpireMaker);
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+    engine.submit(order("a2", 2, Side::Ask, 101, 2));
+
+    assert_eq!(
+        engine.submit(order("b1", 1, Side::Bid, 101, 2)),
+        vec![
+            accepted("b1", 1),
+            expired("a1", 1, 2, ExpireReason::SelfTrade),
+            fill(101, 2, ("a2", 2, 0), ("b1", 1, 0), Side::Ask),
+        ]
+    );
+    assert_eq!(engine.order_book().best_ask(), TickUnit::max_value());
+}
+
+#[test]
+fn duplicate_order() {
+    let mut engine = MatchingEngine::new();
+    engine.submit(order("a1", 1, Side::Ask, 100, 2));
+    assert_eq!(
+        engine.submit(order("a1", 1, Side::Ask, 101, 2)),
+        vec![Event::Rejected {
+            order_id: "a1".to_owned(),
+            owner: 1,
+            reason: RejectReason::DuplicateOrder,
+        }]
+    );
+}
+
+#[test]
+fn fees_and_rebates() {
+    let schedule = FeeSchedule::new(vec![
+        FeeTier::new(0, FeeRates::new(1000, 2000)),
+        FeeTier::new(10000, FeeRates::new(-100, 1000)),
+    ]);
+    let mut engine = MatchingEngine::new().with_fee_schedule(schedule);
+    engine.set_volume(1, 10000);
+
+    engine.submit(order("a1", 1, Side::Ask, 1000, 100));
+
+    // Notional of 40000: the maker is in the rebate tier, the taker in the lowest one.
+    let events = engine.submit(order("b1", 2, Side::Bid, 1000, 40));
+    match &events[1] {
+        Event::Fill(fill) => assert_eq!((fill.maker_fee, fill.taker_fee), (-4, 80)),
+        other => panic!("expected a fill, got `{:?}`", other),
+    }
+
+    // The taker has now reached the second tier.
+    let events = engine.submit(order("b2", 2, Side::Bid, 1000, 60));
+    match &events[1] {
+        Event::Fill(fill) => assert_eq!((fill.maker_fee, fill.taker_fee), (-6, 60)),
+        other => panic!("expected a fill, got `{:?}`", other),
+    }
+
+    assert_eq!(engine.volume(1), 110000);
+    assert_eq!(engine.volume(2), 100000);
+}
@@ -25,9 +25,11 @@
 
 mod test;
 
-use std::fmt;
-use std::convert::TryInto;
-use std::borrow::Cow;
+use core::fmt;
+use core::convert::TryInto;
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::String;
+use alloc::vec::Vec;
 use failure_derive::Fail;
 use serde_derive::{Serialize, Deserialize};
 
@@ -147,6 +149,13 @@ impl Tick {
         self.0
     }
 
+    /// Round a fractional value, e.g. an average price computed from cumulated amounts,
+    /// to the nearest number of ticks.
+    #[cfg(feature = "std")]
+    pub fn rounded(self, value: f64) -> TickUnit {
+        (value * self.0 as f64).round() as TickUnit
+    }
+
     /// Convert an unticked value, e.g. `"0.001"` into a value expressed in ticks,
     /// e.g. if `self.ticks_per_unit == 1000` then this would return `Ok(1)`.
     ///
@@ -256,6 +265,23 @@ impl Tick {
     }
 
     // Not optimized, don't care.
+    #[cfg_attr(
+        not(any(
+            feature = "binance",
+            feature = "bitmex",
+            feature = "bybit",
+            feature = "coinbase",
+            feature = "deribit",
+            feature = "dydx",
+            feature = "gdax",
+            feature = "gemini",
+            feature = "hitbtc",
+            feature = "kraken_futures",
+            feature = "kucoin",
+            feature = "okx",
+        )),
+        allow(dead_code)
+    )]
     crate fn tick_size(unticked: &str) -> Option<Tick> {
         if unticked.starts_with('1') || unticked.starts_with("1.") {
             return Some(Tick::new(1));
@@ -207,3 +207,12 @@ fn tick_size() {
         Tick::tick_size("0.0025")
     );
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn round_fractional() {
+    assert_eq!(3, Tick::new(100).rounded(0.025 + 0.005));
+    assert_eq!(125, Tick::new(100).rounded(1.2549));
+    assert_eq!(126, Tick::new(100).rounded(1.255_001));
+    assert_eq!(0, Tick::new(1000).rounded(0.000_4));
+}